@@ -0,0 +1,43 @@
+//! Parsing of the hot websocket messages with serde_json and simd-json
+//!
+//! `cargo bench --features simd --bench ws_parse_benchmark`
+
+use botnode::market_data::{binance, ftx, json::JsonBuffer};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const FTX_ORDERBOOK: &str = r#"{"channel": "orderbook", "market": "BTC-PERP", "type": "update", "data": {"time": 1654041600.3111522, "checksum": 2194545560, "bids": [[31512.0, 0.4551], [31511.0, 1.2003], [31509.0, 0.0], [31508.0, 3.1412], [31505.0, 0.7286], [31503.0, 12.5], [31500.0, 0.0001], [31498.0, 2.0], [31497.0, 0.3], [31495.0, 4.4865]], "asks": [[31513.0, 0.0978], [31514.0, 0.0], [31516.0, 2.6198], [31517.0, 0.8], [31519.0, 1.0], [31520.0, 7.2013], [31522.0, 0.01], [31525.0, 0.0], [31526.0, 3.5], [31530.0, 10.0]], "action": "update"}}"#;
+
+const FTX_TRADES: &str = r#"{"channel": "trades", "market": "BTC-PERP", "type": "update", "data": [{"id": 4056366412, "price": 31513.0, "size": 0.0978, "side": "buy", "liquidation": false, "time": "2022-06-01T00:00:00.311152+00:00"}, {"id": 4056366413, "price": 31516.0, "size": 0.1022, "side": "buy", "liquidation": false, "time": "2022-06-01T00:00:00.311152+00:00"}, {"id": 4056366414, "price": 31512.0, "size": 1.5, "side": "sell", "liquidation": true, "time": "2022-06-01T00:00:00.318452+00:00"}]}"#;
+
+const BINANCE_DEPTH: &str = r#"{"e": "depthUpdate", "E": 1654041600311, "s": "BTCUSDT", "U": 21787291571, "u": 21787291604, "b": [["31512.01000000", "0.45510000"], ["31511.52000000", "1.20030000"], ["31509.00000000", "0.00000000"], ["31508.44000000", "3.14120000"], ["31505.10000000", "0.72860000"], ["31503.00000000", "12.50000000"], ["31500.00000000", "0.00010000"], ["31498.75000000", "2.00000000"], ["31497.30000000", "0.30000000"], ["31495.00000000", "4.48650000"]], "a": [["31513.02000000", "0.09780000"], ["31514.00000000", "0.00000000"], ["31516.41000000", "2.61980000"], ["31517.00000000", "0.80000000"], ["31519.99000000", "1.00000000"], ["31520.00000000", "7.20130000"], ["31522.13000000", "0.01000000"], ["31525.00000000", "0.00000000"], ["31526.50000000", "3.50000000"], ["31530.00000000", "10.00000000"]]}"#;
+
+const BINANCE_TRADE: &str = r#"{"e": "trade", "E": 1654041600311, "s": "BTCUSDT", "t": 1219924203, "p": "31513.02000000", "q": "0.09780000", "b": 8964867731, "a": 8964867628, "T": 1654041600310, "m": false, "M": true}"#;
+
+/// Benchmarks both parsers on the message in a group named after it
+macro_rules! bench_parsers {
+    ($c:expr, $name:expr, $ty:ty, $msg:expr) => {{
+        let mut group = $c.benchmark_group($name);
+        group.bench_function("serde_json", |b| {
+            b.iter(|| {
+                black_box(serde_json::from_str::<$ty>(black_box($msg)).unwrap());
+            })
+        });
+        let mut json = JsonBuffer::default();
+        group.bench_function("simd_json", |b| {
+            b.iter(|| {
+                black_box(json.parse::<$ty>(black_box($msg)).unwrap());
+            })
+        });
+        group.finish();
+    }};
+}
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    bench_parsers!(c, "ftx_orderbook", ftx::ws::WsMsg, FTX_ORDERBOOK);
+    bench_parsers!(c, "ftx_trades", ftx::ws::WsMsg, FTX_TRADES);
+    bench_parsers!(c, "binance_depth", binance::ws::WsMsg, BINANCE_DEPTH);
+    bench_parsers!(c, "binance_trade", binance::ws::WsMsg, BINANCE_TRADE);
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,51 @@
+//! Paper-trading bot with a strategy defined in code
+//!
+//! Needs a running botvana-server:
+//!
+//! ```sh
+//! cargo r --example paper_bot
+//! ```
+
+use botnode::{config::BotnodeConfig, prelude::*, trading::strategy::*, Botnode};
+
+/// Bids one tick below the best bid once per market
+#[derive(Default)]
+struct JoinBid {
+    placed: Vec<Box<str>>,
+}
+
+impl Strategy for JoinBid {
+    fn name(&self) -> &str {
+        "join-bid"
+    }
+
+    fn on_orderbook(&mut self, market: &str, orderbook: &PlainOrderbook<f64>) -> Vec<OrderIntent> {
+        match orderbook.bids.price_vec.last() {
+            Some(best_bid) if !self.placed.iter().any(|m| m.as_ref() == market) => {
+                self.placed.push(Box::from(market));
+                vec![OrderIntent::limit(market, Side::Buy, best_bid - 0.5, 0.001)]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn main() {
+    let config = BotnodeConfig::from_toml(
+        r#"
+        bot_id = 0
+        server_addr = "127.0.0.1:7978"
+        exchanges = ["ftx"]
+        markets = ["BTC-PERP"]
+        "#,
+    )
+    .expect("invalid configuration");
+
+    Botnode::builder()
+        .config(config)
+        .strategy(JoinBid::default)
+        .paper_trading()
+        .build()
+        .and_then(Botnode::run)
+        .expect("botnode failed");
+}
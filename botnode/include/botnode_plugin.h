@@ -0,0 +1,66 @@
+/*
+ * botnode strategy plugin ABI
+ *
+ * A plugin is a shared library exporting `botnode_strategy_plugin`. botnode
+ * calls all callbacks from the trading engine thread, one at a time. Intents
+ * are written into the `out` buffer, at most `cap` of them, and the number of
+ * written intents is returned. Markets of the intents are copied into their
+ * NUL-terminated `market` array, intents with longer market names are
+ * skipped.
+ */
+
+#ifndef BOTNODE_PLUGIN_H
+#define BOTNODE_PLUGIN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#define BOTNODE_PLUGIN_ABI_VERSION 1
+
+#define BOTNODE_SIDE_BUY 0
+#define BOTNODE_SIDE_SELL 1
+
+/* Size of the market array of intents, including the terminating NUL */
+#define BOTNODE_MARKET_LEN 64
+
+/* Orderbook levels, both sides sorted by price ascending */
+typedef struct {
+    const double *bid_prices;
+    const double *bid_sizes;
+    size_t bid_len;
+    const double *ask_prices;
+    const double *ask_sizes;
+    size_t ask_len;
+} BotnodeOrderbook;
+
+typedef struct {
+    double price;
+    double size;
+    /* Exchange time in nanoseconds since UNIX epoch */
+    int64_t time_ns;
+} BotnodeTrade;
+
+typedef struct {
+    char market[BOTNODE_MARKET_LEN];
+    uint8_t side;
+    /* NaN for market orders */
+    double price;
+    double size;
+} BotnodeOrderIntent;
+
+typedef struct {
+    uint32_t abi_version;
+    /* Creates strategy state from JSON parameters, NULL on failure */
+    void *(*create)(const char *params_json);
+    void (*destroy)(void *state);
+    /* Optional callbacks, NULL when not implemented */
+    size_t (*on_orderbook)(void *state, const char *market, const BotnodeOrderbook *orderbook,
+                           BotnodeOrderIntent *out, size_t cap);
+    size_t (*on_trade)(void *state, const char *market, const BotnodeTrade *trade,
+                       BotnodeOrderIntent *out, size_t cap);
+    size_t (*on_timer)(void *state, int64_t now_ns, BotnodeOrderIntent *out, size_t cap);
+} BotnodeStrategyPlugin;
+
+const BotnodeStrategyPlugin *botnode_strategy_plugin(void);
+
+#endif
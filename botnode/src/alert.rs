@@ -0,0 +1,113 @@
+//! Alerts raised by engines
+//!
+//! Engines raise alerts from their own threads into a bounded process-wide
+//! log. Readers keep their own cursor into the log: the control engine
+//! forwards new alerts to botvana-server and the alert engine delivers them
+//! to the notification sinks.
+
+pub mod engine;
+pub mod rules;
+pub mod sink;
+
+use std::{collections::VecDeque, sync::Mutex};
+
+use once_cell::sync::Lazy;
+
+use botvana::net::msg::{Alert, AlertKind};
+
+/// Maximum number of alerts kept in the log
+const ALERT_LOG_CAP: usize = 256;
+
+static ALERT_LOG: Lazy<Mutex<AlertLog>> = Lazy::new(|| Mutex::new(AlertLog::default()));
+
+/// Bounded log of raised alerts
+#[derive(Debug, Default)]
+struct AlertLog {
+    /// Sequence number of the next raised alert
+    next_seq: u64,
+    alerts: VecDeque<(u64, Alert)>,
+}
+
+impl AlertLog {
+    fn push(&mut self, alert: Alert) {
+        if self.alerts.len() >= ALERT_LOG_CAP {
+            self.alerts.pop_front();
+        }
+        self.alerts.push_back((self.next_seq, alert));
+        self.next_seq += 1;
+    }
+
+    fn read_since(&self, cursor: &mut u64) -> Vec<Alert> {
+        let alerts = self
+            .alerts
+            .iter()
+            .filter(|(seq, _)| *seq >= *cursor)
+            .map(|(_, alert)| alert.clone())
+            .collect();
+        *cursor = self.next_seq;
+
+        alerts
+    }
+}
+
+/// Raises new alert
+///
+/// The alert is logged and appended to the alert log. When the log is full
+/// the oldest alert is dropped.
+pub fn raise<T: ToString>(kind: AlertKind, message: T) {
+    let alert = Alert::new(kind, message);
+    tracing::warn!(reason = "alert", kind = ?alert.kind, message = %alert.message);
+
+    ALERT_LOG.lock().unwrap().push(alert);
+}
+
+/// Returns alerts raised since given cursor and advances the cursor
+///
+/// Alerts dropped from the full log before being read are skipped.
+pub fn read_since(cursor: &mut u64) -> Vec<Alert> {
+    ALERT_LOG.lock().unwrap().read_since(cursor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alert(n: f64) -> Alert {
+        Alert::new(
+            AlertKind::Threshold {
+                rule: "test".to_string(),
+                metric: "test".to_string(),
+                value: n,
+            },
+            "test",
+        )
+    }
+
+    #[test]
+    fn test_alert_log_read_since() {
+        let mut log = AlertLog::default();
+        let mut a = 0;
+        let mut b = 0;
+
+        log.push(alert(1.0));
+        assert_eq!(1, log.read_since(&mut a).len());
+
+        log.push(alert(2.0));
+        assert_eq!(1, log.read_since(&mut a).len());
+        assert_eq!(0, log.read_since(&mut a).len());
+        assert_eq!(2, log.read_since(&mut b).len());
+    }
+
+    #[test]
+    fn test_alert_log_cap() {
+        let mut log = AlertLog::default();
+        let mut cursor = 0;
+
+        for n in 0..ALERT_LOG_CAP + 10 {
+            log.push(alert(n as f64));
+        }
+
+        assert_eq!(ALERT_LOG_CAP, log.read_since(&mut cursor).len());
+        assert_eq!((ALERT_LOG_CAP + 10) as u64, cursor);
+    }
+}
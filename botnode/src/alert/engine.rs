@@ -0,0 +1,114 @@
+use crate::rt::timer::sleep;
+
+use super::{
+    rules::{AlertRule, RuleEvaluator},
+    sink::AlertSink,
+};
+use crate::prelude::*;
+
+/// How often the rules are evaluated and new alerts delivered
+const EVALUATION_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Alerting engine
+///
+/// Evaluates threshold rules over published metrics and delivers every raised
+/// alert to the configured notification sinks.
+pub struct AlertEngine {
+    bot_id: BotId,
+    rules: Vec<AlertRule>,
+    sinks: Vec<Box<dyn AlertSink>>,
+    status_tx: spsc_queue::Producer<EngineStatus>,
+    status_rx: spsc_queue::Consumer<EngineStatus>,
+}
+
+impl AlertEngine {
+    pub fn new(bot_id: BotId, rules: Vec<AlertRule>, sinks: Vec<Box<dyn AlertSink>>) -> Self {
+        let (status_tx, status_rx) = spsc_queue::make(1);
+
+        Self {
+            bot_id,
+            rules,
+            sinks,
+            status_tx,
+            status_rx,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Engine for AlertEngine {
+    fn name(&self) -> String {
+        "alert-engine".to_string()
+    }
+
+    fn status_rx(&self) -> spsc_queue::Consumer<EngineStatus> {
+        self.status_rx.clone()
+    }
+
+    /// The bot keeps trading without alerts
+    fn essential(&self) -> bool {
+        false
+    }
+
+    async fn start(self, shutdown: Shutdown) -> Result<(), EngineError> {
+        info!(
+            "Starting alert engine with sinks {:?}",
+            self.sinks
+                .iter()
+                .map(|sink| sink.name())
+                .collect::<Vec<_>>()
+        );
+
+        self.status_tx.try_push(EngineStatus::Booting);
+
+        run_alert_loop(
+            self.bot_id,
+            RuleEvaluator::new(self.rules),
+            self.sinks,
+            self.status_tx,
+            shutdown,
+        )
+        .await
+    }
+}
+
+/// Alert engine loop
+pub async fn run_alert_loop(
+    bot_id: BotId,
+    mut evaluator: RuleEvaluator,
+    sinks: Vec<Box<dyn AlertSink>>,
+    status_tx: spsc_queue::Producer<EngineStatus>,
+    shutdown: Shutdown,
+) -> Result<(), EngineError> {
+    status_tx.try_push(EngineStatus::Running);
+
+    let mut cursor = 0;
+
+    loop {
+        if shutdown.shutdown_started() {
+            info!("shutting down alert engine");
+            status_tx.try_push(EngineStatus::ShuttingDown);
+
+            return Ok(());
+        }
+
+        let snapshot = crate::metrics::registry().snapshot();
+        for (kind, message) in evaluator.evaluate(
+            &snapshot,
+            std::time::Instant::now(),
+            std::time::SystemTime::now(),
+        ) {
+            super::raise(kind, message);
+        }
+
+        for alert in super::read_since(&mut cursor) {
+            for sink in sinks.iter() {
+                if let Err(e) = sink.send(bot_id.clone(), &alert).await {
+                    warn!("Failed to deliver alert to {}: {e}", sink.name());
+                }
+            }
+        }
+
+        sleep(EVALUATION_INTERVAL).await;
+    }
+}
@@ -0,0 +1,227 @@
+//! Threshold rules evaluated over published metrics
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant, SystemTime},
+};
+
+use botvana::net::msg::AlertKind;
+
+use crate::metrics::{MetricKey, MetricValue};
+
+/// Condition a metric value is checked against
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Condition {
+    /// Value is greater than the threshold
+    Above(f64),
+    /// Value is lower than the threshold
+    Below(f64),
+    /// Value is a UNIX timestamp in seconds older than given duration
+    OlderThan(Duration),
+}
+
+impl Condition {
+    fn is_met(&self, value: f64, now: SystemTime) -> bool {
+        match *self {
+            Condition::Above(threshold) => value > threshold,
+            Condition::Below(threshold) => value < threshold,
+            Condition::OlderThan(max_age) => {
+                let now = now
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64();
+                now - value > max_age.as_secs_f64()
+            }
+        }
+    }
+}
+
+/// Alert rule over a single metric
+#[derive(Clone, Debug)]
+pub struct AlertRule {
+    pub name: &'static str,
+    pub metric: &'static str,
+    pub condition: Condition,
+    /// How long the condition has to hold before the rule fires
+    pub hold: Duration,
+}
+
+impl AlertRule {
+    pub fn new(name: &'static str, metric: &'static str, condition: Condition) -> Self {
+        Self {
+            name,
+            metric,
+            condition,
+            hold: Duration::ZERO,
+        }
+    }
+
+    pub fn hold(mut self, hold: Duration) -> Self {
+        self.hold = hold;
+        self
+    }
+}
+
+/// Returns the rules every bot is evaluated with
+pub fn default_rules() -> Vec<AlertRule> {
+    vec![
+        AlertRule::new(
+            "feed-stale",
+            "market_data_last_message_timestamp_seconds",
+            Condition::OlderThan(Duration::from_secs(30)),
+        ),
+        AlertRule::new(
+            "kill-switch-tripped",
+            "risk_kill_switch_tripped",
+            Condition::Above(0.0),
+        ),
+        AlertRule::new(
+            "drawdown-warning",
+            "position_drawdown_ratio",
+            Condition::Above(0.05),
+        )
+        .hold(Duration::from_secs(10)),
+    ]
+}
+
+#[derive(Debug, Default)]
+struct RuleState {
+    met_since: Option<Instant>,
+    fired: bool,
+}
+
+/// Evaluates rules over metrics snapshots
+///
+/// Each rule fires once per metric when its condition held for the hold
+/// duration and re-arms once the condition clears.
+#[derive(Debug)]
+pub struct RuleEvaluator {
+    rules: Vec<AlertRule>,
+    state: HashMap<(usize, MetricKey), RuleState>,
+}
+
+impl RuleEvaluator {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        Self {
+            rules,
+            state: HashMap::new(),
+        }
+    }
+
+    /// Evaluates the rules, returns alerts of the rules that fired
+    pub fn evaluate(
+        &mut self,
+        metrics: &[(MetricKey, MetricValue)],
+        now: Instant,
+        wall_now: SystemTime,
+    ) -> Vec<(AlertKind, String)> {
+        let mut fired = Vec::new();
+
+        for (idx, rule) in self.rules.iter().enumerate() {
+            for (key, value) in metrics.iter().filter(|(key, _)| key.name == rule.metric) {
+                let value = value.as_f64();
+                let state = self.state.entry((idx, key.clone())).or_default();
+
+                if !rule.condition.is_met(value, wall_now) {
+                    state.met_since = None;
+                    state.fired = false;
+                    continue;
+                }
+
+                let since = *state.met_since.get_or_insert(now);
+                if !state.fired && now.duration_since(since) >= rule.hold {
+                    state.fired = true;
+
+                    let labels: Vec<_> = key
+                        .labels
+                        .iter()
+                        .filter(|(_, v)| &**v != crate::metrics::NOT_APPLICABLE)
+                        .map(|(k, v)| format!("{k}={v}"))
+                        .collect();
+                    fired.push((
+                        AlertKind::Threshold {
+                            rule: rule.name.to_string(),
+                            metric: rule.metric.to_string(),
+                            value,
+                        },
+                        format!(
+                            "{}: {} {{{}}} = {value} ({:?})",
+                            rule.name,
+                            rule.metric,
+                            labels.join(","),
+                            rule.condition
+                        ),
+                    ));
+                }
+            }
+        }
+
+        fired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::MetricLabels;
+
+    fn gauge(name: &'static str, value: f64) -> (MetricKey, MetricValue) {
+        (
+            MetricKey::new(name, &MetricLabels::new().exchange("ftx")),
+            MetricValue::Gauge(value),
+        )
+    }
+
+    #[test]
+    fn test_rule_fires_once_and_rearms() {
+        let mut evaluator =
+            RuleEvaluator::new(vec![AlertRule::new("high", "x", Condition::Above(1.0))]);
+        let now = Instant::now();
+        let wall_now = SystemTime::now();
+
+        assert_eq!(
+            1,
+            evaluator.evaluate(&[gauge("x", 2.0)], now, wall_now).len()
+        );
+        assert_eq!(
+            0,
+            evaluator.evaluate(&[gauge("x", 2.0)], now, wall_now).len()
+        );
+        assert_eq!(
+            0,
+            evaluator.evaluate(&[gauge("x", 0.5)], now, wall_now).len()
+        );
+        assert_eq!(
+            1,
+            evaluator.evaluate(&[gauge("x", 2.0)], now, wall_now).len()
+        );
+    }
+
+    #[test]
+    fn test_rule_hold() {
+        let rule = AlertRule::new("low", "x", Condition::Below(1.0)).hold(Duration::from_secs(5));
+        let mut evaluator = RuleEvaluator::new(vec![rule]);
+        let now = Instant::now();
+        let wall_now = SystemTime::now();
+
+        assert_eq!(
+            0,
+            evaluator.evaluate(&[gauge("x", 0.0)], now, wall_now).len()
+        );
+        assert_eq!(
+            1,
+            evaluator
+                .evaluate(&[gauge("x", 0.0)], now + Duration::from_secs(5), wall_now)
+                .len()
+        );
+    }
+
+    #[test]
+    fn test_condition_older_than() {
+        let condition = Condition::OlderThan(Duration::from_secs(30));
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(100);
+
+        assert!(condition.is_met(60.0, now));
+        assert!(!condition.is_met(80.0, now));
+    }
+}
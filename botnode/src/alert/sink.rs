@@ -0,0 +1,168 @@
+//! Alert notification sinks
+
+use serde::Deserialize;
+#[cfg(feature = "sinks")]
+use serde_json::json;
+
+use botvana::net::msg::Alert;
+
+use crate::prelude::*;
+
+/// Destination alerts are delivered to
+#[async_trait(?Send)]
+pub trait AlertSink {
+    /// Returns the name of the sink
+    fn name(&self) -> &'static str;
+
+    /// Delivers the alert
+    async fn send(&self, bot_id: BotId, alert: &Alert) -> Result<(), AlertSinkError>;
+}
+
+/// Posts alerts as JSON to a webhook
+#[cfg(feature = "sinks")]
+#[derive(Debug)]
+pub struct WebhookSink {
+    url: String,
+}
+
+#[cfg(feature = "sinks")]
+impl WebhookSink {
+    pub fn new<T: ToString>(url: T) -> Self {
+        Self {
+            url: url.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "sinks")]
+#[async_trait(?Send)]
+impl AlertSink for WebhookSink {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    async fn send(&self, bot_id: BotId, alert: &Alert) -> Result<(), AlertSinkError> {
+        let body = json!({
+            "bot_id": bot_id.0,
+            "kind": alert.kind,
+            "message": alert.message,
+            "timestamp": alert.timestamp,
+        });
+        let res = surf::post(&self.url)
+            .body_json(&body)
+            .map_err(AlertSinkError::surf_error)?
+            .await
+            .map_err(AlertSinkError::surf_error)?;
+
+        if !res.status().is_success() {
+            return Err(AlertSinkError::status(res.status()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Sends alerts as Telegram messages using the bot API
+#[cfg(feature = "sinks")]
+#[derive(Debug)]
+pub struct TelegramSink {
+    token: String,
+    chat_id: String,
+}
+
+#[cfg(feature = "sinks")]
+impl TelegramSink {
+    pub fn new<T: ToString, C: ToString>(token: T, chat_id: C) -> Self {
+        Self {
+            token: token.to_string(),
+            chat_id: chat_id.to_string(),
+        }
+    }
+}
+
+#[cfg(feature = "sinks")]
+#[async_trait(?Send)]
+impl AlertSink for TelegramSink {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    async fn send(&self, bot_id: BotId, alert: &Alert) -> Result<(), AlertSinkError> {
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", self.token);
+        let body = json!({
+            "chat_id": self.chat_id,
+            "text": format!("botnode {}: {}", bot_id.0, alert.message),
+        });
+        let res = surf::post(url)
+            .body_json(&body)
+            .map_err(AlertSinkError::surf_error)?
+            .await
+            .map_err(AlertSinkError::surf_error)?;
+
+        if !res.status().is_success() {
+            return Err(AlertSinkError::status(res.status()));
+        }
+
+        Ok(())
+    }
+}
+
+/// Alert sinks configuration
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AlertConfig {
+    /// Enables the webhook sink
+    pub webhook_url: Option<String>,
+    /// Enables the Telegram sink together with `telegram_chat_id`
+    pub telegram_token: Option<String>,
+    pub telegram_chat_id: Option<String>,
+}
+
+/// Builds the configured sinks
+#[cfg(feature = "sinks")]
+pub fn sinks(config: &AlertConfig) -> Vec<Box<dyn AlertSink>> {
+    let mut sinks: Vec<Box<dyn AlertSink>> = Vec::new();
+
+    if let Some(url) = &config.webhook_url {
+        sinks.push(Box::new(WebhookSink::new(url)));
+    }
+
+    match (&config.telegram_token, &config.telegram_chat_id) {
+        (Some(token), Some(chat_id)) => sinks.push(Box::new(TelegramSink::new(token, chat_id))),
+        (Some(_), None) => warn!("Telegram token set without chat id, sink disabled"),
+        _ => {}
+    }
+
+    sinks
+}
+
+/// Builds no sinks, botnode is built without the `sinks` feature
+#[cfg(not(feature = "sinks"))]
+pub fn sinks(config: &AlertConfig) -> Vec<Box<dyn AlertSink>> {
+    if config.webhook_url.is_some() || config.telegram_token.is_some() {
+        warn!("Alert sinks are configured but botnode is built without the `sinks` feature");
+    }
+
+    Vec::new()
+}
+
+/// Error delivering an alert
+#[derive(Debug, thiserror::Error)]
+#[error("Alert sink error: {source}")]
+pub struct AlertSinkError {
+    pub source: Box<dyn std::error::Error>,
+}
+
+impl AlertSinkError {
+    pub fn surf_error(e: surf::Error) -> Self {
+        Self {
+            source: e.to_string().into(),
+        }
+    }
+
+    pub fn status(status: surf::StatusCode) -> Self {
+        Self {
+            source: format!("unexpected response status {status}").into(),
+        }
+    }
+}
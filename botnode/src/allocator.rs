@@ -0,0 +1,152 @@
+//! Allocator statistics
+//!
+//! botnode uses snmalloc by default, the `jemalloc` and `mimalloc` features
+//! switch the global allocator and expose its heap statistics. Without any of
+//! them the system allocator is used. The stats are
+//! published with the other metrics so they are reported to botvana-server.
+
+/// Heap statistics of the global allocator
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AllocatorStats {
+    /// Name of the global allocator
+    pub allocator: &'static str,
+    /// Bytes allocated by the application
+    pub allocated: Option<u64>,
+    /// Bytes in pages with allocations, including unused space in them
+    pub active: Option<u64>,
+    /// Bytes of physical memory held by the allocator
+    pub resident: Option<u64>,
+    /// Bytes of virtual memory mapped by the allocator
+    pub mapped: Option<u64>,
+}
+
+impl AllocatorStats {
+    /// Returns share of resident memory not used by allocations
+    pub fn fragmentation(&self) -> Option<f64> {
+        match (self.allocated, self.resident) {
+            (Some(allocated), Some(resident)) if resident > 0 => {
+                Some(resident.saturating_sub(allocated) as f64 / resident as f64)
+            }
+            _ => None,
+        }
+    }
+
+    /// Publishes the stats into the metrics registry
+    pub fn publish(&self) {
+        let registry = crate::metrics::registry();
+        let labels = crate::metrics::MetricLabels::new().with("allocator", self.allocator);
+
+        let bytes = [
+            ("process_heap_allocated_bytes", self.allocated),
+            ("process_heap_active_bytes", self.active),
+            ("process_heap_resident_bytes", self.resident),
+            ("process_heap_mapped_bytes", self.mapped),
+        ];
+        for (name, value) in bytes {
+            if let Some(value) = value {
+                registry.set_gauge(name, &labels, value as f64);
+            }
+        }
+
+        if let Some(fragmentation) = self.fragmentation() {
+            registry.set_gauge("process_heap_fragmentation_ratio", &labels, fragmentation);
+        }
+    }
+}
+
+/// Returns current heap statistics of the global allocator
+#[cfg(feature = "jemalloc")]
+pub fn stats() -> AllocatorStats {
+    use tikv_jemalloc_ctl::{epoch, stats};
+
+    // Statistics are cached by jemalloc until the epoch is advanced
+    if let Err(e) = epoch::advance() {
+        tracing::warn!("Failed to refresh jemalloc stats: {e}");
+    }
+
+    AllocatorStats {
+        allocator: "jemalloc",
+        allocated: stats::allocated::read().ok().map(|v| v as u64),
+        active: stats::active::read().ok().map(|v| v as u64),
+        resident: stats::resident::read().ok().map(|v| v as u64),
+        mapped: stats::mapped::read().ok().map(|v| v as u64),
+    }
+}
+
+/// Returns current heap statistics of the global allocator
+#[cfg(all(feature = "mimalloc", not(feature = "jemalloc")))]
+pub fn stats() -> AllocatorStats {
+    let mut elapsed = 0;
+    let mut user = 0;
+    let mut system = 0;
+    let mut current_rss = 0;
+    let mut peak_rss = 0;
+    let mut current_commit = 0;
+    let mut peak_commit = 0;
+    let mut page_faults = 0;
+
+    // SAFETY: all pointers are valid for the duration of the call
+    unsafe {
+        libmimalloc_sys::mi_process_info(
+            &mut elapsed,
+            &mut user,
+            &mut system,
+            &mut current_rss,
+            &mut peak_rss,
+            &mut current_commit,
+            &mut peak_commit,
+            &mut page_faults,
+        );
+    }
+
+    AllocatorStats {
+        allocator: "mimalloc",
+        allocated: None,
+        active: Some(current_commit as u64),
+        resident: Some(current_rss as u64),
+        mapped: None,
+    }
+}
+
+/// Returns current heap statistics of the global allocator
+///
+/// snmalloc does not expose statistics, only the allocator name is reported.
+#[cfg(all(
+    feature = "snmalloc",
+    not(any(feature = "jemalloc", feature = "mimalloc"))
+))]
+pub fn stats() -> AllocatorStats {
+    AllocatorStats {
+        allocator: "snmalloc",
+        ..AllocatorStats::default()
+    }
+}
+
+/// Returns current heap statistics of the global allocator
+///
+/// Only the allocator name is reported for the system allocator.
+#[cfg(not(any(feature = "snmalloc", feature = "jemalloc", feature = "mimalloc")))]
+pub fn stats() -> AllocatorStats {
+    AllocatorStats {
+        allocator: "system",
+        ..AllocatorStats::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocator_stats_fragmentation() {
+        let stats = AllocatorStats {
+            allocator: "test",
+            allocated: Some(75),
+            resident: Some(100),
+            ..AllocatorStats::default()
+        };
+
+        assert_eq!(Some(0.25), stats.fragmentation());
+        assert_eq!(None, AllocatorStats::default().fragmentation());
+    }
+}
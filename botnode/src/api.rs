@@ -0,0 +1,12 @@
+//! Local inspection APIs
+//!
+//! Servers for local tooling that read the [`crate::status`] snapshot. They
+//! run on their own threads, outside of the engine cores, and are separate
+//! from the botvana-server control channel.
+
+#[cfg(feature = "grpc")]
+pub mod grpc;
+#[cfg(feature = "api")]
+pub mod http;
+#[cfg(feature = "api")]
+pub mod ws;
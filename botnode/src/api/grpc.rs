@@ -0,0 +1,208 @@
+//! gRPC inspection server
+//!
+//! Implements the `botnode.inspect.v1.Inspect` service from
+//! `botnode/proto/inspect.proto`.
+
+use std::{net::SocketAddr, thread};
+
+use tonic::{transport::Server, Request, Response, Status};
+use tracing::{error, info};
+
+use botvana::{exchange::ExchangeId, market::orderbook::PriceLevelsVec};
+
+use crate::status::{self, BotStatus};
+
+pub mod proto {
+    tonic::include_proto!("botnode.inspect.v1");
+}
+
+use proto::inspect_server::{Inspect, InspectServer};
+
+/// Inspection service reading the process-wide status
+#[derive(Debug, Default)]
+pub struct InspectService;
+
+#[tonic::async_trait]
+impl Inspect for InspectService {
+    async fn get_health(
+        &self,
+        _request: Request<proto::GetHealthRequest>,
+    ) -> Result<Response<proto::GetHealthResponse>, Status> {
+        Ok(Response::new(status::read(health)))
+    }
+
+    async fn list_positions(
+        &self,
+        _request: Request<proto::ListPositionsRequest>,
+    ) -> Result<Response<proto::ListPositionsResponse>, Status> {
+        let positions = status::read(|s| {
+            s.positions
+                .iter()
+                .map(|position| proto::Position {
+                    exchange: position.exchange.as_str().to_string(),
+                    market: position.market.to_string(),
+                    size: position.size,
+                    avg_entry_price: position.avg_entry_price,
+                    realized_pnl: position.realized_pnl,
+                    unrealized_pnl: position.unrealized_pnl,
+                })
+                .collect()
+        });
+
+        Ok(Response::new(proto::ListPositionsResponse { positions }))
+    }
+
+    async fn list_open_orders(
+        &self,
+        _request: Request<proto::ListOpenOrdersRequest>,
+    ) -> Result<Response<proto::ListOpenOrdersResponse>, Status> {
+        let orders = status::read(|s| {
+            s.open_orders
+                .iter()
+                .map(|order| proto::OpenOrder {
+                    id: order.id.clone(),
+                    exchange: order.exchange.as_str().to_string(),
+                    market: order.market.to_string(),
+                    side: order.side.as_str().to_string(),
+                    price: order.price,
+                    size: order.size,
+                    filled_size: order.filled_size,
+                })
+                .collect()
+        });
+
+        Ok(Response::new(proto::ListOpenOrdersResponse { orders }))
+    }
+
+    async fn get_orderbook(
+        &self,
+        request: Request<proto::GetOrderbookRequest>,
+    ) -> Result<Response<proto::GetOrderbookResponse>, Status> {
+        let request = request.into_inner();
+        let exchange = request
+            .exchange
+            .parse::<ExchangeId>()
+            .map_err(Status::invalid_argument)?;
+        let depth = match request.depth {
+            0 => usize::MAX,
+            depth => depth as usize,
+        };
+
+        status::read(|s| {
+            let orderbook = s
+                .orderbooks
+                .get(&(exchange, Box::from(request.market.as_str())))
+                .ok_or_else(|| {
+                    Status::not_found(format!("no orderbook for {exchange} {}", request.market))
+                })?;
+
+            Ok(Response::new(proto::GetOrderbookResponse {
+                bids: levels(&orderbook.bids, depth, true),
+                asks: levels(&orderbook.asks, depth, false),
+                time: orderbook.time,
+            }))
+        })
+    }
+}
+
+/// Builds the health response from the status snapshot
+fn health(status: &BotStatus) -> proto::GetHealthResponse {
+    proto::GetHealthResponse {
+        bot_id: status.bot_id as u32,
+        dry_run: status.dry_run,
+        server_connected: status.server_connected,
+        ready: status.ready(),
+        engines: status
+            .engines
+            .iter()
+            .map(|(name, engine)| proto::EngineHealth {
+                name: name.clone(),
+                status: engine.status.as_str().to_string(),
+                updated_at: engine.updated_at,
+            })
+            .collect(),
+    }
+}
+
+/// Returns up to `depth` best levels, best first
+///
+/// Both sides are sorted by price ascending, so the best bids are at the end.
+fn levels(levels: &PriceLevelsVec<f64>, depth: usize, bids: bool) -> Vec<proto::PriceLevel> {
+    let levels = levels
+        .price_vec
+        .iter()
+        .zip(levels.size_vec.iter())
+        .map(|(price, size)| proto::PriceLevel {
+            price: *price,
+            size: *size,
+        });
+
+    match bids {
+        true => levels.rev().take(depth).collect(),
+        false => levels.take(depth).collect(),
+    }
+}
+
+/// Starts the gRPC server on its own thread
+pub fn start(addr: SocketAddr) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("grpc-server".to_string())
+        .spawn(move || {
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    error!("Failed to start gRPC server runtime: {e}");
+                    return;
+                }
+            };
+
+            info!("Serving gRPC inspection API on {addr}");
+            let server = Server::builder()
+                .add_service(InspectServer::new(InspectService))
+                .serve(addr);
+            if let Err(e) = runtime.block_on(server) {
+                error!("gRPC server failed: {e}");
+            }
+        })
+        .expect("failed to spawn gRPC server thread")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels_best_first() {
+        let mut orderbook = botvana::market::orderbook::PlainOrderbook::new();
+        orderbook.bids.price_vec.extend([98.0, 99.0]);
+        orderbook.bids.size_vec.extend([2.0, 1.0]);
+        orderbook.asks.price_vec.extend([100.0, 101.0]);
+        orderbook.asks.size_vec.extend([3.0, 4.0]);
+
+        let bids = levels(&orderbook.bids, 1, true);
+        assert_eq!(1, bids.len());
+        assert_eq!(99.0, bids[0].price);
+
+        let asks = levels(&orderbook.asks, usize::MAX, false);
+        assert_eq!(
+            vec![100.0, 101.0],
+            asks.iter().map(|l| l.price).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_health_engine_status() {
+        let mut status = BotStatus::default();
+        status.set_engine_status(
+            "trading-engine".to_string(),
+            crate::engine::EngineStatus::Running,
+        );
+
+        let health = health(&status);
+        assert!(!health.ready);
+        assert_eq!("running", health.engines[0].status);
+    }
+}
@@ -0,0 +1,105 @@
+//! HTTP status server
+//!
+//! Serves probes for orchestrators and quick `curl` checks:
+//!
+//! * `/healthz` answers 200 while the process is up
+//! * `/readyz` answers 200 once the bot is connected to botvana-server and
+//!   all engines are running, 503 otherwise
+//! * `/status` returns the status snapshot as JSON
+//! * `/metrics` returns the published metrics in the Prometheus text format
+
+use std::net::SocketAddr;
+
+use serde_json::json;
+use tide::{Request, Response, StatusCode};
+use tracing::{error, info};
+
+use crate::{metrics, rt, status};
+
+/// Builds the tide application serving the status endpoints
+pub fn app() -> tide::Server<()> {
+    let mut app = tide::new();
+
+    app.at("/healthz")
+        .get(|_req: Request<()>| async move { Ok("ok") });
+
+    app.at("/readyz").get(|_req: Request<()>| async move {
+        let response = match status::read(|s| s.ready()) {
+            true => Response::builder(StatusCode::Ok).body("ready"),
+            false => Response::builder(StatusCode::ServiceUnavailable).body("not ready"),
+        };
+        Ok(response.build())
+    });
+
+    app.at("/status").get(|_req: Request<()>| async move {
+        let body = status::read(|s| {
+            json!({
+                "ready": s.ready(),
+                "status": s,
+            })
+        });
+        Ok(Response::builder(StatusCode::Ok).body(body).build())
+    });
+
+    app.at("/metrics").get(|_req: Request<()>| async move {
+        let body = metrics::prometheus::encode(&metrics::registry().snapshot());
+        Ok(Response::builder(StatusCode::Ok)
+            .content_type(metrics::prometheus::CONTENT_TYPE)
+            .body(body)
+            .build())
+    });
+
+    app
+}
+
+/// Starts the HTTP server on its own unpinned executor
+pub fn start(addr: SocketAddr) {
+    let res = rt::spawn_executor("http-server", None, move || async move {
+        info!("Serving HTTP status on {addr}");
+        if let Err(e) = app().listen(addr).await {
+            error!("HTTP status server failed: {e}");
+        }
+    });
+
+    if let Err(e) = res {
+        error!("Failed to start HTTP status server: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tide::http::{Method, Url};
+
+    fn get(path: &str) -> tide::http::Response {
+        let req = tide::http::Request::new(
+            Method::Get,
+            Url::parse(&format!("http://localhost{path}")).unwrap(),
+        );
+        smol::block_on(app().respond(req)).unwrap()
+    }
+
+    #[test]
+    fn test_status_endpoints() {
+        assert_eq!(StatusCode::Ok, get("/healthz").status());
+        assert_eq!(StatusCode::ServiceUnavailable, get("/readyz").status());
+
+        let mut res = get("/status");
+        assert_eq!(StatusCode::Ok, res.status());
+        let body: serde_json::Value =
+            serde_json::from_str(&smol::block_on(res.body_string()).unwrap()).unwrap();
+        assert_eq!(false, body["ready"]);
+        assert!(body["status"]["engines"].is_object());
+
+        crate::metrics::registry().set_counter(
+            "test_http_metrics_total",
+            &crate::metrics::MetricLabels::new(),
+            1,
+        );
+        let mut res = get("/metrics");
+        assert_eq!(StatusCode::Ok, res.status());
+        assert!(smol::block_on(res.body_string())
+            .unwrap()
+            .contains("# TYPE test_http_metrics_total counter"));
+    }
+}
@@ -0,0 +1,188 @@
+//! Local WebSocket UI feed
+//!
+//! Streams the bot state to operator GUIs and TUIs as JSON text messages. A
+//! client first receives a `snapshot` message and then an `update` message
+//! every `api.ws_interval_ms` with the top levels of every orderbook, fills
+//! recorded since the previous message, positions and PnL. The feed is
+//! read-only, messages sent by clients are ignored.
+
+use std::{
+    net::SocketAddr,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use async_lock::Semaphore;
+use async_tungstenite::{
+    accept_async,
+    tungstenite::{Message, Result},
+};
+use futures::prelude::*;
+use serde_json::{json, Value};
+use tracing::*;
+
+use botvana::market::orderbook::PriceLevelsVec;
+
+use crate::{
+    rt::{
+        self,
+        net::{TcpListener, TcpStream},
+    },
+    status::{self, BotStatus},
+};
+
+const MAX_CONNECTIONS: usize = 16;
+const READ_TIMEOUT_US: u64 = 500;
+
+/// Number of orderbook levels per side sent to clients
+pub const FEED_DEPTH: usize = 20;
+
+/// Runs TCP listener loop and spawns new task for each incoming connection
+pub async fn run_listener(addr: SocketAddr, interval: Duration) {
+    let listener = match TcpListener::bind(addr) {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to listen on {addr}: {e}");
+            return;
+        }
+    };
+    let conn_control = Rc::new(Semaphore::new(MAX_CONNECTIONS));
+    info!("Serving WebSocket UI feed on {addr}");
+
+    while let Ok(stream) = listener.accept().await {
+        let conn_control = conn_control.clone();
+        rt::spawn_local(async move {
+            let _permit = conn_control.acquire().await;
+
+            if let Err(e) = handle_connection(stream, interval).await {
+                debug!("UI feed client disconnected: {e}");
+            }
+        });
+    }
+}
+
+/// Sends the snapshot and then throttled updates until the client
+/// disconnects
+async fn handle_connection(stream: TcpStream, interval: Duration) -> Result<()> {
+    let mut ws_stream = accept_async(stream).await?;
+    let mut fill_seq = 0;
+
+    let snapshot = status::read(|s| message("snapshot", s, &mut fill_seq));
+    ws_stream.send(Message::Text(snapshot.to_string())).await?;
+    let mut last_update = Instant::now();
+
+    loop {
+        // Drain client messages, the feed is read-only
+        let msg = rt::timer::timeout(Duration::from_micros(READ_TIMEOUT_US), async {
+            Ok(ws_stream.next().await)
+        })
+        .await;
+        match msg {
+            Ok(None) | Ok(Some(Ok(Message::Close(_)))) => return Ok(()),
+            Ok(Some(Err(e))) => return Err(e),
+            _ => {}
+        }
+
+        if last_update.elapsed() >= interval {
+            let update = status::read(|s| message("update", s, &mut fill_seq));
+            ws_stream.send(Message::Text(update.to_string())).await?;
+            last_update = Instant::now();
+        }
+    }
+}
+
+/// Builds feed message from the status, advancing the fill cursor
+fn message(r#type: &str, status: &BotStatus, fill_seq: &mut u64) -> Value {
+    let fills: Vec<_> = status.fills_since(*fill_seq).collect();
+    *fill_seq = status.fill_seq;
+
+    let orderbooks: Vec<_> = status
+        .orderbooks
+        .values()
+        .map(|orderbook| {
+            json!({
+                "exchange": orderbook.exchange.as_str(),
+                "market": orderbook.market,
+                "bids": levels(&orderbook.bids, true),
+                "asks": levels(&orderbook.asks, false),
+                "time": orderbook.time,
+            })
+        })
+        .collect();
+    let (realized_pnl, unrealized_pnl) = status.pnl();
+
+    json!({
+        "type": r#type,
+        "bot_id": status.bot_id,
+        "dry_run": status.dry_run,
+        "orderbooks": orderbooks,
+        "fills": fills,
+        "positions": status.positions,
+        "pnl": {
+            "realized": realized_pnl,
+            "unrealized": unrealized_pnl,
+        },
+    })
+}
+
+/// Returns up to [`FEED_DEPTH`] best levels as `[price, size]`, best first
+fn levels(levels: &PriceLevelsVec<f64>, bids: bool) -> Vec<[f64; 2]> {
+    let levels = levels
+        .price_vec
+        .iter()
+        .zip(levels.size_vec.iter())
+        .map(|(price, size)| [*price, *size]);
+
+    match bids {
+        true => levels.rev().take(FEED_DEPTH).collect(),
+        false => levels.take(FEED_DEPTH).collect(),
+    }
+}
+
+/// Starts the feed on its own unpinned executor
+pub fn start(addr: SocketAddr, interval: Duration) {
+    let res = rt::spawn_executor("ws-feed", None, move || run_listener(addr, interval));
+
+    if let Err(e) = res {
+        error!("Failed to start WebSocket UI feed: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{status::Fill, trading::strategy::Side};
+    use botvana::{exchange::ExchangeId, market::orderbook::Orderbook};
+
+    #[test]
+    fn test_feed_message() {
+        let mut status = BotStatus::default();
+        let mut orderbook = Orderbook::new(ExchangeId::Ftx, Box::from("BTC-PERP"));
+        orderbook.bids.price_vec.extend([98.0, 99.0]);
+        orderbook.bids.size_vec.extend([2.0, 1.0]);
+        status
+            .orderbooks
+            .insert((ExchangeId::Ftx, Box::from("BTC-PERP")), orderbook);
+        status.record_fill(Fill {
+            seq: 0,
+            order_id: "1".to_string(),
+            exchange: ExchangeId::Ftx,
+            market: Box::from("BTC-PERP"),
+            side: Side::Sell,
+            price: 99.0,
+            size: 1.0,
+            time: 0,
+        });
+
+        let mut fill_seq = 0;
+        let snapshot = message("snapshot", &status, &mut fill_seq);
+        assert_eq!("snapshot", snapshot["type"]);
+        assert_eq!(json!([99.0, 1.0]), snapshot["orderbooks"][0]["bids"][0]);
+        assert_eq!("sell", snapshot["fills"][0]["side"]);
+        assert_eq!(1, fill_seq);
+
+        // Fills are only sent once
+        let update = message("update", &status, &mut fill_seq);
+        assert_eq!(0, update["fills"].as_array().unwrap().len());
+    }
+}
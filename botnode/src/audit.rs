@@ -1,3 +1,216 @@
 //! Audit engine
+//!
+//! Engines send [`AuditEvent`]s describing every action they take to the
+//! audit engine, which writes them into a rotating structured log that forms
+//! the compliance record of the bot. Order decisions, risk rejections,
+//! executions and connection changes carry an [`AuditRecord`] with their
+//! details, so the log can be queried without parsing messages.
 
 pub mod engine;
+pub mod log;
+
+use std::time::SystemTime;
+
+use serde::Serialize;
+
+use crate::{
+    order_engine::{Fill, OrderStatus, OrderUpdate},
+    prelude::*,
+    trading::strategy::{OrderIntent, Side},
+};
+
+/// Length of the audit channel of each engine
+pub const AUDIT_QUEUE_LEN: usize = 1024;
+
+/// Producer half of an engine's audit channel
+pub type AuditProducer = spsc_queue::Producer<AuditEvent>;
+
+/// Severity of an audit event
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+/// Category of an audit event
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    /// Engine started or stopped
+    Lifecycle,
+    /// Configuration received or changed
+    Config,
+    /// Market data anomaly
+    MarketData,
+    /// Order placed, amended or cancelled
+    Order,
+    /// Order filled or rejected by the exchange
+    Execution,
+    /// Position or P&L changed
+    Position,
+    /// Order rejected by the pre-trade risk checks
+    Risk,
+    /// Connection to an exchange or botvana-server established or lost
+    Connection,
+}
+
+/// Structured details of an audit event
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuditRecord {
+    /// Strategy decided to place an order
+    OrderDecision {
+        strategy: Box<str>,
+        market: Box<str>,
+        side: Side,
+        /// Limit price, `None` for a market order
+        price: Option<f64>,
+        size: f64,
+    },
+    /// Order rejected before it was sent to the exchange
+    RiskRejection {
+        client_id: Box<str>,
+        market: Box<str>,
+        side: Side,
+        price: Option<f64>,
+        size: f64,
+        reason: String,
+    },
+    /// Order changed its state on the exchange
+    OrderUpdate {
+        client_id: Box<str>,
+        exchange_id: Option<Box<str>>,
+        market: Box<str>,
+        status: OrderStatus,
+        filled_size: f64,
+        reason: Option<String>,
+    },
+    /// Order was filled
+    Fill {
+        client_id: Option<Box<str>>,
+        market: Box<str>,
+        side: Side,
+        price: f64,
+        size: f64,
+        fee: f64,
+    },
+    /// Connection to an exchange or botvana-server changed state
+    Connection {
+        /// Exchange name or `botvana-server`
+        peer: Box<str>,
+        connected: bool,
+    },
+}
+
+impl AuditRecord {
+    pub fn order_decision(strategy: &str, intent: &OrderIntent) -> Self {
+        Self::OrderDecision {
+            strategy: Box::from(strategy),
+            market: intent.market.clone(),
+            side: intent.side,
+            price: intent.price,
+            size: intent.size,
+        }
+    }
+
+    pub fn order_update(update: &OrderUpdate) -> Self {
+        Self::OrderUpdate {
+            client_id: update.client_id.clone(),
+            exchange_id: update.exchange_id.clone(),
+            market: update.market.clone(),
+            status: update.status,
+            filled_size: update.filled_size,
+            reason: update.reason.clone(),
+        }
+    }
+
+    pub fn fill(fill: &Fill) -> Self {
+        Self::Fill {
+            client_id: fill.client_id.clone(),
+            market: fill.market.clone(),
+            side: fill.side,
+            price: fill.price,
+            size: fill.size,
+            fee: fill.fee,
+        }
+    }
+}
+
+/// Single entry of the audit log
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditEvent {
+    /// Time of the event in milliseconds since UNIX epoch
+    pub timestamp: u64,
+    pub severity: Severity,
+    pub category: Category,
+    /// Name of the engine that produced the event
+    pub engine: &'static str,
+    pub message: String,
+    /// Details of the action, when it has any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record: Option<AuditRecord>,
+}
+
+impl AuditEvent {
+    /// Creates new audit event timestamped with current time
+    pub fn new<T: ToString>(
+        severity: Severity,
+        category: Category,
+        engine: &'static str,
+        message: T,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        Self {
+            timestamp,
+            severity,
+            category,
+            engine,
+            message: message.to_string(),
+            record: None,
+        }
+    }
+
+    /// Attaches structured details to the event
+    pub fn with_record(mut self, record: AuditRecord) -> Self {
+        self.record = Some(record);
+        self
+    }
+}
+
+/// Sends the event to the audit engine
+///
+/// The event is dropped with an error logged when the audit channel is full,
+/// engines never block on auditing.
+pub fn record(audit_tx: &AuditProducer, event: AuditEvent) {
+    if let Some(event) = audit_tx.try_push(event) {
+        error!("Audit channel full, dropping audit event {event:?}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_record_serialization() {
+        let intent = OrderIntent::limit("BTC-PERP", Side::Buy, 100.0, 1.0);
+        let event = AuditEvent::new(Severity::Info, Category::Order, "test", "decided")
+            .with_record(AuditRecord::order_decision("maker", &intent));
+
+        let value = serde_json::to_value(&event).unwrap();
+        assert_eq!("order_decision", value["record"]["kind"]);
+        assert_eq!("maker", value["record"]["strategy"]);
+        assert_eq!("buy", value["record"]["side"]);
+        assert_eq!(100.0, value["record"]["price"]);
+
+        let event = AuditEvent::new(Severity::Info, Category::Lifecycle, "test", "started");
+        let value = serde_json::to_value(&event).unwrap();
+        assert!(value.get("record").is_none());
+    }
+}
@@ -2,12 +2,25 @@ use std::cell::RefCell;
 
 use metered::{clear::Clear, time_source::StdInstant, *};
 
+use super::{
+    log::{AuditLogConfig, RotatingLog},
+    AuditEvent, AuditProducer, Category, Severity, AUDIT_QUEUE_LEN,
+};
 use crate::prelude::*;
 
+/// Maximum number of audit events written per loop iteration
+const MAX_EVENTS_PER_ITERATION: usize = 64;
+
 /// Auditing engine
+///
+/// Consumes structured audit events from all engines over their dedicated
+/// channels and writes them into the rotating audit log. Market data is only checked for late events, which are
+/// summarized into the log every 5 seconds.
 #[derive(Debug)]
 pub struct AuditEngine {
     market_data_rxs: ConsumersMap<Box<str>, MarketEvent>,
+    audit_rxs: ConsumersMap<Box<str>, AuditEvent>,
+    log_config: AuditLogConfig,
     status_tx: spsc_queue::Producer<EngineStatus>,
     status_rx: spsc_queue::Consumer<EngineStatus>,
     metrics: AuditMetrics,
@@ -19,17 +32,35 @@ pub struct AuditMetrics {
 }
 
 impl AuditEngine {
-    pub fn new(market_data_rxs: ConsumersMap<Box<str>, MarketEvent>) -> Self {
+    pub fn new(log_config: AuditLogConfig) -> Self {
         let (status_tx, status_rx) = spsc_queue::make(1);
         let metrics = AuditMetrics::default();
 
         Self {
-            market_data_rxs,
+            market_data_rxs: ConsumersMap::default(),
+            audit_rxs: ConsumersMap::default(),
+            log_config,
             status_tx,
             status_rx,
             metrics,
         }
     }
+
+    /// Sets market data checked for late events
+    pub fn with_market_data(
+        mut self,
+        market_data_rxs: ConsumersMap<Box<str>, MarketEvent>,
+    ) -> Self {
+        self.market_data_rxs = market_data_rxs;
+        self
+    }
+
+    /// Returns new audit channel producer for given engine
+    pub fn audit_tx(&mut self, engine: &str) -> AuditProducer {
+        let (audit_tx, audit_rx) = spsc_queue::make(AUDIT_QUEUE_LEN);
+        self.audit_rxs.insert(Box::from(engine), audit_rx);
+        audit_tx
+    }
 }
 
 #[async_trait(?Send)]
@@ -47,7 +78,22 @@ impl Engine for AuditEngine {
 
         self.status_tx.try_push(EngineStatus::Booting);
 
-        run_audit_loop(self.status_tx, self.market_data_rxs, self.metrics, shutdown).await
+        // The audit engine runs on its own core so blocking file writes
+        // don't hold up any other engine.
+        let log = self.log_config.open().map_err(|e| {
+            self.status_tx.try_push(EngineStatus::Error);
+            EngineError::config(e)
+        })?;
+
+        run_audit_loop(
+            self.status_tx,
+            self.market_data_rxs,
+            self.audit_rxs,
+            log,
+            self.metrics,
+            shutdown,
+        )
+        .await
     }
 }
 
@@ -55,6 +101,8 @@ impl Engine for AuditEngine {
 pub async fn run_audit_loop(
     status_tx: spsc_queue::Producer<EngineStatus>,
     market_data_rxs: ConsumersMap<Box<str>, MarketEvent>,
+    audit_rxs: ConsumersMap<Box<str>, AuditEvent>,
+    mut log: RotatingLog,
     audit_metrics: AuditMetrics,
     shutdown: Shutdown,
 ) -> Result<(), EngineError> {
@@ -64,36 +112,79 @@ pub async fn run_audit_loop(
 
     let throughput = &audit_metrics.throughput;
     let mut start = std::time::Instant::now();
+    let mut late_events = 0;
 
     loop {
+        if shutdown.shutdown_started() {
+            info!("shutting down audit engine");
+
+            // Write out whatever the other engines managed to send
+            while let Some((_, event)) = audit_rxs.poll_values() {
+                write_event(&mut log, &event);
+            }
+            if let Err(e) = log.flush() {
+                error!("Failed to flush audit log: {e}");
+            }
+
+            status_tx.try_push(EngineStatus::ShuttingDown);
+
+            return Ok(());
+        }
+
         measure!(throughput, {
             for (_, market_data_rx) in market_data_rxs.iter() {
                 if let Some(event) = market_data_rx.try_pop() {
-                    let elapsed = event.timestamp.elapsed().unwrap();
+                    let elapsed = event.timestamp.elapsed().unwrap_or_default();
                     trace!("market_event = {event:?}");
 
                     if elapsed > std::time::Duration::from_millis(1) {
                         warn!("Late market event!");
+                        late_events += 1;
                     }
                 }
             }
         });
 
-        if start.elapsed().as_secs() >= 5 {
-            if shutdown.shutdown_started() {
-                info!("shutting down audit engine");
+        for _ in 0..MAX_EVENTS_PER_ITERATION {
+            match audit_rxs.poll_values() {
+                Some((_, event)) => write_event(&mut log, &event),
+                None => break,
+            }
+        }
 
-                status_tx.try_push(EngineStatus::ShuttingDown);
+        if start.elapsed().as_secs() >= 5 {
+            if late_events > 0 {
+                write_event(
+                    &mut log,
+                    &AuditEvent::new(
+                        Severity::Warning,
+                        Category::MarketData,
+                        "audit-engine",
+                        format!("{late_events} late market events over last 5s"),
+                    ),
+                );
+                late_events = 0;
+            }
 
-                return Ok(());
+            if let Err(e) = log.flush() {
+                error!("Failed to flush audit log: {e}");
             }
 
             start = std::time::Instant::now();
-            info!(
-                "max throughput over last 5s = {:?}",
-                throughput.0.borrow().hdr_histogram.mean()
+            let mean_throughput = throughput.0.borrow().hdr_histogram.mean();
+            info!("max throughput over last 5s = {mean_throughput:?}");
+            crate::metrics::registry().set_gauge(
+                "audit_events_per_second_mean",
+                &crate::metrics::MetricLabels::new(),
+                mean_throughput,
             );
             throughput.clear();
         }
     }
 }
+
+fn write_event(log: &mut RotatingLog, event: &AuditEvent) {
+    if let Err(e) = log.write(event) {
+        error!("Failed to write audit event {event:?}: {e}");
+    }
+}
@@ -0,0 +1,112 @@
+//! Rotating audit log
+//!
+//! Events are written as newline-delimited JSON into `audit.log` in the
+//! configured directory. Once the file grows over the size limit or gets
+//! older than the age limit it is renamed to `audit-<timestamp>.log` and a
+//! new file is started.
+
+use std::{io, path::PathBuf, time::Duration};
+
+use serde::Deserialize;
+
+pub use crate::util::rotating_log::RotatingLog;
+
+/// Name the audit log files start with
+pub const LOG_NAME: &str = "audit";
+
+/// Audit log configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuditLogConfig {
+    /// Directory the log files are written to
+    pub dir: PathBuf,
+    /// Size after which the log is rotated
+    pub max_bytes: u64,
+    /// Seconds after which the log is rotated
+    pub max_age_secs: u64,
+}
+
+impl Default for AuditLogConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("audit"),
+            max_bytes: 64 * 1024 * 1024,
+            max_age_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+impl AuditLogConfig {
+    /// Returns age after which the log is rotated
+    pub fn max_age(&self) -> Duration {
+        Duration::from_secs(self.max_age_secs)
+    }
+
+    /// Opens the audit log, appending to the current file if it exists
+    pub fn open(&self) -> io::Result<RotatingLog> {
+        RotatingLog::open(&self.dir, LOG_NAME, self.max_bytes, self.max_age())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::audit::{AuditEvent, Category, Severity};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("botnode-audit-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn event() -> AuditEvent {
+        AuditEvent::new(Severity::Info, Category::Order, "test", "placed order")
+    }
+
+    #[test]
+    fn test_rotating_log_writes_json_lines() {
+        let dir = test_dir("write");
+        let mut log = AuditLogConfig {
+            dir: dir.clone(),
+            ..AuditLogConfig::default()
+        }
+        .open()
+        .unwrap();
+
+        log.write(&event()).unwrap();
+        log.write(&event()).unwrap();
+        log.flush().unwrap();
+
+        let contents = fs::read_to_string(dir.join("audit.log")).unwrap();
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(2, lines.len());
+
+        let value: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!("info", value["severity"]);
+        assert_eq!("order", value["category"]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_rotating_log_rotates_on_size() {
+        let dir = test_dir("rotate");
+        let mut log = AuditLogConfig {
+            dir: dir.clone(),
+            max_bytes: 1,
+            ..AuditLogConfig::default()
+        }
+        .open()
+        .unwrap();
+
+        log.write(&event()).unwrap();
+        log.write(&event()).unwrap();
+        log.flush().unwrap();
+
+        assert_eq!(2, fs::read_dir(&dir).unwrap().count());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
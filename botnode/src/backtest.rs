@@ -0,0 +1,368 @@
+//! Backtesting of strategies against recorded market data
+//!
+//! [`Backtest`] replays a recording made by the [recorder](crate::recorder)
+//! through the same strategies, books, indicators and order routing as the
+//! trading engine, but synchronously and in simulated time: every event is
+//! processed at the time it was recorded, so runs are deterministic and as
+//! fast as the strategies. Orders are executed by a [`SimulatedExchange`]
+//! against the recorded books of one exchange and the fills are tracked in a
+//! [`Ledger`] summarized by the [`BacktestReport`].
+
+pub mod exchange;
+pub mod report;
+
+use std::{
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::Deserialize;
+
+pub use exchange::SimulatedExchange;
+pub use report::{BacktestReport, Ledger};
+
+use crate::{
+    audit::AUDIT_QUEUE_LEN,
+    market_data::book_events::BookEvents,
+    order_engine::{ExchangeEvent, ExchangeRequest},
+    prelude::*,
+    recorder::{read_recording, RecordedEvent},
+    trading::{
+        books::Orderbooks,
+        event_loop::{process_intents, run_strategies},
+        indicators::{Indicators, IndicatorsConfig},
+        router::OrderRouter,
+        strategy::Strategy,
+    },
+};
+
+/// Length of the queue between the order router and the simulated exchange,
+/// drained after every event
+const ORDER_QUEUE_LEN: usize = 1024;
+
+/// Simulated time between calls of the strategies' `on_timer`
+const STRATEGY_TIMER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Backtest configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BacktestConfig {
+    /// Exchange whose recorded books orders are executed against, the first
+    /// recorded exchange when not set
+    pub exchange: Option<Box<str>>,
+    /// Milliseconds between sending a request and the exchange acting on it
+    pub latency_ms: u64,
+    /// Fee of fills providing liquidity in basis points
+    pub maker_fee_bps: f64,
+    /// Fee of fills taking liquidity in basis points
+    pub taker_fee_bps: f64,
+    /// File the report is written to as JSON
+    pub report: Option<PathBuf>,
+}
+
+impl Default for BacktestConfig {
+    fn default() -> Self {
+        Self {
+            exchange: None,
+            latency_ms: 50,
+            maker_fee_bps: 2.0,
+            taker_fee_bps: 5.0,
+            report: None,
+        }
+    }
+}
+
+impl BacktestConfig {
+    pub fn latency(&self) -> Duration {
+        Duration::from_millis(self.latency_ms)
+    }
+}
+
+/// Backtest of strategies against a recording
+pub struct Backtest {
+    config: BacktestConfig,
+    strategies: Vec<Box<dyn Strategy>>,
+    indicators: IndicatorsConfig,
+}
+
+impl Backtest {
+    pub fn new(config: BacktestConfig, strategies: Vec<Box<dyn Strategy>>) -> Self {
+        Self {
+            config,
+            strategies,
+            indicators: IndicatorsConfig::default(),
+        }
+    }
+
+    /// Sets indicators calculated for the strategies
+    pub fn with_indicators(mut self, indicators: IndicatorsConfig) -> Self {
+        self.indicators = indicators;
+        self
+    }
+
+    /// Runs the backtest over the recording in the directory
+    pub fn run_recording(self, dir: &Path) -> Result<BacktestReport, BacktestError> {
+        let recording = read_recording(dir).map_err(BacktestError::with_source)?;
+
+        self.run(recording.filter_map(|recorded| match recorded {
+            Ok(recorded) => Some(recorded),
+            Err(e) => {
+                warn!("Skipping unreadable recorded event: {e}");
+                None
+            }
+        }))
+    }
+
+    /// Runs the backtest over the events
+    pub fn run(
+        self,
+        events: impl IntoIterator<Item = RecordedEvent>,
+    ) -> Result<BacktestReport, BacktestError> {
+        let Backtest {
+            config,
+            mut strategies,
+            indicators,
+        } = self;
+        let (exchange_tx, exchange_rx) = spsc_queue::make(ORDER_QUEUE_LEN);
+        let (audit_tx, audit_rx) = spsc_queue::make(AUDIT_QUEUE_LEN);
+        let mut router = OrderRouter::new(exchange_tx);
+        let mut books = Orderbooks::new(BookEvents::Delta);
+        let mut indicators = Indicators::new(indicators);
+        let mut sim = SimulatedExchange::new(&config);
+        let mut execution: Option<(Box<str>, Ledger)> = None;
+        let mut next_timer: Option<SystemTime> = None;
+        let (mut n_events, mut n_orders) = (0, 0);
+
+        for RecordedEvent { exchange, event } in events {
+            let now = event.timestamp;
+            n_events += 1;
+
+            let (execution_exchange, ledger) = match &mut execution {
+                Some(execution) => execution,
+                None => {
+                    let name = config.exchange.clone().unwrap_or_else(|| exchange.clone());
+                    let id = name.parse().map_err(BacktestError::convert_error)?;
+                    info!("Backtesting with execution on {name}");
+                    execution.insert((name, Ledger::new(id)))
+                }
+            };
+
+            let executed = exchange == *execution_exchange;
+            let mut exchange_events = sim.advance(now);
+            if executed {
+                exchange_events.extend(sim.on_market_event(&event));
+            }
+            for exchange_event in exchange_events {
+                process_exchange_event(
+                    &mut strategies,
+                    &mut router,
+                    &audit_tx,
+                    ledger,
+                    exchange_event,
+                );
+            }
+            // Positions are marked after the fills the trades caused
+            if let (true, MarketEventType::Trades(market, trades)) = (executed, &event.r#type) {
+                if let Some(trade) = trades.last() {
+                    ledger.mark(market, trade.price);
+                }
+            }
+
+            let book = books.on_event(&exchange, &event);
+            let market_indicators = indicators.on_event(&exchange, &event);
+            run_strategies(
+                &mut strategies,
+                &mut router,
+                &audit_tx,
+                &event,
+                book,
+                market_indicators,
+                None,
+            );
+
+            let timer = next_timer.get_or_insert(now + STRATEGY_TIMER_INTERVAL);
+            if now >= *timer {
+                for (idx, strategy) in strategies.iter_mut().enumerate() {
+                    let intents = strategy.on_timer(DateTime::<Utc>::from(now));
+                    process_intents(&mut router, &audit_tx, idx, strategy.name(), intents, None);
+                }
+                *timer = now + STRATEGY_TIMER_INTERVAL;
+            }
+
+            while let Some(request) = exchange_rx.try_pop() {
+                if matches!(request, ExchangeRequest::PlaceOrder(_)) {
+                    n_orders += 1;
+                }
+                sim.submit(request, now);
+            }
+            while let Some(event) = audit_rx.try_pop() {
+                trace!("audit = {event:?}");
+            }
+        }
+
+        let (_, ledger) = execution
+            .ok_or_else(|| BacktestError::convert_error("no events recorded".to_string()))?;
+
+        Ok(ledger.report(n_events, n_orders))
+    }
+}
+
+/// Passes order updates to the strategies that placed the orders and
+/// positions changed by fills to all strategies
+fn process_exchange_event(
+    strategies: &mut [Box<dyn Strategy>],
+    router: &mut OrderRouter,
+    audit_tx: &crate::audit::AuditProducer,
+    ledger: &mut Ledger,
+    event: ExchangeEvent,
+) {
+    match event {
+        ExchangeEvent::Order(update) => {
+            if let Some(idx) = router.owner(&update) {
+                let strategy = &mut strategies[idx];
+                let intents = strategy.on_order_update(&update);
+                process_intents(router, audit_tx, idx, strategy.name(), intents, None);
+            }
+        }
+        ExchangeEvent::Fill(fill) => {
+            let position = ledger.on_fill(fill);
+            for (idx, strategy) in strategies.iter_mut().enumerate() {
+                let intents = strategy.on_position(&position);
+                process_intents(router, audit_tx, idx, strategy.name(), intents, None);
+            }
+        }
+        ExchangeEvent::BalanceChange => {}
+    }
+}
+
+/// Backtest error
+#[derive(Debug, thiserror::Error)]
+#[error("Backtest error: {source}")]
+pub struct BacktestError {
+    pub source: Box<dyn std::error::Error>,
+}
+
+impl BacktestError {
+    pub fn with_source(err: impl std::error::Error + 'static) -> Self {
+        Self {
+            source: Box::new(err),
+        }
+    }
+
+    pub fn convert_error(err: String) -> Self {
+        Self { source: err.into() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        order_engine::OrderUpdate,
+        position::Position,
+        trading::strategy::{OrderIntent, Side},
+    };
+    use botvana::market::trade::Trade;
+
+    /// Buys once at the first book and sells once the position is open
+    #[derive(Default)]
+    struct RoundTrip {
+        bought: bool,
+        sold: bool,
+        updates: usize,
+    }
+
+    impl Strategy for RoundTrip {
+        fn name(&self) -> &str {
+            "round-trip"
+        }
+
+        fn on_orderbook(
+            &mut self,
+            market: &str,
+            _orderbook: &PlainOrderbook<f64>,
+        ) -> Vec<OrderIntent> {
+            match std::mem::replace(&mut self.bought, true) {
+                false => vec![OrderIntent::market(market, Side::Buy, 1.0)],
+                true => Vec::new(),
+            }
+        }
+
+        fn on_order_update(&mut self, _update: &OrderUpdate) -> Vec<OrderIntent> {
+            self.updates += 1;
+            Vec::new()
+        }
+
+        fn on_position(&mut self, position: &Position) -> Vec<OrderIntent> {
+            match position.size > 0.0 && !std::mem::replace(&mut self.sold, true) {
+                true => vec![OrderIntent::limit(&position.market, Side::Sell, 110.0, 1.0)],
+                false => Vec::new(),
+            }
+        }
+    }
+
+    fn recorded(ms: u64, r#type: MarketEventType) -> RecordedEvent {
+        let mut event = MarketEvent::new(r#type);
+        event.timestamp = SystemTime::UNIX_EPOCH + Duration::from_millis(ms);
+
+        RecordedEvent {
+            exchange: Box::from("ftx"),
+            event,
+        }
+    }
+
+    fn book(bid: f64, ask: f64) -> MarketEventType {
+        let mut book = PlainOrderbook::new();
+        book.bids = PriceLevelsVec::from_tuples_vec(&[(bid, 5.0)]);
+        book.asks = PriceLevelsVec::from_tuples_vec(&[(ask, 5.0)]);
+
+        MarketEventType::OrderbookUpdate(Box::from("BTC-PERP"), Box::new(book))
+    }
+
+    #[test]
+    fn test_backtest_round_trip() {
+        let config = BacktestConfig {
+            latency_ms: 10,
+            maker_fee_bps: 0.0,
+            taker_fee_bps: 0.0,
+            ..BacktestConfig::default()
+        };
+        let trade = Trade::new(111.0, 1.0, Utc::now());
+        let events = vec![
+            recorded(0, book(99.0, 100.0)),
+            // Buy reaches the exchange
+            recorded(20, book(99.0, 100.0)),
+            // Sell reaches the exchange and rests
+            recorded(40, book(104.0, 105.0)),
+            recorded(
+                60,
+                MarketEventType::Trades(Box::from("BTC-PERP"), Box::new([trade])),
+            ),
+        ];
+
+        let report = Backtest::new(config, vec![Box::new(RoundTrip::default())])
+            .run(events)
+            .unwrap();
+
+        assert_eq!(ExchangeId::Ftx, report.exchange);
+        assert_eq!((4, 2), (report.events, report.orders));
+        assert_eq!(
+            vec![(Side::Buy, 100.0), (Side::Sell, 110.0)],
+            report
+                .fills
+                .iter()
+                .map(|fill| (fill.side, fill.price))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(10.0, report.realized_pnl);
+        assert_eq!(10.0, report.pnl);
+        assert_eq!(0.0, report.max_drawdown);
+    }
+
+    #[test]
+    fn test_backtest_without_events() {
+        let report = Backtest::new(BacktestConfig::default(), Vec::new()).run(Vec::new());
+
+        assert!(report.is_err());
+    }
+}
@@ -0,0 +1,409 @@
+//! Simulated exchange
+//!
+//! Requests reach the exchange after the configured latency. Market orders
+//! and the crossing part of limit orders take liquidity from the recorded
+//! book, walking it level by level, the rest of a market order is cancelled.
+//! Limit orders rest until the book crosses their price or a trade happens
+//! through it, they are then filled at their price as makers. Taken
+//! liquidity is removed from the book until the next recorded update.
+
+use std::{collections::VecDeque, time::SystemTime};
+
+use botvana::market::trade::Trade;
+
+use super::BacktestConfig;
+use crate::{
+    order_engine::{
+        matching::{crosses, fill, take},
+        AmendRequest, CancelRequest, ExchangeEvent, ExchangeRequest, OrderRequest, OrderStatus,
+        OrderUpdate,
+    },
+    prelude::*,
+    trading::strategy::Side,
+};
+
+/// Exchange filling orders against recorded market data
+#[derive(Debug)]
+pub struct SimulatedExchange {
+    latency: Duration,
+    /// Fees as fraction of the filled notional
+    maker_fee: f64,
+    taker_fee: f64,
+    /// Requests with the time they reach the exchange
+    in_flight: VecDeque<(SystemTime, ExchangeRequest)>,
+    books: HashMap<Box<str>, PlainOrderbook<f64>>,
+    /// Resting orders in the order they were placed
+    orders: Vec<OrderUpdate>,
+    next_id: u64,
+}
+
+impl SimulatedExchange {
+    pub fn new(config: &BacktestConfig) -> Self {
+        Self {
+            latency: config.latency(),
+            maker_fee: config.maker_fee_bps / 10_000.0,
+            taker_fee: config.taker_fee_bps / 10_000.0,
+            in_flight: VecDeque::new(),
+            books: HashMap::new(),
+            orders: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Returns the book of the market as currently simulated
+    pub fn book(&self, market: &str) -> Option<&PlainOrderbook<f64>> {
+        self.books.get(market)
+    }
+
+    /// Returns orders resting on the exchange
+    pub fn open_orders(&self) -> &[OrderUpdate] {
+        &self.orders
+    }
+
+    /// Sends the request, it's acted on once the latency passed
+    pub fn submit(&mut self, request: ExchangeRequest, now: SystemTime) {
+        self.in_flight.push_back((now + self.latency, request));
+    }
+
+    /// Acts on requests that reached the exchange by `now`
+    pub fn advance(&mut self, now: SystemTime) -> Vec<ExchangeEvent> {
+        let mut events = Vec::new();
+
+        while matches!(self.in_flight.front(), Some((arrival, _)) if *arrival <= now) {
+            if let Some((arrival, request)) = self.in_flight.pop_front() {
+                self.process_request(request, arrival, &mut events);
+            }
+        }
+
+        events
+    }
+
+    /// Updates the book of the event's market, returns the fills of resting
+    /// orders it caused
+    pub fn on_market_event(&mut self, event: &MarketEvent) -> Vec<ExchangeEvent> {
+        let mut events = Vec::new();
+        let market = match &event.r#type {
+            MarketEventType::OrderbookUpdate(market, orderbook) => {
+                self.books.insert(market.clone(), (**orderbook).clone());
+                market
+            }
+            MarketEventType::OrderbookDelta(market, delta) => match self.books.get_mut(market) {
+                Some(book) => {
+                    book.apply_delta(delta);
+                    market
+                }
+                None => return events,
+            },
+            MarketEventType::BboUpdate(market, bbo) => {
+                let mut book = PlainOrderbook::new();
+                book.bids = PriceLevelsVec::from_tuples_vec(&[(bbo.bid, bbo.bid_size)]);
+                book.asks = PriceLevelsVec::from_tuples_vec(&[(bbo.ask, bbo.ask_size)]);
+                self.books.insert(market.clone(), book);
+                market
+            }
+            MarketEventType::Trades(market, trades) => {
+                let time = to_millis(event.timestamp);
+                for trade in trades.iter() {
+                    self.fill_through(market, trade, time, &mut events);
+                }
+                return events;
+            }
+            _ => return events,
+        };
+
+        self.fill_resting(market, to_millis(event.timestamp), &mut events);
+
+        events
+    }
+
+    fn process_request(
+        &mut self,
+        request: ExchangeRequest,
+        time: SystemTime,
+        events: &mut Vec<ExchangeEvent>,
+    ) {
+        let time = to_millis(time);
+
+        match request {
+            ExchangeRequest::PlaceOrder(order) => self.place(&order, time, events),
+            ExchangeRequest::CancelOrder(CancelRequest { client_id, .. }) => {
+                self.cancel(|order| order.client_id == client_id, events)
+            }
+            ExchangeRequest::CancelAll(market) => self.cancel(
+                |order| {
+                    market
+                        .as_ref()
+                        .map_or(true, |market| order.market == *market)
+                },
+                events,
+            ),
+            ExchangeRequest::AmendOrder(amend) => self.amend(&amend, time, events),
+        }
+    }
+
+    fn place(&mut self, request: &OrderRequest, time: u64, events: &mut Vec<ExchangeEvent>) {
+        self.next_id += 1;
+        let mut order = OrderUpdate {
+            client_id: request.client_id.clone(),
+            exchange_id: Some(format!("sim-{}", self.next_id).into_boxed_str()),
+            market: request.market.clone(),
+            side: request.side,
+            price: request.price,
+            size: request.size,
+            filled_size: 0.0,
+            status: OrderStatus::Open,
+            reason: None,
+        };
+
+        if self.orders.iter().any(|o| o.client_id == order.client_id) {
+            order.exchange_id = None;
+            order.status = OrderStatus::Rejected;
+            order.reason = Some("duplicate client id".to_string());
+            events.push(ExchangeEvent::Order(order));
+            return;
+        }
+        let book = match self.books.get_mut(&*order.market) {
+            Some(book) => book,
+            None if order.price.is_none() => {
+                order.status = OrderStatus::Rejected;
+                order.reason = Some("no book to fill market order against".to_string());
+                events.push(ExchangeEvent::Order(order));
+                return;
+            }
+            None => {
+                events.push(ExchangeEvent::Order(order.clone()));
+                self.orders.push(order);
+                return;
+            }
+        };
+        if request.post_only && crosses(book, order.side, order.price) {
+            order.status = OrderStatus::Rejected;
+            order.reason = Some("post only order would take liquidity".to_string());
+            events.push(ExchangeEvent::Order(order));
+            return;
+        }
+
+        for (price, size) in take(book, order.side, order.price, order.size) {
+            events.push(fill(&mut order, price, size, self.taker_fee, time));
+        }
+
+        if order.status != OrderStatus::Filled && order.price.is_none() {
+            order.status = OrderStatus::Cancelled;
+            order.reason = Some("market order not fully filled".to_string());
+        }
+        events.push(ExchangeEvent::Order(order.clone()));
+        if !order.status.is_closed() {
+            self.orders.push(order);
+        }
+    }
+
+    fn cancel(
+        &mut self,
+        mut matches: impl FnMut(&OrderUpdate) -> bool,
+        events: &mut Vec<ExchangeEvent>,
+    ) {
+        let mut idx = 0;
+        while idx < self.orders.len() {
+            if matches(&self.orders[idx]) {
+                let mut order = self.orders.remove(idx);
+                order.status = OrderStatus::Cancelled;
+                events.push(ExchangeEvent::Order(order));
+            } else {
+                idx += 1;
+            }
+        }
+    }
+
+    fn amend(&mut self, amend: &AmendRequest, time: u64, events: &mut Vec<ExchangeEvent>) {
+        let order = match self
+            .orders
+            .iter_mut()
+            .find(|order| order.client_id == amend.client_id)
+        {
+            Some(order) => order,
+            None => {
+                warn!("Amend of unknown order {}", amend.client_id);
+                return;
+            }
+        };
+        if let Some(price) = amend.price {
+            order.price = Some(price);
+        }
+        if let Some(size) = amend.size {
+            order.size = size.max(order.filled_size);
+        }
+        if order.remaining_size() == 0.0 {
+            order.status = OrderStatus::Filled;
+        }
+        events.push(ExchangeEvent::Order(order.clone()));
+
+        let market = order.market.clone();
+        self.orders.retain(|order| !order.status.is_closed());
+        self.fill_resting(&market, time, events);
+    }
+
+    /// Fills resting orders of the market crossed by its book
+    fn fill_resting(&mut self, market: &str, time: u64, events: &mut Vec<ExchangeEvent>) {
+        let book = match self.books.get_mut(market) {
+            Some(book) => book,
+            None => return,
+        };
+        let mut orders = std::mem::take(&mut self.orders);
+
+        for order in orders.iter_mut().filter(|order| &*order.market == market) {
+            let size: f64 = take(book, order.side, order.price, order.remaining_size())
+                .iter()
+                .map(|(_, size)| size)
+                .sum();
+            if size > 0.0 {
+                let price = order.price.unwrap_or_default();
+                events.push(fill(order, price, size, self.maker_fee, time));
+                events.push(ExchangeEvent::Order(order.clone()));
+            }
+        }
+
+        orders.retain(|order| !order.status.is_closed());
+        self.orders = orders;
+    }
+
+    /// Fills resting orders of the market the trade happened through
+    fn fill_through(
+        &mut self,
+        market: &str,
+        trade: &Trade,
+        time: u64,
+        events: &mut Vec<ExchangeEvent>,
+    ) {
+        let mut available = trade.size;
+
+        for order in self
+            .orders
+            .iter_mut()
+            .filter(|order| &*order.market == market)
+        {
+            let through = match (order.side, order.price) {
+                (Side::Buy, Some(price)) => trade.price < price,
+                (Side::Sell, Some(price)) => trade.price > price,
+                (_, None) => false,
+            };
+            if !through || available <= 0.0 {
+                continue;
+            }
+
+            let size = order.remaining_size().min(available);
+            available -= size;
+            let price = order.price.unwrap_or_default();
+            events.push(fill(order, price, size, self.maker_fee, time));
+            events.push(ExchangeEvent::Order(order.clone()));
+        }
+
+        self.orders.retain(|order| !order.status.is_closed());
+    }
+}
+
+fn to_millis(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book() -> PlainOrderbook<f64> {
+        let mut book = PlainOrderbook::new();
+        book.bids = PriceLevelsVec::from_tuples_vec(&[(98.0, 1.0), (99.0, 1.0)]);
+        book.asks = PriceLevelsVec::from_tuples_vec(&[(101.0, 1.0), (102.0, 2.0)]);
+        book
+    }
+
+    fn exchange() -> SimulatedExchange {
+        let mut exchange = SimulatedExchange::new(&BacktestConfig {
+            latency_ms: 10,
+            maker_fee_bps: 0.0,
+            taker_fee_bps: 10.0,
+            ..BacktestConfig::default()
+        });
+        exchange.on_market_event(&MarketEvent::orderbook_update(
+            Box::from("BTC/USD"),
+            Box::new(book()),
+        ));
+        exchange
+    }
+
+    fn fills(events: &[ExchangeEvent]) -> Vec<(f64, f64)> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                ExchangeEvent::Fill(fill) => Some((fill.price, fill.size)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_market_order_walks_book_after_latency() {
+        let mut exchange = exchange();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+        let order = OrderRequest::market("1", "BTC/USD", Side::Buy, 2.5);
+        exchange.submit(ExchangeRequest::PlaceOrder(order), now);
+
+        assert!(exchange.advance(now).is_empty());
+
+        let events = exchange.advance(now + Duration::from_millis(10));
+        assert_eq!(vec![(101.0, 1.0), (102.0, 1.5)], fills(&events));
+        match &events[..] {
+            [ExchangeEvent::Fill(fill), _, ExchangeEvent::Order(order)] => {
+                assert!((fill.fee - 0.101).abs() < 1e-9);
+                assert_eq!(OrderStatus::Filled, order.status);
+            }
+            other => panic!("unexpected events {other:?}"),
+        }
+        assert_eq!(vec![0.5], exchange.book("BTC/USD").unwrap().asks.size_vec);
+    }
+
+    #[test]
+    fn test_limit_order_rests_until_crossed() {
+        let mut exchange = exchange();
+        let now = SystemTime::UNIX_EPOCH;
+        let order = OrderRequest::limit("1", "BTC/USD", Side::Buy, 100.0, 1.0);
+        exchange.submit(ExchangeRequest::PlaceOrder(order), now);
+
+        let events = exchange.advance(now + Duration::from_secs(1));
+        assert!(fills(&events).is_empty());
+        assert_eq!(1, exchange.open_orders().len());
+
+        // Trade through the price fills up to the traded size
+        let trades = MarketEvent::trades(
+            Box::from("BTC/USD"),
+            Box::new([Trade::new(99.5, 0.25, Utc::now())]),
+        );
+        assert_eq!(
+            vec![(100.0, 0.25)],
+            fills(&exchange.on_market_event(&trades))
+        );
+
+        // Book moving through the price fills the rest at the order price
+        let mut crossed = book();
+        crossed.asks = PriceLevelsVec::from_tuples_vec(&[(99.5, 5.0)]);
+        let events = exchange.on_market_event(&MarketEvent::orderbook_update(
+            Box::from("BTC/USD"),
+            Box::new(crossed),
+        ));
+        assert_eq!(vec![(100.0, 0.75)], fills(&events));
+        assert!(exchange.open_orders().is_empty());
+    }
+
+    #[test]
+    fn test_post_only_rejected_when_crossing() {
+        let mut exchange = exchange();
+        let order = OrderRequest::limit("1", "BTC/USD", Side::Sell, 99.0, 1.0).post_only();
+        exchange.submit(ExchangeRequest::PlaceOrder(order), SystemTime::UNIX_EPOCH);
+
+        match &exchange.advance(SystemTime::now())[..] {
+            [ExchangeEvent::Order(order)] => assert_eq!(OrderStatus::Rejected, order.status),
+            other => panic!("unexpected events {other:?}"),
+        }
+    }
+}
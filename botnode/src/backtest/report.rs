@@ -0,0 +1,182 @@
+//! Backtest results
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::{order_engine::Fill, position::Position, prelude::*, status::PositionSummary};
+
+/// Results of a backtest
+#[derive(Clone, Debug, Serialize)]
+pub struct BacktestReport {
+    /// Exchange the orders were executed on
+    pub exchange: ExchangeId,
+    /// Number of replayed market events
+    pub events: u64,
+    /// Number of orders placed by the strategies
+    pub orders: u64,
+    pub fills: Vec<Fill>,
+    /// Fees paid, included in the realized P&L
+    pub fees: f64,
+    pub realized_pnl: f64,
+    /// P&L of the positions left open, at the last traded prices
+    pub unrealized_pnl: f64,
+    pub pnl: f64,
+    /// Largest drop of the P&L from its previous peak
+    pub max_drawdown: f64,
+    pub positions: Vec<PositionSummary>,
+}
+
+impl std::fmt::Display for BacktestReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "exchange = {}", self.exchange)?;
+        writeln!(f, "events = {}", self.events)?;
+        writeln!(f, "orders = {}", self.orders)?;
+        writeln!(f, "fills = {}", self.fills.len())?;
+        writeln!(f, "fees = {:.4}", self.fees)?;
+        writeln!(f, "realized_pnl = {:.4}", self.realized_pnl)?;
+        writeln!(f, "unrealized_pnl = {:.4}", self.unrealized_pnl)?;
+        writeln!(f, "pnl = {:.4}", self.pnl)?;
+        write!(f, "max_drawdown = {:.4}", self.max_drawdown)?;
+        for position in &self.positions {
+            write!(
+                f,
+                "\n{} size = {} avg_entry_price = {:.4} realized_pnl = {:.4} unrealized_pnl = {:.4}",
+                position.market,
+                position.size,
+                position.avg_entry_price,
+                position.realized_pnl,
+                position.unrealized_pnl
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Positions and P&L built from the fills of a backtest
+#[derive(Debug)]
+pub struct Ledger {
+    exchange: ExchangeId,
+    positions: BTreeMap<Box<str>, Position>,
+    fills: Vec<Fill>,
+    fees: f64,
+    /// Highest P&L so far
+    peak: f64,
+    max_drawdown: f64,
+}
+
+impl Ledger {
+    pub fn new(exchange: ExchangeId) -> Self {
+        Self {
+            exchange,
+            positions: BTreeMap::new(),
+            fills: Vec::new(),
+            fees: 0.0,
+            peak: 0.0,
+            max_drawdown: 0.0,
+        }
+    }
+
+    /// Applies the fill to the position of its market, returns the position
+    pub fn on_fill(&mut self, fill: Fill) -> Position {
+        let exchange = self.exchange;
+        let position = self
+            .positions
+            .entry(fill.market.clone())
+            .or_insert_with(|| Position::new(exchange, &fill.market));
+        position.apply_fill(fill.side, fill.price, fill.size, fill.fee);
+        // Unmarked positions are valued at the last fill
+        position.mark_price.get_or_insert(fill.price);
+        let position = position.clone();
+
+        self.fees += fill.fee;
+        self.fills.push(fill);
+        self.update_drawdown();
+
+        position
+    }
+
+    /// Marks the position of the market to the price
+    pub fn mark(&mut self, market: &str, price: f64) {
+        if let Some(position) = self.positions.get_mut(market) {
+            position.mark_price = Some(price);
+            self.update_drawdown();
+        }
+    }
+
+    /// Returns realized and unrealized P&L summed over all positions
+    pub fn pnl(&self) -> (f64, f64) {
+        self.positions
+            .values()
+            .fold((0.0, 0.0), |(realized, unrealized), position| {
+                (
+                    realized + position.realized_pnl,
+                    unrealized + position.unrealized_pnl(),
+                )
+            })
+    }
+
+    pub fn report(self, events: u64, orders: u64) -> BacktestReport {
+        let (realized_pnl, unrealized_pnl) = self.pnl();
+
+        BacktestReport {
+            exchange: self.exchange,
+            events,
+            orders,
+            fees: self.fees,
+            realized_pnl,
+            unrealized_pnl,
+            pnl: realized_pnl + unrealized_pnl,
+            max_drawdown: self.max_drawdown,
+            positions: self.positions.values().map(Position::summary).collect(),
+            fills: self.fills,
+        }
+    }
+
+    fn update_drawdown(&mut self) {
+        let (realized, unrealized) = self.pnl();
+        let pnl = realized + unrealized;
+
+        self.peak = self.peak.max(pnl);
+        self.max_drawdown = self.max_drawdown.max(self.peak - pnl);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trading::strategy::Side;
+
+    fn fill(side: Side, price: f64, size: f64) -> Fill {
+        Fill {
+            client_id: None,
+            exchange_id: Box::from("sim-1"),
+            market: Box::from("BTC-PERP"),
+            side,
+            price,
+            size,
+            fee: 0.5,
+            time: 0,
+        }
+    }
+
+    #[test]
+    fn test_ledger_pnl_and_drawdown() {
+        let mut ledger = Ledger::new(ExchangeId::Ftx);
+
+        ledger.on_fill(fill(Side::Buy, 100.0, 1.0));
+        ledger.mark("BTC-PERP", 110.0);
+        ledger.mark("BTC-PERP", 95.0);
+        let position = ledger.on_fill(fill(Side::Sell, 105.0, 1.0));
+        assert_eq!(0.0, position.size);
+
+        let report = ledger.report(4, 2);
+        assert_eq!(2, report.fills.len());
+        assert_eq!(1.0, report.fees);
+        assert_eq!(4.0, report.realized_pnl);
+        assert_eq!(4.0, report.pnl);
+        // Peak of 9.5 at 110, trough of -5.5 at 95
+        assert_eq!(15.0, report.max_drawdown);
+    }
+}
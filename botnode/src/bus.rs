@@ -0,0 +1,266 @@
+//! Typed publish/subscribe bus between engines
+//!
+//! Every [`Topic`] carries one type of message from a single publisher to
+//! any number of subscribers, each over its own ring channel. Engines
+//! subscribe to the topics they need while the control engine wires them up,
+//! the publisher takes the subscriptions made until then with
+//! [`Bus::publisher`] or publishes through the bus it owns with
+//! [`Bus::publish`].
+//!
+//! Lossless subscribers get every message, the publisher retries while their
+//! channel is full. Lossy subscribers drop messages they don't keep up with,
+//! the drops are counted in `bus_dropped_total`.
+
+use std::any::{Any, TypeId};
+
+use crate::{
+    control::ControlCommand, metrics::MetricLabels, order_engine::ExchangeEvent, prelude::*,
+};
+
+/// Attempts to push onto full lossless channel before giving up
+const FAIL_LIMIT: usize = 100;
+
+/// Type of messages published on the bus
+pub trait Topic: 'static {
+    type Message: Clone + std::fmt::Debug + Send + 'static;
+
+    /// Name the topic is reported under
+    const NAME: &'static str;
+
+    /// Capacity of every subscriber's channel
+    const CAPACITY: usize = 1024;
+}
+
+/// Market events of all exchanges
+pub struct MarketEvents;
+
+impl Topic for MarketEvents {
+    type Message = MarketEvent;
+    const NAME: &'static str = "market_events";
+}
+
+/// Order updates and fills from the order engine
+pub struct OrderEvents;
+
+impl Topic for OrderEvents {
+    type Message = ExchangeEvent;
+    const NAME: &'static str = "order_events";
+}
+
+/// Commands from botvana-server relayed by the control engine
+pub struct ControlCommands;
+
+impl Topic for ControlCommands {
+    type Message = ControlCommand;
+    const NAME: &'static str = "control_commands";
+    const CAPACITY: usize = 64;
+}
+
+/// What happens to messages a subscriber doesn't keep up with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    /// Messages are dropped while the channel is full
+    Lossy,
+    /// Publisher retries until the message fits
+    Lossless,
+}
+
+/// Bus holding the subscriptions of all topics
+#[derive(Default)]
+pub struct Bus {
+    topics: HashMap<TypeId, Box<dyn Any + Send>>,
+}
+
+impl Bus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribes to the topic
+    ///
+    /// Fails once the publisher of the topic was taken.
+    pub fn subscribe<T: Topic>(
+        &mut self,
+        delivery: Delivery,
+    ) -> Result<Subscriber<T::Message>, BusError> {
+        let publisher = self
+            .topic::<T>()
+            .as_mut()
+            .ok_or(BusError { topic: T::NAME })?;
+
+        let (tx, rx) = spsc_queue::make(T::CAPACITY);
+        publisher.subscribers.push((delivery, tx));
+
+        Ok(Subscriber { rx })
+    }
+
+    /// Takes the publisher of the topic with all subscriptions made so far
+    pub fn publisher<T: Topic>(&mut self) -> Result<Publisher<T>, BusError> {
+        self.topic::<T>().take().ok_or(BusError { topic: T::NAME })
+    }
+
+    /// Publishes message on the topic to all its subscribers
+    pub fn publish<T: Topic>(&mut self, msg: T::Message) -> Result<(), PublishError> {
+        match self.topic::<T>() {
+            Some(publisher) => publisher.publish(msg),
+            None => Err(PublishError {
+                topic: T::NAME,
+                failed: 0,
+            }),
+        }
+    }
+
+    /// Returns the publisher of the topic, `None` once it was taken
+    fn topic<T: Topic>(&mut self) -> &mut Option<Publisher<T>> {
+        self.topics
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Some(Publisher::<T>::default())))
+            .downcast_mut()
+            .expect("topic stored under its type id")
+    }
+}
+
+/// Publishing side of a topic
+pub struct Publisher<T: Topic> {
+    subscribers: Vec<(Delivery, spsc_queue::Producer<T::Message>)>,
+}
+
+impl<T: Topic> Default for Publisher<T> {
+    fn default() -> Self {
+        Self {
+            subscribers: Vec::new(),
+        }
+    }
+}
+
+impl<T: Topic> Publisher<T> {
+    /// Returns the number of subscribers
+    pub fn subscribers(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    /// Pushes message onto the channels of all subscribers
+    ///
+    /// Returns `Ok` when every lossless subscriber got the message, drops
+    /// of lossy subscribers are only counted.
+    pub fn publish(&self, msg: T::Message) -> Result<(), PublishError> {
+        let mut failed = 0;
+
+        for (delivery, tx) in self.subscribers.iter() {
+            let mut res = tx.try_push(msg.clone());
+
+            if *delivery == Delivery::Lossless {
+                let mut fail_cnt = 0;
+                while let Some(value) = res {
+                    if fail_cnt >= FAIL_LIMIT || tx.consumer_disconnected() {
+                        res = Some(value);
+                        break;
+                    }
+                    fail_cnt += 1;
+                    res = tx.try_push(value);
+                }
+            }
+
+            if res.is_some() {
+                crate::metrics::registry().incr_counter(
+                    "bus_dropped_total",
+                    &MetricLabels::new().with("topic", T::NAME),
+                    1,
+                );
+                if *delivery == Delivery::Lossless {
+                    failed += 1;
+                }
+            }
+        }
+
+        match failed {
+            0 => Ok(()),
+            failed => Err(PublishError {
+                topic: T::NAME,
+                failed,
+            }),
+        }
+    }
+}
+
+/// Receiving side of a subscription
+pub struct Subscriber<M> {
+    rx: spsc_queue::Consumer<M>,
+}
+
+impl<M> Subscriber<M> {
+    /// Returns the next message, if any
+    pub fn try_recv(&self) -> Option<M> {
+        self.rx.try_pop()
+    }
+}
+
+impl<M> Default for Subscriber<M> {
+    /// Returns subscriber that never receives a message
+    fn default() -> Self {
+        Self {
+            rx: spsc_queue::make(1).1,
+        }
+    }
+}
+
+/// Subscribing to a topic whose publisher was already taken
+#[derive(Debug, thiserror::Error)]
+#[error("Publisher of {topic} already taken")]
+pub struct BusError {
+    pub topic: &'static str,
+}
+
+/// Message didn't reach all lossless subscribers
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to publish on {topic} to {failed} subscribers")]
+pub struct PublishError {
+    pub topic: &'static str,
+    pub failed: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Tick(u32);
+
+    struct Ticks;
+
+    impl Topic for Ticks {
+        type Message = Tick;
+        const NAME: &'static str = "ticks";
+        const CAPACITY: usize = 1;
+    }
+
+    #[test]
+    fn test_bus_publish() {
+        let mut bus = Bus::new();
+        let lossless = bus.subscribe::<Ticks>(Delivery::Lossless).unwrap();
+        let lossy = bus.subscribe::<Ticks>(Delivery::Lossy).unwrap();
+
+        bus.publish::<Ticks>(Tick(1)).unwrap();
+        assert_eq!(Some(Tick(1)), lossy.try_recv());
+
+        // Lossy subscriber has room again, the lossless one doesn't
+        assert!(bus.publish::<Ticks>(Tick(2)).is_err());
+        assert_eq!(Some(Tick(2)), lossy.try_recv());
+        assert_eq!(Some(Tick(1)), lossless.try_recv());
+        assert_eq!(None, lossless.try_recv());
+    }
+
+    #[test]
+    fn test_bus_publisher_taken() {
+        let mut bus = Bus::new();
+        let subscriber = bus.subscribe::<Ticks>(Delivery::Lossy).unwrap();
+
+        let publisher = bus.publisher::<Ticks>().unwrap();
+        assert_eq!(1, publisher.subscribers());
+        assert!(bus.subscribe::<Ticks>(Delivery::Lossy).is_err());
+        assert!(bus.publisher::<Ticks>().is_err());
+
+        publisher.publish(Tick(1)).unwrap();
+        assert_eq!(Some(Tick(1)), subscriber.try_recv());
+    }
+}
@@ -1,12 +1,26 @@
-use std::hash::Hash;
+use std::{cell::Cell, hash::Hash, time::Instant};
+
+use serde::Deserialize;
+
+use botvana::net::msg::AlertKind;
 
 use crate::prelude::*;
 
 const FAIL_LIMIT: usize = 100;
 
+/// Default channel occupancy above which the consumer is considered slow
+pub const DEFAULT_OCCUPANCY_THRESHOLD: f64 = 0.8;
+
+/// Default time the occupancy has to stay above the threshold before alerting
+pub const DEFAULT_OCCUPANCY_DURATION: Duration = Duration::from_secs(1);
+
 /// Array of producers for inter-engine channel
 #[derive(Debug)]
-pub struct ProducersArray<T, const N: usize>(pub(super) ArrayVec<spsc_queue::Producer<T>, N>);
+pub struct ProducersArray<T, const N: usize>(
+    pub(super) ArrayVec<spsc_queue::Producer<T>, N>,
+    /// Values dropped for each consumer since the producers were created
+    Cell<[u64; N]>,
+);
 
 impl<T, const N: usize> ProducersArray<T, N>
 where
@@ -14,8 +28,19 @@ where
 {
     /// Pushes value onto all data transmitters
     ///
-    /// Returns `Ok` only when the value was pushed onto all producers.
+    /// Returns `Ok` unless a consumer disconnected, see
+    /// [`ProducersArray::push_ref`].
     pub(crate) fn push_value(&self, event: T) -> Result<(), PushValueError<N>> {
+        self.push_ref(&event)
+    }
+
+    /// Pushes copies of the value onto all data transmitters, leaving the
+    /// value with the caller
+    ///
+    /// Consumers whose channel stays full lose the value, it's counted in
+    /// [`ProducersArray::dropped`]. Returns `Ok` unless a consumer
+    /// disconnected.
+    pub(crate) fn push_ref(&self, event: &T) -> Result<(), PushValueError<N>> {
         let mut err = PushValueError::<N>::default();
 
         self.0.iter().enumerate().for_each(|(idx, tx)| {
@@ -36,9 +61,11 @@ where
                 if fail_cnt > FAIL_LIMIT {
                     if tx.consumer_disconnected() {
                         warn!("Producer {idx} disconnected.");
+                        err.push_failed(idx);
+                    } else {
+                        warn!("Dropped value for lagging consumer {idx} {:?}", tx);
+                        self.count_drop(idx);
                     }
-                    warn!("Failed to push value onto producer {idx} {:?}", tx);
-                    err.push_failed(idx);
                     return;
                 }
                 fail_cnt += 1;
@@ -53,6 +80,283 @@ where
     }
 }
 
+impl<T, const N: usize> ProducersArray<T, N> {
+    /// Returns number of values dropped for each consumer
+    pub fn dropped(&self) -> [u64; N] {
+        self.1.get()
+    }
+
+    fn count_drop(&self, idx: usize) {
+        let mut dropped = self.1.get();
+        dropped[idx] += 1;
+        self.1.set(dropped);
+    }
+
+    /// Returns occupancy of each consumer's channel in range `0.0..=1.0`
+    pub fn occupancy(&self) -> ArrayVec<f64, N> {
+        self.0
+            .iter()
+            .map(|tx| tx.size() as f64 / tx.buffer_size().max(1) as f64)
+            .collect()
+    }
+
+    /// Returns number of values queued in each consumer's channel
+    pub fn depth(&self) -> ArrayVec<usize, N> {
+        self.0.iter().map(|tx| tx.size()).collect()
+    }
+}
+
+/// Escalation of values dropped for lagging consumers
+///
+/// Drops of each consumer are counted over windows of `window_secs`. A
+/// window reaching `alert_threshold` drops raises an alert, reaching
+/// `pause_threshold` also pauses trading until botvana-server resumes it,
+/// strategies would otherwise trade on stale data. Zero thresholds never
+/// escalate.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DropEscalationConfig {
+    pub alert_threshold: u64,
+    pub pause_threshold: u64,
+    pub window_secs: u64,
+}
+
+impl Default for DropEscalationConfig {
+    fn default() -> Self {
+        Self {
+            alert_threshold: 100,
+            pause_threshold: 0,
+            window_secs: 10,
+        }
+    }
+}
+
+impl DropEscalationConfig {
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window_secs)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.window_secs == 0 {
+            return Err("window_secs must be positive".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Action taken on the drops of a consumer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Escalation {
+    Alert(u64),
+    Pause(u64),
+}
+
+/// Drops of a consumer in the current window
+#[derive(Clone, Copy, Debug, Default)]
+struct DropWindow {
+    /// Total drops already observed
+    counted: u64,
+    started: Option<Instant>,
+    dropped: u64,
+    alerted: bool,
+}
+
+/// Detects consumers that are not keeping up with their producer
+///
+/// Alert is raised once a consumer's channel stays above the occupancy
+/// threshold for longer than the configured duration. It is raised again
+/// only after the occupancy drops below the threshold.
+///
+/// Values dropped for the consumers are counted in `channel_dropped_total`
+/// and escalated when configured with [`DropEscalationConfig`].
+#[derive(Debug)]
+pub struct SlowConsumerMonitor<const N: usize> {
+    channel: &'static str,
+    exchange: Option<&'static str>,
+    threshold: f64,
+    duration: Duration,
+    above_since: [Option<Instant>; N],
+    alerted: [bool; N],
+    escalation: Option<DropEscalationConfig>,
+    drops: [DropWindow; N],
+}
+
+impl<const N: usize> SlowConsumerMonitor<N> {
+    pub fn new(channel: &'static str, threshold: f64, duration: Duration) -> Self {
+        Self {
+            channel,
+            exchange: None,
+            threshold,
+            duration,
+            above_since: [None; N],
+            alerted: [false; N],
+            escalation: None,
+            drops: [DropWindow::default(); N],
+        }
+    }
+
+    /// Escalates the values dropped for the consumers
+    pub fn with_drop_escalation(mut self, escalation: DropEscalationConfig) -> Self {
+        self.escalation = Some(escalation);
+        self
+    }
+
+    /// Sets the exchange the channel carries data of
+    pub fn with_exchange(mut self, exchange: &'static str) -> Self {
+        self.exchange = Some(exchange);
+        self
+    }
+
+    fn labels<'a>(&self) -> crate::metrics::MetricLabels<'a> {
+        let labels = crate::metrics::MetricLabels::new().with("channel", self.channel);
+        match self.exchange {
+            Some(exchange) => labels.exchange(exchange),
+            None => labels,
+        }
+    }
+
+    /// Checks occupancy of all consumers of given producers
+    ///
+    /// Publishes the occupancy gauges and raises alert for every consumer
+    /// that became slow, returns indexes of those consumers.
+    pub fn check<T>(&mut self, producers: &ProducersArray<T, N>) -> ArrayVec<usize, N> {
+        let registry = crate::metrics::registry();
+        let mut slow = ArrayVec::new();
+
+        let depths = producers.depth();
+        for (idx, occupancy) in producers.occupancy().into_iter().enumerate() {
+            let consumer = idx.to_string();
+            registry.set_gauge(
+                "channel_occupancy_ratio",
+                &self.labels().with("consumer", &consumer),
+                occupancy,
+            );
+            registry.set_gauge(
+                "channel_depth_messages",
+                &self.labels().with("consumer", &consumer),
+                depths[idx] as f64,
+            );
+
+            if let Some(idx) = self.observe(idx, occupancy, Instant::now()) {
+                crate::alert::raise(
+                    AlertKind::SlowConsumer {
+                        channel: self.channel.to_string(),
+                        consumer: idx,
+                        occupancy,
+                    },
+                    format!(
+                        "consumer {idx} of {} channel above {:.0}% occupancy for {:?}",
+                        self.channel,
+                        self.threshold * 100.0,
+                        self.duration
+                    ),
+                );
+                registry.incr_counter("channel_slow_consumer_alerts_total", &self.labels(), 1);
+                slow.push(idx);
+            }
+        }
+
+        for (idx, dropped) in producers.dropped().into_iter().enumerate() {
+            let new = dropped - self.drops[idx].counted;
+            if new > 0 {
+                registry.incr_counter(
+                    "channel_dropped_total",
+                    &self.labels().with("consumer", &idx.to_string()),
+                    new,
+                );
+            }
+            let paused = crate::risk_engine::paused();
+            match self.observe_drops(idx, dropped, Instant::now(), paused) {
+                Some(Escalation::Alert(dropped)) => self.alert_drops(idx, dropped, ""),
+                Some(Escalation::Pause(dropped)) => {
+                    crate::risk_engine::pause();
+                    self.alert_drops(idx, dropped, ", trading paused");
+                }
+                None => {}
+            }
+        }
+
+        slow
+    }
+
+    fn alert_drops(&self, consumer: usize, dropped: u64, action: &str) {
+        let window = self
+            .escalation
+            .as_ref()
+            .map(|e| e.window())
+            .unwrap_or_default();
+
+        crate::alert::raise(
+            AlertKind::DroppedEvents {
+                channel: self.channel.to_string(),
+                consumer,
+                dropped,
+            },
+            format!(
+                "consumer {consumer} of {} channel lost {dropped} values within {window:?}{action}",
+                self.channel
+            ),
+        );
+    }
+
+    /// Records total drops of the consumer, returns the escalation due
+    ///
+    /// Trading is paused again once resumed while drops stay above the
+    /// pause threshold.
+    fn observe_drops(
+        &mut self,
+        idx: usize,
+        dropped: u64,
+        now: Instant,
+        paused: bool,
+    ) -> Option<Escalation> {
+        let window = &mut self.drops[idx];
+        let new = dropped - window.counted;
+        window.counted = dropped;
+        let escalation = self.escalation.as_ref()?;
+
+        match window.started {
+            Some(started) if now.duration_since(started) < escalation.window() => {}
+            _ => {
+                window.started = Some(now);
+                window.dropped = 0;
+                window.alerted = false;
+            }
+        }
+        window.dropped += new;
+
+        let reached = |threshold: u64| threshold > 0 && window.dropped >= threshold;
+        if !paused && reached(escalation.pause_threshold) {
+            window.alerted = true;
+            return Some(Escalation::Pause(window.dropped));
+        }
+        if !window.alerted && reached(escalation.alert_threshold) {
+            window.alerted = true;
+            return Some(Escalation::Alert(window.dropped));
+        }
+
+        None
+    }
+
+    /// Records observed occupancy, returns the index when alert should be raised
+    fn observe(&mut self, idx: usize, occupancy: f64, now: Instant) -> Option<usize> {
+        if occupancy < self.threshold {
+            self.above_since[idx] = None;
+            self.alerted[idx] = false;
+            return None;
+        }
+
+        let since = *self.above_since[idx].get_or_insert(now);
+        if !self.alerted[idx] && now.duration_since(since) >= self.duration {
+            self.alerted[idx] = true;
+            return Some(idx);
+        }
+
+        None
+    }
+}
+
 #[derive(Debug, Default, thiserror::Error)]
 #[error("Failed to push value to SPSC queue")]
 pub struct PushValueError<const N: usize> {
@@ -71,7 +375,7 @@ impl<const N: usize> PushValueError<N> {
 
 impl<T, const N: usize> Default for ProducersArray<T, N> {
     fn default() -> Self {
-        Self(ArrayVec::<_, N>::new())
+        Self(ArrayVec::<_, N>::new(), Cell::new([0; N]))
     }
 }
 
@@ -143,15 +447,105 @@ mod tests {
     }
 
     #[test]
-    fn test_producers_push_value_queue_full() {
+    fn test_producers_push_value_disconnected_consumer() {
         let (tx, rx) = spsc_queue::make(1);
-        drop(rx);
 
         let mut producers = ProducersArray::<(), 1>::default();
         producers.0.push(tx);
 
         producers.push_value(()).unwrap();
+        rx.disconnect();
         assert!(producers.push_value(()).is_err());
+        assert_eq!(0, producers.dropped()[0]);
+    }
+
+    #[test]
+    fn test_producers_push_value_lagging_consumer() {
+        let (tx, rx) = spsc_queue::make(1);
+        let mut producers = ProducersArray::<u8, 1>::default();
+        producers.0.push(tx);
+
+        producers.push_value(1).unwrap();
+        producers.push_value(2).unwrap();
+
+        assert_eq!([1], producers.dropped());
+        assert_eq!(Some(1), rx.try_pop());
+    }
+
+    #[test]
+    fn test_drop_escalation() {
+        let mut monitor = SlowConsumerMonitor::<2>::new("test", 0.5, Duration::from_secs(1))
+            .with_drop_escalation(DropEscalationConfig {
+                alert_threshold: 10,
+                pause_threshold: 50,
+                window_secs: 10,
+            });
+        let now = Instant::now();
+
+        assert_eq!(None, monitor.observe_drops(0, 5, now, false));
+        assert_eq!(
+            Some(Escalation::Alert(12)),
+            monitor.observe_drops(0, 12, now + Duration::from_secs(1), false)
+        );
+        // Alert once per window
+        assert_eq!(
+            None,
+            monitor.observe_drops(0, 20, now + Duration::from_secs(2), false)
+        );
+        assert_eq!(None, monitor.observe_drops(1, 9, now, false));
+
+        // New window starts over
+        assert_eq!(
+            None,
+            monitor.observe_drops(0, 25, now + Duration::from_secs(11), false)
+        );
+        assert_eq!(
+            Some(Escalation::Pause(60)),
+            monitor.observe_drops(0, 80, now + Duration::from_secs(12), false)
+        );
+        // Not paused again while trading is paused
+        assert_eq!(
+            None,
+            monitor.observe_drops(0, 200, now + Duration::from_secs(13), true)
+        );
+        // Paused again once trading was resumed
+        assert_eq!(
+            Some(Escalation::Pause(200)),
+            monitor.observe_drops(0, 220, now + Duration::from_secs(14), false)
+        );
+    }
+
+    #[test]
+    fn test_producers_occupancy() {
+        let (tx, _rx) = spsc_queue::make(4);
+        let mut producers = ProducersArray::<(), 1>::default();
+        producers.0.push(tx);
+
+        assert_eq!(0.0, producers.occupancy()[0]);
+        producers.push_value(()).unwrap();
+        assert!(producers.occupancy()[0] > 0.0);
+    }
+
+    #[test]
+    fn test_slow_consumer_monitor_observe() {
+        let mut monitor = SlowConsumerMonitor::<2>::new("test", 0.5, Duration::from_secs(1));
+        let now = Instant::now();
+
+        assert_eq!(None, monitor.observe(0, 0.9, now));
+        assert_eq!(None, monitor.observe(1, 0.1, now));
+        assert_eq!(
+            Some(0),
+            monitor.observe(0, 0.9, now + Duration::from_secs(2))
+        );
+        // Alert only once while the consumer stays slow
+        assert_eq!(None, monitor.observe(0, 0.9, now + Duration::from_secs(3)));
+
+        assert_eq!(None, monitor.observe(0, 0.1, now + Duration::from_secs(4)));
+        assert_eq!(None, monitor.observe(0, 0.9, now + Duration::from_secs(5)));
+        assert_eq!(
+            Some(0),
+            monitor.observe(0, 0.9, now + Duration::from_secs(6))
+        );
     }
 
     #[test]
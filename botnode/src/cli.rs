@@ -0,0 +1,325 @@
+//! Command-line interface
+//!
+//! Flags override the values from the configuration file and environment,
+//! see [`crate::config`] for the full precedence.
+
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+use figment::{providers::Serialized, Figment};
+
+#[cfg(feature = "recorder")]
+use crate::market_data::replay::ReplaySpeed;
+use crate::{
+    config::{override_layer, BotnodeConfig, ConfigError, DEFAULT_CONFIG_PATH},
+    telemetry::LogFormat,
+};
+
+/// Botvana trading bot node
+#[derive(Debug, Parser)]
+#[clap(name = "botnode", version)]
+pub struct Cli {
+    /// Configuration file
+    #[clap(long, env = "BOTNODE_CONFIG", default_value = DEFAULT_CONFIG_PATH)]
+    pub config: PathBuf,
+    /// Overrides `bot_id`
+    #[clap(long)]
+    pub bot_id: Option<u16>,
+    /// Overrides `server_addr`
+    #[clap(long)]
+    pub server_addr: Option<String>,
+    /// Overrides `telemetry.log_format` (text or json)
+    #[clap(long)]
+    pub log_format: Option<LogFormat>,
+    /// Sends all orders to the paper adapter instead of the exchange
+    #[clap(long)]
+    pub dry_run: bool,
+    /// Overrides any configuration value, e.g. `--set cpu.trading=4`
+    #[clap(long = "set", value_name = "KEY=VALUE")]
+    pub overrides: Vec<String>,
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// botnode subcommands, `run` when none is given
+#[derive(Clone, Debug, PartialEq, Subcommand)]
+pub enum Command {
+    /// Connects to botvana-server and runs the engines
+    Run,
+    /// Loads and validates the configuration, then exits
+    CheckConfig,
+    /// Prints markets available on the exchange
+    ListMarkets {
+        /// Exchange name as used in the configuration, e.g. `ftx`
+        exchange: String,
+    },
+    /// Records market data of the exchange to disk
+    #[cfg(feature = "recorder")]
+    Record {
+        exchange: String,
+        /// Markets to record
+        #[clap(required = true)]
+        markets: Vec<String>,
+        /// Directory the recording is written to
+        #[clap(long, short, default_value = "recordings")]
+        output: PathBuf,
+    },
+    /// Replays a recording through the market data pipeline
+    #[cfg(feature = "recorder")]
+    Replay {
+        /// Directory of the recording
+        input: PathBuf,
+        /// Replay speed relative to the original timing, 0 for as fast as
+        /// possible
+        #[clap(long, default_value = "1")]
+        speed: f64,
+    },
+    /// Runs the strategies against a recording with simulated execution
+    #[cfg(feature = "recorder")]
+    Backtest {
+        /// Directory of the recording
+        input: PathBuf,
+        /// Strategy to run, all enabled strategies when not given
+        #[clap(long)]
+        strategy: Option<String>,
+    },
+    /// Exports a recording and/or an audit log into Parquet files
+    /// partitioned by date and market
+    #[cfg(feature = "parquet")]
+    Export {
+        /// Directory of the recording
+        #[clap(long, required_unless_present = "audit")]
+        recording: Option<PathBuf>,
+        /// Directory of the audit log
+        #[clap(long)]
+        audit: Option<PathBuf>,
+        /// Directory the Parquet files are written to
+        #[clap(long, short, default_value = "export")]
+        output: PathBuf,
+    },
+    /// Encrypts TOML file of secrets with the passphrase from
+    /// `BOTNODE_SECRETS_PASSPHRASE`
+    EncryptSecrets {
+        /// Plaintext TOML file
+        input: PathBuf,
+        /// Encrypted file to write
+        output: PathBuf,
+    },
+}
+
+impl Command {
+    /// Returns the subcommand name as used on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Run => "run",
+            Command::CheckConfig => "check-config",
+            Command::ListMarkets { .. } => "list-markets",
+            #[cfg(feature = "recorder")]
+            Command::Record { .. } => "record",
+            #[cfg(feature = "recorder")]
+            Command::Replay { .. } => "replay",
+            #[cfg(feature = "recorder")]
+            Command::Backtest { .. } => "backtest",
+            #[cfg(feature = "parquet")]
+            Command::Export { .. } => "export",
+            Command::EncryptSecrets { .. } => "encrypt-secrets",
+        }
+    }
+}
+
+impl Cli {
+    /// Returns the subcommand to execute
+    pub fn command(&self) -> Command {
+        self.command.clone().unwrap_or(Command::Run)
+    }
+
+    /// Returns all configuration layers with the flags merged last
+    ///
+    /// `--set` overrides are applied in order, the dedicated flags win over
+    /// them.
+    pub fn figment(&self) -> Result<Figment, ConfigError> {
+        let mut figment = BotnodeConfig::figment(&self.config);
+
+        for assignment in &self.overrides {
+            figment = figment.merge(override_layer(assignment)?);
+        }
+        if let Some(bot_id) = self.bot_id {
+            figment = figment.merge(Serialized::default("bot_id", bot_id));
+        }
+        if let Some(server_addr) = &self.server_addr {
+            figment = figment.merge(Serialized::default("server_addr", server_addr));
+        }
+        if self.dry_run {
+            figment = figment.merge(Serialized::default("dry_run", true));
+        }
+        if let Some(log_format) = self.log_format {
+            figment = figment.merge(Serialized::default("telemetry.log_format", log_format));
+        }
+
+        #[cfg(feature = "recorder")]
+        match &self.command {
+            Some(Command::Record {
+                exchange,
+                markets,
+                output,
+            }) => {
+                figment = figment
+                    .merge(Serialized::default("exchanges", [exchange]))
+                    .merge(Serialized::default("markets", markets))
+                    .merge(Serialized::default("engines.trading", false))
+                    .merge(Serialized::default("recorder.enabled", true))
+                    .merge(Serialized::default("recorder.dir", output));
+            }
+            Some(Command::Replay { input, speed }) => {
+                figment = figment.merge(Serialized::default("market_data.replay.dir", input));
+                // figment serializes newtype variants without their name
+                figment = match ReplaySpeed::from_factor(*speed) {
+                    ReplaySpeed::Accelerated(factor) => figment.merge(Serialized::default(
+                        "market_data.replay.speed.accelerated",
+                        factor,
+                    )),
+                    speed => figment.merge(Serialized::default("market_data.replay.speed", speed)),
+                };
+            }
+            _ => {}
+        }
+
+        Ok(figment)
+    }
+
+    /// Loads and validates the configuration
+    pub fn load_config(&self) -> Result<BotnodeConfig, ConfigError> {
+        BotnodeConfig::from_figment(self.figment()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_overrides_config() {
+        let cli = Cli::parse_from([
+            "botnode",
+            "--config",
+            "does-not-exist.toml",
+            "--bot-id",
+            "7",
+            "--server-addr",
+            "10.0.0.1:7978",
+            "--set",
+            "cpu.trading=12",
+            "--set",
+            "control.ping_interval_secs=3",
+        ]);
+        let config = cli.load_config().unwrap();
+
+        assert_eq!(7, config.bot_id.0);
+        assert_eq!("10.0.0.1:7978", config.server_addr);
+        assert_eq!(Some(12), config.cpu.trading);
+        assert_eq!(3, config.control.ping_interval_secs);
+    }
+
+    #[test]
+    fn test_cli_flag_wins_over_set() {
+        let cli = Cli::parse_from([
+            "botnode",
+            "--config",
+            "does-not-exist.toml",
+            "--set",
+            "bot_id=1",
+            "--bot-id",
+            "2",
+            "--server-addr",
+            "127.0.0.1:7978",
+        ]);
+
+        assert_eq!(2, cli.load_config().unwrap().bot_id.0);
+    }
+
+    #[test]
+    fn test_cli_dry_run() {
+        let args = [
+            "botnode",
+            "--config",
+            "does-not-exist.toml",
+            "--bot-id",
+            "1",
+            "--server-addr",
+            "127.0.0.1:7978",
+        ];
+
+        let cli = Cli::parse_from(args.iter().chain(&["--dry-run"]));
+        assert!(cli.load_config().unwrap().dry_run);
+
+        let cli = Cli::parse_from(args);
+        assert!(!cli.load_config().unwrap().dry_run);
+    }
+
+    #[test]
+    fn test_cli_subcommands() {
+        assert_eq!(Command::Run, Cli::parse_from(["botnode"]).command());
+        assert_eq!(
+            Command::ListMarkets {
+                exchange: "ftx".to_string()
+            },
+            Cli::parse_from(["botnode", "--bot-id", "1", "list-markets", "ftx"]).command()
+        );
+
+        #[cfg(feature = "recorder")]
+        match Cli::parse_from(["botnode", "record", "ftx", "BTC/USD", "ETH/USD"]).command() {
+            Command::Record {
+                markets, output, ..
+            } => {
+                assert_eq!(2, markets.len());
+                assert_eq!(PathBuf::from("recordings"), output);
+            }
+            command => panic!("unexpected command {command:?}"),
+        }
+
+        assert!(Cli::try_parse_from(["botnode", "list-markets"]).is_err());
+    }
+
+    #[cfg(feature = "recorder")]
+    #[test]
+    fn test_cli_record_and_replay_config() {
+        let args = [
+            "botnode",
+            "--config",
+            "does-not-exist.toml",
+            "--bot-id",
+            "1",
+            "--server-addr",
+            "127.0.0.1:7978",
+        ];
+
+        let cli = Cli::parse_from(
+            args.iter()
+                .chain(&["record", "ftx", "BTC/USD", "-o", "rec"]),
+        );
+        let config = cli.load_config().unwrap();
+        assert_eq!(&[Box::from("ftx")], &*config.exchanges);
+        assert_eq!(&[Box::from("BTC/USD")], &*config.markets);
+        assert!(config.recorder.enabled);
+        assert_eq!(PathBuf::from("rec"), config.recorder.dir);
+        assert!(!config.engines.trading);
+
+        let cli = Cli::parse_from(args.iter().chain(&["replay", "rec", "--speed", "10"]));
+        let replay = cli.load_config().unwrap().market_data.replay.unwrap();
+        assert_eq!(PathBuf::from("rec"), replay.dir);
+        assert_eq!(ReplaySpeed::Accelerated(10.0), replay.speed);
+
+        let cli = Cli::parse_from(args.iter().chain(&["replay", "rec", "--speed", "0"]));
+        assert_eq!(
+            ReplaySpeed::Max,
+            cli.load_config().unwrap().market_data.replay.unwrap().speed
+        );
+    }
+
+    #[test]
+    fn test_cli_invalid_set() {
+        let cli = Cli::parse_from(["botnode", "--set", "bot_id"]);
+
+        assert!(cli.figment().is_err());
+    }
+}
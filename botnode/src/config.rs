@@ -0,0 +1,1079 @@
+//! Botnode configuration
+//!
+//! The configuration is layered, later sources override earlier ones:
+//!
+//! 1. defaults
+//! 2. TOML file, `cfg/botnode.toml` or the file given by `BOTNODE_CONFIG`
+//!    or `--config`
+//! 3. environment variables prefixed with `BOTNODE_`, nested keys are
+//!    separated by `__` (e.g. `BOTNODE_SERVER_ADDR`, `BOTNODE_CPU__TRADING`),
+//!    plus the aliases in [`ENV_ALIASES`]
+//! 4. command-line flags
+//!
+//! Exchanges and markets are normally provided by botvana-server, when set
+//! here they take precedence over the server provided ones.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use figment::{
+    providers::{Env, Format, Serialized, Toml},
+    value::{Dict, UncasedStr, Value},
+    Figment,
+};
+use serde::Deserialize;
+
+use botvana::{
+    exchange::ExchangeId,
+    net::{compression::CompressionConfig, format::WireFormat, msg::BotId},
+};
+
+use crate::{
+    alert::sink::AlertConfig,
+    audit::log::AuditLogConfig,
+    channels::DropEscalationConfig,
+    control::{auth::AuthMethod, fan_out::FanOutConfig, tls::TlsConfig},
+    engine::{ShutdownConfig, SupervisorConfig},
+    market_data::{
+        book_events::BookEvents, trade_tape::TradeTapeConfig, warm_start::WarmStartConfig,
+    },
+    metrics::statsd::StatsdConfig,
+    notify::NotifyConfig,
+    placement::{self, Topology},
+    rate_limit::RateLimitsConfig,
+    risk_engine::RiskConfig,
+    rt::ExecutorConfig,
+    secrets::SecretsConfig,
+    telemetry::TelemetryConfig,
+    trading::indicators::IndicatorsConfig,
+    util::backoff::BackoffConfig,
+};
+#[cfg(feature = "recorder")]
+use crate::{
+    backtest::BacktestConfig, market_data::replay::ReplayConfig, recorder::RecorderConfig,
+};
+
+/// Default location of the configuration file
+pub const DEFAULT_CONFIG_PATH: &str = "cfg/botnode.toml";
+
+/// Sections rejected when botnode is built without the `recorder` feature
+#[cfg(not(feature = "recorder"))]
+const RECORDER_SECTIONS: [&str; 3] = ["recorder", "backtest", "market_data.replay"];
+
+/// Prefix of the environment variables overriding configuration values
+pub const ENV_PREFIX: &str = "BOTNODE_";
+
+/// Environment variables that don't follow the `BOTNODE_<key>` scheme, with
+/// the configuration keys they set
+pub const ENV_ALIASES: &[(&str, &str)] = &[
+    ("BOT_ID", "bot_id"),
+    ("SERVER_ADDR", "server_addr"),
+    ("BOTNODE_LOG_FORMAT", "telemetry.log_format"),
+    ("BOTNODE_STATSD_ADDR", "statsd.addr"),
+    ("BOTNODE_STATSD_PREFIX", "statsd.prefix"),
+    ("BOTNODE_STATSD_DOGSTATSD", "statsd.dogstatsd"),
+    ("BOTNODE_AUDIT_DIR", "audit.dir"),
+    ("BOTNODE_ALERT_WEBHOOK_URL", "alert.webhook_url"),
+    ("BOTNODE_TELEGRAM_TOKEN", "alert.telegram_token"),
+    ("BOTNODE_TELEGRAM_CHAT_ID", "alert.telegram_chat_id"),
+];
+
+/// Botnode configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BotnodeConfig {
+    pub bot_id: BotId,
+    /// Address of botvana-server as `host:port`
+    pub server_addr: String,
+    /// Exchanges to connect to, overrides the server provided list
+    #[serde(default)]
+    pub exchanges: Box<[Box<str>]>,
+    /// Markets to trade, overrides the server provided list
+    #[serde(default)]
+    pub markets: Box<[Box<str>]>,
+    /// File mapping canonical market names to exchange-native symbols
+    pub symbols_file: Option<PathBuf>,
+    #[serde(default)]
+    pub control: ControlConfig,
+    #[serde(default)]
+    pub market_data: MarketDataConfig,
+    #[serde(default)]
+    pub engines: EnginesConfig,
+    #[serde(default)]
+    pub supervisor: SupervisorConfig,
+    /// Deadline of the ordered shutdown of the engines
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    #[serde(default)]
+    pub cpu: CpuConfig,
+    /// Spinning, busy-polling, io_uring depth and task queue priority of the
+    /// engine executors
+    #[serde(default)]
+    pub executors: ExecutorsConfig,
+    /// Strategy parameters by strategy name
+    #[serde(default)]
+    pub strategy: BTreeMap<String, StrategyConfig>,
+    /// Indicators calculated by the trading engine for strategies
+    #[serde(default)]
+    pub indicators: IndicatorsConfig,
+    /// Routes all orders to the paper adapter, regardless of strategy
+    /// configuration
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    /// StatsD exporter, disabled when not set
+    pub statsd: Option<StatsdConfig>,
+    #[serde(default)]
+    pub audit: AuditLogConfig,
+    /// Recording of the market data for replays
+    #[cfg(feature = "recorder")]
+    #[serde(default)]
+    pub recorder: RecorderConfig,
+    /// Simulated execution of the `backtest` subcommand
+    #[cfg(feature = "recorder")]
+    #[serde(default)]
+    pub backtest: BacktestConfig,
+    #[serde(default)]
+    pub alert: AlertConfig,
+    #[serde(default)]
+    pub notify: NotifyConfig,
+    #[serde(default)]
+    pub api: ApiConfig,
+    #[serde(default)]
+    pub execution: ExecutionConfig,
+    /// Limits of the exchange REST calls overriding the documented quotas
+    #[serde(default)]
+    pub rate_limits: RateLimitsConfig,
+    #[serde(default)]
+    pub risk: RiskConfig,
+    #[serde(default)]
+    pub secrets: SecretsConfig,
+}
+
+/// Control engine configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControlConfig {
+    /// Seconds between status reports sent to the server, they keep the
+    /// connection alive
+    pub ping_interval_secs: u64,
+    /// Seconds between metrics reports sent to the server
+    pub metrics_interval_secs: u64,
+    /// Backoff between reconnects to the server
+    pub reconnect: BackoffConfig,
+    /// Connects over TLS when set
+    pub tls: Option<TlsConfig>,
+    /// Answers the server authentication challenge when set
+    pub auth: Option<AuthMethod>,
+    /// Serialization of the messages, has to match the server
+    pub wire_format: WireFormat,
+    /// Compression of the messages sent to the server
+    pub compression: CompressionConfig,
+    /// Market data forwarded to the server
+    pub fan_out: FanOutConfig,
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        Self {
+            ping_interval_secs: 5,
+            metrics_interval_secs: 10,
+            reconnect: BackoffConfig::default(),
+            tls: None,
+            auth: None,
+            wire_format: WireFormat::default(),
+            compression: CompressionConfig::default(),
+            fan_out: FanOutConfig::default(),
+        }
+    }
+}
+
+impl ControlConfig {
+    pub fn ping_interval(&self) -> Duration {
+        Duration::from_secs(self.ping_interval_secs)
+    }
+
+    pub fn metrics_interval(&self) -> Duration {
+        Duration::from_secs(self.metrics_interval_secs)
+    }
+}
+
+/// Market data engines configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MarketDataConfig {
+    /// Backoff between websocket reconnects to the exchange
+    pub reconnect: BackoffConfig,
+    /// Number of best levels kept in each orderbook side, all when not set
+    ///
+    /// FTX and Kraken checksums cover 100 and 10 levels and aren't verified
+    /// on books limited below that.
+    pub max_depth: Option<usize>,
+    /// Whether orderbook updates are emitted as full books, best bid and
+    /// offer changes or changed levels
+    pub book_events: BookEvents,
+    /// Whether order-by-order books are streamed from exchanges offering
+    /// them, their L3 events are emitted besides the book events
+    pub l3_books: bool,
+    /// Milliseconds over which orderbook events of a market are coalesced,
+    /// every update is emitted when zero
+    pub conflation_interval_ms: u64,
+    /// Seconds between keepalive pings to exchanges requiring them
+    pub ping_interval_secs: u64,
+    /// Seconds without any message after which the connection is dropped
+    /// and reconnected
+    pub stale_timeout_secs: u64,
+    /// Seconds between polls of funding rates and open interest of
+    /// perpetual futures, never polled when zero
+    pub funding_poll_interval_secs: u64,
+    /// Seconds between polls of the exchange server time measuring the
+    /// offset of its clock, never polled when zero
+    pub time_sync_interval_secs: u64,
+    /// Seconds of the intervals candles are built from trades at, e.g.
+    /// `[1, 60, 300]`, none when empty
+    pub candle_intervals_secs: Vec<u64>,
+    /// Trades retained for the rolling volume and trade rate of every
+    /// market
+    pub trade_tape: TradeTapeConfig,
+    /// Escalation of market events dropped for lagging consumers
+    pub dropped_events: DropEscalationConfig,
+    /// Saves the orderbooks on shutdown and emits them on startup when set
+    pub warm_start: Option<WarmStartConfig>,
+    /// Replays recordings instead of connecting to the exchanges when set
+    #[cfg(feature = "recorder")]
+    pub replay: Option<ReplayConfig>,
+}
+
+impl Default for MarketDataConfig {
+    fn default() -> Self {
+        Self {
+            reconnect: BackoffConfig::default(),
+            max_depth: None,
+            book_events: BookEvents::default(),
+            l3_books: false,
+            conflation_interval_ms: 0,
+            ping_interval_secs: 10,
+            stale_timeout_secs: 30,
+            funding_poll_interval_secs: 60,
+            time_sync_interval_secs: 60,
+            candle_intervals_secs: Vec::new(),
+            trade_tape: TradeTapeConfig::default(),
+            dropped_events: DropEscalationConfig::default(),
+            warm_start: None,
+            #[cfg(feature = "recorder")]
+            replay: None,
+        }
+    }
+}
+
+impl MarketDataConfig {
+    /// Returns whether recordings are replayed instead of connecting to the
+    /// exchanges
+    pub fn replays(&self) -> bool {
+        #[cfg(feature = "recorder")]
+        {
+            self.replay.is_some()
+        }
+        #[cfg(not(feature = "recorder"))]
+        {
+            false
+        }
+    }
+
+    pub fn ping_interval(&self) -> Duration {
+        Duration::from_secs(self.ping_interval_secs)
+    }
+
+    pub fn stale_timeout(&self) -> Duration {
+        Duration::from_secs(self.stale_timeout_secs)
+    }
+
+    /// Returns interval of the orderbook event conflation, `None` when
+    /// disabled
+    pub fn conflation_interval(&self) -> Option<Duration> {
+        match self.conflation_interval_ms {
+            0 => None,
+            millis => Some(Duration::from_millis(millis)),
+        }
+    }
+
+    /// Returns interval of funding polls, `None` when disabled
+    pub fn funding_poll_interval(&self) -> Option<Duration> {
+        match self.funding_poll_interval_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    /// Returns interval of server time polls, `None` when disabled
+    pub fn time_sync_interval(&self) -> Option<Duration> {
+        match self.time_sync_interval_secs {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    pub fn candle_intervals(&self) -> Vec<Duration> {
+        self.candle_intervals_secs
+            .iter()
+            .map(|secs| Duration::from_secs(*secs))
+            .collect()
+    }
+}
+
+/// Engines started besides the control and market data engines
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnginesConfig {
+    /// Audit, order, position, indicator and trading engines, requires the
+    /// `trading` feature
+    pub trading: bool,
+    /// Alert engine, started with the trading engines
+    pub alert: bool,
+}
+
+impl Default for EnginesConfig {
+    fn default() -> Self {
+        Self {
+            trading: true,
+            alert: true,
+        }
+    }
+}
+
+/// Order engine configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExecutionConfig {
+    /// Name of the execution adapter orders are sent to, `dry_run`
+    /// overrides it with `paper-trading`
+    pub adapter: String,
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        Self {
+            adapter: "null".to_string(),
+        }
+    }
+}
+
+/// Local inspection APIs, each one is disabled when its address isn't set
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    /// Address of the gRPC inspection server, requires the `grpc` feature
+    pub grpc_addr: Option<SocketAddr>,
+    /// Address of the HTTP server serving `/healthz`, `/readyz` and `/status`
+    pub http_addr: Option<SocketAddr>,
+    /// Address of the WebSocket UI feed
+    pub ws_addr: Option<SocketAddr>,
+    /// Milliseconds between updates sent to UI feed clients
+    pub ws_interval_ms: u64,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            grpc_addr: None,
+            http_addr: None,
+            ws_addr: None,
+            ws_interval_ms: 250,
+        }
+    }
+}
+
+impl ApiConfig {
+    pub fn ws_interval(&self) -> Duration {
+        Duration::from_millis(self.ws_interval_ms)
+    }
+}
+
+/// CPU cores the engines are pinned to
+///
+/// Unset cores are assigned after the market data engines, in the order the
+/// fields are listed. With `nic` set they are assigned to free cores of its
+/// NUMA node instead, see [`crate::placement`].
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CpuConfig {
+    /// Network interface of the exchange traffic
+    pub nic: Option<String>,
+    pub control: Option<usize>,
+    /// Core of each market data engine, in the order of the exchanges
+    pub market_data: Option<Box<[usize]>>,
+    pub indicator: Option<usize>,
+    pub trading: Option<usize>,
+    pub exchange: Option<usize>,
+    pub audit: Option<usize>,
+    pub alert: Option<usize>,
+    pub position: Option<usize>,
+    pub recorder: Option<usize>,
+}
+
+/// Cores assigned to every engine
+#[derive(Clone, Debug, PartialEq)]
+pub struct CpuAssignment {
+    pub control: usize,
+    pub market_data: Box<[usize]>,
+    pub indicator: usize,
+    pub trading: usize,
+    pub exchange: usize,
+    pub audit: usize,
+    pub alert: usize,
+    pub position: usize,
+    pub recorder: usize,
+}
+
+impl CpuConfig {
+    /// Assigns cores to all engines for given number of exchanges
+    pub fn assign(&self, n_exchanges: usize) -> CpuAssignment {
+        let market_data = match &self.market_data {
+            Some(cores) => cores.clone(),
+            None => (1..=n_exchanges).collect(),
+        };
+
+        CpuAssignment {
+            control: self.control.unwrap_or(0),
+            indicator: self.indicator.unwrap_or(n_exchanges + 3),
+            trading: self.trading.unwrap_or(n_exchanges + 4),
+            exchange: self.exchange.unwrap_or(n_exchanges + 5),
+            audit: self.audit.unwrap_or(n_exchanges + 6),
+            alert: self.alert.unwrap_or(n_exchanges + 7),
+            position: self.position.unwrap_or(n_exchanges + 8),
+            recorder: self.recorder.unwrap_or(n_exchanges + 9),
+            market_data,
+        }
+    }
+}
+
+impl CpuConfig {
+    /// Assigns cores to all engines for given number of exchanges on the
+    /// NUMA node of the NIC when set
+    pub fn placement(&self, n_exchanges: usize) -> Result<CpuAssignment, String> {
+        let nic = match &self.nic {
+            Some(nic) => nic,
+            None => return Ok(self.assign(n_exchanges)),
+        };
+        let topology = Topology::read();
+        let node = match placement::nic_node(nic)? {
+            Some(node) => node,
+            None => topology.nodes()[0].id,
+        };
+
+        placement::place(self, n_exchanges, &topology, node)
+    }
+}
+
+impl CpuAssignment {
+    /// Returns cores of all engines with the engine names
+    fn cores(&self) -> Vec<(String, usize)> {
+        let mut cores = vec![("control".to_string(), self.control)];
+        cores.extend(
+            self.market_data
+                .iter()
+                .enumerate()
+                .map(|(i, core)| (format!("market_data[{i}]"), *core)),
+        );
+        cores.extend([
+            ("indicator".to_string(), self.indicator),
+            ("trading".to_string(), self.trading),
+            ("exchange".to_string(), self.exchange),
+            ("audit".to_string(), self.audit),
+            ("alert".to_string(), self.alert),
+            ("position".to_string(), self.position),
+            ("recorder".to_string(), self.recorder),
+        ]);
+        cores
+    }
+}
+
+/// Executor settings of the engines
+///
+/// All market data engines share one section. Each section defaults to
+/// spinning for 250µs before parking.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExecutorsConfig {
+    pub control: ExecutorConfig,
+    pub market_data: ExecutorConfig,
+    pub indicator: ExecutorConfig,
+    pub trading: ExecutorConfig,
+    pub exchange: ExecutorConfig,
+    pub audit: ExecutorConfig,
+    pub alert: ExecutorConfig,
+    pub position: ExecutorConfig,
+    pub recorder: ExecutorConfig,
+}
+
+impl ExecutorsConfig {
+    /// Returns settings of all engines with their configuration keys
+    fn engines(&self) -> [(&'static str, &ExecutorConfig); 9] {
+        [
+            ("executors.control", &self.control),
+            ("executors.market_data", &self.market_data),
+            ("executors.indicator", &self.indicator),
+            ("executors.trading", &self.trading),
+            ("executors.exchange", &self.exchange),
+            ("executors.audit", &self.audit),
+            ("executors.alert", &self.alert),
+            ("executors.position", &self.position),
+            ("executors.recorder", &self.recorder),
+        ]
+    }
+}
+
+/// Configuration of a single strategy
+#[derive(Clone, Debug, Deserialize)]
+pub struct StrategyConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Strategy specific parameters
+    #[serde(flatten)]
+    pub params: Dict,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl BotnodeConfig {
+    /// Loads configuration from the file in `BOTNODE_CONFIG` or the default
+    /// location
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::load_from(config_path())
+    }
+
+    /// Loads and validates configuration from given file
+    ///
+    /// A missing file is not an error as long as the required values are
+    /// provided by environment variables.
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        Self::from_figment(Self::figment(path))
+    }
+
+    /// Returns the file and environment layers of the configuration
+    ///
+    /// Callers merge further overrides, like command-line flags, on top.
+    pub fn figment<P: AsRef<Path>>(path: P) -> Figment {
+        let alias = |key: &UncasedStr| {
+            ENV_ALIASES
+                .iter()
+                .find(|(var, _)| key.as_str().eq_ignore_ascii_case(var))
+                .map(|(_, key)| *key)
+        };
+        let aliases = Env::raw()
+            .filter(move |key| alias(key).is_some())
+            .map(move |key| alias(key).unwrap_or_else(|| key.as_str()).into());
+        let prefixed = Env::prefixed(ENV_PREFIX)
+            .filter(|key| !is_reserved_env(key.as_str()))
+            .split("__");
+
+        Figment::new()
+            .merge(Toml::file(path.as_ref()))
+            .merge(prefixed)
+            .merge(aliases)
+    }
+
+    /// Extracts and validates configuration from given figment
+    pub fn from_figment(figment: Figment) -> Result<Self, ConfigError> {
+        #[cfg(not(feature = "recorder"))]
+        for section in RECORDER_SECTIONS {
+            if figment.find_value(section).is_ok() {
+                return Err(ValidationError::new(
+                    section,
+                    "botnode is built without the `recorder` feature",
+                )
+                .into());
+            }
+        }
+        let config: Self = figment.extract().map_err(ConfigError::with_source)?;
+
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Parses and validates configuration from TOML string
+    pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        Self::from_figment(Figment::new().merge(Toml::string(toml)))
+    }
+
+    /// Checks the values that can't be expressed by the types alone
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        match self.server_addr.rsplit_once(':') {
+            Some((host, port)) if !host.is_empty() && port.parse::<u16>().is_ok() => {}
+            _ => {
+                return Err(ValidationError::new(
+                    "server_addr",
+                    format!("expected host:port, got {:?}", self.server_addr),
+                )
+                .into())
+            }
+        }
+
+        for exchange in self.exchanges.iter() {
+            match exchange.parse::<ExchangeId>() {
+                // Replays don't need the adapters
+                Ok(id)
+                    if !crate::market_data::is_adapter_enabled(id)
+                        && !self.market_data.replays() =>
+                {
+                    return Err(ValidationError::new(
+                        "exchanges",
+                        format!(
+                            "{exchange} requires botnode built with the `{}` feature",
+                            crate::market_data::adapter_feature(id)
+                        ),
+                    )
+                    .into());
+                }
+                Ok(_) => {}
+                Err(e) => return Err(ValidationError::new("exchanges", e).into()),
+            }
+        }
+
+        if !crate::order_engine::registry::ExecutionRegistry::default()
+            .contains(&self.execution.adapter)
+        {
+            return Err(ValidationError::new(
+                "execution.adapter",
+                format!("unknown execution adapter {}", self.execution.adapter),
+            )
+            .into());
+        }
+
+        if self.control.ping_interval_secs == 0 {
+            return Err(
+                ValidationError::new("control.ping_interval_secs", "must be positive").into(),
+            );
+        }
+        if self.control.metrics_interval_secs == 0 {
+            return Err(
+                ValidationError::new("control.metrics_interval_secs", "must be positive").into(),
+            );
+        }
+
+        if let Err(e) = self.control.reconnect.validate() {
+            return Err(ValidationError::new("control.reconnect", e).into());
+        }
+        if let Err(e) = self.market_data.reconnect.validate() {
+            return Err(ValidationError::new("market_data.reconnect", e).into());
+        }
+        if self.market_data.max_depth == Some(0) {
+            return Err(ValidationError::new("market_data.max_depth", "must be positive").into());
+        }
+        if self.market_data.ping_interval_secs == 0 {
+            return Err(
+                ValidationError::new("market_data.ping_interval_secs", "must be positive").into(),
+            );
+        }
+        if self.market_data.stale_timeout_secs == 0 {
+            return Err(
+                ValidationError::new("market_data.stale_timeout_secs", "must be positive").into(),
+            );
+        }
+        if self.market_data.candle_intervals_secs.contains(&0) {
+            return Err(ValidationError::new(
+                "market_data.candle_intervals_secs",
+                "must be positive",
+            )
+            .into());
+        }
+        if let Err(e) = self.market_data.trade_tape.validate() {
+            return Err(ValidationError::new("market_data.trade_tape", e).into());
+        }
+        if let Err(e) = self.market_data.dropped_events.validate() {
+            return Err(ValidationError::new("market_data.dropped_events", e).into());
+        }
+        if let Some(Err(e)) = self
+            .market_data
+            .warm_start
+            .as_ref()
+            .map(WarmStartConfig::validate)
+        {
+            return Err(ValidationError::new("market_data.warm_start", e).into());
+        }
+        #[cfg(feature = "recorder")]
+        if let Some(Err(e)) = self.market_data.replay.as_ref().map(ReplayConfig::validate) {
+            return Err(ValidationError::new("market_data.replay", e).into());
+        }
+        if let Err(e) = self.rate_limits.validate() {
+            return Err(ValidationError::new("rate_limits", e).into());
+        }
+        if let Err(e) = self.supervisor.backoff.validate() {
+            return Err(ValidationError::new("supervisor.backoff", e).into());
+        }
+        if self.shutdown.deadline_secs == 0 {
+            return Err(ValidationError::new("shutdown.deadline_secs", "must be positive").into());
+        }
+        if self.control.wire_format.serializer().is_none() {
+            return Err(ValidationError::new(
+                "control.wire_format",
+                "botnode is built without the feature of the format",
+            )
+            .into());
+        }
+        if let Some(algorithm) = self.control.compression.algorithm {
+            if !algorithm.is_supported() {
+                return Err(ValidationError::new(
+                    "control.compression.algorithm",
+                    "botnode is built without the feature of the algorithm",
+                )
+                .into());
+            }
+        }
+        if self.control.fan_out.interval_ms == 0 {
+            return Err(
+                ValidationError::new("control.fan_out.interval_ms", "must be positive").into(),
+            );
+        }
+        if self.control.fan_out.depth == 0 {
+            return Err(ValidationError::new("control.fan_out.depth", "must be positive").into());
+        }
+        if let Some(Err(e)) = self.control.tls.as_ref().map(TlsConfig::validate) {
+            return Err(ValidationError::new("control.tls", e).into());
+        }
+
+        if let Some(cores) = &self.cpu.market_data {
+            if !self.exchanges.is_empty() && cores.len() != self.exchanges.len() {
+                return Err(ValidationError::new(
+                    "cpu.market_data",
+                    format!(
+                        "{} cores given for {} exchanges",
+                        cores.len(),
+                        self.exchanges.len()
+                    ),
+                )
+                .into());
+            }
+        }
+
+        for (field, executor) in self.executors.engines() {
+            if let Err(e) = executor.validate() {
+                return Err(ValidationError::new(field, e).into());
+            }
+        }
+
+        let n_exchanges = self
+            .cpu
+            .market_data
+            .as_ref()
+            .map_or(self.exchanges.len(), |cores| cores.len());
+        let assignment = self
+            .cpu
+            .placement(n_exchanges)
+            .map_err(|e| ValidationError::new("cpu", e))?;
+        let mut used = HashSet::new();
+        for (engine, core) in assignment.cores() {
+            if !used.insert(core) {
+                return Err(ValidationError::new(
+                    "cpu",
+                    format!("core {core} of {engine} engine is assigned to another engine"),
+                )
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns path of the configuration file from `BOTNODE_CONFIG` or the
+/// default location
+pub fn config_path() -> String {
+    std::env::var("BOTNODE_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string())
+}
+
+/// Returns figment layer setting single `key=value` override
+///
+/// Nested keys are separated by dots and the value is parsed like a TOML
+/// value, so `cpu.trading=4` sets a number and `exchanges=[ftx]` an array.
+pub fn override_layer(assignment: &str) -> Result<Serialized<Value>, ConfigError> {
+    let (key, value) = assignment.split_once('=').ok_or_else(|| {
+        ValidationError::new("--set", format!("expected key=value, got {assignment:?}"))
+    })?;
+    let value: Value = value
+        .parse()
+        .unwrap_or_else(|e: std::convert::Infallible| match e {});
+
+    Ok(Serialized::default(key.trim(), value))
+}
+
+/// Returns whether the `BOTNODE_` variable isn't a plain configuration key
+///
+/// Besides the aliases, secrets of the `env` provider and the passphrase of
+/// the secrets file are never configuration.
+fn is_reserved_env(key: &str) -> bool {
+    key.eq_ignore_ascii_case("config")
+        || key.eq_ignore_ascii_case("secrets_passphrase")
+        || key.to_ascii_lowercase().starts_with("secret_")
+        || ENV_ALIASES.iter().any(|(var, _)| {
+            var.strip_prefix(ENV_PREFIX)
+                .map_or(false, |var| key.eq_ignore_ascii_case(var))
+        })
+}
+
+/// Error loading the configuration
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to load configuration: {source}")]
+pub struct ConfigError {
+    pub source: Box<dyn std::error::Error>,
+}
+
+impl ConfigError {
+    pub fn with_source(err: impl std::error::Error + 'static) -> Self {
+        Self {
+            source: Box::new(err),
+        }
+    }
+}
+
+/// Configuration value that failed validation
+#[derive(Debug, thiserror::Error)]
+#[error("invalid value of `{field}`: {reason}")]
+pub struct ValidationError {
+    pub field: &'static str,
+    pub reason: String,
+}
+
+impl ValidationError {
+    pub fn new<T: ToString>(field: &'static str, reason: T) -> Self {
+        Self {
+            field,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl From<ValidationError> for ConfigError {
+    fn from(err: ValidationError) -> Self {
+        ConfigError::with_source(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        bot_id = 1
+        server_addr = "127.0.0.1:7978"
+        exchanges = ["ftx", "binance"]
+        markets = ["BTC/USDT"]
+
+        [control]
+        ping_interval_secs = 2
+
+        [control.reconnect]
+        max_delay_ms = 5000
+        max_attempts = 10
+
+        [engines]
+        alert = false
+
+        [supervisor]
+        max_restarts = 3
+
+        [cpu]
+        market_data = [2, 3]
+
+        [executors.market_data]
+        busy_poll = true
+
+        [api]
+        grpc_addr = "127.0.0.1:7979"
+        http_addr = "127.0.0.1:8081"
+
+        [strategy.market_maker]
+        spread_bps = 5
+        max_position = 0.5
+    "#;
+
+    #[test]
+    fn test_config_from_toml() {
+        let config = BotnodeConfig::from_toml(CONFIG).unwrap();
+
+        assert_eq!(BotId(1), config.bot_id);
+        assert_eq!(2, config.exchanges.len());
+        assert_eq!(Duration::from_secs(2), config.control.ping_interval());
+        assert_eq!(Duration::from_secs(10), config.control.metrics_interval());
+        assert_eq!(5000, config.control.reconnect.max_delay_ms);
+        assert_eq!(Some(10), config.control.reconnect.max_attempts);
+        assert!(config.engines.trading);
+        assert!(!config.engines.alert);
+        assert_eq!(3, config.supervisor.max_restarts);
+        assert_eq!(BackoffConfig::default(), config.market_data.reconnect);
+        assert!(config.executors.market_data.busy_poll);
+        assert_eq!(ExecutorConfig::default(), config.executors.audit);
+        assert_eq!(
+            Some(Duration::from_secs(60)),
+            config.market_data.funding_poll_interval()
+        );
+        assert_eq!(
+            Some("127.0.0.1:7979".parse().unwrap()),
+            config.api.grpc_addr
+        );
+        assert_eq!(
+            Some("127.0.0.1:8081".parse().unwrap()),
+            config.api.http_addr
+        );
+
+        let strategy = &config.strategy["market_maker"];
+        assert!(strategy.enabled);
+        assert!(strategy.params.contains_key("spread_bps"));
+    }
+
+    #[test]
+    fn test_cpu_assign_defaults() {
+        let assignment = CpuConfig::default().assign(2);
+
+        assert_eq!(0, assignment.control);
+        assert_eq!(&[1, 2], &*assignment.market_data);
+        assert_eq!(5, assignment.indicator);
+        assert_eq!(9, assignment.alert);
+        assert_eq!(11, assignment.recorder);
+    }
+
+    #[test]
+    fn test_config_unknown_field() {
+        let err = BotnodeConfig::from_toml(
+            r#"
+            bot_id = 1
+            server_addr = "127.0.0.1:7978"
+            exchange = ["ftx"]
+            "#,
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("exchange"));
+    }
+
+    #[test]
+    fn test_config_zero_candle_interval() {
+        let err = BotnodeConfig::from_toml(
+            r#"
+            bot_id = 1
+            server_addr = "127.0.0.1:7978"
+
+            [market_data]
+            candle_intervals_secs = [60, 0]
+            "#,
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("candle_intervals_secs"));
+    }
+
+    #[test]
+    fn test_config_invalid_server_addr() {
+        let err = BotnodeConfig::from_toml(
+            r#"
+            bot_id = 1
+            server_addr = "localhost"
+            "#,
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("server_addr"));
+    }
+
+    #[cfg(not(feature = "recorder"))]
+    #[test]
+    fn test_config_recorder_without_feature() {
+        let err = BotnodeConfig::from_toml(
+            r#"
+            bot_id = 1
+            server_addr = "127.0.0.1:7978"
+
+            [recorder]
+            enabled = true
+            "#,
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("recorder"));
+    }
+
+    #[test]
+    fn test_config_unknown_exchange() {
+        let err = BotnodeConfig::from_toml(
+            r#"
+            bot_id = 1
+            server_addr = "127.0.0.1:7978"
+            exchanges = ["mtgox"]
+            "#,
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("mtgox"));
+    }
+
+    #[test]
+    fn test_config_unknown_execution_adapter() {
+        let err = BotnodeConfig::from_toml(
+            r#"
+            bot_id = 1
+            server_addr = "127.0.0.1:7978"
+
+            [execution]
+            adapter = "mtgox"
+            "#,
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("execution.adapter"));
+    }
+
+    #[test]
+    fn test_override_layer() {
+        let config = BotnodeConfig::from_figment(
+            Figment::new()
+                .merge(Toml::string(CONFIG))
+                .merge(override_layer("exchanges=[ftx]").unwrap())
+                .merge(override_layer("cpu.market_data=[2]").unwrap())
+                .merge(override_layer("telemetry.log_format=json").unwrap()),
+        )
+        .unwrap();
+
+        assert_eq!(1, config.exchanges.len());
+        assert_eq!("ftx", &*config.exchanges[0]);
+        assert_eq!(Some(&[2][..]), config.cpu.market_data.as_deref());
+        assert_eq!(
+            crate::telemetry::LogFormat::Json,
+            config.telemetry.log_format
+        );
+        assert!(override_layer("bot_id").is_err());
+    }
+
+    #[test]
+    fn test_reserved_env() {
+        assert!(is_reserved_env("CONFIG"));
+        assert!(is_reserved_env("statsd_addr"));
+        assert!(is_reserved_env("SECRET_FTX__API_KEY"));
+        assert!(is_reserved_env("SECRETS_PASSPHRASE"));
+        assert!(!is_reserved_env("SECRETS__PROVIDER"));
+        assert!(!is_reserved_env("server_addr"));
+    }
+
+    #[test]
+    fn test_config_duplicate_cores() {
+        let err = BotnodeConfig::from_toml(
+            r#"
+            bot_id = 1
+            server_addr = "127.0.0.1:7978"
+
+            [cpu]
+            trading = 0
+            "#,
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("core 0"));
+    }
+}
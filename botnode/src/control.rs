@@ -1,5 +1,37 @@
+use botvana::{
+    cfg::ConfigUpdate,
+    net::msg::{KillSwitch, StrategyReload},
+};
+
+pub mod auth;
 pub mod engine;
 pub(crate) mod event_loop;
+pub mod fan_out;
+pub mod report;
+pub mod tls;
+
+/// Command from botvana-server relayed to the engines
+#[derive(Clone, Debug, PartialEq)]
+pub enum ControlCommand {
+    /// Configuration pushed by the server
+    ConfigUpdate(ConfigUpdate),
+    /// Kill switch tripped or reset by the server
+    KillSwitch(KillSwitch),
+    /// Trading paused by the server, strategies are not run until resumed
+    PauseTrading,
+    /// Trading resumed by the server
+    ResumeTrading,
+    /// Open orders are cancelled and positions closed
+    FlattenPositions,
+    /// Strategy is replaced by a new build of it
+    ReloadStrategy(StrategyReload),
+    /// Markets are added to the subscriptions of the market data engines
+    SubscribeMarkets(Box<[Box<str>]>),
+    /// Markets are removed from the subscriptions of the market data engines
+    UnsubscribeMarkets(Box<[Box<str>]>),
+    /// Market data engines subscribing the market send its orderbook
+    OrderbookSnapshot(Box<str>),
+}
 
 /// Botnode status
 #[derive(Clone, PartialEq)]
@@ -10,4 +42,6 @@ enum BotnodeStatus {
     Online,
     /// Not connected to botvana-server
     Offline,
+    /// Botvana-server rejected the authentication
+    Rejected(String),
 }
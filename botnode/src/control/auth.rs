@@ -0,0 +1,56 @@
+//! Authentication to botvana-server
+//!
+//! Servers configured with credentials for the bot answer `Hello` with a
+//! challenge. Depending on `control.auth` the bot answers with the API token
+//! stored under the `botvana.token` secret, or signs the challenge with the
+//! `botvana.secret` secret.
+
+use serde::Deserialize;
+
+use botvana::net::{
+    auth,
+    msg::{AuthProof, BotId},
+};
+
+use crate::secrets::{SecretsError, SecretsProvider};
+
+/// Secret key of the API token
+pub const TOKEN_KEY: &str = "botvana.token";
+
+/// Secret key of the HMAC secret
+pub const SECRET_KEY: &str = "botvana.secret";
+
+/// How the bot proves its identity
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMethod {
+    /// Sends the API token
+    Token,
+    /// Signs the challenge with the shared secret
+    Hmac,
+}
+
+/// Returns the answer to the challenge
+pub async fn proof(
+    method: AuthMethod,
+    secrets: &dyn SecretsProvider,
+    bot_id: &BotId,
+    nonce: &[u8],
+) -> Result<AuthProof, SecretsError> {
+    Ok(match method {
+        AuthMethod::Token => {
+            AuthProof::Token(secrets.require(TOKEN_KEY).await?.expose().to_string())
+        }
+        AuthMethod::Hmac => {
+            let secret = secrets.require(SECRET_KEY).await?;
+            AuthProof::Hmac(auth::sign(secret.expose().as_bytes(), bot_id, nonce))
+        }
+    })
+}
+
+/// Server rejected the bot
+#[derive(Debug, thiserror::Error)]
+#[error("Authentication rejected: {reason}")]
+pub struct AuthRejected {
+    pub reason: String,
+}
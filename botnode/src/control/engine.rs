@@ -1,8 +1,15 @@
-use botvana::exchange::ExchangeId;
-
+#[cfg(feature = "recorder")]
+use crate::recorder::engine::RecorderEngine;
+#[cfg(feature = "trading")]
+use crate::{
+    alert::engine::*, audit::engine::*, config::CpuAssignment, indicator::engine::*,
+    position::engine::PositionEngine, trading::engine::*,
+};
 use crate::{
-    audit::engine::*, engine::*, exchange::engine::*, indicator::engine::*, market_data::*,
-    prelude::*, trading::engine::*,
+    bus::{Bus, ControlCommands, Delivery, Subscriber},
+    engine::*,
+    prelude::*,
+    rt::EngineExecutor,
 };
 
 use super::BotnodeStatus;
@@ -14,74 +21,291 @@ const QUEUE_LEN: usize = 1024;
 ///
 /// Spawns engines per given configuration and connects to botvana-server.
 pub struct ControlEngine {
+    pub(super) config: crate::config::BotnodeConfig,
     pub(super) bot_id: BotId,
     pub(super) server_addr: String,
     pub(super) status: BotnodeStatus,
-    pub(super) ping_interval: std::time::Duration,
+    pub(super) status_interval: std::time::Duration,
+    pub(super) metrics_interval: std::time::Duration,
     pub(super) bot_configuration: Option<BotConfiguration>,
     config_txs: ArrayVec<spsc_queue::Producer<BotConfiguration>, CONSUMER_LIMIT>,
+    /// Bus the engines subscribe to, the control engine publishes the
+    /// server commands on it
+    pub(super) bus: Bus,
     pub(super) status_rxs: HashMap<EngineType, spsc_queue::Consumer<EngineStatus>>,
     pub(super) market_data_rxs: ConsumersMap<Box<str>, MarketEvent>,
+    pub(super) audit_tx: Option<crate::audit::AuditProducer>,
+    symbols: crate::market_data::symbols::SymbolMapper,
+    market_data_registry: crate::market_data::registry::MarketDataRegistry,
+    #[cfg(feature = "trading")]
+    execution_registry: crate::order_engine::registry::ExecutionRegistry,
+    #[cfg_attr(not(feature = "trading"), allow(dead_code))]
+    strategies: Vec<crate::trading::strategy::StrategyFactory>,
+    /// Shutdown of the whole bot, triggered when the server requests it
+    pub(super) bot_shutdown: Shutdown,
+    /// Stops the spawned engines in order when the bot shuts down
+    coordinator: ShutdownCoordinator,
 }
 
 impl ControlEngine {
     /// Create new control engine
-    pub fn new<T: ToString>(bot_id: BotId, server_addr: T) -> Self {
+    pub fn new(config: crate::config::BotnodeConfig) -> Self {
         Self {
-            bot_id,
-            server_addr: server_addr.to_string(),
+            bot_id: config.bot_id.clone(),
+            server_addr: config.server_addr.clone(),
             status: BotnodeStatus::Offline,
-            ping_interval: std::time::Duration::from_secs(5),
+            status_interval: config.control.ping_interval(),
+            metrics_interval: config.control.metrics_interval(),
+            coordinator: ShutdownCoordinator::new(&config.shutdown),
+            config,
             config_txs: ArrayVec::<_, CONSUMER_LIMIT>::new(),
+            bus: Bus::new(),
             bot_configuration: None,
             market_data_rxs: ConsumersMap::default(),
             status_rxs: HashMap::new(),
+            audit_tx: None,
+            symbols: Default::default(),
+            market_data_registry: Default::default(),
+            #[cfg(feature = "trading")]
+            execution_registry: Default::default(),
+            strategies: Vec::new(),
+            bot_shutdown: Shutdown::new(),
         }
     }
 
+    /// Sets symbol mapping shared with the market data engines
+    pub fn with_symbols(mut self, symbols: crate::market_data::symbols::SymbolMap) -> Self {
+        self.symbols = crate::market_data::symbols::SymbolMapper::new(symbols);
+        self
+    }
+
+    /// Sets registry used to spawn market data engines of the configured
+    /// exchanges
+    pub fn with_market_data_registry(
+        mut self,
+        registry: crate::market_data::registry::MarketDataRegistry,
+    ) -> Self {
+        self.market_data_registry = registry;
+        self
+    }
+
+    /// Sets shutdown of the whole bot the server can trigger
+    pub fn with_bot_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.bot_shutdown = shutdown;
+        self
+    }
+
+    /// Sets coordinator the spawned engines are registered with
+    pub fn with_shutdown_coordinator(mut self, coordinator: ShutdownCoordinator) -> Self {
+        self.coordinator = coordinator;
+        self
+    }
+
+    /// Adds strategies run next to the configured ones
+    pub fn with_strategies(
+        mut self,
+        strategies: impl IntoIterator<Item = crate::trading::strategy::StrategyFactory>,
+    ) -> Self {
+        self.strategies.extend(strategies);
+        self
+    }
+
+    /// Returns new subscription to the commands of the server
+    pub fn commands(&mut self) -> Subscriber<crate::control::ControlCommand> {
+        self.bus
+            .subscribe::<ControlCommands>(Delivery::Lossless)
+            .expect("control engine publishes the commands")
+    }
+
     /// Spawns the engines based on given configuration and wires them up using channels.
-    pub(super) fn spawn_engines(
-        &mut self,
-        config: BotConfiguration,
-        shutdown: Shutdown,
-    ) -> Result<(), ()> {
+    ///
+    /// Without the `trading` feature, or with `engines.trading` disabled, only
+    /// the market data engines are spawned. The engines are registered with
+    /// the shutdown coordinator which stops them in order. Fails when the
+    /// engines can't be placed on the cores for the configured exchanges,
+    /// whose number the server may change.
+    pub(super) fn spawn_engines(&mut self, config: BotConfiguration) -> Result<(), EngineError> {
         let n_exchanges = config.exchanges.len();
-        // Build market data receiver hashmap for each client:
+        let cpus = self.config.cpu.placement(n_exchanges).map_err(|e| {
+            error!("Failed to assign cores to the engines: {e}");
+            EngineError::config(crate::config::ValidationError::new("cpu", e))
+        })?;
+        let trading = cfg!(feature = "trading") && self.config.engines.trading;
+        // Build market data receiver hashmap for each client, the control
+        // engine alone or also:
         //  - trading engine
         //  - indicator engine
-        //  - control engine
         //  - audit engine
-        let mut market_data_rxs = vec![
-            ConsumersMap::with_capacity(n_exchanges),
-            ConsumersMap::with_capacity(n_exchanges),
-            ConsumersMap::with_capacity(n_exchanges),
-            ConsumersMap::with_capacity(n_exchanges),
-        ];
+        //  - position engine
+        //  - order engine
+        // plus the recorder engine when recording
+        #[cfg(feature = "recorder")]
+        let recording = self.config.recorder.enabled;
+        #[cfg(not(feature = "recorder"))]
+        let recording = false;
+        let n_consumers = match trading {
+            true => 6,
+            false => 1,
+        } + usize::from(recording);
+        debug_assert!(n_consumers <= crate::market_data::registry::MARKET_DATA_CONSUMERS);
+        let mut market_data_rxs: Vec<_> = (0..n_consumers)
+            .map(|_| ConsumersMap::with_capacity(n_exchanges))
+            .collect();
+        // The audit engine exists before the market data engines so they can
+        // record their connection changes
+        #[cfg(feature = "trading")]
+        let mut audit_engine = trading.then(|| AuditEngine::new(self.config.audit.clone()));
 
         for (i, exchange) in config.exchanges.iter().enumerate() {
             debug!("starting exchange {exchange:?}");
 
-            self.spawn_market_engine(
-                i + 1,
-                exchange.as_ref(),
-                shutdown.clone(),
-                &mut market_data_rxs,
-            )
-            .expect(&format!("Failed to start {exchange} market data engine"));
+            #[cfg(feature = "trading")]
+            let audit_tx = audit_engine
+                .as_mut()
+                .map(|audit_engine| audit_engine.audit_tx(&format!("market-data-{exchange}")));
+            #[cfg(not(feature = "trading"))]
+            let audit_tx = None;
+            let config_rx = self.data_rx();
+            let commands = self.commands();
+            let market_data_engine = self
+                .market_data_registry
+                .spawn(crate::market_data::registry::MarketDataSpawn {
+                    exchange: exchange.as_ref(),
+                    executor: EngineExecutor::new(
+                        cpus.market_data.get(i).copied().unwrap_or(i + 1),
+                        self.config.executors.market_data.clone(),
+                    ),
+                    config_rx,
+                    commands,
+                    symbols: self.symbols.clone(),
+                    config: self.config.market_data.clone(),
+                    consumers: &mut market_data_rxs,
+                    audit_tx,
+                    shutdown: self.coordinator.phase(ShutdownPhase::Connections),
+                })
+                .expect(&format!("Failed to start {exchange} market data engine"));
+            self.status_rxs
+                .insert(market_data_engine.r#type, market_data_engine.status_rx);
+            self.coordinator
+                .register(ShutdownPhase::Connections, market_data_engine.handle);
         }
 
         self.market_data_rxs = market_data_rxs.pop().unwrap();
 
-        let (exchange_request_tx, exchange_request_rx) = spsc_queue::make(100);
+        #[cfg(feature = "recorder")]
+        if recording {
+            let recorder_engine =
+                RecorderEngine::new(market_data_rxs.pop().unwrap(), self.config.recorder.clone());
+            self.status_rxs
+                .insert(EngineType::RecorderEngine, recorder_engine.status_rx());
+
+            self.spawn(
+                ShutdownPhase::Connections,
+                EngineExecutor::new(cpus.recorder, self.config.executors.recorder.clone()),
+                recorder_engine,
+            )
+            .expect("failed to start recorder engine");
+        }
 
-        let mut exchange_engine = ExchangeEngine::new(
-            self.data_rx(),
-            crate::exchange::null_adapter::NullAdapter {},
-            exchange_request_rx,
-        );
+        #[cfg(feature = "trading")]
+        if let Some(audit_engine) = audit_engine {
+            self.spawn_trading_engines(&cpus, &config.exchanges, audit_engine, market_data_rxs);
+        }
+        if !trading {
+            info!("Trading engines disabled, only market data engines run");
+        }
 
+        Ok(())
+    }
+
+    /// Spawns the audit, order, position, indicator, trading and alert engines
+    #[cfg(feature = "trading")]
+    fn spawn_trading_engines(
+        &mut self,
+        cpus: &CpuAssignment,
+        exchanges: &[Box<str>],
+        audit_engine: AuditEngine,
+        mut market_data_rxs: Vec<ConsumersMap<Box<str>, MarketEvent>>,
+    ) {
+        let mut audit_engine = audit_engine.with_market_data(market_data_rxs.pop().unwrap());
+        self.audit_tx = Some(audit_engine.audit_tx("control-engine"));
+
+        let (exchange_request_tx, exchange_request_rx) = spsc_queue::make(100);
+
+        // Positions are kept for the exchange orders are executed on, paper
+        // trading simulates the first configured exchange
+        let execution_exchange = self
+            .config
+            .execution
+            .adapter
+            .parse::<ExchangeId>()
+            .ok()
+            .or_else(|| exchanges.first().and_then(|e| e.parse().ok()));
+        let order_market_data_rxs = market_data_rxs.pop().unwrap();
+
+        // Dry run overrides whatever adapter would be used otherwise so no
+        // order can reach an exchange
+        let config_rx = self.data_rx();
+        let commands = self.commands();
+        let execution_adapter = if self.config.dry_run {
+            warn!("Dry run: all orders go to the paper trading adapter");
+            "paper-trading"
+        } else {
+            self.config.execution.adapter.as_str()
+        };
+        let (exchange_engine, mut exchange_events_rxs) = self
+            .execution_registry
+            .spawn(
+                execution_adapter,
+                crate::order_engine::registry::ExecutionSpawn {
+                    executor: EngineExecutor::new(
+                        cpus.exchange,
+                        self.config.executors.exchange.clone(),
+                    ),
+                    config_rx,
+                    request_rx: exchange_request_rx,
+                    commands,
+                    audit_tx: audit_engine.audit_tx("order-engine"),
+                    secrets: self.config.secrets.clone(),
+                    risk: self.config.risk.clone(),
+                    market_data: execution_exchange.map(|e| (e, order_market_data_rxs)),
+                    consumers: 2,
+                    shutdown: self.coordinator.phase(ShutdownPhase::Orders),
+                },
+            )
+            .expect("failed to start order engine");
         self.status_rxs
-            .insert(EngineType::ExchangeEngine, exchange_engine.status_rx());
+            .insert(exchange_engine.r#type, exchange_engine.status_rx);
+        self.coordinator
+            .register(ShutdownPhase::Orders, exchange_engine.handle);
+
+        let position_market_data_rxs = market_data_rxs.pop().unwrap();
+        let position_rx = match execution_exchange {
+            Some(exchange) => {
+                let mut position_engine = PositionEngine::new(
+                    exchange,
+                    exchange_events_rxs.pop().unwrap(),
+                    position_market_data_rxs,
+                    audit_engine.audit_tx("position-engine"),
+                );
+                self.status_rxs
+                    .insert(EngineType::PositionEngine, position_engine.status_rx());
+                let position_rx = position_engine.data_rx();
+
+                self.spawn(
+                    ShutdownPhase::Orders,
+                    EngineExecutor::new(cpus.position, self.config.executors.position.clone()),
+                    position_engine,
+                )
+                .expect("failed to start position engine");
+
+                position_rx
+            }
+            None => {
+                warn!("No exchange to track positions on, position engine not started");
+                spsc_queue::make(1).1
+            }
+        };
 
         let mut indicator_engine =
             IndicatorEngine::new(self.data_rx(), market_data_rxs.pop().unwrap());
@@ -89,99 +313,90 @@ impl ControlEngine {
         self.status_rxs
             .insert(EngineType::IndicatorEngine, indicator_engine.status_rx());
 
+        let mut strategies = crate::trading::strategy::load_strategies(&self.config.strategy);
+        strategies.extend(self.strategies.iter().map(|factory| factory()));
+
         let trading_engine = TradingEngine::new(
             market_data_rxs.pop().unwrap(),
             indicator_engine.data_rx(),
             exchange_request_tx,
-            exchange_engine.data_rx(),
-        );
+            exchange_events_rxs.remove(0),
+            position_rx,
+            audit_engine.audit_tx("trading-engine"),
+            strategies,
+        )
+        .with_commands(self.commands())
+        .with_book_events(self.config.market_data.book_events)
+        .with_indicators(self.config.indicators.clone());
 
         self.status_rxs
             .insert(EngineType::TradingEngine, trading_engine.status_rx());
 
-        let audit_engine = AuditEngine::new(market_data_rxs.pop().unwrap());
-
         self.status_rxs
             .insert(EngineType::AuditEngine, audit_engine.status_rx());
 
-        spawn_engine(n_exchanges + 3, indicator_engine, shutdown.clone())
-            .expect("failed to start indicator engine");
-
-        spawn_engine(n_exchanges + 4, trading_engine, shutdown.clone())
-            .expect("failed to start trading engine");
-
-        spawn_engine(n_exchanges + 5, exchange_engine, shutdown.clone())
-            .expect("failed to start order engine");
-
-        spawn_engine(n_exchanges + 6, audit_engine, shutdown.clone())
-            .expect("failed to start audit engine");
+        // Strategies stop before the indicators they read
+        self.spawn(
+            ShutdownPhase::Strategies,
+            EngineExecutor::new(cpus.trading, self.config.executors.trading.clone()),
+            trading_engine,
+        )
+        .expect("failed to start trading engine");
+
+        self.spawn(
+            ShutdownPhase::Strategies,
+            EngineExecutor::new(cpus.indicator, self.config.executors.indicator.clone()),
+            indicator_engine,
+        )
+        .expect("failed to start indicator engine");
+
+        self.spawn(
+            ShutdownPhase::Audit,
+            EngineExecutor::new(cpus.audit, self.config.executors.audit.clone()),
+            audit_engine,
+        )
+        .expect("failed to start audit engine");
+
+        if !self.config.engines.alert {
+            return;
+        }
+        // Alert engine owns no channels, it's recreated when it fails
+        let bot_id = self.bot_id.clone();
+        let alert_config = self.config.alert.clone();
+        let alert_engine = spawn_supervised(
+            EngineExecutor::new(cpus.alert, self.config.executors.alert.clone()),
+            EngineType::AlertEngine,
+            RestartPolicy::Backoff,
+            self.config.supervisor.clone(),
+            self.coordinator.phase(ShutdownPhase::Connections),
+            move || {
+                AlertEngine::new(
+                    bot_id.clone(),
+                    crate::alert::rules::default_rules(),
+                    crate::alert::sink::sinks(&alert_config),
+                )
+            },
+        )
+        .expect("failed to start alert engine");
 
-        Ok(())
+        self.status_rxs
+            .insert(alert_engine.r#type, alert_engine.status_rx);
+        self.coordinator
+            .register(ShutdownPhase::Connections, alert_engine.handle);
     }
 
-    fn spawn_market_engine(
-        &mut self,
-        cpu: usize,
-        exchange: &str,
-        shutdown: Shutdown,
-        market_data_rxs: &mut Vec<ConsumersMap<Box<str>, MarketEvent>>,
-    ) -> Result<glommio::ExecutorJoinHandle<()>, StartEngineError> {
-        match exchange {
-            "ftx" => {
-                let ftx_adapter = crate::market_data::ftx::Ftx::default();
-                let mut market_data_engine =
-                    MarketDataEngine::<_, 4>::new(self.data_rx(), ftx_adapter);
-
-                self.status_rxs.insert(
-                    EngineType::MarketDataEngine(ExchangeId::Ftx),
-                    market_data_engine.status_rx(),
-                );
-
-                market_data_rxs.iter_mut().for_each(|rx| {
-                    rx.insert(Box::from(exchange), market_data_engine.data_rx());
-                });
+    /// Spawns the engine and registers it to be stopped in given phase
+    #[cfg(any(feature = "trading", feature = "recorder"))]
+    fn spawn<E: Engine + Send + 'static>(
+        &self,
+        phase: ShutdownPhase,
+        executor: EngineExecutor,
+        engine: E,
+    ) -> Result<(), StartEngineError> {
+        let handle = spawn_engine(executor, engine, self.coordinator.phase(phase))?;
+        self.coordinator.register(phase, handle);
 
-                spawn_engine(cpu, market_data_engine, shutdown)
-            }
-            "binance" => {
-                let binance_adapter = crate::market_data::binance::Binance::default();
-                let mut market_data_engine =
-                    MarketDataEngine::<_, 4>::new(self.data_rx(), binance_adapter);
-
-                market_data_rxs.iter_mut().for_each(|rx| {
-                    rx.insert(Box::from(exchange), market_data_engine.data_rx());
-                });
-
-                self.status_rxs.insert(
-                    EngineType::MarketDataEngine(ExchangeId::BinanceSpot),
-                    market_data_engine.status_rx(),
-                );
-
-                spawn_engine(cpu, market_data_engine, shutdown)
-            }
-            "serum" => {
-                let serum_adapter = crate::market_data::serum::Serum::default();
-                let mut market_data_engine =
-                    MarketDataEngine::<_, 4>::new(self.data_rx(), serum_adapter);
-
-                market_data_rxs.iter_mut().for_each(|rx| {
-                    rx.insert(Box::from(exchange), market_data_engine.data_rx());
-                });
-
-                self.status_rxs.insert(
-                    EngineType::MarketDataEngine(ExchangeId::Serum),
-                    market_data_engine.status_rx(),
-                );
-
-                spawn_engine(cpu, market_data_engine, shutdown)
-            }
-            _ => {
-                error!("Unknown exchange {exchange}");
-                Err(StartEngineError {
-                    source: "Unknown exchange".into(),
-                })
-            }
-        }
+        Ok(())
     }
 }
 
@@ -200,11 +415,34 @@ impl Engine for ControlEngine {
     async fn start(mut self, shutdown: Shutdown) -> Result<(), EngineError> {
         info!("Starting control engine");
 
-        glommio::timer::sleep(std::time::Duration::from_secs(1)).await;
+        crate::rt::timer::sleep(std::time::Duration::from_secs(1)).await;
 
+        let mut backoff = crate::util::backoff::Backoff::new(self.config.control.reconnect.clone());
         while let Err(e) = super::event_loop::run_control_loop(&mut self, shutdown.clone()).await {
+            // Start over from the initial delay once the server accepted us
+            if crate::status::read(|s| s.server_connected) {
+                backoff.reset();
+            }
+            crate::status::update(|s| s.server_connected = false);
+            if !e.is_recoverable() {
+                error!("Control engine stopped: {e}");
+                return Err(e);
+            }
+
             error!("Control engine error: {e:?}");
-            glommio::timer::sleep(std::time::Duration::from_secs(1)).await;
+            match backoff.next_delay() {
+                Some(delay) => {
+                    warn!(
+                        attempt = backoff.attempts(),
+                        "Reconnecting to the server in {delay:?}"
+                    );
+                    crate::rt::timer::sleep(delay).await;
+                }
+                None => {
+                    error!("Giving up after {} reconnect attempts", backoff.attempts());
+                    return Err(e);
+                }
+            }
         }
 
         Ok(())
@@ -1,7 +1,19 @@
 use std::time::SystemTime;
 
+use botvana::{
+    cfg::ConfigUpdate,
+    net::{
+        codec::{Protocol, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION},
+        compression::Compression,
+        msg::{AuthResult, BotMetadata, KillSwitch},
+    },
+};
+
 use super::engine::*;
-use super::BotnodeStatus;
+use super::ControlCommand;
+use super::{fan_out::FanOut, report::StatusReporter, tls::ControlStream, BotnodeStatus};
+use crate::audit::{AuditEvent, AuditRecord, Category, Severity};
+use crate::bus::ControlCommands;
 use crate::prelude::*;
 
 const BOTVANA_SERVER_READ_TIMEOUT: u64 = 50;
@@ -9,7 +21,7 @@ const BOTVANA_SERVER_READ_TIMEOUT: u64 = 50;
 /// Runs the Botnode control engine that runs the connection to Botvana
 ///
 /// This connects to Botvana server on a given address, sends the Hello
-/// message and runs the loop.
+/// message, authenticates and runs the loop.
 pub(crate) async fn run_control_loop(
     control: &mut super::engine::ControlEngine,
     shutdown: Shutdown,
@@ -17,14 +29,22 @@ pub(crate) async fn run_control_loop(
     // Get a token to delay shutdown until the token is dropped
     let _token = shutdown
         .delay_shutdown_token()
-        .map_err(EngineError::with_source)?;
-    let mut framed = connect_botvana_server(control).await?;
+        .map_err(|_| EngineError::Shutdown)?;
+    let (mut framed, protocol) = connect_botvana_server(control).await?;
+    authenticate(control, &mut framed, &protocol).await?;
+    // Only reconnections are audited, the audit engine is spawned with the
+    // configuration received on the first connection
+    audit_server_connection(control, true);
 
     // Await the first message expected to be bot configuration
     let msg = framed.next().await;
-    let mut last_activity = SystemTime::now();
+    let mut last_status_report = SystemTime::now();
+    let mut last_metrics_report = SystemTime::now();
+    let mut status_reporter = StatusReporter::default();
+    let mut alerts_cursor = 0;
+    let mut fan_out = FanOut::new(control.config.control.fan_out.clone());
 
-    process_bot_configuration(control, msg, shutdown.clone())?;
+    process_bot_configuration(control, msg)?;
 
     loop {
         if shutdown.shutdown_started() {
@@ -33,13 +53,41 @@ pub(crate) async fn run_control_loop(
             break Ok(());
         }
 
-        let elapsed = last_activity.elapsed().unwrap();
+        if last_status_report.elapsed().unwrap() > control.status_interval {
+            let report = match &control.bot_configuration {
+                Some(config) => status_reporter.report(&config.exchanges, &config.markets),
+                None => status_reporter.report(&[], &[]),
+            };
+            if let Err(e) = framed.send(Message::StatusReport(report)).await {
+                error!("Failed to send status report: {e:?}");
+            }
+            if protocol.version() >= 4 {
+                let positions = super::report::positions();
+                if let Err(e) = framed.send(Message::Positions(positions)).await {
+                    error!("Failed to send positions: {e:?}");
+                }
+            }
+            last_status_report = SystemTime::now();
+        }
 
-        if elapsed > control.ping_interval {
-            if let Err(e) = framed.send(Message::ping()).await {
-                error!("Failed to send ping message: {e:?}");
+        if last_metrics_report.elapsed().unwrap() > control.metrics_interval {
+            crate::allocator::stats().publish();
+            crate::metrics::registry().set_gauge(
+                "botnode_dry_run",
+                &crate::metrics::MetricLabels::new(),
+                if control.config.dry_run { 1.0 } else { 0.0 },
+            );
+            let report = crate::metrics::registry().report();
+            if let Err(e) = framed.send(Message::metrics(report)).await {
+                error!("Failed to send metrics message: {e:?}");
+            }
+            last_metrics_report = SystemTime::now();
+        }
+
+        for alert in crate::alert::read_since(&mut alerts_cursor) {
+            if let Err(e) = framed.send(Message::Alert(alert)).await {
+                error!("Failed to send alert message: {e:?}");
             }
-            last_activity = SystemTime::now();
         }
 
         for (engine, status_rx) in control.status_rxs.iter() {
@@ -50,6 +98,7 @@ pub(crate) async fn run_control_loop(
             let status = status_rx.try_pop();
             if let Some(status) = status {
                 info!("EngineStatus: {engine:?} = {status:?}");
+                crate::status::update(|s| s.set_engine_status(engine.name(), status));
             }
         }
 
@@ -65,7 +114,6 @@ pub(crate) async fn run_control_loop(
                         .send(Message::market_list(*markets.clone()))
                         .await
                         .unwrap();
-                    last_activity = SystemTime::now();
                 }
                 Some(MarketEvent {
                     r#type: MarketEventType::OrderbookUpdate(market, orderbook),
@@ -78,22 +126,106 @@ pub(crate) async fn run_control_loop(
                         asks: orderbook.asks,
                         time: orderbook.time,
                     };
-                    framed.send(Message::orderbook(orderbook)).await.unwrap();
-                    last_activity = SystemTime::now();
+                    crate::status::update(|s| {
+                        s.orderbooks
+                            .insert((exchange, orderbook.market.clone()), orderbook.clone())
+                    });
+                    if let Some(orderbook) = fan_out.on_orderbook(orderbook) {
+                        if let Err(e) = framed.send(Message::orderbook(orderbook)).await {
+                            error!("Failed to send orderbook: {e:?}");
+                        }
+                    }
+                }
+                Some(MarketEvent {
+                    r#type: MarketEventType::OrderbookSnapshot(market, orderbook),
+                    ..
+                }) if protocol.version() >= 7 => {
+                    let orderbook = Orderbook {
+                        exchange,
+                        market,
+                        bids: orderbook.bids,
+                        asks: orderbook.asks,
+                        time: orderbook.time,
+                    };
+                    if let Err(e) = framed.send(Message::OrderbookSnapshot(orderbook)).await {
+                        error!("Failed to send orderbook snapshot: {e:?}");
+                    }
                 }
                 Some(_) | None => {}
             }
         }
 
+        for orderbook in fan_out.due() {
+            if let Err(e) = framed.send(Message::orderbook(orderbook)).await {
+                error!("Failed to send orderbook: {e:?}");
+            }
+        }
+
         let msg =
-            glommio::timer::timeout(Duration::from_micros(BOTVANA_SERVER_READ_TIMEOUT), async {
+            crate::rt::timer::timeout(Duration::from_micros(BOTVANA_SERVER_READ_TIMEOUT), async {
                 Ok(framed.next().await)
             })
             .await;
 
         // Check if the stream has yielded a value
         match msg {
-            Ok(None) => return Ok(()),
+            Ok(None) => {
+                audit_server_connection(control, false);
+                return Ok(());
+            }
+            Ok(Some(Ok(Message::KillSwitch(kill_switch)))) => {
+                process_kill_switch(control, kill_switch);
+            }
+            Ok(Some(Ok(Message::ConfigUpdate(update)))) => {
+                process_config_update(control, update);
+            }
+            Ok(Some(Ok(Message::PauseTrading))) => {
+                crate::risk_engine::pause();
+                process_command(
+                    control,
+                    ControlCommand::PauseTrading,
+                    "trading paused by server",
+                );
+            }
+            Ok(Some(Ok(Message::ResumeTrading))) => {
+                crate::risk_engine::resume();
+                process_command(
+                    control,
+                    ControlCommand::ResumeTrading,
+                    "trading resumed by server",
+                );
+            }
+            Ok(Some(Ok(Message::FlattenPositions))) => {
+                process_command(
+                    control,
+                    ControlCommand::FlattenPositions,
+                    "flattening positions requested by server",
+                );
+            }
+            Ok(Some(Ok(Message::ReloadStrategy(reload)))) => {
+                let msg = format!("reload of strategy {} requested by server", reload.strategy);
+                process_command(control, ControlCommand::ReloadStrategy(reload), &msg);
+            }
+            Ok(Some(Ok(Message::SubscribeMarkets(markets)))) => {
+                process_subscription(control, ControlCommand::SubscribeMarkets(markets));
+            }
+            Ok(Some(Ok(Message::UnsubscribeMarkets(markets)))) => {
+                process_subscription(control, ControlCommand::UnsubscribeMarkets(markets));
+            }
+            Ok(Some(Ok(Message::GetOrderbookSnapshot(market)))) => {
+                info!("Orderbook snapshot of {market} requested by server");
+                if let Err(e) = control
+                    .bus
+                    .publish::<ControlCommands>(ControlCommand::OrderbookSnapshot(market))
+                {
+                    error!("Failed to request orderbook snapshot: {e}");
+                }
+            }
+            Ok(Some(Ok(Message::Shutdown))) => {
+                warn!("Shutdown requested by botvana-server");
+                audit_command(control, Category::Lifecycle, "shutdown requested by server");
+                control.bot_shutdown.shutdown();
+            }
             Ok(msg) => {
                 debug!("got msg from botvana-server: {msg:?}");
             }
@@ -102,35 +234,291 @@ pub(crate) async fn run_control_loop(
     }
 }
 
-/// Opens a connection to botvana server
+/// Records connection change of botvana-server into the audit log
+fn audit_server_connection(control: &ControlEngine, connected: bool) {
+    if let Some(audit_tx) = &control.audit_tx {
+        let (severity, message) = match connected {
+            true => (Severity::Info, "connected to botvana-server"),
+            false => (Severity::Warning, "disconnected from botvana-server"),
+        };
+        crate::audit::record(
+            audit_tx,
+            AuditEvent::new(severity, Category::Connection, "control-engine", message).with_record(
+                AuditRecord::Connection {
+                    peer: Box::from("botvana-server"),
+                    connected,
+                },
+            ),
+        );
+    }
+}
+
+/// Distributes configuration update pushed by the server to the engines
+fn process_config_update(control: &mut ControlEngine, update: ConfigUpdate) {
+    info!("Configuration update from server: {update:?}");
+
+    if let (Some(markets), Some(config)) = (&update.markets, &mut control.bot_configuration) {
+        config.markets = markets.clone();
+    }
+
+    if let Some(audit_tx) = &control.audit_tx {
+        crate::audit::record(
+            audit_tx,
+            AuditEvent::new(
+                Severity::Warning,
+                Category::Config,
+                "control-engine",
+                format!("configuration update from server {update:?}"),
+            ),
+        );
+    }
+
+    if let Err(e) = control
+        .bus
+        .publish::<ControlCommands>(ControlCommand::ConfigUpdate(update))
+    {
+        error!("Failed to distribute configuration update: {e}");
+    }
+}
+
+/// Distributes operator command sent by the server to the engines
+fn process_command(control: &mut ControlEngine, command: ControlCommand, msg: &str) {
+    info!("Command from server: {command:?}");
+    audit_command(control, Category::Order, msg);
+
+    if let Err(e) = control.bus.publish::<ControlCommands>(command) {
+        error!("Failed to distribute command: {e}");
+    }
+}
+
+/// Updates the configured markets and distributes the subscription change
+/// to the market data engines
+fn process_subscription(control: &mut ControlEngine, command: ControlCommand) {
+    let config = control.bot_configuration.as_mut();
+    let msg = match &command {
+        ControlCommand::SubscribeMarkets(markets) => {
+            if let Some(config) = config {
+                let mut configured = config.markets.to_vec();
+                configured.extend(
+                    markets
+                        .iter()
+                        .filter(|market| !config.markets.contains(market))
+                        .cloned(),
+                );
+                config.markets = configured.into_boxed_slice();
+            }
+            format!("markets {markets:?} subscribed by server")
+        }
+        ControlCommand::UnsubscribeMarkets(markets) => {
+            if let Some(config) = config {
+                config.markets = config
+                    .markets
+                    .iter()
+                    .filter(|market| !markets.contains(market))
+                    .cloned()
+                    .collect();
+            }
+            format!("markets {markets:?} unsubscribed by server")
+        }
+        _ => return,
+    };
+
+    info!("{msg}");
+    audit_command(control, Category::Config, &msg);
+
+    if let Err(e) = control.bus.publish::<ControlCommands>(command) {
+        error!("Failed to distribute subscription change: {e}");
+    }
+}
+
+/// Records operator command sent by the server into the audit log
+fn audit_command(control: &ControlEngine, category: Category, msg: &str) {
+    if let Some(audit_tx) = &control.audit_tx {
+        crate::audit::record(
+            audit_tx,
+            AuditEvent::new(Severity::Critical, category, "control-engine", msg),
+        );
+    }
+}
+
+/// Trips or resets the kill switch as requested by the server
+fn process_kill_switch(control: &mut ControlEngine, kill_switch: KillSwitch) {
+    let msg = match kill_switch.tripped {
+        true => {
+            crate::risk_engine::trip(&kill_switch.reason);
+            format!("kill switch tripped by server: {}", kill_switch.reason)
+        }
+        false => {
+            crate::risk_engine::reset();
+            format!("kill switch reset by server: {}", kill_switch.reason)
+        }
+    };
+
+    if let Err(e) = control
+        .bus
+        .publish::<ControlCommands>(ControlCommand::KillSwitch(kill_switch))
+    {
+        error!("Failed to distribute kill switch: {e}");
+    }
+
+    if let Some(audit_tx) = &control.audit_tx {
+        crate::audit::record(
+            audit_tx,
+            AuditEvent::new(Severity::Critical, Category::Order, "control-engine", msg),
+        );
+    }
+}
+
+/// Opens a connection to botvana server, returns it with the protocol
+/// versions of its codec
 async fn connect_botvana_server(
     control: &mut ControlEngine,
-) -> Result<Framed<TcpStream, BotvanaCodec>, EngineError> {
+) -> Result<(Framed<ControlStream, BotvanaCodec>, Protocol), EngineError> {
     control.status = BotnodeStatus::Connecting;
 
     let stream = TcpStream::connect(control.server_addr.clone())
         .await
-        .map_err(EngineError::with_source)?;
+        .map_err(EngineError::connect)?;
+    let stream = match &control.config.control.tls {
+        #[cfg(feature = "tls")]
+        Some(tls) => {
+            let connector = super::tls::connector(tls).map_err(EngineError::config)?;
+            let server_name = tls.server_name(&control.server_addr);
+            let stream = connector
+                .connect(server_name, stream)
+                .await
+                .map_err(EngineError::connect)?;
+            debug!("TLS connection to {server_name} established");
+
+            ControlStream::Tls(Box::new(stream))
+        }
+        _ => ControlStream::Plain(stream),
+    };
 
-    let mut framed = Framed::new(stream, BotvanaCodec);
+    let serializer = control
+        .config
+        .control
+        .wire_format
+        .serializer()
+        .expect("wire format is validated");
+    let codec =
+        BotvanaCodec::new(serializer).with_compression(control.config.control.compression.clone());
+    let protocol = codec.protocol();
+    let mut framed = Framed::new(stream, codec);
+    crate::status::update(|s| {
+        s.bot_id = control.bot_id.0;
+        s.dry_run = control.config.dry_run;
+        s.server_connected = true;
+    });
 
-    let msg = Message::hello(control.bot_id.clone());
+    let msg = Message::Hello(
+        control.bot_id.clone(),
+        BotMetadata::new(1).dry_run(control.config.dry_run),
+    );
     if let Err(e) = framed.send(msg).await {
         error!("Error framing the message: {e:?}");
     }
 
-    Ok(framed)
+    Ok((framed, protocol))
+}
+
+/// Completes the handshake started by `Hello`
+///
+/// Returns once the server accepted the bot, rejected bots stop the control
+/// engine instead of reconnecting with the same credentials. Servers that
+/// don't send the negotiated protocol version are spoken to with the newest
+/// one.
+async fn authenticate(
+    control: &mut ControlEngine,
+    framed: &mut Framed<ControlStream, BotvanaCodec>,
+    protocol: &Protocol,
+) -> Result<(), EngineError> {
+    loop {
+        match framed.next().await {
+            Some(Ok(Message::ProtocolVersion(version))) => {
+                if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version) {
+                    return Err(rejected(
+                        control,
+                        format!("server picked unsupported protocol version {version}"),
+                    ));
+                }
+                debug!("Speaking protocol version {version}");
+                protocol.set_version(version);
+
+                if version >= 3 {
+                    framed
+                        .send(Message::Compression(Compression::supported()))
+                        .await
+                        .map_err(EngineError::codec)?;
+                }
+            }
+            Some(Ok(Message::Compression(algorithms))) => {
+                debug!("Server decompresses {algorithms:?}");
+                protocol.set_peer_compression(&algorithms);
+            }
+            Some(Ok(Message::AuthChallenge(nonce))) => {
+                let method = control.config.control.auth.ok_or_else(|| {
+                    rejected(control, "server requires authentication, set control.auth")
+                })?;
+                let secrets = crate::secrets::provider(&control.config.secrets)
+                    .map_err(EngineError::config)?;
+                let proof = super::auth::proof(method, &*secrets, &control.bot_id, &nonce)
+                    .await
+                    .map_err(EngineError::config)?;
+                framed
+                    .send(Message::AuthResponse(proof))
+                    .await
+                    .map_err(EngineError::codec)?;
+            }
+            Some(Ok(Message::AuthResult(AuthResult::Accepted))) => {
+                debug!("Authenticated to botvana-server");
+                return Ok(());
+            }
+            Some(Ok(Message::AuthResult(AuthResult::Rejected(reason)))) => {
+                return Err(rejected(control, reason));
+            }
+            Some(Ok(msg)) => {
+                warn!("Expected authentication result, got {msg:?}");
+                return Err(EngineError::config(ControlEngineError {
+                    msg: "Botvana-server didn't authenticate the bot",
+                }));
+            }
+            Some(Err(e)) => return Err(EngineError::codec(e)),
+            None => {
+                return Err(EngineError::connect(ControlEngineError {
+                    msg: "Disconnected from botvana-server",
+                }));
+            }
+        }
+    }
+}
+
+/// Marks the bot as rejected by the server
+fn rejected<T: ToString>(control: &mut ControlEngine, reason: T) -> EngineError {
+    let reason = reason.to_string();
+    error!("Botvana-server rejected the bot: {reason}");
+    control.status = BotnodeStatus::Rejected(reason.clone());
+    crate::status::update(|s| s.auth_rejected = Some(reason.clone()));
+
+    EngineError::config(super::auth::AuthRejected { reason })
 }
 
 fn process_bot_configuration<E: 'static + std::error::Error>(
     control: &mut super::engine::ControlEngine,
     msg: Option<Result<Message, E>>,
-    shutdown: Shutdown,
 ) -> Result<(), EngineError> {
     match msg {
-        Some(Ok(Message::BotConfiguration(bot_config))) => {
+        Some(Ok(Message::BotConfiguration(mut bot_config))) => {
             debug!("received config = {bot_config:?}");
 
+            // Locally configured exchanges and markets take precedence
+            if !control.config.exchanges.is_empty() {
+                bot_config.exchanges = control.config.exchanges.clone();
+            }
+            if !control.config.markets.is_empty() {
+                bot_config.markets = control.config.markets.clone();
+            }
+
             if matches!(
                 control.status,
                 BotnodeStatus::Offline | BotnodeStatus::Connecting
@@ -142,17 +530,43 @@ fn process_bot_configuration<E: 'static + std::error::Error>(
 
             control.bot_configuration = Some(bot_config.clone());
 
-            control
-                .spawn_engines(bot_config.clone(), shutdown.clone())
-                .unwrap();
+            control.spawn_engines(bot_config.clone())?;
+            crate::status::update(|s| s.engines_spawned = true);
+
+            if let Some(audit_tx) = &control.audit_tx {
+                crate::audit::record(
+                    audit_tx,
+                    AuditEvent::new(
+                        Severity::Info,
+                        Category::Config,
+                        "control-engine",
+                        format!("received configuration {bot_config:?}"),
+                    ),
+                );
+                crate::audit::record(
+                    audit_tx,
+                    AuditEvent::new(
+                        Severity::Info,
+                        Category::Lifecycle,
+                        "control-engine",
+                        "spawned engines",
+                    ),
+                );
+            }
 
             control.push_value(bot_config);
         }
         Some(Err(e)) => {
-            return Err(EngineError::with_source(e));
+            return Err(EngineError::codec(e));
+        }
+        Some(Ok(msg)) => {
+            warn!("Expected bot configuration, got {msg:?}");
+            return Err(EngineError::config(ControlEngineError {
+                msg: "Botvana-server didn't send bot configuration",
+            }));
         }
-        _ => {
-            return Err(EngineError::with_source(ControlEngineError {
+        None => {
+            return Err(EngineError::connect(ControlEngineError {
                 msg: "Disconnected from botvana-server",
             }));
         }
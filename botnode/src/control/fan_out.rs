@@ -0,0 +1,200 @@
+//! Market data forwarded to botvana-server
+//!
+//! By default every orderbook update is sent over the control connection.
+//! The `bbo` and `snapshot` modes keep only the latest book of each market
+//! and send it once per interval, cut to the best level or to the
+//! configured depth, so the server can watch many markets of many bots
+//! without receiving every update.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+use botvana::{exchange::ExchangeId, market::orderbook::Orderbook};
+
+/// Which orderbooks are forwarded to the server
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FanOutMode {
+    /// Nothing is forwarded
+    Off,
+    /// Every orderbook update is forwarded
+    All,
+    /// Best bid and offer of each market once per interval
+    Bbo,
+    /// Book of each market cut to `depth` levels once per interval
+    Snapshot,
+}
+
+impl Default for FanOutMode {
+    fn default() -> Self {
+        Self::All
+    }
+}
+
+/// Configuration of the market data forwarded to the server
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FanOutConfig {
+    pub mode: FanOutMode,
+    /// Milliseconds between books forwarded in `bbo` and `snapshot` modes
+    pub interval_ms: u64,
+    /// Levels of each side forwarded in `snapshot` mode
+    pub depth: usize,
+}
+
+impl Default for FanOutConfig {
+    fn default() -> Self {
+        Self {
+            mode: FanOutMode::default(),
+            interval_ms: 1000,
+            depth: 10,
+        }
+    }
+}
+
+impl FanOutConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval_ms)
+    }
+}
+
+/// Throttles orderbooks forwarded to the server
+#[derive(Debug)]
+pub struct FanOut {
+    config: FanOutConfig,
+    /// Latest book of each market updated since the last forward
+    pending: HashMap<(ExchangeId, Box<str>), Orderbook<f64>>,
+    last_forward: Instant,
+}
+
+impl FanOut {
+    pub fn new(config: FanOutConfig) -> Self {
+        Self {
+            config,
+            pending: HashMap::new(),
+            last_forward: Instant::now(),
+        }
+    }
+
+    /// Handles the orderbook update, returns it when it's forwarded right
+    /// away
+    pub fn on_orderbook(&mut self, orderbook: Orderbook<f64>) -> Option<Orderbook<f64>> {
+        match self.config.mode {
+            FanOutMode::Off => None,
+            FanOutMode::All => Some(orderbook),
+            FanOutMode::Bbo | FanOutMode::Snapshot => {
+                self.pending
+                    .insert((orderbook.exchange, orderbook.market.clone()), orderbook);
+                None
+            }
+        }
+    }
+
+    /// Returns the books due to be forwarded, cut to the depth of the mode
+    pub fn due(&mut self) -> Vec<Orderbook<f64>> {
+        if self.pending.is_empty() || self.last_forward.elapsed() < self.config.interval() {
+            return Vec::new();
+        }
+        self.last_forward = Instant::now();
+
+        let depth = match self.config.mode {
+            FanOutMode::Bbo => 1,
+            _ => self.config.depth,
+        };
+
+        self.pending
+            .drain()
+            .map(|(_, mut orderbook)| {
+                orderbook.bids.retain_highest(depth);
+                orderbook.asks.retain_lowest(depth);
+                orderbook
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use botvana::market::orderbook::PriceLevelsVec;
+
+    fn orderbook(market: &str, time: f64) -> Orderbook<f64> {
+        Orderbook {
+            bids: PriceLevelsVec::from_tuples_vec(&[(1.0, 1.0), (2.0, 1.0), (3.0, 1.0)]),
+            asks: PriceLevelsVec::from_tuples_vec(&[(4.0, 1.0), (5.0, 1.0), (6.0, 1.0)]),
+            time,
+            exchange: ExchangeId::Ftx,
+            market: Box::from(market),
+        }
+    }
+
+    fn fan_out(mode: FanOutMode, depth: usize) -> FanOut {
+        FanOut::new(FanOutConfig {
+            mode,
+            interval_ms: 0,
+            depth,
+        })
+    }
+
+    #[test]
+    fn test_fan_out_all() {
+        let mut fan_out = fan_out(FanOutMode::All, 10);
+
+        assert!(fan_out.on_orderbook(orderbook("BTC-PERP", 1.0)).is_some());
+        assert!(fan_out.due().is_empty());
+    }
+
+    #[test]
+    fn test_fan_out_off() {
+        let mut fan_out = fan_out(FanOutMode::Off, 10);
+
+        assert!(fan_out.on_orderbook(orderbook("BTC-PERP", 1.0)).is_none());
+        assert!(fan_out.due().is_empty());
+    }
+
+    #[test]
+    fn test_fan_out_bbo() {
+        let mut fan_out = fan_out(FanOutMode::Bbo, 10);
+
+        assert!(fan_out.on_orderbook(orderbook("BTC-PERP", 1.0)).is_none());
+        assert!(fan_out.on_orderbook(orderbook("BTC-PERP", 2.0)).is_none());
+
+        let due = fan_out.due();
+        assert_eq!(1, due.len());
+        assert_eq!(2.0, due[0].time);
+        assert_eq!(vec![3.0], due[0].bids.price_vec);
+        assert_eq!(vec![4.0], due[0].asks.price_vec);
+        assert!(fan_out.due().is_empty());
+    }
+
+    #[test]
+    fn test_fan_out_snapshot() {
+        let mut fan_out = fan_out(FanOutMode::Snapshot, 2);
+
+        fan_out.on_orderbook(orderbook("BTC-PERP", 1.0));
+        fan_out.on_orderbook(orderbook("ETH-PERP", 1.0));
+
+        let due = fan_out.due();
+        assert_eq!(2, due.len());
+        for orderbook in due {
+            assert_eq!(vec![2.0, 3.0], orderbook.bids.price_vec);
+            assert_eq!(vec![4.0, 5.0], orderbook.asks.price_vec);
+        }
+    }
+
+    #[test]
+    fn test_fan_out_interval() {
+        let mut fan_out = FanOut::new(FanOutConfig {
+            mode: FanOutMode::Bbo,
+            interval_ms: 60_000,
+            depth: 10,
+        });
+
+        fan_out.on_orderbook(orderbook("BTC-PERP", 1.0));
+        assert!(fan_out.due().is_empty());
+    }
+}
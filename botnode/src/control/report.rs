@@ -0,0 +1,132 @@
+//! Status reports sent to botvana-server
+//!
+//! Engine states and positions come from the process-wide status, feed
+//! health and message rates from the metrics published by the market data
+//! adapters.
+
+use std::time::Instant;
+
+use botvana::net::msg::{EngineHealth, FeedHealth, PositionReport, StatusReport};
+
+use crate::{metrics::MetricLabels, prelude::*};
+
+/// Builds status reports, message rates are computed from the message
+/// counts seen by the previous report
+#[derive(Debug, Default)]
+pub struct StatusReporter {
+    previous: Option<(Instant, HashMap<Box<str>, u64>)>,
+}
+
+impl StatusReporter {
+    /// Returns report of current health, every exchange is expected to be
+    /// subscribed to all the markets
+    pub fn report(&mut self, exchanges: &[Box<str>], markets: &[Box<str>]) -> StatusReport {
+        let registry = crate::metrics::registry();
+        let now = Instant::now();
+        let mut messages = HashMap::new();
+
+        let feeds = exchanges
+            .iter()
+            .map(|exchange| {
+                // Adapters label their metrics with the exchange id
+                let exchange = exchange
+                    .parse::<ExchangeId>()
+                    .map_or(&**exchange, |id| id.as_str());
+                let labels = MetricLabels::new().exchange(exchange);
+                let connected = registry
+                    .get("market_data_connected", &labels)
+                    .map_or(false, |value| value.as_f64() > 0.0);
+                let count = registry
+                    .get("market_data_messages_total", &labels)
+                    .map_or(0, |value| value.as_f64() as u64);
+                messages.insert(Box::<str>::from(exchange), count);
+
+                FeedHealth {
+                    exchange: Box::from(exchange),
+                    connected,
+                    markets: Box::from(markets),
+                    messages_per_second: self.rate(exchange, count, now),
+                }
+            })
+            .collect();
+
+        let engines = crate::status::read(|status| {
+            status
+                .engines
+                .iter()
+                .map(|(engine, state)| EngineHealth {
+                    engine: Box::from(engine.as_str()),
+                    status: Box::from(state.status.as_str()),
+                    updated_at: state.updated_at,
+                })
+                .collect()
+        });
+        let allocator = crate::allocator::stats();
+
+        self.previous = Some((now, messages));
+
+        StatusReport::new(engines, feeds, allocator.resident.or(allocator.allocated))
+    }
+
+    /// Returns messages per second received by the exchange since the
+    /// previous report
+    fn rate(&self, exchange: &str, count: u64, now: Instant) -> f64 {
+        let (at, messages) = match &self.previous {
+            Some(previous) => previous,
+            None => return 0.0,
+        };
+        let elapsed = now.duration_since(*at).as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0.0;
+        }
+
+        let previous = messages.get(exchange).copied().unwrap_or_default();
+        count.saturating_sub(previous) as f64 / elapsed
+    }
+}
+
+/// Returns the positions sent with the status report
+pub fn positions() -> Box<[PositionReport]> {
+    crate::status::read(|status| {
+        status
+            .positions
+            .iter()
+            .map(|position| PositionReport {
+                exchange: Box::from(position.exchange.as_str()),
+                market: position.market.clone(),
+                size: position.size,
+                avg_entry_price: position.avg_entry_price,
+                realized_pnl: position.realized_pnl,
+                unrealized_pnl: position.unrealized_pnl,
+            })
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_reporter_rates() {
+        let registry = crate::metrics::registry();
+        let labels = MetricLabels::new().exchange("report-test");
+        let exchanges = [Box::from("report-test")];
+        let markets = [Box::from("BTC-PERP")];
+        let mut reporter = StatusReporter::default();
+
+        registry.set_counter("market_data_messages_total", &labels, 10);
+        let report = reporter.report(&exchanges, &markets);
+        assert_eq!(1, report.feeds.len());
+        assert!(!report.feeds[0].connected);
+        assert_eq!(0.0, report.feeds[0].messages_per_second);
+
+        registry.set_gauge("market_data_connected", &labels, 1.0);
+        registry.set_counter("market_data_messages_total", &labels, 20);
+        std::thread::sleep(Duration::from_millis(1));
+        let report = reporter.report(&exchanges, &markets);
+        assert!(report.feeds[0].connected);
+        assert!(report.feeds[0].messages_per_second > 0.0);
+        assert_eq!(&markets[..], &*report.feeds[0].markets);
+    }
+}
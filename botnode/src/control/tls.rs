@@ -0,0 +1,204 @@
+//! TLS for the connection to botvana-server
+//!
+//! When `[control.tls]` is configured the control engine wraps the TCP
+//! connection in TLS and verifies the server certificate against the
+//! configured CA, or the webpki roots when no CA is given. A client
+//! certificate is presented when the server requires mutual TLS.
+
+use std::{
+    io,
+    path::PathBuf,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::io::{AsyncRead, AsyncWrite};
+use serde::Deserialize;
+
+use crate::rt::net::TcpStream;
+
+/// TLS configuration of the control connection
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    /// PEM file with the CA certificates the server certificate is verified
+    /// against, the webpki roots are used when not set
+    pub ca_file: Option<PathBuf>,
+    /// Name the server certificate is issued for, defaults to the host of
+    /// `server_addr`
+    pub server_name: Option<String>,
+    /// PEM file with the client certificate chain
+    pub cert_file: Option<PathBuf>,
+    /// PEM file with the PKCS#8 or RSA private key of the client certificate
+    pub key_file: Option<PathBuf>,
+}
+
+impl TlsConfig {
+    /// Returns description of the first invalid value
+    pub fn validate(&self) -> Result<(), String> {
+        if self.cert_file.is_some() != self.key_file.is_some() {
+            return Err("cert_file and key_file must be set together".to_string());
+        }
+        if !cfg!(feature = "tls") {
+            return Err("requires botnode built with the `tls` feature".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the name to verify the certificate of the server at given
+    /// address against
+    pub fn server_name<'a>(&'a self, server_addr: &'a str) -> &'a str {
+        match &self.server_name {
+            Some(name) => name,
+            None => server_addr
+                .rsplit_once(':')
+                .map_or(server_addr, |(host, _)| host),
+        }
+    }
+}
+
+/// Connection to botvana-server, encrypted when TLS is configured
+pub enum ControlStream {
+    Plain(TcpStream),
+    #[cfg(feature = "tls")]
+    Tls(Box<async_tls::client::TlsStream<TcpStream>>),
+}
+
+/// Opens TLS connector of the configuration
+///
+/// Reads the certificate files, failing when they are missing or invalid.
+#[cfg(feature = "tls")]
+pub fn connector(config: &TlsConfig) -> Result<async_tls::TlsConnector, TlsError> {
+    use std::{fs::File, io::BufReader, sync::Arc};
+
+    use rustls::internal::pemfile;
+
+    let open = |path: &PathBuf| {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(|e| TlsError::new(format!("failed to open {}: {e}", path.display())))
+    };
+
+    let mut client_config = rustls::ClientConfig::new();
+    match &config.ca_file {
+        Some(path) => {
+            let (valid, _) = client_config
+                .root_store
+                .add_pem_file(&mut open(path)?)
+                .map_err(|_| TlsError::new(format!("invalid CA file {}", path.display())))?;
+            if valid == 0 {
+                return Err(TlsError::new(format!(
+                    "no CA certificates in {}",
+                    path.display()
+                )));
+            }
+        }
+        None => client_config
+            .root_store
+            .add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS),
+    }
+
+    if let (Some(cert_file), Some(key_file)) = (&config.cert_file, &config.key_file) {
+        let certs = pemfile::certs(&mut open(cert_file)?).map_err(|_| {
+            TlsError::new(format!("invalid certificate file {}", cert_file.display()))
+        })?;
+        let mut keys = pemfile::pkcs8_private_keys(&mut open(key_file)?).unwrap_or_default();
+        if keys.is_empty() {
+            keys = pemfile::rsa_private_keys(&mut open(key_file)?).unwrap_or_default();
+        }
+        let key = keys
+            .into_iter()
+            .next()
+            .ok_or_else(|| TlsError::new(format!("no private key in {}", key_file.display())))?;
+
+        client_config
+            .set_single_client_cert(certs, key)
+            .map_err(|e| TlsError::new(format!("invalid client certificate: {e}")))?;
+    }
+
+    Ok(async_tls::TlsConnector::from(Arc::new(client_config)))
+}
+
+impl AsyncRead for ControlStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            ControlStream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            #[cfg(feature = "tls")]
+            ControlStream::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for ControlStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            ControlStream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            #[cfg(feature = "tls")]
+            ControlStream::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ControlStream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            #[cfg(feature = "tls")]
+            ControlStream::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            ControlStream::Plain(stream) => Pin::new(stream).poll_close(cx),
+            #[cfg(feature = "tls")]
+            ControlStream::Tls(stream) => Pin::new(stream.as_mut()).poll_close(cx),
+        }
+    }
+}
+
+/// Error setting up TLS
+#[derive(Debug, thiserror::Error)]
+#[error("TLS error: {source}")]
+pub struct TlsError {
+    pub source: Box<dyn std::error::Error>,
+}
+
+impl TlsError {
+    pub fn new<T: ToString>(msg: T) -> Self {
+        Self {
+            source: msg.to_string().into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tls_server_name() {
+        let mut config = TlsConfig::default();
+        assert_eq!("botvana.local", config.server_name("botvana.local:7978"));
+
+        config.server_name = Some("server.botvana".to_string());
+        assert_eq!("server.botvana", config.server_name("10.0.0.1:7978"));
+    }
+
+    #[test]
+    fn test_tls_validate_client_cert() {
+        let config = TlsConfig {
+            cert_file: Some("client.pem".into()),
+            ..TlsConfig::default()
+        };
+
+        assert!(config.validate().is_err());
+    }
+}
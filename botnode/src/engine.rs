@@ -1,19 +1,48 @@
-use crate::prelude::*;
+use tracing::Instrument;
+
+use crate::{prelude::*, rt::EngineExecutor};
 use botvana::exchange::ExchangeId;
 
+pub mod shutdown;
+pub mod supervisor;
+
+pub use shutdown::{ShutdownConfig, ShutdownCoordinator, ShutdownPhase};
+pub use supervisor::{spawn_supervised, RestartPolicy, SupervisorConfig};
+
 /// Botnode engines type
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub enum EngineType {
+    AlertEngine,
     AuditEngine,
     ControlEngine,
-    ExchangeEngine,
+    OrderEngine,
     IndicatorEngine,
+    PositionEngine,
+    RecorderEngine,
     MarketDataEngine(ExchangeId),
     TradingEngine,
 }
 
+impl EngineType {
+    /// Returns stable identifier of the engine type
+    pub fn name(&self) -> String {
+        match self {
+            EngineType::AlertEngine => "alert-engine".to_string(),
+            EngineType::AuditEngine => "audit-engine".to_string(),
+            EngineType::ControlEngine => "control-engine".to_string(),
+            EngineType::OrderEngine => "order-engine".to_string(),
+            EngineType::IndicatorEngine => "indicator-engine".to_string(),
+            EngineType::PositionEngine => "position-engine".to_string(),
+            EngineType::RecorderEngine => "recorder-engine".to_string(),
+            EngineType::MarketDataEngine(exchange) => format!("market-data-{}", exchange.as_str()),
+            EngineType::TradingEngine => "trading-engine".to_string(),
+        }
+    }
+}
+
 /// Engine status
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum EngineStatus {
     /// The engine is booting
     Booting,
@@ -25,12 +54,120 @@ pub enum EngineStatus {
     Error,
 }
 
+impl EngineStatus {
+    /// Returns lower-case name of the status
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EngineStatus::Booting => "booting",
+            EngineStatus::Running => "running",
+            EngineStatus::ShuttingDown => "shutting_down",
+            EngineStatus::Error => "error",
+        }
+    }
+}
+
 impl Default for EngineStatus {
     fn default() -> Self {
         Self::Booting
     }
 }
 
+/// Engine spawned onto its executor
+pub struct SpawnedEngine {
+    pub r#type: EngineType,
+    pub status_rx: spsc_queue::Consumer<EngineStatus>,
+    pub handle: EngineHandle,
+}
+
+/// State of the executor running an engine
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EngineState {
+    Running,
+    /// The engine returned or was stopped
+    Stopped,
+    /// The engine returned an error
+    Errored,
+}
+
+/// Handle of an engine running on its executor
+///
+/// Every engine has its own shutdown, triggered by [`EngineHandle::stop`] or
+/// by the shutdown of the whole bot.
+#[derive(Clone)]
+pub struct EngineHandle {
+    name: String,
+    state: std::sync::Arc<std::sync::Mutex<EngineState>>,
+    shutdown: Shutdown,
+    stopped: Shutdown,
+}
+
+impl EngineHandle {
+    /// Spawns executor pinned to the CPU of `executor` running the future
+    /// `run` produces with the shutdown of the engine
+    pub(crate) fn spawn<G, F>(
+        name: &str,
+        executor: EngineExecutor,
+        shutdown: Shutdown,
+        run: G,
+    ) -> Result<Self, StartEngineError>
+    where
+        G: FnOnce(Shutdown) -> F + Send + 'static,
+        F: Future<Output = Result<(), EngineError>> + 'static,
+    {
+        let handle = Self {
+            name: name.to_string(),
+            state: Default::default(),
+            shutdown: Shutdown::new(),
+            stopped: Shutdown::new(),
+        };
+
+        let engine = handle.clone();
+        crate::rt::spawn_executor(name, Some(executor), move || async move {
+            let engine_shutdown = engine.shutdown.clone();
+            crate::rt::spawn_local(async move {
+                shutdown.wait_shutdown_triggered().await;
+                engine_shutdown.shutdown();
+            });
+
+            let state = match run(engine.shutdown.clone()).await {
+                Ok(()) => EngineState::Stopped,
+                Err(_) => EngineState::Errored,
+            };
+            *engine.state.lock().unwrap() = state;
+            engine.stopped.shutdown();
+        })?;
+
+        Ok(handle)
+    }
+
+    /// Returns name of the engine
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns current state of the engine
+    pub fn status(&self) -> EngineState {
+        *self.state.lock().unwrap()
+    }
+
+    /// Triggers the shutdown of this engine only
+    pub fn stop(&self) {
+        self.shutdown.shutdown();
+    }
+
+    /// Waits until the engine returns and returns its final state
+    pub async fn join(self) -> EngineState {
+        self.stopped.wait_shutdown_triggered().await;
+        self.status()
+    }
+}
+
+impl Default for EngineState {
+    fn default() -> Self {
+        Self::Running
+    }
+}
+
 /// Engine trait
 #[async_trait(?Send)]
 pub trait Engine {
@@ -40,6 +177,11 @@ pub trait Engine {
     /// Returns engine health receiver
     fn status_rx(&self) -> spsc_queue::Consumer<EngineStatus>;
 
+    /// Returns whether the bot shuts down when the engine fails for good
+    fn essential(&self) -> bool {
+        true
+    }
+
     /// Start the engine loop
     async fn start(self, shutdown: Shutdown) -> Result<(), EngineError>;
 }
@@ -92,6 +234,13 @@ pub trait EngineData {
 
 /// Starts given engine in new executor pinned to given CPU.
 ///
+/// The engine is not restarted, when an essential engine fails the bot is
+/// shut down. See [`spawn_supervised`] for engines that can be restarted.
+///
+/// `executor` is the CPU, or the CPU with the executor settings from
+/// [`ExecutorConfig`](crate::rt::ExecutorConfig). Pinning and the settings
+/// are no-ops on platforms without glommio, see [`crate::rt`].
+///
 /// # Examples
 ///
 /// ```
@@ -117,22 +266,38 @@ pub trait EngineData {
 /// spawn_engine(0, ExampleEngine {}, Shutdown::new()).unwrap();
 /// ```
 pub fn spawn_engine<E: Engine + Send + 'static>(
-    cpu: usize,
+    executor: impl Into<EngineExecutor>,
     engine: E,
     shutdown: Shutdown,
-) -> Result<glommio::ExecutorJoinHandle<()>, StartEngineError> {
-    LocalExecutorBuilder::new(Placement::Fixed(cpu))
-        .spin_before_park(std::time::Duration::from_micros(250))
-        .name(&engine.name())
-        .spawn(move || async move {
-            match engine.start(shutdown).await {
-                Ok(_handle) => {}
+) -> Result<EngineHandle, StartEngineError> {
+    let name = engine.name();
+    let essential = engine.essential();
+    // Engine span is parented by the spawning span so fields like bot id
+    // propagate across executor threads
+    let parent = tracing::Span::current();
+
+    EngineHandle::spawn(
+        &name.clone(),
+        executor.into(),
+        shutdown.clone(),
+        move |engine_shutdown| async move {
+            let span = tracing::info_span!(parent: &parent, "engine", engine = %name);
+
+            let res = engine.start(engine_shutdown.clone()).instrument(span).await;
+            match &res {
+                Ok(()) => {}
+                Err(e) if essential && !engine_shutdown.shutdown_started() => {
+                    error!("Essential engine {name} failed, shutting down: {e}");
+                    shutdown.shutdown();
+                }
                 Err(e) => {
                     error!("Error starting the engine: {:?}", e);
                 }
             }
-        })
-        .map_err(StartEngineError::from)
+
+            res
+        },
+    )
 }
 
 /// Awaits until a value is produced on a given spsc_queue channel
@@ -168,10 +333,32 @@ mod tests {
                 if shutdown.shutdown_started() {
                     return Ok(());
                 }
+                crate::rt::timer::sleep(Duration::from_millis(1)).await;
             }
         }
     }
 
+    struct FailingEngine;
+
+    #[async_trait(?Send)]
+    impl Engine for FailingEngine {
+        fn name(&self) -> String {
+            "failing-engine".to_string()
+        }
+
+        fn status_rx(&self) -> spsc_queue::Consumer<EngineStatus> {
+            unimplemented!();
+        }
+
+        fn essential(&self) -> bool {
+            false
+        }
+
+        async fn start(self, _shutdown: Shutdown) -> Result<(), EngineError> {
+            Err(EngineError::ChannelClosed("test-engine"))
+        }
+    }
+
     fn test_engine() -> TestEngine {
         TestEngine(vec![])
     }
@@ -187,4 +374,25 @@ mod tests {
 
         assert!(shutdown.shutdown_completed());
     }
+
+    #[test]
+    fn test_engine_handle_stop() {
+        let shutdown = Shutdown::new();
+        let handle = spawn_engine(0, test_engine(), shutdown.clone()).unwrap();
+        assert_eq!("test-engine", handle.name());
+        assert_eq!(EngineState::Running, handle.status());
+
+        handle.stop();
+
+        assert_eq!(EngineState::Stopped, crate::rt::block_on(handle.join()));
+        // Only the engine was stopped
+        assert!(!shutdown.shutdown_started());
+    }
+
+    #[test]
+    fn test_engine_handle_errored() {
+        let handle = spawn_engine(0, FailingEngine, Shutdown::new()).unwrap();
+
+        assert_eq!(EngineState::Errored, crate::rt::block_on(handle.join()));
+    }
 }
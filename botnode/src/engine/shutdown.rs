@@ -0,0 +1,223 @@
+//! Ordered shutdown of the engines
+//!
+//! Engines are registered with the [`ShutdownPhase`] they stop in. When the
+//! bot shuts down the engines are stopped phase by phase and each one is
+//! waited for before the next is stopped: strategies stop placing orders
+//! first, then the order engine cancels the open orders, the audit engine
+//! flushes the audit log and finally the market data and control engines
+//! close their connections.
+//!
+//! The whole shutdown has to finish within the configured deadline,
+//! engines still running after it are left behind and the process exits.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::future::{self, Either};
+use serde::Deserialize;
+use tracing::{error, info, warn};
+
+use super::{EngineHandle, EngineState};
+use crate::rt::timer::sleep;
+
+/// Group of engines stopped together, in the order of the variants
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ShutdownPhase {
+    /// Trading and indicator engines, no new orders are placed
+    Strategies,
+    /// Order and position engines, the open orders are cancelled
+    Orders,
+    /// Audit engine, the audit log is flushed
+    Audit,
+    /// Market data, recorder, alert and control engines
+    Connections,
+}
+
+impl ShutdownPhase {
+    const ALL: [ShutdownPhase; 4] = [
+        ShutdownPhase::Strategies,
+        ShutdownPhase::Orders,
+        ShutdownPhase::Audit,
+        ShutdownPhase::Connections,
+    ];
+
+    /// Returns lower-case name of the phase
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShutdownPhase::Strategies => "strategies",
+            ShutdownPhase::Orders => "orders",
+            ShutdownPhase::Audit => "audit",
+            ShutdownPhase::Connections => "connections",
+        }
+    }
+}
+
+/// Shutdown configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShutdownConfig {
+    /// Seconds the engines have to stop before the process exits anyway
+    pub deadline_secs: u64,
+}
+
+impl ShutdownConfig {
+    pub fn deadline(&self) -> Duration {
+        Duration::from_secs(self.deadline_secs)
+    }
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self { deadline_secs: 10 }
+    }
+}
+
+/// Stops registered engines in the order of their phases
+///
+/// Engines are spawned with the shutdown of their phase. An essential engine
+/// failing triggers the shutdown of its phase, which stops the other engines
+/// of the phase right away, the remaining phases are stopped in order.
+#[derive(Clone)]
+pub struct ShutdownCoordinator {
+    phases: Arc<[async_shutdown::Shutdown; 4]>,
+    engines: Arc<Mutex<Vec<(ShutdownPhase, EngineHandle)>>>,
+    deadline: Duration,
+}
+
+impl ShutdownCoordinator {
+    pub fn new(config: &ShutdownConfig) -> Self {
+        Self {
+            phases: Arc::new(ShutdownPhase::ALL.map(|_| async_shutdown::Shutdown::new())),
+            engines: Default::default(),
+            deadline: config.deadline(),
+        }
+    }
+
+    /// Returns shutdown the engines of the phase are spawned with
+    pub fn phase(&self, phase: ShutdownPhase) -> async_shutdown::Shutdown {
+        self.phases[phase as usize].clone()
+    }
+
+    /// Registers engine to be stopped in given phase
+    pub fn register(&self, phase: ShutdownPhase, handle: EngineHandle) {
+        self.engines.lock().unwrap().push((phase, handle));
+    }
+
+    /// Waits until the shutdown of any phase is triggered, e.g. by a failed
+    /// essential engine
+    pub async fn wait_triggered(&self) {
+        future::select_all(
+            self.phases
+                .iter()
+                .map(|phase| Box::pin(phase.wait_shutdown_triggered())),
+        )
+        .await;
+    }
+
+    /// Stops the engines phase by phase, returns whether all of them stopped
+    /// before the deadline
+    pub async fn shutdown(&self) -> bool {
+        let started = Instant::now();
+        let mut engines = std::mem::take(&mut *self.engines.lock().unwrap());
+        // Stable sort keeps the registration order within the phase
+        engines.sort_by_key(|(phase, _)| *phase);
+
+        let mut completed = true;
+        for (phase, handle) in engines {
+            let name = handle.name().to_string();
+            handle.stop();
+
+            let remaining = self.deadline.saturating_sub(started.elapsed());
+            if !completed || remaining.is_zero() {
+                completed = false;
+                continue;
+            }
+
+            let join = Box::pin(handle.join());
+            match future::select(join, Box::pin(sleep(remaining))).await {
+                Either::Left((EngineState::Errored, _)) => {
+                    warn!("Engine {name} failed ({})", phase.as_str())
+                }
+                Either::Left(_) => info!("Engine {name} stopped ({})", phase.as_str()),
+                Either::Right(_) => {
+                    error!("Engine {name} did not stop before the shutdown deadline");
+                    completed = false;
+                }
+            }
+        }
+
+        // Engines spawned without being registered stop with their phase
+        for phase in ShutdownPhase::ALL {
+            self.phase(phase).shutdown();
+        }
+
+        completed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Stopped = Arc<Mutex<Vec<&'static str>>>;
+
+    fn spawn(
+        name: &'static str,
+        shutdown: async_shutdown::Shutdown,
+        stop_after: Duration,
+        stopped: Stopped,
+    ) -> EngineHandle {
+        EngineHandle::spawn(name, 0.into(), shutdown, move |shutdown| async move {
+            shutdown.wait_shutdown_triggered().await;
+            sleep(stop_after).await;
+            stopped.lock().unwrap().push(name);
+            Ok(())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_shutdown_in_phase_order() {
+        let coordinator = ShutdownCoordinator::new(&ShutdownConfig::default());
+        let stopped = Stopped::default();
+        let phases = [
+            (ShutdownPhase::Connections, "market-data", 0),
+            (ShutdownPhase::Audit, "audit", 0),
+            (ShutdownPhase::Strategies, "trading", 20),
+            (ShutdownPhase::Orders, "order", 10),
+            (ShutdownPhase::Connections, "control", 0),
+        ];
+        for (phase, name, stop_after) in phases {
+            let handle = spawn(
+                name,
+                coordinator.phase(phase),
+                Duration::from_millis(stop_after),
+                stopped.clone(),
+            );
+            coordinator.register(phase, handle);
+        }
+
+        assert!(crate::rt::block_on(coordinator.shutdown()));
+        assert_eq!(
+            vec!["trading", "order", "audit", "market-data", "control"],
+            *stopped.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_shutdown_deadline() {
+        let coordinator = ShutdownCoordinator::new(&ShutdownConfig { deadline_secs: 0 });
+        let engine = spawn(
+            "slow",
+            coordinator.phase(ShutdownPhase::Strategies),
+            Duration::from_secs(1),
+            Stopped::default(),
+        );
+        coordinator.register(ShutdownPhase::Strategies, engine.clone());
+
+        assert!(!crate::rt::block_on(coordinator.shutdown()));
+        assert_eq!(EngineState::Running, engine.status());
+    }
+}
@@ -0,0 +1,267 @@
+//! Supervision of engines
+//!
+//! Engines spawned with [`spawn_supervised`] are created by a factory on
+//! their executor and created again after they fail, as far as their
+//! [`RestartPolicy`] allows. Engines spawned with
+//! [`spawn_engine`](super::spawn_engine) own channels that can't be
+//! recreated, they are never restarted. Either way, once an essential engine
+//! fails for good the bot is shut down instead of running without it.
+
+use std::time::Duration;
+
+use futures::future::{self, Either};
+use serde::Deserialize;
+use tracing::{error, warn, Instrument};
+
+use super::{Engine, EngineHandle, EngineStatus, EngineType, SpawnedEngine};
+use crate::{
+    error::{EngineError, StartEngineError},
+    rt::{spsc_queue, EngineExecutor},
+    util::backoff::{Backoff, BackoffConfig},
+};
+
+/// How often statuses of a supervised engine are forwarded
+const STATUS_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What to do when an engine fails
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RestartPolicy {
+    /// Restart right away
+    Always,
+    /// Restart after the backoff delay
+    Backoff,
+    /// Leave the engine stopped
+    Never,
+}
+
+/// Supervision configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SupervisorConfig {
+    /// Restarts after which a failing engine is given up on
+    pub max_restarts: u32,
+    /// Delay between restarts of engines with the `backoff` policy
+    pub backoff: BackoffConfig,
+}
+
+impl Default for SupervisorConfig {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            backoff: BackoffConfig::default(),
+        }
+    }
+}
+
+/// Decision taken when an engine exits
+#[derive(Debug, PartialEq)]
+pub enum Decision {
+    /// Leave the engine stopped
+    Stop,
+    /// Start the engine again after the delay
+    Restart(Duration),
+    /// Shut the bot down
+    Escalate,
+}
+
+/// Restart bookkeeping of one engine
+#[derive(Debug)]
+pub struct Supervisor {
+    policy: RestartPolicy,
+    essential: bool,
+    max_restarts: u32,
+    restarts: u32,
+    backoff: Backoff,
+}
+
+impl Supervisor {
+    pub fn new(policy: RestartPolicy, essential: bool, config: &SupervisorConfig) -> Self {
+        Self {
+            policy,
+            essential,
+            max_restarts: config.max_restarts,
+            restarts: 0,
+            backoff: Backoff::new(config.backoff.clone()),
+        }
+    }
+
+    /// Returns the number of restarts so far
+    pub fn restarts(&self) -> u32 {
+        self.restarts
+    }
+
+    /// Decides what to do with the engine that exited with given result
+    ///
+    /// Errors restarting can't help with, like invalid configuration, are
+    /// never retried.
+    pub fn on_exit(&mut self, result: &Result<(), EngineError>) -> Decision {
+        let err = match result {
+            Ok(()) => return Decision::Stop,
+            Err(err) => err,
+        };
+
+        let delay = match self.policy {
+            _ if !err.is_recoverable() || self.restarts >= self.max_restarts => None,
+            RestartPolicy::Always => Some(Duration::ZERO),
+            RestartPolicy::Backoff => self.backoff.next_delay(),
+            RestartPolicy::Never => None,
+        };
+
+        match delay {
+            Some(delay) => {
+                self.restarts += 1;
+                Decision::Restart(delay)
+            }
+            None if self.essential => Decision::Escalate,
+            None => Decision::Stop,
+        }
+    }
+}
+
+/// Spawns engine created by `make` on new executor pinned to given CPU and
+/// restarts it per `policy`
+///
+/// The factory runs on the executor, so the engine doesn't need to be
+/// `Send`, and every restart creates a new instance. Statuses of all the
+/// instances are reported on the status receiver of the returned engine.
+/// Stopping its handle stops the engine without a restart.
+pub fn spawn_supervised<E, F>(
+    executor: impl Into<EngineExecutor>,
+    r#type: EngineType,
+    policy: RestartPolicy,
+    config: SupervisorConfig,
+    shutdown: async_shutdown::Shutdown,
+    mut make: F,
+) -> Result<SpawnedEngine, StartEngineError>
+where
+    E: Engine + 'static,
+    F: FnMut() -> E + Send + 'static,
+{
+    let name = r#type.name();
+    let (status_tx, status_rx) = spsc_queue::make(16);
+    let parent = tracing::Span::current();
+
+    let handle = EngineHandle::spawn(
+        &name.clone(),
+        executor.into(),
+        shutdown.clone(),
+        move |engine_shutdown| async move {
+            let span = tracing::info_span!(parent: &parent, "engine", engine = %name);
+            let mut supervisor = None;
+
+            loop {
+                let engine = make();
+                let supervisor = supervisor
+                    .get_or_insert_with(|| Supervisor::new(policy, engine.essential(), &config));
+                let engine_status_rx = engine.status_rx();
+
+                let run = engine
+                    .start(engine_shutdown.clone())
+                    .instrument(span.clone());
+                let forward = forward_statuses(&engine_status_rx, &status_tx);
+                futures::pin_mut!(run, forward);
+                let result = match future::select(run, forward).await {
+                    Either::Left((result, _)) | Either::Right((result, _)) => result,
+                };
+                forward_pending(&engine_status_rx, &status_tx);
+
+                if engine_shutdown.shutdown_started() {
+                    break result;
+                }
+                if let Err(e) = &result {
+                    error!("{name} failed: {e}");
+                    status_tx.try_push(EngineStatus::Error);
+                }
+
+                match supervisor.on_exit(&result) {
+                    Decision::Stop => break result,
+                    Decision::Restart(delay) => {
+                        warn!(
+                            restarts = supervisor.restarts(),
+                            "Restarting {name} in {delay:?}"
+                        );
+                        crate::rt::timer::sleep(delay).await;
+                    }
+                    Decision::Escalate => {
+                        error!("Essential engine {name} keeps failing, shutting down");
+                        shutdown.shutdown();
+                        break result;
+                    }
+                }
+            }
+        },
+    )?;
+
+    Ok(SpawnedEngine {
+        r#type,
+        status_rx,
+        handle,
+    })
+}
+
+/// Forwards statuses of the current engine instance until it exits
+async fn forward_statuses(
+    rx: &spsc_queue::Consumer<EngineStatus>,
+    tx: &spsc_queue::Producer<EngineStatus>,
+) -> Result<(), EngineError> {
+    loop {
+        forward_pending(rx, tx);
+        crate::rt::timer::sleep(STATUS_POLL_INTERVAL).await;
+    }
+}
+
+fn forward_pending(
+    rx: &spsc_queue::Consumer<EngineStatus>,
+    tx: &spsc_queue::Producer<EngineStatus>,
+) {
+    while let Some(status) = rx.try_pop() {
+        tx.try_push(status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn io_error() -> EngineError {
+        EngineError::connect(std::io::Error::new(std::io::ErrorKind::Other, "test"))
+    }
+
+    #[test]
+    fn test_supervisor_restarts() {
+        let config = SupervisorConfig {
+            max_restarts: 2,
+            ..SupervisorConfig::default()
+        };
+        let mut supervisor = Supervisor::new(RestartPolicy::Always, true, &config);
+
+        assert_eq!(
+            Decision::Restart(Duration::ZERO),
+            supervisor.on_exit(&Err(io_error()))
+        );
+        assert_eq!(
+            Decision::Restart(Duration::ZERO),
+            supervisor.on_exit(&Err(io_error()))
+        );
+        assert_eq!(Decision::Escalate, supervisor.on_exit(&Err(io_error())));
+        assert_eq!(2, supervisor.restarts());
+    }
+
+    #[test]
+    fn test_supervisor_unrecoverable() {
+        let config = SupervisorConfig::default();
+        let config_error =
+            || EngineError::config(std::io::Error::new(std::io::ErrorKind::Other, "test"));
+
+        let mut essential = Supervisor::new(RestartPolicy::Backoff, true, &config);
+        assert_eq!(Decision::Escalate, essential.on_exit(&Err(config_error())));
+
+        let mut optional = Supervisor::new(RestartPolicy::Backoff, false, &config);
+        assert_eq!(Decision::Stop, optional.on_exit(&Err(config_error())));
+        assert_eq!(Decision::Stop, optional.on_exit(&Ok(())));
+
+        let mut never = Supervisor::new(RestartPolicy::Never, false, &config);
+        assert_eq!(Decision::Stop, never.on_exit(&Err(io_error())));
+    }
+}
@@ -1,18 +1,68 @@
-use crate::prelude::*;
+use std::error::Error;
 
 /// Error encountered while running the engine
+///
+/// The kind of the error tells supervision whether restarting the engine
+/// can help: lost connections and failing adapters usually recover,
+/// invalid configuration and shutdown don't.
 #[derive(Debug, thiserror::Error)]
-#[error("Error running the engine: {source}")]
-pub struct EngineError {
-    source: Box<dyn std::error::Error>,
+pub enum EngineError {
+    /// Connecting to a peer failed or the connection was lost
+    #[error("Connection error: {source}")]
+    Connect { source: Box<dyn Error> },
+    /// Message couldn't be encoded or decoded
+    #[error("Codec error: {source}")]
+    Codec { source: Box<dyn Error> },
+    /// Channel to another engine was closed
+    #[error("Channel to {0} closed")]
+    ChannelClosed(&'static str),
+    /// Configuration is invalid or missing
+    #[error("Configuration error: {source}")]
+    Config { source: Box<dyn Error> },
+    /// Market data or execution adapter failed
+    #[error("Adapter error: {source}")]
+    AdapterError { source: Box<dyn Error> },
+    /// Engine can't run because the bot is shutting down
+    #[error("Shutting down")]
+    Shutdown,
 }
 
 impl EngineError {
-    pub fn with_source<T: std::error::Error + 'static>(source: T) -> Self {
-        Self {
+    pub fn connect<T: Error + 'static>(source: T) -> Self {
+        Self::Connect {
             source: Box::new(source),
         }
     }
+
+    pub fn codec<T: Error + 'static>(source: T) -> Self {
+        Self::Codec {
+            source: Box::new(source),
+        }
+    }
+
+    pub fn config<T: Error + 'static>(source: T) -> Self {
+        Self::Config {
+            source: Box::new(source),
+        }
+    }
+
+    pub fn adapter<T: Error + 'static>(source: T) -> Self {
+        Self::AdapterError {
+            source: Box::new(source),
+        }
+    }
+
+    /// Returns whether restarting the engine can recover from the error
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            EngineError::Connect { .. }
+            | EngineError::Codec { .. }
+            | EngineError::AdapterError { .. } => true,
+            EngineError::ChannelClosed(_) | EngineError::Config { .. } | EngineError::Shutdown => {
+                false
+            }
+        }
+    }
 }
 
 /// Error encountered starting the engine
@@ -22,10 +72,27 @@ pub struct StartEngineError {
     pub source: Box<dyn std::error::Error>,
 }
 
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
 impl<T: 'static> From<glommio::GlommioError<T>> for StartEngineError {
-    fn from(err: GlommioError<T>) -> Self {
+    fn from(err: glommio::GlommioError<T>) -> Self {
         StartEngineError {
             source: Box::new(err),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_engine_error_is_recoverable() {
+        let io = || std::io::Error::new(std::io::ErrorKind::Other, "test");
+
+        assert!(EngineError::connect(io()).is_recoverable());
+        assert!(EngineError::adapter(io()).is_recoverable());
+        assert!(!EngineError::config(io()).is_recoverable());
+        assert!(!EngineError::ChannelClosed("trading-engine").is_recoverable());
+        assert!(!EngineError::Shutdown.is_recoverable());
+    }
+}
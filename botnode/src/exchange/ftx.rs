@@ -1,2 +0,0 @@
-#[derive(Debug, Clone)]
-struct Ftx;
@@ -1,7 +0,0 @@
-use super::adapter::ExchangeAdapter;
-
-pub(crate) struct NullAdapter;
-
-impl ExchangeAdapter for NullAdapter {
-    const NAME: &'static str = "null-adapter";
-}
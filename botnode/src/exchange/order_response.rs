@@ -1,2 +0,0 @@
-#[derive(Clone, Debug)]
-pub struct OrderResponse;
@@ -0,0 +1,417 @@
+//! Parquet export of recordings and audit logs
+//!
+//! [`ParquetExport`] converts the newline-delimited JSON written by the
+//! [recorder](crate::recorder) and the [audit engine](crate::audit) into
+//! Apache Parquet files, so they can be loaded by pandas, polars or any query
+//! engine without a bespoke parser. Files are partitioned Hive style, market
+//! data by date and market and audit events by date:
+//!
+//! ```text
+//! <output>/trades/date=2022-06-01/market=BTC-PERP/part-0.parquet
+//! <output>/books/date=2022-06-01/market=BTC-PERP/part-0.parquet
+//! <output>/bbo/date=2022-06-01/market=BTC-PERP/part-0.parquet
+//! <output>/audit/date=2022-06-01/part-0.parquet
+//! ```
+//!
+//! The partition values are not repeated as columns inside the files.
+
+pub mod table;
+
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    fs::{self, File},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+
+use crate::{
+    prelude::*,
+    recorder::{read_recording, RecordedEvent},
+    util::rotating_log,
+};
+use table::{AuditRows, BboRows, BookRows, Rows, TradeRows};
+
+/// Number of rows buffered per partition before they are written as a row
+/// group
+const ROW_GROUP_LEN: usize = 64 * 1024;
+
+/// Export of recordings and audit logs into partitioned Parquet files
+pub struct ParquetExport {
+    output: PathBuf,
+    trades: Partitions<TradeRows>,
+    books: Partitions<BookRows>,
+    bbo: Partitions<BboRows>,
+    audit: Partitions<AuditRows>,
+    /// Events of types that have no table or could not be read
+    skipped: u64,
+}
+
+impl ParquetExport {
+    /// Creates export writing into the output directory
+    pub fn new(output: &Path) -> Self {
+        Self {
+            output: output.to_path_buf(),
+            trades: Partitions::new("trades"),
+            books: Partitions::new("books"),
+            bbo: Partitions::new("bbo"),
+            audit: Partitions::new("audit"),
+            skipped: 0,
+        }
+    }
+
+    /// Exports all events of the recording in the directory
+    pub fn export_recording(&mut self, dir: &Path) -> Result<(), ExportError> {
+        for recorded in read_recording(dir).map_err(ExportError::with_source)? {
+            match recorded {
+                Ok(recorded) => self.write_event(&recorded)?,
+                Err(e) => {
+                    warn!("Skipping unreadable recorded event: {e}");
+                    self.skipped += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Exports all events of the audit log in the directory
+    pub fn export_audit_log(&mut self, dir: &Path) -> Result<(), ExportError> {
+        let events = rotating_log::read_log::<serde_json::Value>(dir, crate::audit::log::LOG_NAME)
+            .map_err(ExportError::with_source)?;
+
+        for event in events {
+            match event {
+                Ok(event) => self.write_audit_event(&event)?,
+                Err(e) => {
+                    warn!("Skipping unreadable audit event: {e}");
+                    self.skipped += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds the recorded market event to the tables
+    ///
+    /// Trades, orderbook snapshots and deltas and BBO updates are exported,
+    /// other event types are skipped.
+    pub fn write_event(&mut self, recorded: &RecordedEvent) -> Result<(), ExportError> {
+        let RecordedEvent { exchange, event } = recorded;
+        let timestamp = millis(event.timestamp);
+        let date = date(timestamp);
+
+        match &event.r#type {
+            MarketEventType::Trades(market, trades) => {
+                self.trades
+                    .push(&self.output, &market_partition(&date, market), |rows| {
+                        for trade in trades.iter() {
+                            rows.push(timestamp, exchange, trade);
+                        }
+                    })
+            }
+            MarketEventType::OrderbookUpdate(market, book) => {
+                self.books
+                    .push(&self.output, &market_partition(&date, market), |rows| {
+                        rows.push(timestamp, exchange, None, &book.bids, &book.asks)
+                    })
+            }
+            MarketEventType::OrderbookDelta(market, delta) => {
+                self.books
+                    .push(&self.output, &market_partition(&date, market), |rows| {
+                        rows.push(
+                            timestamp,
+                            exchange,
+                            Some(delta.sequence),
+                            &delta.bids,
+                            &delta.asks,
+                        )
+                    })
+            }
+            MarketEventType::BboUpdate(market, bbo) => {
+                self.bbo
+                    .push(&self.output, &market_partition(&date, market), |rows| {
+                        rows.push(timestamp, exchange, bbo)
+                    })
+            }
+            _ => {
+                self.skipped += 1;
+                Ok(())
+            }
+        }
+    }
+
+    /// Adds the audit event, as written into the audit log, to the audit
+    /// table
+    pub fn write_audit_event(&mut self, event: &serde_json::Value) -> Result<(), ExportError> {
+        let timestamp = match event["timestamp"].as_u64() {
+            Some(timestamp) => timestamp as i64,
+            None => {
+                warn!("Skipping audit event without timestamp: {event}");
+                self.skipped += 1;
+                return Ok(());
+            }
+        };
+
+        self.audit.push(
+            &self.output,
+            Path::new(&format!("date={}", date(timestamp))),
+            |rows| rows.push(timestamp, event),
+        )
+    }
+
+    /// Writes out the buffered rows and closes all files
+    pub fn finish(self) -> Result<ExportSummary, ExportError> {
+        let mut summary = ExportSummary {
+            skipped: self.skipped,
+            ..ExportSummary::default()
+        };
+
+        self.trades.finish(&mut summary)?;
+        self.books.finish(&mut summary)?;
+        self.bbo.finish(&mut summary)?;
+        self.audit.finish(&mut summary)?;
+
+        Ok(summary)
+    }
+}
+
+/// Totals of a finished export
+#[derive(Clone, Debug, Default)]
+pub struct ExportSummary {
+    /// Written Parquet files
+    pub files: Vec<PathBuf>,
+    /// Rows written over all files
+    pub rows: u64,
+    /// Events that were not exported
+    pub skipped: u64,
+}
+
+impl std::fmt::Display for ExportSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "files = {}", self.files.len())?;
+        writeln!(f, "rows = {}", self.rows)?;
+        write!(f, "skipped = {}", self.skipped)
+    }
+}
+
+/// Open files of one table by partition
+struct Partitions<R> {
+    table: &'static str,
+    open: BTreeMap<PathBuf, Partition<R>>,
+}
+
+struct Partition<R> {
+    writer: ArrowWriter<File>,
+    rows: R,
+    written: u64,
+}
+
+impl<R: Rows> Partitions<R> {
+    fn new(table: &'static str) -> Self {
+        Self {
+            table,
+            open: BTreeMap::new(),
+        }
+    }
+
+    /// Adds rows to the partition, creating its file first when needed
+    fn push(
+        &mut self,
+        output: &Path,
+        partition: &Path,
+        push: impl FnOnce(&mut R),
+    ) -> Result<(), ExportError> {
+        let path = output
+            .join(self.table)
+            .join(partition)
+            .join("part-0.parquet");
+        let partition = match self.open.entry(path) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let path = entry.key();
+                fs::create_dir_all(path.parent().unwrap_or(output))
+                    .map_err(ExportError::with_source)?;
+                let file = File::create(path).map_err(ExportError::with_source)?;
+                let props = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .build();
+                let writer = ArrowWriter::try_new(file, R::schema(), Some(props))
+                    .map_err(ExportError::with_source)?;
+
+                entry.insert(Partition {
+                    writer,
+                    rows: R::default(),
+                    written: 0,
+                })
+            }
+        };
+
+        push(&mut partition.rows);
+        if partition.rows.len() >= ROW_GROUP_LEN {
+            partition.write_rows()?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(self, summary: &mut ExportSummary) -> Result<(), ExportError> {
+        for (path, mut partition) in self.open {
+            partition.write_rows()?;
+            partition.writer.close().map_err(ExportError::with_source)?;
+
+            summary.rows += partition.written;
+            summary.files.push(path);
+        }
+
+        Ok(())
+    }
+}
+
+impl<R: Rows> Partition<R> {
+    /// Writes the buffered rows as a row group
+    fn write_rows(&mut self) -> Result<(), ExportError> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+
+        self.written += self.rows.len() as u64;
+        let batch = self.rows.take_batch().map_err(ExportError::with_source)?;
+        self.writer.write(&batch).map_err(ExportError::with_source)
+    }
+}
+
+/// Returns milliseconds since UNIX epoch
+fn millis(time: SystemTime) -> i64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+/// Returns UTC date of the timestamp in milliseconds
+fn date(timestamp: i64) -> String {
+    DateTime::<Utc>::from(SystemTime::UNIX_EPOCH + Duration::from_millis(timestamp.max(0) as u64))
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+/// Returns date and market partition directory
+///
+/// Market names are percent-encoded as Hive partition values, `BTC/USD`
+/// becomes `market=BTC%2FUSD`.
+fn market_partition(date: &str, market: &str) -> PathBuf {
+    let mut encoded = String::with_capacity(market.len());
+    for byte in market.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    Path::new(&format!("date={date}")).join(format!("market={encoded}"))
+}
+
+/// Export error
+#[derive(Debug, thiserror::Error)]
+#[error("Export error: {source}")]
+pub struct ExportError {
+    pub source: Box<dyn std::error::Error>,
+}
+
+impl ExportError {
+    pub fn with_source(err: impl std::error::Error + 'static) -> Self {
+        Self {
+            source: Box::new(err),
+        }
+    }
+
+    pub fn convert_error(err: String) -> Self {
+        Self { source: err.into() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    use super::*;
+    use crate::audit::{AuditEvent, Category, Severity};
+    use botvana::market::trade::Trade;
+
+    fn recorded(r#type: MarketEventType) -> RecordedEvent {
+        let mut event = MarketEvent::new(r#type);
+        event.timestamp = SystemTime::UNIX_EPOCH + Duration::from_secs(1_654_041_600);
+
+        RecordedEvent {
+            exchange: Box::from("ftx"),
+            event,
+        }
+    }
+
+    fn num_rows(path: &Path) -> i64 {
+        let reader = SerializedFileReader::new(File::open(path).unwrap()).unwrap();
+        reader.metadata().file_metadata().num_rows()
+    }
+
+    #[test]
+    fn test_export_partitions() {
+        let dir = std::env::temp_dir().join(format!("botnode-export-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut export = ParquetExport::new(&dir);
+
+        let trades = [
+            Trade::new(100.0, 1.0, Utc::now()),
+            Trade::new(101.0, 2.0, Utc::now()),
+        ];
+        export
+            .write_event(&recorded(MarketEventType::Trades(
+                Box::from("BTC/USD"),
+                Box::new(trades),
+            )))
+            .unwrap();
+        let mut book = PlainOrderbook::new();
+        book.bids = PriceLevelsVec::from_tuples_vec(&[(99.0, 1.0), (100.0, 1.0)]);
+        book.asks = PriceLevelsVec::from_tuples_vec(&[(101.0, 1.0)]);
+        export
+            .write_event(&recorded(MarketEventType::OrderbookUpdate(
+                Box::from("ETH-PERP"),
+                Box::new(book),
+            )))
+            .unwrap();
+        export
+            .write_event(&recorded(MarketEventType::MidPriceChange(
+                Box::from("ETH-PERP"),
+                100.0,
+                101.0,
+            )))
+            .unwrap();
+        let event = AuditEvent::new(Severity::Info, Category::Order, "test", "placed");
+        export
+            .write_audit_event(&serde_json::to_value(&event).unwrap())
+            .unwrap();
+
+        let summary = export.finish().unwrap();
+        assert_eq!(
+            (3, 6, 1),
+            (summary.files.len(), summary.rows, summary.skipped)
+        );
+        assert_eq!(
+            2,
+            num_rows(&dir.join("trades/date=2022-06-01/market=BTC%2FUSD/part-0.parquet"))
+        );
+        assert_eq!(
+            3,
+            num_rows(&dir.join("books/date=2022-06-01/market=ETH-PERP/part-0.parquet"))
+        );
+        assert!(summary
+            .files
+            .iter()
+            .any(|path| path.starts_with(dir.join("audit"))));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
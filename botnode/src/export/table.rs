@@ -0,0 +1,310 @@
+//! Row buffers and schemas of the exported tables
+//!
+//! Every table starts with `timestamp`, the time the event was recorded in
+//! milliseconds since UNIX epoch, stored as a timestamp without time zone
+//! that is always UTC.
+
+use std::sync::Arc;
+
+use arrow::{
+    array::{
+        ArrayRef, BooleanArray, Float64Array, StringArray, TimestampMillisecondArray, UInt64Array,
+    },
+    datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+use botvana::market::trade::Trade;
+
+use crate::prelude::*;
+
+/// Buffered rows of a table
+pub trait Rows: Default {
+    /// Returns schema of the table
+    fn schema() -> SchemaRef;
+
+    /// Returns number of buffered rows
+    fn len(&self) -> usize;
+
+    /// Returns whether no rows are buffered
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Takes the buffered rows as a record batch
+    fn take_batch(&mut self) -> Result<RecordBatch, ArrowError>;
+}
+
+fn timestamp_field(name: &str) -> Field {
+    Field::new(
+        name,
+        DataType::Timestamp(TimeUnit::Millisecond, None),
+        false,
+    )
+}
+
+fn strings<T: AsRef<str>>(values: Vec<T>) -> ArrayRef {
+    Arc::new(StringArray::from_iter_values(values))
+}
+
+fn optional_strings(values: Vec<Option<String>>) -> ArrayRef {
+    Arc::new(values.into_iter().collect::<StringArray>())
+}
+
+/// Rows of the `trades` table, one per trade
+#[derive(Debug, Default)]
+pub struct TradeRows {
+    timestamp: Vec<i64>,
+    exchange: Vec<Box<str>>,
+    time: Vec<i64>,
+    price: Vec<f64>,
+    size: Vec<f64>,
+}
+
+impl TradeRows {
+    pub fn push(&mut self, timestamp: i64, exchange: &str, trade: &Trade) {
+        self.timestamp.push(timestamp);
+        self.exchange.push(Box::from(exchange));
+        self.time.push(trade.time.timestamp_millis());
+        self.price.push(trade.price);
+        self.size.push(trade.size);
+    }
+}
+
+impl Rows for TradeRows {
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            timestamp_field("timestamp"),
+            Field::new("exchange", DataType::Utf8, false),
+            // Time of the trade specified by the exchange
+            timestamp_field("time"),
+            Field::new("price", DataType::Float64, false),
+            Field::new("size", DataType::Float64, false),
+        ]))
+    }
+
+    fn len(&self) -> usize {
+        self.timestamp.len()
+    }
+
+    fn take_batch(&mut self) -> Result<RecordBatch, ArrowError> {
+        let rows = std::mem::take(self);
+
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(TimestampMillisecondArray::from(rows.timestamp)),
+                strings(rows.exchange),
+                Arc::new(TimestampMillisecondArray::from(rows.time)),
+                Arc::new(Float64Array::from(rows.price)),
+                Arc::new(Float64Array::from(rows.size)),
+            ],
+        )
+    }
+}
+
+/// Rows of the `books` table, one per price level of an orderbook snapshot
+/// or delta
+///
+/// Rows of a snapshot have no sequence, delta rows with zero size remove the
+/// level.
+#[derive(Debug, Default)]
+pub struct BookRows {
+    timestamp: Vec<i64>,
+    exchange: Vec<Box<str>>,
+    snapshot: Vec<bool>,
+    sequence: Vec<Option<u64>>,
+    side: Vec<&'static str>,
+    price: Vec<f64>,
+    size: Vec<f64>,
+}
+
+impl BookRows {
+    pub fn push(
+        &mut self,
+        timestamp: i64,
+        exchange: &str,
+        sequence: Option<u64>,
+        bids: &PriceLevelsVec<f64>,
+        asks: &PriceLevelsVec<f64>,
+    ) {
+        for (side, levels) in [("bid", bids), ("ask", asks)] {
+            for (price, size) in levels.price_vec.iter().zip(&levels.size_vec) {
+                self.timestamp.push(timestamp);
+                self.exchange.push(Box::from(exchange));
+                self.snapshot.push(sequence.is_none());
+                self.sequence.push(sequence);
+                self.side.push(side);
+                self.price.push(*price);
+                self.size.push(*size);
+            }
+        }
+    }
+}
+
+impl Rows for BookRows {
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            timestamp_field("timestamp"),
+            Field::new("exchange", DataType::Utf8, false),
+            Field::new("snapshot", DataType::Boolean, false),
+            Field::new("sequence", DataType::UInt64, true),
+            Field::new("side", DataType::Utf8, false),
+            Field::new("price", DataType::Float64, false),
+            Field::new("size", DataType::Float64, false),
+        ]))
+    }
+
+    fn len(&self) -> usize {
+        self.timestamp.len()
+    }
+
+    fn take_batch(&mut self) -> Result<RecordBatch, ArrowError> {
+        let rows = std::mem::take(self);
+
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(TimestampMillisecondArray::from(rows.timestamp)),
+                strings(rows.exchange),
+                Arc::new(BooleanArray::from(rows.snapshot)),
+                Arc::new(UInt64Array::from(rows.sequence)),
+                strings(rows.side),
+                Arc::new(Float64Array::from(rows.price)),
+                Arc::new(Float64Array::from(rows.size)),
+            ],
+        )
+    }
+}
+
+/// Rows of the `bbo` table, one per best bid and offer update
+#[derive(Debug, Default)]
+pub struct BboRows {
+    timestamp: Vec<i64>,
+    exchange: Vec<Box<str>>,
+    bid: Vec<f64>,
+    bid_size: Vec<f64>,
+    ask: Vec<f64>,
+    ask_size: Vec<f64>,
+}
+
+impl BboRows {
+    pub fn push(&mut self, timestamp: i64, exchange: &str, bbo: &Bbo<f64>) {
+        self.timestamp.push(timestamp);
+        self.exchange.push(Box::from(exchange));
+        self.bid.push(bbo.bid);
+        self.bid_size.push(bbo.bid_size);
+        self.ask.push(bbo.ask);
+        self.ask_size.push(bbo.ask_size);
+    }
+}
+
+impl Rows for BboRows {
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            timestamp_field("timestamp"),
+            Field::new("exchange", DataType::Utf8, false),
+            Field::new("bid", DataType::Float64, false),
+            Field::new("bid_size", DataType::Float64, false),
+            Field::new("ask", DataType::Float64, false),
+            Field::new("ask_size", DataType::Float64, false),
+        ]))
+    }
+
+    fn len(&self) -> usize {
+        self.timestamp.len()
+    }
+
+    fn take_batch(&mut self) -> Result<RecordBatch, ArrowError> {
+        let rows = std::mem::take(self);
+
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(TimestampMillisecondArray::from(rows.timestamp)),
+                strings(rows.exchange),
+                Arc::new(Float64Array::from(rows.bid)),
+                Arc::new(Float64Array::from(rows.bid_size)),
+                Arc::new(Float64Array::from(rows.ask)),
+                Arc::new(Float64Array::from(rows.ask_size)),
+            ],
+        )
+    }
+}
+
+/// Rows of the `audit` table, one per audit event
+///
+/// The structured record is kept as JSON, its kind and market are extracted
+/// into their own columns for filtering.
+#[derive(Debug, Default)]
+pub struct AuditRows {
+    timestamp: Vec<i64>,
+    severity: Vec<String>,
+    category: Vec<String>,
+    engine: Vec<String>,
+    message: Vec<String>,
+    kind: Vec<Option<String>>,
+    market: Vec<Option<String>>,
+    record: Vec<Option<String>>,
+}
+
+impl AuditRows {
+    /// Adds the event as written into the audit log
+    pub fn push(&mut self, timestamp: i64, event: &serde_json::Value) {
+        let string = |value: &serde_json::Value| value.as_str().map(str::to_string);
+        let record = event.get("record").filter(|record| !record.is_null());
+
+        self.timestamp.push(timestamp);
+        self.severity
+            .push(string(&event["severity"]).unwrap_or_default());
+        self.category
+            .push(string(&event["category"]).unwrap_or_default());
+        self.engine
+            .push(string(&event["engine"]).unwrap_or_default());
+        self.message
+            .push(string(&event["message"]).unwrap_or_default());
+        self.kind
+            .push(record.and_then(|record| string(&record["kind"])));
+        self.market
+            .push(record.and_then(|record| string(&record["market"])));
+        self.record.push(record.map(|record| record.to_string()));
+    }
+}
+
+impl Rows for AuditRows {
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            timestamp_field("timestamp"),
+            Field::new("severity", DataType::Utf8, false),
+            Field::new("category", DataType::Utf8, false),
+            Field::new("engine", DataType::Utf8, false),
+            Field::new("message", DataType::Utf8, false),
+            Field::new("kind", DataType::Utf8, true),
+            Field::new("market", DataType::Utf8, true),
+            // Structured record as JSON
+            Field::new("record", DataType::Utf8, true),
+        ]))
+    }
+
+    fn len(&self) -> usize {
+        self.timestamp.len()
+    }
+
+    fn take_batch(&mut self) -> Result<RecordBatch, ArrowError> {
+        let rows = std::mem::take(self);
+
+        RecordBatch::try_new(
+            Self::schema(),
+            vec![
+                Arc::new(TimestampMillisecondArray::from(rows.timestamp)),
+                strings(rows.severity),
+                strings(rows.category),
+                strings(rows.engine),
+                strings(rows.message),
+                optional_strings(rows.kind),
+                optional_strings(rows.market),
+                optional_strings(rows.record),
+            ],
+        )
+    }
+}
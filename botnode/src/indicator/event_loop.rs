@@ -31,7 +31,7 @@ pub async fn run_indicator_loop(
 ) -> Result<(), EngineError> {
     let _token = shutdown
         .delay_shutdown_token()
-        .map_err(EngineError::with_source)?;
+        .map_err(|_| EngineError::Shutdown)?;
 
     let mut indicator_state = IndicatorState::default();
 
@@ -87,6 +87,49 @@ fn process_market_event(
         MarketEventType::MidPriceChange(market_symbol, bid, ask) => {
             trace!("{market_symbol} {bid}/{ask}");
         }
+        MarketEventType::BboUpdate(market_symbol, bbo) => {
+            let time = event
+                .timestamp
+                .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64();
+
+            indicator_state.update_tob(time, market_symbol.clone(), bbo.bid, bbo.ask);
+
+            trace!("{market_symbol}: {}/{}", bbo.bid, bbo.ask);
+        }
+        MarketEventType::OrderbookDelta(market_symbol, delta) => {
+            // Top of book is only tracked from full orderbooks and BBO updates
+            trace!("{market_symbol} delta {}", delta.sequence);
+        }
+        MarketEventType::FundingRate(market_symbol, funding) => {
+            trace!("{market_symbol} funding rate {}", funding.rate);
+        }
+        MarketEventType::OpenInterest(market_symbol, open_interest) => {
+            trace!(
+                "{market_symbol} open interest {}",
+                open_interest.open_interest
+            );
+        }
+        MarketEventType::Liquidation(market_symbol, liquidations) => {
+            trace!("{market_symbol} {} liquidations", liquidations.len());
+        }
+        MarketEventType::Candle(market_symbol, candle) => {
+            trace!(
+                "{market_symbol} candle {} close {}",
+                candle.open_time,
+                candle.close
+            );
+        }
+        MarketEventType::L3Snapshot(market_symbol, orders) => {
+            trace!("{market_symbol} L3 snapshot of {} orders", orders.len());
+        }
+        MarketEventType::L3Update(market_symbol, updates) => {
+            trace!("{market_symbol} {} L3 updates", updates.len());
+        }
+        MarketEventType::OrderbookSnapshot(market_symbol, _) => {
+            trace!("{market_symbol} orderbook snapshot");
+        }
     }
 
     Ok(())
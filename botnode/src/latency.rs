@@ -0,0 +1,266 @@
+//! Latency measurement
+//!
+//! Orders carry a [`TickStamp`] of the market event that triggered them, the
+//! order engine measures the elapsed time once the order hits the wire.
+//!
+//! Events received from a live feed are stamped when their message is read
+//! from the socket. The market data engine measures the parse latency, the
+//! trading engine the time the events spent in the channel and the time
+//! strategies took to decide on them, see [`PipelineLatency`].
+
+use std::time::{Duration, Instant, SystemTime};
+
+use hdrhistogram::Histogram;
+
+use crate::{metrics::MetricLabels, prelude::*};
+
+/// Highest latency tracked by the histograms: 60 seconds in microseconds
+const MAX_LATENCY_US: u64 = 60_000_000;
+
+/// Published percentiles
+const QUANTILES: [(f64, &str); 4] = [(0.5, "0.5"), (0.9, "0.9"), (0.99, "0.99"), (0.999, "0.999")];
+
+/// Timestamp of the market event that triggered an order
+#[derive(Clone, Debug)]
+pub struct TickStamp {
+    /// Name of the strategy that produced the order
+    pub strategy: Box<str>,
+    /// Time the triggering market event was produced
+    pub event_time: SystemTime,
+    /// Time the message of the triggering market event was read from the
+    /// socket, set for events received from a live feed
+    pub received: Option<Instant>,
+    /// Span the order is sent in when the triggering market event is traced
+    pub span: Option<tracing::Span>,
+}
+
+impl TickStamp {
+    /// Creates tick stamp from the triggering market event
+    pub fn from_event(strategy: &str, event: &MarketEvent) -> Self {
+        Self {
+            strategy: Box::from(strategy),
+            event_time: event.timestamp,
+            received: event.times.map(|times| times.received),
+            span: event.span.clone(),
+        }
+    }
+
+    /// Returns time elapsed since the triggering market event was received,
+    /// or produced when it didn't come from a live feed
+    pub fn elapsed(&self) -> Duration {
+        match self.received {
+            Some(received) => received.elapsed(),
+            None => self.event_time.elapsed().unwrap_or_default(),
+        }
+    }
+}
+
+/// Latency histogram in microseconds
+#[derive(Clone, Debug)]
+pub struct LatencyHistogram(Histogram<u64>);
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self(Histogram::new_with_bounds(1, MAX_LATENCY_US, 3).expect("valid histogram bounds"))
+    }
+}
+
+impl LatencyHistogram {
+    /// Records given latency
+    pub fn record(&mut self, latency: Duration) {
+        self.0.saturating_record(latency.as_micros() as u64);
+    }
+
+    /// Returns the latency at given quantile
+    pub fn quantile(&self, quantile: f64) -> Duration {
+        Duration::from_micros(self.0.value_at_quantile(quantile))
+    }
+
+    /// Returns number of recorded values
+    pub fn len(&self) -> u64 {
+        self.0.len()
+    }
+
+    /// Returns true if no values were recorded
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Publishes percentiles into the metrics registry and resets the histogram
+    pub fn publish(&mut self, name: &'static str, labels: &MetricLabels) {
+        let registry = crate::metrics::registry();
+
+        for (quantile, quantile_label) in QUANTILES {
+            registry.set_gauge(
+                name,
+                &labels.clone().with("quantile", quantile_label),
+                self.0.value_at_quantile(quantile) as f64,
+            );
+        }
+
+        self.0.reset();
+    }
+}
+
+/// Tick-to-trade latency histograms per strategy
+#[derive(Debug, Default)]
+pub struct TickToTrade {
+    strategies: HashMap<Box<str>, LatencyHistogram>,
+}
+
+impl TickToTrade {
+    /// Records latency of the order triggered by given tick
+    pub fn record(&mut self, tick: &TickStamp) {
+        let latency = tick.elapsed();
+        trace!("{} tick-to-trade = {latency:?}", tick.strategy);
+
+        record_keyed(&mut self.strategies, &tick.strategy, latency);
+    }
+
+    /// Returns histogram for given strategy
+    pub fn strategy(&self, strategy: &str) -> Option<&LatencyHistogram> {
+        self.strategies.get(strategy)
+    }
+
+    /// Publishes percentiles of all strategies into the metrics registry
+    pub fn publish(&mut self) {
+        for (strategy, histogram) in self.strategies.iter_mut() {
+            if histogram.is_empty() {
+                continue;
+            }
+
+            info!(
+                "{strategy} tick-to-trade p50 = {:?} p99 = {:?}",
+                histogram.quantile(0.5),
+                histogram.quantile(0.99)
+            );
+            histogram.publish(
+                "trading_tick_to_trade_microseconds",
+                &MetricLabels::new().strategy(strategy),
+            );
+        }
+    }
+}
+
+/// Latency histograms of market events passing through the trading engine
+#[derive(Debug, Default)]
+pub struct PipelineLatency {
+    /// Time from pushing to popping the event per exchange
+    channel: HashMap<Box<str>, LatencyHistogram>,
+    /// Time taken by the strategy handling the event per strategy
+    decision: HashMap<Box<str>, LatencyHistogram>,
+}
+
+impl PipelineLatency {
+    /// Records time the event received from the exchange spent in the
+    /// channel, events not received from a live feed are ignored
+    pub fn record_channel(&mut self, exchange: &str, event: &MarketEvent) {
+        if let Some(times) = event.times {
+            record_keyed(&mut self.channel, exchange, times.sent.elapsed());
+        }
+    }
+
+    /// Records time the strategy took to decide on a market event
+    pub fn record_decision(&mut self, strategy: &str, latency: Duration) {
+        record_keyed(&mut self.decision, strategy, latency);
+    }
+
+    /// Returns channel latency histogram of given exchange
+    pub fn channel(&self, exchange: &str) -> Option<&LatencyHistogram> {
+        self.channel.get(exchange)
+    }
+
+    /// Returns decision latency histogram of given strategy
+    pub fn decision(&self, strategy: &str) -> Option<&LatencyHistogram> {
+        self.decision.get(strategy)
+    }
+
+    /// Publishes percentiles of all histograms into the metrics registry
+    pub fn publish(&mut self) {
+        for (exchange, histogram) in self.channel.iter_mut() {
+            if !histogram.is_empty() {
+                histogram.publish(
+                    "market_data_channel_latency_microseconds",
+                    &MetricLabels::new().exchange(exchange),
+                );
+            }
+        }
+        for (strategy, histogram) in self.decision.iter_mut() {
+            if !histogram.is_empty() {
+                histogram.publish(
+                    "trading_strategy_decision_latency_microseconds",
+                    &MetricLabels::new().strategy(strategy),
+                );
+            }
+        }
+    }
+}
+
+fn record_keyed(
+    histograms: &mut HashMap<Box<str>, LatencyHistogram>,
+    key: &str,
+    latency: Duration,
+) {
+    match histograms.get_mut(key) {
+        Some(histogram) => histogram.record(latency),
+        None => {
+            let mut histogram = LatencyHistogram::default();
+            histogram.record(latency);
+            histograms.insert(Box::from(key), histogram);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_histogram_quantile() {
+        let mut histogram = LatencyHistogram::default();
+
+        for us in 1..=100 {
+            histogram.record(Duration::from_micros(us));
+        }
+
+        assert_eq!(100, histogram.len());
+        assert_eq!(Duration::from_micros(50), histogram.quantile(0.5));
+        assert_eq!(Duration::from_micros(100), histogram.quantile(1.0));
+    }
+
+    #[test]
+    fn test_tick_to_trade_per_strategy() {
+        let mut tick_to_trade = TickToTrade::default();
+        let event = MarketEvent::mid_price_change(Box::from("BTC/USD"), 1.0, 2.0);
+
+        tick_to_trade.record(&TickStamp::from_event("a", &event));
+        tick_to_trade.record(&TickStamp::from_event("a", &event));
+        tick_to_trade.record(&TickStamp::from_event("b", &event));
+
+        assert_eq!(2, tick_to_trade.strategy("a").unwrap().len());
+        assert_eq!(1, tick_to_trade.strategy("b").unwrap().len());
+        assert!(tick_to_trade.strategy("c").is_none());
+    }
+
+    #[test]
+    fn test_pipeline_latency() {
+        let mut latency = PipelineLatency::default();
+        let mut event = MarketEvent::mid_price_change(Box::from("BTC/USD"), 1.0, 2.0);
+
+        // Replayed events carry no pipeline times
+        latency.record_channel("ftx", &event);
+        assert!(latency.channel("ftx").is_none());
+
+        let received = Instant::now();
+        event.times = Some(botvana::market::event::PipelineTimes {
+            received,
+            sent: received,
+        });
+        latency.record_channel("ftx", &event);
+        latency.record_decision("a", Duration::from_micros(5));
+
+        assert_eq!(1, latency.channel("ftx").unwrap().len());
+        assert_eq!(1, latency.decision("a").unwrap().len());
+        assert_eq!(Some(received), TickStamp::from_event("a", &event).received);
+    }
+}
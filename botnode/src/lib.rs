@@ -1,14 +1,49 @@
+pub mod alert;
+pub mod allocator;
+pub mod api;
 pub mod audit;
+#[cfg(feature = "recorder")]
+pub mod backtest;
+pub mod bus;
 pub mod channels;
+pub mod cli;
+pub mod config;
 pub mod control;
 pub mod engine;
 pub mod error;
-pub mod exchange;
+#[cfg(feature = "parquet")]
+pub mod export;
+// Backtests run the indicators and strategies without the trading engines
+#[cfg_attr(not(any(feature = "trading", feature = "recorder")), allow(dead_code))]
 pub mod indicator;
+pub mod latency;
 pub mod market_data;
+pub mod metrics;
+pub mod node;
+pub mod notify;
+// Only configuration validation uses the execution adapters without the
+// trading engines
+#[cfg_attr(not(feature = "trading"), allow(dead_code))]
+pub mod order_engine;
+pub mod perf;
+pub mod placement;
+#[cfg_attr(not(feature = "trading"), allow(dead_code))]
+pub mod position;
+pub mod rate_limit;
+#[cfg(feature = "recorder")]
+pub mod recorder;
+#[cfg_attr(not(feature = "trading"), allow(dead_code))]
+pub mod risk_engine;
+pub mod rt;
+pub mod secrets;
+pub mod status;
+pub mod telemetry;
+#[cfg_attr(not(any(feature = "trading", feature = "recorder")), allow(dead_code))]
 pub mod trading;
 pub mod util;
 
+pub use node::{Botnode, BotnodeBuilder};
+
 /// Useful prelude for implementing botnode engines
 pub mod prelude {
     pub use std::collections::HashMap;
@@ -19,13 +54,6 @@ pub mod prelude {
     pub use async_trait::async_trait;
     pub use chrono::{DateTime, Utc};
     pub use futures::prelude::*;
-    pub use glommio::{
-        channels::shared_channel::{self, *},
-        channels::spsc_queue,
-        net::TcpStream,
-        prelude::*,
-        LocalExecutor, LocalExecutorBuilder,
-    };
     pub use tracing::{debug, error, info, trace, warn};
 
     pub use botvana::{
@@ -47,5 +75,6 @@ pub mod prelude {
         engine::*,
         error::{EngineError, StartEngineError},
         indicator::IndicatorEvent,
+        rt::{net::TcpStream, spsc_queue},
     };
 }
@@ -1,89 +1,245 @@
-use std::{env::var, panic};
+use clap::Parser;
 
-use async_shutdown::Shutdown;
-use futures::prelude::*;
-use glommio::LocalExecutor;
-use signal_hook::consts::signal::*;
-use signal_hook_async_std::Signals;
-use tracing::{debug, error, info};
-use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+use botvana::market::MarketType;
 
-use botnode::{control::engine::*, engine::*};
-use botvana::net::msg::BotId;
+#[cfg(feature = "recorder")]
+use botnode::{backtest::Backtest, trading::strategy::load_strategies};
+use botnode::{
+    cli::{Cli, Command},
+    config::BotnodeConfig,
+    market_data::{self, symbols::SymbolMap},
+    secrets, Botnode,
+};
 
+#[cfg(feature = "jemalloc")]
 #[global_allocator]
-static ALLOC: snmalloc_rs::SnMalloc = snmalloc_rs::SnMalloc;
-
-fn main() {
-    tracing_subscriber::registry()
-        .with(EnvFilter::from_default_env())
-        .with(fmt::layer().with_thread_names(true))
-        .init();
+static ALLOC: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 
-    let (bot_id, server_addr) = load_configuration();
+#[cfg(all(feature = "mimalloc", not(feature = "jemalloc")))]
+#[global_allocator]
+static ALLOC: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
-    let shutdown = Shutdown::new();
+#[cfg(all(
+    feature = "snmalloc",
+    not(any(feature = "jemalloc", feature = "mimalloc"))
+))]
+#[global_allocator]
+static ALLOC: snmalloc_rs::SnMalloc = snmalloc_rs::SnMalloc;
 
-    {
-        let shutdown = shutdown.clone();
+fn main() {
+    let cli = Cli::parse();
 
-        panic::set_hook(Box::new(move |p| {
-            error!("Panic coming from one of the threads, exiting");
-            debug!("panic = {:?}", p);
-            shutdown.shutdown();
-        }));
+    match cli.command() {
+        Command::Run => {
+            let config = load_config(&cli);
+            let symbols = load_symbols(&config);
+            run(config, symbols)
+        }
+        // Recording and replaying are runs with the recorder or the replay
+        // configured by the subcommand
+        #[cfg(feature = "recorder")]
+        Command::Record { .. } | Command::Replay { .. } => {
+            let config = load_config(&cli);
+            let symbols = load_symbols(&config);
+            run(config, symbols)
+        }
+        Command::CheckConfig => {
+            let config = load_config(&cli);
+            load_symbols(&config);
+            check_config(config)
+        }
+        Command::ListMarkets { exchange } => list_markets(&exchange),
+        Command::EncryptSecrets { input, output } => encrypt_secrets(&input, &output),
+        #[cfg(feature = "recorder")]
+        Command::Backtest { input, strategy } => {
+            let config = load_config(&cli);
+            backtest(config, &input, strategy.as_deref())
+        }
+        #[cfg(feature = "parquet")]
+        Command::Export {
+            recording,
+            audit,
+            output,
+        } => export(recording.as_deref(), audit.as_deref(), &output),
     }
-
-    // Start the control engine that will connect to botvana-server and
-    // receive the configuration. Then the control engine spawns other engines
-    // based on the configuration it recieves.
-    let control_engine = ControlEngine::new(bot_id, server_addr);
-    spawn_engine(0, control_engine, shutdown.clone()).expect("failed to start control engine");
-
-    // Setup signal handlers for shutdown
-    let signals = Signals::new(&[SIGINT, SIGTERM, SIGQUIT]).expect("Failed to register signals");
-    let local_ex = LocalExecutor::default();
-    local_ex.run(handle_signals(signals, shutdown));
 }
 
-/// Loads configuration from ENV variables
+/// Loads the configuration or exits
 ///
-/// Panics if the BOT_ID or SERVER_ADDR variables are missing or
-/// BOT_ID can't be parsed as u16 number.
-fn load_configuration() -> (BotId, String) {
-    let bot_id = var("BOT_ID")
-        .expect("Please specify BOT_ID")
-        .parse::<BotId>()
-        .expect("BOT_ID must be u16 number");
-
-    info!("bot_id = {}", bot_id.0);
+/// Logging is configured by the config itself, so errors go to stderr.
+fn load_config(cli: &Cli) -> BotnodeConfig {
+    match cli.load_config() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
 
-    let server_addr = var("SERVER_ADDR").expect("Please specify SERVER_ADDR");
+/// Loads the symbol mapping file given by the config or exits
+fn load_symbols(config: &BotnodeConfig) -> SymbolMap {
+    match &config.symbols_file {
+        Some(path) => SymbolMap::load(path).unwrap_or_else(|e| {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }),
+        None => SymbolMap::default(),
+    }
+}
 
-    (bot_id, server_addr)
+/// Prints summary of the validated configuration
+fn check_config(config: BotnodeConfig) {
+    let n_exchanges = config
+        .cpu
+        .market_data
+        .as_ref()
+        .map_or(config.exchanges.len(), |cores| cores.len());
+
+    println!("bot_id = {}", config.bot_id.0);
+    println!("server_addr = {}", config.server_addr);
+    println!("exchanges = {:?}", config.exchanges);
+    println!("markets = {:?}", config.markets);
+    // Validated placement
+    if let Ok(cpus) = config.cpu.placement(n_exchanges) {
+        println!("cpu = {cpus:?}");
+    }
+    if let Some(nic) = &config.cpu.nic {
+        println!("nic = {nic}");
+    }
+    println!(
+        "strategies = {:?}",
+        config
+            .strategy
+            .iter()
+            .filter(|(_, strategy)| strategy.enabled)
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>()
+    );
+    println!("Configuration OK");
 }
 
-/// Handles shutdown signals from OS
-///
-/// The function will wait for one of SIGTERM, SIGINT or SIGQUIT signals
-/// and starts shutdown procedure when it recieves any of them.
-async fn handle_signals(signals: Signals, shutdown: Shutdown) {
-    let mut signals = signals.fuse();
-
-    while let Some(signal) = signals.next().await {
-        match signal {
-            SIGTERM | SIGINT | SIGQUIT => {
-                info!("Shutting down");
-                shutdown.shutdown();
-                break;
+/// Prints markets of the exchange fetched by its market data adapter
+fn list_markets(exchange: &str) {
+    match botnode::rt::block_on(market_data::fetch_markets(exchange)) {
+        Ok(markets) => {
+            for market in markets.iter() {
+                let r#type = match market.r#type {
+                    MarketType::Spot(_) => "spot",
+                    MarketType::Futures(_) => "futures",
+                    MarketType::Options(_) => "options",
+                };
+                println!(
+                    "{}\t{}\t{}\tprice_increment={}\tsize_increment={}",
+                    market.name,
+                    market.native_symbol,
+                    r#type,
+                    market.price_increment,
+                    market.size_increment
+                );
             }
-            _ => unreachable!(),
         }
+        Err(e) => {
+            eprintln!("Failed to fetch {exchange} markets: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Encrypts plaintext secrets file for the `file` secrets provider
+fn encrypt_secrets(input: &std::path::Path, output: &std::path::Path) {
+    let res = std::env::var(secrets::file::PASSPHRASE_ENV)
+        .map_err(|_| format!("{} is not set", secrets::file::PASSPHRASE_ENV))
+        .and_then(|passphrase| {
+            let plaintext = std::fs::read_to_string(input).map_err(|e| e.to_string())?;
+            let data =
+                secrets::file::encrypt(&plaintext, &passphrase).map_err(|e| e.to_string())?;
+            std::fs::write(output, data).map_err(|e| e.to_string())
+        });
+
+    if let Err(e) = res {
+        eprintln!("Failed to encrypt secrets: {e}");
+        std::process::exit(1);
     }
+}
 
-    shutdown.wait_shutdown_complete().await;
+/// Backtests the strategies against the recording and prints the report
+#[cfg(feature = "recorder")]
+fn backtest(config: BotnodeConfig, input: &std::path::Path, strategy: Option<&str>) {
+    let strategies = load_strategies(
+        config
+            .strategy
+            .iter()
+            .filter(|(name, _)| strategy.map_or(true, |strategy| *name == strategy)),
+    );
+    if strategies.is_empty() {
+        eprintln!("No strategies to backtest");
+        std::process::exit(1);
+    }
+
+    let res = Backtest::new(config.backtest.clone(), strategies)
+        .with_indicators(config.indicators.clone())
+        .run_recording(input);
+    let report = match res {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    println!("{report}");
+
+    if let Some(path) = &config.backtest.report {
+        let res = serde_json::to_vec_pretty(&report)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(path, json).map_err(|e| e.to_string()));
+        if let Err(e) = res {
+            eprintln!("Failed to write report to {}: {e}", path.display());
+            std::process::exit(1);
+        }
+    }
+}
 
-    info!("Shutdown complete: bye");
+/// Exports the recording and the audit log into Parquet files
+#[cfg(feature = "parquet")]
+fn export(
+    recording: Option<&std::path::Path>,
+    audit: Option<&std::path::Path>,
+    output: &std::path::Path,
+) {
+    let export = || {
+        let mut export = botnode::export::ParquetExport::new(output);
+        if let Some(dir) = recording {
+            export.export_recording(dir)?;
+        }
+        if let Some(dir) = audit {
+            export.export_audit_log(dir)?;
+        }
+        export.finish()
+    };
+
+    match export() {
+        Ok(summary) => println!("{summary}"),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
 
-    std::process::exit(0);
+/// Runs the bot until it receives a shutdown signal
+fn run(config: BotnodeConfig, symbols: SymbolMap) {
+    let res = Botnode::builder()
+        .config(config)
+        .symbols(symbols)
+        .build()
+        .and_then(Botnode::run);
+
+    match res {
+        Ok(()) => std::process::exit(0),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
 }
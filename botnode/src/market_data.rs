@@ -1,26 +1,134 @@
 // Core market data modules
 pub mod adapter;
+pub mod book_events;
+pub mod candles;
+pub mod clock;
+pub mod conflation;
 pub mod engine;
 pub mod error;
+pub mod json;
+pub mod metrics;
+pub mod pool;
+pub mod registry;
+#[cfg(feature = "recorder")]
+pub mod replay;
+pub mod sequence;
+pub mod subscriptions;
+pub mod symbols;
+pub mod time_sync;
+pub mod trade_tape;
+pub mod warm_start;
 
-// Exchange adapters
+// Exchange adapters, each behind the Cargo feature of the same name
+#[cfg(feature = "binance")]
 pub mod binance;
+#[cfg(feature = "bitstamp")]
+pub mod bitstamp;
+#[cfg(feature = "bybit")]
+pub mod bybit;
+#[cfg(feature = "coinbase")]
+pub mod coinbase;
+#[cfg(feature = "deribit")]
+pub mod deribit;
+#[cfg(feature = "ftx")]
 pub mod ftx;
+#[cfg(feature = "gemini")]
+pub mod gemini;
+#[cfg(feature = "kraken")]
+pub mod kraken;
+#[cfg(feature = "okx")]
+pub mod okx;
+#[cfg(feature = "serum")]
 pub mod serum;
 
 pub use engine::*;
 
+use botvana::{exchange::ExchangeId, market::Market};
+
+use crate::market_data::{
+    adapter::RestMarketDataAdapter,
+    error::{MarketDataError, UnknownVariantError},
+};
+
+/// Returns the Cargo feature enabling adapter of the exchange
+pub fn adapter_feature(exchange: ExchangeId) -> &'static str {
+    match exchange {
+        ExchangeId::Ftx => "ftx",
+        ExchangeId::BinanceSpot => "binance",
+        ExchangeId::Serum => "serum",
+        ExchangeId::Coinbase => "coinbase",
+        ExchangeId::Kraken => "kraken",
+        ExchangeId::Bybit => "bybit",
+        ExchangeId::Okx => "okx",
+        ExchangeId::Deribit => "deribit",
+        ExchangeId::Bitstamp => "bitstamp",
+        ExchangeId::Gemini => "gemini",
+    }
+}
+
+/// Returns whether adapter of the exchange is compiled in
+pub fn is_adapter_enabled(exchange: ExchangeId) -> bool {
+    match exchange {
+        ExchangeId::Ftx => cfg!(feature = "ftx"),
+        ExchangeId::BinanceSpot => cfg!(feature = "binance"),
+        ExchangeId::Serum => cfg!(feature = "serum"),
+        ExchangeId::Coinbase => cfg!(feature = "coinbase"),
+        ExchangeId::Kraken => cfg!(feature = "kraken"),
+        ExchangeId::Bybit => cfg!(feature = "bybit"),
+        ExchangeId::Okx => cfg!(feature = "okx"),
+        ExchangeId::Deribit => cfg!(feature = "deribit"),
+        ExchangeId::Bitstamp => cfg!(feature = "bitstamp"),
+        ExchangeId::Gemini => cfg!(feature = "gemini"),
+    }
+}
+
+/// Fetches markets of the exchange given by its configuration name
+pub async fn fetch_markets(exchange: &str) -> Result<Box<[Market]>, MarketDataError> {
+    match exchange {
+        #[cfg(feature = "ftx")]
+        "ftx" => ftx::Ftx::default().fetch_markets().await,
+        #[cfg(feature = "binance")]
+        "binance" => binance::Binance::default().fetch_markets().await,
+        #[cfg(feature = "serum")]
+        "serum" => serum::Serum::default().fetch_markets().await,
+        #[cfg(feature = "coinbase")]
+        "coinbase" => coinbase::Coinbase::default().fetch_markets().await,
+        #[cfg(feature = "kraken")]
+        "kraken" => kraken::Kraken::default().fetch_markets().await,
+        #[cfg(feature = "bybit")]
+        "bybit" => bybit::Bybit::default().fetch_markets().await,
+        #[cfg(feature = "okx")]
+        "okx" => okx::Okx::default().fetch_markets().await,
+        #[cfg(feature = "deribit")]
+        "deribit" => deribit::Deribit::default().fetch_markets().await,
+        #[cfg(feature = "bitstamp")]
+        "bitstamp" => bitstamp::Bitstamp::default().fetch_markets().await,
+        #[cfg(feature = "gemini")]
+        "gemini" => gemini::Gemini::default().fetch_markets().await,
+        _ => Err(MarketDataError::with_source(UnknownVariantError {
+            variant: exchange.to_string(),
+        })),
+    }
+}
+
 mod prelude {
     pub use std::{
         borrow::Borrow,
         cell::RefCell,
         collections::HashMap,
-        time::{Duration, SystemTime},
+        time::Duration,
     };
 
-    pub use metered::{clear::Clear, time_source::StdInstant, *};
+    pub use metered::{clear::Clear, *};
     pub use serde_json::json;
     pub use surf::Url;
 
-    pub use crate::market_data::{adapter::*, error::*};
+    pub use crate::market_data::{
+        adapter::*,
+        book_events::{BookEmitter, BookEvents},
+        error::*,
+        metrics::AdapterMetrics,
+        sequence::SequenceTracker,
+    };
+    pub use crate::rate_limit::{self, Endpoint};
 }
@@ -2,44 +2,94 @@
 //!
 //! This module defines market data adapter traits that when implemented allow
 //! the market data engine to operate on any exchange.
+//!
+//! The traits are object safe: names are returned by methods and futures are
+//! boxed by `async_trait`, so adapters chosen at runtime can be used as
+//! [`BoxedMarketDataAdapter`].
 
+use crate::rt::timer::{sleep, timeout};
 use async_tungstenite::{async_std::connect_async, tungstenite::Message};
-use glommio::timer::sleep;
 
-use crate::{market_data::prelude::*, prelude::*};
-use botvana::{exchange::ExchangeId, market::MarketVec};
+use crate::{
+    config::MarketDataConfig,
+    market_data::{
+        candles::CandleAggregator, conflation::Conflator, prelude::*, subscriptions::Subscriptions,
+        symbols::ExchangeSymbols, trade_tape::TradeTapes,
+    },
+    prelude::*,
+    util::backoff::{Backoff, BackoffConfig},
+};
+use botvana::{
+    exchange::ExchangeId,
+    market::{event::PipelineTimes, MarketVec},
+};
+
+/// How often the occupancy of consumer channels is checked
+const OCCUPANCY_CHECK_INTERVAL: Duration = Duration::from_millis(100);
 
 /// Market data adapter trait
 #[async_trait(?Send)]
 pub trait MarketDataAdapter<const TX_CAP: usize> {
-    const NAME: &'static str;
-    const EXCHANGE_REF: ExchangeId;
+    /// Returns name of the adapter
+    fn name(&self) -> &'static str;
+
+    /// Returns exchange the adapter connects to
+    fn exchange(&self) -> ExchangeId;
+
+    /// Returns adapter metrics
+    fn metrics(&self) -> &AdapterMetrics;
 
     /// Fetches and returns markets information
     async fn fetch_markets(&self) -> Result<Box<MarketVec>, MarketDataError>;
 
     /// Runs the adapter event loop
+    ///
+    /// Markets are subscribed by their native symbols, events are emitted
+    /// under the canonical names of `symbols`. Changes of the subscriptions
+    /// are applied while running, every reconnect subscribes the current
+    /// markets.
     async fn run_loop(
         &mut self,
         data_txs: crate::channels::ProducersArray<MarketEvent, TX_CAP>,
-        markets: &[&str],
+        subscriptions: &mut Subscriptions,
+        config: MarketDataConfig,
+        symbols: &ExchangeSymbols,
         shutdown: Shutdown,
     ) -> Result<(), MarketDataError> {
+        let mut backoff = Backoff::new(config.reconnect.clone());
         loop {
             if let Err(e) = self
-                .run_exchange_connection_loop(&data_txs, &markets, shutdown.clone())
+                .run_exchange_connection_loop(
+                    &data_txs,
+                    subscriptions,
+                    &config,
+                    symbols,
+                    shutdown.clone(),
+                )
                 .await
             {
                 error!("Error running exchange connection loop: {e}");
             }
+            // Start over from the initial delay when the connection was
+            // established and subscribed
+            if self.metrics().uptime().is_some() {
+                backoff.reset();
+            }
+            self.metrics().record_disconnected();
 
             if shutdown.shutdown_started() {
                 break Ok(());
             }
 
-            let wait = Duration::from_secs(5);
+            let wait = backoff.next_delay().ok_or_else(|| {
+                MarketDataError::convert_error(format!(
+                    "gave up after {} reconnect attempts",
+                    backoff.attempts()
+                ))
+            })?;
             warn!("disconnected from the exchange; waiting for {wait:?}");
             sleep(wait).await;
+            self.metrics().record_reconnect();
         }
     }
 
@@ -47,7 +97,9 @@ pub trait MarketDataAdapter<const TX_CAP: usize> {
     async fn run_exchange_connection_loop(
         &mut self,
         data_txs: &crate::channels::ProducersArray<MarketEvent, TX_CAP>,
-        markets: &[&str],
+        subscriptions: &mut Subscriptions,
+        config: &MarketDataConfig,
+        symbols: &ExchangeSymbols,
         shutdown: Shutdown,
     ) -> Result<Option<MarketEvent>, MarketDataError>;
 }
@@ -59,8 +111,64 @@ pub trait WsMarketDataAdapter {
     /// Returns set of subscribe messages to send to subscribe to given markets
     fn subscribe_msgs(&mut self, markets: &[&str]) -> Box<[String]>;
 
-    /// Returns throughput metrics
-    fn throughput_metrics(&self) -> &Throughput<StdInstant, RefCell<metered::common::TxPerSec>>;
+    /// Returns whether [`WsMarketDataAdapter::subscribe_msgs`] adds markets
+    /// to a connection that is already subscribed, markets subscribed at
+    /// runtime reconnect otherwise
+    fn subscribes_incrementally(&self) -> bool {
+        true
+    }
+
+    /// Returns messages unsubscribing given markets on the open connection,
+    /// `None` reconnects instead
+    fn unsubscribe_msgs(&mut self, _markets: &[&str]) -> Option<Box<[String]>> {
+        None
+    }
+
+    /// Returns whether orderbooks are seeded from REST snapshots after
+    /// subscribing and resyncing, for exchanges streaming only diffs
+    fn requires_snapshot(&self) -> bool {
+        false
+    }
+
+    /// Returns whether orderbooks are seeded from REST snapshots after
+    /// subscribing, before the first websocket update arrives
+    fn seeds_from_snapshot(&self) -> bool {
+        self.requires_snapshot()
+    }
+
+    /// Returns messages resubscribing the orderbook of the market after it
+    /// went out of sync, `None` reconnects instead
+    fn resync_msgs(&self, _market: &str) -> Option<Box<[String]>> {
+        None
+    }
+
+    /// Returns message sent every `ping_interval_secs` to keep the
+    /// connection open, for exchanges closing idle connections
+    fn ping_msg(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns update ids of the orderbooks, for exchanges numbering their
+    /// diffs
+    fn sequences(&self) -> Option<&SequenceTracker> {
+        None
+    }
+
+    /// Returns event left over by the last processed message, for messages
+    /// carrying more than one event
+    fn take_pending_event(&self) -> Option<MarketEvent> {
+        None
+    }
+
+    /// Returns adapter metrics
+    fn metrics(&self) -> &AdapterMetrics;
+
+    /// Sets the events emitted on orderbook updates
+    fn set_book_events(&mut self, book_events: BookEvents);
+
+    /// Sets whether order-by-order books are streamed, for exchanges
+    /// offering them
+    fn set_l3_books(&mut self, _enabled: bool) {}
 
     /// Processes Websocket text message
     fn process_ws_msg(
@@ -70,11 +178,114 @@ pub trait WsMarketDataAdapter {
     ) -> Result<Option<MarketEvent>, MarketDataError>;
 }
 
+/// Websocket and REST-API market data adapter usable as trait object
+pub trait DynMarketDataAdapter: WsMarketDataAdapter + RestMarketDataAdapter + Send {}
+
+impl<T: WsMarketDataAdapter + RestMarketDataAdapter + Send> DynMarketDataAdapter for T {}
+
+/// Market data adapter chosen at runtime
+pub type BoxedMarketDataAdapter = Box<dyn DynMarketDataAdapter>;
+
+impl WsMarketDataAdapter for BoxedMarketDataAdapter {
+    fn ws_url(&self) -> Box<str> {
+        (**self).ws_url()
+    }
+
+    fn subscribe_msgs(&mut self, markets: &[&str]) -> Box<[String]> {
+        (**self).subscribe_msgs(markets)
+    }
+
+    fn subscribes_incrementally(&self) -> bool {
+        (**self).subscribes_incrementally()
+    }
+
+    fn unsubscribe_msgs(&mut self, markets: &[&str]) -> Option<Box<[String]>> {
+        (**self).unsubscribe_msgs(markets)
+    }
+
+    fn requires_snapshot(&self) -> bool {
+        (**self).requires_snapshot()
+    }
+
+    fn seeds_from_snapshot(&self) -> bool {
+        (**self).seeds_from_snapshot()
+    }
+
+    fn resync_msgs(&self, market: &str) -> Option<Box<[String]>> {
+        (**self).resync_msgs(market)
+    }
+
+    fn ping_msg(&self) -> Option<String> {
+        (**self).ping_msg()
+    }
+
+    fn sequences(&self) -> Option<&SequenceTracker> {
+        (**self).sequences()
+    }
+
+    fn take_pending_event(&self) -> Option<MarketEvent> {
+        (**self).take_pending_event()
+    }
+
+    fn metrics(&self) -> &AdapterMetrics {
+        WsMarketDataAdapter::metrics(&**self)
+    }
+
+    fn set_book_events(&mut self, book_events: BookEvents) {
+        (**self).set_book_events(book_events)
+    }
+
+    fn set_l3_books(&mut self, enabled: bool) {
+        (**self).set_l3_books(enabled)
+    }
+
+    fn process_ws_msg(
+        &self,
+        msg: &str,
+        markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    ) -> Result<Option<MarketEvent>, MarketDataError> {
+        (**self).process_ws_msg(msg, markets)
+    }
+}
+
+#[async_trait(?Send)]
+impl RestMarketDataAdapter for BoxedMarketDataAdapter {
+    fn name(&self) -> &'static str {
+        RestMarketDataAdapter::name(&**self)
+    }
+
+    fn exchange(&self) -> ExchangeId {
+        RestMarketDataAdapter::exchange(&**self)
+    }
+
+    async fn fetch_orderbook_snapshot(
+        &self,
+        symbol: &str,
+    ) -> Result<PlainOrderbook<f64>, MarketDataError> {
+        (**self).fetch_orderbook_snapshot(symbol).await
+    }
+
+    async fn fetch_markets(&self) -> Result<Box<[Market]>, MarketDataError> {
+        RestMarketDataAdapter::fetch_markets(&**self).await
+    }
+
+    async fn fetch_funding(&self, market: &str) -> Result<Box<[MarketEvent]>, MarketDataError> {
+        (**self).fetch_funding(market).await
+    }
+
+    async fn fetch_server_time(&self) -> Result<Option<DateTime<Utc>>, MarketDataError> {
+        (**self).fetch_server_time().await
+    }
+}
+
 /// REST-API market data adapter
 #[async_trait(?Send)]
 pub trait RestMarketDataAdapter {
-    const NAME: &'static str;
-    const EXCHANGE_REF: ExchangeId;
+    /// Returns name of the adapter
+    fn name(&self) -> &'static str;
+
+    /// Returns exchange the adapter connects to
+    fn exchange(&self) -> ExchangeId;
 
     /// Fetch orderbook snapshot for given symbol
     async fn fetch_orderbook_snapshot(
@@ -84,6 +295,18 @@ pub trait RestMarketDataAdapter {
 
     /// Fetches availables markets
     async fn fetch_markets(&self) -> Result<Box<[Market]>, MarketDataError>;
+
+    /// Fetches funding rate and open interest events of the market, polled
+    /// every `funding_poll_interval_secs` for exchanges not streaming them
+    async fn fetch_funding(&self, _market: &str) -> Result<Box<[MarketEvent]>, MarketDataError> {
+        Ok(Box::new([]))
+    }
+
+    /// Fetches the time of the exchange clock, `None` for exchanges without
+    /// a time endpoint
+    async fn fetch_server_time(&self) -> Result<Option<DateTime<Utc>>, MarketDataError> {
+        Ok(None)
+    }
 }
 
 #[async_trait(?Send)]
@@ -91,8 +314,17 @@ impl<T, const TX_CAP: usize> MarketDataAdapter<TX_CAP> for T
 where
     T: WsMarketDataAdapter + RestMarketDataAdapter,
 {
-    const NAME: &'static str = <T as RestMarketDataAdapter>::NAME;
-    const EXCHANGE_REF: ExchangeId = <T as RestMarketDataAdapter>::EXCHANGE_REF;
+    fn name(&self) -> &'static str {
+        <T as RestMarketDataAdapter>::name(self)
+    }
+
+    fn exchange(&self) -> ExchangeId {
+        <T as RestMarketDataAdapter>::exchange(self)
+    }
+
+    fn metrics(&self) -> &AdapterMetrics {
+        <T as WsMarketDataAdapter>::metrics(self)
+    }
 
     /// Fetches availables markets on Binance
     async fn fetch_markets(&self) -> Result<Box<MarketVec>, MarketDataError> {
@@ -104,18 +336,30 @@ where
     async fn run_exchange_connection_loop(
         &mut self,
         data_txs: &crate::channels::ProducersArray<MarketEvent, TX_CAP>,
-        markets: &[&str],
+        subscriptions: &mut Subscriptions,
+        config: &MarketDataConfig,
+        symbols: &ExchangeSymbols,
         shutdown: Shutdown,
     ) -> Result<Option<MarketEvent>, MarketDataError> {
         let _token = shutdown
             .delay_shutdown_token()
             .map_err(MarketDataError::with_source)?;
+        // Also forgets the best bids and offers emitted before reconnecting
+        self.set_book_events(config.book_events);
+        self.set_l3_books(config.l3_books);
         let url = self.ws_url();
         info!("connecting to {}", url);
         let (mut ws_stream, _) = connect_async(url.to_string())
             .await
             .map_err(MarketDataError::with_source)?;
 
+        // Diffs are applied to fresh snapshots after every (re)subscription
+        if let Some(sequences) = self.sequences() {
+            sequences.clear();
+        }
+        // Changes made while disconnected are subscribed right away
+        subscriptions.poll();
+        let markets = subscriptions.markets();
         for msg in self.subscribe_msgs(&markets).iter() {
             info!("sending = {}", msg);
             ws_stream
@@ -123,34 +367,258 @@ where
                 .await
                 .map_err(MarketDataError::with_source)?;
         }
+        <T as WsMarketDataAdapter>::metrics(self).record_connected();
 
         let mut markets: HashMap<Box<str>, PlainOrderbook<_>> = markets
             .iter()
-            .map(|m| (Box::from(*m), PlainOrderbook::with_capacity(100)))
+            .map(|m| {
+                let orderbook = PlainOrderbook::with_capacity(100).with_max_depth(config.max_depth);
+                (Box::from(*m), orderbook)
+            })
             .collect();
+        // Diffs received meanwhile wait in the socket until the snapshots
+        // are in place
+        let mut snapshot_retries = SnapshotRetries::new(config.reconnect.clone());
+        if self.seeds_from_snapshot() {
+            for (market, orderbook) in markets.iter_mut() {
+                match self.fetch_orderbook_snapshot(market).await {
+                    Ok(snapshot) => *orderbook = snapshot.with_max_depth(config.max_depth),
+                    Err(e) => {
+                        warn!("Failed to fetch {market} snapshot: {e}");
+                        if !snapshot_retries.failed(market, std::time::Instant::now()) {
+                            return Err(e);
+                        }
+                    }
+                }
+            }
+        }
         let mut start = std::time::Instant::now();
-        let throughput = self.throughput_metrics();
+        let mut last_occupancy_check = std::time::Instant::now();
+        let exchange = <T as RestMarketDataAdapter>::exchange(self).as_str();
+        let mut slow_consumers = crate::channels::SlowConsumerMonitor::<TX_CAP>::new(
+            "market_data",
+            crate::channels::DEFAULT_OCCUPANCY_THRESHOLD,
+            crate::channels::DEFAULT_OCCUPANCY_DURATION,
+        )
+        .with_exchange(exchange)
+        .with_drop_escalation(config.dropped_events.clone());
+        let mut clock_skew = crate::market_data::clock::ClockSkewMonitor::default();
+        let mut time_sync = crate::market_data::time_sync::TimeSync::default();
+        // Candles open when the connection drops are discarded
+        let mut candles = CandleAggregator::new(&config.candle_intervals());
+        let mut closed_candles = Vec::new();
+        let mut tapes = TradeTapes::new(config.trade_tape.clone());
+        let mut conflator = Conflator::new(config.conflation_interval());
+        let mut conflated = Vec::new();
+        let mut perf =
+            crate::perf::PerfSection::new("process_ws_msg", crate::perf::DEFAULT_SAMPLE_EVERY);
+        let mut sampler = crate::telemetry::PipelineSampler::default();
 
         info!("markets = {:?}", markets);
 
-        loop {
+        let ping_msg = self.ping_msg();
+        let (ping_interval, stale_timeout) = (config.ping_interval(), config.stale_timeout());
+        let mut last_ping = std::time::Instant::now();
+        let mut last_msg = std::time::Instant::now();
+        let funding_poll_interval = config.funding_poll_interval();
+        let mut last_funding_poll: Option<std::time::Instant> = None;
+        let mut time_sync_interval = config.time_sync_interval();
+        let mut last_time_sync: Option<std::time::Instant> = None;
+
+        'connection: loop {
             if shutdown.shutdown_started() {
                 info!("Market data adapter shutting down");
+                if let Some(warm_start) = &config.warm_start {
+                    warm_start.save_logged(exchange, &markets);
+                }
                 break Ok(None);
             }
 
-            let msg = ws_stream.next().await;
+            // Snapshots that failed to fetch, their markets stay unseeded or
+            // out of sync until then
+            for market in snapshot_retries.due(std::time::Instant::now()) {
+                match self.fetch_orderbook_snapshot(&market).await {
+                    Ok(orderbook) => {
+                        info!("Fetched {market} snapshot after a retry");
+                        snapshot_retries.remove(&market);
+                        markets.insert(market, orderbook.with_max_depth(config.max_depth));
+                        // Emits the new snapshot in full with the next update
+                        self.set_book_events(config.book_events);
+                    }
+                    Err(e) => {
+                        warn!("Failed to fetch {market} snapshot: {e}");
+                        if !snapshot_retries.failed(&market, std::time::Instant::now()) {
+                            break 'connection Err(e);
+                        }
+                    }
+                }
+            }
+            // Borrowed for one round, resyncing resets the book events
+            let metrics = <T as WsMarketDataAdapter>::metrics(self);
+            let throughput = &metrics.throughput;
+
+            // Coalesced orderbook events whose interval passed
+            conflator.flush_due(std::time::Instant::now(), &mut conflated);
+            for mut event in conflated.drain(..) {
+                event.times = event.times.map(|times| pipeline_times(times.received));
+                data_txs
+                    .push_ref(&event)
+                    .map_err(MarketDataError::with_source)?;
+                crate::market_data::pool::recycle(event);
+            }
+
+            if let Some(ping_msg) = &ping_msg {
+                if last_ping.elapsed() >= ping_interval {
+                    last_ping = std::time::Instant::now();
+                    ws_stream
+                        .send(Message::text(ping_msg))
+                        .await
+                        .map_err(MarketDataError::with_source)?;
+                }
+            }
+
+            // Polled right after subscribing and then every interval, messages
+            // received meanwhile wait in the socket
+            if let Some(interval) = funding_poll_interval {
+                if last_funding_poll.map_or(true, |polled| polled.elapsed() >= interval) {
+                    last_funding_poll = Some(std::time::Instant::now());
+                    for market in markets.keys() {
+                        let events = match self.fetch_funding(market).await {
+                            Ok(events) => events,
+                            Err(e) => {
+                                warn!("Failed to fetch funding of {market}: {e}");
+                                continue;
+                            }
+                        };
+                        for mut event in events.into_vec() {
+                            symbols.canonicalize(&mut event);
+                            // Polled events carry local times already
+                            event.timestamp =
+                                crate::market_data::time_sync::utc(std::time::Instant::now());
+                            data_txs
+                                .push_value(event)
+                                .map_err(MarketDataError::with_source)?;
+                        }
+                    }
+                }
+            }
+
+            // Polled like the funding, exchanges without a time endpoint
+            // aren't polled again
+            if let Some(interval) = time_sync_interval {
+                if last_time_sync.map_or(true, |synced| synced.elapsed() >= interval) {
+                    let sent = std::time::Instant::now();
+                    last_time_sync = Some(sent);
+                    match self.fetch_server_time().await {
+                        Ok(Some(server_time)) => {
+                            time_sync.observe(server_time, sent, std::time::Instant::now())
+                        }
+                        Ok(None) => time_sync_interval = None,
+                        Err(e) => warn!("Failed to fetch server time: {e}"),
+                    }
+                }
+            }
+
+            // Wakes up for the next ping, funding poll or time sync or once
+            // the connection goes stale
+            let mut wait = stale_timeout.saturating_sub(last_msg.elapsed());
+            if ping_msg.is_some() {
+                wait = wait.min(ping_interval.saturating_sub(last_ping.elapsed()));
+            }
+            if let (Some(interval), Some(polled)) = (funding_poll_interval, last_funding_poll) {
+                wait = wait.min(interval.saturating_sub(polled.elapsed()));
+            }
+            if let (Some(interval), Some(synced)) = (time_sync_interval, last_time_sync) {
+                wait = wait.min(interval.saturating_sub(synced.elapsed()));
+            }
+            if let Some(due) = conflator.next_due(std::time::Instant::now()) {
+                wait = wait.min(due);
+            }
+            if let Some(due) = snapshot_retries.next_due(std::time::Instant::now()) {
+                wait = wait.min(due);
+            }
+            let msg = match timeout(wait, async { Ok(ws_stream.next().await) }).await {
+                Ok(msg) => {
+                    last_msg = std::time::Instant::now();
+                    msg
+                }
+                Err(_) if last_msg.elapsed() >= stale_timeout => {
+                    warn!(
+                        reason = "stale",
+                        "No message for {stale_timeout:?}, reconnecting"
+                    );
+                    metrics.record_stale_disconnect();
+                    break Ok(None);
+                }
+                Err(_) => continue,
+            };
+            let mut out_of_sync = None;
             measure!(throughput, {
                 match msg {
-                    Some(Ok(Message::Text(msg))) => match self.process_ws_msg(&msg, &mut markets) {
-                        Ok(Some(event)) => {
+                    Some(Ok(Message::Text(msg))) => {
+                        metrics.record_message();
+                        let tick_span = sampler.sample(exchange);
+                        let parse_span = tick_span.as_ref().map_or_else(
+                            tracing::Span::none,
+                            |tick_span| tracing::info_span!(parent: tick_span, "parse"),
+                        );
+
+                        match parse_span
+                            .in_scope(|| perf.measure(|| self.process_ws_msg(&msg, &mut markets)))
+                        {
+                            Ok(Some(mut event)) => {
+                                metrics.record_parse_latency(last_msg.elapsed());
+                                limit_depth(&mut event, &mut markets, config.max_depth);
+                                tapes.observe(&event);
+                                symbols.canonicalize(&mut event);
+                                if let Some(tick_span) = tick_span {
+                                    if let Some(market) = event.market() {
+                                        tick_span.record("market", &market);
+                                    }
+                                    event.span = Some(tick_span);
+                                }
+                                // Skew of the local clock is observed before the
+                                // exchange times are shifted onto it
+                                clock_skew.observe_event(&event);
+                                time_sync.normalize(&mut event, last_msg);
+                                candles.observe(&event, &mut closed_candles);
+                                event.times = Some(pipeline_times(last_msg));
+                                if let Some(event) = conflator.push(event, last_msg) {
+                                    data_txs
+                                        .push_ref(&event)
+                                        .map_err(MarketDataError::with_source)?;
+                                    crate::market_data::pool::recycle(event);
+                                }
+                            }
+                            Ok(None) => {}
+                            Err(e) => match e.out_of_sync_market() {
+                                Some(market) => {
+                                    warn!("{e}, resyncing");
+                                    metrics.record_book_resync();
+                                    out_of_sync = Some((Box::<str>::from(market), e));
+                                }
+                                None => {
+                                    metrics.record_parse_error();
+                                    warn!("Failed to process websocket message: {e}");
+                                }
+                            },
+                        }
+                        while let Some(mut event) = self.take_pending_event() {
+                            symbols.canonicalize(&mut event);
+                            time_sync.normalize(&mut event, last_msg);
+                            event.times = Some(pipeline_times(last_msg));
+                            if let Some(event) = conflator.push(event, last_msg) {
+                                data_txs
+                                    .push_value(event)
+                                    .map_err(MarketDataError::with_source)?;
+                            }
+                        }
+                        for event in closed_candles.drain(..) {
                             data_txs
                                 .push_value(event)
                                 .map_err(MarketDataError::with_source)?;
                         }
-                        Ok(None) => {}
-                        Err(e) => warn!("Failed to process websocket message: {e}"),
-                    },
+                    }
                     Some(Ok(Message::Ping(_))) => {
                         debug!(message = "ping",);
                     }
@@ -171,6 +639,18 @@ where
                 }
             });
 
+            if last_occupancy_check.elapsed() >= OCCUPANCY_CHECK_INTERVAL {
+                last_occupancy_check = std::time::Instant::now();
+                slow_consumers.check(data_txs);
+
+                candles.close_expired(Utc::now(), &mut closed_candles);
+                for event in closed_candles.drain(..) {
+                    data_txs
+                        .push_value(event)
+                        .map_err(MarketDataError::with_source)?;
+                }
+            }
+
             if start.elapsed().as_secs() >= 5 {
                 start = std::time::Instant::now();
                 data_txs.0.iter().enumerate().for_each(|(idx, tx)| {
@@ -180,8 +660,496 @@ where
                     "max throughput over last 5s = {:?}",
                     throughput.0.borrow().hdr_histogram.max()
                 );
+                metrics.publish(exchange);
+                clock_skew.publish(exchange);
+                time_sync.publish(exchange);
+                tapes.publish(exchange);
+                conflator.publish(exchange);
+                perf.publish();
+                info!(
+                    uptime = ?metrics.uptime(),
+                    reconnects = metrics.reconnects(),
+                    time_to_resubscribe = ?metrics.time_to_resubscribe(),
+                    "connection stats"
+                );
                 throughput.clear();
             }
+
+            // Resubscribe the market instead of letting the book drift
+            if let Some((market, e)) = out_of_sync {
+                let msgs = match self.resync_msgs(&market) {
+                    Some(msgs) => msgs,
+                    None => break Err(e),
+                };
+                // Diffs of the old subscription are dropped until the new
+                // snapshot is in place
+                if let Some(sequences) = self.sequences() {
+                    sequences.forget(&market);
+                }
+                for msg in msgs.iter() {
+                    info!("sending = {}", msg);
+                    ws_stream
+                        .send(Message::text(msg))
+                        .await
+                        .map_err(MarketDataError::with_source)?;
+                }
+                if self.requires_snapshot() {
+                    match self.fetch_orderbook_snapshot(&market).await {
+                        Ok(orderbook) => {
+                            snapshot_retries.remove(&market);
+                            markets.insert(market, orderbook.with_max_depth(config.max_depth));
+                            // Emits the new snapshot in full with the next update
+                            self.set_book_events(config.book_events);
+                        }
+                        Err(e) => {
+                            warn!("Failed to fetch {market} snapshot: {e}, staying out of sync");
+                            if !snapshot_retries.failed(&market, std::time::Instant::now()) {
+                                break Err(e);
+                            }
+                        }
+                    }
+                }
+            }
+
+            // Markets added or removed by the server
+            if let Some(change) = subscriptions.poll() {
+                let mut msgs = Vec::new();
+                if !change.unsubscribed.is_empty() {
+                    match self.unsubscribe_msgs(&change.unsubscribed()) {
+                        Some(unsubscribe) => msgs.extend(unsubscribe.into_vec()),
+                        None => {
+                            info!("Reconnecting to unsubscribe {:?}", change.unsubscribed);
+                            break Ok(None);
+                        }
+                    }
+                }
+                if !change.subscribed.is_empty() {
+                    if !self.subscribes_incrementally() {
+                        info!("Reconnecting to subscribe {:?}", change.subscribed);
+                        break Ok(None);
+                    }
+                    msgs.extend(self.subscribe_msgs(&change.subscribed()).into_vec());
+                }
+                for msg in msgs.iter() {
+                    info!("sending = {}", msg);
+                    ws_stream
+                        .send(Message::text(msg))
+                        .await
+                        .map_err(MarketDataError::with_source)?;
+                }
+
+                for market in change.unsubscribed.iter() {
+                    markets.remove(market);
+                    tapes.remove(market);
+                    conflator.remove(symbols.to_canonical(market));
+                    snapshot_retries.remove(market);
+                    if let Some(sequences) = self.sequences() {
+                        sequences.forget(market);
+                    }
+                }
+                for market in change.subscribed.into_iter() {
+                    let orderbook = match self.seeds_from_snapshot() {
+                        true => match self.fetch_orderbook_snapshot(&market).await {
+                            Ok(orderbook) => orderbook,
+                            Err(e) => {
+                                warn!("Failed to fetch {market} snapshot: {e}");
+                                if !snapshot_retries.failed(&market, std::time::Instant::now()) {
+                                    break 'connection Err(e);
+                                }
+                                PlainOrderbook::with_capacity(100)
+                            }
+                        },
+                        false => PlainOrderbook::with_capacity(100),
+                    };
+                    markets.insert(market, orderbook.with_max_depth(config.max_depth));
+                }
+            }
+
+            // Orderbooks requested by the server, sent as held right now
+            for market in subscriptions.take_snapshot_requests() {
+                if let Some(orderbook) = markets.get(&market) {
+                    let mut event =
+                        MarketEvent::orderbook_snapshot(market, Box::new(orderbook.clone()));
+                    symbols.canonicalize(&mut event);
+                    data_txs
+                        .push_value(event)
+                        .map_err(MarketDataError::with_source)?;
+                }
+            }
+        }
+    }
+}
+
+/// Returns pipeline times of an event parsed from the message received at
+/// given time and sent now
+fn pipeline_times(received: std::time::Instant) -> PipelineTimes {
+    PipelineTimes {
+        received,
+        sent: std::time::Instant::now(),
+    }
+}
+
+/// Limits the depth of the updated orderbook and of the book in the event
+///
+/// Books adapters replace on snapshots are created without the limit, it is
+/// applied from their first update on.
+fn limit_depth(
+    event: &mut MarketEvent,
+    markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    max_depth: Option<usize>,
+) {
+    if max_depth.is_none() {
+        return;
+    }
+
+    let market = match &mut event.r#type {
+        MarketEventType::OrderbookUpdate(market, orderbook) => {
+            if orderbook.max_depth != max_depth {
+                orderbook.set_max_depth(max_depth);
+            }
+            market
+        }
+        MarketEventType::BboUpdate(market, _) | MarketEventType::OrderbookDelta(market, _) => {
+            market
+        }
+        _ => return,
+    };
+    match markets.get_mut(&**market) {
+        Some(book) if book.max_depth != max_depth => book.set_max_depth(max_depth),
+        _ => {}
+    }
+}
+
+/// Snapshots that failed to fetch, retried per market with a backoff
+///
+/// Other markets keep streaming while a market waits for its retry, the
+/// connection is only given up once the attempts of a market run out.
+#[derive(Debug)]
+pub struct SnapshotRetries {
+    config: BackoffConfig,
+    pending: HashMap<Box<str>, (Backoff, std::time::Instant)>,
+}
+
+impl SnapshotRetries {
+    pub fn new(config: BackoffConfig) -> Self {
+        Self {
+            config,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Schedules the next retry of the market, `false` when its attempts
+    /// ran out
+    pub fn failed(&mut self, market: &str, now: std::time::Instant) -> bool {
+        let config = &self.config;
+        let (backoff, due) = self
+            .pending
+            .entry(Box::from(market))
+            .or_insert_with(|| (Backoff::new(config.clone()), now));
+
+        match backoff.next_delay() {
+            Some(delay) => {
+                *due = now + delay;
+                true
+            }
+            None => {
+                self.pending.remove(market);
+                false
+            }
+        }
+    }
+
+    /// Forgets the market once its snapshot is in place or it's unsubscribed
+    pub fn remove(&mut self, market: &str) {
+        self.pending.remove(market);
+    }
+
+    /// Returns markets whose retry is due
+    pub fn due(&self, now: std::time::Instant) -> Vec<Box<str>> {
+        self.pending
+            .iter()
+            .filter(|(_, (_, due))| *due <= now)
+            .map(|(market, _)| market.clone())
+            .collect()
+    }
+
+    /// Returns time until the next retry is due
+    pub fn next_due(&self, now: std::time::Instant) -> Option<Duration> {
+        self.pending
+            .values()
+            .map(|(_, due)| due.saturating_duration_since(now))
+            .min()
+    }
+}
+
+/// Diffs buffered for a market waiting for its snapshot
+const MAX_BUFFERED_DIFFS: usize = 1024;
+
+/// Orderbook diff streamed by an exchange
+#[derive(Debug)]
+pub struct BookDiff {
+    /// First update id of diffs chained to the previous one, `None` for
+    /// exchanges only ordering their diffs
+    pub first_update_id: Option<u64>,
+    pub last_update_id: u64,
+    pub time: f64,
+    pub bids: PriceLevelsVec<f64>,
+    pub asks: PriceLevelsVec<f64>,
+}
+
+/// Keeps orderbooks streamed as diffs in sync with their snapshots
+///
+/// Diffs arriving before the snapshot of their market are buffered. Once the
+/// snapshot is in place, seeded from REST by [`DiffSync::seed`] or streamed
+/// in-band through [`DiffSync::apply_snapshot`], the buffered diffs not
+/// included in it are replayed by update id and the book is emitted whole.
+/// Later diffs are checked against the [`SequenceTracker`] and applied one by
+/// one, a missed diff is reported as [`SequenceGap`] for the adapter loop to
+/// resync the market.
+#[derive(Debug, Default)]
+pub struct DiffSync {
+    sequences: SequenceTracker,
+    buffered: RefCell<HashMap<Box<str>, std::collections::VecDeque<BookDiff>>>,
+}
+
+impl DiffSync {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns update ids of the synced orderbooks
+    pub fn sequences(&self) -> &SequenceTracker {
+        &self.sequences
+    }
+
+    /// Applies the diffs of the market following the snapshot with given
+    /// last update id, which the adapter loop puts in place
+    pub fn seed(&self, market: &str, last_update_id: u64) {
+        self.sequences.reset(market, last_update_id);
+    }
+
+    /// Replaces the orderbook of the market with a streamed snapshot and
+    /// replays the diffs buffered meanwhile
+    pub fn apply_snapshot(
+        &self,
+        market: Box<str>,
+        last_update_id: u64,
+        orderbook: PlainOrderbook<f64>,
+        markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+        book_events: &BookEmitter,
+    ) -> Result<Option<MarketEvent>, MarketDataError> {
+        self.seed(&market, last_update_id);
+        markets.insert(market.clone(), orderbook);
+        self.replay(&market, markets)?;
+
+        match markets.get(&market) {
+            Some(orderbook) => Ok(book_events.event(market, orderbook)),
+            None => Ok(None),
+        }
+    }
+
+    /// Applies the diff to the orderbook of the market, buffers it while the
+    /// market waits for its snapshot
+    pub fn apply_diff(
+        &self,
+        market: Box<str>,
+        diff: BookDiff,
+        markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+        book_events: &BookEmitter,
+    ) -> Result<Option<MarketEvent>, MarketDataError> {
+        if self.sequences.last_update_id(&market).is_none() {
+            let mut buffered = self.buffered.borrow_mut();
+            let diffs = buffered.entry(market).or_default();
+            if diffs.len() == MAX_BUFFERED_DIFFS {
+                diffs.pop_front();
+            }
+            diffs.push_back(diff);
+
+            return Ok(None);
+        }
+
+        let replayed = self.replay(&market, markets)?;
+        let applied = self.apply(&market, &diff, markets)?;
+
+        match markets.get(&market) {
+            // Replayed diffs weren't emitted
+            Some(orderbook) if replayed => Ok(book_events.event(market, orderbook)),
+            Some(orderbook) if applied => {
+                Ok(book_events.delta(market, orderbook, &diff.bids, &diff.asks))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Replays the buffered diffs of the market, returns whether there were
+    /// any
+    fn replay(
+        &self,
+        market: &str,
+        markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    ) -> Result<bool, MarketDataError> {
+        let diffs = match self.buffered.borrow_mut().remove(market) {
+            Some(diffs) => diffs,
+            None => return Ok(false),
+        };
+        for diff in diffs.iter() {
+            self.apply(market, diff, markets)?;
+        }
+
+        Ok(true)
+    }
+
+    /// Applies the diff when it follows the orderbook, returns whether it was
+    /// applied
+    fn apply(
+        &self,
+        market: &str,
+        diff: &BookDiff,
+        markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    ) -> Result<bool, MarketDataError> {
+        // Diffs that are only ordered never miss a predecessor
+        let first_update_id = diff.first_update_id.unwrap_or(0);
+        let apply = self
+            .sequences
+            .check(market, first_update_id, diff.last_update_id)
+            .map_err(MarketDataError::with_source)?;
+
+        match markets.get_mut(market) {
+            Some(orderbook) if apply => {
+                orderbook.update_with_timestamp(&diff.bids, &diff.asks, diff.time);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(first_update_id: Option<u64>, last_update_id: u64, bid: (f64, f64)) -> BookDiff {
+        BookDiff {
+            first_update_id,
+            last_update_id,
+            time: last_update_id as f64,
+            bids: PriceLevelsVec::from_tuples_vec_unsorted(&mut [bid]),
+            asks: PriceLevelsVec::new(),
+        }
+    }
+
+    fn snapshot() -> PlainOrderbook<f64> {
+        let mut orderbook = PlainOrderbook::new();
+        orderbook.update(
+            &PriceLevelsVec::from_tuples_vec_unsorted(&mut [(100.0, 1.0)]),
+            &PriceLevelsVec::from_tuples_vec_unsorted(&mut [(101.0, 1.0)]),
+        );
+        orderbook
+    }
+
+    #[test]
+    fn test_diff_sync_replays_buffered_diffs() {
+        let sync = DiffSync::new();
+        let book_events = BookEmitter::default();
+        let mut markets = HashMap::new();
+
+        // Included in the snapshot, then following it
+        for diff in [
+            diff(Some(9), 10, (99.0, 1.0)),
+            diff(Some(11), 12, (98.0, 1.0)),
+        ] {
+            let event = sync
+                .apply_diff(Box::from("BTC/USD"), diff, &mut markets, &book_events)
+                .unwrap();
+            assert!(event.is_none());
         }
+
+        let event = sync
+            .apply_snapshot(
+                Box::from("BTC/USD"),
+                10,
+                snapshot(),
+                &mut markets,
+                &book_events,
+            )
+            .unwrap();
+        assert!(event.is_some());
+        assert_eq!(vec![98.0, 100.0], markets["BTC/USD"].bids.price_vec);
+
+        assert!(sync
+            .apply_diff(
+                Box::from("BTC/USD"),
+                diff(Some(13), 13, (97.0, 1.0)),
+                &mut markets,
+                &book_events
+            )
+            .unwrap()
+            .is_some());
+        let err = sync
+            .apply_diff(
+                Box::from("BTC/USD"),
+                diff(Some(15), 15, (96.0, 1.0)),
+                &mut markets,
+                &book_events,
+            )
+            .unwrap_err();
+        assert_eq!(Some("BTC/USD"), err.out_of_sync_market());
+    }
+
+    #[test]
+    fn test_diff_sync_ordered_diffs() {
+        let sync = DiffSync::new();
+        let book_events = BookEmitter::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTC/USD"), snapshot());
+        sync.seed("BTC/USD", 1000);
+
+        // Older than the snapshot
+        assert!(sync
+            .apply_diff(
+                Box::from("BTC/USD"),
+                diff(None, 999, (99.0, 1.0)),
+                &mut markets,
+                &book_events
+            )
+            .unwrap()
+            .is_none());
+        // Skipping ahead isn't a gap
+        assert!(sync
+            .apply_diff(
+                Box::from("BTC/USD"),
+                diff(None, 1500, (99.5, 1.0)),
+                &mut markets,
+                &book_events
+            )
+            .unwrap()
+            .is_some());
+        assert_eq!(vec![99.5, 100.0], markets["BTC/USD"].bids.price_vec);
+    }
+
+    #[test]
+    fn test_snapshot_retries() {
+        let mut retries = SnapshotRetries::new(BackoffConfig {
+            initial_delay_ms: 1000,
+            jitter: 0.0,
+            max_attempts: Some(2),
+            ..BackoffConfig::default()
+        });
+        let now = std::time::Instant::now();
+        assert_eq!(None, retries.next_due(now));
+
+        assert!(retries.failed("BTC/USD", now));
+        assert!(retries.failed("ETH/USD", now));
+        assert_eq!(Some(Duration::from_secs(1)), retries.next_due(now));
+        assert!(retries.due(now).is_empty());
+
+        let later = now + Duration::from_secs(1);
+        assert_eq!(2, retries.due(later).len());
+        retries.remove("ETH/USD");
+        // Waits twice as long after the second failure
+        assert!(retries.failed("BTC/USD", later));
+        assert_eq!(Some(Duration::from_secs(2)), retries.next_due(later));
+        assert!(!retries.failed("BTC/USD", later));
+        assert!(retries.due(later + Duration::from_secs(2)).is_empty());
     }
 }
@@ -1,35 +1,87 @@
 //! Binance market data adapter
+//!
+//! Markets come from `/api/v3/exchangeInfo`. Orderbooks are seeded from
+//! `/api/v3/depth` snapshots after subscribing and kept up to date with the
+//! `depth@100ms` diff stream, trades come from the `aggTrade` stream.
+//!
+//! Diffs carry the range of update ids they contain: diffs already included
+//! in the snapshot are dropped and a diff not continuing the previous one is
+//! reported as a sequence gap, after which the depth stream of the market is
+//! resubscribed and its book seeded from a new snapshot.
+//!
+//! Once the markets were fetched, books are kept in fixed-point at the
+//! increments of their market and converted to `f64` for the events.
+//!
+//! Liquidations are published as `forceOrder` events on the USDⓈ-M futures
+//! streams only, the adapter parses them but the spot streams it subscribes
+//! to don't carry them.
 
 pub(crate) mod rest;
-pub(crate) mod ws;
+pub mod ws;
+
+use std::cell::{Cell, RefCell};
 
 use chrono::TimeZone;
 
-use super::prelude::*;
+use super::{json::JsonBuffer, prelude::*};
 use crate::prelude::*;
-use botvana::exchange::ExchangeId;
+use botvana::{
+    exchange::ExchangeId,
+    market::{
+        derivatives::{Liquidation, PositionSide},
+        fixed::{Fixed, MarketScales},
+        trade::TradeSide,
+    },
+};
 
 #[derive(Debug)]
 pub struct Binance {
-    pub metrics: BinanceMetrics,
-    cur_idx: u64,
+    pub metrics: AdapterMetrics,
+    /// Id of the last sent request
+    cur_idx: Cell<u64>,
     api_url: Box<str>,
+    sequences: SequenceTracker,
+    book_events: BookEmitter,
+    /// Price and size scales by Binance symbol
+    scales: RefCell<HashMap<Box<str>, MarketScales>>,
+    /// Fixed-point books of the markets with known scales, seeded from the
+    /// `f64` book with the first diff after its snapshot
+    books: RefCell<HashMap<Box<str>, PlainOrderbook<Fixed>>>,
+    json: RefCell<JsonBuffer>,
+}
+
+impl Binance {
+    /// Returns id for the next request
+    fn next_idx(&self) -> u64 {
+        self.cur_idx.set(self.cur_idx.get() + 1);
+        self.cur_idx.get()
+    }
 }
 
 impl Default for Binance {
     fn default() -> Self {
         Binance {
             api_url: Box::from("https://api.binance.com"),
-            cur_idx: 0,
-            metrics: BinanceMetrics::default(),
+            cur_idx: Cell::new(0),
+            metrics: AdapterMetrics::default(),
+            sequences: SequenceTracker::new(),
+            book_events: BookEmitter::default(),
+            scales: RefCell::new(HashMap::new()),
+            books: RefCell::new(HashMap::new()),
+            json: RefCell::new(JsonBuffer::default()),
         }
     }
 }
 
 #[async_trait(?Send)]
 impl RestMarketDataAdapter for Binance {
-    const NAME: &'static str = "binance-rest";
-    const EXCHANGE_REF: ExchangeId = ExchangeId::BinanceSpot;
+    fn name(&self) -> &'static str {
+        "binance-rest"
+    }
+
+    fn exchange(&self) -> ExchangeId {
+        ExchangeId::BinanceSpot
+    }
 
     /// Fetches availables markets on Binance
     async fn fetch_markets(&self) -> Result<Box<[Market]>, MarketDataError> {
@@ -39,6 +91,7 @@ impl RestMarketDataAdapter for Binance {
             .try_into()
             .map_err(MarketDataError::with_source)?;
 
+        rate_limit::acquire(ExchangeId::BinanceSpot, Endpoint::Markets).await;
         let mut res = client
             .get(format!("/api/v3/exchangeInfo"))
             .await
@@ -53,24 +106,35 @@ impl RestMarketDataAdapter for Binance {
 
         debug!("{} markets on Binance", info.symbols.len());
 
-        Ok(info
+        let markets: Box<[Market]> = info
             .symbols
             .iter()
             .filter_map(|sym| Market::try_from(sym).ok())
-            .collect())
+            .collect();
+        let mut scales = self.scales.borrow_mut();
+        for market in markets.iter() {
+            if let Some(market_scales) = market.scales() {
+                scales.insert(Box::from(market.native_symbol.as_str()), market_scales);
+            }
+        }
+
+        Ok(markets)
     }
 
-    /// Fetches orderbook snapshot
+    /// Fetches orderbook snapshot and remembers its last update id
     async fn fetch_orderbook_snapshot(
         &self,
         symbol: &str,
     ) -> Result<PlainOrderbook<f64>, MarketDataError> {
+        let market = symbol;
+        let symbol = native_symbol(market);
         let client: surf::Client = surf::Config::new()
             .set_base_url(Url::parse(&self.api_url).map_err(MarketDataError::with_source)?)
             .set_timeout(Some(Duration::from_secs(5)))
             .try_into()
             .map_err(MarketDataError::with_source)?;
 
+        rate_limit::acquire(ExchangeId::BinanceSpot, Endpoint::Orderbook).await;
         let mut res = client
             .get(format!("/api/v3/depth?symbol={}", symbol))
             .await
@@ -85,38 +149,88 @@ impl RestMarketDataAdapter for Binance {
 
         let mut orderbook = PlainOrderbook::<f64>::with_capacity(1000);
         orderbook.update(&snapshot.bids, &snapshot.asks);
+        self.sequences.reset(market, snapshot.last_update_id);
+        // Replaced by the one seeded from this snapshot
+        self.books.borrow_mut().remove(market);
+
+        Ok(match self.scales.borrow().get(symbol.as_str()) {
+            Some(scales) => orderbook.to_fixed(scales).to_f64(scales),
+            None => orderbook,
+        })
+    }
+
+    async fn fetch_server_time(&self) -> Result<Option<DateTime<Utc>>, MarketDataError> {
+        let client: surf::Client = surf::Config::new()
+            .set_base_url(Url::parse(&self.api_url).map_err(MarketDataError::with_source)?)
+            .set_timeout(Some(Duration::from_secs(5)))
+            .try_into()
+            .map_err(MarketDataError::with_source)?;
+
+        rate_limit::acquire(ExchangeId::BinanceSpot, Endpoint::Time).await;
+        let mut res = client
+            .get("/api/v3/time")
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let time = serde_json::from_slice::<rest::ServerTime>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?;
 
-        Ok(orderbook)
+        Ok(Some(Utc.timestamp_millis(time.server_time)))
     }
 }
 
 impl WsMarketDataAdapter for Binance {
-    fn throughput_metrics(&self) -> &Throughput<StdInstant, RefCell<metered::common::TxPerSec>> {
-        &self.metrics.throughput
+    fn metrics(&self) -> &AdapterMetrics {
+        &self.metrics
+    }
+
+    fn set_book_events(&mut self, book_events: BookEvents) {
+        self.book_events = BookEmitter::new(book_events);
     }
 
     fn ws_url(&self) -> Box<str> {
         Box::from("wss://stream.binance.com:9443/ws")
     }
 
+    fn requires_snapshot(&self) -> bool {
+        true
+    }
+
+    fn sequences(&self) -> Option<&SequenceTracker> {
+        Some(&self.sequences)
+    }
+
     fn subscribe_msgs(&mut self, markets: &[&str]) -> Box<[String]> {
-        self.cur_idx += 1;
+        Box::new([json!({
+            "method": "SUBSCRIBE",
+            "params": streams(markets),
+            "id": self.next_idx()
+        })
+        .to_string()])
+    }
 
-        let params: Vec<_> = markets
-            .iter()
-            .map(|market| {
-                let market = market.to_lowercase().replace("-", "").replace("/", "");
-
-                [
-                    format!("{market}@depth@100ms"),
-                    format!("{market}@trade"),
-                    format!("{market}@bookTicker"),
-                ]
-            })
-            .flatten()
-            .collect();
+    fn unsubscribe_msgs(&mut self, markets: &[&str]) -> Option<Box<[String]>> {
+        Some(Box::new([json!({
+            "method": "UNSUBSCRIBE",
+            "params": streams(markets),
+            "id": self.next_idx()
+        })
+        .to_string()]))
+    }
+
+    /// Resubscribes the depth stream of the market
+    fn resync_msgs(&self, market: &str) -> Option<Box<[String]>> {
+        let market = native_symbol(market).to_lowercase();
+        let params = [format!("{market}@depth@100ms")];
 
-        Box::new([json!({"method": "SUBSCRIBE", "params": params, "id": self.cur_idx}).to_string()])
+        Some(Box::new([
+            json!({"method": "UNSUBSCRIBE", "params": params, "id": self.next_idx()}).to_string(),
+            json!({"method": "SUBSCRIBE", "params": params, "id": self.next_idx()}).to_string(),
+        ]))
     }
 
     fn process_ws_msg(
@@ -126,77 +240,183 @@ impl WsMarketDataAdapter for Binance {
     ) -> Result<Option<MarketEvent>, MarketDataError> {
         trace!("got ws_msg = {msg:?}");
 
-        let ws_msg = serde_json::from_slice::<ws::WsMsg>(msg.as_bytes());
+        let mut json = self.json.borrow_mut();
+        let ws_msg = json.parse::<ws::WsMsg>(msg);
 
         match ws_msg {
             Err(e) => {
                 error!("Error parsing ws_msg: {msg}");
 
-                Err(MarketDataError {
-                    source: Box::new(e),
-                })
+                Err(e)
             }
-            Ok(ws_msg) => Ok(process_data_ws_message(ws_msg, markets)?),
+            Ok(ws_msg) => Ok(process_data_ws_message(
+                ws_msg,
+                markets,
+                &self.sequences,
+                &self.scales.borrow(),
+                &self.books,
+                &self.book_events,
+            )?),
         }
     }
 }
 
+/// Converts market name to Binance symbol, `BTC/USDT` to `BTCUSDT`
+fn native_symbol(market: &str) -> String {
+    market.to_uppercase().replace(['-', '/'], "")
+}
+
+/// Returns depth, trade and book ticker streams of the markets
+fn streams(markets: &[&str]) -> Vec<String> {
+    markets
+        .iter()
+        .flat_map(|market| {
+            let market = native_symbol(market).to_lowercase();
+
+            [
+                format!("{market}@depth@100ms"),
+                format!("{market}@aggTrade"),
+                format!("{market}@bookTicker"),
+            ]
+        })
+        .collect()
+}
+
+/// Returns the market name Binance symbol was subscribed as
+fn internal_symbol(
+    markets: &HashMap<Box<str>, PlainOrderbook<f64>>,
+    symbol: &str,
+) -> Option<Box<str>> {
+    markets.keys().find(|k| native_symbol(k) == symbol).cloned()
+}
+
 #[inline]
 fn process_data_ws_message(
     ws_msg: ws::WsMsg,
     markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    sequences: &SequenceTracker,
+    scales: &HashMap<Box<str>, MarketScales>,
+    books: &RefCell<HashMap<Box<str>, PlainOrderbook<Fixed>>>,
+    book_events: &BookEmitter,
 ) -> Result<Option<MarketEvent>, MarketDataError> {
-    let data = ws_msg;
-    match data {
+    match ws_msg {
+        ws::WsMsg::AggTrade(trade) => {
+            let dt = Utc.timestamp_millis(trade.trade_time);
+            let symbol =
+                internal_symbol(markets, trade.symbol).unwrap_or_else(|| Box::from(trade.symbol));
+            let trade_scales = scales.get(trade.symbol);
+            // Sold by the taker when the buyer's order was resting
+            let side = match trade.maket_maker_buyer {
+                true => TradeSide::Sell,
+                false => TradeSide::Buy,
+            };
+            let mut trade =
+                botvana::market::trade::Trade::new(trade.price, trade.size, dt).with_side(side);
+            if let Some(scales) = trade_scales {
+                trade.round_to(scales);
+            }
+
+            Ok(Some(MarketEvent::trades(symbol, Box::new([trade]))))
+        }
         ws::WsMsg::Trade(trade) => {
-            let dt = Utc.timestamp(trade.trade_time as i64, 0);
-            let symbol = trade.symbol;
-            let trade = botvana::market::trade::Trade::new(trade.price, trade.size, dt);
+            let dt = Utc.timestamp_millis(trade.trade_time as i64);
+            let symbol =
+                internal_symbol(markets, trade.symbol).unwrap_or_else(|| Box::from(trade.symbol));
+            let trade_scales = scales.get(trade.symbol);
+            // Sold by the taker when the buyer's order was resting
+            let side = match trade.maket_maker_buyer {
+                true => TradeSide::Sell,
+                false => TradeSide::Buy,
+            };
+            let mut trade =
+                botvana::market::trade::Trade::new(trade.price, trade.size, dt).with_side(side);
+            if let Some(scales) = trade_scales {
+                trade.round_to(scales);
+            }
 
-            Ok(Some(MarketEvent::trades(
-                Box::from(symbol),
-                Box::new([trade]),
-            )))
+            Ok(Some(MarketEvent::trades(symbol, Box::new([trade]))))
         }
-        ws::WsMsg::DepthUpdate(update) => {
+        ws::WsMsg::DepthUpdate(mut update) => {
             // Convert symbol coming in from the WS API to "internal"
-            let symbol = markets
-                .keys()
-                .find(|k| k.replace("/", "") == update.symbol)
-                .cloned();
-            if let Some(symbol) = symbol {
-                let orderbook = markets.get_mut(&symbol);
-                if let Some(orderbook) = orderbook {
-                    orderbook.update_with_timestamp(&update.bids, &update.asks, update.event_time);
-                    Ok(Some(MarketEvent::orderbook_update(
-                        Box::from(update.symbol),
-                        Box::new(orderbook.clone()),
-                    )))
-                } else {
-                    warn!("No orderbook snapshot found for {symbol}");
-                    Ok(None)
+            let symbol = match internal_symbol(markets, update.symbol) {
+                Some(symbol) => symbol,
+                None => {
+                    warn!("No symbol mapping found for {}", update.symbol);
+                    return Ok(None);
+                }
+            };
+            // Without snapshot or already included in it
+            let apply = sequences
+                .check(&symbol, update.first_update_id, update.final_update_id)
+                .map_err(MarketDataError::with_source)?;
+            if !apply {
+                return Ok(None);
+            }
+
+            let orderbook = match markets.get_mut(&symbol) {
+                Some(orderbook) => orderbook,
+                None => {
+                    books.borrow_mut().remove(&symbol);
+                    return Ok(None);
                 }
-            } else {
-                warn!("No symbol mapping found for {}", update.symbol);
-                Ok(None)
+            };
+            if let Some(scales) = scales.get(update.symbol) {
+                let (bids, asks) = (update.bids.to_fixed(scales), update.asks.to_fixed(scales));
+                let mut books = books.borrow_mut();
+                if !books.contains_key(&symbol) {
+                    books.insert(symbol.clone(), orderbook.to_fixed(scales));
+                }
+                books.get_mut(&symbol).unwrap().update_with_timestamp(
+                    &bids,
+                    &asks,
+                    update.event_time,
+                );
+                update.bids = bids.to_f64(scales);
+                update.asks = asks.to_f64(scales);
             }
+            orderbook.update_with_timestamp(&update.bids, &update.asks, update.event_time);
+
+            Ok(book_events.delta(symbol, orderbook, &update.bids, &update.asks))
         }
         ws::WsMsg::OrderbookTicker(book_ticker) => {
-            return Ok(Some(MarketEvent::mid_price_change(
-                Box::from(book_ticker.symbol),
+            let symbol = internal_symbol(markets, book_ticker.symbol)
+                .unwrap_or_else(|| Box::from(book_ticker.symbol));
+
+            Ok(Some(MarketEvent::mid_price_change(
+                symbol,
                 book_ticker.bid_price,
                 book_ticker.ask_price,
             )))
         }
+        ws::WsMsg::ForceOrder(force_order) => {
+            let order = &force_order.order;
+            let symbol =
+                internal_symbol(markets, order.symbol).unwrap_or_else(|| Box::from(order.symbol));
+            let position = match order.side {
+                "SELL" => PositionSide::Long,
+                "BUY" => PositionSide::Short,
+                other => {
+                    return Err(MarketDataError::with_source(UnknownVariantError {
+                        variant: other.to_string(),
+                    }))
+                }
+            };
+            let liquidation = Liquidation {
+                position,
+                price: order.average_price,
+                size: order.filled_size,
+                time: Utc.timestamp_millis(order.trade_time),
+            };
+
+            Ok(Some(MarketEvent::liquidation(
+                symbol,
+                Box::new([liquidation]),
+            )))
+        }
         ws::WsMsg::Response(_response) => Ok(None),
     }
 }
 
-#[derive(Default, Debug)]
-pub struct BinanceMetrics {
-    throughput: Throughput<StdInstant, RefCell<metered::common::TxPerSec>>,
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -250,30 +470,163 @@ mod tests {
     }
 
     #[test]
-    fn test_process_ws_msg_depth_update() {
-        let depth_msg = r#"{
-            "e": "depthUpdate",
+    fn test_process_ws_msg_agg_trade() {
+        let agg_trade_msg = r#"{
+            "e": "aggTrade",
             "E": 123456789,
             "s": "BNBBTC",
-            "U": 157,
-            "u": 160,
-            "b": [
-              [
-                "0.0024",
-                "10"
-              ]
-            ],
-            "a": [
-              [
-                "0.0026",
-                "100"
-              ]
-            ]
+            "a": 12345,
+            "p": "0.001",
+            "q": "100",
+            "f": 100,
+            "l": 105,
+            "T": 1642011077609,
+            "m": true,
+            "M": true
         }"#;
         let b = Binance::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BNB/BTC"), PlainOrderbook::new());
+
+        let event = b.process_ws_msg(agg_trade_msg, &mut markets).unwrap();
+
+        match event.map(|event| event.r#type) {
+            Some(MarketEventType::Trades(market, trades)) => {
+                assert_eq!("BNB/BTC", &*market);
+                assert_eq!(1642011077609, trades[0].time.timestamp_millis());
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_process_ws_msg_depth_update() {
+        let depth_msg = |first: u64, last: u64| {
+            format!(
+                r#"{{
+                    "e": "depthUpdate",
+                    "E": 123456789,
+                    "s": "BNBBTC",
+                    "U": {first},
+                    "u": {last},
+                    "b": [["0.0024", "10"]],
+                    "a": [["0.0026", "100"]]
+                }}"#
+            )
+        };
+        let b = Binance::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BNB/BTC"), PlainOrderbook::new());
+
+        // Diffs are ignored until there is a snapshot
+        assert!(b
+            .process_ws_msg(&depth_msg(157, 160), &mut markets)
+            .unwrap()
+            .is_none());
+
+        b.sequences.reset("BNB/BTC", 158);
+        let event = b
+            .process_ws_msg(&depth_msg(157, 160), &mut markets)
+            .unwrap();
+        assert!(event.is_some());
+        assert_eq!(Some(&0.0024), markets["BNB/BTC"].bids.price_vec.last());
+
+        // Already applied
+        assert!(b
+            .process_ws_msg(&depth_msg(157, 160), &mut markets)
+            .unwrap()
+            .is_none());
+        let err = b
+            .process_ws_msg(&depth_msg(165, 170), &mut markets)
+            .unwrap_err();
+        assert_eq!(Some("BNB/BTC"), err.out_of_sync_market());
+        // Dropped until the resync snapshot
+        assert!(b
+            .process_ws_msg(&depth_msg(171, 175), &mut markets)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_depth_update_fixed_book() {
+        use botvana::market::fixed::Scale;
+
+        let depth_msg = |first: u64, last: u64, bid: &str, size: &str| {
+            format!(
+                r#"{{
+                    "e": "depthUpdate",
+                    "E": 123456789,
+                    "s": "BNBBTC",
+                    "U": {first},
+                    "u": {last},
+                    "b": [["{bid}", "{size}"]],
+                    "a": []
+                }}"#
+            )
+        };
+        let b = Binance::default();
+        let scales = MarketScales {
+            price: Scale::new(0.0001).unwrap(),
+            size: Scale::new(0.01).unwrap(),
+        };
+        b.scales.borrow_mut().insert(Box::from("BNBBTC"), scales);
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BNB/BTC"), PlainOrderbook::new());
+        b.sequences.reset("BNB/BTC", 158);
+
+        b.process_ws_msg(&depth_msg(157, 160, "0.0024", "10"), &mut markets)
+            .unwrap();
+        assert_eq!(vec![Fixed(24)], b.books.borrow()["BNB/BTC"].bids.price_vec);
+
+        // Removed although Binance formatted its price differently
+        b.process_ws_msg(&depth_msg(161, 161, "0.0024000001", "0"), &mut markets)
+            .unwrap();
+        assert!(b.books.borrow()["BNB/BTC"].bids.price_vec.is_empty());
+        assert!(markets["BNB/BTC"].bids.price_vec.is_empty());
+    }
+
+    #[test]
+    fn test_process_ws_msg_force_order() {
+        let msg = r#"{
+            "e": "forceOrder",
+            "E": 1568014460893,
+            "o": {
+                "s": "BTCUSDT",
+                "S": "SELL",
+                "o": "LIMIT",
+                "f": "IOC",
+                "q": "0.014",
+                "p": "9910",
+                "ap": "9910",
+                "X": "FILLED",
+                "l": "0.014",
+                "z": "0.014",
+                "T": 1568014460893
+            }
+        }"#;
 
-        b.process_ws_msg(depth_msg, &mut HashMap::new()).unwrap();
+        match Binance::default()
+            .process_ws_msg(msg, &mut HashMap::new())
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::Liquidation(market, liquidations)) => {
+                assert_eq!("BTCUSDT", &*market);
+                assert_eq!(PositionSide::Long, liquidations[0].position);
+                assert_eq!(9910.0, liquidations[0].price);
+                assert_eq!(0.014, liquidations[0].size);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_resync_msgs() {
+        let b = Binance::default();
+        let msgs = b.resync_msgs("BNB/BTC").unwrap();
 
-        //assert!(event.is_some());
+        assert_eq!(2, msgs.len());
+        assert!(msgs[0].contains("UNSUBSCRIBE"));
+        assert!(msgs[1].contains("bnbbtc@depth@100ms"));
     }
 }
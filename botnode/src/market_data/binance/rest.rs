@@ -68,6 +68,14 @@ pub struct SymbolInfo<'a> {
     pub quote_asset_precision: u8,
 }
 
+/// Response of `/api/v3/time`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerTime {
+    /// Milliseconds timestamp
+    pub server_time: i64,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -15,9 +15,13 @@ pub enum WsMsg<'a> {
     #[serde(borrow)]
     OrderbookTicker(WsBookTicker<'a>),
     #[serde(borrow)]
+    AggTrade(WsAggTrade<'a>),
+    #[serde(borrow)]
     Trade(WsTrade<'a>),
     #[serde(borrow)]
     DepthUpdate(WsDepthUpdate<'a>),
+    #[serde(borrow)]
+    ForceOrder(WsForceOrder<'a>),
     Response(WsResponse),
 }
 
@@ -47,6 +51,34 @@ pub struct WsTrade<'a> {
     pub maket_maker_buyer: bool,
 }
 
+/// Trades of a single taker order at the same price
+#[derive(Debug, Deserialize)]
+pub struct WsAggTrade<'a> {
+    #[serde(rename = "e")]
+    pub event: &'a str,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "s")]
+    pub symbol: &'a str,
+    #[serde(rename = "a")]
+    pub agg_trade_id: u64,
+    #[serde(rename = "p")]
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub price: f64,
+    #[serde(rename = "q")]
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub size: f64,
+    #[serde(rename = "f")]
+    pub first_trade_id: u64,
+    #[serde(rename = "l")]
+    pub last_trade_id: u64,
+    /// Trade time in milliseconds
+    #[serde(rename = "T")]
+    pub trade_time: i64,
+    #[serde(rename = "m")]
+    pub maket_maker_buyer: bool,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct WsDepthUpdate<'a> {
     #[serde(rename = "E")]
@@ -65,6 +97,36 @@ pub struct WsDepthUpdate<'a> {
     pub asks: PriceLevelsVec<f64>,
 }
 
+/// Liquidation order of the USDⓈ-M futures `forceOrder` stream
+#[derive(Debug, Deserialize)]
+pub struct WsForceOrder<'a> {
+    #[serde(rename = "e")]
+    pub event: &'a str,
+    #[serde(rename = "E")]
+    pub event_time: u64,
+    #[serde(rename = "o", borrow)]
+    pub order: WsForceOrderData<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WsForceOrderData<'a> {
+    #[serde(rename = "s")]
+    pub symbol: &'a str,
+    /// `SELL` closes a long position, `BUY` a short one
+    #[serde(rename = "S")]
+    pub side: &'a str,
+    #[serde(rename = "ap")]
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub average_price: f64,
+    /// Filled size
+    #[serde(rename = "z")]
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub filled_size: f64,
+    /// Trade time in milliseconds
+    #[serde(rename = "T")]
+    pub trade_time: i64,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct WsBookTicker<'a> {
     #[serde(rename = "u")]
@@ -0,0 +1,359 @@
+//! Bitstamp market data adapter
+//!
+//! Markets come from `/api/v2/trading-pairs-info/`. The Websocket API only
+//! streams orderbook diffs on the `diff_order_book_{pair}` channel, books
+//! are seeded from `/api/v2/order_book/{pair}/` snapshots and synced with the
+//! diffs by [`DiffSync`]. Diffs aren't numbered, they are ordered by their
+//! microsecond timestamps, diffs older than the snapshot are dropped. Trades
+//! come from the `live_trades_{pair}` channel.
+//!
+//! The adapter keeps connections alive with `bts:heartbeat` requests.
+
+pub(crate) mod rest;
+pub(crate) mod ws;
+
+use chrono::TimeZone;
+
+use super::prelude::*;
+use crate::prelude::*;
+use botvana::exchange::ExchangeId;
+
+const DIFF_CHANNEL_PREFIX: &str = "diff_order_book_";
+const TRADES_CHANNEL_PREFIX: &str = "live_trades_";
+
+#[derive(Debug)]
+pub struct Bitstamp {
+    pub metrics: AdapterMetrics,
+    api_url: Box<str>,
+    ws_url: Box<str>,
+    diffs: DiffSync,
+    book_events: BookEmitter,
+}
+
+impl Default for Bitstamp {
+    fn default() -> Self {
+        Bitstamp {
+            api_url: Box::from("https://www.bitstamp.net"),
+            ws_url: Box::from("wss://ws.bitstamp.net"),
+            metrics: AdapterMetrics::default(),
+            diffs: DiffSync::new(),
+            book_events: BookEmitter::default(),
+        }
+    }
+}
+
+impl Bitstamp {
+    fn client(&self) -> Result<surf::Client, MarketDataError> {
+        surf::Config::new()
+            .set_base_url(Url::parse(&self.api_url).map_err(MarketDataError::with_source)?)
+            .set_timeout(Some(Duration::from_secs(5)))
+            .try_into()
+            .map_err(MarketDataError::with_source)
+    }
+}
+
+#[async_trait(?Send)]
+impl RestMarketDataAdapter for Bitstamp {
+    fn name(&self) -> &'static str {
+        "bitstamp-rest"
+    }
+
+    fn exchange(&self) -> ExchangeId {
+        ExchangeId::Bitstamp
+    }
+
+    /// Fetches trading pairs on Bitstamp
+    async fn fetch_markets(&self) -> Result<Box<[Market]>, MarketDataError> {
+        rate_limit::acquire(ExchangeId::Bitstamp, Endpoint::Markets).await;
+        let mut res = self
+            .client()?
+            .get("/api/v2/trading-pairs-info/")
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let pairs = serde_json::from_slice::<Vec<rest::TradingPair>>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?;
+        let markets: Box<[Market]> = pairs
+            .iter()
+            .filter(|pair| pair.trading == "Enabled")
+            .filter_map(|pair| Market::try_from(pair).ok())
+            .collect();
+
+        debug!("{} trading pairs on Bitstamp", markets.len());
+
+        Ok(markets)
+    }
+
+    /// Fetches orderbook snapshot and syncs the diffs following it
+    async fn fetch_orderbook_snapshot(
+        &self,
+        symbol: &str,
+    ) -> Result<PlainOrderbook<f64>, MarketDataError> {
+        rate_limit::acquire(ExchangeId::Bitstamp, Endpoint::Orderbook).await;
+        let mut res = self
+            .client()?
+            .get(format!("/api/v2/order_book/{}/", native_symbol(symbol)))
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let snapshot = serde_json::from_slice::<rest::OrderbookSnapshot>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?;
+        self.diffs.seed(symbol, snapshot.microtimestamp);
+
+        Ok(PlainOrderbook {
+            bids: snapshot.bids,
+            asks: snapshot.asks,
+            time: (snapshot.microtimestamp / 1000) as f64,
+            max_depth: None,
+        })
+    }
+}
+
+impl WsMarketDataAdapter for Bitstamp {
+    fn metrics(&self) -> &AdapterMetrics {
+        &self.metrics
+    }
+
+    fn set_book_events(&mut self, book_events: BookEvents) {
+        self.book_events = BookEmitter::new(book_events);
+    }
+
+    fn requires_snapshot(&self) -> bool {
+        true
+    }
+
+    fn sequences(&self) -> Option<&SequenceTracker> {
+        Some(self.diffs.sequences())
+    }
+
+    fn ws_url(&self) -> Box<str> {
+        self.ws_url.clone()
+    }
+
+    fn subscribe_msgs(&mut self, markets: &[&str]) -> Box<[String]> {
+        info!("Subscribing for {markets:?}");
+
+        channel_msgs("bts:subscribe", markets)
+    }
+
+    fn unsubscribe_msgs(&mut self, markets: &[&str]) -> Option<Box<[String]>> {
+        info!("Unsubscribing {markets:?}");
+
+        Some(channel_msgs("bts:unsubscribe", markets))
+    }
+
+    fn ping_msg(&self) -> Option<String> {
+        Some(json!({"event": "bts:heartbeat"}).to_string())
+    }
+
+    fn process_ws_msg(
+        &self,
+        msg: &str,
+        markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    ) -> Result<Option<MarketEvent>, MarketDataError> {
+        trace!("got ws_msg = {msg:?}");
+
+        match serde_json::from_slice::<ws::WsMsg>(msg.as_bytes()) {
+            Ok(ws_msg) => {
+                process_public_ws_message(ws_msg, markets, &self.diffs, &self.book_events)
+            }
+            Err(e) => {
+                error!("Error parsing ws_msg: {msg}");
+
+                Err(MarketDataError::with_source(e))
+            }
+        }
+    }
+}
+
+/// Converts market name to Bitstamp pair, `BTC/USD` to `btcusd`
+fn native_symbol(market: &str) -> String {
+    market.to_lowercase().replace('/', "")
+}
+
+/// Returns `event` messages of the diff and trade channels of the markets
+fn channel_msgs(event: &str, markets: &[&str]) -> Box<[String]> {
+    markets
+        .iter()
+        .flat_map(|market| {
+            let pair = native_symbol(market);
+
+            [
+                format!("{DIFF_CHANNEL_PREFIX}{pair}"),
+                format!("{TRADES_CHANNEL_PREFIX}{pair}"),
+            ]
+        })
+        .map(|channel| json!({"event": event, "data": {"channel": channel}}).to_string())
+        .collect()
+}
+
+/// Returns the market name the channel of Bitstamp pair was subscribed as
+fn internal_symbol(
+    markets: &HashMap<Box<str>, PlainOrderbook<f64>>,
+    channel: &str,
+    prefix: &str,
+) -> Option<Box<str>> {
+    let pair = channel.strip_prefix(prefix)?;
+
+    markets.keys().find(|k| native_symbol(k) == pair).cloned()
+}
+
+#[inline]
+fn process_public_ws_message(
+    ws_msg: ws::WsMsg,
+    markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    diffs: &DiffSync,
+    book_events: &BookEmitter,
+) -> Result<Option<MarketEvent>, MarketDataError> {
+    match ws_msg {
+        ws::WsMsg::Data(mut diff_msg) => {
+            let symbol = match internal_symbol(markets, diff_msg.channel, DIFF_CHANNEL_PREFIX) {
+                Some(symbol) => symbol,
+                None => {
+                    warn!("No symbol mapping found for {}", diff_msg.channel);
+                    return Ok(None);
+                }
+            };
+            let diff = &mut diff_msg.data;
+
+            diffs.apply_diff(
+                symbol,
+                BookDiff {
+                    first_update_id: None,
+                    last_update_id: diff.microtimestamp,
+                    time: (diff.microtimestamp / 1000) as f64,
+                    bids: PriceLevelsVec::from_tuples_vec_unsorted(&mut diff.bids),
+                    asks: PriceLevelsVec::from_tuples_vec_unsorted(&mut diff.asks),
+                },
+                markets,
+                book_events,
+            )
+        }
+        ws::WsMsg::Trade(trade_msg) => {
+            let symbol = internal_symbol(markets, trade_msg.channel, TRADES_CHANNEL_PREFIX)
+                .unwrap_or_else(|| Box::from(trade_msg.channel));
+            let trade = &trade_msg.data;
+            let trade = botvana::market::trade::Trade::new(
+                trade
+                    .price_str
+                    .parse::<f64>()
+                    .map_err(MarketDataError::with_source)?,
+                trade
+                    .amount_str
+                    .parse::<f64>()
+                    .map_err(MarketDataError::with_source)?,
+                Utc.timestamp_nanos(trade.microtimestamp as i64 * 1000),
+            );
+
+            Ok(Some(MarketEvent::trades(symbol, Box::new([trade]))))
+        }
+        ws::WsMsg::Error(error_msg) => Err(MarketDataError::convert_error(format!(
+            "Bitstamp error on {}: {}",
+            error_msg.channel, error_msg.data.message
+        ))),
+        ws::WsMsg::RequestReconnect => {
+            warn!("Bitstamp requested reconnect");
+            Ok(None)
+        }
+        ws::WsMsg::SubscriptionSucceeded
+        | ws::WsMsg::UnsubscriptionSucceeded
+        | ws::WsMsg::Heartbeat => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff_msg(microtimestamp: u64, bid: &str) -> String {
+        format!(
+            r#"{{
+                "data": {{
+                    "timestamp": "1643643586",
+                    "microtimestamp": "{microtimestamp}",
+                    "bids": [["{bid}", "0.5"]],
+                    "asks": []
+                }},
+                "channel": "diff_order_book_btcusd",
+                "event": "data"
+            }}"#
+        )
+    }
+
+    #[test]
+    fn test_native_symbol() {
+        assert_eq!("btcusd", native_symbol("BTC/USD"));
+    }
+
+    #[test]
+    fn test_process_ws_msg_diffs_after_snapshot() {
+        let bitstamp = Bitstamp::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTC/USD"), PlainOrderbook::new());
+        bitstamp.diffs.seed("BTC/USD", 1643643586000000);
+
+        let msg = diff_msg(1643643585000000, "36700");
+        assert!(bitstamp
+            .process_ws_msg(&msg, &mut markets)
+            .unwrap()
+            .is_none());
+
+        let msg = diff_msg(1643643587000000, "36780");
+        match bitstamp
+            .process_ws_msg(&msg, &mut markets)
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::OrderbookUpdate(market, orderbook)) => {
+                assert_eq!("BTC/USD", &*market);
+                assert_eq!(vec![36780.0], orderbook.bids.price_vec);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_process_ws_msg_trade() {
+        let bitstamp = Bitstamp::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTC/USD"), PlainOrderbook::new());
+
+        let msg = r#"{
+            "data": {
+                "id": 216155654,
+                "timestamp": "1643643587",
+                "amount": 0.0016,
+                "amount_str": "0.00160000",
+                "price": 36771,
+                "price_str": "36771",
+                "type": 0,
+                "microtimestamp": "1643643587276000",
+                "buy_order_id": 1449568084365312,
+                "sell_order_id": 1449568078221312
+            },
+            "channel": "live_trades_btcusd",
+            "event": "trade"
+        }"#;
+
+        match bitstamp
+            .process_ws_msg(msg, &mut markets)
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::Trades(market, trades)) => {
+                assert_eq!("BTC/USD", &*market);
+                assert_eq!(36771.0, trades[0].price);
+                assert_eq!(0.0016, trades[0].size);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Deserializer};
+
+use botvana::{exchange::ExchangeId, market::orderbook::*};
+
+/// Trading pair returned by `/api/v2/trading-pairs-info/`
+#[derive(Debug, Deserialize)]
+pub struct TradingPair<'a> {
+    /// Market name, e.g. `BTC/USD`
+    pub name: &'a str,
+    /// Symbol used in URLs and channels, e.g. `btcusd`
+    pub url_symbol: &'a str,
+    pub base_decimals: u8,
+    pub counter_decimals: u8,
+    /// `Enabled` or `Disabled`
+    pub trading: &'a str,
+}
+
+impl<'a> TryFrom<&TradingPair<'a>> for botvana::market::Market {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(pair: &TradingPair<'a>) -> Result<Self, Self::Error> {
+        let (base, quote) = pair
+            .name
+            .split_once('/')
+            .ok_or_else(|| format!("Invalid pair name: {}", pair.name))?;
+
+        Ok(Self {
+            exchange: ExchangeId::Bitstamp,
+            name: pair.name.to_string(),
+            native_symbol: pair.url_symbol.to_string(),
+            size_increment: 1.0 / 10_i32.pow(pair.base_decimals as u32) as f64,
+            price_increment: 1.0 / 10_i32.pow(pair.counter_decimals as u32) as f64,
+            r#type: botvana::market::MarketType::Spot(botvana::market::SpotMarket {
+                base: base.to_string(),
+                quote: quote.to_string(),
+            }),
+        })
+    }
+}
+
+/// Orderbook returned by `/api/v2/order_book/{pair}/`
+#[derive(Debug, Deserialize)]
+pub struct OrderbookSnapshot {
+    #[serde(deserialize_with = "deserialize_into_price_levels_vec")]
+    pub bids: PriceLevelsVec<f64>,
+    #[serde(deserialize_with = "deserialize_into_price_levels_vec")]
+    pub asks: PriceLevelsVec<f64>,
+    /// Microseconds timestamp, diffs up to it are included
+    #[serde(deserialize_with = "super::ws::de_number_from_str")]
+    pub microtimestamp: u64,
+}
+
+fn deserialize_into_price_levels_vec<'de, D>(
+    deserializer: D,
+) -> Result<PriceLevelsVec<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut levels = super::ws::de_price_levels_from_str(deserializer)?;
+
+    Ok(PriceLevelsVec::from_tuples_vec_unsorted(&mut levels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_trading_pairs() {
+        let sample = r#"[{
+            "name": "BTC/USD",
+            "url_symbol": "btcusd",
+            "base_decimals": 8,
+            "counter_decimals": 0,
+            "instant_order_counter_decimals": 2,
+            "minimum_order": "10 USD",
+            "trading": "Enabled",
+            "instant_and_market_orders": "Enabled",
+            "description": "Bitcoin / U.S. dollar"
+        }]"#;
+
+        let pairs = serde_json::from_str::<Vec<TradingPair>>(sample).unwrap();
+        let market = botvana::market::Market::try_from(&pairs[0]).unwrap();
+
+        assert_eq!("BTC/USD", market.name);
+        assert_eq!("btcusd", market.native_symbol);
+        assert_eq!(1.0, market.price_increment);
+        assert_eq!(0.00000001, market.size_increment);
+    }
+
+    #[test]
+    fn test_parse_orderbook_snapshot() {
+        let sample = r#"{
+            "timestamp": "1643643584",
+            "microtimestamp": "1643643584684047",
+            "bids": [["36786", "0.20000000"], ["36785", "1.5"]],
+            "asks": [["36788", "0.05000000"]]
+        }"#;
+
+        let snapshot = serde_json::from_str::<OrderbookSnapshot>(sample).unwrap();
+
+        assert_eq!(vec![36785.0, 36786.0], snapshot.bids.price_vec);
+        assert_eq!(1643643584684047, snapshot.microtimestamp);
+    }
+}
@@ -0,0 +1,117 @@
+use serde::{Deserialize, Deserializer};
+
+/// Bitstamp Websocket message
+#[derive(Debug, Deserialize)]
+#[serde(tag = "event")]
+pub enum WsMsg<'a> {
+    #[serde(rename = "data", borrow)]
+    Data(ChannelMsg<'a, OrderbookDiff>),
+    #[serde(rename = "trade", borrow)]
+    Trade(ChannelMsg<'a, Trade<'a>>),
+    #[serde(rename = "bts:subscription_succeeded")]
+    SubscriptionSucceeded,
+    #[serde(rename = "bts:unsubscription_succeeded")]
+    UnsubscriptionSucceeded,
+    #[serde(rename = "bts:heartbeat")]
+    Heartbeat,
+    /// Sent before Bitstamp closes the connection for maintenance
+    #[serde(rename = "bts:request_reconnect")]
+    RequestReconnect,
+    #[serde(rename = "bts:error", borrow)]
+    Error(ChannelMsg<'a, Error<'a>>),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChannelMsg<'a, T> {
+    pub channel: &'a str,
+    pub data: T,
+}
+
+/// Data of the `diff_order_book_{pair}` channel
+#[derive(Debug, Deserialize)]
+pub struct OrderbookDiff {
+    /// Size of zero removes the level
+    #[serde(deserialize_with = "de_price_levels_from_str")]
+    pub bids: Vec<(f64, f64)>,
+    #[serde(deserialize_with = "de_price_levels_from_str")]
+    pub asks: Vec<(f64, f64)>,
+    /// Microseconds timestamp
+    #[serde(deserialize_with = "de_number_from_str")]
+    pub microtimestamp: u64,
+}
+
+/// Data of the `live_trades_{pair}` channel
+#[derive(Debug, Deserialize)]
+pub struct Trade<'a> {
+    pub price_str: &'a str,
+    pub amount_str: &'a str,
+    /// Microseconds timestamp
+    #[serde(deserialize_with = "de_number_from_str")]
+    pub microtimestamp: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Error<'a> {
+    #[serde(default)]
+    pub message: &'a str,
+}
+
+pub(super) fn de_price_levels_from_str<'de, D>(deserializer: D) -> Result<Vec<(f64, f64)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let levels: Vec<(&'de str, &'de str)> = Deserialize::deserialize(deserializer)?;
+
+    levels
+        .iter()
+        .map(|(price, size)| Ok((price.parse::<f64>()?, size.parse::<f64>()?)))
+        .collect::<Result<_, std::num::ParseFloatError>>()
+        .map_err(serde::de::Error::custom)
+}
+
+pub(super) fn de_number_from_str<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let number: &'de str = Deserialize::deserialize(deserializer)?;
+
+    number.parse().map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_diff() {
+        let msg = r#"{
+            "data": {
+                "timestamp": "1643643586",
+                "microtimestamp": "1643643586544025",
+                "bids": [["36780", "0.00000000"]],
+                "asks": [["36790", "0.51000000"]]
+            },
+            "channel": "diff_order_book_btcusd",
+            "event": "data"
+        }"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::Data(diff) => {
+                assert_eq!("diff_order_book_btcusd", diff.channel);
+                assert_eq!(vec![(36780.0, 0.0)], diff.data.bids);
+                assert_eq!(1643643586544025, diff.data.microtimestamp);
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_subscription_succeeded() {
+        let msg = r#"{"event": "bts:subscription_succeeded", "channel": "live_trades_btcusd", "data": {}}"#;
+
+        assert!(matches!(
+            serde_json::from_str::<WsMsg>(msg).unwrap(),
+            WsMsg::SubscriptionSucceeded
+        ));
+    }
+}
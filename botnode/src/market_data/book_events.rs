@@ -0,0 +1,212 @@
+//! Orderbook events emitted by the adapters
+//!
+//! By default every orderbook update is emitted as a clone of the full book.
+//! Strategies only interested in the top of book can have the adapters emit
+//! [`MarketEventType::BboUpdate`] instead, which is only sent when the best
+//! bid or offer actually changed.
+//!
+//! Engines maintaining their own books can have the adapters emit
+//! [`MarketEventType::OrderbookDelta`] with just the changed levels. The book
+//! is emitted in full after every snapshot, and on every update by adapters
+//! whose updates aren't plain level changes, e.g. Kraken which also drops
+//! levels past its subscribed depth.
+//!
+//! Emitted orderbooks and deltas are copied into boxes of the [`pool`].
+//!
+//! [`MarketEventType::BboUpdate`]: botvana::market::event::MarketEventType::BboUpdate
+//! [`MarketEventType::OrderbookDelta`]: botvana::market::event::MarketEventType::OrderbookDelta
+
+use std::{cell::RefCell, collections::HashMap};
+
+use serde::Deserialize;
+
+use botvana::market::{
+    event::MarketEvent,
+    orderbook::{Bbo, PlainOrderbook, PriceLevelsVec},
+};
+
+use crate::market_data::pool;
+
+/// Events emitted on orderbook updates
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BookEvents {
+    /// `OrderbookUpdate` with the whole book on every update
+    Full,
+    /// `BboUpdate` when the best bid or offer changed
+    Bbo,
+    /// `OrderbookDelta` with the changed levels
+    Delta,
+}
+
+impl Default for BookEvents {
+    fn default() -> Self {
+        BookEvents::Full
+    }
+}
+
+/// Turns updated orderbooks into market events
+#[derive(Debug, Default)]
+pub struct BookEmitter {
+    mode: BookEvents,
+    /// Last emitted best bid and offer by market
+    last_bbo: RefCell<HashMap<Box<str>, Bbo<f64>>>,
+    /// Sequence of the last emitted delta by market, 0 after a full book
+    sequences: RefCell<HashMap<Box<str>, u64>>,
+}
+
+impl BookEmitter {
+    pub fn new(mode: BookEvents) -> Self {
+        Self {
+            mode,
+            last_bbo: RefCell::new(HashMap::new()),
+            sequences: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the emitted events
+    pub fn mode(&self) -> BookEvents {
+        self.mode
+    }
+
+    /// Returns event for the replaced or updated orderbook of the market,
+    /// `None` when only emitting BBO changes and the top of book stayed the
+    /// same
+    ///
+    /// Deltas start over from this book.
+    pub fn event(&self, market: Box<str>, orderbook: &PlainOrderbook<f64>) -> Option<MarketEvent> {
+        match self.mode {
+            BookEvents::Full => Some(MarketEvent::orderbook_update(
+                market,
+                pool::with(|pool| pool.orderbook(orderbook)),
+            )),
+            BookEvents::Delta => {
+                self.sequences.borrow_mut().insert(market.clone(), 0);
+
+                Some(MarketEvent::orderbook_update(
+                    market,
+                    pool::with(|pool| pool.orderbook(orderbook)),
+                ))
+            }
+            BookEvents::Bbo => {
+                let bbo = orderbook.bbo()?;
+                let mut last_bbo = self.last_bbo.borrow_mut();
+                if last_bbo.get(&market) == Some(&bbo) {
+                    return None;
+                }
+                last_bbo.insert(market.clone(), bbo);
+
+                Some(MarketEvent::bbo_update(market, bbo))
+            }
+        }
+    }
+
+    /// Returns event for the orderbook of the market updated with given
+    /// levels
+    ///
+    /// The first update after [`BookEmitter::new`] is emitted in full, so
+    /// books seeded from REST snapshots reach the consumers too.
+    pub fn delta(
+        &self,
+        market: Box<str>,
+        orderbook: &PlainOrderbook<f64>,
+        bids: &PriceLevelsVec<f64>,
+        asks: &PriceLevelsVec<f64>,
+    ) -> Option<MarketEvent> {
+        if self.mode != BookEvents::Delta {
+            return self.event(market, orderbook);
+        }
+
+        let next = self
+            .sequences
+            .borrow_mut()
+            .get_mut(&market)
+            .map(|sequence| {
+                *sequence += 1;
+                *sequence
+            });
+        let sequence = match next {
+            Some(sequence) => sequence,
+            None => return self.event(market, orderbook),
+        };
+
+        Some(MarketEvent::orderbook_delta(
+            market,
+            pool::with(|pool| pool.delta(bids, asks, orderbook.time, sequence)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use botvana::market::{
+        event::MarketEventType,
+        orderbook::{PriceLevelsVec, UpdateOrderbook},
+    };
+
+    #[test]
+    fn test_book_emitter_bbo() {
+        let emitter = BookEmitter::new(BookEvents::Bbo);
+        let mut orderbook = PlainOrderbook::<f64>::new();
+        orderbook.update(
+            &PriceLevelsVec::from_tuples_vec(&[(9.0, 1.0), (9.5, 1.0)]),
+            &PriceLevelsVec::from_tuples_vec(&[(10.0, 1.0)]),
+        );
+
+        let event = emitter.event(Box::from("BTC/USD"), &orderbook).unwrap();
+        assert!(matches!(event.r#type, MarketEventType::BboUpdate(_, bbo) if bbo.bid == 9.5));
+
+        // Change below the top of book
+        orderbook.update(
+            &PriceLevelsVec::from_tuples_vec(&[(9.0, 2.0)]),
+            &PriceLevelsVec::new(),
+        );
+        assert!(emitter.event(Box::from("BTC/USD"), &orderbook).is_none());
+
+        orderbook.update(
+            &PriceLevelsVec::new(),
+            &PriceLevelsVec::from_tuples_vec(&[(10.0, 0.5)]),
+        );
+        assert!(emitter.event(Box::from("BTC/USD"), &orderbook).is_some());
+    }
+
+    #[test]
+    fn test_book_emitter_delta() {
+        let emitter = BookEmitter::new(BookEvents::Delta);
+        let mut orderbook = PlainOrderbook::<f64>::new();
+        let bids = PriceLevelsVec::from_tuples_vec(&[(9.0, 1.0)]);
+        let asks = PriceLevelsVec::from_tuples_vec(&[(10.0, 1.0)]);
+        orderbook.update(&bids, &asks);
+
+        // Nothing emitted in full yet
+        let event = emitter.delta(Box::from("BTC/USD"), &orderbook, &bids, &asks);
+        let mut replica = match event.unwrap().r#type {
+            MarketEventType::OrderbookUpdate(_, orderbook) => *orderbook,
+            other => panic!("expected full orderbook, got {other:?}"),
+        };
+
+        let bids = PriceLevelsVec::from_tuples_vec(&[(9.0, 0.0), (9.5, 2.0)]);
+        orderbook.update(&bids, &PriceLevelsVec::new());
+        for sequence in 1..=2 {
+            let event = emitter.delta(
+                Box::from("BTC/USD"),
+                &orderbook,
+                &bids,
+                &PriceLevelsVec::new(),
+            );
+            match event.unwrap().r#type {
+                MarketEventType::OrderbookDelta(_, delta) => {
+                    assert_eq!(sequence, delta.sequence);
+                    replica.apply_delta(&delta);
+                }
+                other => panic!("expected delta, got {other:?}"),
+            }
+        }
+
+        assert_eq!(orderbook.bids.price_vec, replica.bids.price_vec);
+        assert_eq!(orderbook.bids.size_vec, replica.bids.size_vec);
+        assert_eq!(orderbook.asks.price_vec, replica.asks.price_vec);
+    }
+}
@@ -0,0 +1,496 @@
+//! Bybit linear perpetuals market data adapter
+//!
+//! Markets come from the paged `/v5/market/instruments-info` of the `linear`
+//! category, only perpetuals are kept. The Websocket API streams the
+//! `orderbook.200` topic, a `snapshot` after subscribing followed by `delta`
+//! changes, trades from the `publicTrade` topic and liquidations from the
+//! `liquidation` topic.
+//!
+//! Bybit closes connections that aren't pinged every 20 seconds, the adapter
+//! asks for `{"op": "ping"}` keepalives.
+
+pub(crate) mod rest;
+pub(crate) mod ws;
+
+use chrono::TimeZone;
+
+use super::prelude::*;
+use crate::prelude::*;
+use botvana::{
+    exchange::ExchangeId,
+    market::derivatives::{Liquidation, PositionSide},
+};
+
+/// Subscribed book depth
+const BOOK_DEPTH: usize = 200;
+
+#[derive(Debug)]
+pub struct Bybit {
+    pub metrics: AdapterMetrics,
+    api_url: Box<str>,
+    ws_url: Box<str>,
+    book_events: BookEmitter,
+}
+
+impl Default for Bybit {
+    fn default() -> Self {
+        Bybit {
+            api_url: Box::from("https://api.bybit.com"),
+            ws_url: Box::from("wss://stream.bybit.com/v5/public/linear"),
+            metrics: AdapterMetrics::default(),
+            book_events: BookEmitter::default(),
+        }
+    }
+}
+
+impl Bybit {
+    fn client(&self) -> Result<surf::Client, MarketDataError> {
+        surf::Config::new()
+            .set_base_url(Url::parse(&self.api_url).map_err(MarketDataError::with_source)?)
+            .set_timeout(Some(Duration::from_secs(5)))
+            .try_into()
+            .map_err(MarketDataError::with_source)
+    }
+}
+
+#[async_trait(?Send)]
+impl RestMarketDataAdapter for Bybit {
+    fn name(&self) -> &'static str {
+        "bybit-rest"
+    }
+
+    fn exchange(&self) -> ExchangeId {
+        ExchangeId::Bybit
+    }
+
+    /// Fetches linear perpetuals traded on Bybit
+    async fn fetch_markets(&self) -> Result<Box<[Market]>, MarketDataError> {
+        let client = self.client()?;
+        let mut markets = Vec::new();
+        let mut cursor = String::new();
+
+        loop {
+            rate_limit::acquire(ExchangeId::Bybit, Endpoint::Markets).await;
+            let mut res = client
+                .get(format!(
+                    "/v5/market/instruments-info?category=linear&limit=1000&cursor={cursor}"
+                ))
+                .await
+                .map_err(MarketDataError::surf_error)?;
+            let body = res
+                .body_string()
+                .await
+                .map_err(MarketDataError::surf_error)?;
+
+            let res =
+                serde_json::from_slice::<rest::Response<rest::InstrumentsInfo>>(body.as_bytes())
+                    .map_err(MarketDataError::with_source)?;
+            let page = res
+                .result
+                .filter(|_| res.ret_code == 0)
+                .ok_or_else(|| MarketDataError::convert_error(res.ret_msg))?;
+
+            markets.extend(
+                page.list
+                    .iter()
+                    .filter(|instrument| instrument.status == "Trading")
+                    .filter_map(|instrument| Market::try_from(instrument).ok()),
+            );
+
+            if page.next_page_cursor.is_empty() {
+                break;
+            }
+            cursor = page.next_page_cursor;
+        }
+
+        debug!("{} linear perpetuals on Bybit", markets.len());
+
+        Ok(markets.into_boxed_slice())
+    }
+
+    async fn fetch_orderbook_snapshot(
+        &self,
+        symbol: &str,
+    ) -> Result<PlainOrderbook<f64>, MarketDataError> {
+        rate_limit::acquire(ExchangeId::Bybit, Endpoint::Orderbook).await;
+        let mut res = self
+            .client()?
+            .get(format!(
+                "/v5/market/orderbook?category=linear&symbol={}&limit={BOOK_DEPTH}",
+                native_symbol(symbol)
+            ))
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let res =
+            serde_json::from_slice::<rest::Response<rest::OrderbookSnapshot>>(body.as_bytes())
+                .map_err(MarketDataError::with_source)?;
+        let snapshot = res
+            .result
+            .filter(|_| res.ret_code == 0)
+            .ok_or_else(|| MarketDataError::convert_error(res.ret_msg))?;
+
+        Ok(PlainOrderbook {
+            bids: snapshot.bids,
+            asks: snapshot.asks,
+            time: snapshot.ts as f64,
+            max_depth: None,
+        })
+    }
+
+    async fn fetch_server_time(&self) -> Result<Option<DateTime<Utc>>, MarketDataError> {
+        rate_limit::acquire(ExchangeId::Bybit, Endpoint::Time).await;
+        let mut res = self
+            .client()?
+            .get("/v5/market/time")
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let res = serde_json::from_slice::<rest::Response<rest::ServerTime>>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?;
+        let time = res
+            .result
+            .filter(|_| res.ret_code == 0)
+            .ok_or_else(|| MarketDataError::convert_error(res.ret_msg))?;
+        let nanos = time
+            .time_nano
+            .parse::<i64>()
+            .map_err(MarketDataError::with_source)?;
+
+        Ok(Some(Utc.timestamp_nanos(nanos)))
+    }
+}
+
+impl WsMarketDataAdapter for Bybit {
+    fn metrics(&self) -> &AdapterMetrics {
+        &self.metrics
+    }
+
+    fn set_book_events(&mut self, book_events: BookEvents) {
+        self.book_events = BookEmitter::new(book_events);
+    }
+
+    fn ws_url(&self) -> Box<str> {
+        self.ws_url.clone()
+    }
+
+    fn subscribe_msgs(&mut self, markets: &[&str]) -> Box<[String]> {
+        info!("Subscribing for {markets:?}");
+
+        topic_msgs("subscribe", markets)
+    }
+
+    fn unsubscribe_msgs(&mut self, markets: &[&str]) -> Option<Box<[String]>> {
+        info!("Unsubscribing {markets:?}");
+
+        Some(topic_msgs("unsubscribe", markets))
+    }
+
+    fn resync_msgs(&self, market: &str) -> Option<Box<[String]>> {
+        let args = [format!("orderbook.{BOOK_DEPTH}.{}", native_symbol(market))];
+
+        Some(Box::new([
+            json!({"op": "unsubscribe", "args": args}).to_string(),
+            json!({"op": "subscribe", "args": args}).to_string(),
+        ]))
+    }
+
+    fn ping_msg(&self) -> Option<String> {
+        Some(json!({"op": "ping"}).to_string())
+    }
+
+    fn process_ws_msg(
+        &self,
+        msg: &str,
+        markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    ) -> Result<Option<MarketEvent>, MarketDataError> {
+        trace!("got ws_msg = {msg:?}");
+
+        match serde_json::from_slice::<ws::WsMsg>(msg.as_bytes()) {
+            Ok(ws_msg) => process_public_ws_message(ws_msg, markets, &self.book_events),
+            Err(e) => {
+                error!("Error parsing ws_msg: {msg}");
+
+                Err(MarketDataError::with_source(e))
+            }
+        }
+    }
+}
+
+/// Converts market name to Bybit symbol, `BTC/USDT` to `BTCUSDT`
+fn native_symbol(market: &str) -> String {
+    market.to_uppercase().replace('/', "")
+}
+
+/// Returns `op` requests of the orderbook, trade and liquidation topics of
+/// the markets
+fn topic_msgs(op: &str, markets: &[&str]) -> Box<[String]> {
+    let args: Vec<_> = markets
+        .iter()
+        .flat_map(|market| {
+            let symbol = native_symbol(market);
+
+            [
+                format!("orderbook.{BOOK_DEPTH}.{symbol}"),
+                format!("publicTrade.{symbol}"),
+                format!("liquidation.{symbol}"),
+            ]
+        })
+        .collect();
+
+    // Bybit limits the number of topics in one request
+    args.chunks(10)
+        .map(|args| json!({"op": op, "args": args}).to_string())
+        .collect()
+}
+
+/// Returns the market name Bybit symbol was subscribed as
+fn internal_symbol(
+    markets: &HashMap<Box<str>, PlainOrderbook<f64>>,
+    symbol: &str,
+) -> Option<Box<str>> {
+    markets.keys().find(|k| native_symbol(k) == symbol).cloned()
+}
+
+#[inline]
+fn process_public_ws_message(
+    ws_msg: ws::WsMsg,
+    markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    book_events: &BookEmitter,
+) -> Result<Option<MarketEvent>, MarketDataError> {
+    match ws_msg {
+        ws::WsMsg::Response(response) if !response.success => Err(MarketDataError::convert_error(
+            format!("Bybit {} failed: {}", response.op, response.ret_msg),
+        )),
+        ws::WsMsg::Response(_) => Ok(None),
+        ws::WsMsg::Orderbook(mut book_msg) => {
+            let data = &mut book_msg.data;
+            let symbol = match internal_symbol(markets, data.symbol) {
+                Some(symbol) => symbol,
+                None => {
+                    warn!("No symbol mapping found for {}", data.symbol);
+                    return Ok(None);
+                }
+            };
+            let bids = PriceLevelsVec::from_tuples_vec_unsorted(&mut data.bids);
+            let asks = PriceLevelsVec::from_tuples_vec_unsorted(&mut data.asks);
+
+            match book_msg.r#type {
+                // Also sent with update id 1 after Bybit restarted the feed
+                "snapshot" => {
+                    let orderbook = PlainOrderbook {
+                        bids,
+                        asks,
+                        time: book_msg.ts as f64,
+                        max_depth: None,
+                    };
+
+                    let event = book_events.event(symbol.clone(), &orderbook);
+                    markets.insert(symbol, orderbook);
+
+                    Ok(event)
+                }
+                "delta" => match markets.get_mut(&symbol) {
+                    Some(orderbook) => {
+                        orderbook.update_with_timestamp(&bids, &asks, book_msg.ts as f64);
+
+                        Ok(book_events.delta(symbol, orderbook, &bids, &asks))
+                    }
+                    None => Ok(None),
+                },
+                other => Err(MarketDataError::with_source(UnknownVariantError {
+                    variant: other.to_string(),
+                })),
+            }
+        }
+        ws::WsMsg::Trades(trades_msg) => {
+            let symbol = match trades_msg.data.first() {
+                Some(trade) => internal_symbol(markets, trade.symbol)
+                    .unwrap_or_else(|| Box::from(trade.symbol)),
+                None => return Ok(None),
+            };
+            let trades = trades_msg
+                .data
+                .iter()
+                .map(|trade| {
+                    Ok(botvana::market::trade::Trade::new(
+                        trade.price.parse::<f64>()?,
+                        trade.size.parse::<f64>()?,
+                        Utc.timestamp_millis(trade.time),
+                    ))
+                })
+                .collect::<Result<Box<[_]>, std::num::ParseFloatError>>()
+                .map_err(MarketDataError::with_source)?;
+
+            Ok(Some(MarketEvent::trades(symbol, trades)))
+        }
+        ws::WsMsg::Liquidation(liquidation_msg) => {
+            let data = &liquidation_msg.data;
+            let symbol =
+                internal_symbol(markets, data.symbol).unwrap_or_else(|| Box::from(data.symbol));
+            let position = match data.side {
+                "Buy" => PositionSide::Long,
+                "Sell" => PositionSide::Short,
+                other => {
+                    return Err(MarketDataError::with_source(UnknownVariantError {
+                        variant: other.to_string(),
+                    }))
+                }
+            };
+            let liquidation = Liquidation {
+                position,
+                price: data.price.parse().map_err(MarketDataError::with_source)?,
+                size: data.size.parse().map_err(MarketDataError::with_source)?,
+                time: Utc.timestamp_millis(data.updated_time),
+            };
+
+            Ok(Some(MarketEvent::liquidation(
+                symbol,
+                Box::new([liquidation]),
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_native_symbol() {
+        assert_eq!("BTCUSDT", native_symbol("btc/usdt"));
+        assert_eq!("ETHUSDT", native_symbol("ETHUSDT"));
+    }
+
+    #[test]
+    fn test_process_ws_msg_snapshot_and_delta() {
+        let bybit = Bybit::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTC/USDT"), PlainOrderbook::new());
+
+        let snapshot = r#"{
+            "topic": "orderbook.200.BTCUSDT",
+            "type": "snapshot",
+            "ts": 1672304484978,
+            "data": {
+                "s": "BTCUSDT",
+                "b": [["16493.50", "0.006"], ["16493.00", "0.100"]],
+                "a": [["16611.00", "0.029"], ["16612.00", "0.213"]],
+                "u": 18521288,
+                "seq": 7961638724
+            },
+            "cts": 1672304484976
+        }"#;
+        let event = bybit.process_ws_msg(snapshot, &mut markets).unwrap();
+        assert!(event.is_some());
+        assert_eq!(vec![16493.0, 16493.5], markets["BTC/USDT"].bids.price_vec);
+
+        let delta = r#"{
+            "topic": "orderbook.200.BTCUSDT",
+            "type": "delta",
+            "ts": 1672304484979,
+            "data": {
+                "s": "BTCUSDT",
+                "b": [["16493.50", "0"]],
+                "a": [["16611.50", "1.5"]],
+                "u": 18521289,
+                "seq": 7961638725
+            },
+            "cts": 1672304484977
+        }"#;
+        match bybit
+            .process_ws_msg(delta, &mut markets)
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::OrderbookUpdate(market, orderbook)) => {
+                assert_eq!("BTC/USDT", &*market);
+                assert_eq!(vec![16493.0], orderbook.bids.price_vec);
+                assert_eq!(vec![16611.0, 16611.5, 16612.0], orderbook.asks.price_vec);
+                assert_eq!(1672304484979.0, orderbook.time);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_process_ws_msg_trades() {
+        let bybit = Bybit::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTCUSDT"), PlainOrderbook::new());
+
+        let msg = r#"{
+            "topic": "publicTrade.BTCUSDT",
+            "type": "snapshot",
+            "ts": 1672304486868,
+            "data": [
+                {"T": 1672304486865, "s": "BTCUSDT", "S": "Buy", "v": "0.001", "p": "16578.50", "i": "1", "BT": false},
+                {"T": 1672304486866, "s": "BTCUSDT", "S": "Sell", "v": "0.25", "p": "16578.00", "i": "2", "BT": false}
+            ]
+        }"#;
+
+        match bybit
+            .process_ws_msg(msg, &mut markets)
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::Trades(market, trades)) => {
+                assert_eq!("BTCUSDT", &*market);
+                assert_eq!(2, trades.len());
+                assert_eq!(16578.5, trades[0].price);
+                assert_eq!(0.25, trades[1].size);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_process_ws_msg_liquidation() {
+        let bybit = Bybit::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTCUSDT"), PlainOrderbook::new());
+
+        let msg = r#"{
+            "topic": "liquidation.BTCUSDT",
+            "type": "snapshot",
+            "ts": 1673251091822,
+            "data": {
+                "price": "16501.50",
+                "side": "Sell",
+                "size": "0.2",
+                "symbol": "BTCUSDT",
+                "updatedTime": 1673251091822
+            }
+        }"#;
+
+        match bybit
+            .process_ws_msg(msg, &mut markets)
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::Liquidation(market, liquidations)) => {
+                assert_eq!("BTCUSDT", &*market);
+                assert_eq!(PositionSide::Short, liquidations[0].position);
+                assert_eq!(16501.5, liquidations[0].price);
+                assert_eq!(0.2, liquidations[0].size);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_process_ws_msg_failed_subscription() {
+        let bybit = Bybit::default();
+        let msg = r#"{"success": false, "ret_msg": "error:handler not found", "conn_id": "1", "op": "subscribe"}"#;
+
+        assert!(bybit.process_ws_msg(msg, &mut HashMap::new()).is_err());
+    }
+}
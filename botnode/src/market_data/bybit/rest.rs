@@ -0,0 +1,166 @@
+use serde::{Deserialize, Deserializer};
+
+use botvana::{exchange::ExchangeId, market::orderbook::*};
+
+/// Envelope of every Bybit v5 REST response
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Response<T> {
+    pub ret_code: i64,
+    pub ret_msg: String,
+    pub result: Option<T>,
+}
+
+/// Page of instruments returned by `/v5/market/instruments-info`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstrumentsInfo<'a> {
+    #[serde(borrow)]
+    pub list: Vec<Instrument<'a>>,
+    /// Cursor of the next page, empty on the last one
+    #[serde(default)]
+    pub next_page_cursor: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Instrument<'a> {
+    pub symbol: &'a str,
+    pub contract_type: &'a str,
+    pub status: &'a str,
+    pub base_coin: &'a str,
+    pub quote_coin: &'a str,
+    #[serde(borrow)]
+    pub price_filter: PriceFilter<'a>,
+    #[serde(borrow)]
+    pub lot_size_filter: LotSizeFilter<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceFilter<'a> {
+    pub tick_size: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LotSizeFilter<'a> {
+    pub qty_step: &'a str,
+}
+
+impl<'a> TryFrom<&Instrument<'a>> for botvana::market::Market {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(instrument: &Instrument<'a>) -> Result<Self, Self::Error> {
+        if instrument.contract_type != "LinearPerpetual" {
+            return Err(format!("Unsupported contract type: {}", instrument.contract_type).into());
+        }
+
+        Ok(Self {
+            exchange: ExchangeId::Bybit,
+            name: instrument.symbol.to_string(),
+            native_symbol: instrument.symbol.to_string(),
+            size_increment: instrument.lot_size_filter.qty_step.parse()?,
+            price_increment: instrument.price_filter.tick_size.parse()?,
+            r#type: botvana::market::MarketType::Futures(
+                botvana::market::FuturesMarket::perpetual(),
+            ),
+        })
+    }
+}
+
+/// Orderbook returned by `/v5/market/orderbook`
+#[derive(Debug, Deserialize)]
+pub struct OrderbookSnapshot {
+    #[serde(rename = "b", deserialize_with = "deserialize_into_price_levels_vec")]
+    pub bids: PriceLevelsVec<f64>,
+    #[serde(rename = "a", deserialize_with = "deserialize_into_price_levels_vec")]
+    pub asks: PriceLevelsVec<f64>,
+    /// Milliseconds timestamp
+    pub ts: i64,
+}
+
+fn deserialize_into_price_levels_vec<'de, D>(
+    deserializer: D,
+) -> Result<PriceLevelsVec<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut levels = super::ws::de_price_levels_from_str(deserializer)?;
+
+    Ok(PriceLevelsVec::from_tuples_vec_unsorted(&mut levels))
+}
+
+/// Result of `/v5/market/time`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerTime {
+    /// Nanoseconds timestamp
+    pub time_nano: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_instruments_info() {
+        let sample = r#"{
+            "retCode": 0,
+            "retMsg": "OK",
+            "result": {
+                "category": "linear",
+                "list": [{
+                    "symbol": "BTCUSDT",
+                    "contractType": "LinearPerpetual",
+                    "status": "Trading",
+                    "baseCoin": "BTC",
+                    "quoteCoin": "USDT",
+                    "launchTime": "1585526400000",
+                    "priceScale": "2",
+                    "priceFilter": {"minPrice": "0.10", "maxPrice": "199999.80", "tickSize": "0.10"},
+                    "lotSizeFilter": {"maxOrderQty": "100.000", "minOrderQty": "0.001", "qtyStep": "0.001"}
+                }, {
+                    "symbol": "BTC-29DEC23",
+                    "contractType": "LinearFutures",
+                    "status": "Trading",
+                    "baseCoin": "BTC",
+                    "quoteCoin": "USDC",
+                    "priceFilter": {"tickSize": "0.50"},
+                    "lotSizeFilter": {"qtyStep": "0.001"}
+                }],
+                "nextPageCursor": ""
+            }
+        }"#;
+
+        let res = serde_json::from_str::<Response<InstrumentsInfo>>(sample).unwrap();
+        let list = res.result.unwrap().list;
+        let market = botvana::market::Market::try_from(&list[0]).unwrap();
+
+        assert_eq!("BTCUSDT", market.name);
+        assert_eq!(0.1, market.price_increment);
+        assert_eq!(0.001, market.size_increment);
+        assert!(botvana::market::Market::try_from(&list[1]).is_err());
+    }
+
+    #[test]
+    fn test_parse_orderbook_snapshot() {
+        let sample = r#"{
+            "retCode": 0,
+            "retMsg": "OK",
+            "result": {
+                "s": "BTCUSDT",
+                "b": [["16493.50", "0.006"], ["16493.00", "0.100"]],
+                "a": [["16611.00", "0.029"]],
+                "ts": 1672304484978,
+                "u": 18521288
+            }
+        }"#;
+
+        let res = serde_json::from_str::<Response<OrderbookSnapshot>>(sample).unwrap();
+        let snapshot = res.result.unwrap();
+
+        assert_eq!(vec![16493.0, 16493.5], snapshot.bids.price_vec);
+        assert_eq!(vec![16611.0], snapshot.asks.price_vec);
+    }
+}
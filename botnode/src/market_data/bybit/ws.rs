@@ -0,0 +1,145 @@
+use serde::{Deserialize, Deserializer};
+
+/// Bybit public Websocket message
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum WsMsg<'a> {
+    #[serde(borrow)]
+    Orderbook(OrderbookMsg<'a>),
+    #[serde(borrow)]
+    Trades(TradesMsg<'a>),
+    #[serde(borrow)]
+    Liquidation(LiquidationMsg<'a>),
+    #[serde(borrow)]
+    Response(OpResponse<'a>),
+}
+
+/// Message of the `orderbook.200.{symbol}` topic
+#[derive(Debug, Deserialize)]
+pub struct OrderbookMsg<'a> {
+    /// `snapshot` replacing the book or `delta` with changed levels
+    pub r#type: &'a str,
+    /// Milliseconds timestamp
+    pub ts: i64,
+    #[serde(borrow)]
+    pub data: OrderbookData<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrderbookData<'a> {
+    #[serde(rename = "s")]
+    pub symbol: &'a str,
+    /// Size of zero removes the level
+    #[serde(rename = "b", deserialize_with = "de_price_levels_from_str")]
+    pub bids: Vec<(f64, f64)>,
+    #[serde(rename = "a", deserialize_with = "de_price_levels_from_str")]
+    pub asks: Vec<(f64, f64)>,
+}
+
+/// Message of the `publicTrade.{symbol}` topic
+#[derive(Debug, Deserialize)]
+pub struct TradesMsg<'a> {
+    #[serde(borrow)]
+    pub data: Vec<Trade<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Trade<'a> {
+    /// Milliseconds timestamp
+    #[serde(rename = "T")]
+    pub time: i64,
+    #[serde(rename = "s")]
+    pub symbol: &'a str,
+    #[serde(rename = "p")]
+    pub price: &'a str,
+    #[serde(rename = "v")]
+    pub size: &'a str,
+}
+
+/// Message of the `liquidation.{symbol}` topic
+#[derive(Debug, Deserialize)]
+pub struct LiquidationMsg<'a> {
+    #[serde(borrow)]
+    pub data: LiquidationData<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LiquidationData<'a> {
+    pub symbol: &'a str,
+    /// Side of the liquidated position, `Buy` for longs
+    pub side: &'a str,
+    pub price: &'a str,
+    pub size: &'a str,
+    /// Milliseconds timestamp
+    pub updated_time: i64,
+}
+
+/// Reply to `subscribe`, `unsubscribe` and `ping` requests
+#[derive(Debug, Deserialize)]
+pub struct OpResponse<'a> {
+    pub success: bool,
+    #[serde(default)]
+    pub ret_msg: &'a str,
+    pub op: &'a str,
+}
+
+pub(super) fn de_price_levels_from_str<'de, D>(deserializer: D) -> Result<Vec<(f64, f64)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let levels: Vec<(&'de str, &'de str)> = Deserialize::deserialize(deserializer)?;
+
+    levels
+        .iter()
+        .map(|(price, size)| Ok((price.parse::<f64>()?, size.parse::<f64>()?)))
+        .collect::<Result<_, std::num::ParseFloatError>>()
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_trades() {
+        let msg = r#"{
+            "topic": "publicTrade.BTCUSDT",
+            "type": "snapshot",
+            "ts": 1672304486868,
+            "data": [{
+                "T": 1672304486865,
+                "s": "BTCUSDT",
+                "S": "Buy",
+                "v": "0.001",
+                "p": "16578.50",
+                "L": "PlusTick",
+                "i": "20f43950-d8dd-5b31-9112-a178eb6023af",
+                "BT": false
+            }]
+        }"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::Trades(trades) => {
+                assert_eq!(1, trades.data.len());
+                assert_eq!("16578.50", trades.data[0].price);
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_pong() {
+        let msg = r#"{
+            "success": true,
+            "ret_msg": "pong",
+            "conn_id": "0970e817-426e-429a-a679-ff7f55e0b16a",
+            "op": "ping"
+        }"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::Response(response) => assert_eq!("ping", response.op),
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+}
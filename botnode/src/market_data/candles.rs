@@ -0,0 +1,230 @@
+//! Candle aggregation
+//!
+//! Candles are built from the trades of every market at each configured
+//! interval. Intervals are aligned to the Unix epoch and bucketed by the
+//! exchange time of the trades. A candle is closed once a trade of a later
+//! interval arrives or [`CLOSE_GRACE`] after its interval ended, trades older
+//! than the open candle are dropped.
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use botvana::market::{
+    candle::Candle,
+    event::{MarketEvent, MarketEventType},
+    trade::Trade,
+};
+
+use crate::prelude::HashMap;
+
+/// Time candles are kept open past their interval for trades delayed by
+/// latency and clock skew
+pub const CLOSE_GRACE: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Candle of one interval of a market
+#[derive(Debug, Default)]
+struct Slot {
+    candle: Option<Candle>,
+    /// Trades before the end of the last closed candle are dropped
+    closed_until: Option<DateTime<Utc>>,
+}
+
+/// Builds candles from trade events
+#[derive(Debug)]
+pub struct CandleAggregator {
+    intervals: Box<[Duration]>,
+    /// Slots by market, one for each interval
+    slots: HashMap<Box<str>, Box<[Slot]>>,
+}
+
+impl CandleAggregator {
+    /// Creates aggregator building candles of the given intervals
+    pub fn new(intervals: &[std::time::Duration]) -> Self {
+        Self {
+            intervals: intervals
+                .iter()
+                .filter_map(|interval| Duration::from_std(*interval).ok())
+                .filter(|interval| interval.num_milliseconds() > 0)
+                .collect(),
+            slots: HashMap::new(),
+        }
+    }
+
+    /// Returns whether any candles are built
+    pub fn is_enabled(&self) -> bool {
+        !self.intervals.is_empty()
+    }
+
+    /// Adds the trades of the event to the candles of its market and pushes
+    /// events of the candles they closed
+    pub fn observe(&mut self, event: &MarketEvent, closed: &mut Vec<MarketEvent>) {
+        let (market, trades) = match &event.r#type {
+            MarketEventType::Trades(market, trades) if self.is_enabled() => (market, trades),
+            _ => return,
+        };
+        if !self.slots.contains_key(&**market) {
+            let slots = self.intervals.iter().map(|_| Slot::default()).collect();
+            self.slots.insert(market.clone(), slots);
+        }
+        let slots = match self.slots.get_mut(&**market) {
+            Some(slots) => slots,
+            None => return,
+        };
+
+        for (slot, interval) in slots.iter_mut().zip(self.intervals.iter()) {
+            for trade in trades.iter() {
+                if let Some(candle) = slot.add(trade, *interval) {
+                    closed.push(MarketEvent::candle(market.clone(), candle));
+                }
+            }
+        }
+    }
+
+    /// Closes candles whose interval ended more than [`CLOSE_GRACE`] before
+    /// `now` and pushes their events
+    pub fn close_expired(&mut self, now: DateTime<Utc>, closed: &mut Vec<MarketEvent>) {
+        let grace = Duration::from_std(CLOSE_GRACE).unwrap_or_else(|_| Duration::zero());
+
+        for (market, slots) in self.slots.iter_mut() {
+            for slot in slots.iter_mut() {
+                let expired = match &slot.candle {
+                    Some(candle) => candle.close_time() + grace <= now,
+                    None => false,
+                };
+                if expired {
+                    if let Some(candle) = slot.close() {
+                        closed.push(MarketEvent::candle(market.clone(), candle));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Slot {
+    /// Adds the trade, returns the candle closed by it
+    fn add(&mut self, trade: &Trade, interval: Duration) -> Option<Candle> {
+        if matches!(self.closed_until, Some(closed_until) if trade.time < closed_until) {
+            return None;
+        }
+        let open_time = bucket_start(trade.time, interval);
+
+        match &mut self.candle {
+            Some(candle) if candle.open_time == open_time => {
+                candle.update(trade);
+                None
+            }
+            Some(candle) if candle.open_time > open_time => None,
+            _ => {
+                let closed = self.close();
+                self.candle = Some(Candle::new(open_time, interval, trade));
+                closed
+            }
+        }
+    }
+
+    fn close(&mut self) -> Option<Candle> {
+        let candle = self.candle.take()?;
+        self.closed_until = Some(candle.close_time());
+
+        Some(candle)
+    }
+}
+
+/// Returns start of the epoch-aligned interval containing `time`
+fn bucket_start(time: DateTime<Utc>, interval: Duration) -> DateTime<Utc> {
+    let time_ms = time.timestamp_millis();
+
+    Utc.timestamp_millis(time_ms - time_ms.rem_euclid(interval.num_milliseconds()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trades(market: &str, trades: &[(i64, f64, f64)]) -> MarketEvent {
+        let trades = trades
+            .iter()
+            .map(|(time_ms, price, size)| Trade::new(*price, *size, Utc.timestamp_millis(*time_ms)))
+            .collect();
+
+        MarketEvent::trades(Box::from(market), trades)
+    }
+
+    #[test]
+    fn test_candles_closed_by_later_trades() {
+        let mut aggregator = CandleAggregator::new(&[std::time::Duration::from_secs(60)]);
+        let mut closed = Vec::new();
+
+        aggregator.observe(
+            &trades(
+                "BTC/USD",
+                &[
+                    (60_000, 10.0, 1.0),
+                    (61_000, 12.0, 1.0),
+                    (119_999, 11.0, 2.0),
+                ],
+            ),
+            &mut closed,
+        );
+        assert!(closed.is_empty());
+
+        // Late trade of the closed interval is dropped
+        aggregator.observe(
+            &trades("BTC/USD", &[(120_500, 13.0, 1.0), (90_000, 1.0, 1.0)]),
+            &mut closed,
+        );
+        aggregator.observe(&trades("BTC/USD", &[(100_000, 1.0, 1.0)]), &mut closed);
+
+        assert_eq!(1, closed.len());
+        match &closed[0].r#type {
+            MarketEventType::Candle(market, candle) => {
+                assert_eq!("BTC/USD", &**market);
+                assert_eq!(Utc.timestamp(60, 0), candle.open_time);
+                assert_eq!(
+                    (10.0, 12.0, 10.0, 11.0),
+                    (candle.open, candle.high, candle.low, candle.close)
+                );
+                assert_eq!(4.0, candle.volume);
+                assert_eq!(3, candle.trades);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_candles_closed_after_interval() {
+        let mut aggregator = CandleAggregator::new(&[
+            std::time::Duration::from_secs(1),
+            std::time::Duration::from_secs(60),
+        ]);
+        let mut closed = Vec::new();
+
+        aggregator.observe(&trades("BTC/USD", &[(1_500, 10.0, 1.0)]), &mut closed);
+        aggregator.close_expired(Utc.timestamp_millis(2_500), &mut closed);
+        assert!(closed.is_empty());
+
+        aggregator.close_expired(Utc.timestamp_millis(3_000), &mut closed);
+        assert_eq!(1, closed.len());
+        match &closed[0].r#type {
+            MarketEventType::Candle(_, candle) => {
+                assert_eq!(Duration::seconds(1), candle.interval);
+                assert_eq!(Utc.timestamp(1, 0), candle.open_time);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_bucket_start() {
+        let time = Utc.timestamp_nanos(301_234_567_891);
+
+        assert_eq!(
+            Utc.timestamp(300, 0),
+            bucket_start(time, Duration::minutes(5))
+        );
+        assert_eq!(
+            Utc.timestamp(301, 0),
+            bucket_start(time, Duration::seconds(1))
+        );
+    }
+}
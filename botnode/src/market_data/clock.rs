@@ -0,0 +1,131 @@
+//! Exchange clock-skew monitoring
+//!
+//! The skew is estimated from exchange timestamps carried by websocket
+//! messages. Each sample is local receive time minus exchange time, so it
+//! includes network latency; the smallest sample over a window is the best
+//! estimate of the clock offset.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use botvana::{
+    market::event::{MarketEvent, MarketEventType},
+    net::msg::AlertKind,
+};
+
+/// Default skew above which an alert is raised
+///
+/// Well within the request signing windows of the supported exchanges.
+pub const DEFAULT_MAX_CLOCK_SKEW: Duration = Duration::from_secs(1);
+
+/// Clock skew between an exchange and local clock
+#[derive(Debug)]
+pub struct ClockSkewMonitor {
+    max_skew: Duration,
+    window_min_ms: Option<i64>,
+    last_skew_ms: Option<i64>,
+    alerted: bool,
+}
+
+impl ClockSkewMonitor {
+    pub fn new(max_skew: Duration) -> Self {
+        Self {
+            max_skew,
+            window_min_ms: None,
+            last_skew_ms: None,
+            alerted: false,
+        }
+    }
+
+    /// Observes exchange timestamps carried by given market event
+    pub fn observe_event(&mut self, event: &MarketEvent) {
+        if let MarketEventType::Trades(_, trades) = &event.r#type {
+            if let Some(trade) = trades.iter().max_by_key(|trade| trade.time) {
+                self.observe(trade.time, Utc::now());
+            }
+        }
+    }
+
+    /// Observes single exchange timestamp received at given local time
+    pub fn observe(&mut self, exchange_time: DateTime<Utc>, local_time: DateTime<Utc>) {
+        let skew_ms = (local_time - exchange_time).num_milliseconds();
+
+        self.window_min_ms = Some(match self.window_min_ms {
+            Some(min) if min.abs() <= skew_ms.abs() => min,
+            _ => skew_ms,
+        });
+    }
+
+    /// Returns the skew estimated over the last finished window in milliseconds
+    pub fn skew_ms(&self) -> Option<i64> {
+        self.last_skew_ms
+    }
+
+    /// Finishes current window, publishes the estimated skew and raises alert
+    /// when it exceeds the maximum skew
+    pub fn publish(&mut self, exchange: &str) {
+        let skew_ms = match self.window_min_ms.take() {
+            Some(skew_ms) => skew_ms,
+            None => return,
+        };
+        self.last_skew_ms = Some(skew_ms);
+
+        crate::metrics::registry().set_gauge(
+            "market_data_clock_skew_milliseconds",
+            &crate::metrics::MetricLabels::new().exchange(exchange),
+            skew_ms as f64,
+        );
+
+        let exceeded = skew_ms.unsigned_abs() as u128 > self.max_skew.as_millis();
+        if exceeded && !self.alerted {
+            crate::alert::raise(
+                AlertKind::ClockSkew {
+                    exchange: exchange.to_string(),
+                    skew_ms,
+                },
+                format!(
+                    "{exchange} clock skew {skew_ms}ms exceeds {:?}",
+                    self.max_skew
+                ),
+            );
+        }
+        self.alerted = exceeded;
+    }
+}
+
+impl Default for ClockSkewMonitor {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CLOCK_SKEW)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_skew_window_min() {
+        let mut monitor = ClockSkewMonitor::default();
+        let now = Utc::now();
+
+        monitor.observe(now - chrono::Duration::milliseconds(30), now);
+        monitor.observe(now - chrono::Duration::milliseconds(10), now);
+        monitor.observe(now - chrono::Duration::milliseconds(20), now);
+        assert_eq!(None, monitor.skew_ms());
+
+        monitor.publish("test");
+        assert_eq!(Some(10), monitor.skew_ms());
+    }
+
+    #[test]
+    fn test_clock_skew_exchange_ahead() {
+        let mut monitor = ClockSkewMonitor::default();
+        let now = Utc::now();
+
+        monitor.observe(now + chrono::Duration::milliseconds(1500), now);
+        monitor.publish("test");
+
+        assert_eq!(Some(-1500), monitor.skew_ms());
+    }
+}
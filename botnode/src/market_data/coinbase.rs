@@ -0,0 +1,692 @@
+//! Coinbase Exchange market data adapter
+//!
+//! Markets come from `/products`. The Websocket feed streams the `level2`
+//! channel, a full `snapshot` of every orderbook after subscribing followed
+//! by `l2update` changes, and trades from the `matches` channel.
+//!
+//! With `l3_books` the `full` channel is streamed instead: every order
+//! opened, changed, matched and done. The order-by-order books are seeded
+//! from `/products/{id}/book?level=3` snapshots and kept in the adapter, the
+//! applied changes are emitted as L3 events followed by the changes of the
+//! aggregated books. Messages are numbered per product, messages included
+//! in the snapshot are dropped and a missed message resubscribes the market.
+
+pub(crate) mod rest;
+pub(crate) mod ws;
+
+use std::collections::VecDeque;
+
+use super::prelude::*;
+use crate::prelude::*;
+use botvana::{
+    exchange::ExchangeId,
+    market::orderbook::l3::{BookSide, L3Order, L3Orderbook, L3Update},
+};
+
+#[derive(Debug)]
+pub struct Coinbase {
+    pub metrics: AdapterMetrics,
+    api_url: Box<str>,
+    ws_url: Box<str>,
+    book_events: BookEmitter,
+    /// Whether the `full` channel is streamed instead of `level2`
+    l3_books: bool,
+    /// Order-by-order books of the `full` channel by market
+    l3: RefCell<HashMap<Box<str>, L3Orderbook>>,
+    sequences: SequenceTracker,
+    /// Events of the last message besides the returned one
+    pending: RefCell<VecDeque<MarketEvent>>,
+}
+
+impl Default for Coinbase {
+    fn default() -> Self {
+        Coinbase {
+            api_url: Box::from("https://api.exchange.coinbase.com"),
+            ws_url: Box::from("wss://ws-feed.exchange.coinbase.com"),
+            metrics: AdapterMetrics::default(),
+            book_events: BookEmitter::default(),
+            l3_books: false,
+            l3: RefCell::new(HashMap::new()),
+            sequences: SequenceTracker::new(),
+            pending: RefCell::new(VecDeque::new()),
+        }
+    }
+}
+
+impl Coinbase {
+    fn client(&self) -> Result<surf::Client, MarketDataError> {
+        surf::Config::new()
+            .set_base_url(Url::parse(&self.api_url).map_err(MarketDataError::with_source)?)
+            .set_timeout(Some(Duration::from_secs(5)))
+            // Coinbase rejects requests without user agent
+            .add_header("User-Agent", "botnode")
+            .map_err(MarketDataError::surf_error)?
+            .try_into()
+            .map_err(MarketDataError::with_source)
+    }
+
+    /// Returns the subscribed channels
+    fn channels(&self) -> &'static [&'static str] {
+        match self.l3_books {
+            true => &["full"],
+            false => &["level2", "matches"],
+        }
+    }
+
+    /// Fetches level 3 orderbook and seeds the order-by-order book of the
+    /// market with it
+    async fn fetch_l3_snapshot(
+        &self,
+        market: &str,
+    ) -> Result<PlainOrderbook<f64>, MarketDataError> {
+        rate_limit::acquire(ExchangeId::Coinbase, Endpoint::Orderbook).await;
+        let mut res = self
+            .client()?
+            .get(format!("/products/{}/book?level=3", native_symbol(market)))
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let snapshot = serde_json::from_slice::<rest::L3Snapshot>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?;
+        let orders = snapshot.orders().map_err(MarketDataError::convert_error)?;
+
+        Ok(self.seed_l3(market, snapshot.sequence, orders))
+    }
+
+    /// Puts the order-by-order book of the market in place, `full` channel
+    /// messages following the sequence are applied to it
+    fn seed_l3(&self, market: &str, sequence: u64, orders: Vec<L3Order>) -> PlainOrderbook<f64> {
+        let book = L3Orderbook::from_orders(&orders);
+        let orderbook = book.to_plain(Utc::now().timestamp_millis() as f64);
+
+        self.l3.borrow_mut().insert(Box::from(market), book);
+        self.sequences.reset(market, sequence);
+        self.pending
+            .borrow_mut()
+            .push_back(MarketEvent::l3_snapshot(
+                Box::from(market),
+                orders.into_boxed_slice(),
+            ));
+
+        orderbook
+    }
+}
+
+#[async_trait(?Send)]
+impl RestMarketDataAdapter for Coinbase {
+    fn name(&self) -> &'static str {
+        "coinbase-rest"
+    }
+
+    fn exchange(&self) -> ExchangeId {
+        ExchangeId::Coinbase
+    }
+
+    /// Fetches available products on Coinbase
+    async fn fetch_markets(&self) -> Result<Box<[Market]>, MarketDataError> {
+        rate_limit::acquire(ExchangeId::Coinbase, Endpoint::Markets).await;
+        let mut res = self
+            .client()?
+            .get("/products")
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let products = serde_json::from_slice::<Box<[rest::Product]>>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?;
+
+        debug!("{} products on Coinbase", products.len());
+
+        Ok(products
+            .iter()
+            .filter(|product| !product.trading_disabled)
+            .filter_map(|product| Market::try_from(product).ok())
+            .collect())
+    }
+
+    /// Fetches aggregated level 2 orderbook, or the level 3 one with
+    /// `l3_books`
+    async fn fetch_orderbook_snapshot(
+        &self,
+        symbol: &str,
+    ) -> Result<PlainOrderbook<f64>, MarketDataError> {
+        if self.l3_books {
+            return self.fetch_l3_snapshot(symbol).await;
+        }
+        rate_limit::acquire(ExchangeId::Coinbase, Endpoint::Orderbook).await;
+        let mut res = self
+            .client()?
+            .get(format!("/products/{}/book?level=2", native_symbol(symbol)))
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let snapshot = serde_json::from_slice::<rest::OrderbookSnapshot>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?;
+
+        Ok(PlainOrderbook {
+            bids: snapshot.bids,
+            asks: snapshot.asks,
+            time: 0.0,
+            max_depth: None,
+        })
+    }
+
+    async fn fetch_server_time(&self) -> Result<Option<DateTime<Utc>>, MarketDataError> {
+        rate_limit::acquire(ExchangeId::Coinbase, Endpoint::Time).await;
+        let mut res = self
+            .client()?
+            .get("/time")
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let time = serde_json::from_slice::<rest::ServerTime>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?;
+
+        Ok(Some(time.iso))
+    }
+}
+
+impl WsMarketDataAdapter for Coinbase {
+    fn metrics(&self) -> &AdapterMetrics {
+        &self.metrics
+    }
+
+    fn set_book_events(&mut self, book_events: BookEvents) {
+        self.book_events = BookEmitter::new(book_events);
+    }
+
+    fn set_l3_books(&mut self, enabled: bool) {
+        self.l3_books = enabled;
+    }
+
+    fn ws_url(&self) -> Box<str> {
+        self.ws_url.clone()
+    }
+
+    /// The `full` channel sends no snapshots
+    fn requires_snapshot(&self) -> bool {
+        self.l3_books
+    }
+
+    fn sequences(&self) -> Option<&SequenceTracker> {
+        self.l3_books.then(|| &self.sequences)
+    }
+
+    fn take_pending_event(&self) -> Option<MarketEvent> {
+        self.pending.borrow_mut().pop_front()
+    }
+
+    fn subscribe_msgs(&mut self, markets: &[&str]) -> Box<[String]> {
+        info!("Subscribing for {markets:?}");
+
+        Box::new([channels_msg("subscribe", markets, self.channels())])
+    }
+
+    fn unsubscribe_msgs(&mut self, markets: &[&str]) -> Option<Box<[String]>> {
+        info!("Unsubscribing {markets:?}");
+
+        Some(Box::new([channels_msg(
+            "unsubscribe",
+            markets,
+            self.channels(),
+        )]))
+    }
+
+    /// Resubscribes the `full` channel of the market
+    fn resync_msgs(&self, market: &str) -> Option<Box<[String]>> {
+        if !self.l3_books {
+            return None;
+        }
+
+        Some(Box::new([
+            channels_msg("unsubscribe", &[market], self.channels()),
+            channels_msg("subscribe", &[market], self.channels()),
+        ]))
+    }
+
+    fn process_ws_msg(
+        &self,
+        msg: &str,
+        markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    ) -> Result<Option<MarketEvent>, MarketDataError> {
+        trace!("got ws_msg = {msg:?}");
+
+        match serde_json::from_slice::<ws::WsMsg>(msg.as_bytes()) {
+            Ok(ws_msg) if self.l3_books => process_full_ws_message(
+                ws_msg,
+                markets,
+                &mut self.l3.borrow_mut(),
+                &self.sequences,
+                &self.book_events,
+                &mut self.pending.borrow_mut(),
+            ),
+            Ok(ws_msg) => process_feed_ws_message(ws_msg, markets, &self.book_events),
+            Err(e) => {
+                error!("Error parsing ws_msg: {msg}");
+
+                Err(MarketDataError::with_source(e))
+            }
+        }
+    }
+}
+
+/// Converts market name to Coinbase product id, `BTC/USD` to `BTC-USD`
+fn native_symbol(market: &str) -> String {
+    market.to_uppercase().replace('/', "-")
+}
+
+/// Returns message of given type for the channels of the markets
+fn channels_msg(r#type: &str, markets: &[&str], channels: &[&str]) -> String {
+    let product_ids: Vec<_> = markets.iter().map(|m| native_symbol(m)).collect();
+
+    json!({
+        "type": r#type,
+        "product_ids": product_ids,
+        "channels": channels,
+    })
+    .to_string()
+}
+
+/// Parses RFC 3339 time of a message to milliseconds
+fn parse_time(time: &str) -> Result<f64, MarketDataError> {
+    Ok(chrono::DateTime::parse_from_rfc3339(time)
+        .map_err(MarketDataError::with_source)?
+        .timestamp_millis() as f64)
+}
+
+/// Returns the market name Coinbase product was subscribed as
+fn internal_symbol(
+    markets: &HashMap<Box<str>, PlainOrderbook<f64>>,
+    product_id: &str,
+) -> Option<Box<str>> {
+    markets
+        .keys()
+        .find(|k| native_symbol(k) == product_id)
+        .cloned()
+}
+
+#[inline]
+fn process_feed_ws_message(
+    ws_msg: ws::WsMsg,
+    markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    book_events: &BookEmitter,
+) -> Result<Option<MarketEvent>, MarketDataError> {
+    match ws_msg {
+        ws::WsMsg::Subscriptions { channels } => {
+            for channel in channels {
+                info!("Subscribed {}: {:?}", channel.name, channel.product_ids);
+            }
+
+            Ok(None)
+        }
+        ws::WsMsg::Error { message, reason } => Err(MarketDataError::convert_error(format!(
+            "Coinbase error: {message} {reason}"
+        ))),
+        ws::WsMsg::Snapshot(mut snapshot) => {
+            let symbol = match internal_symbol(markets, snapshot.product_id) {
+                Some(symbol) => symbol,
+                None => {
+                    warn!("No symbol mapping found for {}", snapshot.product_id);
+                    return Ok(None);
+                }
+            };
+            let orderbook = PlainOrderbook {
+                bids: PriceLevelsVec::from_tuples_vec_unsorted(&mut snapshot.bids),
+                asks: PriceLevelsVec::from_tuples_vec_unsorted(&mut snapshot.asks),
+                time: Utc::now().timestamp_millis() as f64,
+                max_depth: None,
+            };
+
+            let event = book_events.event(symbol.clone(), &orderbook);
+            markets.insert(symbol, orderbook);
+
+            Ok(event)
+        }
+        ws::WsMsg::L2update(update) => {
+            let symbol = match internal_symbol(markets, update.product_id) {
+                Some(symbol) => symbol,
+                None => {
+                    warn!("No symbol mapping found for {}", update.product_id);
+                    return Ok(None);
+                }
+            };
+            let time = parse_time(update.time)?;
+            let (bids, asks) = update.levels().map_err(MarketDataError::convert_error)?;
+            let bids = PriceLevelsVec::from_tuples_vec(&bids);
+            let asks = PriceLevelsVec::from_tuples_vec(&asks);
+
+            match markets.get_mut(&symbol) {
+                Some(orderbook) => {
+                    orderbook.update_with_timestamp(&bids, &asks, time);
+
+                    Ok(book_events.delta(symbol, orderbook, &bids, &asks))
+                }
+                None => Ok(None),
+            }
+        }
+        ws::WsMsg::Match(trade) | ws::WsMsg::LastMatch(trade) => {
+            let symbol = internal_symbol(markets, trade.product_id)
+                .unwrap_or_else(|| Box::from(trade.product_id));
+            let trade = botvana::market::trade::Trade::try_from(&trade)
+                .map_err(MarketDataError::convert_error)?;
+
+            Ok(Some(MarketEvent::trades(symbol, Box::new([trade]))))
+        }
+        ws::WsMsg::Received(_)
+        | ws::WsMsg::Activate(_)
+        | ws::WsMsg::Open(_)
+        | ws::WsMsg::Change(_)
+        | ws::WsMsg::Done(_)
+        | ws::WsMsg::Other => Ok(None),
+    }
+}
+
+/// Applies `full` channel message to the order-by-order book of its market
+///
+/// Returns trade of matches and change of the aggregated book otherwise,
+/// the applied L3 update and the book change of matches are queued.
+#[inline]
+fn process_full_ws_message(
+    ws_msg: ws::WsMsg,
+    markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    l3: &mut HashMap<Box<str>, L3Orderbook>,
+    sequences: &SequenceTracker,
+    book_events: &BookEmitter,
+    pending: &mut VecDeque<MarketEvent>,
+) -> Result<Option<MarketEvent>, MarketDataError> {
+    let (product_id, sequence) = match &ws_msg {
+        ws::WsMsg::Received(msg) | ws::WsMsg::Activate(msg) => (msg.product_id, msg.sequence),
+        ws::WsMsg::Open(msg) => (msg.product_id, msg.sequence),
+        ws::WsMsg::Change(msg) => (msg.product_id, msg.sequence),
+        ws::WsMsg::Done(msg) => (msg.product_id, msg.sequence),
+        ws::WsMsg::Match(msg) => (msg.product_id, msg.sequence),
+        _ => return process_feed_ws_message(ws_msg, markets, book_events),
+    };
+    let symbol = match internal_symbol(markets, product_id) {
+        Some(symbol) => symbol,
+        None => {
+            warn!("No symbol mapping found for {product_id}");
+            return Ok(None);
+        }
+    };
+    if !sequences
+        .check(&symbol, sequence, sequence)
+        .map_err(MarketDataError::with_source)?
+    {
+        return Ok(None);
+    }
+
+    let (update, time) = match &ws_msg {
+        ws::WsMsg::Open(open) => (
+            L3Update::Open(open.order().map_err(MarketDataError::convert_error)?),
+            open.time,
+        ),
+        ws::WsMsg::Change(change) => match change.new_size {
+            Some(size) => (
+                L3Update::Change {
+                    id: Box::from(change.order_id),
+                    size: size.parse().map_err(MarketDataError::with_source)?,
+                },
+                change.time,
+            ),
+            None => return Ok(None),
+        },
+        ws::WsMsg::Done(done) => (
+            L3Update::Done {
+                id: Box::from(done.order_id),
+            },
+            done.time,
+        ),
+        ws::WsMsg::Match(trade) => (
+            L3Update::Fill {
+                id: Box::from(trade.maker_order_id),
+                size: trade.size.parse().map_err(MarketDataError::with_source)?,
+            },
+            trade.time,
+        ),
+        _ => return Ok(None),
+    };
+
+    // Orders done without reaching the book change nothing
+    let changed = l3.get_mut(&symbol).and_then(|book| {
+        let (side, price) = book.apply(&update)?;
+        Some((side, price, book.level_size(side, price)))
+    });
+    let delta = match changed {
+        Some((side, price, size)) => {
+            pending.push_back(MarketEvent::l3_update(symbol.clone(), Box::new([update])));
+            let level = PriceLevelsVec::from_tuples_vec(&[(price, size)]);
+            let empty = PriceLevelsVec::new();
+            let (bids, asks) = match side {
+                BookSide::Bid => (&level, &empty),
+                BookSide::Ask => (&empty, &level),
+            };
+
+            match markets.get_mut(&symbol) {
+                Some(orderbook) => {
+                    orderbook.update_with_timestamp(bids, asks, parse_time(time)?);
+                    book_events.delta(symbol.clone(), orderbook, bids, asks)
+                }
+                None => None,
+            }
+        }
+        None => None,
+    };
+
+    match ws_msg {
+        ws::WsMsg::Match(trade) => {
+            pending.extend(delta);
+            let trade = botvana::market::trade::Trade::try_from(&trade)
+                .map_err(MarketDataError::convert_error)?;
+
+            Ok(Some(MarketEvent::trades(symbol, Box::new([trade]))))
+        }
+        _ => Ok(delta),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_native_symbol() {
+        assert_eq!("BTC-USD", native_symbol("btc/usd"));
+        assert_eq!("ETH-EUR", native_symbol("ETH-EUR"));
+    }
+
+    #[test]
+    fn test_process_ws_msg_snapshot_and_update() {
+        let cb = Coinbase::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTC/USD"), PlainOrderbook::new());
+
+        let snapshot = r#"{
+            "type": "snapshot",
+            "product_id": "BTC-USD",
+            "bids": [["10101.10", "0.45054140"], ["10101.00", "1.0"]],
+            "asks": [["10102.55", "0.57753524"]]
+        }"#;
+        let event = cb.process_ws_msg(snapshot, &mut markets).unwrap();
+        assert!(event.is_some());
+        assert_eq!(vec![10101.0, 10101.1], markets["BTC/USD"].bids.price_vec);
+
+        let update = r#"{
+            "type": "l2update",
+            "product_id": "BTC-USD",
+            "changes": [["buy", "10101.10", "0"], ["sell", "10102.00", "2.5"]],
+            "time": "2019-08-14T20:42:27.265Z"
+        }"#;
+        match cb
+            .process_ws_msg(update, &mut markets)
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::OrderbookUpdate(market, orderbook)) => {
+                assert_eq!("BTC/USD", &*market);
+                assert_eq!(vec![10101.0], orderbook.bids.price_vec);
+                assert_eq!(vec![10102.0, 10102.55], orderbook.asks.price_vec);
+                assert_eq!(1565815347265.0, orderbook.time);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_process_ws_msg_match() {
+        let cb = Coinbase::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTC-USD"), PlainOrderbook::new());
+
+        let msg = r#"{
+            "type": "match",
+            "trade_id": 10,
+            "sequence": 50,
+            "maker_order_id": "ac928c66-ca53-498f-9c13-a110027a60e8",
+            "taker_order_id": "132fb6ae-456b-4654-b4e0-d681ac05cea1",
+            "time": "2014-11-07T08:19:27.028459Z",
+            "product_id": "BTC-USD",
+            "size": "5.23512",
+            "price": "400.23",
+            "side": "sell"
+        }"#;
+
+        match cb
+            .process_ws_msg(msg, &mut markets)
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::Trades(market, trades)) => {
+                assert_eq!("BTC-USD", &*market);
+                assert_eq!(400.23, trades[0].price);
+                assert_eq!(5.23512, trades[0].size);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_process_full_channel() {
+        let mut cb = Coinbase::default();
+        cb.set_l3_books(true);
+        let orders = vec![
+            L3Order {
+                id: Box::from("a"),
+                side: BookSide::Bid,
+                price: 100.0,
+                size: 1.0,
+            },
+            L3Order {
+                id: Box::from("b"),
+                side: BookSide::Ask,
+                price: 101.0,
+                size: 2.0,
+            },
+        ];
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTC/USD"), cb.seed_l3("BTC/USD", 10, orders));
+        assert!(matches!(
+            cb.take_pending_event().map(|event| event.r#type),
+            Some(MarketEventType::L3Snapshot(_, orders)) if orders.len() == 2
+        ));
+
+        let open = |sequence: u64, size: &str| {
+            format!(
+                r#"{{"type": "open", "product_id": "BTC-USD", "sequence": {sequence},
+                "time": "2014-11-07T08:19:27.028459Z", "order_id": "c", "price": "100.0",
+                "remaining_size": "{size}", "side": "buy"}}"#
+            )
+        };
+        // Included in the snapshot
+        assert!(cb
+            .process_ws_msg(&open(10, "1.0"), &mut markets)
+            .unwrap()
+            .is_none());
+
+        match cb
+            .process_ws_msg(&open(11, "0.5"), &mut markets)
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::OrderbookUpdate(market, orderbook)) => {
+                assert_eq!("BTC/USD", &*market);
+                assert_eq!(vec![1.5], orderbook.bids.size_vec);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+        match cb.take_pending_event().map(|event| event.r#type) {
+            Some(MarketEventType::L3Update(_, updates)) => {
+                assert!(matches!(&updates[..], [L3Update::Open(order)] if &*order.id == "c"));
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+
+        let msg = r#"{
+            "type": "match",
+            "trade_id": 10,
+            "sequence": 12,
+            "maker_order_id": "b",
+            "taker_order_id": "x",
+            "time": "2014-11-07T08:19:27.028459Z",
+            "product_id": "BTC-USD",
+            "size": "0.5",
+            "price": "101.0",
+            "side": "sell"
+        }"#;
+        match cb
+            .process_ws_msg(msg, &mut markets)
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::Trades(_, trades)) => assert_eq!(0.5, trades[0].size),
+            other => panic!("unexpected event {other:?}"),
+        }
+        assert!(matches!(
+            cb.take_pending_event().map(|event| event.r#type),
+            Some(MarketEventType::L3Update(_, updates))
+                if updates[..] == [L3Update::Fill { id: Box::from("b"), size: 0.5 }]
+        ));
+        match cb.take_pending_event().map(|event| event.r#type) {
+            Some(MarketEventType::OrderbookUpdate(_, orderbook)) => {
+                assert_eq!(vec![1.5], orderbook.asks.size_vec);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+
+        // Sequence 13 is missing
+        let msg = r#"{
+            "type": "done",
+            "product_id": "BTC-USD",
+            "sequence": 14,
+            "time": "2014-11-07T08:19:27.028459Z",
+            "order_id": "a",
+            "reason": "canceled",
+            "side": "buy"
+        }"#;
+        let err = cb.process_ws_msg(msg, &mut markets).unwrap_err();
+        assert_eq!(Some("BTC/USD"), err.out_of_sync_market());
+    }
+
+    #[test]
+    fn test_process_ws_msg_error() {
+        let cb = Coinbase::default();
+        let msg = r#"{"type": "error", "message": "Failed to subscribe", "reason": "BTC-XYZ is not a valid product"}"#;
+
+        assert!(cb.process_ws_msg(msg, &mut HashMap::new()).is_err());
+    }
+}
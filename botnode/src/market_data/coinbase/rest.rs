@@ -0,0 +1,168 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer};
+
+use botvana::{
+    exchange::ExchangeId,
+    market::orderbook::{
+        l3::{BookSide, L3Order},
+        *,
+    },
+};
+
+/// Product listed by `/products`
+#[derive(Debug, Deserialize)]
+pub struct Product<'a> {
+    pub id: &'a str,
+    pub base_currency: &'a str,
+    pub quote_currency: &'a str,
+    pub base_increment: &'a str,
+    pub quote_increment: &'a str,
+    #[serde(default)]
+    pub trading_disabled: bool,
+}
+
+impl<'a> TryFrom<&Product<'a>> for botvana::market::Market {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(product: &Product<'a>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            exchange: ExchangeId::Coinbase,
+            name: product.id.to_string(),
+            native_symbol: product.id.to_string(),
+            size_increment: product.base_increment.parse()?,
+            price_increment: product.quote_increment.parse()?,
+            r#type: botvana::market::MarketType::Spot(botvana::market::SpotMarket {
+                base: product.base_currency.to_string(),
+                quote: product.quote_currency.to_string(),
+            }),
+        })
+    }
+}
+
+/// Aggregated orderbook returned by `/products/{id}/book?level=2`
+#[derive(Debug, Deserialize)]
+pub struct OrderbookSnapshot {
+    #[serde(deserialize_with = "deserialize_into_price_levels_vec")]
+    pub bids: PriceLevelsVec<f64>,
+    #[serde(deserialize_with = "deserialize_into_price_levels_vec")]
+    pub asks: PriceLevelsVec<f64>,
+}
+
+/// Deserializes `[price, size, num_orders]` levels
+fn deserialize_into_price_levels_vec<'de, D>(
+    deserializer: D,
+) -> Result<PriceLevelsVec<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let buf = Box::<[(String, String, u64)]>::deserialize(deserializer)?;
+
+    let mut levels = buf
+        .iter()
+        .map(|(price, size, _)| Ok((price.parse::<f64>()?, size.parse::<f64>()?)))
+        .collect::<Result<Vec<(f64, f64)>, std::num::ParseFloatError>>()
+        .map_err(serde::de::Error::custom)?;
+
+    Ok(PriceLevelsVec::from_tuples_vec_unsorted(&mut levels))
+}
+
+/// Orders returned by `/products/{id}/book?level=3`, each level in queue
+/// order
+#[derive(Debug, Deserialize)]
+pub struct L3Snapshot<'a> {
+    /// Last `full` channel message included in the snapshot
+    pub sequence: u64,
+    /// `[price, size, order_id]` orders
+    #[serde(borrow)]
+    pub bids: Vec<(&'a str, &'a str, &'a str)>,
+    #[serde(borrow)]
+    pub asks: Vec<(&'a str, &'a str, &'a str)>,
+}
+
+impl<'a> L3Snapshot<'a> {
+    /// Returns the orders, bids before asks
+    pub fn orders(&self) -> Result<Vec<L3Order>, String> {
+        let bids = self.bids.iter().map(|order| (BookSide::Bid, order));
+        let asks = self.asks.iter().map(|order| (BookSide::Ask, order));
+
+        bids.chain(asks)
+            .map(|(side, (price, size, id))| {
+                Ok(L3Order {
+                    id: Box::from(*id),
+                    side,
+                    price: price.parse::<f64>().map_err(|e| e.to_string())?,
+                    size: size.parse::<f64>().map_err(|e| e.to_string())?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Response of `/time`
+#[derive(Debug, Deserialize)]
+pub struct ServerTime {
+    pub iso: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_products() {
+        let sample = r#"[{
+            "id": "BTC-USD",
+            "base_currency": "BTC",
+            "quote_currency": "USD",
+            "quote_increment": "0.01",
+            "base_increment": "0.00000001",
+            "display_name": "BTC/USD",
+            "min_market_funds": "1",
+            "margin_enabled": false,
+            "post_only": false,
+            "limit_only": false,
+            "cancel_only": false,
+            "status": "online",
+            "status_message": "",
+            "auction_mode": false
+        }]"#;
+
+        let products = serde_json::from_str::<Vec<Product>>(sample).unwrap();
+        let market = botvana::market::Market::try_from(&products[0]).unwrap();
+
+        assert_eq!("BTC-USD", market.name);
+        assert_eq!(0.01, market.price_increment);
+        assert_eq!(0.00000001, market.size_increment);
+    }
+
+    #[test]
+    fn test_parse_orderbook_snapshot() {
+        let sample = r#"{
+            "bids": [["295.96", "4.39088265", 2], ["295.95", "1.5", 1]],
+            "asks": [["295.97", "25.23542881", 12]],
+            "sequence": 3
+        }"#;
+
+        let snapshot = serde_json::from_str::<OrderbookSnapshot>(sample).unwrap();
+
+        assert_eq!(vec![295.95, 295.96], snapshot.bids.price_vec);
+        assert_eq!(vec![295.97], snapshot.asks.price_vec);
+    }
+
+    #[test]
+    fn test_parse_l3_snapshot() {
+        let sample = r#"{
+            "bids": [["295.96", "0.05", "3b0f1225-7f84-490b-a29f-0faef9de823a"]],
+            "asks": [["295.97", "5.7", "da863862-25f4-4868-ac41-005d11ab0a5f"]],
+            "sequence": 3
+        }"#;
+
+        let snapshot = serde_json::from_str::<L3Snapshot>(sample).unwrap();
+        let orders = snapshot.orders().unwrap();
+
+        assert_eq!(3, snapshot.sequence);
+        assert_eq!(BookSide::Bid, orders[0].side);
+        assert_eq!(0.05, orders[0].size);
+        assert_eq!("da863862-25f4-4868-ac41-005d11ab0a5f", &*orders[1].id);
+    }
+}
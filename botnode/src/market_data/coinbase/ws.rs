@@ -0,0 +1,281 @@
+use serde::{Deserialize, Deserializer};
+
+use botvana::market::orderbook::l3::{BookSide, L3Order};
+
+/// Coinbase Websocket feed message
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsMsg<'a> {
+    Subscriptions {
+        #[serde(borrow)]
+        channels: Vec<Channel<'a>>,
+    },
+    Error {
+        message: &'a str,
+        #[serde(default)]
+        reason: &'a str,
+    },
+    #[serde(borrow)]
+    Snapshot(L2snapshot<'a>),
+    #[serde(borrow)]
+    L2update(L2update<'a>),
+    #[serde(borrow)]
+    Match(Match<'a>),
+    /// Most recent match sent right after subscribing
+    #[serde(borrow)]
+    LastMatch(Match<'a>),
+    /// Order accepted by the matching engine, not in the book yet
+    #[serde(borrow)]
+    Received(Sequenced<'a>),
+    /// Stop order triggered, not in the book yet
+    #[serde(borrow)]
+    Activate(Sequenced<'a>),
+    #[serde(borrow)]
+    Open(Open<'a>),
+    #[serde(borrow)]
+    Change(Change<'a>),
+    #[serde(borrow)]
+    Done(Done<'a>),
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Channel<'a> {
+    pub name: &'a str,
+    #[serde(default)]
+    pub product_ids: Vec<&'a str>,
+}
+
+/// Full aggregated orderbook sent once after subscribing to `level2`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct L2snapshot<'a> {
+    pub product_id: &'a str,
+    #[serde(deserialize_with = "de_price_levels_from_str")]
+    pub bids: Vec<(f64, f64)>,
+    #[serde(deserialize_with = "de_price_levels_from_str")]
+    pub asks: Vec<(f64, f64)>,
+}
+
+/// Changed price levels, size of zero removes the level
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct L2update<'a> {
+    pub product_id: &'a str,
+    pub time: &'a str,
+    #[serde(borrow)]
+    pub changes: Vec<(&'a str, &'a str, &'a str)>,
+}
+
+impl<'a> L2update<'a> {
+    /// Splits changes into bid and ask levels
+    pub fn levels(&self) -> Result<(Vec<(f64, f64)>, Vec<(f64, f64)>), String> {
+        let mut bids = Vec::new();
+        let mut asks = Vec::new();
+
+        for (side, price, size) in &self.changes {
+            let level = (
+                price.parse::<f64>().map_err(|e| e.to_string())?,
+                size.parse::<f64>().map_err(|e| e.to_string())?,
+            );
+            match *side {
+                "buy" => bids.push(level),
+                "sell" => asks.push(level),
+                _ => return Err(format!("unknown side: {side}")),
+            }
+        }
+
+        Ok((bids, asks))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Match<'a> {
+    pub product_id: &'a str,
+    /// Position in the `full` channel feed of the product
+    #[serde(default)]
+    pub sequence: u64,
+    /// Order of the `full` channel book the match took size from
+    #[serde(default)]
+    pub maker_order_id: &'a str,
+    pub time: &'a str,
+    pub price: &'a str,
+    pub size: &'a str,
+}
+
+/// `full` channel message not changing the book
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Sequenced<'a> {
+    pub product_id: &'a str,
+    pub sequence: u64,
+}
+
+/// Remaining size of the order was placed on the book
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Open<'a> {
+    pub product_id: &'a str,
+    pub sequence: u64,
+    pub time: &'a str,
+    pub order_id: &'a str,
+    pub price: &'a str,
+    pub remaining_size: &'a str,
+    pub side: &'a str,
+}
+
+/// Size of the order was decreased, `new_size` is missing for market orders
+/// not in the book
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Change<'a> {
+    pub product_id: &'a str,
+    pub sequence: u64,
+    pub time: &'a str,
+    pub order_id: &'a str,
+    #[serde(default, borrow)]
+    pub new_size: Option<&'a str>,
+}
+
+/// Order left the book or was done without reaching it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Done<'a> {
+    pub product_id: &'a str,
+    pub sequence: u64,
+    pub time: &'a str,
+    pub order_id: &'a str,
+}
+
+impl<'a> Open<'a> {
+    /// Returns the opened order
+    pub fn order(&self) -> Result<L3Order, String> {
+        Ok(L3Order {
+            id: Box::from(self.order_id),
+            side: book_side(self.side)?,
+            price: self.price.parse::<f64>().map_err(|e| e.to_string())?,
+            size: self
+                .remaining_size
+                .parse::<f64>()
+                .map_err(|e| e.to_string())?,
+        })
+    }
+}
+
+/// Returns side of the book orders of given side rest on
+pub fn book_side(side: &str) -> Result<BookSide, String> {
+    match side {
+        "buy" => Ok(BookSide::Bid),
+        "sell" => Ok(BookSide::Ask),
+        _ => Err(format!("unknown side: {side}")),
+    }
+}
+
+impl<'a> TryFrom<&Match<'a>> for botvana::market::trade::Trade {
+    type Error = String;
+
+    fn try_from(trade: &Match<'a>) -> Result<Self, Self::Error> {
+        Ok(Self::new(
+            trade.price.parse::<f64>().map_err(|e| e.to_string())?,
+            trade.size.parse::<f64>().map_err(|e| e.to_string())?,
+            trade
+                .time
+                .parse()
+                .map_err(|_| format!("error parsing: {}", trade.time))?,
+        ))
+    }
+}
+
+fn de_price_levels_from_str<'de, D>(deserializer: D) -> Result<Vec<(f64, f64)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let levels: Vec<(&'de str, &'de str)> = Deserialize::deserialize(deserializer)?;
+
+    levels
+        .iter()
+        .map(|(price, size)| Ok((price.parse::<f64>()?, size.parse::<f64>()?)))
+        .collect::<Result<_, std::num::ParseFloatError>>()
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_subscriptions() {
+        let msg = r#"{
+            "type": "subscriptions",
+            "channels": [
+                {"name": "level2", "product_ids": ["BTC-USD"]},
+                {"name": "matches", "product_ids": ["BTC-USD"]}
+            ]
+        }"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::Subscriptions { channels } => assert_eq!(2, channels.len()),
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_l2update() {
+        let msg = r#"{
+            "type": "l2update",
+            "product_id": "BTC-USD",
+            "changes": [["buy", "10101.80000000", "0.162567"], ["sell", "10102.0", "0"]],
+            "time": "2019-08-14T20:42:27.265Z"
+        }"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::L2update(update) => {
+                let (bids, asks) = update.levels().unwrap();
+                assert_eq!(vec![(10101.8, 0.162567)], bids);
+                assert_eq!(vec![(10102.0, 0.0)], asks);
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_full_channel() {
+        let msg = r#"{
+            "type": "change",
+            "product_id": "BTC-USD",
+            "sequence": 80,
+            "time": "2014-11-07T08:19:27.028459Z",
+            "order_id": "ac928c66-ca53-498f-9c13-a110027a60e8",
+            "new_funds": "5.23512",
+            "old_funds": "12.4",
+            "side": "buy"
+        }"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::Change(change) => assert_eq!(None, change.new_size),
+            other => panic!("unexpected message {other:?}"),
+        }
+
+        let msg = r#"{
+            "type": "open",
+            "product_id": "BTC-USD",
+            "sequence": 81,
+            "time": "2014-11-07T08:19:27.028459Z",
+            "order_id": "d50ec984-77a8-460a-b958-66f114b0de9b",
+            "price": "200.2",
+            "remaining_size": "1.00",
+            "side": "sell"
+        }"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::Open(open) => {
+                let order = open.order().unwrap();
+                assert_eq!(BookSide::Ask, order.side);
+                assert_eq!(200.2, order.price);
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_unknown_type() {
+        let msg = r#"{"type": "heartbeat", "sequence": 90, "product_id": "BTC-USD"}"#;
+
+        assert_eq!(WsMsg::Other, serde_json::from_str::<WsMsg>(msg).unwrap());
+    }
+}
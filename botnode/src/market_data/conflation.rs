@@ -0,0 +1,297 @@
+//! Conflation of orderbook events
+//!
+//! Strategies slower than the feed fall behind and the ring channels drop
+//! whatever doesn't fit, old and new events alike. With
+//! `conflation_interval_ms` set, the market data engine emits at most one
+//! orderbook event per market every interval: an update of a market quiet for
+//! the interval is emitted right away, updates following it are coalesced
+//! until the interval passes and only the latest state is emitted.
+//!
+//! Full books and best bids and offers replace the pending ones, deltas are
+//! applied to a pending full book or merged with a pending delta, the levels
+//! of the later delta winning. Deltas are renumbered so every consumer sees
+//! them in sequence. Other events aren't delayed.
+//!
+//! Number of coalesced events is published as the
+//! `market_data_conflated_events` gauge.
+
+use std::time::Instant;
+
+use crate::{market_data::pool, metrics::MetricLabels, prelude::*};
+
+/// Orderbook events of a market
+#[derive(Debug)]
+struct MarketState {
+    market: Box<str>,
+    /// Time the last event of the market was emitted
+    emitted_at: Instant,
+    /// Event coalescing the updates since
+    pending: Option<MarketEvent>,
+    /// Sequence of the last emitted delta, 0 after a full book
+    sequence: u64,
+}
+
+/// Coalesces orderbook events of each market over an interval
+#[derive(Debug, Default)]
+pub struct Conflator {
+    interval: Option<Duration>,
+    markets: Vec<MarketState>,
+    conflated: u64,
+}
+
+impl Conflator {
+    /// Creates conflator emitting every event right away when the interval
+    /// is `None`
+    pub fn new(interval: Option<Duration>) -> Self {
+        Self {
+            interval,
+            markets: Vec::new(),
+            conflated: 0,
+        }
+    }
+
+    /// Takes the event received at `now`, returns it when it's to be
+    /// emitted right away
+    pub fn push(&mut self, event: MarketEvent, now: Instant) -> Option<MarketEvent> {
+        let interval = match self.interval {
+            Some(interval) if is_book_event(&event) => interval,
+            _ => return Some(event),
+        };
+        let market = event.market()?;
+        let idx = match self.markets.iter().position(|m| &*m.market == market) {
+            Some(idx) => idx,
+            None => {
+                self.markets.push(MarketState {
+                    market: Box::from(market),
+                    emitted_at: now,
+                    pending: None,
+                    sequence: 0,
+                });
+                let state = self.markets.last_mut().unwrap();
+                return Some(state.emit(event));
+            }
+        };
+        let state = &mut self.markets[idx];
+
+        match state.pending.take() {
+            Some(mut pending) => {
+                self.conflated += 1;
+                coalesce(&mut pending, event);
+                state.pending = Some(pending);
+                None
+            }
+            None if now.saturating_duration_since(state.emitted_at) >= interval => {
+                state.emitted_at = now;
+                Some(state.emit(event))
+            }
+            None => {
+                state.pending = Some(event);
+                None
+            }
+        }
+    }
+
+    /// Moves the pending events whose interval passed at `now` into
+    /// `events`
+    pub fn flush_due(&mut self, now: Instant, events: &mut Vec<MarketEvent>) {
+        let interval = match self.interval {
+            Some(interval) => interval,
+            None => return,
+        };
+
+        for state in self.markets.iter_mut() {
+            if now.saturating_duration_since(state.emitted_at) < interval {
+                continue;
+            }
+            if let Some(event) = state.pending.take() {
+                state.emitted_at = now;
+                events.push(state.emit(event));
+            }
+        }
+    }
+
+    /// Returns time until the next pending event is due, `None` when none
+    /// is pending
+    pub fn next_due(&self, now: Instant) -> Option<Duration> {
+        let interval = self.interval?;
+
+        self.markets
+            .iter()
+            .filter(|state| state.pending.is_some())
+            .map(|state| (state.emitted_at + interval).saturating_duration_since(now))
+            .min()
+    }
+
+    /// Drops the pending event of the unsubscribed market
+    pub fn remove(&mut self, market: &str) {
+        self.markets.retain(|state| &*state.market != market);
+    }
+
+    /// Publishes number of coalesced events
+    pub fn publish(&self, exchange: &str) {
+        crate::metrics::registry().set_gauge(
+            "market_data_conflated_events",
+            &MetricLabels::new().exchange(exchange),
+            self.conflated as f64,
+        );
+    }
+}
+
+impl MarketState {
+    /// Numbers the emitted delta after the previously emitted one
+    fn emit(&mut self, mut event: MarketEvent) -> MarketEvent {
+        match &mut event.r#type {
+            MarketEventType::OrderbookUpdate(..) => self.sequence = 0,
+            MarketEventType::OrderbookDelta(_, delta) => {
+                self.sequence += 1;
+                delta.sequence = self.sequence;
+            }
+            _ => {}
+        }
+
+        event
+    }
+}
+
+fn is_book_event(event: &MarketEvent) -> bool {
+    matches!(
+        event.r#type,
+        MarketEventType::OrderbookUpdate(..)
+            | MarketEventType::OrderbookDelta(..)
+            | MarketEventType::BboUpdate(..)
+    )
+}
+
+/// Updates the pending event by the later one
+fn coalesce(pending: &mut MarketEvent, mut later: MarketEvent) {
+    match (&mut pending.r#type, &later.r#type) {
+        (
+            MarketEventType::OrderbookUpdate(_, orderbook),
+            MarketEventType::OrderbookDelta(_, delta),
+        ) => {
+            orderbook.apply_delta(delta);
+        }
+        (
+            MarketEventType::OrderbookDelta(_, earlier),
+            MarketEventType::OrderbookDelta(_, delta),
+        ) => {
+            earlier.bids = merge_levels(&earlier.bids, &delta.bids);
+            earlier.asks = merge_levels(&earlier.asks, &delta.asks);
+            earlier.time = delta.time;
+        }
+        // Full books and best bids and offers carry the whole state
+        _ => {
+            pool::recycle(std::mem::replace(pending, later));
+            return;
+        }
+    }
+
+    pending.timestamp = later.timestamp;
+    pending.span = later.span.take();
+    pending.times = later.times;
+    pool::recycle(later);
+}
+
+/// Returns the changed levels of both deltas, sorted by price, sizes of the
+/// later delta replacing the earlier ones
+fn merge_levels(earlier: &PriceLevelsVec<f64>, later: &PriceLevelsVec<f64>) -> PriceLevelsVec<f64> {
+    let mut levels: Vec<_> = earlier
+        .price_vec
+        .iter()
+        .copied()
+        .zip(earlier.size_vec.iter().copied())
+        .filter(|(price, _)| !later.price_vec.contains(price))
+        .chain(
+            later
+                .price_vec
+                .iter()
+                .copied()
+                .zip(later.size_vec.iter().copied()),
+        )
+        .collect();
+    levels.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    PriceLevelsVec::from_tuples_vec(&levels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(bids: &[(f64, f64)], sequence: u64) -> MarketEvent {
+        MarketEvent::orderbook_delta(
+            Box::from("BTC-PERP"),
+            Box::new(OrderbookDelta {
+                bids: PriceLevelsVec::from_tuples_vec(bids),
+                asks: PriceLevelsVec::new(),
+                time: sequence as f64,
+                sequence,
+            }),
+        )
+    }
+
+    fn book() -> MarketEvent {
+        MarketEvent::orderbook_update(
+            Box::from("BTC-PERP"),
+            Box::new(PlainOrderbook {
+                bids: PriceLevelsVec::from_tuples_vec(&[(99.0, 1.0), (100.0, 1.0)]),
+                asks: PriceLevelsVec::from_tuples_vec(&[(101.0, 1.0)]),
+                time: 0.0,
+                max_depth: None,
+            }),
+        )
+    }
+
+    #[test]
+    fn test_coalesce_deltas() {
+        let mut conflator = Conflator::new(Some(Duration::from_millis(10)));
+        let start = Instant::now();
+        let mut events = Vec::new();
+
+        // Quiet market is emitted right away
+        assert!(conflator.push(book(), start).is_some());
+        assert!(conflator.push(delta(&[(100.0, 2.0)], 1), start).is_none());
+        assert!(conflator
+            .push(delta(&[(98.0, 1.0), (100.0, 3.0)], 2), start)
+            .is_none());
+        assert_eq!(Some(Duration::from_millis(10)), conflator.next_due(start));
+
+        conflator.flush_due(start + Duration::from_millis(5), &mut events);
+        assert!(events.is_empty());
+        conflator.flush_due(start + Duration::from_millis(10), &mut events);
+        match &events[0].r#type {
+            MarketEventType::OrderbookDelta(_, delta) => {
+                assert_eq!(vec![98.0, 100.0], delta.bids.price_vec);
+                assert_eq!(vec![1.0, 3.0], delta.bids.size_vec);
+                assert_eq!(1, delta.sequence);
+                assert_eq!(2.0, delta.time);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+        assert_eq!(None, conflator.next_due(start));
+
+        // Delta applied to the pending full book
+        let later = start + Duration::from_millis(15);
+        assert!(conflator.push(book(), later).is_none());
+        assert!(conflator.push(delta(&[(99.0, 0.0)], 1), later).is_none());
+        events.clear();
+        conflator.flush_due(later + Duration::from_millis(5), &mut events);
+        match &events[0].r#type {
+            MarketEventType::OrderbookUpdate(_, orderbook) => {
+                assert_eq!(vec![100.0], orderbook.bids.price_vec);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+        assert_eq!(2, conflator.conflated);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut conflator = Conflator::new(None);
+        let now = Instant::now();
+
+        assert!(conflator.push(book(), now).is_some());
+        assert!(conflator.push(delta(&[(100.0, 2.0)], 1), now).is_some());
+        assert_eq!(None, conflator.next_due(now));
+    }
+}
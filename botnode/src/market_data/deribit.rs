@@ -0,0 +1,486 @@
+//! Deribit futures and options market data adapter
+//!
+//! Markets are the active futures and options of every currency from
+//! `/api/v2/public/get_instruments`, including expiries and option strikes.
+//! The JSON-RPC Websocket API streams orderbooks from the `book.{instrument}.raw`
+//! channel, a `snapshot` after subscribing followed by `change` messages
+//! chained by `prev_change_id`, and trades from `trades.{instrument}.raw`.
+//!
+//! A change not continuing the previous one is reported as a sequence gap,
+//! after which the book channel is resubscribed for a new snapshot. The
+//! adapter keeps connections alive with `public/test` requests.
+
+pub(crate) mod rest;
+pub(crate) mod ws;
+
+use std::cell::Cell;
+
+use chrono::TimeZone;
+
+use super::prelude::*;
+use crate::prelude::*;
+use botvana::exchange::ExchangeId;
+
+/// Depth of REST orderbook snapshots
+const SNAPSHOT_DEPTH: usize = 1000;
+
+#[derive(Debug)]
+pub struct Deribit {
+    pub metrics: AdapterMetrics,
+    /// Id of the last sent request
+    cur_idx: Cell<u64>,
+    api_url: Box<str>,
+    ws_url: Box<str>,
+    sequences: SequenceTracker,
+    book_events: BookEmitter,
+}
+
+impl Deribit {
+    /// Returns id for the next request
+    fn next_idx(&self) -> u64 {
+        self.cur_idx.set(self.cur_idx.get() + 1);
+        self.cur_idx.get()
+    }
+
+    fn client(&self) -> Result<surf::Client, MarketDataError> {
+        surf::Config::new()
+            .set_base_url(Url::parse(&self.api_url).map_err(MarketDataError::with_source)?)
+            .set_timeout(Some(Duration::from_secs(5)))
+            .try_into()
+            .map_err(MarketDataError::with_source)
+    }
+}
+
+impl Default for Deribit {
+    fn default() -> Self {
+        Deribit {
+            api_url: Box::from("https://www.deribit.com"),
+            ws_url: Box::from("wss://www.deribit.com/ws/api/v2"),
+            cur_idx: Cell::new(0),
+            metrics: AdapterMetrics::default(),
+            sequences: SequenceTracker::new(),
+            book_events: BookEmitter::default(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl RestMarketDataAdapter for Deribit {
+    fn name(&self) -> &'static str {
+        "deribit-rest"
+    }
+
+    fn exchange(&self) -> ExchangeId {
+        ExchangeId::Deribit
+    }
+
+    /// Fetches active futures and options traded on Deribit
+    async fn fetch_markets(&self) -> Result<Box<[Market]>, MarketDataError> {
+        rate_limit::acquire(ExchangeId::Deribit, Endpoint::Markets).await;
+        let mut res = self
+            .client()?
+            .get("/api/v2/public/get_instruments?currency=any&expired=false")
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let res = serde_json::from_slice::<rest::Response<Vec<rest::Instrument>>>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?;
+        let instruments = res.into_result().map_err(MarketDataError::convert_error)?;
+
+        let markets: Box<[Market]> = instruments
+            .iter()
+            .filter(|instrument| instrument.is_active)
+            .filter_map(|instrument| Market::try_from(instrument).ok())
+            .collect();
+
+        debug!("{} futures and options on Deribit", markets.len());
+
+        Ok(markets)
+    }
+
+    async fn fetch_orderbook_snapshot(
+        &self,
+        symbol: &str,
+    ) -> Result<PlainOrderbook<f64>, MarketDataError> {
+        rate_limit::acquire(ExchangeId::Deribit, Endpoint::Orderbook).await;
+        let mut res = self
+            .client()?
+            .get(format!(
+                "/api/v2/public/get_order_book?instrument_name={}&depth={SNAPSHOT_DEPTH}",
+                native_symbol(symbol)
+            ))
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let res =
+            serde_json::from_slice::<rest::Response<rest::OrderbookSnapshot>>(body.as_bytes())
+                .map_err(MarketDataError::with_source)?;
+        let snapshot = res.into_result().map_err(MarketDataError::convert_error)?;
+
+        Ok(PlainOrderbook {
+            bids: snapshot.bids,
+            asks: snapshot.asks,
+            time: snapshot.timestamp as f64,
+            max_depth: None,
+        })
+    }
+
+    async fn fetch_server_time(&self) -> Result<Option<DateTime<Utc>>, MarketDataError> {
+        rate_limit::acquire(ExchangeId::Deribit, Endpoint::Time).await;
+        let mut res = self
+            .client()?
+            .get("/api/v2/public/get_time")
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let res = serde_json::from_slice::<rest::Response<i64>>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?;
+        let millis = res.into_result().map_err(MarketDataError::convert_error)?;
+
+        Ok(Some(Utc.timestamp_millis(millis)))
+    }
+}
+
+impl WsMarketDataAdapter for Deribit {
+    fn metrics(&self) -> &AdapterMetrics {
+        &self.metrics
+    }
+
+    fn set_book_events(&mut self, book_events: BookEvents) {
+        self.book_events = BookEmitter::new(book_events);
+    }
+
+    fn sequences(&self) -> Option<&SequenceTracker> {
+        Some(&self.sequences)
+    }
+
+    fn ws_url(&self) -> Box<str> {
+        self.ws_url.clone()
+    }
+
+    fn subscribe_msgs(&mut self, markets: &[&str]) -> Box<[String]> {
+        info!("Subscribing for {markets:?}");
+
+        Box::new([json!({
+            "jsonrpc": "2.0",
+            "id": self.next_idx(),
+            "method": "public/subscribe",
+            "params": {"channels": channels(markets)}
+        })
+        .to_string()])
+    }
+
+    fn unsubscribe_msgs(&mut self, markets: &[&str]) -> Option<Box<[String]>> {
+        info!("Unsubscribing {markets:?}");
+
+        Some(Box::new([json!({
+            "jsonrpc": "2.0",
+            "id": self.next_idx(),
+            "method": "public/unsubscribe",
+            "params": {"channels": channels(markets)}
+        })
+        .to_string()]))
+    }
+
+    fn resync_msgs(&self, market: &str) -> Option<Box<[String]>> {
+        let channels = [format!("book.{}.raw", native_symbol(market))];
+
+        Some(Box::new([
+            json!({
+                "jsonrpc": "2.0",
+                "id": self.next_idx(),
+                "method": "public/unsubscribe",
+                "params": {"channels": channels}
+            })
+            .to_string(),
+            json!({
+                "jsonrpc": "2.0",
+                "id": self.next_idx(),
+                "method": "public/subscribe",
+                "params": {"channels": channels}
+            })
+            .to_string(),
+        ]))
+    }
+
+    fn ping_msg(&self) -> Option<String> {
+        Some(json!({"jsonrpc": "2.0", "id": self.next_idx(), "method": "public/test"}).to_string())
+    }
+
+    fn process_ws_msg(
+        &self,
+        msg: &str,
+        markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    ) -> Result<Option<MarketEvent>, MarketDataError> {
+        trace!("got ws_msg = {msg:?}");
+
+        match serde_json::from_slice::<ws::WsMsg>(msg.as_bytes()) {
+            Ok(ws_msg) => {
+                process_public_ws_message(ws_msg, markets, &self.sequences, &self.book_events)
+            }
+            Err(e) => {
+                error!("Error parsing ws_msg: {msg}");
+
+                Err(MarketDataError::with_source(e))
+            }
+        }
+    }
+}
+
+/// Converts market name to Deribit instrument name
+fn native_symbol(market: &str) -> String {
+    market.to_uppercase()
+}
+
+/// Returns raw book and trade channels of the markets
+fn channels(markets: &[&str]) -> Vec<String> {
+    markets
+        .iter()
+        .flat_map(|market| {
+            let instrument = native_symbol(market);
+
+            [
+                format!("book.{instrument}.raw"),
+                format!("trades.{instrument}.raw"),
+            ]
+        })
+        .collect()
+}
+
+/// Returns the market name Deribit instrument was subscribed as
+fn internal_symbol(
+    markets: &HashMap<Box<str>, PlainOrderbook<f64>>,
+    instrument: &str,
+) -> Option<Box<str>> {
+    markets
+        .keys()
+        .find(|k| native_symbol(k) == instrument)
+        .cloned()
+}
+
+#[inline]
+fn process_public_ws_message(
+    ws_msg: ws::WsMsg,
+    markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    sequences: &SequenceTracker,
+    book_events: &BookEmitter,
+) -> Result<Option<MarketEvent>, MarketDataError> {
+    match ws_msg {
+        ws::WsMsg::Response(ws::RpcResponse {
+            error: Some(error), ..
+        }) => Err(MarketDataError::convert_error(format!(
+            "Deribit error {}: {}",
+            error.code, error.message
+        ))),
+        ws::WsMsg::Response(_) => Ok(None),
+        ws::WsMsg::Subscription(subscription) => match subscription.params.data {
+            ws::SubscriptionData::Book(mut book) => {
+                let symbol = match internal_symbol(markets, book.instrument_name) {
+                    Some(symbol) => symbol,
+                    None => {
+                        warn!("No symbol mapping found for {}", book.instrument_name);
+                        return Ok(None);
+                    }
+                };
+                let bids = PriceLevelsVec::from_tuples_vec_unsorted(&mut book.bids);
+                let asks = PriceLevelsVec::from_tuples_vec_unsorted(&mut book.asks);
+
+                match book.r#type {
+                    "snapshot" => {
+                        sequences.reset(&symbol, book.change_id);
+
+                        let orderbook = PlainOrderbook {
+                            bids,
+                            asks,
+                            time: book.timestamp as f64,
+                            max_depth: None,
+                        };
+
+                        let event = book_events.event(symbol.clone(), &orderbook);
+                        markets.insert(symbol, orderbook);
+
+                        Ok(event)
+                    }
+                    "change" => {
+                        let prev_change_id = book.prev_change_id.unwrap_or_default();
+                        let apply = sequences
+                            .check(&symbol, prev_change_id + 1, book.change_id)
+                            .map_err(MarketDataError::with_source)?;
+                        if !apply {
+                            return Ok(None);
+                        }
+
+                        match markets.get_mut(&symbol) {
+                            Some(orderbook) => {
+                                orderbook.update_with_timestamp(
+                                    &bids,
+                                    &asks,
+                                    book.timestamp as f64,
+                                );
+
+                                Ok(book_events.delta(symbol, orderbook, &bids, &asks))
+                            }
+                            None => Ok(None),
+                        }
+                    }
+                    other => Err(MarketDataError::with_source(UnknownVariantError {
+                        variant: other.to_string(),
+                    })),
+                }
+            }
+            ws::SubscriptionData::Trades(trades) => {
+                let symbol = match trades.first() {
+                    Some(trade) => internal_symbol(markets, trade.instrument_name)
+                        .unwrap_or_else(|| Box::from(trade.instrument_name)),
+                    None => return Ok(None),
+                };
+                let trades = trades
+                    .iter()
+                    .map(|trade| {
+                        botvana::market::trade::Trade::new(
+                            trade.price,
+                            trade.amount,
+                            Utc.timestamp_millis(trade.timestamp),
+                        )
+                    })
+                    .collect();
+
+                Ok(Some(MarketEvent::trades(symbol, trades)))
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book_msg(r#type: &str, prev_change_id: u64, change_id: u64) -> String {
+        format!(
+            r#"{{
+                "jsonrpc": "2.0",
+                "method": "subscription",
+                "params": {{
+                    "channel": "book.BTC-PERPETUAL.raw",
+                    "data": {{
+                        "type": "{type}",
+                        "timestamp": 1554373911330,
+                        "prev_change_id": {prev_change_id},
+                        "instrument_name": "BTC-PERPETUAL",
+                        "change_id": {change_id},
+                        "bids": [["new", 5042.0, 30.0], ["new", 5041.5, 10.0]],
+                        "asks": [["new", 5043.0, 1.0]]
+                    }}
+                }}
+            }}"#
+        )
+    }
+
+    #[test]
+    fn test_process_ws_msg_snapshot_and_change() {
+        let deribit = Deribit::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTC-PERPETUAL"), PlainOrderbook::new());
+
+        let event = deribit
+            .process_ws_msg(&book_msg("snapshot", 0, 100), &mut markets)
+            .unwrap();
+        assert!(event.is_some());
+        assert_eq!(
+            vec![5041.5, 5042.0],
+            markets["BTC-PERPETUAL"].bids.price_vec
+        );
+
+        let change = r#"{
+            "jsonrpc": "2.0",
+            "method": "subscription",
+            "params": {
+                "channel": "book.BTC-PERPETUAL.raw",
+                "data": {
+                    "type": "change",
+                    "timestamp": 1554373911331,
+                    "prev_change_id": 100,
+                    "instrument_name": "BTC-PERPETUAL",
+                    "change_id": 101,
+                    "bids": [["delete", 5042.0, 0]],
+                    "asks": [["new", 5043.5, 2.0]]
+                }
+            }
+        }"#;
+        match deribit
+            .process_ws_msg(change, &mut markets)
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::OrderbookUpdate(market, orderbook)) => {
+                assert_eq!("BTC-PERPETUAL", &*market);
+                assert_eq!(vec![5041.5], orderbook.bids.price_vec);
+                assert_eq!(vec![5043.0, 5043.5], orderbook.asks.price_vec);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+
+        let err = deribit
+            .process_ws_msg(&book_msg("change", 105, 106), &mut markets)
+            .unwrap_err();
+        assert_eq!(Some("BTC-PERPETUAL"), err.out_of_sync_market());
+    }
+
+    #[test]
+    fn test_process_ws_msg_trades() {
+        let deribit = Deribit::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTC-PERPETUAL"), PlainOrderbook::new());
+
+        let msg = r#"{
+            "jsonrpc": "2.0",
+            "method": "subscription",
+            "params": {
+                "channel": "trades.BTC-PERPETUAL.raw",
+                "data": [
+                    {"trade_id": "1", "timestamp": 1590484156350, "price": 8950.0, "instrument_name": "BTC-PERPETUAL", "direction": "sell", "amount": 10.0},
+                    {"trade_id": "2", "timestamp": 1590484156351, "price": 8950.5, "instrument_name": "BTC-PERPETUAL", "direction": "buy", "amount": 20.0}
+                ]
+            }
+        }"#;
+
+        match deribit
+            .process_ws_msg(msg, &mut markets)
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::Trades(market, trades)) => {
+                assert_eq!("BTC-PERPETUAL", &*market);
+                assert_eq!(2, trades.len());
+                assert_eq!(20.0, trades[1].size);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_subscribe_msgs() {
+        let mut deribit = Deribit::default();
+        let msgs = deribit.subscribe_msgs(&["BTC-PERPETUAL", "BTC-29MAR24-60000-C"]);
+
+        assert_eq!(1, msgs.len());
+        assert!(msgs[0].contains("book.BTC-29MAR24-60000-C.raw"));
+        assert!(msgs[0].contains("trades.BTC-PERPETUAL.raw"));
+
+        let msgs = deribit.unsubscribe_msgs(&["BTC-PERPETUAL"]).unwrap();
+        assert!(msgs[0].contains("public/unsubscribe"));
+        assert!(msgs[0].contains("book.BTC-PERPETUAL.raw"));
+    }
+}
@@ -0,0 +1,198 @@
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Deserializer};
+
+use botvana::{
+    exchange::ExchangeId,
+    market::{orderbook::*, FuturesMarket, MarketType, OptionKind, OptionMarket},
+};
+
+/// JSON-RPC envelope of Deribit REST responses
+#[derive(Debug, Deserialize)]
+pub struct Response<T> {
+    pub result: Option<T>,
+    pub error: Option<super::ws::RpcError>,
+}
+
+impl<T> Response<T> {
+    /// Returns the result or the error message
+    pub fn into_result(self) -> Result<T, String> {
+        match (self.result, self.error) {
+            (_, Some(error)) => Err(error.message),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err("Empty result".to_string()),
+        }
+    }
+}
+
+/// Instrument returned by `/api/v2/public/get_instruments`
+#[derive(Debug, Deserialize)]
+pub struct Instrument<'a> {
+    pub instrument_name: &'a str,
+    /// `future`, `option`, `spot` or the `_combo` variants
+    pub kind: &'a str,
+    pub is_active: bool,
+    /// `perpetual` on perpetual futures
+    pub settlement_period: &'a str,
+    /// Milliseconds timestamp
+    pub expiration_timestamp: i64,
+    pub base_currency: &'a str,
+    pub tick_size: f64,
+    pub min_trade_amount: f64,
+    /// `call` or `put` on options
+    pub option_type: Option<&'a str>,
+    pub strike: Option<f64>,
+}
+
+impl<'a> TryFrom<&Instrument<'a>> for botvana::market::Market {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(instrument: &Instrument<'a>) -> Result<Self, Self::Error> {
+        let expires_at = Utc.timestamp_millis(instrument.expiration_timestamp);
+        let r#type = match instrument.kind {
+            "future" if instrument.settlement_period == "perpetual" => {
+                MarketType::Futures(FuturesMarket::perpetual())
+            }
+            "future" => MarketType::Futures(FuturesMarket::expiring(expires_at)),
+            "option" => MarketType::Options(OptionMarket {
+                underlying: instrument.base_currency.to_string(),
+                kind: match instrument.option_type {
+                    Some("call") => OptionKind::Call,
+                    Some("put") => OptionKind::Put,
+                    other => return Err(format!("Invalid option type: {other:?}").into()),
+                },
+                strike: instrument.strike.ok_or("Missing option strike")?,
+                expires_at,
+            }),
+            other => return Err(format!("Unsupported instrument kind: {other}").into()),
+        };
+
+        Ok(Self {
+            exchange: ExchangeId::Deribit,
+            name: instrument.instrument_name.to_string(),
+            native_symbol: instrument.instrument_name.to_string(),
+            size_increment: instrument.min_trade_amount,
+            price_increment: instrument.tick_size,
+            r#type,
+        })
+    }
+}
+
+/// Orderbook returned by `/api/v2/public/get_order_book`
+#[derive(Debug, Deserialize)]
+pub struct OrderbookSnapshot {
+    #[serde(deserialize_with = "deserialize_into_price_levels_vec")]
+    pub bids: PriceLevelsVec<f64>,
+    #[serde(deserialize_with = "deserialize_into_price_levels_vec")]
+    pub asks: PriceLevelsVec<f64>,
+    /// Milliseconds timestamp
+    pub timestamp: i64,
+}
+
+fn deserialize_into_price_levels_vec<'de, D>(
+    deserializer: D,
+) -> Result<PriceLevelsVec<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut levels: Vec<(f64, f64)> = Deserialize::deserialize(deserializer)?;
+
+    Ok(PriceLevelsVec::from_tuples_vec_unsorted(&mut levels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_instruments() {
+        let sample = r#"{
+            "jsonrpc": "2.0",
+            "result": [{
+                "instrument_name": "BTC-PERPETUAL",
+                "kind": "future",
+                "is_active": true,
+                "settlement_period": "perpetual",
+                "expiration_timestamp": 32503708800000,
+                "base_currency": "BTC",
+                "quote_currency": "USD",
+                "tick_size": 0.5,
+                "min_trade_amount": 10,
+                "contract_size": 10
+            }, {
+                "instrument_name": "BTC-29MAR24",
+                "kind": "future",
+                "is_active": true,
+                "settlement_period": "month",
+                "expiration_timestamp": 1711699200000,
+                "base_currency": "BTC",
+                "tick_size": 2.5,
+                "min_trade_amount": 10
+            }, {
+                "instrument_name": "BTC-29MAR24-60000-C",
+                "kind": "option",
+                "is_active": true,
+                "settlement_period": "month",
+                "expiration_timestamp": 1711699200000,
+                "base_currency": "BTC",
+                "tick_size": 0.0005,
+                "min_trade_amount": 0.1,
+                "option_type": "call",
+                "strike": 60000.0
+            }],
+            "usIn": 1,
+            "usOut": 2
+        }"#;
+
+        let res = serde_json::from_str::<Response<Vec<Instrument>>>(sample).unwrap();
+        let markets = res
+            .result
+            .unwrap()
+            .iter()
+            .map(|instrument| botvana::market::Market::try_from(instrument).unwrap())
+            .collect::<Vec<_>>();
+
+        match &markets[0].r#type {
+            MarketType::Futures(futures) => assert!(futures.is_perpetual()),
+            other => panic!("unexpected type {other:?}"),
+        }
+        match &markets[1].r#type {
+            MarketType::Futures(futures) => {
+                assert_eq!(
+                    Some(Utc.timestamp_millis(1711699200000)),
+                    futures.expires_at
+                )
+            }
+            other => panic!("unexpected type {other:?}"),
+        }
+        match &markets[2].r#type {
+            MarketType::Options(option) => {
+                assert_eq!("BTC", option.underlying);
+                assert_eq!(OptionKind::Call, option.kind);
+                assert_eq!(60000.0, option.strike);
+            }
+            other => panic!("unexpected type {other:?}"),
+        }
+        assert_eq!(0.0005, markets[2].price_increment);
+        assert_eq!(0.1, markets[2].size_increment);
+    }
+
+    #[test]
+    fn test_parse_orderbook_snapshot() {
+        let sample = r#"{
+            "jsonrpc": "2.0",
+            "result": {
+                "timestamp": 1550757626706,
+                "instrument_name": "BTC-PERPETUAL",
+                "change_id": 474988,
+                "bids": [[3955.75, 30.0], [3940.75, 102020.0]],
+                "asks": [[3976.0, 18.0]]
+            }
+        }"#;
+
+        let res = serde_json::from_str::<Response<OrderbookSnapshot>>(sample).unwrap();
+        let snapshot = res.result.unwrap();
+
+        assert_eq!(vec![3940.75, 3955.75], snapshot.bids.price_vec);
+        assert_eq!(1550757626706, snapshot.timestamp);
+    }
+}
@@ -0,0 +1,168 @@
+use serde::{Deserialize, Deserializer};
+
+/// Deribit JSON-RPC Websocket message
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum WsMsg<'a> {
+    #[serde(borrow)]
+    Subscription(Subscription<'a>),
+    Response(RpcResponse),
+}
+
+/// Notification of a subscribed channel
+#[derive(Debug, Deserialize)]
+pub struct Subscription<'a> {
+    #[serde(borrow)]
+    pub params: SubscriptionParams<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubscriptionParams<'a> {
+    #[serde(borrow)]
+    pub data: SubscriptionData<'a>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SubscriptionData<'a> {
+    #[serde(borrow)]
+    Book(BookData<'a>),
+    #[serde(borrow)]
+    Trades(Vec<Trade<'a>>),
+}
+
+/// Data of the `book.{instrument}.raw` channel
+#[derive(Debug, Deserialize)]
+pub struct BookData<'a> {
+    /// `snapshot` replacing the book or `change` with changed levels
+    pub r#type: &'a str,
+    pub instrument_name: &'a str,
+    /// Milliseconds timestamp
+    pub timestamp: i64,
+    /// Not set on snapshots
+    pub prev_change_id: Option<u64>,
+    pub change_id: u64,
+    /// Size of zero removes the level
+    #[serde(deserialize_with = "de_level_changes")]
+    pub bids: Vec<(f64, f64)>,
+    #[serde(deserialize_with = "de_level_changes")]
+    pub asks: Vec<(f64, f64)>,
+}
+
+/// Trade of the `trades.{instrument}.raw` channel
+#[derive(Debug, Deserialize)]
+pub struct Trade<'a> {
+    pub instrument_name: &'a str,
+    pub price: f64,
+    pub amount: f64,
+    /// Milliseconds timestamp
+    pub timestamp: i64,
+}
+
+/// Reply to requests
+#[derive(Debug, Deserialize)]
+pub struct RpcResponse {
+    pub error: Option<RpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+/// Deserializes levels encoded as `["new" | "change" | "delete", price, size]`
+fn de_level_changes<'de, D>(deserializer: D) -> Result<Vec<(f64, f64)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let levels: Vec<(&'de str, f64, f64)> = Deserialize::deserialize(deserializer)?;
+
+    Ok(levels
+        .into_iter()
+        .map(|(action, price, size)| match action {
+            "delete" => (price, 0.0),
+            _ => (price, size),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_book_change() {
+        let msg = r#"{
+            "jsonrpc": "2.0",
+            "method": "subscription",
+            "params": {
+                "channel": "book.BTC-PERPETUAL.raw",
+                "data": {
+                    "type": "change",
+                    "timestamp": 1554373911330,
+                    "prev_change_id": 297217,
+                    "instrument_name": "BTC-PERPETUAL",
+                    "change_id": 297218,
+                    "bids": [["delete", 5042.34, 0], ["change", 5041.94, 20.5]],
+                    "asks": [["new", 5043.0, 1.0]]
+                }
+            }
+        }"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::Subscription(subscription) => match subscription.params.data {
+                SubscriptionData::Book(book) => {
+                    assert_eq!(Some(297217), book.prev_change_id);
+                    assert_eq!(vec![(5042.34, 0.0), (5041.94, 20.5)], book.bids);
+                }
+                other => panic!("unexpected data {other:?}"),
+            },
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_trades() {
+        let msg = r#"{
+            "jsonrpc": "2.0",
+            "method": "subscription",
+            "params": {
+                "channel": "trades.BTC-PERPETUAL.raw",
+                "data": [{
+                    "trade_seq": 30289442,
+                    "trade_id": "48079269",
+                    "timestamp": 1590484156350,
+                    "tick_direction": 2,
+                    "price": 8950.0,
+                    "instrument_name": "BTC-PERPETUAL",
+                    "index_price": 8955.88,
+                    "direction": "sell",
+                    "amount": 10.0
+                }]
+            }
+        }"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::Subscription(subscription) => match subscription.params.data {
+                SubscriptionData::Trades(trades) => assert_eq!(8950.0, trades[0].price),
+                other => panic!("unexpected data {other:?}"),
+            },
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_error_response() {
+        let msg = r#"{
+            "jsonrpc": "2.0",
+            "id": 3,
+            "error": {"code": 11050, "message": "bad_request"}
+        }"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::Response(response) => assert_eq!(11050, response.error.unwrap().code),
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+}
@@ -1,6 +1,14 @@
 //! Market Data Engine
 
-use crate::{market_data::adapter::*, prelude::*};
+use tracing::Instrument;
+
+use crate::{
+    bus::Subscriber,
+    config::MarketDataConfig,
+    control::ControlCommand,
+    market_data::{adapter::*, subscriptions::Subscriptions, symbols::ExchangeSymbols},
+    prelude::*,
+};
 
 pub const MARKET_DATA_QUEUE_LEN: usize = 512;
 
@@ -9,10 +17,15 @@ pub const MARKET_DATA_QUEUE_LEN: usize = 512;
 /// It maintains connection to the exchange and produces raw market data.
 pub struct MarketDataEngine<A: MarketDataAdapter<TX_CAP>, const TX_CAP: usize> {
     adapter: A,
+    symbols: ExchangeSymbols,
+    config: MarketDataConfig,
     config_rx: spsc_queue::Consumer<BotConfiguration>,
+    /// Commands of the server changing the subscribed markets
+    commands: Subscriber<ControlCommand>,
     data_txs: crate::channels::ProducersArray<MarketEvent, TX_CAP>,
     status_tx: spsc_queue::Producer<EngineStatus>,
     status_rx: spsc_queue::Consumer<EngineStatus>,
+    audit_tx: Option<crate::audit::AuditProducer>,
 }
 
 impl<A: MarketDataAdapter<TX_CAP>, const TX_CAP: usize> MarketDataEngine<A, TX_CAP> {
@@ -20,18 +33,47 @@ impl<A: MarketDataAdapter<TX_CAP>, const TX_CAP: usize> MarketDataEngine<A, TX_C
         let (status_tx, status_rx) = spsc_queue::make(1);
         Self {
             adapter,
+            symbols: ExchangeSymbols::default(),
+            config: MarketDataConfig::default(),
             config_rx,
+            commands: Subscriber::default(),
             data_txs: crate::channels::ProducersArray::<MarketEvent, TX_CAP>::default(),
             status_tx,
             status_rx,
+            audit_tx: None,
         }
     }
+
+    /// Sets mapping between canonical and native market names
+    pub fn with_symbols(mut self, symbols: ExchangeSymbols) -> Self {
+        self.symbols = symbols;
+        self
+    }
+
+    /// Sets reconnect backoff and orderbook depth
+    pub fn with_config(mut self, config: MarketDataConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets subscription to the server commands, markets are subscribed
+    /// and unsubscribed while running as the server requests
+    pub fn with_commands(mut self, commands: Subscriber<ControlCommand>) -> Self {
+        self.commands = commands;
+        self
+    }
+
+    /// Sets audit channel the connection changes are recorded to
+    pub fn with_audit(mut self, audit_tx: crate::audit::AuditProducer) -> Self {
+        self.audit_tx = Some(audit_tx);
+        self
+    }
 }
 
 #[async_trait(?Send)]
 impl<A: MarketDataAdapter<TX_CAP>, const TX_CAP: usize> Engine for MarketDataEngine<A, TX_CAP> {
     fn name(&self) -> String {
-        format!("market-data-{}", A::NAME)
+        format!("market-data-{}", self.adapter.name())
     }
 
     fn status_rx(&self) -> spsc_queue::Consumer<EngineStatus> {
@@ -40,15 +82,37 @@ impl<A: MarketDataAdapter<TX_CAP>, const TX_CAP: usize> Engine for MarketDataEng
 
     /// Start the market data engine
     async fn start(mut self, shutdown: Shutdown) -> Result<(), EngineError> {
-        info!("Starting market data engine for {}", A::NAME);
+        info!("Starting market data engine for {}", self.adapter.name());
 
         self.status_tx.try_push(EngineStatus::Booting);
 
+        if let Some(audit_tx) = self.audit_tx.take() {
+            let exchange = self.adapter.exchange();
+            self.adapter
+                .metrics()
+                .set_audit(exchange.as_str(), audit_tx);
+        }
+
         // First, fetch available markets using the adapter
+        let mut listed = Box::<[Market]>::default();
         match self.adapter.fetch_markets().await {
             Ok(markets) => {
-                let event = MarketEvent::markets(markets.into());
+                let mut markets: Box<[Market]> = markets.iter().map(Market::from).collect();
+
+                // Check the symbol mapping against what the exchange lists
+                if !self.symbols.is_empty() {
+                    for error in self.symbols.validate(&markets) {
+                        error!(
+                            "Invalid symbol mapping for {}: {error}",
+                            self.adapter.exchange()
+                        );
+                    }
+                    self.symbols.normalize(&mut markets);
+                }
+
+                let event = MarketEvent::markets(Box::new(markets.clone().into()));
                 self.push_value(event);
+                listed = markets;
             }
             Err(e) => {
                 error!("Failed to fetch market info: {e:?}");
@@ -57,24 +121,47 @@ impl<A: MarketDataAdapter<TX_CAP>, const TX_CAP: usize> Engine for MarketDataEng
 
         // Await configuration from botvana-server
         debug!("Waiting for configuration");
-        let config = await_value(self.config_rx);
+        let config = await_value(self.config_rx.clone());
         debug!("Got config = {config:?}");
-        let markets: Vec<_> = config
-            .markets
-            .iter()
-            .map(|market| market.as_ref())
-            .collect();
+        let mut subscriptions = Subscriptions::new(
+            &config.markets,
+            self.symbols.clone(),
+            listed,
+            std::mem::take(&mut self.commands),
+        );
+
+        // Books saved on the last shutdown stand in until fresh ones arrive
+        if let (Some(warm_start), false) = (&self.config.warm_start, self.config.replays()) {
+            let exchange = self.adapter.exchange();
+            for mut event in warm_start.warm_events(exchange.as_str(), &subscriptions.markets()) {
+                self.symbols.canonicalize(&mut event);
+                self.push_value(event);
+            }
+        }
 
         self.status_tx.try_push(EngineStatus::Running);
 
         info!("Running loop w/ markets = {:?}", config.markets);
+        let span = tracing::info_span!(
+            "markets",
+            exchange = %self.adapter.exchange(),
+            markets = ?subscriptions.markets()
+        );
         if let Err(e) = self
             .adapter
-            .run_loop(self.data_txs, &markets[..], shutdown)
+            .run_loop(
+                self.data_txs,
+                &mut subscriptions,
+                self.config,
+                &self.symbols,
+                shutdown,
+            )
+            .instrument(span)
             .await
         {
             error!("Error running loop: {e}");
             self.status_tx.try_push(EngineStatus::Error);
+            return Err(EngineError::adapter(e));
         }
 
         Ok(())
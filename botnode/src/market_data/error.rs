@@ -18,6 +18,35 @@ impl MarketDataError {
     pub fn surf_error(e: surf::Error) -> Self {
         Self::with_source(SurfError { error: e })
     }
+
+    /// Returns the market to resync when the error is [`BookOutOfSync`] or
+    /// [`SequenceGap`]
+    pub fn out_of_sync_market(&self) -> Option<&str> {
+        if let Some(err) = self.source.downcast_ref::<BookOutOfSync>() {
+            return Some(&err.market);
+        }
+
+        self.source
+            .downcast_ref::<SequenceGap>()
+            .map(|err| &*err.market)
+    }
+}
+
+/// Local orderbook no longer matches the exchange and has to be resynced
+#[derive(Debug, thiserror::Error)]
+#[error("{market} orderbook out of sync: {reason}")]
+pub struct BookOutOfSync {
+    pub market: Box<str>,
+    pub reason: String,
+}
+
+/// Orderbook diff doesn't continue the previously applied one
+#[derive(Debug, thiserror::Error)]
+#[error("Sequence gap in {market} orderbook updates: expected update {expected}, got {got}")]
+pub struct SequenceGap {
+    pub market: Box<str>,
+    pub expected: u64,
+    pub got: u64,
 }
 
 #[derive(Debug, thiserror::Error)]
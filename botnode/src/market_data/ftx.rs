@@ -1,35 +1,76 @@
 //! FTX market data adapter implementation
+//!
+//! Every orderbook message carries a CRC32 checksum of the top 100 levels.
+//! The checksum is verified after applying the message, a book failing it
+//! is dropped and resubscribed to get a fresh `partial`.
+//!
+//! FTX closes connections that aren't pinged every 15 seconds, the adapter
+//! asks for `{"op": "ping"}` keepalives and ignores the `pong` replies.
+//!
+//! Books are seeded from `/api/markets/{market}/orderbook` snapshots until
+//! the `partial` of their subscription arrives.
+//!
+//! Funding rates and open interest of futures are polled from
+//! `/api/futures/{future}/stats`. Trades flagged as liquidations are also
+//! emitted as liquidation events.
+//!
+//! Books of markets with known increments are kept in fixed-point, so levels
+//! compare exactly however FTX formatted them, and converted to `f64` for the
+//! events. The levels of updates are parsed into buffers of the [`pool`]
+//! reused across messages.
 
-pub(crate) mod rest;
-pub(crate) mod ws;
+pub mod rest;
+pub mod ws;
 
 use std::{borrow::Borrow, cell::RefCell, collections::HashMap, time::Duration};
 
-use metered::{time_source::StdInstant, *};
 use serde_json::json;
 use surf::Url;
 
 use crate::{
-    market_data::{adapter::*, error::*},
+    market_data::{
+        adapter::*,
+        book_events::{BookEmitter, BookEvents},
+        error::*,
+        json::JsonBuffer,
+        metrics::AdapterMetrics,
+        pool,
+    },
     prelude::*,
+    rate_limit::{self, Endpoint},
+};
+use botvana::{
+    exchange::ExchangeId,
+    market::{
+        derivatives::{FundingRate, Liquidation, OpenInterest},
+        fixed::{Fixed, MarketScales},
+    },
 };
-use botvana::exchange::ExchangeId;
 
 /// FTX market data
 #[derive(Default, Debug)]
 pub struct Ftx {
-    pub metrics: FtxMetrics,
-}
-
-#[derive(Default, Debug)]
-pub struct FtxMetrics {
-    throughput: Throughput<StdInstant, RefCell<metered::common::TxPerSec>>,
+    pub metrics: AdapterMetrics,
+    book_events: BookEmitter,
+    /// Price and size scales by market, known once markets were fetched
+    scales: RefCell<HashMap<Box<str>, MarketScales>>,
+    /// Fixed-point books of the markets with known scales, the `f64` books
+    /// of the events are converted from them
+    books: RefCell<HashMap<Box<str>, PlainOrderbook<Fixed>>>,
+    /// Liquidations among the last processed trades
+    liquidations: RefCell<Option<MarketEvent>>,
+    json: RefCell<JsonBuffer>,
 }
 
 #[async_trait(?Send)]
 impl RestMarketDataAdapter for Ftx {
-    const NAME: &'static str = "ftx-rest";
-    const EXCHANGE_REF: ExchangeId = ExchangeId::Ftx;
+    fn name(&self) -> &'static str {
+        "ftx-rest"
+    }
+
+    fn exchange(&self) -> ExchangeId {
+        ExchangeId::Ftx
+    }
 
     /// Fetches available markets on FTX
     async fn fetch_markets(&self) -> Result<Box<[Market]>, MarketDataError> {
@@ -39,6 +80,7 @@ impl RestMarketDataAdapter for Ftx {
             .try_into()
             .map_err(MarketDataError::with_source)?;
 
+        rate_limit::acquire(ExchangeId::Ftx, Endpoint::Markets).await;
         let mut res = client
             .get("/api/markets")
             .await
@@ -54,45 +96,175 @@ impl RestMarketDataAdapter for Ftx {
 
         let rest::ResponseResult::Markets(markets) = result;
 
-        Ok(markets
+        let markets: Box<[Market]> = markets
             .iter()
             .filter_map(|m| {
                 <Market as TryFrom<&'_ rest::MarketInfo<'_>>>::try_from(m.borrow()).ok()
             })
-            .collect())
+            .collect();
+        let mut scales = self.scales.borrow_mut();
+        for market in markets.iter() {
+            if let Some(market_scales) = market.scales() {
+                scales.insert(Box::from(market.native_symbol.as_str()), market_scales);
+            }
+        }
+
+        Ok(markets)
     }
 
+    /// Fetches the checksummed depth of the orderbook
     async fn fetch_orderbook_snapshot(
         &self,
-        _symbol: &str,
+        symbol: &str,
     ) -> Result<PlainOrderbook<f64>, MarketDataError> {
-        Ok(PlainOrderbook::<f64>::new())
+        let client: surf::Client = surf::Config::new()
+            .set_base_url(Url::parse("https://ftx.com").map_err(MarketDataError::with_source)?)
+            .set_timeout(Some(Duration::from_secs(5)))
+            .try_into()
+            .map_err(MarketDataError::with_source)?;
+
+        rate_limit::acquire(ExchangeId::Ftx, Endpoint::Orderbook).await;
+        let mut res = client
+            .get(format!(
+                "/api/markets/{symbol}/orderbook?depth={CHECKSUM_DEPTH}"
+            ))
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let snapshot = serde_json::from_slice::<rest::OrderbookResponse>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?
+            .result;
+
+        let (mut bids, mut asks) = (snapshot.bids, snapshot.asks);
+        if let Some(scales) = self.scales.borrow().get(symbol) {
+            bids.round_to(scales);
+            asks.round_to(scales);
+        }
+        let mut orderbook = PlainOrderbook::<f64>::with_capacity(CHECKSUM_DEPTH);
+        orderbook.update(&bids, &asks);
+
+        Ok(orderbook)
+    }
+
+    /// Fetches funding rate and open interest of the future, spot markets
+    /// have none
+    async fn fetch_funding(&self, market: &str) -> Result<Box<[MarketEvent]>, MarketDataError> {
+        if market.contains('/') {
+            return Ok(Box::new([]));
+        }
+
+        let client: surf::Client = surf::Config::new()
+            .set_base_url(Url::parse("https://ftx.com").map_err(MarketDataError::with_source)?)
+            .set_timeout(Some(Duration::from_secs(5)))
+            .try_into()
+            .map_err(MarketDataError::with_source)?;
+
+        rate_limit::acquire(ExchangeId::Ftx, Endpoint::Funding).await;
+        let mut res = client
+            .get(format!("/api/futures/{market}/stats"))
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let stats = serde_json::from_slice::<rest::FutureStatsResponse>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?
+            .result;
+        let time = Utc::now();
+
+        let mut events = vec![MarketEvent::open_interest(
+            Box::from(market),
+            OpenInterest {
+                open_interest: stats.open_interest,
+                time,
+            },
+        )];
+        if let Some(rate) = stats.next_funding_rate {
+            events.push(MarketEvent::funding_rate(
+                Box::from(market),
+                FundingRate {
+                    rate,
+                    next_funding_time: stats.next_funding_time,
+                    time,
+                },
+            ));
+        }
+
+        Ok(events.into_boxed_slice())
+    }
+
+    async fn fetch_server_time(&self) -> Result<Option<DateTime<Utc>>, MarketDataError> {
+        let client: surf::Client = surf::Config::new()
+            .set_base_url(Url::parse("https://ftx.com").map_err(MarketDataError::with_source)?)
+            .set_timeout(Some(Duration::from_secs(5)))
+            .try_into()
+            .map_err(MarketDataError::with_source)?;
+
+        rate_limit::acquire(ExchangeId::Ftx, Endpoint::Time).await;
+        let mut res = client
+            .get("/api/time")
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let time = serde_json::from_slice::<rest::TimeResponse>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?;
+
+        Ok(Some(time.result))
     }
 }
 
 impl WsMarketDataAdapter for Ftx {
-    fn throughput_metrics(&self) -> &Throughput<StdInstant, RefCell<metered::common::TxPerSec>> {
-        &self.metrics.throughput
+    fn metrics(&self) -> &AdapterMetrics {
+        &self.metrics
+    }
+
+    fn set_book_events(&mut self, book_events: BookEvents) {
+        self.book_events = BookEmitter::new(book_events);
     }
 
     fn ws_url(&self) -> Box<str> {
         Box::from("wss://ftx.com/ws")
     }
 
+    fn seeds_from_snapshot(&self) -> bool {
+        true
+    }
+
+    fn ping_msg(&self) -> Option<String> {
+        Some(json!({"op": "ping"}).to_string())
+    }
+
     fn subscribe_msgs(&mut self, markets: &[&str]) -> Box<[String]> {
-        markets
-            .iter()
-            .map(|market| {
-                info!("Subscribing for {market}");
-
-                [
-                    json!({"op": "subscribe", "channel": "orderbook", "market": market})
-                        .to_string(),
-                    json!({"op": "subscribe", "channel": "trades", "market": market}).to_string(),
-                ]
-            })
-            .flatten()
-            .collect()
+        info!("Subscribing for {markets:?}");
+
+        channel_msgs("subscribe", markets)
+    }
+
+    fn unsubscribe_msgs(&mut self, markets: &[&str]) -> Option<Box<[String]>> {
+        info!("Unsubscribing {markets:?}");
+
+        Some(channel_msgs("unsubscribe", markets))
+    }
+
+    fn resync_msgs(&self, market: &str) -> Option<Box<[String]>> {
+        Some(Box::new([
+            json!({"op": "unsubscribe", "channel": "orderbook", "market": market}).to_string(),
+            json!({"op": "subscribe", "channel": "orderbook", "market": market}).to_string(),
+        ]))
+    }
+
+    fn take_pending_event(&self) -> Option<MarketEvent> {
+        self.liquidations.take()
     }
 
     /// Processes Websocket text message
@@ -101,23 +273,56 @@ impl WsMarketDataAdapter for Ftx {
         msg: &str,
         markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
     ) -> Result<Option<MarketEvent>, MarketDataError> {
-        let ws_msg = serde_json::from_slice::<ws::WsMsg>(msg.as_bytes());
+        let mut json = self.json.borrow_mut();
+        let ws_msg = json.parse::<ws::WsMsg>(msg);
 
         match ws_msg {
-            Ok(ws_msg) => Ok(process_market_ws_message(ws_msg, markets)?),
+            Ok(ws_msg) => {
+                let scales = ws_msg
+                    .market
+                    .and_then(|market| self.scales.borrow().get(market).copied());
+                Ok(process_market_ws_message(
+                    ws_msg,
+                    markets,
+                    scales,
+                    &self.books,
+                    &self.metrics,
+                    &self.book_events,
+                    &self.liquidations,
+                )?)
+            }
+            Err(_) if is_pong(msg) => Ok(None),
             Err(e) => {
                 error!("Failed to parse {msg}");
 
-                Err(MarketDataError::with_source(e))
+                Err(e)
             }
         }
     }
 }
 
+/// Returns `op` messages of the orderbook and trades channels of the markets
+fn channel_msgs(op: &str, markets: &[&str]) -> Box<[String]> {
+    markets
+        .iter()
+        .flat_map(|market| {
+            [
+                json!({"op": op, "channel": "orderbook", "market": market}).to_string(),
+                json!({"op": op, "channel": "trades", "market": market}).to_string(),
+            ]
+        })
+        .collect()
+}
+
 #[inline]
 fn process_market_ws_message(
     mut ws_msg: ws::WsMsg,
     markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    scales: Option<MarketScales>,
+    books: &RefCell<HashMap<Box<str>, PlainOrderbook<Fixed>>>,
+    metrics: &AdapterMetrics,
+    book_events: &BookEmitter,
+    liquidations: &RefCell<Option<MarketEvent>>,
 ) -> Result<Option<MarketEvent>, MarketDataError> {
     let data = ws_msg.data.to_mut();
     let market = match ws_msg.market {
@@ -129,9 +334,28 @@ fn process_market_ws_message(
         ws::Data::Trades(trades) => {
             trace!("got trades = {trades:?}");
 
+            // Emitted after the trades they are part of
+            let liquidated: Box<[_]> = trades
+                .iter()
+                .filter(|trade| trade.liquidation)
+                .filter_map(|trade| Liquidation::try_from(trade).ok())
+                .collect();
+            if !liquidated.is_empty() {
+                liquidations.replace(Some(MarketEvent::liquidation(
+                    Box::from(market),
+                    liquidated,
+                )));
+            }
+
             let trades: Vec<_> = trades
                 .iter()
                 .filter_map(|trade| botvana::market::trade::Trade::try_from(trade).ok())
+                .map(|mut trade| {
+                    if let Some(scales) = &scales {
+                        trade.round_to(scales);
+                    }
+                    trade
+                })
                 .collect();
 
             Ok(Some(MarketEvent::trades(
@@ -140,25 +364,64 @@ fn process_market_ws_message(
             )))
         }
         ws::Data::Orderbook(ref mut orderbook_msg) => {
-            let orderbook = match orderbook_msg.action {
+            let levels = match orderbook_msg.action {
                 "partial" => {
-                    let orderbook = PlainOrderbook {
+                    let mut orderbook = PlainOrderbook {
                         bids: PriceLevelsVec::from_tuples_vec_unsorted(&mut orderbook_msg.bids),
                         asks: PriceLevelsVec::from_tuples_vec_unsorted(&mut orderbook_msg.asks),
                         time: orderbook_msg.time,
+                        max_depth: None,
                     };
+                    if let Some(scales) = &scales {
+                        let fixed = orderbook.to_fixed(scales);
+                        orderbook = fixed.to_f64(scales);
+                        books.borrow_mut().insert(Box::from(market), fixed);
+                    }
                     info!("{market} orderbook = {orderbook:?}");
-                    markets.insert(Box::from(market), orderbook.clone());
-                    orderbook
+                    // Books seeded from REST snapshots have no timestamp,
+                    // replacing them isn't a resync
+                    let previous = markets.insert(Box::from(market), orderbook);
+                    if let Some(previous) = previous {
+                        if previous.time > 0.0 {
+                            metrics.record_book_resync();
+                        }
+                        pool::put_levels(previous.bids);
+                        pool::put_levels(previous.asks);
+                    }
+                    None
                 }
                 "update" => {
-                    let orderbook = markets.get_mut(market).unwrap();
-                    orderbook.update_with_timestamp(
-                        &PriceLevelsVec::from_tuples_vec(&orderbook_msg.bids),
-                        &PriceLevelsVec::from_tuples_vec(&orderbook_msg.asks),
-                        orderbook_msg.time,
-                    );
-                    orderbook.clone()
+                    // Dropped book waits for the partial after resubscribing
+                    let orderbook = match markets.get_mut(market) {
+                        Some(orderbook) => orderbook,
+                        None => {
+                            books.borrow_mut().remove(market);
+                            return Ok(None);
+                        }
+                    };
+                    let (mut bids, mut asks) = pool::with(|pool| (pool.levels(), pool.levels()));
+                    bids.extend_from_tuples(&orderbook_msg.bids);
+                    asks.extend_from_tuples(&orderbook_msg.asks);
+                    if let Some(scales) = &scales {
+                        let (fixed_bids, fixed_asks) =
+                            (bids.to_fixed(scales), asks.to_fixed(scales));
+                        let mut books = books.borrow_mut();
+                        // Books seeded from REST snapshots have no fixed book yet
+                        if !books.contains_key(market) {
+                            books.insert(Box::from(market), orderbook.to_fixed(scales));
+                        }
+                        books.get_mut(market).unwrap().update_with_timestamp(
+                            &fixed_bids,
+                            &fixed_asks,
+                            orderbook_msg.time,
+                        );
+                        pool::put_levels(bids);
+                        pool::put_levels(asks);
+                        bids = fixed_bids.to_f64(scales);
+                        asks = fixed_asks.to_f64(scales);
+                    }
+                    orderbook.update_with_timestamp(&bids, &asks, orderbook_msg.time);
+                    Some((bids, asks))
                 }
                 action => {
                     return Err(MarketDataError::with_source(UnknownVariantError {
@@ -167,10 +430,47 @@ fn process_market_ws_message(
                 }
             };
 
-            Ok(Some(MarketEvent::orderbook_update(
-                Box::from(market),
-                Box::new(orderbook),
-            )))
+            // Both actions leave the book in place
+            let computed = match (&scales, books.borrow().get(market)) {
+                // Fixed books aren't limited, so they're verified at any depth
+                (Some(scales), Some(book)) => Some(checksum(
+                    book,
+                    |price| scales.price.to_f64(price),
+                    |size| scales.size.to_f64(size),
+                )),
+                _ => {
+                    let orderbook = &markets[market];
+                    // Books limited below the checksummed depth can't be verified
+                    match orderbook.max_depth.map_or(false, |d| d < CHECKSUM_DEPTH) {
+                        true => None,
+                        false => Some(checksum(orderbook, |price| price, |size| size)),
+                    }
+                }
+            };
+            if let Some(computed) = computed.filter(|c| *c != orderbook_msg.checksum) {
+                metrics.record_checksum_failure();
+                markets.remove(market);
+                books.borrow_mut().remove(market);
+
+                return Err(MarketDataError::with_source(BookOutOfSync {
+                    market: Box::from(market),
+                    reason: format!(
+                        "checksum {} does not match computed {computed}",
+                        orderbook_msg.checksum
+                    ),
+                }));
+            }
+
+            let orderbook = &markets[market];
+            Ok(match levels {
+                Some((bids, asks)) => {
+                    let event = book_events.delta(Box::from(market), orderbook, &bids, &asks);
+                    pool::put_levels(bids);
+                    pool::put_levels(asks);
+                    event
+                }
+                None => book_events.event(Box::from(market), orderbook),
+            })
         }
         ws::Data::None(_) => {
             info!("none data");
@@ -179,3 +479,272 @@ fn process_market_ws_message(
         }
     }
 }
+
+/// Returns whether the message replies to the keepalive ping
+fn is_pong(msg: &str) -> bool {
+    serde_json::from_str::<ws::Status>(msg).map_or(false, |status| status.r#type == "pong")
+}
+
+/// Number of levels on each side included in the checksum
+const CHECKSUM_DEPTH: usize = 100;
+
+/// Computes FTX orderbook checksum: CRC32 of `bid_price:bid_size:ask_price:ask_size`
+/// of the best levels interleaved, continuing with the longer side once the
+/// other one runs out
+///
+/// Levels are formatted from their `f64` values given by `price` and `size`.
+fn checksum<T: Copy>(
+    orderbook: &PlainOrderbook<T>,
+    price: impl Fn(T) -> f64,
+    size: impl Fn(T) -> f64,
+) -> u32 {
+    let (bids, asks) = (&orderbook.bids, &orderbook.asks);
+    let mut bids = bids.price_vec.iter().zip(&bids.size_vec).rev();
+    let mut asks = asks.price_vec.iter().zip(&asks.size_vec);
+    let mut values = Vec::with_capacity(CHECKSUM_DEPTH * 4);
+
+    for _ in 0..CHECKSUM_DEPTH {
+        let (bid, ask) = (bids.next(), asks.next());
+        if bid.is_none() && ask.is_none() {
+            break;
+        }
+        for (p, s) in bid.into_iter().chain(ask) {
+            values.push(format_float(price(*p)));
+            values.push(format_float(size(*s)));
+        }
+    }
+
+    crc32fast::hash(values.join(":").as_bytes())
+}
+
+/// Formats the number the way FTX does for the checksum, like Python's
+/// `repr`: `1.0` for whole numbers and `1e-07` for small ones
+fn format_float(value: f64) -> String {
+    let scientific = format!("{value:e}");
+    let (mantissa, exp) = scientific.split_once('e').unwrap_or((&scientific, "0"));
+    let exp = exp.parse::<i32>().unwrap_or_default();
+
+    if (-4..16).contains(&exp) {
+        let plain = value.to_string();
+        match plain.contains('.') {
+            true => plain,
+            false => plain + ".0",
+        }
+    } else {
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{mantissa}e{sign}{:02}", exp.abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orderbook_msg(action: &str, checksum: u32) -> String {
+        format!(
+            r#"{{
+                "channel": "orderbook",
+                "market": "BTC-PERP",
+                "type": "{action}",
+                "data": {{
+                    "time": 1603264861.3111522,
+                    "checksum": {checksum},
+                    "bids": [[100.0, 1.5], [99.5, 2.0]],
+                    "asks": [[100.5, 0.00001], [101.0, 3.0], [102.0, 0.0000001]],
+                    "action": "{action}"
+                }}
+            }}"#
+        )
+    }
+
+    #[test]
+    fn test_process_ws_msg_pong() {
+        let ftx = Ftx::default();
+
+        assert!(ftx
+            .process_ws_msg(r#"{"type": "pong"}"#, &mut HashMap::new())
+            .unwrap()
+            .is_none());
+        assert!(ftx
+            .process_ws_msg(r#"{"type": "error"}"#, &mut HashMap::new())
+            .is_err());
+    }
+
+    #[test]
+    fn test_process_ws_msg_liquidation_trades() {
+        let ftx = Ftx::default();
+        let msg = r#"{
+            "channel": "trades",
+            "market": "BTC-PERP",
+            "type": "update",
+            "data": [{
+                "id": 1,
+                "price": 60000.0,
+                "size": 0.5,
+                "side": "buy",
+                "liquidation": false,
+                "time": "2021-10-20T07:08:49.436257+00:00"
+            }, {
+                "id": 2,
+                "price": 59990.0,
+                "size": 2.0,
+                "side": "sell",
+                "liquidation": true,
+                "time": "2021-10-20T07:08:49.436257+00:00"
+            }]
+        }"#;
+
+        match ftx.process_ws_msg(msg, &mut HashMap::new()).unwrap() {
+            Some(MarketEvent {
+                r#type: MarketEventType::Trades(_, trades),
+                ..
+            }) => assert_eq!(2, trades.len()),
+            other => panic!("unexpected event {other:?}"),
+        }
+        match ftx.take_pending_event().map(|event| event.r#type) {
+            Some(MarketEventType::Liquidation(market, liquidations)) => {
+                use botvana::market::derivatives::PositionSide;
+
+                assert_eq!("BTC-PERP", &*market);
+                assert_eq!(1, liquidations.len());
+                assert_eq!(PositionSide::Long, liquidations[0].position);
+                assert_eq!(2.0, liquidations[0].size);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+        assert!(ftx.take_pending_event().is_none());
+    }
+
+    #[test]
+    fn test_format_float() {
+        assert_eq!("1.0", format_float(1.0));
+        assert_eq!("100.5", format_float(100.5));
+        assert_eq!("0.0001", format_float(0.0001));
+        assert_eq!("1e-05", format_float(0.00001));
+        assert_eq!("1.5e-07", format_float(0.00000015));
+        assert_eq!("1e+16", format_float(1e16));
+    }
+
+    #[test]
+    fn test_checksum() {
+        let mut markets = HashMap::new();
+        let ftx = Ftx::default();
+
+        // zlib.crc32(b"100.0:1.5:100.5:1e-05:99.5:2.0:101.0:3.0:102.0:1e-07")
+        let event = ftx
+            .process_ws_msg(&orderbook_msg("partial", 26574315), &mut markets)
+            .unwrap();
+
+        assert!(event.is_some());
+        assert_eq!(0, ftx.metrics.checksum_failures());
+    }
+
+    #[test]
+    fn test_partial_replaces_seeded_book() {
+        let mut markets = HashMap::new();
+        let ftx = Ftx::default();
+        assert!(ftx.seeds_from_snapshot());
+
+        // Seeded from the REST snapshot, without a timestamp
+        let mut seeded = PlainOrderbook::new();
+        seeded.update(
+            &PriceLevelsVec::from_tuples_vec(&[(90.0, 1.0)]),
+            &PriceLevelsVec::from_tuples_vec(&[(110.0, 1.0)]),
+        );
+        markets.insert(Box::from("BTC-PERP"), seeded);
+
+        ftx.process_ws_msg(&orderbook_msg("partial", 26574315), &mut markets)
+            .unwrap();
+        assert_eq!(vec![99.5, 100.0], markets["BTC-PERP"].bids.price_vec);
+        assert_eq!(0, ftx.metrics.book_resyncs());
+
+        // A partial replacing a streamed book is a resync
+        ftx.process_ws_msg(&orderbook_msg("partial", 26574315), &mut markets)
+            .unwrap();
+        assert_eq!(1, ftx.metrics.book_resyncs());
+    }
+
+    #[test]
+    fn test_checksum_failure_drops_book() {
+        let mut markets = HashMap::new();
+        let ftx = Ftx::default();
+
+        let err = ftx
+            .process_ws_msg(&orderbook_msg("partial", 1), &mut markets)
+            .unwrap_err();
+
+        assert_eq!(Some("BTC-PERP"), err.out_of_sync_market());
+        assert_eq!(1, ftx.metrics.checksum_failures());
+        assert!(!markets.contains_key("BTC-PERP"));
+
+        // Updates are ignored until the next partial
+        assert!(ftx
+            .process_ws_msg(&orderbook_msg("update", 1), &mut markets)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_levels_rounded_to_increments() {
+        use botvana::market::fixed::Scale;
+
+        let mut markets = HashMap::new();
+        let ftx = Ftx::default();
+        ftx.scales.borrow_mut().insert(
+            Box::from("BTC-PERP"),
+            MarketScales {
+                price: Scale::new(0.5).unwrap(),
+                size: Scale::new(0.0000001).unwrap(),
+            },
+        );
+
+        // Rounding levels already on the increments keeps the checksum
+        ftx.process_ws_msg(&orderbook_msg("partial", 26574315), &mut markets)
+            .unwrap();
+        let msg = r#"{
+            "channel": "orderbook",
+            "market": "BTC-PERP",
+            "type": "update",
+            "data": {
+                "time": 1603264862.0,
+                "checksum": 2354975681,
+                "bids": [[100.00000000001, 0.0]],
+                "asks": [],
+                "action": "update"
+            }
+        }"#;
+        // zlib.crc32(b"99.5:2.0:100.5:1e-05:101.0:3.0:102.0:1e-07")
+        ftx.process_ws_msg(msg, &mut markets).unwrap();
+
+        // Level removed although FTX formatted its price differently
+        assert_eq!(vec![99.5], markets["BTC-PERP"].bids.price_vec);
+    }
+
+    #[test]
+    fn test_fixed_book_verified_past_max_depth() {
+        use botvana::market::fixed::Scale;
+
+        let mut markets = HashMap::new();
+        let ftx = Ftx::default();
+        let scales = MarketScales {
+            price: Scale::new(0.5).unwrap(),
+            size: Scale::new(0.0000001).unwrap(),
+        };
+        ftx.scales
+            .borrow_mut()
+            .insert(Box::from("BTC-PERP"), scales);
+
+        ftx.process_ws_msg(&orderbook_msg("partial", 26574315), &mut markets)
+            .unwrap();
+        assert_eq!(Fixed(200), ftx.books.borrow()["BTC-PERP"].bids.price_vec[1]);
+
+        // Limiting the emitted book leaves the fixed one checksummed
+        markets.get_mut("BTC-PERP").unwrap().set_max_depth(Some(1));
+        let err = ftx
+            .process_ws_msg(&orderbook_msg("update", 1), &mut markets)
+            .unwrap_err();
+
+        assert_eq!(Some("BTC-PERP"), err.out_of_sync_market());
+        assert!(!ftx.books.borrow().contains_key("BTC-PERP"));
+    }
+}
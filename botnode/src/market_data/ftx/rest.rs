@@ -1,8 +1,9 @@
 use std::borrow::Cow;
 
-use serde::Deserialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer};
 
-use botvana::exchange::ExchangeId;
+use botvana::{exchange::ExchangeId, market::orderbook::PriceLevelsVec};
 
 #[derive(Debug, Clone, PartialEq, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -64,7 +65,10 @@ impl<'a> TryFrom<&'a MarketInfo<'a>> for botvana::market::Market {
                     .ok_or_else(|| "Missing quote currency".to_string())?
                     .to_string(),
             }),
-            "future" => botvana::market::MarketType::Futures,
+            // `/markets` doesn't list expiries of dated futures
+            "future" => botvana::market::MarketType::Futures(botvana::market::FuturesMarket {
+                expires_at: None,
+            }),
             _ => return Err(format!("Invalid market type: {}", market.r#type)),
         };
 
@@ -85,3 +89,96 @@ pub enum MarketType {
     Spot,
     Future,
 }
+
+/// Response of `/api/markets/{market}/orderbook`
+#[derive(Debug, Deserialize)]
+pub struct OrderbookResponse {
+    pub success: bool,
+    pub result: OrderbookSnapshot,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrderbookSnapshot {
+    #[serde(deserialize_with = "deserialize_into_price_levels_vec")]
+    pub bids: PriceLevelsVec<f64>,
+    #[serde(deserialize_with = "deserialize_into_price_levels_vec")]
+    pub asks: PriceLevelsVec<f64>,
+}
+
+/// Response of `/api/time`
+#[derive(Debug, Deserialize)]
+pub struct TimeResponse {
+    pub result: DateTime<Utc>,
+}
+
+/// Response of `/api/futures/{future}/stats`
+#[derive(Debug, Deserialize)]
+pub struct FutureStatsResponse {
+    pub success: bool,
+    pub result: FutureStats,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FutureStats {
+    /// Set on perpetual futures only
+    pub next_funding_rate: Option<f64>,
+    pub next_funding_time: Option<DateTime<Utc>>,
+    pub open_interest: f64,
+}
+
+fn deserialize_into_price_levels_vec<'de, D>(
+    deserializer: D,
+) -> Result<PriceLevelsVec<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut levels = Vec::<(f64, f64)>::deserialize(deserializer)?;
+
+    Ok(PriceLevelsVec::from_tuples_vec_unsorted(&mut levels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_orderbook_snapshot_parse() {
+        let json = r#"{
+            "success": true,
+            "result": {
+                "asks": [[4114.25, 6.263], [4114.5, 1.0]],
+                "bids": [[4112.25, 49.29], [4112.0, 2.5]]
+            }
+        }"#;
+
+        let response: OrderbookResponse = serde_json::from_str(json).unwrap();
+        assert!(response.success);
+        assert_eq!(Some(&4112.25), response.result.bids.price_vec.last());
+        assert_eq!(Some(&4114.25), response.result.asks.price_vec.first());
+    }
+
+    #[test]
+    fn test_future_stats_parse() {
+        let json = r#"{
+            "success": true,
+            "result": {
+                "volume": 1000.23,
+                "nextFundingRate": 0.00025,
+                "nextFundingTime": "2019-03-29T03:00:00+00:00",
+                "openInterest": 21124.583
+            }
+        }"#;
+
+        let response: FutureStatsResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(Some(0.00025), response.result.next_funding_rate);
+        assert_eq!(
+            Some(1553828400),
+            response
+                .result
+                .next_funding_time
+                .map(|time| time.timestamp())
+        );
+        assert_eq!(21124.583, response.result.open_interest);
+    }
+}
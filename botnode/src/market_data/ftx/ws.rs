@@ -13,6 +13,13 @@ pub struct WsMsg<'a> {
     pub data: Cow<'a, Data<'a>>,
 }
 
+/// Message without data, e.g. `{"type": "pong"}`
+#[derive(Debug, Deserialize)]
+pub struct Status<'a> {
+    #[serde(borrow)]
+    pub r#type: &'a str,
+}
+
 /// Data in the websocket message
 #[derive(Debug, Clone, PartialEq, Deserialize)]
 #[serde(untagged)]
@@ -28,7 +35,8 @@ pub enum Data<'a> {
 #[derive(Debug, Clone, PartialEq, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OrderbookMsg<'a> {
-    //pub checksum: i32,
+    /// CRC32 of the top 100 levels of the book after applying the message
+    pub checksum: u32,
     pub time: f64,
     pub bids: Box<[(f64, f64)]>,
     pub asks: Box<[(f64, f64)]>,
@@ -46,6 +54,29 @@ pub struct Trade<'a> {
     pub time: &'a str,
 }
 
+impl<'a> TryFrom<&Trade<'a>> for botvana::market::derivatives::Liquidation {
+    type Error = String;
+
+    fn try_from(trade: &Trade<'a>) -> Result<Self, Self::Error> {
+        use botvana::market::derivatives::PositionSide;
+
+        Ok(Self {
+            // Side of the order taking over the liquidated position
+            position: match trade.side {
+                "sell" => PositionSide::Long,
+                "buy" => PositionSide::Short,
+                other => return Err(format!("invalid side: {other}")),
+            },
+            price: trade.price,
+            size: trade.size,
+            time: trade
+                .time
+                .parse()
+                .map_err(|_| format!("error parsing: {}", trade.time))?,
+        })
+    }
+}
+
 impl<'a> TryFrom<&Trade<'a>> for botvana::market::trade::Trade {
     type Error = String;
 
@@ -53,6 +84,7 @@ impl<'a> TryFrom<&Trade<'a>> for botvana::market::trade::Trade {
         Ok(Self {
             price: trade.price,
             size: trade.size,
+            side: trade.side.parse().ok(),
             received_at: std::time::Instant::now(),
             time: trade
                 .time
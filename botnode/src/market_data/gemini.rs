@@ -0,0 +1,356 @@
+//! Gemini market data adapter
+//!
+//! Markets come from `/v1/symbols` and the details of every symbol. The v2
+//! market data Websocket API streams the `l2` subscription: the first
+//! `l2_updates` of a symbol lists its whole book and is applied as snapshot,
+//! later ones only the changed levels. Gemini doesn't number the updates,
+//! [`DiffSync`] orders them by arrival. Trades arrive as `trade` messages.
+
+pub(crate) mod rest;
+pub(crate) mod ws;
+
+use std::cell::Cell;
+
+use chrono::TimeZone;
+
+use super::prelude::*;
+use crate::prelude::*;
+use botvana::exchange::ExchangeId;
+
+#[derive(Debug)]
+pub struct Gemini {
+    pub metrics: AdapterMetrics,
+    api_url: Box<str>,
+    ws_url: Box<str>,
+    /// Number of the last received orderbook message
+    book_msgs: Cell<u64>,
+    diffs: DiffSync,
+    book_events: BookEmitter,
+}
+
+impl Default for Gemini {
+    fn default() -> Self {
+        Gemini {
+            api_url: Box::from("https://api.gemini.com"),
+            ws_url: Box::from("wss://api.gemini.com/v2/marketdata"),
+            metrics: AdapterMetrics::default(),
+            book_msgs: Cell::new(0),
+            diffs: DiffSync::new(),
+            book_events: BookEmitter::default(),
+        }
+    }
+}
+
+impl Gemini {
+    fn client(&self) -> Result<surf::Client, MarketDataError> {
+        surf::Config::new()
+            .set_base_url(Url::parse(&self.api_url).map_err(MarketDataError::with_source)?)
+            .set_timeout(Some(Duration::from_secs(5)))
+            .try_into()
+            .map_err(MarketDataError::with_source)
+    }
+
+    async fn get_body(
+        &self,
+        client: &surf::Client,
+        endpoint: Endpoint,
+        path: &str,
+    ) -> Result<String, MarketDataError> {
+        rate_limit::acquire(ExchangeId::Gemini, endpoint).await;
+        let mut res = client
+            .get(path)
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        res.body_string().await.map_err(MarketDataError::surf_error)
+    }
+}
+
+#[async_trait(?Send)]
+impl RestMarketDataAdapter for Gemini {
+    fn name(&self) -> &'static str {
+        "gemini-rest"
+    }
+
+    fn exchange(&self) -> ExchangeId {
+        ExchangeId::Gemini
+    }
+
+    /// Fetches open markets on Gemini
+    async fn fetch_markets(&self) -> Result<Box<[Market]>, MarketDataError> {
+        let client = self.client()?;
+        let body = self
+            .get_body(&client, Endpoint::Markets, "/v1/symbols")
+            .await?;
+        let symbols = serde_json::from_slice::<Vec<&str>>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?;
+
+        let mut markets = Vec::with_capacity(symbols.len());
+        for symbol in symbols {
+            let body = self
+                .get_body(
+                    &client,
+                    Endpoint::Markets,
+                    &format!("/v1/symbols/details/{symbol}"),
+                )
+                .await?;
+            let details = serde_json::from_slice::<rest::SymbolDetails>(body.as_bytes())
+                .map_err(MarketDataError::with_source)?;
+
+            if details.status == "open" {
+                if let Ok(market) = Market::try_from(&details) {
+                    markets.push(market);
+                }
+            }
+        }
+
+        debug!("{} markets on Gemini", markets.len());
+
+        Ok(markets.into_boxed_slice())
+    }
+
+    async fn fetch_orderbook_snapshot(
+        &self,
+        symbol: &str,
+    ) -> Result<PlainOrderbook<f64>, MarketDataError> {
+        let body = self
+            .get_body(
+                &self.client()?,
+                Endpoint::Orderbook,
+                &format!(
+                    "/v1/book/{}?limit_bids=0&limit_asks=0",
+                    native_symbol(symbol)
+                ),
+            )
+            .await?;
+        let snapshot = serde_json::from_slice::<rest::OrderbookSnapshot>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?;
+
+        Ok(PlainOrderbook {
+            bids: snapshot.bids,
+            asks: snapshot.asks,
+            time: Utc::now().timestamp_millis() as f64,
+            max_depth: None,
+        })
+    }
+}
+
+impl WsMarketDataAdapter for Gemini {
+    fn metrics(&self) -> &AdapterMetrics {
+        &self.metrics
+    }
+
+    fn set_book_events(&mut self, book_events: BookEvents) {
+        self.book_events = BookEmitter::new(book_events);
+    }
+
+    fn sequences(&self) -> Option<&SequenceTracker> {
+        Some(self.diffs.sequences())
+    }
+
+    fn ws_url(&self) -> Box<str> {
+        self.ws_url.clone()
+    }
+
+    fn subscribe_msgs(&mut self, markets: &[&str]) -> Box<[String]> {
+        info!("Subscribing for {markets:?}");
+
+        Box::new([subscriptions_msg("subscribe", markets)])
+    }
+
+    fn unsubscribe_msgs(&mut self, markets: &[&str]) -> Option<Box<[String]>> {
+        info!("Unsubscribing {markets:?}");
+
+        Some(Box::new([subscriptions_msg("unsubscribe", markets)]))
+    }
+
+    fn process_ws_msg(
+        &self,
+        msg: &str,
+        markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    ) -> Result<Option<MarketEvent>, MarketDataError> {
+        trace!("got ws_msg = {msg:?}");
+
+        match serde_json::from_slice::<ws::WsMsg>(msg.as_bytes()) {
+            Ok(ws_msg) => self.process_public_ws_message(ws_msg, markets),
+            Err(e) => {
+                error!("Error parsing ws_msg: {msg}");
+
+                Err(MarketDataError::with_source(e))
+            }
+        }
+    }
+}
+
+impl Gemini {
+    #[inline]
+    fn process_public_ws_message(
+        &self,
+        ws_msg: ws::WsMsg,
+        markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    ) -> Result<Option<MarketEvent>, MarketDataError> {
+        match ws_msg {
+            ws::WsMsg::L2Updates(mut updates) => {
+                let symbol = match internal_symbol(markets, updates.symbol) {
+                    Some(symbol) => symbol,
+                    None => {
+                        warn!("No symbol mapping found for {}", updates.symbol);
+                        return Ok(None);
+                    }
+                };
+                let msg_idx = self.book_msgs.get() + 1;
+                self.book_msgs.set(msg_idx);
+                let time = Utc::now().timestamp_millis() as f64;
+                let bids = PriceLevelsVec::from_tuples_vec_unsorted(&mut updates.changes.bids);
+                let asks = PriceLevelsVec::from_tuples_vec_unsorted(&mut updates.changes.asks);
+
+                // Symbols are synced from scratch after every subscription
+                if self.diffs.sequences().last_update_id(&symbol).is_none() {
+                    let orderbook = PlainOrderbook {
+                        bids,
+                        asks,
+                        time,
+                        max_depth: None,
+                    };
+
+                    return self.diffs.apply_snapshot(
+                        symbol,
+                        msg_idx,
+                        orderbook,
+                        markets,
+                        &self.book_events,
+                    );
+                }
+
+                self.diffs.apply_diff(
+                    symbol,
+                    BookDiff {
+                        first_update_id: None,
+                        last_update_id: msg_idx,
+                        time,
+                        bids,
+                        asks,
+                    },
+                    markets,
+                    &self.book_events,
+                )
+            }
+            ws::WsMsg::Trade(trade) => {
+                let symbol = internal_symbol(markets, trade.symbol)
+                    .unwrap_or_else(|| Box::from(trade.symbol));
+                let trade = botvana::market::trade::Trade::new(
+                    trade
+                        .price
+                        .parse::<f64>()
+                        .map_err(MarketDataError::with_source)?,
+                    trade
+                        .quantity
+                        .parse::<f64>()
+                        .map_err(MarketDataError::with_source)?,
+                    Utc.timestamp_millis(trade.timestamp),
+                );
+
+                Ok(Some(MarketEvent::trades(symbol, Box::new([trade]))))
+            }
+            ws::WsMsg::Heartbeat | ws::WsMsg::Other => Ok(None),
+        }
+    }
+}
+
+/// Converts market name to Gemini symbol, `BTC/USD` to `BTCUSD`
+fn native_symbol(market: &str) -> String {
+    market.to_uppercase().replace('/', "")
+}
+
+/// Returns message of given type for the l2 subscription of the markets
+fn subscriptions_msg(r#type: &str, markets: &[&str]) -> String {
+    let symbols: Vec<_> = markets.iter().map(|market| native_symbol(market)).collect();
+
+    json!({
+        "type": r#type,
+        "subscriptions": [{"name": "l2", "symbols": symbols}]
+    })
+    .to_string()
+}
+
+/// Returns the market name Gemini symbol was subscribed as
+fn internal_symbol(
+    markets: &HashMap<Box<str>, PlainOrderbook<f64>>,
+    symbol: &str,
+) -> Option<Box<str>> {
+    markets.keys().find(|k| native_symbol(k) == symbol).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_ws_msg_snapshot_and_changes() {
+        let gemini = Gemini::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTC/USD"), PlainOrderbook::new());
+
+        let snapshot = r#"{
+            "type": "l2_updates",
+            "symbol": "BTCUSD",
+            "changes": [
+                ["buy", "9122.04", "0.5"],
+                ["buy", "9121.5", "1.0"],
+                ["sell", "9122.07", "0.98942292"]
+            ],
+            "trades": [],
+            "auction_events": []
+        }"#;
+        let event = gemini.process_ws_msg(snapshot, &mut markets).unwrap();
+        assert!(event.is_some());
+        assert_eq!(vec![9121.5, 9122.04], markets["BTC/USD"].bids.price_vec);
+
+        let changes = r#"{
+            "type": "l2_updates",
+            "symbol": "BTCUSD",
+            "changes": [["buy", "9122.04", "0"], ["sell", "9122.5", "2.0"]]
+        }"#;
+        match gemini
+            .process_ws_msg(changes, &mut markets)
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::OrderbookUpdate(market, orderbook)) => {
+                assert_eq!("BTC/USD", &*market);
+                assert_eq!(vec![9121.5], orderbook.bids.price_vec);
+                assert_eq!(vec![9122.07, 9122.5], orderbook.asks.price_vec);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_process_ws_msg_trade() {
+        let gemini = Gemini::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTC/USD"), PlainOrderbook::new());
+
+        let msg = r#"{
+            "type": "trade",
+            "symbol": "BTCUSD",
+            "event_id": 3575573053,
+            "timestamp": 1606506069473,
+            "price": "17366.97",
+            "quantity": "0.00548411",
+            "side": "buy"
+        }"#;
+
+        match gemini
+            .process_ws_msg(msg, &mut markets)
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::Trades(market, trades)) => {
+                assert_eq!("BTC/USD", &*market);
+                assert_eq!(17366.97, trades[0].price);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+}
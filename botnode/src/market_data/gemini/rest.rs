@@ -0,0 +1,108 @@
+use serde::{Deserialize, Deserializer};
+
+use botvana::{exchange::ExchangeId, market::orderbook::*};
+
+/// Symbol returned by `/v1/symbols/details/{symbol}`
+#[derive(Debug, Deserialize)]
+pub struct SymbolDetails<'a> {
+    pub symbol: &'a str,
+    pub base_currency: &'a str,
+    pub quote_currency: &'a str,
+    /// Size increment
+    pub tick_size: f64,
+    pub quote_increment: f64,
+    /// `open`, `closed`, `cancel_only`, `post_only` or `limit_only`
+    pub status: &'a str,
+}
+
+impl<'a> TryFrom<&SymbolDetails<'a>> for botvana::market::Market {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(details: &SymbolDetails<'a>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            exchange: ExchangeId::Gemini,
+            name: format!("{}/{}", details.base_currency, details.quote_currency),
+            native_symbol: details.symbol.to_string(),
+            size_increment: details.tick_size,
+            price_increment: details.quote_increment,
+            r#type: botvana::market::MarketType::Spot(botvana::market::SpotMarket {
+                base: details.base_currency.to_string(),
+                quote: details.quote_currency.to_string(),
+            }),
+        })
+    }
+}
+
+/// Orderbook returned by `/v1/book/{symbol}`
+#[derive(Debug, Deserialize)]
+pub struct OrderbookSnapshot {
+    #[serde(deserialize_with = "deserialize_into_price_levels_vec")]
+    pub bids: PriceLevelsVec<f64>,
+    #[serde(deserialize_with = "deserialize_into_price_levels_vec")]
+    pub asks: PriceLevelsVec<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PriceLevel<'a> {
+    price: &'a str,
+    amount: &'a str,
+}
+
+fn deserialize_into_price_levels_vec<'de, D>(
+    deserializer: D,
+) -> Result<PriceLevelsVec<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let levels: Vec<PriceLevel<'de>> = Deserialize::deserialize(deserializer)?;
+    let mut levels = levels
+        .iter()
+        .map(|level| Ok((level.price.parse::<f64>()?, level.amount.parse::<f64>()?)))
+        .collect::<Result<Vec<_>, std::num::ParseFloatError>>()
+        .map_err(serde::de::Error::custom)?;
+
+    Ok(PriceLevelsVec::from_tuples_vec_unsorted(&mut levels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_symbol_details() {
+        let sample = r#"{
+            "symbol": "BTCUSD",
+            "base_currency": "BTC",
+            "quote_currency": "USD",
+            "tick_size": 1E-8,
+            "quote_increment": 0.01,
+            "min_order_size": "0.00001",
+            "status": "open",
+            "wrap_enabled": false
+        }"#;
+
+        let details = serde_json::from_str::<SymbolDetails>(sample).unwrap();
+        let market = botvana::market::Market::try_from(&details).unwrap();
+
+        assert_eq!("BTC/USD", market.name);
+        assert_eq!("BTCUSD", market.native_symbol);
+        assert_eq!(0.01, market.price_increment);
+        assert_eq!(0.00000001, market.size_increment);
+    }
+
+    #[test]
+    fn test_parse_orderbook_snapshot() {
+        let sample = r#"{
+            "bids": [
+                {"price": "3607.85", "amount": "6.643373", "timestamp": "1547147541"},
+                {"price": "3607.1", "amount": "1.5", "timestamp": "1547147541"}
+            ],
+            "asks": [{"price": "3607.86", "amount": "14.68205084", "timestamp": "1547147541"}]
+        }"#;
+
+        let snapshot = serde_json::from_str::<OrderbookSnapshot>(sample).unwrap();
+
+        assert_eq!(vec![3607.1, 3607.85], snapshot.bids.price_vec);
+        assert_eq!(vec![14.68205084], snapshot.asks.size_vec);
+    }
+}
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Deserializer};
+
+/// Gemini v2 market data Websocket message
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WsMsg<'a> {
+    #[serde(borrow)]
+    L2Updates(L2Updates<'a>),
+    #[serde(borrow)]
+    Trade(Trade<'a>),
+    Heartbeat,
+    /// Candles and other unsubscribed types
+    #[serde(other)]
+    Other,
+}
+
+/// Orderbook changes of the `l2` subscription, the first message of a symbol
+/// lists the whole book
+#[derive(Debug, Deserialize)]
+pub struct L2Updates<'a> {
+    pub symbol: &'a str,
+    #[serde(deserialize_with = "de_changes")]
+    pub changes: Changes,
+}
+
+/// Changed levels, size of zero removes the level
+#[derive(Debug, Default)]
+pub struct Changes {
+    pub bids: Vec<(f64, f64)>,
+    pub asks: Vec<(f64, f64)>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Trade<'a> {
+    pub symbol: &'a str,
+    pub price: &'a str,
+    pub quantity: &'a str,
+    /// Milliseconds timestamp
+    pub timestamp: i64,
+}
+
+/// Deserializes changes encoded as `["buy" | "sell", price, size]`
+fn de_changes<'de, D>(deserializer: D) -> Result<Changes, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let levels: Vec<(&'de str, &'de str, &'de str)> = Deserialize::deserialize(deserializer)?;
+    let mut changes = Changes::default();
+
+    for (side, price, size) in levels {
+        let level = (
+            price.parse::<f64>().map_err(serde::de::Error::custom)?,
+            size.parse::<f64>().map_err(serde::de::Error::custom)?,
+        );
+        match side {
+            "buy" => changes.bids.push(level),
+            "sell" => changes.asks.push(level),
+            other => return Err(serde::de::Error::unknown_variant(other, &["buy", "sell"])),
+        }
+    }
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_l2_updates() {
+        let msg = r#"{
+            "type": "l2_updates",
+            "symbol": "BTCUSD",
+            "changes": [
+                ["buy", "9122.04", "0.00121425"],
+                ["sell", "9122.07", "0.98942292"],
+                ["sell", "9122.1", "0"]
+            ],
+            "trades": [],
+            "auction_events": []
+        }"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::L2Updates(updates) => {
+                assert_eq!("BTCUSD", updates.symbol);
+                assert_eq!(vec![(9122.04, 0.00121425)], updates.changes.bids);
+                assert_eq!(
+                    vec![(9122.07, 0.98942292), (9122.1, 0.0)],
+                    updates.changes.asks
+                );
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_unknown_type() {
+        let msg = r#"{"type": "candles_1m_updates", "symbol": "BTCUSD", "changes": []}"#;
+
+        assert!(matches!(
+            serde_json::from_str::<WsMsg>(msg).unwrap(),
+            WsMsg::Other
+        ));
+    }
+}
@@ -0,0 +1,67 @@
+//! JSON parsing of the hot websocket messages
+//!
+//! Orderbook and trade messages of FTX and Binance are parsed through
+//! [`JsonBuffer`]. By default it's serde_json, with the `simd` feature it's
+//! simd-json, which parses in place: the message is copied into a buffer
+//! reused by every message of the connection so that parsing doesn't
+//! allocate. Strings of the parsed messages borrow from the buffer. Compare
+//! the parsers with `cargo bench --features simd --bench ws_parse_benchmark`.
+
+use serde::Deserialize;
+
+use crate::market_data::error::MarketDataError;
+
+/// Buffer of the message copy parsed in place
+#[derive(Debug, Default)]
+pub struct JsonBuffer(#[cfg(feature = "simd")] Vec<u8>);
+
+impl JsonBuffer {
+    /// Parses the message with simd-json
+    #[cfg(feature = "simd")]
+    pub fn parse<'a, T: Deserialize<'a>>(&'a mut self, msg: &str) -> Result<T, MarketDataError> {
+        self.0.clear();
+        self.0.extend_from_slice(msg.as_bytes());
+
+        simd_json::serde::from_slice(&mut self.0).map_err(MarketDataError::with_source)
+    }
+
+    /// Parses the message with serde_json
+    #[cfg(not(feature = "simd"))]
+    pub fn parse<'a, T: Deserialize<'a>>(&mut self, msg: &'a str) -> Result<T, MarketDataError> {
+        serde_json::from_str(msg).map_err(MarketDataError::with_source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct Msg<'a> {
+        channel: &'a str,
+        levels: Vec<(f64, f64)>,
+    }
+
+    #[test]
+    fn test_parse_messages() {
+        let mut json = JsonBuffer::default();
+
+        for (msg, channel) in [
+            (
+                r#"{"channel": "orderbook", "levels": [[100.5, 2.0]]}"#,
+                "orderbook",
+            ),
+            (r#"{"channel": "trades", "levels": []}"#, "trades"),
+        ] {
+            let msg = json.parse::<Msg>(msg).unwrap();
+            assert_eq!(channel, msg.channel);
+        }
+
+        let msg = json
+            .parse::<Msg>(r#"{"channel": "orderbook", "levels": [[100.5, 2.0], [101.0, 0.5]]}"#)
+            .unwrap();
+        assert_eq!(vec![(100.5, 2.0), (101.0, 0.5)], msg.levels);
+
+        assert!(json.parse::<Msg>(r#"{"channel": "#).is_err());
+    }
+}
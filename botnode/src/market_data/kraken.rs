@@ -0,0 +1,541 @@
+//! Kraken market data adapter
+//!
+//! Markets come from `/0/public/AssetPairs`. The Websocket API streams the
+//! `book` channel, a snapshot after subscribing followed by updates, and
+//! the `trade` channel.
+//!
+//! Every book update carries a CRC32 checksum of the top 10 levels. The
+//! checksum is verified after applying the update and a mismatch
+//! resubscribes the book of the market, updates are dropped until the new
+//! snapshot arrives.
+
+pub(crate) mod rest;
+pub(crate) mod ws;
+
+use chrono::TimeZone;
+
+use super::prelude::*;
+use crate::prelude::*;
+use botvana::exchange::ExchangeId;
+
+/// Subscribed book depth, also the number of levels in the checksum
+const BOOK_DEPTH: usize = 10;
+
+#[derive(Debug)]
+pub struct Kraken {
+    pub metrics: AdapterMetrics,
+    api_url: Box<str>,
+    ws_url: Box<str>,
+    /// Precision of synced books by Kraken pair, books missing here wait
+    /// for a snapshot
+    books: RefCell<HashMap<Box<str>, Precision>>,
+    book_events: BookEmitter,
+}
+
+impl Default for Kraken {
+    fn default() -> Self {
+        Kraken {
+            api_url: Box::from("https://api.kraken.com"),
+            ws_url: Box::from("wss://ws.kraken.com"),
+            metrics: AdapterMetrics::default(),
+            books: RefCell::new(HashMap::new()),
+            book_events: BookEmitter::default(),
+        }
+    }
+}
+
+impl Kraken {
+    fn client(&self) -> Result<surf::Client, MarketDataError> {
+        surf::Config::new()
+            .set_base_url(Url::parse(&self.api_url).map_err(MarketDataError::with_source)?)
+            .set_timeout(Some(Duration::from_secs(5)))
+            .try_into()
+            .map_err(MarketDataError::with_source)
+    }
+
+    fn book_subscription(&self, event: &str, pairs: &[String]) -> String {
+        json!({
+            "event": event,
+            "pair": pairs,
+            "subscription": {"name": "book", "depth": BOOK_DEPTH},
+        })
+        .to_string()
+    }
+}
+
+#[async_trait(?Send)]
+impl RestMarketDataAdapter for Kraken {
+    fn name(&self) -> &'static str {
+        "kraken-rest"
+    }
+
+    fn exchange(&self) -> ExchangeId {
+        ExchangeId::Kraken
+    }
+
+    /// Fetches available pairs on Kraken
+    async fn fetch_markets(&self) -> Result<Box<[Market]>, MarketDataError> {
+        rate_limit::acquire(ExchangeId::Kraken, Endpoint::Markets).await;
+        let mut res = self
+            .client()?
+            .get("/0/public/AssetPairs")
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let res = serde_json::from_slice::<rest::Response<rest::AssetPairs>>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?;
+        let pairs = res
+            .result
+            .ok_or_else(|| MarketDataError::convert_error(res.error.join(", ")))?;
+
+        debug!("{} pairs on Kraken", pairs.len());
+
+        Ok(pairs
+            .values()
+            .filter_map(|pair| Market::try_from(pair).ok())
+            .collect())
+    }
+
+    async fn fetch_orderbook_snapshot(
+        &self,
+        symbol: &str,
+    ) -> Result<PlainOrderbook<f64>, MarketDataError> {
+        let pair = native_symbol(symbol).replace('/', "");
+        rate_limit::acquire(ExchangeId::Kraken, Endpoint::Orderbook).await;
+        let mut res = self
+            .client()?
+            .get(format!("/0/public/Depth?pair={pair}&count={BOOK_DEPTH}"))
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let res =
+            serde_json::from_slice::<rest::Response<HashMap<String, rest::Depth>>>(body.as_bytes())
+                .map_err(MarketDataError::with_source)?;
+        // Result is keyed by the full pair name, e.g. `XXBTZUSD`
+        let depth = res
+            .result
+            .and_then(|result| result.into_values().next())
+            .ok_or_else(|| MarketDataError::convert_error(res.error.join(", ")))?;
+
+        Ok(PlainOrderbook {
+            bids: depth.bids,
+            asks: depth.asks,
+            time: 0.0,
+            max_depth: None,
+        })
+    }
+}
+
+impl WsMarketDataAdapter for Kraken {
+    fn metrics(&self) -> &AdapterMetrics {
+        &self.metrics
+    }
+
+    fn set_book_events(&mut self, book_events: BookEvents) {
+        self.book_events = BookEmitter::new(book_events);
+    }
+
+    fn ws_url(&self) -> Box<str> {
+        self.ws_url.clone()
+    }
+
+    fn subscribe_msgs(&mut self, markets: &[&str]) -> Box<[String]> {
+        info!("Subscribing for {markets:?}");
+        self.books.borrow_mut().clear();
+
+        let pairs: Vec<_> = markets.iter().map(|m| native_symbol(m)).collect();
+
+        Box::new([
+            self.book_subscription("subscribe", &pairs),
+            json!({"event": "subscribe", "pair": pairs, "subscription": {"name": "trade"}})
+                .to_string(),
+        ])
+    }
+
+    /// Subscribing forgets the books of all pairs
+    fn subscribes_incrementally(&self) -> bool {
+        false
+    }
+
+    fn resync_msgs(&self, market: &str) -> Option<Box<[String]>> {
+        let pairs = [native_symbol(market)];
+
+        Some(Box::new([
+            self.book_subscription("unsubscribe", &pairs),
+            self.book_subscription("subscribe", &pairs),
+        ]))
+    }
+
+    fn process_ws_msg(
+        &self,
+        msg: &str,
+        markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    ) -> Result<Option<MarketEvent>, MarketDataError> {
+        trace!("got ws_msg = {msg:?}");
+
+        let ws_msg = match serde_json::from_slice::<ws::WsMsg>(msg.as_bytes()) {
+            Ok(ws_msg) => ws_msg,
+            Err(e) => {
+                error!("Error parsing ws_msg: {msg}");
+
+                return Err(MarketDataError::with_source(e));
+            }
+        };
+
+        match ws_msg {
+            ws::WsMsg::Event(ws::Event::SubscriptionStatus {
+                status: "error",
+                pair,
+                error_message,
+            }) => Err(MarketDataError::convert_error(format!(
+                "Kraken subscription error for {}: {}",
+                pair.unwrap_or_default(),
+                error_message.unwrap_or_default()
+            ))),
+            ws::WsMsg::Event(ws::Event::SystemStatus { status }) => {
+                info!("Kraken system status: {status}");
+
+                Ok(None)
+            }
+            ws::WsMsg::Event(event) => {
+                debug!("event = {event:?}");
+
+                Ok(None)
+            }
+            ws::WsMsg::Trade(_, trades, _, pair) => {
+                let symbol = internal_symbol(markets, pair).unwrap_or_else(|| Box::from(pair));
+                let trades = trades
+                    .iter()
+                    .map(|(price, size, time, ..)| {
+                        Ok(botvana::market::trade::Trade::new(
+                            price.parse().map_err(MarketDataError::with_source)?,
+                            size.parse().map_err(MarketDataError::with_source)?,
+                            Utc.timestamp_millis(parse_time_millis(time)?),
+                        ))
+                    })
+                    .collect::<Result<Box<[_]>, MarketDataError>>()?;
+
+                Ok(Some(MarketEvent::trades(symbol, trades)))
+            }
+            ws::WsMsg::Book(_, data, _, pair) => self.process_book(&[data], pair, markets),
+            ws::WsMsg::BookBoth(_, asks, bids, _, pair) => {
+                self.process_book(&[asks, bids], pair, markets)
+            }
+        }
+    }
+}
+
+impl Kraken {
+    /// Applies book snapshot or update and verifies the checksum
+    fn process_book(
+        &self,
+        data: &[ws::BookData],
+        pair: &str,
+        markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    ) -> Result<Option<MarketEvent>, MarketDataError> {
+        let symbol = match internal_symbol(markets, pair) {
+            Some(symbol) => symbol,
+            None => {
+                warn!("No symbol mapping found for {pair}");
+                return Ok(None);
+            }
+        };
+        let mut books = self.books.borrow_mut();
+
+        if let Some(snapshot) = data.iter().find(|data| data.is_snapshot()) {
+            let mut orderbook = PlainOrderbook::with_capacity(BOOK_DEPTH);
+            let time = apply_levels(
+                &mut orderbook,
+                &snapshot.snapshot_bids,
+                &snapshot.snapshot_asks,
+            )?;
+            orderbook.time = time;
+
+            books.insert(Box::from(pair), Precision::of(snapshot));
+            let event = self.book_events.event(symbol.clone(), &orderbook);
+            markets.insert(symbol, orderbook);
+
+            return Ok(event);
+        }
+
+        // Waiting for the snapshot after (re)subscribing
+        let precision = match books.get(pair) {
+            Some(precision) => *precision,
+            None => return Ok(None),
+        };
+        let orderbook = match markets.get_mut(&symbol) {
+            Some(orderbook) => orderbook,
+            None => return Ok(None),
+        };
+
+        for update in data {
+            let time = apply_levels(orderbook, &update.bids, &update.asks)?;
+            orderbook.time = orderbook.time.max(time);
+        }
+        truncate(orderbook, BOOK_DEPTH);
+
+        // Books limited below the checksummed depth can't be verified
+        let truncated = orderbook.max_depth.map_or(false, |d| d < BOOK_DEPTH);
+        let expected = data.iter().rev().find_map(|update| update.checksum);
+        if let Some(expected) = expected.filter(|_| !truncated) {
+            let expected = expected
+                .parse::<u32>()
+                .map_err(MarketDataError::with_source)?;
+            let computed = checksum(orderbook, &precision);
+
+            if computed != expected {
+                books.remove(pair);
+                self.metrics.record_checksum_failure();
+
+                return Err(MarketDataError::with_source(BookOutOfSync {
+                    market: symbol,
+                    reason: format!("checksum {expected} does not match computed {computed}"),
+                }));
+            }
+        }
+
+        Ok(self.book_events.event(symbol, orderbook))
+    }
+}
+
+/// Number of decimals Kraken formats prices and volumes of a pair with
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Precision {
+    price: usize,
+    volume: usize,
+}
+
+impl Precision {
+    fn of(snapshot: &ws::BookData) -> Self {
+        let decimals = |s: &str| s.split_once('.').map_or(0, |(_, d)| d.len());
+        let level = snapshot
+            .snapshot_asks
+            .first()
+            .or_else(|| snapshot.snapshot_bids.first());
+
+        Self {
+            price: level.map_or(0, |level| decimals(level.price)),
+            volume: level.map_or(0, |level| decimals(level.volume)),
+        }
+    }
+}
+
+/// Applies levels to the orderbook, returns the latest level time in
+/// milliseconds
+fn apply_levels(
+    orderbook: &mut PlainOrderbook<f64>,
+    bids: &[ws::Level],
+    asks: &[ws::Level],
+) -> Result<f64, MarketDataError> {
+    let parse = |levels: &[ws::Level]| {
+        levels
+            .iter()
+            .map(ws::Level::parse)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(MarketDataError::convert_error)
+    };
+    let mut time = 0;
+    for level in bids.iter().chain(asks) {
+        time = time.max(parse_time_millis(level.time)?);
+    }
+
+    orderbook.update(
+        &PriceLevelsVec::from_tuples_vec_unsorted(&mut parse(bids)?),
+        &PriceLevelsVec::from_tuples_vec_unsorted(&mut parse(asks)?),
+    );
+
+    Ok(time as f64)
+}
+
+/// Keeps only the best `depth` levels on both sides, Kraken doesn't send
+/// deletes for levels falling out of the subscribed depth
+fn truncate(orderbook: &mut PlainOrderbook<f64>, depth: usize) {
+    let bids = &mut orderbook.bids;
+    if bids.price_vec.len() > depth {
+        let excess = bids.price_vec.len() - depth;
+        bids.price_vec.drain(..excess);
+        bids.size_vec.drain(..excess);
+    }
+
+    let asks = &mut orderbook.asks;
+    asks.price_vec.truncate(depth);
+    asks.size_vec.truncate(depth);
+}
+
+/// Computes Kraken book checksum: CRC32 of the top asks followed by the top
+/// bids, each level as price and volume without the decimal point and
+/// leading zeros
+fn checksum(orderbook: &PlainOrderbook<f64>, precision: &Precision) -> u32 {
+    let mut payload = String::with_capacity(BOOK_DEPTH * 2 * 32);
+    let mut push = |value: f64, decimals: usize| {
+        let formatted = format!("{value:.decimals$}").replace('.', "");
+        payload.push_str(formatted.trim_start_matches('0'));
+    };
+
+    let asks = &orderbook.asks;
+    for (price, size) in asks.price_vec.iter().zip(&asks.size_vec).take(BOOK_DEPTH) {
+        push(*price, precision.price);
+        push(*size, precision.volume);
+    }
+    let bids = &orderbook.bids;
+    for (price, size) in bids
+        .price_vec
+        .iter()
+        .zip(&bids.size_vec)
+        .rev()
+        .take(BOOK_DEPTH)
+    {
+        push(*price, precision.price);
+        push(*size, precision.volume);
+    }
+
+    crc32fast::hash(payload.as_bytes())
+}
+
+/// Parses `seconds.micros` timestamp into milliseconds
+fn parse_time_millis(time: &str) -> Result<i64, MarketDataError> {
+    let secs = time.parse::<f64>().map_err(MarketDataError::with_source)?;
+
+    Ok((secs * 1000.0) as i64)
+}
+
+/// Converts market name to Kraken pair, `BTC-USD` to `XBT/USD`
+fn native_symbol(market: &str) -> String {
+    market
+        .to_uppercase()
+        .replace('-', "/")
+        .split('/')
+        .map(|asset| match asset {
+            "BTC" => "XBT",
+            asset => asset,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Returns the market name Kraken pair was subscribed as
+fn internal_symbol(
+    markets: &HashMap<Box<str>, PlainOrderbook<f64>>,
+    pair: &str,
+) -> Option<Box<str>> {
+    markets.keys().find(|k| native_symbol(k) == pair).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SNAPSHOT: &str = r#"[
+        0,
+        {
+            "as": [
+                ["0.05005", "0.00000500", "1582905487.684110"],
+                ["0.05010", "0.00000500", "1582905486.187983"],
+                ["0.05015", "0.00000500", "1582905484.480241"]
+            ],
+            "bs": [
+                ["0.05000", "0.00000500", "1582904661.260052"],
+                ["0.04995", "0.00000500", "1582904663.435663"]
+            ]
+        },
+        "book-10",
+        "XBT/USD"
+    ]"#;
+
+    fn update(checksum: u32) -> String {
+        format!(
+            r#"[
+                0,
+                {{"a": [["0.05005", "0.00000600", "1582905488.000000"]], "c": "{checksum}"}},
+                "book-10",
+                "XBT/USD"
+            ]"#
+        )
+    }
+
+    fn setup() -> (Kraken, HashMap<Box<str>, PlainOrderbook<f64>>) {
+        let kraken = Kraken::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTC/USD"), PlainOrderbook::new());
+
+        kraken.process_ws_msg(SNAPSHOT, &mut markets).unwrap();
+
+        (kraken, markets)
+    }
+
+    #[test]
+    fn test_native_symbol() {
+        assert_eq!("XBT/USD", native_symbol("btc-usd"));
+        assert_eq!("ETH/EUR", native_symbol("ETH/EUR"));
+    }
+
+    #[test]
+    fn test_checksum() {
+        let (_, mut markets) = setup();
+        let precision = Precision {
+            price: 5,
+            volume: 8,
+        };
+
+        // Asks then bids from the best level
+        let payload = "50055005010500501550050005004995500";
+        assert_eq!(
+            crc32fast::hash(payload.as_bytes()),
+            checksum(&markets["BTC/USD"], &precision)
+        );
+
+        truncate(markets.get_mut("BTC/USD").unwrap(), 1);
+        assert_eq!(
+            crc32fast::hash(b"50055005000500"),
+            checksum(&markets["BTC/USD"], &precision)
+        );
+    }
+
+    #[test]
+    fn test_process_ws_msg_update_checksum() {
+        let (kraken, mut markets) = setup();
+        let expected = crc32fast::hash(b"50056005010500501550050005004995500");
+
+        let event = kraken
+            .process_ws_msg(&update(expected), &mut markets)
+            .unwrap();
+
+        match event.map(|event| event.r#type) {
+            Some(MarketEventType::OrderbookUpdate(market, orderbook)) => {
+                assert_eq!("BTC/USD", &*market);
+                assert_eq!(0.000006, orderbook.asks.size_vec[0]);
+                assert_eq!(1582905488000.0, orderbook.time);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_process_ws_msg_checksum_mismatch() {
+        let (kraken, mut markets) = setup();
+
+        let err = kraken.process_ws_msg(&update(1), &mut markets).unwrap_err();
+        assert_eq!(Some("BTC/USD"), err.out_of_sync_market());
+        assert_eq!(1, kraken.metrics.checksum_failures());
+
+        // Updates are dropped until the book is resubscribed
+        assert!(kraken
+            .process_ws_msg(&update(1), &mut markets)
+            .unwrap()
+            .is_none());
+        assert_eq!(2, kraken.resync_msgs("BTC/USD").unwrap().len());
+
+        assert!(kraken
+            .process_ws_msg(SNAPSHOT, &mut markets)
+            .unwrap()
+            .is_some());
+    }
+}
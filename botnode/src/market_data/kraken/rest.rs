@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Deserializer};
+
+use botvana::{exchange::ExchangeId, market::orderbook::*};
+
+/// Response envelope of the public REST API
+#[derive(Debug, Deserialize)]
+pub struct Response<T> {
+    pub error: Vec<String>,
+    pub result: Option<T>,
+}
+
+/// Tradable pair listed by `/0/public/AssetPairs`
+#[derive(Debug, Deserialize)]
+pub struct AssetPair<'a> {
+    pub altname: &'a str,
+    /// Pair name used by the Websocket API, missing for dark pool pairs
+    pub wsname: Option<&'a str>,
+    pub pair_decimals: u32,
+    pub lot_decimals: u32,
+}
+
+impl<'a> TryFrom<&AssetPair<'a>> for botvana::market::Market {
+    type Error = String;
+
+    fn try_from(pair: &AssetPair<'a>) -> Result<Self, Self::Error> {
+        let wsname = pair
+            .wsname
+            .ok_or_else(|| format!("{} is not available over websocket", pair.altname))?;
+        let (base, quote) = wsname
+            .split_once('/')
+            .ok_or_else(|| format!("invalid pair name: {wsname}"))?;
+
+        Ok(Self {
+            exchange: ExchangeId::Kraken,
+            name: wsname.to_string(),
+            native_symbol: wsname.to_string(),
+            size_increment: 10_f64.powi(-(pair.lot_decimals as i32)),
+            price_increment: 10_f64.powi(-(pair.pair_decimals as i32)),
+            r#type: botvana::market::MarketType::Spot(botvana::market::SpotMarket {
+                base: base.to_string(),
+                quote: quote.to_string(),
+            }),
+        })
+    }
+}
+
+pub type AssetPairs<'a> = HashMap<&'a str, AssetPair<'a>>;
+
+/// Orderbook returned by `/0/public/Depth`
+#[derive(Debug, Deserialize)]
+pub struct Depth {
+    #[serde(deserialize_with = "deserialize_into_price_levels_vec")]
+    pub bids: PriceLevelsVec<f64>,
+    #[serde(deserialize_with = "deserialize_into_price_levels_vec")]
+    pub asks: PriceLevelsVec<f64>,
+}
+
+/// Deserializes `[price, volume, timestamp]` levels
+fn deserialize_into_price_levels_vec<'de, D>(
+    deserializer: D,
+) -> Result<PriceLevelsVec<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let buf = Box::<[(String, String, u64)]>::deserialize(deserializer)?;
+
+    let mut levels = buf
+        .iter()
+        .map(|(price, size, _)| Ok((price.parse::<f64>()?, size.parse::<f64>()?)))
+        .collect::<Result<Vec<(f64, f64)>, std::num::ParseFloatError>>()
+        .map_err(serde::de::Error::custom)?;
+
+    Ok(PriceLevelsVec::from_tuples_vec_unsorted(&mut levels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_asset_pairs() {
+        let sample = r#"{
+            "error": [],
+            "result": {
+                "XXBTZUSD": {
+                    "altname": "XBTUSD",
+                    "wsname": "XBT/USD",
+                    "aclass_base": "currency",
+                    "base": "XXBT",
+                    "aclass_quote": "currency",
+                    "quote": "ZUSD",
+                    "lot": "unit",
+                    "pair_decimals": 1,
+                    "lot_decimals": 8,
+                    "lot_multiplier": 1,
+                    "ordermin": "0.0001"
+                }
+            }
+        }"#;
+
+        let res = serde_json::from_str::<Response<AssetPairs>>(sample).unwrap();
+        let pairs = res.result.unwrap();
+        let market = botvana::market::Market::try_from(&pairs["XXBTZUSD"]).unwrap();
+
+        assert_eq!("XBT/USD", market.name);
+        assert_eq!(0.1, market.price_increment);
+        assert_eq!(0.00000001, market.size_increment);
+    }
+
+    #[test]
+    fn test_parse_depth() {
+        let sample = r#"{
+            "error": [],
+            "result": {
+                "XXBTZUSD": {
+                    "asks": [["30384.10000", "0.040", 1688671969]],
+                    "bids": [["30297.00000", "0.115", 1688671915], ["30296.70000", "0.002", 1688671674]]
+                }
+            }
+        }"#;
+
+        let res = serde_json::from_str::<Response<HashMap<String, Depth>>>(sample).unwrap();
+        let depth = &res.result.unwrap()["XXBTZUSD"];
+
+        assert_eq!(vec![30296.7, 30297.0], depth.bids.price_vec);
+        assert_eq!(vec![30384.1], depth.asks.price_vec);
+    }
+}
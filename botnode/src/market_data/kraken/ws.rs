@@ -0,0 +1,188 @@
+use std::marker::PhantomData;
+
+use serde::{
+    de::{self, IgnoredAny, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+
+/// Kraken Websocket message
+///
+/// Events are JSON objects, channel data are arrays ending with the channel
+/// name and the pair.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum WsMsg<'a> {
+    #[serde(borrow)]
+    Event(Event<'a>),
+    Trade(u64, #[serde(borrow)] Vec<TradeData<'a>>, &'a str, &'a str),
+    Book(u64, #[serde(borrow)] BookData<'a>, &'a str, &'a str),
+    /// Update changing both sides of the book
+    BookBoth(
+        u64,
+        #[serde(borrow)] BookData<'a>,
+        #[serde(borrow)] BookData<'a>,
+        &'a str,
+        &'a str,
+    ),
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "event", rename_all = "camelCase")]
+pub enum Event<'a> {
+    SystemStatus {
+        status: &'a str,
+    },
+    #[serde(rename_all = "camelCase")]
+    SubscriptionStatus {
+        status: &'a str,
+        pair: Option<&'a str>,
+        error_message: Option<&'a str>,
+    },
+    Heartbeat,
+    #[serde(other)]
+    Other,
+}
+
+/// `[price, volume, time, side, order type, misc]`
+pub type TradeData<'a> = (&'a str, &'a str, &'a str, &'a str, &'a str, &'a str);
+
+/// Book snapshot (`as`, `bs`) or update (`a`, `b`) with checksum
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BookData<'a> {
+    #[serde(rename = "as", default, borrow)]
+    pub snapshot_asks: Vec<Level<'a>>,
+    #[serde(rename = "bs", default, borrow)]
+    pub snapshot_bids: Vec<Level<'a>>,
+    #[serde(rename = "a", default, borrow)]
+    pub asks: Vec<Level<'a>>,
+    #[serde(rename = "b", default, borrow)]
+    pub bids: Vec<Level<'a>>,
+    #[serde(rename = "c")]
+    pub checksum: Option<&'a str>,
+}
+
+impl<'a> BookData<'a> {
+    pub fn is_snapshot(&self) -> bool {
+        !self.snapshot_asks.is_empty() || !self.snapshot_bids.is_empty()
+    }
+}
+
+/// Price level as sent by Kraken, strings keep the precision needed for
+/// the checksum
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level<'a> {
+    pub price: &'a str,
+    pub volume: &'a str,
+    pub time: &'a str,
+}
+
+impl<'a> Level<'a> {
+    pub fn parse(&self) -> Result<(f64, f64), String> {
+        Ok((
+            self.price.parse::<f64>().map_err(|e| e.to_string())?,
+            self.volume.parse::<f64>().map_err(|e| e.to_string())?,
+        ))
+    }
+}
+
+impl<'de: 'a, 'a> Deserialize<'de> for Level<'a> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LevelVisitor<'a>(PhantomData<&'a ()>);
+
+        impl<'de: 'a, 'a> Visitor<'de> for LevelVisitor<'a> {
+            type Value = Level<'a>;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("price level array")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Level<'a>, A::Error> {
+                let price = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let volume = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                let time = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+                // Updates republished after a price change carry an "r" flag
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+
+                Ok(Level {
+                    price,
+                    volume,
+                    time,
+                })
+            }
+        }
+
+        deserializer.deserialize_seq(LevelVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_events() {
+        assert_eq!(
+            WsMsg::Event(Event::Heartbeat),
+            serde_json::from_str(r#"{"event":"heartbeat"}"#).unwrap()
+        );
+        assert_eq!(
+            WsMsg::Event(Event::SubscriptionStatus {
+                status: "subscribed",
+                pair: Some("XBT/USD"),
+                error_message: None,
+            }),
+            serde_json::from_str(
+                r#"{
+                    "channelID": 10001,
+                    "channelName": "book-10",
+                    "event": "subscriptionStatus",
+                    "pair": "XBT/USD",
+                    "status": "subscribed",
+                    "subscription": {"depth": 10, "name": "book"}
+                }"#
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_book_update_both_sides() {
+        let msg = r#"[
+            1234,
+            {"a": [["5541.30000", "2.50700000", "1534614248.456738"]]},
+            {"b": [["5541.20000", "1.52900000", "1534614248.765567", "r"]], "c": "974942666"},
+            "book-10",
+            "XBT/USD"
+        ]"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::BookBoth(_, asks, bids, "book-10", "XBT/USD") => {
+                assert_eq!("5541.30000", asks.asks[0].price);
+                assert_eq!("1.52900000", bids.bids[0].volume);
+                assert_eq!(Some("974942666"), bids.checksum);
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_trade() {
+        let msg = r#"[
+            0,
+            [["5541.20000", "0.15850568", "1534614057.321597", "s", "l", ""]],
+            "trade",
+            "XBT/USD"
+        ]"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::Trade(_, trades, "trade", "XBT/USD") => assert_eq!("5541.20000", trades[0].0),
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! Market data adapter metrics
+//!
+//! Every adapter owns one `AdapterMetrics` so all exchanges report the same
+//! set of metrics regardless of their wire protocol.
+
+use std::{
+    cell::{Cell, RefCell},
+    time::{Duration, Instant},
+};
+
+use metered::{common::TxPerSec, time_source::StdInstant, Throughput};
+
+use crate::{
+    audit::{AuditEvent, AuditProducer, AuditRecord, Category, Severity},
+    latency::LatencyHistogram,
+    metrics::MetricLabels,
+};
+
+/// Metrics tracked by every market data adapter
+#[derive(Default, Debug)]
+pub struct AdapterMetrics {
+    /// Websocket messages processed per second
+    pub throughput: Throughput<StdInstant, RefCell<TxPerSec>>,
+    messages: Cell<u64>,
+    parse_errors: Cell<u64>,
+    reconnects: Cell<u64>,
+    book_resyncs: Cell<u64>,
+    checksum_failures: Cell<u64>,
+    stale_disconnects: Cell<u64>,
+    last_message_at: Cell<Option<Instant>>,
+    connected_at: Cell<Option<Instant>>,
+    disconnected_at: Cell<Option<Instant>>,
+    total_uptime: Cell<Duration>,
+    time_to_resubscribe: Cell<Option<Duration>>,
+    /// Time from reading a message off the socket to its parsed event
+    parse_latency: RefCell<LatencyHistogram>,
+    /// Exchange name and audit channel connection changes are recorded to
+    audit: RefCell<Option<(Box<str>, AuditProducer)>>,
+}
+
+impl AdapterMetrics {
+    /// Records received websocket message
+    pub fn record_message(&self) {
+        let now = Instant::now();
+        self.messages.set(self.messages.get() + 1);
+        self.last_message_at.set(Some(now));
+
+        if let Some(disconnected_at) = self.disconnected_at.take() {
+            self.time_to_resubscribe
+                .set(Some(now.duration_since(disconnected_at)));
+        }
+    }
+
+    /// Records time between reading a message and parsing its event
+    pub fn record_parse_latency(&self, latency: Duration) {
+        self.parse_latency.borrow_mut().record(latency);
+    }
+
+    /// Sets audit channel connection changes of the exchange are recorded to
+    pub fn set_audit(&self, exchange: &str, audit_tx: AuditProducer) {
+        self.audit.replace(Some((Box::from(exchange), audit_tx)));
+    }
+
+    /// Records established and subscribed websocket connection
+    pub fn record_connected(&self) {
+        self.connected_at.set(Some(Instant::now()));
+        self.audit_connection(true);
+    }
+
+    /// Records lost websocket connection
+    pub fn record_disconnected(&self) {
+        if let Some(connected_at) = self.connected_at.take() {
+            self.total_uptime
+                .set(self.total_uptime.get() + connected_at.elapsed());
+            self.audit_connection(false);
+        }
+        self.disconnected_at.set(Some(Instant::now()));
+    }
+
+    fn audit_connection(&self, connected: bool) {
+        if let Some((exchange, audit_tx)) = &*self.audit.borrow() {
+            let (severity, message) = match connected {
+                true => (Severity::Info, format!("connected to {exchange}")),
+                false => (Severity::Warning, format!("disconnected from {exchange}")),
+            };
+            crate::audit::record(
+                audit_tx,
+                AuditEvent::new(
+                    severity,
+                    Category::Connection,
+                    "market-data-engine",
+                    message,
+                )
+                .with_record(AuditRecord::Connection {
+                    peer: exchange.clone(),
+                    connected,
+                }),
+            );
+        }
+    }
+
+    /// Records websocket message that failed to parse or process
+    pub fn record_parse_error(&self) {
+        self.parse_errors.set(self.parse_errors.get() + 1);
+    }
+
+    /// Records reconnection to the exchange
+    pub fn record_reconnect(&self) {
+        self.reconnects.set(self.reconnects.get() + 1);
+    }
+
+    /// Records orderbook that had to be re-synchronized from a snapshot
+    pub fn record_book_resync(&self) {
+        self.book_resyncs.set(self.book_resyncs.get() + 1);
+    }
+
+    /// Records orderbook failing the exchange checksum
+    pub fn record_checksum_failure(&self) {
+        self.checksum_failures.set(self.checksum_failures.get() + 1);
+    }
+
+    /// Records connection dropped for not receiving any message in time
+    pub fn record_stale_disconnect(&self) {
+        self.stale_disconnects.set(self.stale_disconnects.get() + 1);
+    }
+
+    /// Returns total number of received messages
+    pub fn messages(&self) -> u64 {
+        self.messages.get()
+    }
+
+    /// Returns total number of parse errors
+    pub fn parse_errors(&self) -> u64 {
+        self.parse_errors.get()
+    }
+
+    /// Returns total number of reconnects
+    pub fn reconnects(&self) -> u64 {
+        self.reconnects.get()
+    }
+
+    /// Returns total number of orderbook resyncs
+    pub fn book_resyncs(&self) -> u64 {
+        self.book_resyncs.get()
+    }
+
+    /// Returns total number of orderbook checksum failures
+    pub fn checksum_failures(&self) -> u64 {
+        self.checksum_failures.get()
+    }
+
+    /// Returns total number of connections dropped as stale
+    pub fn stale_disconnects(&self) -> u64 {
+        self.stale_disconnects.get()
+    }
+
+    /// Returns uptime of the current connection
+    pub fn uptime(&self) -> Option<Duration> {
+        self.connected_at.get().map(|at| at.elapsed())
+    }
+
+    /// Returns uptime summed over all connections
+    pub fn total_uptime(&self) -> Duration {
+        self.total_uptime.get() + self.uptime().unwrap_or_default()
+    }
+
+    /// Returns time it took from the last disconnect to the first message on
+    /// the new connection
+    pub fn time_to_resubscribe(&self) -> Option<Duration> {
+        self.time_to_resubscribe.get()
+    }
+
+    /// Returns time elapsed since the last received message
+    pub fn last_message_age(&self) -> Option<Duration> {
+        self.last_message_at.get().map(|at| at.elapsed())
+    }
+
+    /// Publishes current values into the metrics registry
+    pub fn publish(&self, exchange: &str) {
+        let registry = crate::metrics::registry();
+        let labels = MetricLabels::new().exchange(exchange);
+
+        registry.set_gauge(
+            "market_data_messages_per_second_max",
+            &labels,
+            self.throughput.0.borrow().hdr_histogram.max() as f64,
+        );
+        registry.set_counter("market_data_messages_total", &labels, self.messages());
+        registry.set_counter(
+            "market_data_parse_errors_total",
+            &labels,
+            self.parse_errors(),
+        );
+        registry.set_counter("market_data_reconnects_total", &labels, self.reconnects());
+        registry.set_counter(
+            "market_data_book_resyncs_total",
+            &labels,
+            self.book_resyncs(),
+        );
+        registry.set_counter(
+            "market_data_checksum_failures_total",
+            &labels,
+            self.checksum_failures(),
+        );
+        registry.set_counter(
+            "market_data_stale_disconnects_total",
+            &labels,
+            self.stale_disconnects(),
+        );
+
+        registry.set_gauge(
+            "market_data_connected",
+            &labels,
+            if self.uptime().is_some() { 1.0 } else { 0.0 },
+        );
+        registry.set_gauge(
+            "market_data_connection_uptime_seconds",
+            &labels,
+            self.uptime().unwrap_or_default().as_secs_f64(),
+        );
+        registry.set_gauge(
+            "market_data_total_uptime_seconds",
+            &labels,
+            self.total_uptime().as_secs_f64(),
+        );
+
+        let mut parse_latency = self.parse_latency.borrow_mut();
+        if !parse_latency.is_empty() {
+            parse_latency.publish("market_data_parse_latency_microseconds", &labels);
+        }
+
+        if let Some(time_to_resubscribe) = self.time_to_resubscribe() {
+            registry.set_gauge(
+                "market_data_time_to_resubscribe_seconds",
+                &labels,
+                time_to_resubscribe.as_secs_f64(),
+            );
+        }
+
+        if let Some(age) = self.last_message_age() {
+            let last_message_at = std::time::SystemTime::now() - age;
+            registry.set_gauge(
+                "market_data_last_message_timestamp_seconds",
+                &labels,
+                last_message_at
+                    .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs_f64(),
+            );
+            registry.set_gauge(
+                "market_data_last_message_age_seconds",
+                &labels,
+                age.as_secs_f64(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_adapter_metrics_counters() {
+        let metrics = AdapterMetrics::default();
+        assert_eq!(None, metrics.last_message_age());
+
+        metrics.record_message();
+        metrics.record_message();
+        metrics.record_parse_error();
+        metrics.record_reconnect();
+        metrics.record_book_resync();
+        metrics.record_checksum_failure();
+        metrics.record_stale_disconnect();
+
+        assert_eq!(2, metrics.messages());
+        assert_eq!(1, metrics.parse_errors());
+        assert_eq!(1, metrics.reconnects());
+        assert_eq!(1, metrics.book_resyncs());
+        assert_eq!(1, metrics.checksum_failures());
+        assert_eq!(1, metrics.stale_disconnects());
+        assert!(metrics.last_message_age().is_some());
+    }
+
+    #[test]
+    fn test_adapter_metrics_connection() {
+        let metrics = AdapterMetrics::default();
+        assert_eq!(None, metrics.uptime());
+
+        metrics.record_connected();
+        assert!(metrics.uptime().is_some());
+
+        metrics.record_disconnected();
+        assert_eq!(None, metrics.uptime());
+        assert_eq!(None, metrics.time_to_resubscribe());
+
+        metrics.record_connected();
+        metrics.record_message();
+        assert!(metrics.time_to_resubscribe().is_some());
+    }
+
+    #[test]
+    fn test_adapter_metrics_audit_connection() {
+        let metrics = AdapterMetrics::default();
+        let (audit_tx, audit_rx) = crate::rt::spsc_queue::make(8);
+        metrics.set_audit("ftx", audit_tx);
+
+        // Disconnect without a connection is not a connection change
+        metrics.record_disconnected();
+        metrics.record_connected();
+        metrics.record_disconnected();
+
+        let records: Vec<_> = std::iter::from_fn(|| audit_rx.try_pop())
+            .map(|event| event.record)
+            .collect();
+        assert_eq!(
+            vec![
+                Some(AuditRecord::Connection {
+                    peer: Box::from("ftx"),
+                    connected: true
+                }),
+                Some(AuditRecord::Connection {
+                    peer: Box::from("ftx"),
+                    connected: false
+                }),
+            ],
+            records
+        );
+    }
+}
@@ -0,0 +1,509 @@
+//! OKX market data adapter
+//!
+//! Markets are spot pairs and perpetual swaps from `/api/v5/public/instruments`.
+//! The public Websocket API needs no login, orderbooks are streamed from the
+//! `books` channel, a `snapshot` after subscribing followed by `update`
+//! changes numbered by `seqId`. When only the top of book is emitted the
+//! adapter subscribes the lighter `books5` channel instead, sending the best
+//! five levels whole on every change. Trades come from the `trades` channel.
+//!
+//! OKX encodes price levels as `[price, size, liquidated orders, orders]`
+//! arrays of strings, only the price and size are kept. Connections idle for
+//! 30 seconds are closed, the adapter keeps them open with plain text `ping`
+//! answered by `pong`.
+
+pub(crate) mod rest;
+pub(crate) mod ws;
+
+use chrono::TimeZone;
+
+use super::prelude::*;
+use crate::prelude::*;
+use botvana::exchange::ExchangeId;
+
+/// Depth of REST orderbook snapshots
+const SNAPSHOT_DEPTH: usize = 400;
+
+#[derive(Debug)]
+pub struct Okx {
+    pub metrics: AdapterMetrics,
+    api_url: Box<str>,
+    ws_url: Box<str>,
+    /// `books`, or `books5` when emitting BBO updates
+    book_channel: &'static str,
+    sequences: SequenceTracker,
+    book_events: BookEmitter,
+}
+
+impl Default for Okx {
+    fn default() -> Self {
+        Okx {
+            api_url: Box::from("https://www.okx.com"),
+            ws_url: Box::from("wss://ws.okx.com:8443/ws/v5/public"),
+            book_channel: "books",
+            metrics: AdapterMetrics::default(),
+            sequences: SequenceTracker::new(),
+            book_events: BookEmitter::default(),
+        }
+    }
+}
+
+impl Okx {
+    fn client(&self) -> Result<surf::Client, MarketDataError> {
+        surf::Config::new()
+            .set_base_url(Url::parse(&self.api_url).map_err(MarketDataError::with_source)?)
+            .set_timeout(Some(Duration::from_secs(5)))
+            .try_into()
+            .map_err(MarketDataError::with_source)
+    }
+
+    /// Returns `op` requests of the book and trades channels of the markets
+    fn channel_msgs(&self, op: &str, markets: &[&str]) -> Box<[String]> {
+        let args: Vec<_> = markets
+            .iter()
+            .flat_map(|market| {
+                let inst_id = native_symbol(market);
+
+                [
+                    json!({"channel": self.book_channel, "instId": inst_id}),
+                    json!({"channel": "trades", "instId": inst_id}),
+                ]
+            })
+            .collect();
+
+        // Keep requests under the 4096 bytes allowed by OKX
+        args.chunks(50)
+            .map(|args| json!({"op": op, "args": args}).to_string())
+            .collect()
+    }
+}
+
+#[async_trait(?Send)]
+impl RestMarketDataAdapter for Okx {
+    fn name(&self) -> &'static str {
+        "okx-rest"
+    }
+
+    fn exchange(&self) -> ExchangeId {
+        ExchangeId::Okx
+    }
+
+    /// Fetches spot markets and perpetual swaps traded on OKX
+    async fn fetch_markets(&self) -> Result<Box<[Market]>, MarketDataError> {
+        let client = self.client()?;
+        let mut markets = Vec::new();
+
+        for inst_type in ["SPOT", "SWAP"] {
+            rate_limit::acquire(ExchangeId::Okx, Endpoint::Markets).await;
+            let mut res = client
+                .get(format!("/api/v5/public/instruments?instType={inst_type}"))
+                .await
+                .map_err(MarketDataError::surf_error)?;
+            let body = res
+                .body_string()
+                .await
+                .map_err(MarketDataError::surf_error)?;
+
+            let res = serde_json::from_slice::<rest::Response<rest::Instrument>>(body.as_bytes())
+                .map_err(MarketDataError::with_source)?;
+            if res.code != "0" {
+                return Err(MarketDataError::convert_error(res.msg));
+            }
+
+            markets.extend(
+                res.data
+                    .iter()
+                    .filter(|instrument| instrument.state == "live")
+                    .filter_map(|instrument| Market::try_from(instrument).ok()),
+            );
+        }
+
+        debug!("{} markets on OKX", markets.len());
+
+        Ok(markets.into_boxed_slice())
+    }
+
+    async fn fetch_orderbook_snapshot(
+        &self,
+        symbol: &str,
+    ) -> Result<PlainOrderbook<f64>, MarketDataError> {
+        rate_limit::acquire(ExchangeId::Okx, Endpoint::Orderbook).await;
+        let mut res = self
+            .client()?
+            .get(format!(
+                "/api/v5/market/books?instId={}&sz={SNAPSHOT_DEPTH}",
+                native_symbol(symbol)
+            ))
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let res =
+            serde_json::from_slice::<rest::Response<rest::OrderbookSnapshot>>(body.as_bytes())
+                .map_err(MarketDataError::with_source)?;
+        if res.code != "0" {
+            return Err(MarketDataError::convert_error(res.msg));
+        }
+        let snapshot = res.data.into_iter().next().ok_or_else(|| {
+            MarketDataError::convert_error("Empty OKX orderbook snapshot".to_string())
+        })?;
+
+        Ok(PlainOrderbook {
+            bids: snapshot.bids,
+            asks: snapshot.asks,
+            time: snapshot.ts as f64,
+            max_depth: None,
+        })
+    }
+
+    async fn fetch_server_time(&self) -> Result<Option<DateTime<Utc>>, MarketDataError> {
+        rate_limit::acquire(ExchangeId::Okx, Endpoint::Time).await;
+        let mut res = self
+            .client()?
+            .get("/api/v5/public/time")
+            .await
+            .map_err(MarketDataError::surf_error)?;
+        let body = res
+            .body_string()
+            .await
+            .map_err(MarketDataError::surf_error)?;
+
+        let res = serde_json::from_slice::<rest::Response<rest::ServerTime>>(body.as_bytes())
+            .map_err(MarketDataError::with_source)?;
+        if res.code != "0" {
+            return Err(MarketDataError::convert_error(res.msg));
+        }
+        let time =
+            res.data.into_iter().next().ok_or_else(|| {
+                MarketDataError::convert_error("Empty OKX server time".to_string())
+            })?;
+
+        Ok(Some(Utc.timestamp_millis(time.ts)))
+    }
+}
+
+impl WsMarketDataAdapter for Okx {
+    fn metrics(&self) -> &AdapterMetrics {
+        &self.metrics
+    }
+
+    fn set_book_events(&mut self, book_events: BookEvents) {
+        self.book_channel = match book_events {
+            BookEvents::Bbo => "books5",
+            BookEvents::Full | BookEvents::Delta => "books",
+        };
+        self.book_events = BookEmitter::new(book_events);
+    }
+
+    fn sequences(&self) -> Option<&SequenceTracker> {
+        Some(&self.sequences)
+    }
+
+    fn ws_url(&self) -> Box<str> {
+        self.ws_url.clone()
+    }
+
+    fn subscribe_msgs(&mut self, markets: &[&str]) -> Box<[String]> {
+        info!("Subscribing for {markets:?}");
+
+        self.channel_msgs("subscribe", markets)
+    }
+
+    fn unsubscribe_msgs(&mut self, markets: &[&str]) -> Option<Box<[String]>> {
+        info!("Unsubscribing {markets:?}");
+
+        Some(self.channel_msgs("unsubscribe", markets))
+    }
+
+    fn resync_msgs(&self, market: &str) -> Option<Box<[String]>> {
+        let args = [json!({"channel": self.book_channel, "instId": native_symbol(market)})];
+
+        Some(Box::new([
+            json!({"op": "unsubscribe", "args": args}).to_string(),
+            json!({"op": "subscribe", "args": args}).to_string(),
+        ]))
+    }
+
+    fn ping_msg(&self) -> Option<String> {
+        Some(String::from("ping"))
+    }
+
+    fn process_ws_msg(
+        &self,
+        msg: &str,
+        markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    ) -> Result<Option<MarketEvent>, MarketDataError> {
+        trace!("got ws_msg = {msg:?}");
+
+        if msg == "pong" {
+            return Ok(None);
+        }
+
+        match serde_json::from_slice::<ws::WsMsg>(msg.as_bytes()) {
+            Ok(ws_msg) => {
+                process_public_ws_message(ws_msg, markets, &self.sequences, &self.book_events)
+            }
+            Err(e) => {
+                error!("Error parsing ws_msg: {msg}");
+
+                Err(MarketDataError::with_source(e))
+            }
+        }
+    }
+}
+
+/// Converts market name to OKX instrument id, `BTC/USDT` to `BTC-USDT`
+fn native_symbol(market: &str) -> String {
+    market.to_uppercase().replace('/', "-")
+}
+
+/// Returns the market name OKX instrument was subscribed as
+fn internal_symbol(
+    markets: &HashMap<Box<str>, PlainOrderbook<f64>>,
+    inst_id: &str,
+) -> Option<Box<str>> {
+    markets
+        .keys()
+        .find(|k| native_symbol(k) == inst_id)
+        .cloned()
+}
+
+#[inline]
+fn process_public_ws_message(
+    ws_msg: ws::WsMsg,
+    markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    sequences: &SequenceTracker,
+    book_events: &BookEmitter,
+) -> Result<Option<MarketEvent>, MarketDataError> {
+    match ws_msg {
+        ws::WsMsg::Event(event) if event.event == "error" => Err(MarketDataError::convert_error(
+            format!("OKX error {}: {}", event.code, event.msg),
+        )),
+        ws::WsMsg::Event(_) => Ok(None),
+        ws::WsMsg::Books(books_msg) => {
+            let symbol = match internal_symbol(markets, books_msg.arg.inst_id) {
+                Some(symbol) => symbol,
+                None => {
+                    warn!("No symbol mapping found for {}", books_msg.arg.inst_id);
+                    return Ok(None);
+                }
+            };
+            let mut book = match books_msg.data.into_iter().next() {
+                Some(book) => book,
+                None => return Ok(None),
+            };
+            let bids = PriceLevelsVec::from_tuples_vec_unsorted(&mut book.bids);
+            let asks = PriceLevelsVec::from_tuples_vec_unsorted(&mut book.asks);
+
+            match books_msg.action {
+                // `books5` sends the whole top of book every time
+                None | Some("snapshot") => {
+                    if books_msg.action.is_some() {
+                        sequences.reset(&symbol, book.seq_id as u64);
+                    }
+
+                    let orderbook = PlainOrderbook {
+                        bids,
+                        asks,
+                        time: book.ts as f64,
+                        max_depth: None,
+                    };
+
+                    let event = book_events.event(symbol.clone(), &orderbook);
+                    markets.insert(symbol, orderbook);
+
+                    Ok(event)
+                }
+                Some("update") => {
+                    // Sequence ids may start over lower after OKX maintenance
+                    if book.seq_id < book.prev_seq_id {
+                        sequences.reset(&symbol, book.seq_id as u64);
+                    } else {
+                        let apply = sequences
+                            .check(&symbol, book.prev_seq_id as u64 + 1, book.seq_id as u64)
+                            .map_err(MarketDataError::with_source)?;
+                        if !apply {
+                            return Ok(None);
+                        }
+                    }
+
+                    match markets.get_mut(&symbol) {
+                        Some(orderbook) => {
+                            orderbook.update_with_timestamp(&bids, &asks, book.ts as f64);
+
+                            Ok(book_events.delta(symbol, orderbook, &bids, &asks))
+                        }
+                        None => Ok(None),
+                    }
+                }
+                Some(other) => Err(MarketDataError::with_source(UnknownVariantError {
+                    variant: other.to_string(),
+                })),
+            }
+        }
+        ws::WsMsg::Trades(trades_msg) => {
+            let symbol = internal_symbol(markets, trades_msg.arg.inst_id)
+                .unwrap_or_else(|| Box::from(trades_msg.arg.inst_id));
+            let trades = trades_msg
+                .data
+                .iter()
+                .map(|trade| {
+                    Ok(botvana::market::trade::Trade::new(
+                        trade.px.parse::<f64>()?,
+                        trade.sz.parse::<f64>()?,
+                        Utc.timestamp_millis(trade.ts),
+                    ))
+                })
+                .collect::<Result<Box<[_]>, std::num::ParseFloatError>>()
+                .map_err(MarketDataError::with_source)?;
+
+            Ok(Some(MarketEvent::trades(symbol, trades)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn books_msg(action: &str, prev_seq_id: i64, seq_id: i64) -> String {
+        format!(
+            r#"{{
+                "arg": {{"channel": "books", "instId": "BTC-USDT"}},
+                "action": "{action}",
+                "data": [{{
+                    "asks": [["8476.98", "415", "0", "13"], ["8477", "7", "0", "2"]],
+                    "bids": [["8476.97", "256", "0", "12"], ["8475.55", "101", "0", "1"]],
+                    "ts": "1597026383085",
+                    "checksum": -855196043,
+                    "prevSeqId": {prev_seq_id},
+                    "seqId": {seq_id}
+                }}]
+            }}"#
+        )
+    }
+
+    #[test]
+    fn test_native_symbol() {
+        assert_eq!("BTC-USDT", native_symbol("btc/usdt"));
+        assert_eq!("BTC-USDT-SWAP", native_symbol("BTC-USDT-SWAP"));
+    }
+
+    #[test]
+    fn test_process_ws_msg_snapshot_and_update() {
+        let okx = Okx::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTC/USDT"), PlainOrderbook::new());
+
+        let event = okx
+            .process_ws_msg(&books_msg("snapshot", -1, 100), &mut markets)
+            .unwrap();
+        assert!(event.is_some());
+        assert_eq!(vec![8475.55, 8476.97], markets["BTC/USDT"].bids.price_vec);
+        assert_eq!(Some(100), okx.sequences.last_update_id("BTC/USDT"));
+
+        let update = r#"{
+            "arg": {"channel": "books", "instId": "BTC-USDT"},
+            "action": "update",
+            "data": [{
+                "asks": [["8477", "0", "0", "0"]],
+                "bids": [["8476.5", "3", "0", "1"]],
+                "ts": "1597026383086",
+                "prevSeqId": 100,
+                "seqId": 102
+            }]
+        }"#;
+        match okx
+            .process_ws_msg(update, &mut markets)
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::OrderbookUpdate(market, orderbook)) => {
+                assert_eq!("BTC/USDT", &*market);
+                assert_eq!(vec![8475.55, 8476.5, 8476.97], orderbook.bids.price_vec);
+                assert_eq!(vec![8476.98], orderbook.asks.price_vec);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+
+        let err = okx
+            .process_ws_msg(&books_msg("update", 105, 110), &mut markets)
+            .unwrap_err();
+        assert_eq!(Some("BTC/USDT"), err.out_of_sync_market());
+    }
+
+    #[test]
+    fn test_process_ws_msg_books5() {
+        let mut okx = Okx::default();
+        okx.set_book_events(BookEvents::Bbo);
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTC/USDT"), PlainOrderbook::new());
+
+        let msg = r#"{
+            "arg": {"channel": "books5", "instId": "BTC-USDT"},
+            "data": [{
+                "asks": [["8476.98", "415", "0", "13"]],
+                "bids": [["8476.97", "256", "0", "12"]],
+                "instId": "BTC-USDT",
+                "ts": "1597026383085",
+                "seqId": 123456
+            }]
+        }"#;
+
+        let subscribe = okx.subscribe_msgs(&["BTC/USDT"]);
+        assert!(subscribe[0].contains("books5"));
+        assert!(okx.process_ws_msg(msg, &mut markets).unwrap().is_some());
+        assert_eq!(vec![8476.98], markets["BTC/USDT"].asks.price_vec);
+    }
+
+    #[test]
+    fn test_process_ws_msg_trades() {
+        let okx = Okx::default();
+        let mut markets = HashMap::new();
+        markets.insert(Box::from("BTC/USDT"), PlainOrderbook::new());
+
+        let msg = r#"{
+            "arg": {"channel": "trades", "instId": "BTC-USDT"},
+            "data": [{
+                "instId": "BTC-USDT",
+                "tradeId": "130639474",
+                "px": "42219.9",
+                "sz": "0.12060306",
+                "side": "buy",
+                "ts": "1630048897897"
+            }]
+        }"#;
+
+        match okx
+            .process_ws_msg(msg, &mut markets)
+            .unwrap()
+            .map(|event| event.r#type)
+        {
+            Some(MarketEventType::Trades(market, trades)) => {
+                assert_eq!("BTC/USDT", &*market);
+                assert_eq!(42219.9, trades[0].price);
+                assert_eq!(0.12060306, trades[0].size);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_process_ws_msg_pong_and_error() {
+        let okx = Okx::default();
+
+        assert!(okx
+            .process_ws_msg("pong", &mut HashMap::new())
+            .unwrap()
+            .is_none());
+        assert!(okx
+            .process_ws_msg(
+                r#"{"event": "error", "code": "60012", "msg": "Invalid request"}"#,
+                &mut HashMap::new()
+            )
+            .is_err());
+    }
+}
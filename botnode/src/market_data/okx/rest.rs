@@ -0,0 +1,159 @@
+use serde::{Deserialize, Deserializer};
+
+use botvana::{exchange::ExchangeId, market::orderbook::*};
+
+/// Envelope of every OKX v5 REST response
+#[derive(Debug, Deserialize)]
+pub struct Response<T> {
+    /// `0` on success
+    pub code: String,
+    pub msg: String,
+    #[serde(default = "Vec::new")]
+    pub data: Vec<T>,
+}
+
+/// Instrument returned by `/api/v5/public/instruments`
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Instrument<'a> {
+    /// `SPOT` or `SWAP`
+    pub inst_type: &'a str,
+    pub inst_id: &'a str,
+    /// Empty on swaps
+    pub base_ccy: &'a str,
+    pub quote_ccy: &'a str,
+    pub tick_sz: &'a str,
+    pub lot_sz: &'a str,
+    pub state: &'a str,
+}
+
+impl<'a> TryFrom<&Instrument<'a>> for botvana::market::Market {
+    type Error = Box<dyn std::error::Error>;
+
+    fn try_from(instrument: &Instrument<'a>) -> Result<Self, Self::Error> {
+        let (name, r#type) = match instrument.inst_type {
+            "SPOT" => (
+                format!("{}/{}", instrument.base_ccy, instrument.quote_ccy),
+                botvana::market::MarketType::Spot(botvana::market::SpotMarket {
+                    base: instrument.base_ccy.to_string(),
+                    quote: instrument.quote_ccy.to_string(),
+                }),
+            ),
+            "SWAP" => (
+                instrument.inst_id.to_string(),
+                botvana::market::MarketType::Futures(botvana::market::FuturesMarket::perpetual()),
+            ),
+            other => return Err(format!("Unsupported instrument type: {other}").into()),
+        };
+
+        Ok(Self {
+            exchange: ExchangeId::Okx,
+            name,
+            native_symbol: instrument.inst_id.to_string(),
+            size_increment: instrument.lot_sz.parse()?,
+            price_increment: instrument.tick_sz.parse()?,
+            r#type,
+        })
+    }
+}
+
+/// Orderbook returned by `/api/v5/market/books`
+#[derive(Debug, Deserialize)]
+pub struct OrderbookSnapshot {
+    #[serde(deserialize_with = "deserialize_into_price_levels_vec")]
+    pub bids: PriceLevelsVec<f64>,
+    #[serde(deserialize_with = "deserialize_into_price_levels_vec")]
+    pub asks: PriceLevelsVec<f64>,
+    /// Milliseconds timestamp
+    #[serde(deserialize_with = "super::ws::de_number_from_str")]
+    pub ts: i64,
+}
+
+fn deserialize_into_price_levels_vec<'de, D>(
+    deserializer: D,
+) -> Result<PriceLevelsVec<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut levels = super::ws::de_price_levels(deserializer)?;
+
+    Ok(PriceLevelsVec::from_tuples_vec_unsorted(&mut levels))
+}
+
+/// Server time returned by `/api/v5/public/time`
+#[derive(Debug, Deserialize)]
+pub struct ServerTime {
+    /// Milliseconds timestamp
+    #[serde(deserialize_with = "super::ws::de_number_from_str")]
+    pub ts: i64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_instruments() {
+        let sample = r#"{
+            "code": "0",
+            "msg": "",
+            "data": [{
+                "instType": "SPOT",
+                "instId": "BTC-USDT",
+                "uly": "",
+                "baseCcy": "BTC",
+                "quoteCcy": "USDT",
+                "settleCcy": "",
+                "ctVal": "",
+                "tickSz": "0.1",
+                "lotSz": "0.00000001",
+                "minSz": "0.00001",
+                "state": "live"
+            }, {
+                "instType": "SWAP",
+                "instId": "BTC-USDT-SWAP",
+                "uly": "BTC-USDT",
+                "baseCcy": "",
+                "quoteCcy": "",
+                "settleCcy": "USDT",
+                "ctVal": "0.01",
+                "tickSz": "0.1",
+                "lotSz": "1",
+                "minSz": "1",
+                "state": "live"
+            }]
+        }"#;
+
+        let res = serde_json::from_str::<Response<Instrument>>(sample).unwrap();
+        let spot = botvana::market::Market::try_from(&res.data[0]).unwrap();
+        let swap = botvana::market::Market::try_from(&res.data[1]).unwrap();
+
+        assert_eq!("BTC/USDT", spot.name);
+        assert_eq!("BTC-USDT", spot.native_symbol);
+        assert_eq!(0.1, spot.price_increment);
+        assert_eq!(0.00000001, spot.size_increment);
+        assert_eq!("BTC-USDT-SWAP", swap.name);
+        assert_eq!(1.0, swap.size_increment);
+    }
+
+    #[test]
+    fn test_parse_orderbook_snapshot() {
+        let sample = r#"{
+            "code": "0",
+            "msg": "",
+            "data": [{
+                "asks": [["41006.8", "0.60038921", "0", "1"]],
+                "bids": [["41006.3", "0.30178218", "0", "2"], ["41006.1", "0.05", "0", "1"]],
+                "ts": "1629966436396"
+            }]
+        }"#;
+
+        let res = serde_json::from_str::<Response<OrderbookSnapshot>>(sample).unwrap();
+        let snapshot = &res.data[0];
+
+        assert_eq!(vec![41006.1, 41006.3], snapshot.bids.price_vec);
+        assert_eq!(vec![0.05, 0.30178218], snapshot.bids.size_vec);
+        assert_eq!(vec![41006.8], snapshot.asks.price_vec);
+        assert_eq!(1629966436396, snapshot.ts);
+    }
+}
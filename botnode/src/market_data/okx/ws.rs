@@ -0,0 +1,182 @@
+use serde::{Deserialize, Deserializer};
+
+/// OKX public Websocket message
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum WsMsg<'a> {
+    #[serde(borrow)]
+    Books(BooksMsg<'a>),
+    #[serde(borrow)]
+    Trades(TradesMsg<'a>),
+    #[serde(borrow)]
+    Event(Event<'a>),
+}
+
+/// Reply to `subscribe` and `unsubscribe` requests
+#[derive(Debug, Deserialize)]
+pub struct Event<'a> {
+    /// `subscribe`, `unsubscribe` or `error`
+    pub event: &'a str,
+    #[serde(default)]
+    pub code: &'a str,
+    #[serde(default)]
+    pub msg: &'a str,
+}
+
+/// Message of the `books` and `books5` channels
+#[derive(Debug, Deserialize)]
+pub struct BooksMsg<'a> {
+    #[serde(borrow)]
+    pub arg: Arg<'a>,
+    /// `snapshot` or `update` on the `books` channel, `books5` always sends
+    /// the whole book without action
+    pub action: Option<&'a str>,
+    pub data: Vec<Book>,
+}
+
+/// Message of the `trades` channel
+#[derive(Debug, Deserialize)]
+pub struct TradesMsg<'a> {
+    #[serde(borrow)]
+    pub arg: Arg<'a>,
+    #[serde(borrow)]
+    pub data: Vec<Trade<'a>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Arg<'a> {
+    pub inst_id: &'a str,
+}
+
+/// Orderbook of the `books` and `books5` channels
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Book {
+    /// Size of zero removes the level
+    #[serde(deserialize_with = "de_price_levels")]
+    pub bids: Vec<(f64, f64)>,
+    #[serde(deserialize_with = "de_price_levels")]
+    pub asks: Vec<(f64, f64)>,
+    /// Milliseconds timestamp
+    #[serde(deserialize_with = "de_number_from_str")]
+    pub ts: i64,
+    /// Sequence of the previous message, -1 on snapshots
+    #[serde(default)]
+    pub prev_seq_id: i64,
+    #[serde(default)]
+    pub seq_id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Trade<'a> {
+    pub px: &'a str,
+    pub sz: &'a str,
+    /// Milliseconds timestamp
+    #[serde(deserialize_with = "de_number_from_str")]
+    pub ts: i64,
+}
+
+/// Deserializes levels encoded as `[price, size, liquidated orders, orders]`
+/// arrays of strings
+pub(super) fn de_price_levels<'de, D>(deserializer: D) -> Result<Vec<(f64, f64)>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let levels: Vec<Box<[&'de str]>> = Deserialize::deserialize(deserializer)?;
+
+    levels
+        .iter()
+        .map(|level| match &level[..] {
+            [price, size, ..] => Ok((
+                price.parse::<f64>().map_err(serde::de::Error::custom)?,
+                size.parse::<f64>().map_err(serde::de::Error::custom)?,
+            )),
+            _ => Err(serde::de::Error::invalid_length(
+                level.len(),
+                &"price and size",
+            )),
+        })
+        .collect()
+}
+
+pub(super) fn de_number_from_str<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let number: &'de str = Deserialize::deserialize(deserializer)?;
+
+    number.parse().map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_books_snapshot() {
+        let msg = r#"{
+            "arg": {"channel": "books", "instId": "BTC-USDT"},
+            "action": "snapshot",
+            "data": [{
+                "asks": [["8476.98", "415", "0", "13"], ["8477", "7", "0", "2"]],
+                "bids": [["8476.97", "256", "0", "12"]],
+                "ts": "1597026383085",
+                "checksum": -855196043,
+                "prevSeqId": -1,
+                "seqId": 123456
+            }]
+        }"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::Books(books) => {
+                assert_eq!("BTC-USDT", books.arg.inst_id);
+                assert_eq!(Some("snapshot"), books.action);
+                assert_eq!(vec![(8476.98, 415.0), (8477.0, 7.0)], books.data[0].asks);
+                assert_eq!(1597026383085, books.data[0].ts);
+                assert_eq!(123456, books.data[0].seq_id);
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_trades() {
+        let msg = r#"{
+            "arg": {"channel": "trades", "instId": "BTC-USDT"},
+            "data": [{
+                "instId": "BTC-USDT",
+                "tradeId": "130639474",
+                "px": "42219.9",
+                "sz": "0.12060306",
+                "side": "buy",
+                "ts": "1630048897897"
+            }]
+        }"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::Trades(trades) => {
+                assert_eq!("BTC-USDT", trades.arg.inst_id);
+                assert_eq!(1630048897897, trades.data[0].ts);
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_error_event() {
+        let msg = r#"{"event": "error", "code": "60012", "msg": "Invalid request"}"#;
+
+        match serde_json::from_str::<WsMsg>(msg).unwrap() {
+            WsMsg::Event(event) => assert_eq!("60012", event.code),
+            other => panic!("unexpected message {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_short_level() {
+        let book = r#"{"asks": [["8476.98"]], "bids": [], "ts": "1"}"#;
+
+        assert!(serde_json::from_str::<Book>(book).is_err());
+    }
+}
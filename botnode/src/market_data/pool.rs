@@ -0,0 +1,174 @@
+//! Buffers reused across websocket messages
+//!
+//! Orderbook updates used to allocate new price levels for every message and
+//! new boxes for the emitted orderbooks and deltas. The market data engine
+//! runs on its own executor thread, which keeps a pool of these buffers: the
+//! adapters take cleared price levels and payload boxes from it and the
+//! engine gives the payloads back once the event was copied onto the
+//! consumers' channels. Buffers keep their capacity, so a book of steady
+//! depth stops allocating after the first messages.
+//!
+//! The pool holds at most [`POOL_CAPACITY`] buffers of each kind, buffers
+//! given back to a full pool are dropped.
+
+use std::cell::RefCell;
+
+use botvana::market::{
+    event::{MarketEvent, MarketEventType},
+    orderbook::{OrderbookDelta, PlainOrderbook, PriceLevelsVec},
+};
+
+/// Buffers of each kind kept by the pool
+pub const POOL_CAPACITY: usize = 16;
+
+thread_local! {
+    /// Pool of the executor thread
+    static POOL: RefCell<EventPool> = RefCell::new(EventPool::default());
+}
+
+/// Free price levels and event payloads
+#[derive(Debug, Default)]
+pub struct EventPool {
+    levels: Vec<PriceLevelsVec<f64>>,
+    orderbooks: Vec<Box<PlainOrderbook<f64>>>,
+    deltas: Vec<Box<OrderbookDelta<f64>>>,
+}
+
+impl EventPool {
+    /// Returns empty price levels
+    pub fn levels(&mut self) -> PriceLevelsVec<f64> {
+        self.levels.pop().unwrap_or_default()
+    }
+
+    /// Gives back price levels
+    pub fn put_levels(&mut self, mut levels: PriceLevelsVec<f64>) {
+        if self.levels.len() < POOL_CAPACITY {
+            levels.clear();
+            self.levels.push(levels);
+        }
+    }
+
+    /// Returns boxed copy of the orderbook
+    pub fn orderbook(&mut self, orderbook: &PlainOrderbook<f64>) -> Box<PlainOrderbook<f64>> {
+        match self.orderbooks.pop() {
+            Some(mut boxed) => {
+                boxed.bids.copy_from(&orderbook.bids);
+                boxed.asks.copy_from(&orderbook.asks);
+                boxed.time = orderbook.time;
+                boxed.max_depth = orderbook.max_depth;
+                boxed
+            }
+            None => Box::new(orderbook.clone()),
+        }
+    }
+
+    /// Returns boxed delta with copies of the levels
+    pub fn delta(
+        &mut self,
+        bids: &PriceLevelsVec<f64>,
+        asks: &PriceLevelsVec<f64>,
+        time: f64,
+        sequence: u64,
+    ) -> Box<OrderbookDelta<f64>> {
+        let mut delta = self.deltas.pop().unwrap_or_default();
+        delta.bids.copy_from(bids);
+        delta.asks.copy_from(asks);
+        delta.time = time;
+        delta.sequence = sequence;
+
+        delta
+    }
+
+    /// Takes back the payload of the event
+    pub fn recycle(&mut self, event: MarketEvent) {
+        match event.r#type {
+            MarketEventType::OrderbookUpdate(_, orderbook) => {
+                if self.orderbooks.len() < POOL_CAPACITY {
+                    self.orderbooks.push(orderbook);
+                }
+            }
+            MarketEventType::OrderbookDelta(_, delta) => {
+                if self.deltas.len() < POOL_CAPACITY {
+                    self.deltas.push(delta);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Runs the closure with the pool of the current thread
+pub fn with<R>(f: impl FnOnce(&mut EventPool) -> R) -> R {
+    POOL.with(|pool| f(&mut pool.borrow_mut()))
+}
+
+/// Returns empty price levels from the pool of the current thread
+pub fn levels() -> PriceLevelsVec<f64> {
+    with(|pool| pool.levels())
+}
+
+/// Gives back price levels to the pool of the current thread
+pub fn put_levels(levels: PriceLevelsVec<f64>) {
+    with(|pool| pool.put_levels(levels))
+}
+
+/// Gives back the payload of the event to the pool of the current thread
+pub fn recycle(event: MarketEvent) {
+    with(|pool| pool.recycle(event))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reuse_payloads() {
+        let mut pool = EventPool::default();
+        let orderbook = PlainOrderbook {
+            bids: PriceLevelsVec::from_tuples_vec(&[(99.0, 1.0), (100.0, 2.0)]),
+            asks: PriceLevelsVec::from_tuples_vec(&[(101.0, 1.5)]),
+            time: 1.0,
+            max_depth: None,
+        };
+
+        let boxed = pool.orderbook(&orderbook);
+        let ptr = &*boxed as *const PlainOrderbook<f64>;
+        let bids_ptr = boxed.bids.price_vec.as_ptr();
+        pool.recycle(MarketEvent::orderbook_update(Box::from("BTC-PERP"), boxed));
+
+        let mut updated = orderbook.clone();
+        updated.bids = PriceLevelsVec::from_tuples_vec(&[(100.0, 3.0)]);
+        updated.time = 2.0;
+        let boxed = pool.orderbook(&updated);
+
+        assert_eq!(ptr, &*boxed as *const _);
+        assert_eq!(bids_ptr, boxed.bids.price_vec.as_ptr());
+        assert_eq!(vec![100.0], boxed.bids.price_vec);
+        assert_eq!(vec![3.0], boxed.bids.size_vec);
+        assert_eq!(vec![101.0], boxed.asks.price_vec);
+        assert_eq!(2.0, boxed.time);
+
+        let delta = pool.delta(&updated.bids, &updated.asks, 2.0, 1);
+        let ptr = &*delta as *const OrderbookDelta<f64>;
+        pool.recycle(MarketEvent::orderbook_delta(Box::from("BTC-PERP"), delta));
+        let delta = pool.delta(&orderbook.bids, &orderbook.asks, 3.0, 2);
+
+        assert_eq!(ptr, &*delta as *const _);
+        assert_eq!(vec![99.0, 100.0], delta.bids.price_vec);
+        assert_eq!(2, delta.sequence);
+    }
+
+    #[test]
+    fn test_levels_capacity() {
+        let mut pool = EventPool::default();
+
+        for _ in 0..POOL_CAPACITY + 1 {
+            pool.put_levels(PriceLevelsVec::from_tuples_vec(&[(100.0, 1.0)]));
+        }
+        assert_eq!(POOL_CAPACITY, pool.levels.len());
+
+        let levels = pool.levels();
+        assert_eq!(0, levels.len());
+        assert!(levels.price_vec.capacity() >= 1);
+    }
+}
@@ -0,0 +1,246 @@
+//! Market data adapter registry
+//!
+//! Maps exchange names as used in the configuration to constructors of the
+//! matching adapters, so the control engine can start any configured
+//! exchange without knowing the adapter types.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    bus::Subscriber,
+    config::MarketDataConfig,
+    control::ControlCommand,
+    market_data::{
+        adapter::{
+            BoxedMarketDataAdapter, DynMarketDataAdapter, MarketDataAdapter, RestMarketDataAdapter,
+        },
+        symbols::SymbolMapper,
+        MarketDataEngine,
+    },
+    prelude::*,
+    rt::EngineExecutor,
+};
+
+/// Maximum number of consumers of every market data engine
+pub const MARKET_DATA_CONSUMERS: usize = 7;
+
+/// Everything needed to spawn a market data engine
+pub struct MarketDataSpawn<'a> {
+    /// Exchange name as used in the configuration
+    pub exchange: &'a str,
+    /// Core and settings of the engine's executor
+    pub executor: EngineExecutor,
+    pub config_rx: spsc_queue::Consumer<BotConfiguration>,
+    /// Server commands changing the subscribed markets
+    pub commands: Subscriber<ControlCommand>,
+    /// Mapping shared by the market data engines of all exchanges
+    pub symbols: SymbolMapper,
+    /// Reconnect backoff and orderbook depth
+    pub config: MarketDataConfig,
+    /// Receivers of the consumers, the engine inserts its receiver into each
+    pub consumers: &'a mut [ConsumersMap<Box<str>, MarketEvent>],
+    /// Audit channel connection changes are recorded to
+    pub audit_tx: Option<crate::audit::AuditProducer>,
+    pub shutdown: Shutdown,
+}
+
+/// Creates market data adapter
+pub type MarketDataConstructor = fn() -> BoxedMarketDataAdapter;
+
+/// Market data adapter constructors by exchange name
+#[derive(Clone)]
+pub struct MarketDataRegistry {
+    constructors: BTreeMap<String, MarketDataConstructor>,
+}
+
+impl MarketDataRegistry {
+    /// Creates registry without any adapters
+    pub fn empty() -> Self {
+        Self {
+            constructors: BTreeMap::new(),
+        }
+    }
+
+    /// Registers constructor under given exchange name, replacing the
+    /// previous one
+    pub fn register(&mut self, exchange: &str, constructor: MarketDataConstructor) {
+        self.constructors.insert(exchange.to_string(), constructor);
+    }
+
+    /// Registers default-constructed adapter under given exchange name
+    pub fn register_adapter<A: DynMarketDataAdapter + Default + 'static>(
+        &mut self,
+        exchange: &str,
+    ) {
+        self.register(exchange, || -> BoxedMarketDataAdapter {
+            Box::new(A::default())
+        });
+    }
+
+    /// Creates adapter of the exchange
+    pub fn create(&self, exchange: &str) -> Option<BoxedMarketDataAdapter> {
+        self.constructors
+            .get(exchange)
+            .map(|constructor| constructor())
+    }
+
+    /// Returns whether there is an adapter for the exchange
+    pub fn contains(&self, exchange: &str) -> bool {
+        self.constructors.contains_key(exchange)
+    }
+
+    /// Returns names of all registered exchanges
+    pub fn exchanges(&self) -> impl Iterator<Item = &str> {
+        self.constructors.keys().map(String::as_str)
+    }
+
+    /// Spawns market data engine of the exchange, replaying its recordings
+    /// when configured
+    pub fn spawn(&self, spawn: MarketDataSpawn<'_>) -> Result<SpawnedEngine, StartEngineError> {
+        #[cfg(feature = "recorder")]
+        if let Some(replay) = spawn.config.replay.clone() {
+            let exchange = spawn
+                .exchange
+                .parse::<ExchangeId>()
+                .map_err(|e| StartEngineError { source: e.into() })?;
+            let adapter =
+                crate::market_data::replay::ReplayAdapter::new(exchange, spawn.exchange, replay);
+
+            return spawn_market_data_engine(spawn, exchange, adapter);
+        }
+
+        let adapter = self
+            .create(spawn.exchange)
+            .ok_or_else(|| StartEngineError {
+                source: format!("Unknown exchange {}", spawn.exchange).into(),
+            })?;
+        let exchange = RestMarketDataAdapter::exchange(&adapter);
+
+        spawn_market_data_engine(spawn, exchange, adapter)
+    }
+}
+
+fn spawn_market_data_engine<A>(
+    spawn: MarketDataSpawn<'_>,
+    exchange: ExchangeId,
+    adapter: A,
+) -> Result<SpawnedEngine, StartEngineError>
+where
+    A: MarketDataAdapter<MARKET_DATA_CONSUMERS> + Send + 'static,
+{
+    let r#type = EngineType::MarketDataEngine(exchange);
+
+    let mut engine = MarketDataEngine::<_, MARKET_DATA_CONSUMERS>::new(spawn.config_rx, adapter)
+        .with_symbols(spawn.symbols.exchange(exchange))
+        .with_config(spawn.config)
+        .with_commands(spawn.commands);
+    if let Some(audit_tx) = spawn.audit_tx {
+        engine = engine.with_audit(audit_tx);
+    }
+
+    spawn.consumers.iter_mut().for_each(|rx| {
+        rx.insert(Box::from(spawn.exchange), engine.data_rx());
+    });
+    let status_rx = engine.status_rx();
+
+    Ok(SpawnedEngine {
+        r#type,
+        status_rx,
+        handle: spawn_engine(spawn.executor, engine, spawn.shutdown)?,
+    })
+}
+
+impl Default for MarketDataRegistry {
+    /// Returns registry with all adapters compiled into botnode
+    fn default() -> Self {
+        #[allow(unused_mut)]
+        let mut registry = Self::empty();
+
+        #[cfg(feature = "ftx")]
+        registry.register_adapter::<crate::market_data::ftx::Ftx>("ftx");
+        #[cfg(feature = "binance")]
+        registry.register_adapter::<crate::market_data::binance::Binance>("binance");
+        #[cfg(feature = "serum")]
+        registry.register_adapter::<crate::market_data::serum::Serum>("serum");
+        #[cfg(feature = "coinbase")]
+        registry.register_adapter::<crate::market_data::coinbase::Coinbase>("coinbase");
+        #[cfg(feature = "kraken")]
+        registry.register_adapter::<crate::market_data::kraken::Kraken>("kraken");
+        #[cfg(feature = "bybit")]
+        registry.register_adapter::<crate::market_data::bybit::Bybit>("bybit");
+        #[cfg(feature = "okx")]
+        registry.register_adapter::<crate::market_data::okx::Okx>("okx");
+        #[cfg(feature = "deribit")]
+        registry.register_adapter::<crate::market_data::deribit::Deribit>("deribit");
+        #[cfg(feature = "bitstamp")]
+        registry.register_adapter::<crate::market_data::bitstamp::Bitstamp>("bitstamp");
+        #[cfg(feature = "gemini")]
+        registry.register_adapter::<crate::market_data::gemini::Gemini>("gemini");
+
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_unknown_exchange() {
+        let registry = MarketDataRegistry::empty();
+        let (_config_tx, config_rx) = spsc_queue::make(1);
+
+        let res = registry.spawn(MarketDataSpawn {
+            exchange: "unknown",
+            executor: 0.into(),
+            config_rx,
+            commands: Subscriber::default(),
+            symbols: SymbolMapper::default(),
+            config: MarketDataConfig::default(),
+            consumers: &mut [],
+            audit_tx: None,
+            shutdown: Shutdown::new(),
+        });
+
+        assert!(!registry.contains("unknown"));
+        assert!(res.is_err());
+    }
+
+    #[cfg(feature = "all-exchanges")]
+    #[test]
+    fn test_registry_default_exchanges() {
+        let registry = MarketDataRegistry::default();
+
+        assert_eq!(
+            vec![
+                "binance", "bitstamp", "bybit", "coinbase", "deribit", "ftx", "gemini", "kraken",
+                "okx", "serum"
+            ],
+            registry.exchanges().collect::<Vec<_>>()
+        );
+
+        // Heterogeneous adapters behind the same type
+        let adapters: Vec<_> = registry
+            .exchanges()
+            .filter_map(|exchange| registry.create(exchange))
+            .collect();
+        assert_eq!(
+            vec![
+                botvana::exchange::ExchangeId::BinanceSpot,
+                botvana::exchange::ExchangeId::Bitstamp,
+                botvana::exchange::ExchangeId::Bybit,
+                botvana::exchange::ExchangeId::Coinbase,
+                botvana::exchange::ExchangeId::Deribit,
+                botvana::exchange::ExchangeId::Ftx,
+                botvana::exchange::ExchangeId::Gemini,
+                botvana::exchange::ExchangeId::Kraken,
+                botvana::exchange::ExchangeId::Okx,
+                botvana::exchange::ExchangeId::Serum
+            ],
+            adapters
+                .iter()
+                .map(RestMarketDataAdapter::exchange)
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,315 @@
+//! Replay of recorded market data
+//!
+//! [`ReplayAdapter`] stands in for the adapter of an exchange and emits the
+//! events the [recorder](crate::recorder) wrote for it, so strategies run
+//! offline through the same engines as live. Events are replayed at their
+//! recorded pace, accelerated by a factor or as fast as the consumers keep
+//! up. They are stamped with the time they are replayed at, the exchange
+//! times of trades and books are kept.
+
+use std::{
+    path::PathBuf,
+    time::{Instant, SystemTime},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::prelude::*;
+use crate::{
+    config::MarketDataConfig,
+    market_data::{subscriptions::Subscriptions, symbols::ExchangeSymbols},
+    prelude::*,
+    recorder::read_recording,
+    rt::timer::sleep,
+};
+use botvana::market::MarketVec;
+
+/// Time waited for the consumers to make room when replaying as fast as
+/// possible
+const BACKPRESSURE_WAIT: Duration = Duration::from_micros(100);
+
+/// Longest sleep between shutdown checks
+const MAX_SLEEP: Duration = Duration::from_millis(100);
+
+/// Pace of the replay
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplaySpeed {
+    /// Events are as far apart as when they were recorded
+    Original,
+    /// Gaps between events are shortened by the factor
+    Accelerated(f64),
+    /// Events are emitted as soon as the consumers have room for them
+    Max,
+}
+
+impl ReplaySpeed {
+    /// Returns the speed of replaying `factor` times faster than recorded,
+    /// as fast as possible when the factor is zero
+    pub fn from_factor(factor: f64) -> Self {
+        if factor == 0.0 {
+            Self::Max
+        } else if factor == 1.0 {
+            Self::Original
+        } else {
+            Self::Accelerated(factor)
+        }
+    }
+}
+
+impl Default for ReplaySpeed {
+    fn default() -> Self {
+        Self::Original
+    }
+}
+
+/// Replay configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReplayConfig {
+    /// Directory of the recordings
+    pub dir: PathBuf,
+    pub speed: ReplaySpeed,
+}
+
+impl Default for ReplayConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("recordings"),
+            speed: ReplaySpeed::default(),
+        }
+    }
+}
+
+impl ReplayConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        match self.speed {
+            ReplaySpeed::Accelerated(factor) if factor.is_nan() || factor <= 0.0 => {
+                Err(format!("acceleration must be positive, got {factor}"))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Schedules replayed events relative to the first one
+#[derive(Debug)]
+pub struct ReplayClock {
+    speed: ReplaySpeed,
+    /// Replay and recording time of the first event
+    start: Option<(Instant, SystemTime)>,
+}
+
+impl ReplayClock {
+    pub fn new(speed: ReplaySpeed) -> Self {
+        Self { speed, start: None }
+    }
+
+    /// Returns how long to wait before emitting event recorded at given time
+    pub fn delay(&mut self, recorded_at: SystemTime) -> Duration {
+        let factor = match self.speed {
+            ReplaySpeed::Original => 1.0,
+            ReplaySpeed::Accelerated(factor) => factor,
+            ReplaySpeed::Max => return Duration::ZERO,
+        };
+        let (started, first) = *self.start.get_or_insert((Instant::now(), recorded_at));
+        let offset = recorded_at.duration_since(first).unwrap_or_default();
+
+        offset.div_f64(factor).saturating_sub(started.elapsed())
+    }
+}
+
+/// Market data adapter replaying recordings of an exchange
+#[derive(Debug)]
+pub struct ReplayAdapter {
+    exchange: ExchangeId,
+    /// Exchange name the events were recorded under
+    recorded_as: Box<str>,
+    config: ReplayConfig,
+    metrics: AdapterMetrics,
+}
+
+impl ReplayAdapter {
+    pub fn new(exchange: ExchangeId, recorded_as: &str, config: ReplayConfig) -> Self {
+        Self {
+            exchange,
+            recorded_as: Box::from(recorded_as),
+            config,
+            metrics: AdapterMetrics::default(),
+        }
+    }
+
+    /// Returns the recorded events of the exchange
+    fn events(&self) -> Result<impl Iterator<Item = MarketEvent> + '_, MarketDataError> {
+        let recording = read_recording(&self.config.dir).map_err(MarketDataError::with_source)?;
+
+        Ok(recording.filter_map(move |recorded| match recorded {
+            Ok(recorded) if recorded.exchange == self.recorded_as => Some(recorded.event),
+            Ok(_) => None,
+            Err(e) => {
+                self.metrics.record_parse_error();
+                warn!("Skipping unreadable recorded event: {e}");
+                None
+            }
+        }))
+    }
+}
+
+#[async_trait(?Send)]
+impl<const TX_CAP: usize> MarketDataAdapter<TX_CAP> for ReplayAdapter {
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+
+    fn exchange(&self) -> ExchangeId {
+        self.exchange
+    }
+
+    fn metrics(&self) -> &AdapterMetrics {
+        &self.metrics
+    }
+
+    /// Returns the first markets recorded for the exchange
+    async fn fetch_markets(&self) -> Result<Box<MarketVec>, MarketDataError> {
+        self.events()?
+            .find_map(|event| match event.r#type {
+                MarketEventType::Markets(markets) => Some(markets),
+                _ => None,
+            })
+            .ok_or_else(|| {
+                MarketDataError::convert_error(format!(
+                    "no markets recorded for {}",
+                    self.recorded_as
+                ))
+            })
+    }
+
+    /// Replays the recordings once, there is nothing to reconnect to
+    async fn run_loop(
+        &mut self,
+        data_txs: crate::channels::ProducersArray<MarketEvent, TX_CAP>,
+        subscriptions: &mut Subscriptions,
+        config: MarketDataConfig,
+        symbols: &ExchangeSymbols,
+        shutdown: Shutdown,
+    ) -> Result<(), MarketDataError> {
+        self.run_exchange_connection_loop(&data_txs, subscriptions, &config, symbols, shutdown)
+            .await?;
+        self.metrics.record_disconnected();
+        info!("Replay of {} finished", self.recorded_as);
+
+        Ok(())
+    }
+
+    async fn run_exchange_connection_loop(
+        &mut self,
+        data_txs: &crate::channels::ProducersArray<MarketEvent, TX_CAP>,
+        subscriptions: &mut Subscriptions,
+        _config: &MarketDataConfig,
+        symbols: &ExchangeSymbols,
+        shutdown: Shutdown,
+    ) -> Result<Option<MarketEvent>, MarketDataError> {
+        let _token = shutdown
+            .delay_shutdown_token()
+            .map_err(MarketDataError::with_source)?;
+        // Recorded events carry the canonical names
+        let canonical = |subscriptions: &Subscriptions| -> Vec<Box<str>> {
+            subscriptions
+                .markets()
+                .iter()
+                .map(|market| Box::from(symbols.to_canonical(market)))
+                .collect()
+        };
+        let mut markets = canonical(subscriptions);
+        let mut clock = ReplayClock::new(self.config.speed);
+        info!(
+            "Replaying {} from {} at {:?} speed",
+            self.recorded_as,
+            self.config.dir.display(),
+            self.config.speed
+        );
+        self.metrics.record_connected();
+
+        for mut event in self.events()? {
+            if shutdown.shutdown_started() {
+                info!("Market data adapter shutting down");
+                return Ok(None);
+            }
+            if subscriptions.poll().is_some() {
+                markets = canonical(subscriptions);
+            }
+            // The engine emits the markets itself
+            let market = match event.market_mut() {
+                Some(market) => market,
+                None => continue,
+            };
+            if !markets.contains(market) {
+                continue;
+            }
+
+            // Long gaps are slept through in steps to notice shutdowns
+            loop {
+                let delay = clock.delay(event.timestamp);
+                if delay.is_zero() || shutdown.shutdown_started() {
+                    break;
+                }
+                sleep(delay.min(MAX_SLEEP)).await;
+            }
+            while data_txs
+                .occupancy()
+                .iter()
+                .any(|occupancy| *occupancy >= 1.0)
+            {
+                sleep(BACKPRESSURE_WAIT).await;
+            }
+
+            self.metrics.record_message();
+            event.timestamp = SystemTime::now();
+            data_txs
+                .push_value(event)
+                .map_err(MarketDataError::with_source)?;
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_replay_clock() {
+        let recorded = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+
+        let mut clock = ReplayClock::new(ReplaySpeed::Max);
+        clock.delay(recorded);
+        assert_eq!(
+            Duration::ZERO,
+            clock.delay(recorded + Duration::from_secs(10))
+        );
+
+        let mut clock = ReplayClock::new(ReplaySpeed::Accelerated(10.0));
+        assert_eq!(Duration::ZERO, clock.delay(recorded));
+        let delay = clock.delay(recorded + Duration::from_secs(10));
+        assert!(delay <= Duration::from_secs(1));
+        assert!(delay > Duration::from_millis(900));
+
+        // Events recorded out of order are emitted right away
+        assert_eq!(
+            Duration::ZERO,
+            clock.delay(recorded - Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn test_replay_config_validate() {
+        let config = ReplayConfig {
+            speed: ReplaySpeed::Accelerated(0.0),
+            ..ReplayConfig::default()
+        };
+
+        assert!(config.validate().is_err());
+        assert!(ReplayConfig::default().validate().is_ok());
+    }
+}
@@ -0,0 +1,105 @@
+//! Update id tracking of orderbooks streamed as diffs
+//!
+//! Exchanges streaming orderbook diffs number them, a book is only correct as
+//! long as every diff continues the previous one. Adapters record the last
+//! update id of every REST snapshot in a [`SequenceTracker`] and check each
+//! diff against it: diffs already included are dropped and a missed diff is
+//! reported as [`SequenceGap`], so the adapter loop resubscribes the market
+//! and seeds it from a new snapshot.
+
+use std::{cell::RefCell, collections::HashMap};
+
+use crate::market_data::error::SequenceGap;
+
+/// Last applied update id of each orderbook by market
+#[derive(Debug, Default)]
+pub struct SequenceTracker {
+    last_update_ids: RefCell<HashMap<Box<str>, u64>>,
+}
+
+impl SequenceTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts tracking the market from the snapshot with given last update id
+    pub fn reset(&self, market: &str, last_update_id: u64) {
+        self.last_update_ids
+            .borrow_mut()
+            .insert(Box::from(market), last_update_id);
+    }
+
+    /// Stops tracking the market until its next snapshot
+    pub fn forget(&self, market: &str) {
+        self.last_update_ids.borrow_mut().remove(market);
+    }
+
+    /// Stops tracking all markets
+    pub fn clear(&self) {
+        self.last_update_ids.borrow_mut().clear();
+    }
+
+    /// Returns the last applied update id of the market
+    pub fn last_update_id(&self, market: &str) -> Option<u64> {
+        self.last_update_ids.borrow().get(market).copied()
+    }
+
+    /// Checks diff of the market containing updates `first..=last`, returns
+    /// whether it is to be applied
+    ///
+    /// Diffs of markets without snapshot and diffs included in the snapshot
+    /// are not applied. On a gap the market is forgotten, so the diffs
+    /// following it are dropped until the next snapshot.
+    pub fn check(&self, market: &str, first: u64, last: u64) -> Result<bool, SequenceGap> {
+        let mut last_update_ids = self.last_update_ids.borrow_mut();
+        let last_update_id = match last_update_ids.get_mut(market) {
+            Some(last_update_id) => last_update_id,
+            None => return Ok(false),
+        };
+
+        if last <= *last_update_id {
+            return Ok(false);
+        }
+        if first > *last_update_id + 1 {
+            let expected = *last_update_id + 1;
+            last_update_ids.remove(market);
+
+            return Err(SequenceGap {
+                market: Box::from(market),
+                expected,
+                got: first,
+            });
+        }
+        *last_update_id = last;
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequence_tracker_gap() {
+        let tracker = SequenceTracker::new();
+        assert!(!tracker.check("BTC/USD", 1, 5).unwrap());
+
+        tracker.reset("BTC/USD", 10);
+        // Included in the snapshot
+        assert!(!tracker.check("BTC/USD", 5, 10).unwrap());
+        // Straddling the snapshot
+        assert!(tracker.check("BTC/USD", 8, 12).unwrap());
+        assert!(tracker.check("BTC/USD", 13, 13).unwrap());
+
+        let gap = tracker.check("BTC/USD", 15, 20).unwrap_err();
+        assert_eq!(14, gap.expected);
+        assert_eq!(15, gap.got);
+
+        // Dropped until the next snapshot
+        assert!(!tracker.check("BTC/USD", 21, 22).unwrap());
+        assert_eq!(None, tracker.last_update_id("BTC/USD"));
+        tracker.reset("BTC/USD", 30);
+        assert!(tracker.check("BTC/USD", 31, 32).unwrap());
+    }
+}
@@ -3,28 +3,29 @@
 mod rest;
 mod ws;
 
-use std::{cell::RefCell, convert::TryFrom};
+use std::convert::TryFrom;
 
-use metered::{common::TxPerSec, time_source::StdInstant, *};
 use serde_json::json;
 use surf::Url;
 
 use crate::{
-    market_data::{adapter::*, error::*},
+    market_data::{
+        adapter::*,
+        book_events::{BookEmitter, BookEvents},
+        error::*,
+        metrics::AdapterMetrics,
+    },
     prelude::*,
+    rate_limit::{self, Endpoint},
 };
 
-#[derive(Default, Debug)]
-pub struct SerumMetrics {
-    throughput: Throughput<StdInstant, RefCell<TxPerSec>>,
-}
-
 /// Serum adapter
 #[derive(Debug)]
 pub struct Serum {
-    pub metrics: SerumMetrics,
+    pub metrics: AdapterMetrics,
     pub rest_url: &'static str,
     pub ws_url: &'static str,
+    book_events: BookEmitter,
 }
 
 impl Default for Serum {
@@ -33,14 +34,20 @@ impl Default for Serum {
             rest_url: "http://localhost:8000",
             ws_url: "ws://localhost:8000/v1/ws",
             metrics: Default::default(),
+            book_events: BookEmitter::default(),
         }
     }
 }
 
 #[async_trait(?Send)]
 impl RestMarketDataAdapter for Serum {
-    const NAME: &'static str = "serum";
-    const EXCHANGE_REF: ExchangeId = ExchangeId::Serum;
+    fn name(&self) -> &'static str {
+        "serum"
+    }
+
+    fn exchange(&self) -> ExchangeId {
+        ExchangeId::Serum
+    }
 
     /// Fetches available markets on Serum
     async fn fetch_markets(&self) -> Result<Box<[Market]>, MarketDataError> {
@@ -50,6 +57,7 @@ impl RestMarketDataAdapter for Serum {
             .try_into()
             .map_err(MarketDataError::with_source)?;
 
+        rate_limit::acquire(ExchangeId::Serum, Endpoint::Markets).await;
         let mut res = client
             .get("/api/markets")
             .await
@@ -77,8 +85,12 @@ impl RestMarketDataAdapter for Serum {
 }
 
 impl WsMarketDataAdapter for Serum {
-    fn throughput_metrics(&self) -> &Throughput<StdInstant, RefCell<metered::common::TxPerSec>> {
-        &self.metrics.throughput
+    fn metrics(&self) -> &AdapterMetrics {
+        &self.metrics
+    }
+
+    fn set_book_events(&mut self, book_events: BookEvents) {
+        self.book_events = BookEmitter::new(book_events);
     }
 
     fn ws_url(&self) -> Box<str> {
@@ -94,6 +106,15 @@ impl WsMarketDataAdapter for Serum {
         ])
     }
 
+    fn unsubscribe_msgs(&mut self, markets: &[&str]) -> Option<Box<[String]>> {
+        info!("Unsubscribing {markets:?}");
+
+        Some(Box::new([
+            json!({"op": "unsubscribe", "channel": "level2", "markets": markets}).to_string(),
+            json!({"op": "unsubscribe", "channel": "trades", "markets": markets}).to_string(),
+        ]))
+    }
+
     /// Processes Websocket text message
     fn process_ws_msg(
         &self,
@@ -103,7 +124,11 @@ impl WsMarketDataAdapter for Serum {
         let ws_msg = serde_json::from_slice::<ws::WsMsg>(msg.as_bytes());
 
         match ws_msg {
-            Ok(ws_msg) => Ok(process_market_ws_message(ws_msg, markets)?),
+            Ok(ws_msg) => Ok(process_market_ws_message(
+                ws_msg,
+                markets,
+                &self.book_events,
+            )?),
             Err(e) => {
                 error!("Failed to parse {msg}");
 
@@ -117,6 +142,7 @@ impl WsMarketDataAdapter for Serum {
 fn process_market_ws_message(
     ws_msg: ws::WsMsg,
     markets: &mut HashMap<Box<str>, PlainOrderbook<f64>>,
+    book_events: &BookEmitter,
 ) -> Result<Option<MarketEvent>, MarketDataError> {
     info!("ws_msg = {ws_msg:?}");
 
@@ -155,31 +181,25 @@ fn process_market_ws_message(
                 bids: PriceLevelsVec::from_tuples_vec_unsorted(&mut snapshot.bids),
                 asks: PriceLevelsVec::from_tuples_vec_unsorted(&mut snapshot.asks),
                 time: time as f64,
+                max_depth: None,
             };
 
-            markets.insert(Box::from(snapshot.market), orderbook.clone());
+            let event = book_events.event(Box::from(snapshot.market), &orderbook);
+            markets.insert(Box::from(snapshot.market), orderbook);
 
-            Ok(Some(MarketEvent::orderbook_update(
-                Box::from(snapshot.market),
-                Box::new(orderbook),
-            )))
+            Ok(event)
         }
         ws::WsMsg::L2update(update) => {
             let time = chrono::DateTime::parse_from_rfc3339(update.timestamp)
                 .map_err(MarketDataError::with_source)?
                 .timestamp_millis();
             let orderbook = markets.get_mut(update.market).unwrap();
+            let bids = PriceLevelsVec::from_tuples_vec(&update.bids);
+            let asks = PriceLevelsVec::from_tuples_vec(&update.asks);
 
-            orderbook.update_with_timestamp(
-                &PriceLevelsVec::from_tuples_vec(&update.bids),
-                &PriceLevelsVec::from_tuples_vec(&update.asks),
-                time as f64,
-            );
+            orderbook.update_with_timestamp(&bids, &asks, time as f64);
 
-            Ok(Some(MarketEvent::orderbook_update(
-                Box::from(update.market),
-                Box::new(orderbook.clone()),
-            )))
+            Ok(book_events.delta(Box::from(update.market), orderbook, &bids, &asks))
         }
         _ => Ok(None),
     }
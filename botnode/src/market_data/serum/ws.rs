@@ -165,6 +165,8 @@ impl<'a> TryFrom<&Trade<'a>> for botvana::market::trade::Trade {
         Ok(Self {
             price: trade.price.parse::<f64>().map_err(|e| e.to_string())?,
             size: trade.size.parse::<f64>().map_err(|e| e.to_string())?,
+            // serum-vial reports the side of the taker
+            side: trade.side.parse().ok(),
             received_at: std::time::Instant::now(),
             time: trade
                 .timestamp
@@ -0,0 +1,234 @@
+//! Markets subscribed by a market data engine
+//!
+//! The markets of the bot configuration are subscribed once the engine
+//! starts, botvana-server adds and removes markets while the bot runs with
+//! `SubscribeMarkets` and `UnsubscribeMarkets`. The adapter loop polls the
+//! changes, sends the subscribe and unsubscribe messages on the open
+//! connection and creates or drops the orderbooks of the markets. Adapters
+//! that can't change the subscriptions of an open connection reconnect,
+//! subscribing the new set of markets.
+//!
+//! Orderbook snapshots requested with `GetOrderbookSnapshot` are collected
+//! by the poll too, the loop emits the books of the subscribed markets among
+//! them for the control engine to send to the server.
+
+use botvana::market::Market;
+
+use crate::{
+    bus::Subscriber, control::ControlCommand, market_data::symbols::ExchangeSymbols, prelude::*,
+};
+
+/// Markets added and removed since the previous poll, by native symbol
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SubscriptionChange {
+    pub subscribed: Vec<Box<str>>,
+    pub unsubscribed: Vec<Box<str>>,
+}
+
+impl SubscriptionChange {
+    /// Returns native symbols of the subscribed markets as passed to the
+    /// adapter
+    pub fn subscribed(&self) -> Vec<&str> {
+        self.subscribed.iter().map(|market| &**market).collect()
+    }
+
+    /// Returns native symbols of the unsubscribed markets as passed to the
+    /// adapter
+    pub fn unsubscribed(&self) -> Vec<&str> {
+        self.unsubscribed.iter().map(|market| &**market).collect()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.subscribed.is_empty() && self.unsubscribed.is_empty()
+    }
+}
+
+/// Markets subscribed by the engine and the commands changing them
+pub struct Subscriptions {
+    /// Native symbols in the order they were subscribed
+    markets: Vec<Box<str>>,
+    symbols: ExchangeSymbols,
+    /// Markets the exchange lists, aliases resolve to one of them
+    listed: Box<[Market]>,
+    commands: Subscriber<ControlCommand>,
+    /// Native symbols of the subscribed markets whose orderbooks were
+    /// requested since they were last taken
+    snapshots: Vec<Box<str>>,
+}
+
+impl Subscriptions {
+    /// Creates subscriptions of given canonical markets
+    pub fn new(
+        markets: &[Box<str>],
+        symbols: ExchangeSymbols,
+        listed: Box<[Market]>,
+        commands: Subscriber<ControlCommand>,
+    ) -> Self {
+        let mut subscriptions = Self {
+            markets: Vec::with_capacity(markets.len()),
+            symbols,
+            listed,
+            commands,
+            snapshots: Vec::new(),
+        };
+        for market in markets {
+            subscriptions.subscribe(market);
+        }
+
+        subscriptions
+    }
+
+    /// Returns native symbols of the subscribed markets
+    pub fn markets(&self) -> Vec<&str> {
+        self.markets.iter().map(|market| &**market).collect()
+    }
+
+    /// Applies the commands received since the previous poll, returns the
+    /// change when any market was added or removed
+    pub fn poll(&mut self) -> Option<SubscriptionChange> {
+        let mut change = SubscriptionChange::default();
+
+        while let Some(command) = self.commands.try_recv() {
+            match command {
+                ControlCommand::SubscribeMarkets(markets) => {
+                    for market in markets.iter() {
+                        if let Some(market) = self.subscribe(market) {
+                            match change.unsubscribed.iter().position(|m| *m == market) {
+                                Some(idx) => {
+                                    change.unsubscribed.remove(idx);
+                                }
+                                None => change.subscribed.push(market),
+                            }
+                        }
+                    }
+                }
+                ControlCommand::UnsubscribeMarkets(markets) => {
+                    for market in markets.iter() {
+                        if let Some(market) = self.unsubscribe(market) {
+                            match change.subscribed.iter().position(|m| *m == market) {
+                                Some(idx) => {
+                                    change.subscribed.remove(idx);
+                                }
+                                None => change.unsubscribed.push(market),
+                            }
+                        }
+                    }
+                }
+                ControlCommand::OrderbookSnapshot(market) => match self.resolve(&market) {
+                    Some(native) if self.markets.contains(&native) => {
+                        if !self.snapshots.contains(&native) {
+                            self.snapshots.push(native);
+                        }
+                    }
+                    _ => debug!("Orderbook of {market} requested, not subscribed"),
+                },
+                _ => {}
+            }
+        }
+
+        match change.is_empty() {
+            true => None,
+            false => {
+                info!("Subscription change {change:?}");
+                Some(change)
+            }
+        }
+    }
+
+    /// Takes native symbols of the markets whose orderbooks were requested
+    pub fn take_snapshot_requests(&mut self) -> Vec<Box<str>> {
+        std::mem::take(&mut self.snapshots)
+    }
+
+    /// Adds the canonical market, returns its native symbol unless it was
+    /// already subscribed or isn't listed
+    fn subscribe(&mut self, market: &str) -> Option<Box<str>> {
+        let native = self.resolve(market)?;
+        if self.markets.contains(&native) {
+            return None;
+        }
+        self.markets.push(native.clone());
+
+        Some(native)
+    }
+
+    /// Removes the canonical market, returns its native symbol when it was
+    /// subscribed
+    fn unsubscribe(&mut self, market: &str) -> Option<Box<str>> {
+        let native = self.resolve(market)?;
+        let idx = self.markets.iter().position(|m| *m == native)?;
+
+        Some(self.markets.remove(idx))
+    }
+
+    fn resolve(&self, market: &str) -> Option<Box<str>> {
+        match self.symbols.resolve(market, &self.listed) {
+            Some(market) => Some(Box::from(self.symbols.to_native(market))),
+            None => {
+                error!("No listed market for {market}");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bus::{Bus, ControlCommands, Delivery};
+
+    fn markets(markets: &[&str]) -> Box<[Box<str>]> {
+        markets.iter().map(|market| Box::from(*market)).collect()
+    }
+
+    #[test]
+    fn test_poll_subscription_changes() {
+        let mut bus = Bus::new();
+        let commands = bus
+            .subscribe::<ControlCommands>(Delivery::Lossless)
+            .unwrap();
+        let mut subscriptions = Subscriptions::new(
+            &markets(&["BTC-PERP", "ETH-PERP", "BTC-PERP"]),
+            ExchangeSymbols::default(),
+            Box::new([]),
+            commands,
+        );
+        assert_eq!(vec!["BTC-PERP", "ETH-PERP"], subscriptions.markets());
+        assert_eq!(None, subscriptions.poll());
+
+        bus.publish::<ControlCommands>(ControlCommand::SubscribeMarkets(markets(&[
+            "SOL-PERP",
+            "BTC-PERP",
+            "AVAX-PERP",
+        ])))
+        .unwrap();
+        bus.publish::<ControlCommands>(ControlCommand::PauseTrading)
+            .unwrap();
+        bus.publish::<ControlCommands>(ControlCommand::UnsubscribeMarkets(markets(&[
+            "ETH-PERP",
+            "AVAX-PERP",
+            "DOGE-PERP",
+        ])))
+        .unwrap();
+
+        assert_eq!(
+            Some(SubscriptionChange {
+                subscribed: vec![Box::from("SOL-PERP")],
+                unsubscribed: vec![Box::from("ETH-PERP")],
+            }),
+            subscriptions.poll()
+        );
+        assert_eq!(vec!["BTC-PERP", "SOL-PERP"], subscriptions.markets());
+
+        for market in ["SOL-PERP", "ETH-PERP", "SOL-PERP"] {
+            bus.publish::<ControlCommands>(ControlCommand::OrderbookSnapshot(Box::from(market)))
+                .unwrap();
+        }
+        assert_eq!(None, subscriptions.poll());
+        assert_eq!(
+            vec![Box::<str>::from("SOL-PERP")],
+            subscriptions.take_snapshot_requests()
+        );
+        assert!(subscriptions.take_snapshot_requests().is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+//! Symbol mapping between canonical and exchange-native market names
+//!
+//! The mapping file lists, per exchange, the native symbol of each canonical
+//! market name and aliases resolving to the first listed market that the
+//! exchange currently offers, e.g. roll chains of quarterly futures:
+//!
+//! ```toml
+//! [binance.symbols]
+//! "BTC/USDT" = "BTCUSDT"
+//!
+//! [ftx.aliases]
+//! "BTC-QUARTERLY" = ["BTC-0624", "BTC-0930"]
+//! ```
+//!
+//! Markets without an entry keep their name on every exchange.
+//!
+//! Market data engines subscribe with native symbols and emit events under
+//! the canonical names, so strategies use the same name for a market on
+//! every exchange. The [`SymbolMapper`] shares the mapping between engines.
+
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use figment::{
+    providers::{Format, Toml},
+    Figment,
+};
+use serde::Deserialize;
+
+use botvana::{
+    exchange::ExchangeId,
+    market::{event::MarketEvent, Market},
+};
+
+use crate::config::{ConfigError, ValidationError};
+
+/// Symbol mapping of all exchanges keyed by exchange name
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct SymbolMap {
+    exchanges: HashMap<String, ExchangeSymbols>,
+}
+
+/// Symbol mapping of single exchange
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExchangeSymbols {
+    /// Native symbol by canonical name
+    symbols: HashMap<String, String>,
+    /// Candidate canonical names by alias, in order of preference
+    aliases: HashMap<String, Vec<String>>,
+    #[serde(skip)]
+    canonical: HashMap<String, String>,
+}
+
+impl SymbolMap {
+    /// Loads the mapping from given TOML file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Err(ValidationError::new(
+                "symbols_file",
+                format!("{} does not exist", path.display()),
+            )
+            .into());
+        }
+
+        Self::from_figment(Figment::from(Toml::file(path)))
+    }
+
+    /// Parses the mapping from TOML string
+    pub fn from_toml(toml: &str) -> Result<Self, ConfigError> {
+        Self::from_figment(Figment::from(Toml::string(toml)))
+    }
+
+    fn from_figment(figment: Figment) -> Result<Self, ConfigError> {
+        let mut map: Self = figment.extract().map_err(ConfigError::with_source)?;
+
+        for (exchange, symbols) in map.exchanges.iter_mut() {
+            if let Err(e) = exchange.parse::<ExchangeId>() {
+                return Err(ValidationError::new("symbols_file", e).into());
+            }
+
+            symbols.canonical = symbols
+                .symbols
+                .iter()
+                .map(|(canonical, native)| (native.clone(), canonical.clone()))
+                .collect();
+            if symbols.canonical.len() != symbols.symbols.len() {
+                return Err(ValidationError::new(
+                    "symbols_file",
+                    format!("{exchange} maps several canonical names to the same native symbol"),
+                )
+                .into());
+            }
+        }
+
+        Ok(map)
+    }
+
+    /// Returns mapping of given exchange
+    pub fn exchange(&self, exchange: &str) -> ExchangeSymbols {
+        self.exchanges.get(exchange).cloned().unwrap_or_default()
+    }
+}
+
+impl ExchangeSymbols {
+    /// Returns whether there are no mappings for the exchange
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty() && self.aliases.is_empty()
+    }
+
+    /// Returns native symbol of given canonical market name
+    pub fn to_native<'a>(&'a self, canonical: &'a str) -> &'a str {
+        self.symbols
+            .get(canonical)
+            .map(String::as_str)
+            .unwrap_or(canonical)
+    }
+
+    /// Returns canonical market name of given native symbol
+    pub fn to_canonical<'a>(&'a self, native: &'a str) -> &'a str {
+        self.canonical
+            .get(native)
+            .map(String::as_str)
+            .unwrap_or(native)
+    }
+
+    /// Resolves alias to the first candidate listed in given markets
+    ///
+    /// Names that aren't aliases resolve to themselves.
+    pub fn resolve<'a>(&'a self, name: &'a str, markets: &[Market]) -> Option<&'a str> {
+        match self.aliases.get(name) {
+            Some(candidates) => candidates
+                .iter()
+                .map(String::as_str)
+                .find(|candidate| is_listed(markets, self.to_native(candidate))),
+            None => Some(name),
+        }
+    }
+
+    /// Renames the market of the event from native symbol to canonical name
+    pub fn canonicalize(&self, event: &mut MarketEvent) {
+        if let Some(market) = event.market_mut() {
+            if let Some(canonical) = self.canonical.get(&**market) {
+                *market = Box::from(canonical.as_str());
+            }
+        }
+    }
+
+    /// Replaces native market names with the canonical ones
+    pub fn normalize(&self, markets: &mut [Market]) {
+        for market in markets.iter_mut() {
+            if let Some(canonical) = self.canonical.get(&market.native_symbol) {
+                market.name = canonical.clone();
+            }
+        }
+    }
+
+    /// Returns mapped native symbols that the exchange doesn't list
+    ///
+    /// Aliases are reported when none of their candidates is listed.
+    pub fn validate(&self, markets: &[Market]) -> Vec<String> {
+        let mut errors: Vec<_> = self
+            .symbols
+            .iter()
+            .filter(|(_, native)| !is_listed(markets, native))
+            .map(|(canonical, native)| format!("{canonical} maps to unlisted symbol {native}"))
+            .collect();
+
+        errors.extend(
+            self.aliases
+                .keys()
+                .filter(|alias| self.resolve(alias, markets).is_none())
+                .map(|alias| format!("none of the {alias} candidates is listed")),
+        );
+
+        errors.sort();
+        errors
+    }
+}
+
+/// Symbol mapping of all exchanges shared by the engines
+#[derive(Clone, Debug, Default)]
+pub struct SymbolMapper {
+    exchanges: Arc<HashMap<ExchangeId, ExchangeSymbols>>,
+}
+
+impl SymbolMapper {
+    pub fn new(map: SymbolMap) -> Self {
+        // Exchange names were validated while loading the map
+        let exchanges = map
+            .exchanges
+            .into_iter()
+            .filter_map(|(exchange, symbols)| Some((exchange.parse().ok()?, symbols)))
+            .collect();
+
+        Self {
+            exchanges: Arc::new(exchanges),
+        }
+    }
+
+    /// Returns mapping of given exchange
+    pub fn exchange(&self, exchange: ExchangeId) -> ExchangeSymbols {
+        self.exchanges.get(&exchange).cloned().unwrap_or_default()
+    }
+
+    /// Returns native symbol of the canonical market name on the exchange
+    pub fn to_native<'a>(&'a self, exchange: ExchangeId, canonical: &'a str) -> &'a str {
+        match self.exchanges.get(&exchange) {
+            Some(symbols) => symbols.to_native(canonical),
+            None => canonical,
+        }
+    }
+
+    /// Returns canonical market name of the native symbol on the exchange
+    pub fn to_canonical<'a>(&'a self, exchange: ExchangeId, native: &'a str) -> &'a str {
+        match self.exchanges.get(&exchange) {
+            Some(symbols) => symbols.to_canonical(native),
+            None => native,
+        }
+    }
+}
+
+/// Returns whether the native symbol is listed in given markets
+fn is_listed(markets: &[Market], native: &str) -> bool {
+    markets
+        .iter()
+        .any(|market| market.native_symbol == native || market.name == native)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use botvana::market::{FuturesMarket, MarketType};
+
+    const SYMBOLS: &str = r#"
+        [binance.symbols]
+        "BTC/USDT" = "BTCUSDT"
+        "ETH/USDT" = "ETHUSDT"
+
+        [ftx.aliases]
+        "BTC-QUARTERLY" = ["BTC-0624", "BTC-0930"]
+    "#;
+
+    fn market(exchange: ExchangeId, name: &str, native_symbol: &str) -> Market {
+        Market {
+            exchange,
+            name: name.to_string(),
+            native_symbol: native_symbol.to_string(),
+            size_increment: 0.001,
+            price_increment: 0.01,
+            r#type: MarketType::Futures(FuturesMarket::perpetual()),
+        }
+    }
+
+    #[test]
+    fn test_symbol_map_native_and_canonical() {
+        let map = SymbolMap::from_toml(SYMBOLS).unwrap();
+        let binance = map.exchange("binance");
+
+        assert_eq!("BTCUSDT", binance.to_native("BTC/USDT"));
+        assert_eq!("ETH/USDT", binance.to_canonical("ETHUSDT"));
+        assert_eq!("SOL/USDT", binance.to_native("SOL/USDT"));
+        assert!(map.exchange("serum").is_empty());
+    }
+
+    #[test]
+    fn test_symbol_map_resolve_roll_chain() {
+        let map = SymbolMap::from_toml(SYMBOLS).unwrap();
+        let ftx = map.exchange("ftx");
+        let markets = [market(ExchangeId::Ftx, "BTC-0930", "BTC-0930")];
+
+        assert_eq!(Some("BTC-0930"), ftx.resolve("BTC-QUARTERLY", &markets));
+        assert_eq!(Some("BTC-PERP"), ftx.resolve("BTC-PERP", &markets));
+        assert_eq!(None, ftx.resolve("BTC-QUARTERLY", &[]));
+    }
+
+    #[test]
+    fn test_symbol_map_validate() {
+        let map = SymbolMap::from_toml(SYMBOLS).unwrap();
+        let binance = map.exchange("binance");
+        let mut markets = vec![market(ExchangeId::BinanceSpot, "BTCUSDT", "BTCUSDT")];
+
+        assert_eq!(
+            vec!["ETH/USDT maps to unlisted symbol ETHUSDT".to_string()],
+            binance.validate(&markets)
+        );
+
+        binance.normalize(&mut markets);
+        assert_eq!("BTC/USDT", markets[0].name);
+    }
+
+    #[test]
+    fn test_symbol_mapper_canonical_events() {
+        let mapper = SymbolMapper::new(SymbolMap::from_toml(SYMBOLS).unwrap());
+
+        assert_eq!(
+            "BTCUSDT",
+            mapper.to_native(ExchangeId::BinanceSpot, "BTC/USDT")
+        );
+        assert_eq!("BTC/USDT", mapper.to_native(ExchangeId::Ftx, "BTC/USDT"));
+        assert_eq!(
+            "ETH/USDT",
+            mapper.to_canonical(ExchangeId::BinanceSpot, "ETHUSDT")
+        );
+
+        let binance = mapper.exchange(ExchangeId::BinanceSpot);
+        let mut event = MarketEvent::mid_price_change(Box::from("BTCUSDT"), 1.0, 2.0);
+        binance.canonicalize(&mut event);
+        assert_eq!(Some("BTC/USDT"), event.market_mut().map(|market| &**market));
+    }
+
+    #[test]
+    fn test_symbol_map_invalid() {
+        assert!(SymbolMap::from_toml("[mtgox.symbols]\n\"BTC/USD\" = \"BTCUSD\"").is_err());
+        assert!(SymbolMap::from_toml(
+            "[ftx.symbols]\n\"BTC/USD\" = \"BTCUSD\"\n\"XBT/USD\" = \"BTCUSD\""
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,210 @@
+//! Exchange clock offsets and timestamp normalization
+//!
+//! Market data engines poll the server time of their exchange over REST
+//! every `time_sync_interval_secs`. Each sample estimates the offset of the
+//! exchange clock from the local one at the middle of the request round
+//! trip, the sample with the shortest round trip among the recent ones is
+//! the most accurate and is used.
+//!
+//! Events are normalized to one pair of clocks shared by all engines: the
+//! monotonic `Instant` of the pipeline times and the UTC time derived from
+//! it by [`utc`]. `MarketEvent.timestamp` becomes the time the message was
+//! read from the socket and the exchange times of trades, liquidations,
+//! funding rates and open interest are shifted onto the local clock, so
+//! latencies and backtests over several exchanges compare times of one
+//! clock.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant, SystemTime},
+};
+
+use once_cell::sync::Lazy;
+
+use botvana::market::event::{MarketEvent, MarketEventType};
+
+use crate::{metrics::MetricLabels, prelude::*};
+
+/// Server time samples the offset is chosen from
+const SAMPLES: usize = 8;
+
+/// Wall clock time at the monotonic anchor
+static ANCHOR: Lazy<(Instant, SystemTime)> = Lazy::new(|| (Instant::now(), SystemTime::now()));
+
+/// Returns UTC time of the monotonic instant
+///
+/// The monotonic clock is anchored to the wall clock once per process, later
+/// steps of the wall clock don't move the returned times.
+pub fn utc(instant: Instant) -> SystemTime {
+    let (anchor, anchor_utc) = *ANCHOR;
+
+    match instant.checked_duration_since(anchor) {
+        Some(elapsed) => anchor_utc + elapsed,
+        None => anchor_utc - anchor.duration_since(instant),
+    }
+}
+
+/// Offset of the exchange clock from the local one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClockOffset {
+    /// Exchange time minus local time in microseconds
+    pub offset_us: i64,
+    /// Round trip of the server time request
+    pub rtt: Duration,
+}
+
+/// Clock offset of one exchange
+#[derive(Debug, Default)]
+pub struct TimeSync {
+    samples: VecDeque<ClockOffset>,
+    offset: Option<ClockOffset>,
+}
+
+impl TimeSync {
+    /// Records the server time returned by the request sent and answered at
+    /// given instants
+    pub fn observe(&mut self, server_time: DateTime<Utc>, sent: Instant, received: Instant) {
+        let rtt = received.saturating_duration_since(sent);
+        let local = DateTime::<Utc>::from(utc(sent + rtt / 2));
+        let offset_us = match (server_time - local).num_microseconds() {
+            Some(offset_us) => offset_us,
+            None => {
+                warn!("Server time {server_time} is off by more than the clock range");
+                return;
+            }
+        };
+
+        if self.samples.len() == SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(ClockOffset { offset_us, rtt });
+        self.offset = self.samples.iter().min_by_key(|sample| sample.rtt).copied();
+    }
+
+    /// Returns the offset of the most accurate recent sample
+    pub fn offset(&self) -> Option<ClockOffset> {
+        self.offset
+    }
+
+    /// Shifts the exchange time onto the local clock
+    pub fn to_local(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        match self.offset {
+            Some(offset) => time - chrono::Duration::microseconds(offset.offset_us),
+            None => time,
+        }
+    }
+
+    /// Stamps the event with the time its message was received and shifts
+    /// the exchange times it carries onto the local clock
+    pub fn normalize(&self, event: &mut MarketEvent, received: Instant) {
+        event.timestamp = utc(received);
+        if self.offset.map_or(true, |offset| offset.offset_us == 0) {
+            return;
+        }
+
+        match &mut event.r#type {
+            MarketEventType::Trades(_, trades) => {
+                for trade in trades.iter_mut() {
+                    trade.time = self.to_local(trade.time);
+                }
+            }
+            MarketEventType::Liquidation(_, liquidations) => {
+                for liquidation in liquidations.iter_mut() {
+                    liquidation.time = self.to_local(liquidation.time);
+                }
+            }
+            MarketEventType::FundingRate(_, funding) => {
+                funding.time = self.to_local(funding.time);
+            }
+            MarketEventType::OpenInterest(_, open_interest) => {
+                open_interest.time = self.to_local(open_interest.time);
+            }
+            _ => {}
+        }
+    }
+
+    /// Publishes the offset and round trip of the used sample
+    pub fn publish(&self, exchange: &str) {
+        let offset = match self.offset {
+            Some(offset) => offset,
+            None => return,
+        };
+        let registry = crate::metrics::registry();
+        let labels = MetricLabels::new().exchange(exchange);
+
+        registry.set_gauge(
+            "market_data_clock_offset_milliseconds",
+            &labels,
+            offset.offset_us as f64 / 1000.0,
+        );
+        registry.set_gauge(
+            "market_data_time_sync_rtt_milliseconds",
+            &labels,
+            offset.rtt.as_secs_f64() * 1000.0,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use botvana::market::trade::Trade;
+
+    #[test]
+    fn test_utc_follows_monotonic_clock() {
+        let now = Instant::now();
+        let later = now + Duration::from_millis(1500);
+
+        assert_eq!(
+            Duration::from_millis(1500),
+            utc(later).duration_since(utc(now)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_observe_shortest_round_trip() {
+        let mut sync = TimeSync::default();
+        let sent = Instant::now();
+        let local = |instant| DateTime::<Utc>::from(utc(instant));
+
+        // Exchange 250ms ahead, answered after 40ms
+        let received = sent + Duration::from_millis(40);
+        sync.observe(
+            local(sent + Duration::from_millis(20)) + chrono::Duration::milliseconds(250),
+            sent,
+            received,
+        );
+        // Slower sample is less accurate and ignored
+        let received = sent + Duration::from_millis(400);
+        sync.observe(
+            local(sent + Duration::from_millis(200)) + chrono::Duration::milliseconds(300),
+            sent,
+            received,
+        );
+
+        assert_eq!(
+            Some(ClockOffset {
+                offset_us: 250_000,
+                rtt: Duration::from_millis(40),
+            }),
+            sync.offset()
+        );
+
+        let time = local(sent);
+        let mut event = MarketEvent::trades(
+            Box::from("BTC-PERP"),
+            Box::new([Trade::new(
+                1.0,
+                1.0,
+                time + chrono::Duration::milliseconds(250),
+            )]),
+        );
+        sync.normalize(&mut event, received);
+
+        assert_eq!(utc(received), event.timestamp);
+        match event.r#type {
+            MarketEventType::Trades(_, trades) => assert_eq!(time, trades[0].time),
+            _ => unreachable!(),
+        }
+    }
+}
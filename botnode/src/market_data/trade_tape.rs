@@ -0,0 +1,302 @@
+//! Rolling statistics of the recent trades
+//!
+//! The market data engine keeps a [`TradeTape`] of every market next to its
+//! orderbook. A tape retains the trades received within the last
+//! `window_secs`, at most `max_trades` of them, and keeps the buy, sell and
+//! total volume of the retained trades up to date as trades come and go.
+//! Trades of exchanges not reporting the taker side count towards the total
+//! volume only.
+//!
+//! Tapes are keyed by the native symbols like the orderbooks and are dropped
+//! with them when the market is unsubscribed. The statistics of every tape
+//! are published as `market_data_trade_signed_volume`,
+//! `market_data_trade_rate` and `market_data_trade_average_size` gauges.
+
+use std::{collections::VecDeque, time::Instant};
+
+use serde::Deserialize;
+
+use botvana::market::trade::{Trade, TradeSide};
+
+use crate::{metrics::MetricLabels, prelude::*};
+
+/// Trades retained by the tapes
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TradeTapeConfig {
+    /// Most trades retained per market
+    pub max_trades: usize,
+    /// Seconds trades are retained for
+    pub window_secs: u64,
+}
+
+impl Default for TradeTapeConfig {
+    fn default() -> Self {
+        Self {
+            max_trades: 1000,
+            window_secs: 60,
+        }
+    }
+}
+
+impl TradeTapeConfig {
+    pub fn window(&self) -> Duration {
+        Duration::from_secs(self.window_secs)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_trades == 0 {
+            return Err("max_trades must be positive".to_string());
+        }
+        if self.window_secs == 0 {
+            return Err("window_secs must be positive".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Retained trade
+#[derive(Clone, Copy, Debug)]
+struct TapeTrade {
+    size: f64,
+    side: Option<TradeSide>,
+    received_at: Instant,
+}
+
+/// Recent trades of a market
+#[derive(Debug)]
+pub struct TradeTape {
+    trades: VecDeque<TapeTrade>,
+    max_trades: usize,
+    window: Duration,
+    volume: f64,
+    buy_volume: f64,
+    sell_volume: f64,
+}
+
+impl TradeTape {
+    pub fn new(config: &TradeTapeConfig) -> Self {
+        Self {
+            trades: VecDeque::with_capacity(config.max_trades),
+            max_trades: config.max_trades,
+            window: config.window(),
+            volume: 0.0,
+            buy_volume: 0.0,
+            sell_volume: 0.0,
+        }
+    }
+
+    /// Appends the trade, dropping the oldest one when the tape is full
+    pub fn push(&mut self, trade: &Trade) {
+        if self.trades.len() == self.max_trades {
+            self.pop();
+        }
+
+        let trade = TapeTrade {
+            size: trade.size,
+            side: trade.side,
+            received_at: trade.received_at,
+        };
+        self.add(&trade, 1.0);
+        self.trades.push_back(trade);
+    }
+
+    /// Drops the trades received before the window ending at `now`
+    pub fn evict(&mut self, now: Instant) {
+        while let Some(oldest) = self.trades.front() {
+            if now.saturating_duration_since(oldest.received_at) <= self.window {
+                break;
+            }
+            self.pop();
+        }
+    }
+
+    fn pop(&mut self) {
+        if let Some(trade) = self.trades.pop_front() {
+            self.add(&trade, -1.0);
+        }
+        // Sums drift from repeated subtraction, an empty tape starts over
+        if self.trades.is_empty() {
+            self.volume = 0.0;
+            self.buy_volume = 0.0;
+            self.sell_volume = 0.0;
+        }
+    }
+
+    fn add(&mut self, trade: &TapeTrade, sign: f64) {
+        self.volume += sign * trade.size;
+        match trade.side {
+            Some(TradeSide::Buy) => self.buy_volume += sign * trade.size,
+            Some(TradeSide::Sell) => self.sell_volume += sign * trade.size,
+            None => {}
+        }
+    }
+
+    /// Returns number of retained trades
+    pub fn len(&self) -> usize {
+        self.trades.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trades.is_empty()
+    }
+
+    /// Returns size of the retained trades
+    pub fn volume(&self) -> f64 {
+        self.volume
+    }
+
+    /// Returns size of the retained trades bought by their takers
+    pub fn buy_volume(&self) -> f64 {
+        self.buy_volume
+    }
+
+    /// Returns size of the retained trades sold by their takers
+    pub fn sell_volume(&self) -> f64 {
+        self.sell_volume
+    }
+
+    /// Returns the bought minus the sold size
+    pub fn signed_volume(&self) -> f64 {
+        self.buy_volume - self.sell_volume
+    }
+
+    /// Returns average size of the retained trades
+    pub fn average_size(&self) -> Option<f64> {
+        (!self.is_empty()).then(|| self.volume / self.trades.len() as f64)
+    }
+
+    /// Returns trades per second over the window ending at `now`
+    ///
+    /// A full tape covers less than the window, its rate is measured since
+    /// its oldest trade.
+    pub fn trade_rate(&self, now: Instant) -> f64 {
+        let span = match (self.trades.len() < self.max_trades, self.trades.front()) {
+            (_, None) => return 0.0,
+            (true, Some(_)) => self.window,
+            (false, Some(oldest)) => now.saturating_duration_since(oldest.received_at),
+        };
+        if span.is_zero() {
+            return 0.0;
+        }
+
+        self.trades.len() as f64 / span.as_secs_f64()
+    }
+}
+
+/// Trade tapes by market
+#[derive(Debug, Default)]
+pub struct TradeTapes {
+    config: TradeTapeConfig,
+    tapes: HashMap<Box<str>, TradeTape>,
+}
+
+impl TradeTapes {
+    pub fn new(config: TradeTapeConfig) -> Self {
+        Self {
+            config,
+            tapes: HashMap::new(),
+        }
+    }
+
+    /// Appends the trades of the event to the tape of their market
+    pub fn observe(&mut self, event: &MarketEvent) {
+        let (market, trades) = match &event.r#type {
+            MarketEventType::Trades(market, trades) => (market, trades),
+            _ => return,
+        };
+        let config = &self.config;
+        let tape = self
+            .tapes
+            .entry(market.clone())
+            .or_insert_with(|| TradeTape::new(config));
+
+        for trade in trades.iter() {
+            tape.push(trade);
+        }
+    }
+
+    /// Returns the tape of the market
+    pub fn get(&self, market: &str) -> Option<&TradeTape> {
+        self.tapes.get(market)
+    }
+
+    /// Drops the tape of the unsubscribed market
+    pub fn remove(&mut self, market: &str) {
+        self.tapes.remove(market);
+    }
+
+    /// Evicts the old trades and publishes statistics of every tape
+    pub fn publish(&mut self, exchange: &str) {
+        let registry = crate::metrics::registry();
+        let now = Instant::now();
+
+        for (market, tape) in self.tapes.iter_mut() {
+            tape.evict(now);
+            let labels = MetricLabels::new().exchange(exchange).symbol(market);
+            registry.set_gauge(
+                "market_data_trade_signed_volume",
+                &labels,
+                tape.signed_volume(),
+            );
+            registry.set_gauge("market_data_trade_rate", &labels, tape.trade_rate(now));
+            registry.set_gauge(
+                "market_data_trade_average_size",
+                &labels,
+                tape.average_size().unwrap_or_default(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trade(size: f64, side: Option<TradeSide>, received_at: Instant) -> Trade {
+        let mut trade = Trade::new(100.0, size, Utc::now());
+        trade.side = side;
+        trade.received_at = received_at;
+        trade
+    }
+
+    #[test]
+    fn test_rolling_volume() {
+        let config = TradeTapeConfig {
+            max_trades: 3,
+            window_secs: 10,
+        };
+        let mut tape = TradeTape::new(&config);
+        let start = Instant::now();
+
+        tape.push(&trade(1.0, Some(TradeSide::Buy), start));
+        tape.push(&trade(2.0, Some(TradeSide::Sell), start));
+        tape.push(&trade(0.5, None, start + Duration::from_secs(5)));
+
+        assert_eq!(-1.0, tape.signed_volume());
+        assert_eq!(3.5, tape.volume());
+        assert_eq!(Some(3.5 / 3.0), tape.average_size());
+        // Full tape, 3 trades in 5s
+        assert_eq!(0.6, tape.trade_rate(start + Duration::from_secs(5)));
+
+        // Drops the oldest buy
+        tape.push(&trade(
+            4.0,
+            Some(TradeSide::Buy),
+            start + Duration::from_secs(6),
+        ));
+        assert_eq!(3, tape.len());
+        assert_eq!(2.0, tape.signed_volume());
+
+        tape.evict(start + Duration::from_secs(12));
+        assert_eq!(2, tape.len());
+        assert_eq!(4.0, tape.signed_volume());
+        assert_eq!(0.2, tape.trade_rate(start + Duration::from_secs(12)));
+
+        tape.evict(start + Duration::from_secs(60));
+        assert!(tape.is_empty());
+        assert_eq!(0.0, tape.volume());
+        assert_eq!(None, tape.average_size());
+    }
+}
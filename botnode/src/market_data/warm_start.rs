@@ -0,0 +1,218 @@
+//! Warm start of the orderbooks
+//!
+//! After a restart strategies see empty books until every exchange sent its
+//! snapshots again. With `[market_data.warm_start]` set, the market data
+//! engine of each exchange saves its books into `<dir>/<exchange>.books` on
+//! shutdown and, once subscribed, emits those of its markets as orderbook
+//! updates before connecting. Fresh books replace them as they arrive.
+//!
+//! Warm books keep the exchange time of their last update and are stamped
+//! with the time they were saved, strategies tell them apart by their age.
+//! Books saved more than `max_age_secs` ago aren't emitted.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use botvana::market::orderbook::snapshot::{BookSnapshot, BookSnapshots};
+
+use crate::prelude::*;
+
+/// Extension of the snapshot files
+const EXTENSION: &str = "books";
+
+/// Orderbooks saved on shutdown and emitted on startup
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WarmStartConfig {
+    /// Directory the books are saved to
+    pub dir: PathBuf,
+    /// Seconds after which saved books are too old to be emitted
+    pub max_age_secs: u64,
+}
+
+impl Default for WarmStartConfig {
+    fn default() -> Self {
+        Self {
+            dir: PathBuf::from("books"),
+            max_age_secs: 300,
+        }
+    }
+}
+
+impl WarmStartConfig {
+    pub fn max_age(&self) -> Duration {
+        Duration::from_secs(self.max_age_secs)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_age_secs == 0 {
+            return Err("max_age_secs must be positive".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Returns path of the books of the exchange
+    pub fn path(&self, exchange: &str) -> PathBuf {
+        self.dir.join(exchange).with_extension(EXTENSION)
+    }
+
+    /// Saves the non-empty books, replacing the previously saved ones
+    pub fn save(
+        &self,
+        exchange: &str,
+        markets: &HashMap<Box<str>, PlainOrderbook<f64>>,
+    ) -> io::Result<usize> {
+        let books: Vec<_> = markets
+            .iter()
+            .filter(|(_, orderbook)| {
+                !orderbook.bids.price_vec.is_empty() || !orderbook.asks.price_vec.is_empty()
+            })
+            .map(|(market, orderbook)| BookSnapshot {
+                market: market.clone(),
+                orderbook: orderbook.clone(),
+            })
+            .collect();
+        let count = books.len();
+        let buf = BookSnapshots::new(books)
+            .encode()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        fs::create_dir_all(&self.dir)?;
+        // Books are swapped in whole, a crash while writing leaves the
+        // previous ones
+        let path = self.path(exchange);
+        let tmp = path.with_extension(format!("{EXTENSION}.tmp"));
+        fs::write(&tmp, buf)?;
+        fs::rename(tmp, path)?;
+
+        Ok(count)
+    }
+
+    /// Loads the saved books, `None` when there are none or they are older
+    /// than `max_age_secs`
+    pub fn load(&self, exchange: &str) -> io::Result<Option<BookSnapshots>> {
+        let path = self.path(exchange);
+        let snapshots = match read(&path) {
+            Ok(snapshots) => snapshots,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        if snapshots.age() > self.max_age() {
+            info!(
+                "Books of {exchange} saved {:?} ago are too old for a warm start",
+                snapshots.age()
+            );
+            return Ok(None);
+        }
+
+        Ok(Some(snapshots))
+    }
+
+    /// Returns the warm orderbook updates of the subscribed markets
+    ///
+    /// Failures are logged, the engine starts without warm books then.
+    pub fn warm_events(&self, exchange: &str, markets: &[&str]) -> Vec<MarketEvent> {
+        let snapshots = match self.load(exchange) {
+            Ok(Some(snapshots)) => snapshots,
+            Ok(None) => return Vec::new(),
+            Err(e) => {
+                warn!(
+                    "Failed to load books from {}: {e}",
+                    self.path(exchange).display()
+                );
+                return Vec::new();
+            }
+        };
+        let saved_at = snapshots.saved_at;
+
+        snapshots
+            .books
+            .into_iter()
+            .filter(|book| markets.contains(&&*book.market))
+            .map(|book| {
+                let mut event =
+                    MarketEvent::orderbook_update(book.market, Box::new(book.orderbook));
+                event.timestamp = saved_at;
+                event
+            })
+            .collect()
+    }
+
+    /// Saves the books on shutdown, failures are logged
+    pub fn save_logged(&self, exchange: &str, markets: &HashMap<Box<str>, PlainOrderbook<f64>>) {
+        match self.save(exchange, markets) {
+            Ok(count) => info!("Saved {count} books to {}", self.path(exchange).display()),
+            Err(e) => warn!(
+                "Failed to save books to {}: {e}",
+                self.path(exchange).display()
+            ),
+        }
+    }
+}
+
+fn read(path: &Path) -> io::Result<BookSnapshots> {
+    let buf = fs::read(path)?;
+
+    BookSnapshots::decode(&buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_warm_events() {
+        let dir = std::env::temp_dir().join(format!("botnode-warm-start-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = WarmStartConfig {
+            dir: dir.clone(),
+            ..WarmStartConfig::default()
+        };
+        let mut markets = HashMap::new();
+        markets.insert(
+            Box::from("BTC-PERP"),
+            PlainOrderbook {
+                bids: PriceLevelsVec::from_tuples_vec(&[(100.0, 1.0)]),
+                asks: PriceLevelsVec::from_tuples_vec(&[(101.0, 2.0)]),
+                time: 1.0,
+                max_depth: None,
+            },
+        );
+        markets.insert(Box::from("ETH-PERP"), PlainOrderbook::new());
+        markets.insert(
+            Box::from("SOL-PERP"),
+            PlainOrderbook {
+                bids: PriceLevelsVec::from_tuples_vec(&[(10.0, 1.0)]),
+                asks: PriceLevelsVec::new(),
+                time: 1.0,
+                max_depth: None,
+            },
+        );
+
+        // Empty books aren't saved
+        assert_eq!(2, config.save("ftx", &markets).unwrap());
+        assert!(config.load("binance").unwrap().is_none());
+
+        let events = config.warm_events("ftx", &["BTC-PERP", "ETH-PERP"]);
+        assert_eq!(1, events.len());
+        match &events[0].r#type {
+            MarketEventType::OrderbookUpdate(market, orderbook) => {
+                assert_eq!("BTC-PERP", &**market);
+                assert_eq!(vec![101.0], orderbook.asks.price_vec);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+
+        fs::write(config.path("ftx"), b"garbage").unwrap();
+        assert!(config.load("ftx").is_err());
+        assert!(config.warm_events("ftx", &["BTC-PERP"]).is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,284 @@
+//! Botnode metrics registry
+//!
+//! Engines keep their hot-path metrics local to the thread they run on and
+//! periodically publish a snapshot into the process-wide registry. Exporters
+//! only ever read from the registry so they never touch engine internals.
+//!
+//! ## Naming scheme
+//!
+//! Metric names are `snake_case` and start with the subsystem producing them
+//! (`market_data`, `trading`, `order`, `engine`, `audit`, `channel`,
+//! `perf`). Counters end with `_total`, other metrics end with their unit
+//! (`_seconds`, `_milliseconds`, `_microseconds`, `_ratio`, `_per_second`,
+//! `_messages`).
+//!
+//! Every metric carries the `exchange`, `symbol` and `strategy` labels, see
+//! [`MetricLabels`].
+
+pub mod prometheus;
+pub mod statsd;
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use arrayvec::ArrayVec;
+use once_cell::sync::Lazy;
+
+use botvana::metrics::{MetricSample, MetricsReport};
+
+static REGISTRY: Lazy<MetricsRegistry> = Lazy::new(MetricsRegistry::default);
+
+/// Returns the process-wide metrics registry
+pub fn registry() -> &'static MetricsRegistry {
+    &REGISTRY
+}
+
+/// Value of the standard labels that do not apply to a metric
+pub const NOT_APPLICABLE: &str = "none";
+
+/// Maximum number of metric specific labels
+const EXTRA_LABELS_CAP: usize = 4;
+
+/// Labels of a metric
+///
+/// The standard `exchange`, `symbol` and `strategy` labels are always present
+/// and always come first, so dashboards can filter any metric the same way.
+/// Metric specific labels follow in the order they were added.
+#[derive(Clone, Debug, Default)]
+pub struct MetricLabels<'a> {
+    exchange: Option<&'a str>,
+    symbol: Option<&'a str>,
+    strategy: Option<&'a str>,
+    extra: ArrayVec<(&'static str, &'a str), EXTRA_LABELS_CAP>,
+}
+
+impl<'a> MetricLabels<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn exchange(mut self, exchange: &'a str) -> Self {
+        self.exchange = Some(exchange);
+        self
+    }
+
+    pub fn symbol(mut self, symbol: &'a str) -> Self {
+        self.symbol = Some(symbol);
+        self
+    }
+
+    pub fn strategy(mut self, strategy: &'a str) -> Self {
+        self.strategy = Some(strategy);
+        self
+    }
+
+    /// Adds metric specific label
+    ///
+    /// Panics when more than `EXTRA_LABELS_CAP` labels are added.
+    pub fn with(mut self, name: &'static str, value: &'a str) -> Self {
+        self.extra.push((name, value));
+        self
+    }
+
+    /// Returns all labels with the standard ones first
+    pub fn to_vec(&self) -> Vec<(&'static str, &'a str)> {
+        let mut labels = Vec::with_capacity(3 + self.extra.len());
+        labels.push(("exchange", self.exchange.unwrap_or(NOT_APPLICABLE)));
+        labels.push(("symbol", self.symbol.unwrap_or(NOT_APPLICABLE)));
+        labels.push(("strategy", self.strategy.unwrap_or(NOT_APPLICABLE)));
+        labels.extend(self.extra.iter().copied());
+        labels
+    }
+}
+
+/// Metric identification: name plus a set of labels
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct MetricKey {
+    pub name: &'static str,
+    pub labels: Box<[(&'static str, Box<str>)]>,
+}
+
+impl MetricKey {
+    pub fn new(name: &'static str, labels: &MetricLabels) -> Self {
+        Self {
+            name,
+            labels: labels
+                .to_vec()
+                .into_iter()
+                .map(|(k, v)| (k, Box::from(v)))
+                .collect(),
+        }
+    }
+}
+
+/// Value of a single metric
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MetricValue {
+    /// Monotonically increasing counter
+    Counter(u64),
+    /// Value that can go up and down
+    Gauge(f64),
+}
+
+impl MetricValue {
+    /// Returns the value as `f64` regardless of the metric kind
+    pub fn as_f64(&self) -> f64 {
+        match self {
+            MetricValue::Counter(v) => *v as f64,
+            MetricValue::Gauge(v) => *v,
+        }
+    }
+}
+
+/// Shared registry of published metrics
+#[derive(Clone, Debug, Default)]
+pub struct MetricsRegistry {
+    inner: Arc<Mutex<BTreeMap<MetricKey, MetricValue>>>,
+}
+
+impl MetricsRegistry {
+    /// Sets gauge to given value
+    pub fn set_gauge(&self, name: &'static str, labels: &MetricLabels, value: f64) {
+        let mut metrics = self.inner.lock().unwrap();
+        metrics.insert(MetricKey::new(name, labels), MetricValue::Gauge(value));
+    }
+
+    /// Sets counter to given absolute value
+    pub fn set_counter(&self, name: &'static str, labels: &MetricLabels, value: u64) {
+        let mut metrics = self.inner.lock().unwrap();
+        metrics.insert(MetricKey::new(name, labels), MetricValue::Counter(value));
+    }
+
+    /// Increments counter by given delta
+    pub fn incr_counter(&self, name: &'static str, labels: &MetricLabels, delta: u64) {
+        let mut metrics = self.inner.lock().unwrap();
+        let value = metrics
+            .entry(MetricKey::new(name, labels))
+            .or_insert(MetricValue::Counter(0));
+
+        match value {
+            MetricValue::Counter(v) => *v += delta,
+            MetricValue::Gauge(_) => *value = MetricValue::Counter(delta),
+        }
+    }
+
+    /// Returns the metric value for given key if it was published
+    pub fn get(&self, name: &'static str, labels: &MetricLabels) -> Option<MetricValue> {
+        let metrics = self.inner.lock().unwrap();
+        metrics.get(&MetricKey::new(name, labels)).copied()
+    }
+
+    /// Returns copy of all published metrics
+    pub fn snapshot(&self) -> Vec<(MetricKey, MetricValue)> {
+        let metrics = self.inner.lock().unwrap();
+        metrics.iter().map(|(k, v)| (k.clone(), *v)).collect()
+    }
+
+    /// Returns all published metrics as a report for botvana-server
+    pub fn report(&self) -> MetricsReport {
+        let samples = self
+            .snapshot()
+            .into_iter()
+            .map(|(key, value)| MetricSample {
+                name: Box::from(key.name),
+                labels: key
+                    .labels
+                    .iter()
+                    .map(|(k, v)| (Box::from(*k), v.clone()))
+                    .collect(),
+                value: value.as_f64(),
+            })
+            .collect();
+
+        MetricsReport::new(samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_gauge() {
+        let registry = MetricsRegistry::default();
+        let ftx = MetricLabels::new().exchange("ftx");
+        registry.set_gauge("test_gauge", &ftx, 1.5);
+        registry.set_gauge("test_gauge", &ftx, 2.5);
+
+        assert_eq!(
+            Some(MetricValue::Gauge(2.5)),
+            registry.get("test_gauge", &ftx)
+        );
+        assert_eq!(
+            None,
+            registry.get("test_gauge", &MetricLabels::new().exchange("binance"))
+        );
+    }
+
+    #[test]
+    fn test_incr_counter() {
+        let registry = MetricsRegistry::default();
+        registry.incr_counter("test_counter", &MetricLabels::new(), 1);
+        registry.incr_counter("test_counter", &MetricLabels::new(), 2);
+
+        assert_eq!(
+            Some(MetricValue::Counter(3)),
+            registry.get("test_counter", &MetricLabels::new())
+        );
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let registry = MetricsRegistry::default();
+        registry.set_counter("b", &MetricLabels::new(), 1);
+        registry.set_gauge("a", &MetricLabels::new(), 1.0);
+
+        let snapshot = registry.snapshot();
+
+        assert_eq!(2, snapshot.len());
+        assert_eq!("a", snapshot[0].0.name);
+        assert_eq!("b", snapshot[1].0.name);
+    }
+
+    #[test]
+    fn test_report() {
+        let registry = MetricsRegistry::default();
+        registry.set_counter("messages", &MetricLabels::new().exchange("ftx"), 2);
+
+        let report = registry.report();
+
+        assert_eq!(
+            Some(2.0),
+            report
+                .get(
+                    "messages",
+                    &[
+                        ("exchange", "ftx"),
+                        ("symbol", "none"),
+                        ("strategy", "none")
+                    ]
+                )
+                .map(|s| s.value)
+        );
+    }
+
+    #[test]
+    fn test_metric_labels_order() {
+        let labels = MetricLabels::new()
+            .with("quantile", "0.5")
+            .strategy("mm")
+            .exchange("ftx");
+
+        assert_eq!(
+            vec![
+                ("exchange", "ftx"),
+                ("symbol", NOT_APPLICABLE),
+                ("strategy", "mm"),
+                ("quantile", "0.5")
+            ],
+            labels.to_vec()
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Prometheus text exposition of the published metrics
+//!
+//! Served by the HTTP status server on `/metrics` for Prometheus to scrape.
+//! Metric names and labels are exported as they are published, samples of
+//! one metric are grouped under a single `# TYPE` line.
+
+use std::fmt::Write;
+
+use super::{MetricKey, MetricValue};
+
+/// Content type of the text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Encodes the metrics snapshot in the Prometheus text format
+///
+/// The snapshot is expected sorted by metric name, as returned by
+/// [`MetricsRegistry::snapshot`](super::MetricsRegistry::snapshot).
+pub fn encode(snapshot: &[(MetricKey, MetricValue)]) -> String {
+    let mut out = String::new();
+    let mut last_name = None;
+
+    for (key, value) in snapshot {
+        if last_name != Some(key.name) {
+            let kind = match value {
+                MetricValue::Counter(_) => "counter",
+                MetricValue::Gauge(_) => "gauge",
+            };
+            let _ = writeln!(out, "# TYPE {} {kind}", key.name);
+            last_name = Some(key.name);
+        }
+
+        out.push_str(key.name);
+        if !key.labels.is_empty() {
+            out.push('{');
+            for (idx, (name, value)) in key.labels.iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                let _ = write!(out, "{name}=\"{}\"", escape_label_value(value));
+            }
+            out.push('}');
+        }
+        let _ = match value {
+            MetricValue::Counter(value) => writeln!(out, " {value}"),
+            MetricValue::Gauge(value) => writeln!(out, " {}", format_float(*value)),
+        };
+    }
+
+    out
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn format_float(value: f64) -> String {
+    match value {
+        v if v.is_nan() => "NaN".to_string(),
+        v if v == f64::INFINITY => "+Inf".to_string(),
+        v if v == f64::NEG_INFINITY => "-Inf".to_string(),
+        v => v.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{MetricLabels, MetricsRegistry};
+
+    #[test]
+    fn test_encode() {
+        let registry = MetricsRegistry::default();
+        registry.set_counter(
+            "market_data_messages_total",
+            &MetricLabels::new().exchange("ftx"),
+            7,
+        );
+        registry.set_counter(
+            "market_data_messages_total",
+            &MetricLabels::new().exchange("binance"),
+            3,
+        );
+        registry.set_gauge(
+            "channel_occupancy_ratio",
+            &MetricLabels::new().with("channel", "market \"data\""),
+            0.5,
+        );
+
+        assert_eq!(
+            "# TYPE channel_occupancy_ratio gauge\n\
+             channel_occupancy_ratio{exchange=\"none\",symbol=\"none\",strategy=\"none\",channel=\"market \\\"data\\\"\"} 0.5\n\
+             # TYPE market_data_messages_total counter\n\
+             market_data_messages_total{exchange=\"binance\",symbol=\"none\",strategy=\"none\"} 3\n\
+             market_data_messages_total{exchange=\"ftx\",symbol=\"none\",strategy=\"none\"} 7\n",
+            encode(&registry.snapshot())
+        );
+    }
+}
@@ -0,0 +1,246 @@
+//! StatsD metrics exporter
+//!
+//! Periodically sends all published metrics over UDP. With DogStatsD enabled
+//! labels are sent as tags, plain StatsD gets them folded into the name.
+
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, UdpSocket},
+    thread,
+    time::Duration,
+};
+
+use serde::Deserialize;
+use tracing::{error, info, warn};
+
+use super::{MetricKey, MetricValue, NOT_APPLICABLE};
+
+/// Largest datagram we send, safe for most networks
+const MAX_DATAGRAM_LEN: usize = 1432;
+
+/// StatsD exporter configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatsdConfig {
+    /// Address of the StatsD agent
+    pub addr: SocketAddr,
+    /// Prefix prepended to every metric name
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+    /// Send labels as DogStatsD tags
+    #[serde(default = "default_dogstatsd")]
+    pub dogstatsd: bool,
+    /// Seconds between flushes
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_prefix() -> String {
+    "botnode".to_string()
+}
+
+fn default_dogstatsd() -> bool {
+    true
+}
+
+fn default_interval_secs() -> u64 {
+    10
+}
+
+impl StatsdConfig {
+    /// Returns how often metrics are flushed
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+}
+
+/// StatsD exporter
+pub struct StatsdExporter {
+    config: StatsdConfig,
+    socket: UdpSocket,
+    last_counters: HashMap<MetricKey, u64>,
+}
+
+impl StatsdExporter {
+    /// Creates new exporter bound to an ephemeral local port
+    pub fn new(config: StatsdConfig) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(config.addr)?;
+
+        Ok(Self {
+            config,
+            socket,
+            last_counters: HashMap::new(),
+        })
+    }
+
+    /// Spawns the exporter on its own thread
+    pub fn spawn(mut self) -> thread::JoinHandle<()> {
+        thread::Builder::new()
+            .name("statsd-exporter".to_string())
+            .spawn(move || {
+                info!("Exporting metrics to statsd at {}", self.config.addr);
+
+                loop {
+                    thread::sleep(self.config.interval());
+                    self.flush(&super::registry().snapshot());
+                }
+            })
+            .expect("failed to spawn statsd exporter")
+    }
+
+    /// Sends given metrics, packing as many lines as fit into each datagram
+    pub fn flush(&mut self, metrics: &[(MetricKey, MetricValue)]) {
+        let mut buf = String::with_capacity(MAX_DATAGRAM_LEN);
+
+        for (key, value) in metrics {
+            let line = match self.format_metric(key, *value) {
+                Some(line) => line,
+                None => continue,
+            };
+
+            if !buf.is_empty() && buf.len() + line.len() + 1 > MAX_DATAGRAM_LEN {
+                self.send(&buf);
+                buf.clear();
+            }
+
+            if !buf.is_empty() {
+                buf.push('\n');
+            }
+            buf.push_str(&line);
+        }
+
+        if !buf.is_empty() {
+            self.send(&buf);
+        }
+    }
+
+    fn send(&self, datagram: &str) {
+        if let Err(e) = self.socket.send(datagram.as_bytes()) {
+            warn!("Failed to send statsd datagram: {e}");
+        }
+    }
+
+    /// Formats single metric as StatsD line
+    ///
+    /// Counters are sent as deltas since the last flush, returns `None` when
+    /// a counter did not change.
+    fn format_metric(&mut self, key: &MetricKey, value: MetricValue) -> Option<String> {
+        let (value, kind) = match value {
+            MetricValue::Gauge(v) => (v.to_string(), "g"),
+            MetricValue::Counter(v) => {
+                let last = self.last_counters.insert(key.clone(), v).unwrap_or(0);
+                if v == last {
+                    return None;
+                }
+                // Counter was reset, report the whole value
+                let delta = if v > last { v - last } else { v };
+
+                (delta.to_string(), "c")
+            }
+        };
+
+        let mut line = format!("{}.{}", self.config.prefix, key.name);
+
+        if self.config.dogstatsd {
+            line.push_str(&format!(":{value}|{kind}"));
+
+            if !key.labels.is_empty() {
+                let tags: Vec<_> = key
+                    .labels
+                    .iter()
+                    .map(|(k, v)| format!("{k}:{}", sanitize(v)))
+                    .collect();
+                line.push_str("|#");
+                line.push_str(&tags.join(","));
+            }
+        } else {
+            for (_, v) in key.labels.iter() {
+                if &**v == NOT_APPLICABLE {
+                    continue;
+                }
+                line.push('.');
+                line.push_str(&sanitize(v));
+            }
+            line.push_str(&format!(":{value}|{kind}"));
+        }
+
+        Some(line)
+    }
+}
+
+/// Replaces characters with special meaning in the StatsD protocol
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            ':' | '|' | '@' | ',' | '#' | '/' | ' ' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Starts the exporter on its own thread
+pub fn start(config: StatsdConfig) -> Option<thread::JoinHandle<()>> {
+    match StatsdExporter::new(config) {
+        Ok(exporter) => Some(exporter.spawn()),
+        Err(e) => {
+            error!("Failed to start statsd exporter: {e}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::MetricLabels;
+
+    fn exporter(dogstatsd: bool) -> StatsdExporter {
+        StatsdExporter::new(StatsdConfig {
+            addr: "127.0.0.1:8125".parse().unwrap(),
+            prefix: "botnode".to_string(),
+            dogstatsd,
+            interval_secs: 10,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_format_gauge_dogstatsd() {
+        let mut exporter = exporter(true);
+        let key = MetricKey::new("throughput", &MetricLabels::new().exchange("ftx"));
+
+        assert_eq!(
+            Some("botnode.throughput:1.5|g|#exchange:ftx,symbol:none,strategy:none".to_string()),
+            exporter.format_metric(&key, MetricValue::Gauge(1.5))
+        );
+    }
+
+    #[test]
+    fn test_format_gauge_plain() {
+        let mut exporter = exporter(false);
+        let key = MetricKey::new("throughput", &MetricLabels::new().symbol("BTC/USD"));
+
+        assert_eq!(
+            Some("botnode.throughput.BTC_USD:2|g".to_string()),
+            exporter.format_metric(&key, MetricValue::Gauge(2.0))
+        );
+    }
+
+    #[test]
+    fn test_format_counter_delta() {
+        let mut exporter = exporter(false);
+        let key = MetricKey::new("messages_total", &MetricLabels::new());
+
+        assert_eq!(
+            Some("botnode.messages_total:5|c".to_string()),
+            exporter.format_metric(&key, MetricValue::Counter(5))
+        );
+        assert_eq!(None, exporter.format_metric(&key, MetricValue::Counter(5)));
+        assert_eq!(
+            Some("botnode.messages_total:3|c".to_string()),
+            exporter.format_metric(&key, MetricValue::Counter(8))
+        );
+    }
+}
@@ -0,0 +1,375 @@
+//! High-level bot API
+//!
+//! [`Botnode::builder`] assembles the whole bot from a [`BotnodeConfig`]:
+//! the control engine connecting to botvana-server, and once it receives the
+//! bot configuration the market data, indicator, trading, order, audit and
+//! alert engines. Strategies are the configured ones plus any added to the
+//! builder.
+//!
+//! ```no_run
+//! use botnode::{trading::strategy::Strategy, Botnode};
+//!
+//! struct Noop;
+//!
+//! impl Strategy for Noop {
+//!     fn name(&self) -> &str {
+//!         "noop"
+//!     }
+//! }
+//!
+//! Botnode::builder()
+//!     .strategy(|| Noop)
+//!     .paper_trading()
+//!     .build()
+//!     .unwrap()
+//!     .run()
+//!     .unwrap();
+//! ```
+
+use std::{panic, sync::Arc};
+
+use async_shutdown::Shutdown;
+use futures::prelude::*;
+use signal_hook::consts::signal::*;
+use signal_hook_async_std::Signals;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    config::{BotnodeConfig, ConfigError},
+    control::engine::ControlEngine,
+    engine::{spawn_engine, ShutdownCoordinator, ShutdownPhase},
+    market_data::{registry::MarketDataRegistry, symbols::SymbolMap},
+    notify,
+    rt::EngineExecutor,
+    trading::strategy::{Strategy, StrategyFactory},
+};
+
+/// Configured bot ready to run
+pub struct Botnode {
+    config: BotnodeConfig,
+    symbols: SymbolMap,
+    market_data_registry: MarketDataRegistry,
+    strategies: Vec<StrategyFactory>,
+    telemetry: bool,
+    shutdown: Shutdown,
+}
+
+impl Botnode {
+    /// Returns builder with default configuration sources and adapters
+    pub fn builder() -> BotnodeBuilder {
+        BotnodeBuilder::default()
+    }
+
+    pub fn config(&self) -> &BotnodeConfig {
+        &self.config
+    }
+
+    /// Returns handle that stops the bot when shut down
+    pub fn shutdown(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// Runs the bot until it receives a shutdown signal or the shutdown
+    /// handle is triggered
+    pub fn run(self) -> Result<(), BotnodeError> {
+        let telemetry = match self.telemetry {
+            true => Some(
+                crate::telemetry::init(self.config.telemetry.log_format)
+                    .map_err(BotnodeError::with_source)?,
+            ),
+            false => None,
+        };
+        let config = self.config;
+        crate::telemetry::set_pipeline_sample_every(config.telemetry.pipeline_sample_every);
+        info!("bot_id = {}", config.bot_id.0);
+
+        // Engines inherit this span so every log line carries the bot id and
+        // whether the bot is in dry-run mode
+        let _bot_span = tracing::info_span!(
+            "botnode",
+            bot_id = config.bot_id.0,
+            dry_run = config.dry_run
+        )
+        .entered();
+        if config.dry_run {
+            warn!("DRY RUN: orders are sent to the paper adapter, not to exchanges");
+        }
+
+        // Optional exporters run on their own threads, outside of the engine cores
+        #[cfg(feature = "sinks")]
+        if let Some(statsd) = config.statsd.clone() {
+            crate::metrics::statsd::start(statsd);
+        }
+        #[cfg(not(feature = "sinks"))]
+        if config.statsd.is_some() {
+            warn!("statsd is configured but botnode is built without the `sinks` feature");
+        }
+        crate::rate_limit::configure(&config.rate_limits);
+        start_api_servers(&config);
+        notify::start(&config.bot_id, &config.notify);
+
+        {
+            let shutdown = self.shutdown.clone();
+
+            panic::set_hook(Box::new(move |p| {
+                error!("Panic coming from one of the threads, exiting");
+                debug!("panic = {:?}", p);
+                notify::notify(notify::Notification::crash(p));
+                shutdown.shutdown();
+            }));
+        }
+
+        // Start the control engine that will connect to botvana-server and
+        // receive the configuration. Then the control engine spawns other engines
+        // based on the configuration it recieves.
+        let control_executor = EngineExecutor::new(
+            config.cpu.control.unwrap_or(0),
+            config.executors.control.clone(),
+        );
+        let coordinator = ShutdownCoordinator::new(&config.shutdown);
+        let control_engine = ControlEngine::new(config)
+            .with_symbols(self.symbols)
+            .with_market_data_registry(self.market_data_registry)
+            .with_strategies(self.strategies)
+            .with_bot_shutdown(self.shutdown.clone())
+            .with_shutdown_coordinator(coordinator.clone());
+        // The control engine is stopped last, after the engines it spawned
+        let control_handle = spawn_engine(
+            control_executor,
+            control_engine,
+            coordinator.phase(ShutdownPhase::Connections),
+        )
+        .map_err(BotnodeError::with_source)?;
+        coordinator.register(ShutdownPhase::Connections, control_handle);
+
+        // Setup signal handlers for shutdown
+        let signals =
+            Signals::new([SIGINT, SIGTERM, SIGQUIT]).map_err(BotnodeError::with_source)?;
+        let completed = crate::rt::block_on(handle_signals(signals, self.shutdown, coordinator));
+
+        if !completed {
+            error!("Engines did not stop before the shutdown deadline, exiting");
+        }
+        if let Some(telemetry) = telemetry {
+            telemetry.shutdown();
+        }
+        if !completed {
+            std::process::exit(1);
+        }
+        info!("Shutdown complete: bye");
+
+        Ok(())
+    }
+}
+
+/// Builder of [`Botnode`]
+pub struct BotnodeBuilder {
+    config: Option<BotnodeConfig>,
+    symbols: Option<SymbolMap>,
+    market_data_registry: MarketDataRegistry,
+    strategies: Vec<StrategyFactory>,
+    paper_trading: bool,
+    telemetry: bool,
+}
+
+impl Default for BotnodeBuilder {
+    fn default() -> Self {
+        Self {
+            config: None,
+            symbols: None,
+            market_data_registry: MarketDataRegistry::default(),
+            strategies: Vec::new(),
+            paper_trading: false,
+            telemetry: true,
+        }
+    }
+}
+
+impl BotnodeBuilder {
+    /// Uses given configuration instead of loading it from the default
+    /// configuration file and environment
+    pub fn config(mut self, config: BotnodeConfig) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Uses given symbol mapping instead of loading `symbols_file`
+    pub fn symbols(mut self, symbols: SymbolMap) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    /// Uses given registry to spawn market data engines
+    pub fn market_data_registry(mut self, registry: MarketDataRegistry) -> Self {
+        self.market_data_registry = registry;
+        self
+    }
+
+    /// Adds strategy run next to the configured ones
+    ///
+    /// The factory creates the strategy every time the trading engine is
+    /// spawned.
+    pub fn strategy<S, F>(mut self, factory: F) -> Self
+    where
+        S: Strategy + 'static,
+        F: Fn() -> S + Send + Sync + 'static,
+    {
+        self.strategies.push(Arc::new(move || -> Box<dyn Strategy> {
+            Box::new(factory())
+        }));
+        self
+    }
+
+    /// Sends all orders to the paper adapter, same as `dry_run = true`
+    pub fn paper_trading(mut self) -> Self {
+        self.paper_trading = true;
+        self
+    }
+
+    /// Sets whether [`Botnode::run`] installs the global tracing subscriber,
+    /// enabled by default
+    pub fn telemetry(mut self, enabled: bool) -> Self {
+        self.telemetry = enabled;
+        self
+    }
+
+    /// Loads missing configuration and validates it
+    pub fn build(self) -> Result<Botnode, BotnodeError> {
+        let mut config = match self.config {
+            Some(config) => config,
+            None => BotnodeConfig::load()?,
+        };
+        config.dry_run |= self.paper_trading;
+        config.validate()?;
+
+        let symbols = match (self.symbols, &config.symbols_file) {
+            (Some(symbols), _) => symbols,
+            (None, Some(path)) => SymbolMap::load(path)?,
+            (None, None) => SymbolMap::default(),
+        };
+
+        Ok(Botnode {
+            config,
+            symbols,
+            market_data_registry: self.market_data_registry,
+            strategies: self.strategies,
+            telemetry: self.telemetry,
+            shutdown: Shutdown::new(),
+        })
+    }
+}
+
+/// Starts the configured local inspection servers
+fn start_api_servers(config: &BotnodeConfig) {
+    #[cfg(feature = "api")]
+    if let Some(addr) = config.api.http_addr {
+        crate::api::http::start(addr);
+    }
+    #[cfg(feature = "api")]
+    if let Some(addr) = config.api.ws_addr {
+        crate::api::ws::start(addr, config.api.ws_interval());
+    }
+    #[cfg(not(feature = "api"))]
+    if config.api.http_addr.is_some() || config.api.ws_addr.is_some() {
+        warn!("api.http_addr or api.ws_addr is set but botnode is built without the `api` feature");
+    }
+
+    #[cfg(feature = "grpc")]
+    if let Some(addr) = config.api.grpc_addr {
+        crate::api::grpc::start(addr);
+    }
+    #[cfg(not(feature = "grpc"))]
+    if config.api.grpc_addr.is_some() {
+        warn!("api.grpc_addr is set but botnode is built without the `grpc` feature");
+    }
+}
+
+/// Handles shutdown signals from OS
+///
+/// The function will wait for one of SIGTERM, SIGINT or SIGQUIT signals, or
+/// the shutdown triggered elsewhere, stops the engines in order and returns
+/// whether they stopped before the deadline.
+async fn handle_signals(
+    signals: Signals,
+    shutdown: Shutdown,
+    coordinator: ShutdownCoordinator,
+) -> bool {
+    let mut signals = signals.fuse();
+    let triggered = shutdown.wait_shutdown_triggered().fuse();
+    let failed = coordinator.wait_triggered().fuse();
+    futures::pin_mut!(triggered, failed);
+
+    futures::select! {
+        signal = signals.next() => match signal {
+            Some(SIGTERM | SIGINT | SIGQUIT) => info!("Shutting down"),
+            _ => unreachable!(),
+        },
+        _ = triggered => info!("Shutting down"),
+        _ = failed => info!("Engine failed, shutting down"),
+    }
+    shutdown.shutdown();
+
+    if !coordinator.shutdown().await {
+        return false;
+    }
+    shutdown.wait_shutdown_complete().await;
+
+    true
+}
+
+/// Error building or running the bot
+#[derive(Debug, thiserror::Error)]
+#[error("Botnode error: {source}")]
+pub struct BotnodeError {
+    source: Box<dyn std::error::Error>,
+}
+
+impl BotnodeError {
+    pub fn with_source<T: std::error::Error + 'static>(source: T) -> Self {
+        Self {
+            source: Box::new(source),
+        }
+    }
+}
+
+impl From<ConfigError> for BotnodeError {
+    fn from(err: ConfigError) -> Self {
+        Self::with_source(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestStrategy;
+
+    impl Strategy for TestStrategy {
+        fn name(&self) -> &str {
+            "test"
+        }
+    }
+
+    #[test]
+    fn test_builder_paper_trading() {
+        let config = BotnodeConfig::from_toml(
+            r#"
+            bot_id = 1
+            server_addr = "127.0.0.1:7978"
+            "#,
+        )
+        .unwrap();
+
+        let botnode = Botnode::builder()
+            .config(config)
+            .strategy(|| TestStrategy)
+            .paper_trading()
+            .telemetry(false)
+            .build()
+            .unwrap();
+
+        assert!(botnode.config().dry_run);
+        assert_eq!(1, botnode.strategies.len());
+        assert_eq!("test", (botnode.strategies[0])().name());
+    }
+}
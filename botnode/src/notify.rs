@@ -0,0 +1,420 @@
+//! Notifications to humans
+//!
+//! Strategies, risk checks and the supervisor push important events (big
+//! fills, tripped kill switch, crashes) with [`notify`] from any thread. The
+//! notifier runs on its own thread, renders each notification with its
+//! template and sends it to the configured Telegram, Discord and Slack
+//! channels.
+//!
+//! Every channel is rate limited with a token bucket, notifications over the
+//! limit are dropped and counted in the next message sent. Critical
+//! notifications are never limited.
+
+#[cfg(feature = "sinks")]
+pub mod sender;
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+use botvana::net::msg::BotId;
+
+/// Maximum number of notifications waiting to be sent
+const QUEUE_CAP: usize = 256;
+
+/// How often the notifier checks for new notifications
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub const FILL: &str = "fill";
+pub const KILL_SWITCH: &str = "kill_switch";
+pub const CRASH: &str = "crash";
+
+/// Templates used unless overridden in the configuration
+const DEFAULT_TEMPLATES: &[(&str, &str)] = &[
+    (FILL, "{bot}: filled {side} {size} {market} @ {price}"),
+    (KILL_SWITCH, "{bot}: kill switch tripped: {reason}"),
+    (CRASH, "{bot}: crashed: {message}"),
+];
+
+static QUEUE: Lazy<Mutex<VecDeque<Notification>>> = Lazy::new(|| Mutex::new(VecDeque::new()));
+
+/// Importance of a notification
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Info,
+    Warning,
+    /// Sent regardless of rate limits
+    Critical,
+}
+
+/// Event to notify about
+#[derive(Clone, Debug, PartialEq)]
+pub struct Notification {
+    /// Event name selecting the template
+    pub event: String,
+    pub priority: Priority,
+    /// Values substituted into the template
+    pub fields: BTreeMap<String, String>,
+}
+
+impl Notification {
+    pub fn new<T: ToString>(event: T, priority: Priority) -> Self {
+        Self {
+            event: event.to_string(),
+            priority,
+            fields: BTreeMap::new(),
+        }
+    }
+
+    /// Adds template field
+    pub fn field<K: ToString, V: ToString>(mut self, key: K, value: V) -> Self {
+        self.fields.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Order fill
+    pub fn fill(market: &str, side: &str, price: f64, size: f64) -> Self {
+        Self::new(FILL, Priority::Info)
+            .field("market", market)
+            .field("side", side)
+            .field("price", price)
+            .field("size", size)
+    }
+
+    /// Kill switch tripped, trading stopped
+    pub fn kill_switch<T: ToString>(reason: T) -> Self {
+        Self::new(KILL_SWITCH, Priority::Critical).field("reason", reason)
+    }
+
+    /// Engine or bot crashed
+    pub fn crash<T: ToString>(message: T) -> Self {
+        Self::new(CRASH, Priority::Critical).field("message", message)
+    }
+}
+
+/// Queues the notification for sending
+///
+/// When the queue is full the oldest notification is dropped.
+pub fn notify(notification: Notification) {
+    tracing::info!(
+        reason = "notification",
+        event = %notification.event,
+        priority = ?notification.priority
+    );
+
+    let mut queue = QUEUE.lock().unwrap_or_else(|e| e.into_inner());
+    if queue.len() >= QUEUE_CAP {
+        queue.pop_front();
+    }
+    queue.push_back(notification);
+}
+
+fn drain() -> Vec<Notification> {
+    QUEUE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .drain(..)
+        .collect()
+}
+
+/// Notifier configuration
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    pub telegram: Option<TelegramConfig>,
+    pub discord: Option<WebhookConfig>,
+    pub slack: Option<WebhookConfig>,
+    /// Notifications below this priority are not sent
+    pub min_priority: Option<Priority>,
+    pub rate_limit: RateLimitConfig,
+    /// Templates by event name, `{field}` is replaced by the field value and
+    /// `{bot}` by the bot name
+    pub templates: HashMap<String, String>,
+}
+
+impl NotifyConfig {
+    /// Returns whether any channel is configured
+    pub fn is_enabled(&self) -> bool {
+        self.telegram.is_some() || self.discord.is_some() || self.slack.is_some()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TelegramConfig {
+    pub token: String,
+    pub chat_id: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub webhook_url: String,
+}
+
+/// Rate limit of each channel
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    /// Notifications that can be sent at once
+    pub burst: u32,
+    /// Seconds to regain a single notification
+    pub refill_secs: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            burst: 5,
+            refill_secs: 12,
+        }
+    }
+}
+
+/// Token bucket limiting how often a channel sends
+#[derive(Debug)]
+pub struct RateLimiter {
+    burst: f64,
+    refill: Duration,
+    tokens: f64,
+    last: Instant,
+    /// Notifications dropped since the last one sent
+    suppressed: u64,
+}
+
+impl RateLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            burst: config.burst as f64,
+            refill: Duration::from_secs(config.refill_secs.max(1)),
+            tokens: config.burst as f64,
+            last: Instant::now(),
+            suppressed: 0,
+        }
+    }
+
+    /// Takes a token at given time, returns the number of notifications
+    /// suppressed before this one or `None` when over the limit
+    pub fn acquire(&mut self, now: Instant, priority: Priority) -> Option<u64> {
+        let elapsed = now.saturating_duration_since(self.last);
+        self.tokens =
+            (self.tokens + elapsed.as_secs_f64() / self.refill.as_secs_f64()).min(self.burst);
+        self.last = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+        } else if priority != Priority::Critical {
+            self.suppressed += 1;
+            return None;
+        }
+
+        Some(std::mem::take(&mut self.suppressed))
+    }
+}
+
+/// Renders notification texts from templates
+#[derive(Clone, Debug)]
+pub struct Templates {
+    bot: String,
+    templates: HashMap<String, String>,
+}
+
+impl Templates {
+    pub fn new(bot_id: &BotId, overrides: &HashMap<String, String>) -> Self {
+        let mut templates: HashMap<_, _> = DEFAULT_TEMPLATES
+            .iter()
+            .map(|(event, template)| (event.to_string(), template.to_string()))
+            .collect();
+        templates.extend(overrides.clone());
+
+        Self {
+            bot: format!("botnode {}", bot_id.0),
+            templates,
+        }
+    }
+
+    /// Renders the notification, events without template list their fields
+    pub fn render(&self, notification: &Notification) -> String {
+        let template = match self.templates.get(&notification.event) {
+            Some(template) => template,
+            None => {
+                let fields: Vec<_> = notification
+                    .fields
+                    .iter()
+                    .map(|(key, value)| format!("{key}={value}"))
+                    .collect();
+                return format!("{}: {} {}", self.bot, notification.event, fields.join(" "))
+                    .trim_end()
+                    .to_string();
+            }
+        };
+
+        notification.fields.iter().fold(
+            template.replace("{bot}", &self.bot),
+            |text, (key, value)| text.replace(&format!("{{{key}}}"), value),
+        )
+    }
+}
+
+/// Channel notifications are sent to
+#[async_trait::async_trait(?Send)]
+pub trait NotificationSender: Send {
+    /// Returns the name of the channel
+    fn name(&self) -> &'static str;
+
+    /// Sends the text
+    async fn send(&self, text: &str) -> Result<(), NotifyError>;
+}
+
+/// Notifier delivering queued notifications to all channels
+pub struct Notifier {
+    templates: Templates,
+    min_priority: Priority,
+    senders: Vec<(Box<dyn NotificationSender>, RateLimiter)>,
+}
+
+impl Notifier {
+    pub fn new(
+        bot_id: &BotId,
+        config: &NotifyConfig,
+        senders: Vec<Box<dyn NotificationSender>>,
+    ) -> Self {
+        Self {
+            templates: Templates::new(bot_id, &config.templates),
+            min_priority: config.min_priority.unwrap_or(Priority::Info),
+            senders: senders
+                .into_iter()
+                .map(|sender| (sender, RateLimiter::new(&config.rate_limit)))
+                .collect(),
+        }
+    }
+
+    /// Sends the notification to every channel under its rate limit
+    pub async fn deliver(&mut self, notification: &Notification) {
+        if notification.priority < self.min_priority {
+            return;
+        }
+
+        let text = self.templates.render(notification);
+        let now = Instant::now();
+
+        for (sender, limiter) in self.senders.iter_mut() {
+            let text = match limiter.acquire(now, notification.priority) {
+                Some(0) => text.clone(),
+                Some(suppressed) => format!("{text}\n({suppressed} notifications suppressed)"),
+                None => continue,
+            };
+
+            if let Err(e) = sender.send(&text).await {
+                tracing::error!("Failed to send {} notification: {e}", sender.name());
+            }
+        }
+    }
+
+    /// Spawns the notifier on its own thread
+    pub fn spawn(mut self) -> thread::JoinHandle<()> {
+        thread::Builder::new()
+            .name("notifier".to_string())
+            .spawn(move || {
+                crate::rt::block_on(async move {
+                    loop {
+                        for notification in drain() {
+                            self.deliver(&notification).await;
+                        }
+                        crate::rt::timer::sleep(POLL_INTERVAL).await;
+                    }
+                })
+            })
+            .expect("failed to spawn notifier")
+    }
+}
+
+/// Starts the notifier when any channel is configured
+pub fn start(bot_id: &BotId, config: &NotifyConfig) -> Option<thread::JoinHandle<()>> {
+    if !config.is_enabled() {
+        return None;
+    }
+
+    #[cfg(feature = "sinks")]
+    {
+        let senders = sender::senders(config);
+        tracing::info!("Sending notifications to {} channels", senders.len());
+        Some(Notifier::new(bot_id, config, senders).spawn())
+    }
+    #[cfg(not(feature = "sinks"))]
+    {
+        let _ = bot_id;
+        tracing::warn!(
+            "Notifications are configured but botnode is built without the `sinks` feature"
+        );
+        None
+    }
+}
+
+/// Error sending a notification
+#[derive(Debug, thiserror::Error)]
+#[error("Notification error: {source}")]
+pub struct NotifyError {
+    pub source: Box<dyn std::error::Error>,
+}
+
+impl NotifyError {
+    pub fn new<T: ToString>(msg: T) -> Self {
+        Self {
+            source: msg.to_string().into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_templates() {
+        let mut overrides = HashMap::new();
+        overrides.insert(CRASH.to_string(), "{bot} is down ({message})".to_string());
+        let templates = Templates::new(&BotId(3), &overrides);
+
+        assert_eq!(
+            "botnode 3: filled buy 0.5 BTC-PERP @ 100",
+            templates.render(&Notification::fill("BTC-PERP", "buy", 100.0, 0.5))
+        );
+        assert_eq!(
+            "botnode 3 is down (oom)",
+            templates.render(&Notification::crash("oom"))
+        );
+        assert_eq!(
+            "botnode 3: custom a=1",
+            templates.render(&Notification::new("custom", Priority::Info).field("a", 1))
+        );
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut limiter = RateLimiter::new(&RateLimitConfig {
+            burst: 2,
+            refill_secs: 10,
+        });
+        let now = Instant::now();
+
+        assert_eq!(Some(0), limiter.acquire(now, Priority::Info));
+        assert_eq!(Some(0), limiter.acquire(now, Priority::Info));
+        assert_eq!(None, limiter.acquire(now, Priority::Warning));
+        // Critical notifications go through and report the suppressed ones
+        assert_eq!(Some(1), limiter.acquire(now, Priority::Critical));
+
+        assert_eq!(None, limiter.acquire(now, Priority::Info));
+        assert_eq!(
+            Some(1),
+            limiter.acquire(now + Duration::from_secs(10), Priority::Info)
+        );
+    }
+}
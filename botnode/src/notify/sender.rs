@@ -0,0 +1,121 @@
+//! Telegram, Discord and Slack senders
+
+use serde_json::{json, Value};
+
+use super::{NotificationSender, NotifyConfig, NotifyError};
+
+/// Sends messages using the Telegram bot API
+#[derive(Debug)]
+pub struct TelegramSender {
+    token: String,
+    chat_id: String,
+}
+
+impl TelegramSender {
+    pub fn new<T: ToString, C: ToString>(token: T, chat_id: C) -> Self {
+        Self {
+            token: token.to_string(),
+            chat_id: chat_id.to_string(),
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl NotificationSender for TelegramSender {
+    fn name(&self) -> &'static str {
+        "telegram"
+    }
+
+    async fn send(&self, text: &str) -> Result<(), NotifyError> {
+        let url = format!("https://api.telegram.org/bot{}/sendMessage", self.token);
+        post(&url, json!({ "chat_id": self.chat_id, "text": text })).await
+    }
+}
+
+/// Posts messages to a Discord channel webhook
+#[derive(Debug)]
+pub struct DiscordSender {
+    webhook_url: String,
+}
+
+impl DiscordSender {
+    pub fn new<T: ToString>(webhook_url: T) -> Self {
+        Self {
+            webhook_url: webhook_url.to_string(),
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl NotificationSender for DiscordSender {
+    fn name(&self) -> &'static str {
+        "discord"
+    }
+
+    async fn send(&self, text: &str) -> Result<(), NotifyError> {
+        post(&self.webhook_url, json!({ "content": text })).await
+    }
+}
+
+/// Posts messages to a Slack incoming webhook
+#[derive(Debug)]
+pub struct SlackSender {
+    webhook_url: String,
+}
+
+impl SlackSender {
+    pub fn new<T: ToString>(webhook_url: T) -> Self {
+        Self {
+            webhook_url: webhook_url.to_string(),
+        }
+    }
+}
+
+#[async_trait::async_trait(?Send)]
+impl NotificationSender for SlackSender {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    async fn send(&self, text: &str) -> Result<(), NotifyError> {
+        post(&self.webhook_url, json!({ "text": text })).await
+    }
+}
+
+/// Posts JSON body, failing on non-success status
+async fn post(url: &str, body: Value) -> Result<(), NotifyError> {
+    let res = surf::post(url)
+        .body_json(&body)
+        .map_err(NotifyError::new)?
+        .await
+        .map_err(NotifyError::new)?;
+
+    if !res.status().is_success() {
+        return Err(NotifyError::new(format!(
+            "unexpected response status {}",
+            res.status()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Builds senders of the configured channels
+pub fn senders(config: &NotifyConfig) -> Vec<Box<dyn NotificationSender>> {
+    let mut senders: Vec<Box<dyn NotificationSender>> = Vec::new();
+
+    if let Some(telegram) = &config.telegram {
+        senders.push(Box::new(TelegramSender::new(
+            &telegram.token,
+            &telegram.chat_id,
+        )));
+    }
+    if let Some(discord) = &config.discord {
+        senders.push(Box::new(DiscordSender::new(&discord.webhook_url)));
+    }
+    if let Some(slack) = &config.slack {
+        senders.push(Box::new(SlackSender::new(&slack.webhook_url)));
+    }
+
+    senders
+}
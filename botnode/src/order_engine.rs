@@ -0,0 +1,55 @@
+//! Order engine
+//!
+//! The order engine owns the execution adapter of the bot. It receives
+//! [`ExchangeRequest`]s from the trading engine, places, amends and cancels
+//! orders through the adapter and pushes every order state change and fill
+//! back as [`ExchangeEvent`]s over its ring channels.
+
+pub(crate) mod adapter;
+pub(crate) mod engine;
+pub(crate) mod error;
+#[cfg(feature = "ftx")]
+pub(crate) mod ftx;
+pub(crate) mod matching;
+pub(crate) mod null_adapter;
+pub(crate) mod order_request;
+pub(crate) mod order_response;
+pub(crate) mod paper_trading_adapter;
+pub(crate) mod registry;
+
+pub use adapter::ExecutionAdapter;
+pub use error::ExchangeError;
+pub use order_request::{AmendRequest, CancelRequest, OrderRequest};
+pub use order_response::{Fill, OrderStatus, OrderUpdate};
+
+/// Event generated by an exchange - order or balance related
+#[derive(Clone, Debug)]
+pub enum ExchangeEvent {
+    BalanceChange,
+    /// Order changed its state
+    Order(OrderUpdate),
+    /// Order was filled, fully or partially
+    Fill(Fill),
+}
+
+/// Request to the order engine
+#[derive(Clone, Debug)]
+pub enum ExchangeRequest {
+    PlaceOrder(OrderRequest),
+    CancelOrder(CancelRequest),
+    AmendOrder(AmendRequest),
+    /// Cancels all open orders, optionally only on one market
+    CancelAll(Option<Box<str>>),
+}
+
+impl ExchangeRequest {
+    /// Returns lower-case name of the request kind
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExchangeRequest::PlaceOrder(_) => "place",
+            ExchangeRequest::CancelOrder(_) => "cancel",
+            ExchangeRequest::AmendOrder(_) => "amend",
+            ExchangeRequest::CancelAll(_) => "cancel_all",
+        }
+    }
+}
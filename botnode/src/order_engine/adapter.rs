@@ -0,0 +1,57 @@
+//! Execution Adapter
+//!
+//! This module defines the execution adapter trait that when implemented
+//! allows the order engine to trade on any exchange.
+
+use super::{
+    error::ExchangeError,
+    order_request::{AmendRequest, CancelRequest, OrderRequest},
+    order_response::OrderUpdate,
+    ExchangeEvent,
+};
+use crate::{prelude::*, secrets::SecretsProvider};
+
+/// How often adapters are polled for events unless they ask otherwise
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Execution adapter trait
+///
+/// Order methods return the state of the order as acknowledged by the
+/// exchange. Fills and state changes that happen later on, like a resting
+/// order getting filled, are returned by [`ExecutionAdapter::poll_events`].
+#[async_trait(?Send)]
+pub trait ExecutionAdapter {
+    /// Returns name of the adapter
+    fn name(&self) -> &'static str;
+
+    /// Returns how often [`ExecutionAdapter::poll_events`] is called,
+    /// adapters streaming the events are polled on every iteration of the
+    /// order engine
+    fn poll_interval(&self) -> Duration {
+        DEFAULT_POLL_INTERVAL
+    }
+
+    /// Prepares the adapter for trading, e.g. loads API credentials
+    async fn connect(&mut self, _secrets: &dyn SecretsProvider) -> Result<(), ExchangeError> {
+        Ok(())
+    }
+
+    /// Places new order
+    async fn place_order(&mut self, order: &OrderRequest) -> Result<OrderUpdate, ExchangeError>;
+
+    /// Cancels open order
+    async fn cancel_order(&mut self, cancel: &CancelRequest) -> Result<OrderUpdate, ExchangeError>;
+
+    /// Changes price or size of open order
+    async fn amend_order(&mut self, amend: &AmendRequest) -> Result<OrderUpdate, ExchangeError>;
+
+    /// Cancels all open orders, only on the market if given
+    async fn cancel_all(&mut self, market: Option<&str>) -> Result<(), ExchangeError>;
+
+    /// Returns fills and order updates since the previous call
+    async fn poll_events(&mut self) -> Result<Vec<ExchangeEvent>, ExchangeError>;
+
+    /// Called with every market event of the exchange orders are executed
+    /// on, adapters simulating execution fill orders against it
+    fn on_market_event(&mut self, _event: &MarketEvent) {}
+}
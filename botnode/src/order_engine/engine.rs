@@ -0,0 +1,558 @@
+use botvana::cfg::ConfigUpdate;
+use tracing::Instrument;
+
+use crate::audit::{AuditEvent, AuditProducer, AuditRecord, Category, Severity};
+use crate::bus::Subscriber;
+use crate::control::ControlCommand;
+use crate::order_engine::*;
+use crate::risk_engine::{RiskConfig, RiskEngine};
+use crate::{
+    latency::{LatencyHistogram, TickToTrade},
+    metrics::MetricLabels,
+    prelude::*,
+    secrets::SecretsConfig,
+};
+
+const CONSUMER_LIMIT: usize = 16;
+const QUEUE_LEN: usize = 1024;
+
+/// How often the kill switch is checked
+const KILL_SWITCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Order engine for Botnode
+pub struct OrderEngine<A> {
+    adapter: A,
+    config_rx: spsc_queue::Consumer<BotConfiguration>,
+    request_rx: spsc_queue::Consumer<ExchangeRequest>,
+    commands: Subscriber<ControlCommand>,
+    /// Market data passed to the adapter, only events of the exchange
+    /// orders are executed on
+    market_data_rxs: ConsumersMap<Box<str>, MarketEvent>,
+    market_data_exchange: Option<ExchangeId>,
+    audit_tx: AuditProducer,
+    secrets: SecretsConfig,
+    risk: RiskEngine,
+    /// Open orders were cancelled after the kill switch tripped
+    killed: bool,
+    data_txs: ArrayVec<spsc_queue::Producer<ExchangeEvent>, CONSUMER_LIMIT>,
+    status_tx: spsc_queue::Producer<EngineStatus>,
+    status_rx: spsc_queue::Consumer<EngineStatus>,
+}
+
+impl<A: ExecutionAdapter> OrderEngine<A> {
+    pub fn new(
+        config_rx: spsc_queue::Consumer<BotConfiguration>,
+        adapter: A,
+        request_rx: spsc_queue::Consumer<ExchangeRequest>,
+        audit_tx: AuditProducer,
+        secrets: SecretsConfig,
+        risk: RiskConfig,
+    ) -> Self {
+        let (status_tx, status_rx) = spsc_queue::make(1);
+        Self {
+            adapter,
+            config_rx,
+            request_rx,
+            commands: Subscriber::default(),
+            market_data_rxs: ConsumersMap::default(),
+            market_data_exchange: None,
+            audit_tx,
+            secrets,
+            risk: RiskEngine::new(risk),
+            killed: false,
+            data_txs: ArrayVec::new(),
+            status_tx,
+            status_rx,
+        }
+    }
+
+    /// Sets subscription to the commands of botvana-server
+    pub fn with_commands(mut self, commands: Subscriber<ControlCommand>) -> Self {
+        self.commands = commands;
+        self
+    }
+
+    /// Sets market data passed to the adapter, events of other exchanges
+    /// than `exchange` are dropped
+    pub fn with_market_data(
+        mut self,
+        exchange: ExchangeId,
+        market_data_rxs: ConsumersMap<Box<str>, MarketEvent>,
+    ) -> Self {
+        self.market_data_exchange = Some(exchange);
+        self.market_data_rxs = market_data_rxs;
+        self
+    }
+
+    fn audit<T: ToString>(&self, severity: Severity, category: Category, message: T) {
+        crate::audit::record(
+            &self.audit_tx,
+            AuditEvent::new(severity, category, "order-engine", message),
+        );
+    }
+
+    fn audit_record<T: ToString>(
+        &self,
+        severity: Severity,
+        category: Category,
+        message: T,
+        record: AuditRecord,
+    ) {
+        crate::audit::record(
+            &self.audit_tx,
+            AuditEvent::new(severity, category, "order-engine", message).with_record(record),
+        );
+    }
+
+    /// Runs the order event loop
+    async fn run_event_loop(&mut self, shutdown: Shutdown) -> Result<(), EngineError> {
+        let mut tick_to_trade = TickToTrade::default();
+        let mut loop_latency = LatencyHistogram::default();
+        let mut start = std::time::Instant::now();
+        let mut last_poll = std::time::Instant::now();
+        let mut last_kill_switch_check = std::time::Instant::now();
+        let mut last_iteration = std::time::Instant::now();
+
+        self.status_tx.try_push(EngineStatus::Running);
+
+        loop {
+            if shutdown.shutdown_started() {
+                // Strategies are stopped before the order engine, nothing
+                // replaces the cancelled orders
+                self.process_request(ExchangeRequest::CancelAll(None), &mut tick_to_trade)
+                    .await;
+
+                break Ok(());
+            }
+
+            loop_latency.record(last_iteration.elapsed());
+            last_iteration = std::time::Instant::now();
+
+            // Markets of other exchanges are drained too so their queues
+            // don't fill up
+            for (exchange, market_data_rx) in self.market_data_rxs.iter() {
+                if let Some(event) = market_data_rx.try_pop() {
+                    if matches!(self.market_data_exchange, Some(e) if e.as_str() == &**exchange) {
+                        self.adapter.on_market_event(&event);
+                    }
+                }
+            }
+
+            if let Some(request) = self.request_rx.try_pop() {
+                self.process_request(request, &mut tick_to_trade).await;
+            }
+
+            match self.commands.try_recv() {
+                Some(ControlCommand::ConfigUpdate(ConfigUpdate {
+                    risk: Some(limits), ..
+                })) => {
+                    self.audit(
+                        Severity::Warning,
+                        Category::Config,
+                        format!("risk limits updated by server: {limits:?}"),
+                    );
+                    self.risk.set_config(limits.into());
+                }
+                Some(ControlCommand::FlattenPositions) => {
+                    for request in self.flatten_requests() {
+                        self.process_request(request, &mut tick_to_trade).await;
+                    }
+                }
+                _ => {}
+            }
+
+            if last_poll.elapsed() >= self.adapter.poll_interval() {
+                last_poll = std::time::Instant::now();
+                match self.adapter.poll_events().await {
+                    Ok(events) => events.into_iter().for_each(|event| self.publish(event)),
+                    Err(e) => warn!("Failed to poll {}: {e}", self.adapter.name()),
+                }
+            }
+            if last_kill_switch_check.elapsed() >= KILL_SWITCH_INTERVAL {
+                last_kill_switch_check = std::time::Instant::now();
+                self.check_kill_switch().await;
+            }
+
+            if start.elapsed().as_secs() >= 5 {
+                start = std::time::Instant::now();
+                tick_to_trade.publish();
+                loop_latency.publish(
+                    "engine_loop_iteration_microseconds",
+                    &MetricLabels::new().with("engine", "order-engine"),
+                );
+            }
+        }
+    }
+
+    /// Returns requests cancelling all open orders and closing the filled
+    /// positions with market orders
+    fn flatten_requests(&mut self) -> Vec<ExchangeRequest> {
+        let closing = self.risk.closing_orders();
+        self.audit(
+            Severity::Critical,
+            Category::Order,
+            format!("flattening {} positions", closing.len()),
+        );
+
+        let session = Utc::now().timestamp_millis();
+        std::iter::once(ExchangeRequest::CancelAll(None))
+            .chain(
+                closing
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, (market, side, size))| {
+                        let client_id = format!("flatten-{session}-{idx}");
+                        ExchangeRequest::PlaceOrder(OrderRequest::market(
+                            &client_id, &market, side, size,
+                        ))
+                    }),
+            )
+            .collect()
+    }
+
+    /// Cancels all open orders once the kill switch trips
+    async fn check_kill_switch(&mut self) {
+        match crate::risk_engine::kill_switch() {
+            Some(reason) if !self.killed => {
+                self.killed = true;
+                self.audit(
+                    Severity::Critical,
+                    Category::Order,
+                    format!("kill switch tripped ({reason}), cancelling all orders"),
+                );
+
+                if let Err(e) = self.adapter.cancel_all(None).await {
+                    self.audit(
+                        Severity::Critical,
+                        Category::Execution,
+                        format!("failed to cancel all orders: {e}"),
+                    );
+                }
+            }
+            Some(_) => {}
+            None => self.killed = false,
+        }
+    }
+
+    async fn process_request(&mut self, request: ExchangeRequest, tick_to_trade: &mut TickToTrade) {
+        crate::metrics::registry().incr_counter(
+            "order_requests_total",
+            &MetricLabels::new().with("request", request.as_str()),
+            1,
+        );
+
+        match request {
+            ExchangeRequest::PlaceOrder(order) => {
+                trace!("placing order = {order:?}");
+                self.audit(
+                    Severity::Info,
+                    Category::Order,
+                    format!(
+                        "placing order {} {} {} {} @ {:?}",
+                        order.client_id,
+                        order.side.as_str(),
+                        order.size,
+                        order.market,
+                        order.price
+                    ),
+                );
+
+                if let Some(tick) = &order.tick {
+                    tick_to_trade.record(tick);
+                }
+
+                let send_span = order
+                    .tick
+                    .as_ref()
+                    .and_then(|tick| tick.span.as_ref())
+                    .map_or_else(tracing::Span::none, |span| {
+                        tracing::info_span!(
+                            parent: span,
+                            "order_send",
+                            client_id = &*order.client_id
+                        )
+                    });
+                let res = match self.risk.check_order(&order, std::time::Instant::now()) {
+                    Ok(()) => self.adapter.place_order(&order).instrument(send_span).await,
+                    Err(rejection) => {
+                        self.audit_record(
+                            Severity::Warning,
+                            Category::Risk,
+                            format!("order {} rejected: {rejection}", order.client_id),
+                            AuditRecord::RiskRejection {
+                                client_id: order.client_id.clone(),
+                                market: order.market.clone(),
+                                side: order.side,
+                                price: order.price,
+                                size: order.size,
+                                reason: rejection.to_string(),
+                            },
+                        );
+                        Err(ExchangeError::new(rejection))
+                    }
+                };
+                let update = match res {
+                    Ok(update) => update,
+                    // Rejections are order state changes the strategy has to
+                    // know about
+                    Err(e) => OrderUpdate {
+                        client_id: order.client_id,
+                        exchange_id: None,
+                        market: order.market,
+                        side: order.side,
+                        price: order.price,
+                        size: order.size,
+                        filled_size: 0.0,
+                        status: OrderStatus::Rejected,
+                        reason: Some(e.to_string()),
+                    },
+                };
+                self.publish(ExchangeEvent::Order(update));
+            }
+            ExchangeRequest::CancelOrder(cancel) => {
+                trace!("cancelling order = {cancel:?}");
+                self.audit(
+                    Severity::Info,
+                    Category::Order,
+                    format!("cancelling order {}", cancel.client_id),
+                );
+
+                match self.adapter.cancel_order(&cancel).await {
+                    Ok(update) => self.publish(ExchangeEvent::Order(update)),
+                    Err(e) => self.audit(
+                        Severity::Warning,
+                        Category::Execution,
+                        format!("failed to cancel order {}: {e}", cancel.client_id),
+                    ),
+                }
+            }
+            ExchangeRequest::AmendOrder(amend) => {
+                trace!("amending order = {amend:?}");
+                self.audit(
+                    Severity::Info,
+                    Category::Order,
+                    format!(
+                        "amending order {} price {:?} size {:?}",
+                        amend.client_id, amend.price, amend.size
+                    ),
+                );
+
+                if let Some(reason) = crate::risk_engine::kill_switch() {
+                    self.audit(
+                        Severity::Warning,
+                        Category::Execution,
+                        format!(
+                            "amend of order {} rejected: kill switch tripped: {reason}",
+                            amend.client_id
+                        ),
+                    );
+                    return;
+                }
+
+                match self.adapter.amend_order(&amend).await {
+                    Ok(update) => self.publish(ExchangeEvent::Order(update)),
+                    Err(e) => self.audit(
+                        Severity::Warning,
+                        Category::Execution,
+                        format!("failed to amend order {}: {e}", amend.client_id),
+                    ),
+                }
+            }
+            ExchangeRequest::CancelAll(market) => {
+                let scope = market.as_deref().unwrap_or("all markets");
+                self.audit(
+                    Severity::Warning,
+                    Category::Order,
+                    format!("cancelling all orders on {scope}"),
+                );
+
+                if let Err(e) = self.adapter.cancel_all(market.as_deref()).await {
+                    self.audit(
+                        Severity::Critical,
+                        Category::Execution,
+                        format!("failed to cancel all orders on {scope}: {e}"),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Audits the event and pushes it to all consumers
+    fn publish(&mut self, event: ExchangeEvent) {
+        let registry = crate::metrics::registry();
+        match &event {
+            ExchangeEvent::Order(update) => {
+                self.risk.on_order_update(update);
+                registry.incr_counter(
+                    "order_updates_total",
+                    &MetricLabels::new()
+                        .symbol(&update.market)
+                        .with("status", update.status.as_str()),
+                    1,
+                );
+            }
+            ExchangeEvent::Fill(fill) => {
+                self.risk.on_fill(fill);
+                registry.incr_counter(
+                    "order_fills_total",
+                    &MetricLabels::new().symbol(&fill.market),
+                    1,
+                );
+            }
+            ExchangeEvent::BalanceChange => {}
+        }
+
+        match &event {
+            ExchangeEvent::Order(update) => self.audit_record(
+                match update.status {
+                    OrderStatus::Rejected => Severity::Warning,
+                    _ => Severity::Info,
+                },
+                Category::Execution,
+                format!(
+                    "order {} {}{}",
+                    update.client_id,
+                    update.status.as_str(),
+                    update
+                        .reason
+                        .as_ref()
+                        .map(|reason| format!(": {reason}"))
+                        .unwrap_or_default()
+                ),
+                AuditRecord::order_update(update),
+            ),
+            ExchangeEvent::Fill(fill) => self.audit_record(
+                Severity::Info,
+                Category::Execution,
+                format!(
+                    "filled {} {} {} @ {}",
+                    fill.side.as_str(),
+                    fill.size,
+                    fill.market,
+                    fill.price
+                ),
+                AuditRecord::fill(fill),
+            ),
+            ExchangeEvent::BalanceChange => {}
+        }
+
+        self.push_value(event);
+    }
+}
+
+#[async_trait(?Send)]
+impl<A: ExecutionAdapter> Engine for OrderEngine<A> {
+    fn name(&self) -> String {
+        "order-engine".to_string()
+    }
+
+    fn status_rx(&self) -> spsc_queue::Consumer<EngineStatus> {
+        self.status_rx.clone()
+    }
+
+    async fn start(mut self, shutdown: Shutdown) -> Result<(), EngineError> {
+        info!("Starting order engine with {}", self.adapter.name());
+
+        self.status_tx.try_push(EngineStatus::Booting);
+
+        let config = await_value(self.config_rx.clone());
+        info!("got config = {config:?}");
+
+        let secrets = crate::secrets::provider(&self.secrets).map_err(EngineError::config)?;
+        if let Err(e) = self.adapter.connect(&*secrets).await {
+            self.status_tx.try_push(EngineStatus::Error);
+            self.audit(
+                Severity::Critical,
+                Category::Lifecycle,
+                format!("failed to connect {}: {e}", self.adapter.name()),
+            );
+            return Err(EngineError::adapter(e));
+        }
+
+        self.run_event_loop(shutdown).await
+    }
+}
+
+#[async_trait(?Send)]
+impl<A: ExecutionAdapter> EngineData for OrderEngine<A> {
+    type Data = ExchangeEvent;
+
+    fn data_txs(&self) -> &[spsc_queue::Producer<Self::Data>] {
+        &self.data_txs
+    }
+
+    fn data_rx(&mut self) -> spsc_queue::Consumer<Self::Data> {
+        let (data_tx, data_rx) = spsc_queue::make(QUEUE_LEN);
+        self.data_txs.push(data_tx);
+        data_rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        order_engine::paper_trading_adapter::PaperTradingAdapter, trading::strategy::Side,
+    };
+
+    #[test]
+    fn test_process_request_rejection() {
+        let (_config_tx, config_rx) = spsc_queue::make(1);
+        let (_request_tx, request_rx) = spsc_queue::make(1);
+        let (audit_tx, audit_rx) = spsc_queue::make(16);
+        let mut engine = OrderEngine::new(
+            config_rx,
+            PaperTradingAdapter::default(),
+            request_rx,
+            audit_tx,
+            SecretsConfig::default(),
+            RiskConfig {
+                max_order_size: Some(2.0),
+                ..Default::default()
+            },
+        );
+        let events_rx = engine.data_rx();
+        let mut tick_to_trade = TickToTrade::default();
+
+        crate::rt::block_on(async {
+            let order = OrderRequest::limit("1", "BTC-PERP", Side::Buy, 100.0, 1.0);
+            let oversized = OrderRequest::limit("2", "BTC-PERP", Side::Buy, 100.0, 3.0);
+            for order in [order.clone(), order, oversized] {
+                engine
+                    .process_request(ExchangeRequest::PlaceOrder(order), &mut tick_to_trade)
+                    .await;
+            }
+        });
+
+        match events_rx.try_pop() {
+            Some(ExchangeEvent::Order(update)) => assert_eq!(OrderStatus::Open, update.status),
+            other => panic!("unexpected event {other:?}"),
+        }
+        // Duplicate client id
+        match events_rx.try_pop() {
+            Some(ExchangeEvent::Order(update)) => {
+                assert_eq!(OrderStatus::Rejected, update.status);
+                assert!(update.reason.is_some());
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+        // Over the risk limit
+        match events_rx.try_pop() {
+            Some(ExchangeEvent::Order(update)) => {
+                assert_eq!(OrderStatus::Rejected, update.status);
+                assert!(update.reason.unwrap().contains("over limit"));
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+        let mut risk_rejections = Vec::new();
+        while let Some(event) = audit_rx.try_pop() {
+            if event.category == Category::Risk {
+                risk_rejections.push(event.record);
+            }
+        }
+        match &risk_rejections[..] {
+            [Some(AuditRecord::RiskRejection { client_id, .. })] => assert_eq!("2", &**client_id),
+            other => panic!("unexpected risk rejections {other:?}"),
+        }
+    }
+}
@@ -5,6 +5,12 @@ pub struct ExchangeError {
 }
 
 impl ExchangeError {
+    pub fn new<T: ToString>(reason: T) -> Self {
+        Self {
+            source: reason.to_string().into(),
+        }
+    }
+
     pub(crate) fn with_source(err: impl std::error::Error + 'static) -> Self {
         Self {
             source: Box::new(err),
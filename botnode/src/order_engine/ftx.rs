@@ -0,0 +1,599 @@
+//! FTX execution adapter
+//!
+//! Orders are placed, amended and cancelled by client id over the REST API.
+//! Every request is signed with the `ftx.api_key` and `ftx.api_secret`
+//! secrets, `ftx.subaccount` selects the subaccount to trade on.
+//!
+//! Fills and order changes are streamed by the authenticated websocket,
+//! which the order engine polls on every iteration. While it's down fills
+//! are polled from `/api/fills` instead, and once it's subscribed again the
+//! fills missed meanwhile are caught up over REST.
+
+pub(crate) mod rest;
+pub(crate) mod ws;
+
+use std::time::Instant;
+
+use async_tungstenite::{async_std::connect_async, tungstenite::Message};
+use ring::hmac;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use surf::{http::Method, Url};
+
+use super::*;
+use crate::{
+    prelude::*,
+    rate_limit::{self, Endpoint},
+    secrets::SecretsProvider,
+    util::backoff::{Backoff, BackoffConfig},
+};
+
+const API_URL: &str = "https://ftx.com";
+const WS_URL: &str = "wss://ftx.com/ws";
+
+/// How often fills are polled over REST while the websocket is down
+const REST_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Time without any message after which the websocket is reconnected
+const STALE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Client ids of closed orders kept for their late fills
+const CLOSED_CLIENT_IDS: usize = 1024;
+
+/// FTX execution adapter
+pub(crate) struct Ftx {
+    api_url: Box<str>,
+    ws_url: Box<str>,
+    credentials: Option<Credentials>,
+    /// Authenticated websocket streaming fills and orders
+    ws: Option<ws::Connection>,
+    ws_backoff: Backoff,
+    /// Websocket is reconnected after this time
+    ws_reconnect_at: Option<Instant>,
+    last_rest_poll: Instant,
+    /// Open orders by client id
+    orders: HashMap<Box<str>, OrderUpdate>,
+    /// Client ids by FTX order id, fills only carry the latter
+    client_ids: HashMap<u64, Box<str>>,
+    /// Fills up to this one were already returned
+    last_fill_id: u64,
+    /// Time of the last fill returned, in seconds
+    last_fill_time: i64,
+}
+
+impl Default for Ftx {
+    fn default() -> Self {
+        Self {
+            api_url: Box::from(API_URL),
+            ws_url: Box::from(WS_URL),
+            credentials: None,
+            ws: None,
+            ws_backoff: Backoff::new(BackoffConfig::default()),
+            ws_reconnect_at: None,
+            last_rest_poll: Instant::now(),
+            orders: HashMap::new(),
+            client_ids: HashMap::new(),
+            last_fill_id: 0,
+            last_fill_time: Utc::now().timestamp(),
+        }
+    }
+}
+
+struct Credentials {
+    api_key: String,
+    key: hmac::Key,
+    subaccount: Option<String>,
+}
+
+impl Ftx {
+    /// Sends signed request, returning the `result` of the response
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+    ) -> Result<T, ExchangeError> {
+        let credentials = self
+            .credentials
+            .as_ref()
+            .ok_or_else(|| ExchangeError::new("FTX adapter is not connected"))?;
+        let url =
+            Url::parse(&format!("{}{path}", self.api_url)).map_err(ExchangeError::with_source)?;
+        let body = body.map(|body| body.to_string()).unwrap_or_default();
+        // Waits before signing, FTX rejects stale timestamps
+        rate_limit::acquire(ExchangeId::Ftx, Endpoint::Orders).await;
+        let ts = Utc::now().timestamp_millis();
+
+        let mut req = surf::RequestBuilder::new(method, url)
+            .header("FTX-KEY", credentials.api_key.as_str())
+            .header("FTX-TS", ts.to_string())
+            .header(
+                "FTX-SIGN",
+                sign(&credentials.key, ts, method.as_ref(), path, &body),
+            );
+        if let Some(subaccount) = &credentials.subaccount {
+            req = req.header("FTX-SUBACCOUNT", subaccount.as_str());
+        }
+        if !body.is_empty() {
+            req = req.body(body).content_type(surf::http::mime::JSON);
+        }
+
+        let mut res = req.await.map_err(ExchangeError::new)?;
+        let body = res.body_string().await.map_err(ExchangeError::new)?;
+
+        serde_json::from_str::<rest::Response<T>>(&body)
+            .map_err(ExchangeError::with_source)?
+            .into_result()
+    }
+
+    /// Records the order returned by FTX and converts it to an update
+    fn track_order(&mut self, client_id: &str, order: rest::Order) -> OrderUpdate {
+        let update = order.to_update(client_id);
+        self.client_ids.insert(order.id, Box::from(client_id));
+
+        if update.status.is_closed() {
+            self.orders.remove(client_id);
+        } else {
+            self.orders.insert(Box::from(client_id), update.clone());
+        }
+
+        update
+    }
+
+    fn order(&self, client_id: &str) -> Result<&OrderUpdate, ExchangeError> {
+        self.orders
+            .get(client_id)
+            .ok_or_else(|| ExchangeError::new(format!("unknown order {client_id}")))
+    }
+
+    /// Converts new fill to events, updating the filled order unless the
+    /// order changes are streamed
+    fn process_fill(&mut self, fill: rest::Fill, update_order: bool) -> Vec<ExchangeEvent> {
+        let mut events = Vec::with_capacity(2);
+        self.last_fill_id = fill.id;
+        self.last_fill_time = fill.time.timestamp();
+        let client_id = fill
+            .order_id
+            .and_then(|order_id| self.client_ids.get(&order_id).cloned());
+
+        if let Some(order) = client_id
+            .as_ref()
+            .filter(|_| update_order)
+            .and_then(|client_id| self.orders.get_mut(client_id))
+        {
+            order.filled_size += fill.size;
+            order.status = if order.remaining_size() <= f64::EPSILON {
+                OrderStatus::Filled
+            } else {
+                OrderStatus::PartiallyFilled
+            };
+            events.push(ExchangeEvent::Order(order.clone()));
+
+            if order.status.is_closed() {
+                let client_id = order.client_id.clone();
+                self.orders.remove(&client_id);
+                self.client_ids.retain(|_, id| *id != client_id);
+            }
+        }
+
+        events.insert(
+            0,
+            ExchangeEvent::Fill(Fill {
+                client_id,
+                exchange_id: fill.id.to_string().into_boxed_str(),
+                market: Box::from(fill.market),
+                side: fill.side.into(),
+                price: fill.price,
+                size: fill.size,
+                fee: fill.fee,
+                time: fill.time.timestamp_millis() as u64,
+            }),
+        );
+
+        events
+    }
+
+    /// Converts streamed change of an open order to an update, `None` when
+    /// nothing changed since the order was last returned
+    ///
+    /// Orders not placed by the adapter or already reported as closed, e.g.
+    /// by cancelling them, are skipped.
+    fn process_order(&mut self, order: rest::Order) -> Option<ExchangeEvent> {
+        let client_id = match &order.client_id {
+            Some(client_id) => Box::from(client_id.as_str()),
+            None => self.client_ids.get(&order.id)?.clone(),
+        };
+        let known = self.orders.get(&client_id)?;
+        let update = order.to_update(&client_id);
+        if known.status == update.status
+            && known.filled_size == update.filled_size
+            && known.price == update.price
+            && known.size == update.size
+        {
+            return None;
+        }
+
+        let update = self.track_order(&client_id, order);
+        if update.status.is_closed()
+            && self.client_ids.len() > self.orders.len() + CLOSED_CLIENT_IDS
+        {
+            let orders = &self.orders;
+            self.client_ids.retain(|_, id| orders.contains_key(id));
+        }
+
+        Some(ExchangeEvent::Order(update))
+    }
+
+    /// Polls fills since the last one returned over REST
+    async fn poll_fills(&mut self, events: &mut Vec<ExchangeEvent>) -> Result<(), ExchangeError> {
+        self.last_rest_poll = Instant::now();
+        let path = format!("/api/fills?start_time={}", self.last_fill_time);
+        let mut fills: Vec<rest::Fill> = self.request(Method::Get, &path, None).await?;
+        fills.retain(|fill| fill.id > self.last_fill_id);
+        fills.sort_by_key(|fill| fill.id);
+
+        for fill in fills {
+            events.extend(self.process_fill(fill, true));
+        }
+
+        Ok(())
+    }
+
+    /// Connects the websocket, logs in and subscribes the channels
+    async fn connect_ws(&self) -> Result<ws::Connection, ExchangeError> {
+        let credentials = self
+            .credentials
+            .as_ref()
+            .ok_or_else(|| ExchangeError::new("FTX adapter is not connected"))?;
+        let (mut stream, _) = connect_async(self.ws_url.to_string())
+            .await
+            .map_err(ExchangeError::with_source)?;
+
+        let login = ws::login_msg(
+            &credentials.api_key,
+            &credentials.key,
+            credentials.subaccount.as_deref(),
+            Utc::now().timestamp_millis(),
+        );
+        for msg in std::iter::once(login).chain(ws::subscribe_msgs()) {
+            stream
+                .send(Message::text(msg))
+                .await
+                .map_err(ExchangeError::with_source)?;
+        }
+
+        Ok(ws::Connection::new(stream))
+    }
+
+    /// Reads the messages waiting on the websocket, reconnecting it when
+    /// it's down and due
+    async fn poll_ws(&mut self, events: &mut Vec<ExchangeEvent>) {
+        let mut conn = match self.ws.take() {
+            Some(conn) => conn,
+            None if self.ws_reconnect_at.map_or(false, |at| Instant::now() < at) => return,
+            None => match self.connect_ws().await {
+                Ok(conn) => {
+                    info!("Connected FTX websocket");
+                    conn
+                }
+                Err(e) => {
+                    self.schedule_ws_reconnect(e);
+                    return;
+                }
+            },
+        };
+
+        match self.read_ws(&mut conn, events).await {
+            Ok(()) => self.ws = Some(conn),
+            Err(e) => self.schedule_ws_reconnect(e),
+        }
+    }
+
+    fn schedule_ws_reconnect(&mut self, e: ExchangeError) {
+        // Default backoff retries forever
+        let wait = self.ws_backoff.next_delay().unwrap_or(STALE_TIMEOUT);
+        warn!("FTX websocket down: {e}, reconnecting in {wait:?}");
+        self.ws_reconnect_at = Some(Instant::now() + wait);
+    }
+
+    async fn read_ws(
+        &mut self,
+        conn: &mut ws::Connection,
+        events: &mut Vec<ExchangeEvent>,
+    ) -> Result<(), ExchangeError> {
+        let was_streaming = conn.is_streaming();
+
+        while let Some(msg) = conn.stream.next().now_or_never() {
+            let msg = msg
+                .ok_or_else(|| ExchangeError::new("connection closed"))?
+                .map_err(ExchangeError::with_source)?;
+            conn.last_msg = Instant::now();
+            let text = match msg {
+                Message::Text(text) => text,
+                _ => continue,
+            };
+
+            match serde_json::from_str::<ws::WsMsg>(&text) {
+                Ok(ws::WsMsg::Update(ws::Update::Fills(fill))) => {
+                    if fill.id > self.last_fill_id {
+                        events.extend(self.process_fill(fill, false));
+                    }
+                }
+                Ok(ws::WsMsg::Update(ws::Update::Orders(order))) => {
+                    events.extend(self.process_order(order));
+                }
+                Ok(ws::WsMsg::Subscribed { channel }) => {
+                    debug!("Subscribed FTX {channel} channel");
+                    conn.subscribed += 1;
+                    // Fills missed while the websocket was down come before
+                    // the streamed ones
+                    if channel == "fills" {
+                        self.poll_fills(events).await?;
+                    }
+                }
+                Ok(ws::WsMsg::Error { msg }) => return Err(ExchangeError::new(msg)),
+                Ok(ws::WsMsg::Info { msg }) => {
+                    return Err(ExchangeError::new(format!("FTX notice: {msg}")))
+                }
+                Ok(ws::WsMsg::Pong) => {}
+                Err(e) => warn!("Failed to parse FTX websocket message {text}: {e}"),
+            }
+        }
+
+        if !was_streaming && conn.is_streaming() {
+            info!("Streaming FTX fills and orders");
+            self.ws_backoff.reset();
+        }
+        if conn.last_msg.elapsed() >= STALE_TIMEOUT {
+            return Err(ExchangeError::new(format!(
+                "no message for {STALE_TIMEOUT:?}"
+            )));
+        }
+        if conn.last_ping.elapsed() >= PING_INTERVAL {
+            conn.last_ping = Instant::now();
+            conn.stream
+                .send(Message::text(ws::PING))
+                .await
+                .map_err(ExchangeError::with_source)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait(?Send)]
+impl ExecutionAdapter for Ftx {
+    fn name(&self) -> &'static str {
+        "ftx-execution"
+    }
+
+    async fn connect(&mut self, secrets: &dyn SecretsProvider) -> Result<(), ExchangeError> {
+        let api_key = secrets
+            .require("ftx.api_key")
+            .await
+            .map_err(ExchangeError::with_source)?;
+        let api_secret = secrets
+            .require("ftx.api_secret")
+            .await
+            .map_err(ExchangeError::with_source)?;
+        let subaccount = secrets
+            .get("ftx.subaccount")
+            .await
+            .map_err(ExchangeError::with_source)?;
+
+        self.credentials = Some(Credentials {
+            api_key: api_key.expose().to_string(),
+            key: hmac::Key::new(hmac::HMAC_SHA256, api_secret.expose().as_bytes()),
+            subaccount: subaccount.map(|subaccount| subaccount.expose().to_string()),
+        });
+
+        // Fills are polled over REST until the websocket is up
+        match self.connect_ws().await {
+            Ok(conn) => self.ws = Some(conn),
+            Err(e) => self.schedule_ws_reconnect(e),
+        }
+
+        Ok(())
+    }
+
+    fn poll_interval(&self) -> Duration {
+        match self.ws {
+            Some(_) => Duration::ZERO,
+            None => REST_POLL_INTERVAL,
+        }
+    }
+
+    async fn place_order(&mut self, order: &OrderRequest) -> Result<OrderUpdate, ExchangeError> {
+        let body = json!({
+            "market": &*order.market,
+            "side": order.side.as_str(),
+            "price": order.price,
+            "type": if order.price.is_some() { "limit" } else { "market" },
+            "size": order.size,
+            "postOnly": order.post_only,
+            "clientId": &*order.client_id,
+        });
+        let res: rest::Order = self
+            .request(Method::Post, "/api/orders", Some(body))
+            .await?;
+
+        Ok(self.track_order(&order.client_id, res))
+    }
+
+    async fn cancel_order(&mut self, cancel: &CancelRequest) -> Result<OrderUpdate, ExchangeError> {
+        let mut update = self.order(&cancel.client_id)?.clone();
+        let path = format!("/api/orders/by_client_id/{}", cancel.client_id);
+        let _: String = self.request(Method::Delete, &path, None).await?;
+
+        self.orders.remove(&cancel.client_id);
+        update.status = OrderStatus::Cancelled;
+
+        Ok(update)
+    }
+
+    async fn amend_order(&mut self, amend: &AmendRequest) -> Result<OrderUpdate, ExchangeError> {
+        self.order(&amend.client_id)?;
+        let path = format!("/api/orders/by_client_id/{}/modify", amend.client_id);
+        let body = json!({
+            "price": amend.price,
+            "size": amend.size,
+        });
+        let res: rest::Order = self.request(Method::Post, &path, Some(body)).await?;
+
+        Ok(self.track_order(&amend.client_id, res))
+    }
+
+    async fn cancel_all(&mut self, market: Option<&str>) -> Result<(), ExchangeError> {
+        let body = market.map(|market| json!({ "market": market }));
+        let _: String = self.request(Method::Delete, "/api/orders", body).await?;
+
+        self.orders
+            .retain(|_, order| market.map_or(false, |market| &*order.market != market));
+
+        Ok(())
+    }
+
+    async fn poll_events(&mut self) -> Result<Vec<ExchangeEvent>, ExchangeError> {
+        let mut events = Vec::new();
+        if self.credentials.is_some() {
+            self.poll_ws(&mut events).await;
+        }
+
+        let streaming = self.ws.as_ref().map_or(false, ws::Connection::is_streaming);
+        if !streaming && self.last_rest_poll.elapsed() >= REST_POLL_INTERVAL {
+            // Streamed events are returned regardless
+            if let Err(e) = self.poll_fills(&mut events).await {
+                warn!("Failed to poll FTX fills: {e}");
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+/// Returns hex HMAC-SHA256 signature of the request
+fn sign(key: &hmac::Key, ts: i64, method: &str, path: &str, body: &str) -> String {
+    hex_signature(key, &format!("{ts}{method}{path}{body}"))
+}
+
+/// Returns hex HMAC-SHA256 signature of the payload
+fn hex_signature(key: &hmac::Key, payload: &str) -> String {
+    hmac::sign(key, payload.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trading::strategy::Side;
+
+    #[test]
+    fn test_sign() {
+        let key = hmac::Key::new(
+            hmac::HMAC_SHA256,
+            b"T4lPid48QtjNxjLUFOcUZghD7CUJ7sTVsfuvQZF2",
+        );
+
+        assert_eq!(
+            "dbc62ec300b2624c580611858d94f2332ac636bb86eccfa1167a7777c496ee6f",
+            sign(&key, 1588591511721, "GET", "/api/markets", "")
+        );
+    }
+
+    #[test]
+    fn test_process_fill() {
+        let mut ftx = Ftx::default();
+        ftx.track_order(
+            "bot-1",
+            rest::Order {
+                id: 9596912,
+                client_id: Some("bot-1".to_string()),
+                market: "BTC-PERP".to_string(),
+                side: rest::Side::Buy,
+                price: Some(100.0),
+                size: 2.0,
+                filled_size: 0.0,
+                status: rest::OrderStatus::New,
+            },
+        );
+
+        let events = ftx.process_fill(
+            rest::Fill {
+                id: 1,
+                order_id: Some(9596912),
+                market: "BTC-PERP".to_string(),
+                side: rest::Side::Buy,
+                price: 100.0,
+                size: 2.0,
+                fee: 0.01,
+                time: Utc::now(),
+            },
+            true,
+        );
+
+        match &events[..] {
+            [ExchangeEvent::Fill(fill), ExchangeEvent::Order(order)] => {
+                assert_eq!(Some("bot-1"), fill.client_id.as_deref());
+                assert_eq!(Side::Buy, fill.side);
+                assert_eq!(OrderStatus::Filled, order.status);
+            }
+            other => panic!("unexpected events {other:?}"),
+        }
+        assert!(ftx.orders.is_empty());
+        assert!(ftx.client_ids.is_empty());
+    }
+
+    #[test]
+    fn test_process_streamed_order() {
+        let order = |filled_size, status| rest::Order {
+            id: 9596912,
+            client_id: Some("bot-1".to_string()),
+            market: "BTC-PERP".to_string(),
+            side: rest::Side::Buy,
+            price: Some(100.0),
+            size: 2.0,
+            filled_size,
+            status,
+        };
+        let mut ftx = Ftx::default();
+        ftx.track_order("bot-1", order(0.0, rest::OrderStatus::New));
+
+        // Already returned when placed
+        assert!(ftx
+            .process_order(order(0.0, rest::OrderStatus::Open))
+            .is_none());
+        match ftx.process_order(order(2.0, rest::OrderStatus::Closed)) {
+            Some(ExchangeEvent::Order(update)) => assert_eq!(OrderStatus::Filled, update.status),
+            other => panic!("unexpected event {other:?}"),
+        }
+        assert!(ftx.orders.is_empty());
+        assert!(ftx
+            .process_order(order(2.0, rest::OrderStatus::Closed))
+            .is_none());
+
+        // Streamed fills don't change the streamed orders
+        let events = ftx.process_fill(
+            rest::Fill {
+                id: 1,
+                order_id: Some(9596912),
+                market: "BTC-PERP".to_string(),
+                side: rest::Side::Buy,
+                price: 100.0,
+                size: 2.0,
+                fee: 0.01,
+                time: Utc::now(),
+            },
+            false,
+        );
+        match &events[..] {
+            [ExchangeEvent::Fill(fill)] => assert_eq!(Some("bot-1"), fill.client_id.as_deref()),
+            other => panic!("unexpected events {other:?}"),
+        }
+        assert_eq!(1, ftx.last_fill_id);
+    }
+}
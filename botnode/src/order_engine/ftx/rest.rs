@@ -0,0 +1,145 @@
+use serde::Deserialize;
+
+use crate::order_engine::{error::ExchangeError, OrderStatus as BotOrderStatus, OrderUpdate};
+use crate::prelude::*;
+use crate::trading::strategy;
+
+/// Envelope of every FTX REST response
+#[derive(Deserialize, Debug)]
+pub(crate) struct Response<T> {
+    pub success: bool,
+    pub result: Option<T>,
+    pub error: Option<String>,
+}
+
+impl<T> Response<T> {
+    /// Returns the result or the error reported by FTX
+    pub fn into_result(self) -> Result<T, ExchangeError> {
+        match (self.success, self.result) {
+            (true, Some(result)) => Ok(result),
+            _ => Err(ExchangeError::new(
+                self.error.unwrap_or_else(|| "request failed".to_string()),
+            )),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Side {
+    Buy,
+    Sell,
+}
+
+impl From<Side> for strategy::Side {
+    fn from(side: Side) -> Self {
+        match side {
+            Side::Buy => strategy::Side::Buy,
+            Side::Sell => strategy::Side::Sell,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum OrderStatus {
+    New,
+    Open,
+    Closed,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Order {
+    pub id: u64,
+    /// Set on orders placed with a client id
+    pub client_id: Option<String>,
+    pub market: String,
+    pub side: Side,
+    pub price: Option<f64>,
+    pub size: f64,
+    pub filled_size: f64,
+    pub status: OrderStatus,
+}
+
+impl Order {
+    pub fn to_update(&self, client_id: &str) -> OrderUpdate {
+        let status = match self.status {
+            OrderStatus::Closed if self.filled_size >= self.size => BotOrderStatus::Filled,
+            OrderStatus::Closed => BotOrderStatus::Cancelled,
+            _ if self.filled_size > 0.0 => BotOrderStatus::PartiallyFilled,
+            _ => BotOrderStatus::Open,
+        };
+
+        OrderUpdate {
+            client_id: Box::from(client_id),
+            exchange_id: Some(self.id.to_string().into_boxed_str()),
+            market: Box::from(self.market.as_str()),
+            side: self.side.into(),
+            price: self.price,
+            size: self.size,
+            filled_size: self.filled_size,
+            status,
+            reason: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Fill {
+    pub id: u64,
+    pub order_id: Option<u64>,
+    pub market: String,
+    pub side: Side,
+    pub price: f64,
+    pub size: f64,
+    pub fee: f64,
+    pub time: DateTime<Utc>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_order_response() {
+        let json = r#"{"success":true,"result":{"createdAt":"2019-03-05T09:56:55.728933+00:00","filledSize":0.5,"future":null,"id":9596912,"market":"XRP-PERP","price":0.306525,"remainingSize":0.5,"side":"sell","size":1.0,"status":"open","type":"limit","reduceOnly":false,"ioc":false,"postOnly":false,"clientId":"bot-1"}}"#;
+
+        let order = serde_json::from_str::<Response<Order>>(json)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        let update = order.to_update("bot-1");
+
+        assert_eq!(Some("9596912"), update.exchange_id.as_deref());
+        assert_eq!(strategy::Side::Sell, update.side);
+        assert_eq!(BotOrderStatus::PartiallyFilled, update.status);
+    }
+
+    #[test]
+    fn test_error_response() {
+        let json = r#"{"success":false,"error":"Not logged in"}"#;
+
+        let err = serde_json::from_str::<Response<Order>>(json)
+            .unwrap()
+            .into_result()
+            .unwrap_err();
+
+        assert_eq!("Exchange error: Not logged in", err.to_string());
+    }
+
+    #[test]
+    fn test_fills_response() {
+        let json = r#"{"success":true,"result":[{"fee":20.1374935,"feeCurrency":"USD","feeRate":0.0005,"future":"EOS-0329","id":11215,"liquidity":"taker","market":"EOS-0329","baseCurrency":null,"quoteCurrency":null,"orderId":8436981,"tradeId":1013912,"price":4.201,"side":"buy","size":9587,"time":"2019-03-27T19:15:10.204619+00:00","type":"order"}]}"#;
+
+        let fills = serde_json::from_str::<Response<Vec<Fill>>>(json)
+            .unwrap()
+            .into_result()
+            .unwrap();
+
+        assert_eq!(1, fills.len());
+        assert_eq!(Some(8436981), fills[0].order_id);
+        assert_eq!(9587.0, fills[0].size);
+    }
+}
@@ -0,0 +1,153 @@
+//! FTX authenticated websocket
+//!
+//! The connection logs in with the signature of `{time}websocket_login` and
+//! subscribes to the `fills` and `orders` channels. Their updates carry the
+//! same fills and orders as the REST API.
+
+use std::time::Instant;
+
+use async_tungstenite::{async_std::ConnectStream, WebSocketStream};
+use ring::hmac;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::rest;
+
+/// Channels the connection subscribes to
+pub(crate) const CHANNELS: [&str; 2] = ["fills", "orders"];
+
+/// Keepalive FTX expects at least every 15 seconds
+pub(crate) const PING: &str = r#"{"op":"ping"}"#;
+
+/// Message received on the authenticated connection
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub(crate) enum WsMsg {
+    Update(Update),
+    Subscribed {
+        channel: String,
+    },
+    /// Failed login or subscription
+    Error {
+        msg: String,
+    },
+    /// Notice of FTX, e.g. that the server is restarting
+    Info {
+        msg: String,
+    },
+    Pong,
+}
+
+/// Update of a subscribed channel
+#[derive(Deserialize, Debug)]
+#[serde(tag = "channel", content = "data", rename_all = "lowercase")]
+pub(crate) enum Update {
+    Fills(rest::Fill),
+    Orders(rest::Order),
+}
+
+/// Open authenticated connection
+pub(crate) struct Connection {
+    pub stream: WebSocketStream<ConnectStream>,
+    /// Number of channels FTX confirmed the subscription of
+    pub subscribed: usize,
+    pub last_ping: Instant,
+    pub last_msg: Instant,
+}
+
+impl Connection {
+    pub fn new(stream: WebSocketStream<ConnectStream>) -> Self {
+        Self {
+            stream,
+            subscribed: 0,
+            last_ping: Instant::now(),
+            last_msg: Instant::now(),
+        }
+    }
+
+    /// Returns whether all channels are subscribed
+    pub fn is_streaming(&self) -> bool {
+        self.subscribed >= CHANNELS.len()
+    }
+}
+
+/// Returns the login message signed at `ts` milliseconds
+pub(crate) fn login_msg(
+    api_key: &str,
+    key: &hmac::Key,
+    subaccount: Option<&str>,
+    ts: i64,
+) -> String {
+    let mut args = json!({
+        "key": api_key,
+        "sign": super::hex_signature(key, &format!("{ts}websocket_login")),
+        "time": ts,
+    });
+    if let Some(subaccount) = subaccount {
+        args["subaccount"] = json!(subaccount);
+    }
+
+    json!({ "op": "login", "args": args }).to_string()
+}
+
+/// Returns the messages subscribing the channels
+pub(crate) fn subscribe_msgs() -> impl Iterator<Item = String> {
+    CHANNELS
+        .iter()
+        .map(|channel| json!({ "op": "subscribe", "channel": channel }).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_login_msg() {
+        let key = hmac::Key::new(
+            hmac::HMAC_SHA256,
+            b"T4lPid48QtjNxjLUFOcUZghD7CUJ7sTVsfuvQZF2",
+        );
+        let msg: serde_json::Value =
+            serde_json::from_str(&login_msg("key", &key, Some("bot"), 1588591511721)).unwrap();
+
+        assert_eq!("login", msg["op"]);
+        assert_eq!(
+            "699b8345968d8bd89958d6fb06e0ac79ea1c80c51388653a74b3b7abb5311ac8",
+            msg["args"]["sign"]
+        );
+        assert_eq!("bot", msg["args"]["subaccount"]);
+    }
+
+    #[test]
+    fn test_parse_updates() {
+        let fill = r#"{"channel":"fills","type":"update","data":{"fee":78.05799225,"feeRate":0.0014,"future":"BTC-PERP","id":7828307,"liquidity":"taker","market":"BTC-PERP","orderId":38065410,"tradeId":19129310,"price":3723.75,"side":"buy","size":14.973,"time":"2019-05-07T16:40:58.358438+00:00","type":"order"}}"#;
+        match serde_json::from_str::<WsMsg>(fill).unwrap() {
+            WsMsg::Update(Update::Fills(fill)) => {
+                assert_eq!(Some(38065410), fill.order_id);
+                assert_eq!(14.973, fill.size);
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+
+        let order = r#"{"channel":"orders","type":"update","data":{"id":24852229,"clientId":"bot-1","market":"XRP-PERP","type":"limit","side":"buy","size":42353.0,"price":0.2977,"reduceOnly":false,"ioc":false,"postOnly":false,"status":"closed","filledSize":42353.0,"remainingSize":0.0,"avgFillPrice":0.2978}}"#;
+        match serde_json::from_str::<WsMsg>(order).unwrap() {
+            WsMsg::Update(Update::Orders(order)) => {
+                assert_eq!(Some("bot-1"), order.client_id.as_deref());
+                assert_eq!(rest::OrderStatus::Closed, order.status);
+            }
+            other => panic!("unexpected message {other:?}"),
+        }
+
+        assert!(matches!(
+            serde_json::from_str::<WsMsg>(r#"{"type":"subscribed","channel":"fills"}"#).unwrap(),
+            WsMsg::Subscribed { .. }
+        ));
+        assert!(matches!(
+            serde_json::from_str::<WsMsg>(
+                r#"{"type":"error","code":400,"msg":"Invalid login credentials"}"#
+            )
+            .unwrap(),
+            WsMsg::Error { .. }
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+//! Matching of orders against an orderbook
+//!
+//! Shared by the adapters simulating execution and the backtest exchange.
+
+use super::{ExchangeEvent, Fill, OrderStatus, OrderUpdate};
+use crate::{prelude::*, trading::strategy::Side};
+
+/// Returns whether an order at the price would take liquidity, market
+/// orders always do
+pub fn crosses(book: &PlainOrderbook<f64>, side: Side, price: Option<f64>) -> bool {
+    let price = match price {
+        Some(price) => price,
+        None => return true,
+    };
+
+    match side {
+        Side::Buy => matches!(book.asks.price_vec.first(), Some(ask) if *ask <= price),
+        Side::Sell => matches!(book.bids.price_vec.last(), Some(bid) if *bid >= price),
+    }
+}
+
+/// Takes up to `size` from the levels of the book at or better than the
+/// limit price, best first, returning the taken prices and sizes
+///
+/// Emptied levels are removed from the book.
+pub fn take(
+    book: &mut PlainOrderbook<f64>,
+    side: Side,
+    limit: Option<f64>,
+    mut size: f64,
+) -> Vec<(f64, f64)> {
+    let mut taken = Vec::new();
+
+    while size > 0.0 {
+        // Asks are sorted from the best, bids towards it
+        let (levels, idx) = match side {
+            Side::Buy => (&mut book.asks, 0),
+            Side::Sell => match book.bids.len().checked_sub(1) {
+                Some(idx) => (&mut book.bids, idx),
+                None => break,
+            },
+        };
+        let price = match levels.price_vec.get(idx) {
+            Some(price) => *price,
+            None => break,
+        };
+        let within_limit = match (side, limit) {
+            (_, None) => true,
+            (Side::Buy, Some(limit)) => price <= limit,
+            (Side::Sell, Some(limit)) => price >= limit,
+        };
+        if !within_limit {
+            break;
+        }
+
+        let level_size = &mut levels.size_vec[idx];
+        let take = level_size.min(size);
+        *level_size -= take;
+        size -= take;
+        if *level_size <= 0.0 {
+            levels.price_vec.remove(idx);
+            levels.size_vec.remove(idx);
+        }
+        if take > 0.0 {
+            taken.push((price, take));
+        }
+    }
+
+    taken
+}
+
+/// Returns size resting in the book at the price on the side of an order
+pub fn level_size(book: &PlainOrderbook<f64>, side: Side, price: f64) -> f64 {
+    let levels = match side {
+        Side::Buy => &book.bids,
+        Side::Sell => &book.asks,
+    };
+
+    levels
+        .price_vec
+        .iter()
+        .position(|level| *level == price)
+        .map_or(0.0, |idx| levels.size_vec[idx])
+}
+
+/// Fills the order, returning the fill event
+///
+/// `fee_rate` is the fraction of the filled notional paid as fee and `time`
+/// is in milliseconds since UNIX epoch.
+pub fn fill(
+    order: &mut OrderUpdate,
+    price: f64,
+    size: f64,
+    fee_rate: f64,
+    time: u64,
+) -> ExchangeEvent {
+    order.filled_size += size;
+    order.status = match order.remaining_size() > f64::EPSILON {
+        true => OrderStatus::PartiallyFilled,
+        false => OrderStatus::Filled,
+    };
+
+    ExchangeEvent::Fill(Fill {
+        client_id: Some(order.client_id.clone()),
+        exchange_id: order.exchange_id.clone().unwrap_or_default(),
+        market: order.market.clone(),
+        side: order.side,
+        price,
+        size,
+        fee: price * size * fee_rate,
+        time,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_walks_book() {
+        let mut book = PlainOrderbook::new();
+        book.bids = PriceLevelsVec::from_tuples_vec(&[(98.0, 1.0), (99.0, 1.0)]);
+        book.asks = PriceLevelsVec::from_tuples_vec(&[(101.0, 1.0), (102.0, 2.0)]);
+
+        assert!(crosses(&book, Side::Sell, None));
+        assert!(crosses(&book, Side::Sell, Some(99.0)));
+        assert!(!crosses(&book, Side::Buy, Some(100.0)));
+
+        assert_eq!(
+            vec![(99.0, 1.0), (98.0, 0.5)],
+            take(&mut book, Side::Sell, Some(97.0), 1.5)
+        );
+        assert_eq!(
+            vec![(101.0, 1.0)],
+            take(&mut book, Side::Buy, Some(101.5), 5.0)
+        );
+        assert_eq!(0.5, level_size(&book, Side::Buy, 98.0));
+        assert_eq!(0.0, level_size(&book, Side::Sell, 101.0));
+    }
+}
@@ -0,0 +1,50 @@
+use super::*;
+use crate::prelude::*;
+
+/// Adapter acknowledging orders without sending them anywhere, orders never
+/// fill
+#[derive(Debug, Default)]
+pub(crate) struct NullAdapter;
+
+#[async_trait(?Send)]
+impl ExecutionAdapter for NullAdapter {
+    fn name(&self) -> &'static str {
+        "null-adapter"
+    }
+
+    async fn place_order(&mut self, order: &OrderRequest) -> Result<OrderUpdate, ExchangeError> {
+        Ok(OrderUpdate {
+            client_id: order.client_id.clone(),
+            exchange_id: None,
+            market: order.market.clone(),
+            side: order.side,
+            price: order.price,
+            size: order.size,
+            filled_size: 0.0,
+            status: OrderStatus::Open,
+            reason: None,
+        })
+    }
+
+    async fn cancel_order(&mut self, cancel: &CancelRequest) -> Result<OrderUpdate, ExchangeError> {
+        Err(ExchangeError::new(format!(
+            "unknown order {}",
+            cancel.client_id
+        )))
+    }
+
+    async fn amend_order(&mut self, amend: &AmendRequest) -> Result<OrderUpdate, ExchangeError> {
+        Err(ExchangeError::new(format!(
+            "unknown order {}",
+            amend.client_id
+        )))
+    }
+
+    async fn cancel_all(&mut self, _market: Option<&str>) -> Result<(), ExchangeError> {
+        Ok(())
+    }
+
+    async fn poll_events(&mut self) -> Result<Vec<ExchangeEvent>, ExchangeError> {
+        Ok(Vec::new())
+    }
+}
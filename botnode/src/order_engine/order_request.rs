@@ -0,0 +1,66 @@
+use crate::{latency::TickStamp, trading::strategy::Side};
+
+/// Request to place an order
+#[derive(Clone, Debug)]
+pub struct OrderRequest {
+    /// Order id assigned by the bot
+    pub client_id: Box<str>,
+    pub market: Box<str>,
+    pub side: Side,
+    /// Limit price, `None` for a market order
+    pub price: Option<f64>,
+    pub size: f64,
+    /// Order is cancelled instead of taking liquidity
+    pub post_only: bool,
+    /// Market event that triggered the order
+    pub tick: Option<TickStamp>,
+}
+
+impl OrderRequest {
+    /// Creates limit order request
+    pub fn limit(client_id: &str, market: &str, side: Side, price: f64, size: f64) -> Self {
+        Self {
+            client_id: Box::from(client_id),
+            market: Box::from(market),
+            side,
+            price: Some(price),
+            size,
+            post_only: false,
+            tick: None,
+        }
+    }
+
+    /// Creates market order request
+    pub fn market(client_id: &str, market: &str, side: Side, size: f64) -> Self {
+        Self {
+            price: None,
+            ..Self::limit(client_id, market, side, 0.0, size)
+        }
+    }
+
+    pub fn post_only(mut self) -> Self {
+        self.post_only = true;
+        self
+    }
+
+    pub fn with_tick(mut self, tick: TickStamp) -> Self {
+        self.tick = Some(tick);
+        self
+    }
+}
+
+/// Request to cancel an order
+#[derive(Clone, Debug, PartialEq)]
+pub struct CancelRequest {
+    pub client_id: Box<str>,
+    pub market: Box<str>,
+}
+
+/// Request to change price or size of an order
+#[derive(Clone, Debug, PartialEq)]
+pub struct AmendRequest {
+    pub client_id: Box<str>,
+    pub market: Box<str>,
+    pub price: Option<f64>,
+    pub size: Option<f64>,
+}
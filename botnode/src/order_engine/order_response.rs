@@ -0,0 +1,74 @@
+use serde::Serialize;
+
+use crate::trading::strategy::Side;
+
+/// State of an order on the exchange
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    /// Accepted by the exchange and resting in the book
+    Open,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected,
+}
+
+impl OrderStatus {
+    /// Returns whether the order can't change anymore
+    pub fn is_closed(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Filled | OrderStatus::Cancelled | OrderStatus::Rejected
+        )
+    }
+
+    /// Returns lower-case name of the status
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrderStatus::Open => "open",
+            OrderStatus::PartiallyFilled => "partially_filled",
+            OrderStatus::Filled => "filled",
+            OrderStatus::Cancelled => "cancelled",
+            OrderStatus::Rejected => "rejected",
+        }
+    }
+}
+
+/// Order state change reported by the execution adapter
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderUpdate {
+    pub client_id: Box<str>,
+    /// Order id assigned by the exchange, if it has been accepted
+    pub exchange_id: Option<Box<str>>,
+    pub market: Box<str>,
+    pub side: Side,
+    /// Limit price, `None` for a market order
+    pub price: Option<f64>,
+    pub size: f64,
+    pub filled_size: f64,
+    pub status: OrderStatus,
+    /// Reason given by the exchange for rejected orders
+    pub reason: Option<String>,
+}
+
+impl OrderUpdate {
+    /// Returns size left to be filled
+    pub fn remaining_size(&self) -> f64 {
+        (self.size - self.filled_size).max(0.0)
+    }
+}
+
+/// Execution of an order
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Fill {
+    pub client_id: Option<Box<str>>,
+    pub exchange_id: Box<str>,
+    pub market: Box<str>,
+    pub side: Side,
+    pub price: f64,
+    pub size: f64,
+    pub fee: f64,
+    /// Time of the fill in milliseconds since UNIX epoch
+    pub time: u64,
+}
@@ -0,0 +1,510 @@
+use std::time::SystemTime;
+
+use botvana::market::{
+    orderbook::l3::{BookSide, L3Orderbook},
+    trade::Trade,
+};
+
+use super::matching::{crosses, fill, level_size, take};
+use super::*;
+use crate::{prelude::*, trading::strategy::Side};
+
+/// Order resting in the simulated book
+#[derive(Debug)]
+struct RestingOrder {
+    update: OrderUpdate,
+    /// Size resting at the order's price that is filled before it
+    queue_ahead: f64,
+    /// Arrival number the order queues behind in the L3 book of its market
+    queue_seq: Option<u64>,
+}
+
+/// Simulated execution adapter filling orders against the live orderbooks,
+/// orders never leave the bot
+///
+/// Market orders and limit orders crossing the spread take liquidity from
+/// the book of the exchange orders are executed on, level by level. Limit
+/// orders join the back of the queue at their price: trades at the price
+/// fill the size ahead of the order first and a level shrinking below that
+/// size moves the order up. Once the book or a trade moves through the
+/// price the order is filled in full. Liquidity taken by orders is removed
+/// from the book until the exchange updates the level. Fills pay no fees.
+///
+/// Markets streamed with L3 books tell the orders queued ahead apart: a
+/// limit order only moves up when orders resting at its price before it was
+/// placed are filled, decreased or cancelled, not when orders behind it
+/// leave.
+#[derive(Debug, Default)]
+pub(crate) struct PaperTradingAdapter {
+    books: HashMap<Box<str>, PlainOrderbook<f64>>,
+    l3_books: HashMap<Box<str>, L3Orderbook>,
+    /// Resting orders in the order they were placed
+    orders: Vec<RestingOrder>,
+    next_id: u64,
+    events: Vec<ExchangeEvent>,
+}
+
+impl PaperTradingAdapter {
+    /// Fills resting orders of the market crossed by its book and moves the
+    /// others up their queues
+    fn match_book(&mut self, market: &str) {
+        let book = match self.books.get_mut(market) {
+            Some(book) => book,
+            None => return,
+        };
+        let time = now_millis();
+
+        for order in self
+            .orders
+            .iter_mut()
+            .filter(|order| &*order.update.market == market)
+        {
+            let (side, price) = match (order.update.side, order.update.price) {
+                (side, Some(price)) => (side, price),
+                (_, None) => continue,
+            };
+
+            if crosses(book, side, Some(price)) {
+                let size: f64 = take(book, side, Some(price), order.update.remaining_size())
+                    .iter()
+                    .map(|(_, size)| size)
+                    .sum();
+                self.events
+                    .push(fill(&mut order.update, price, size, 0.0, time));
+                self.events.push(ExchangeEvent::Order(order.update.clone()));
+            } else {
+                order.queue_ahead = order.queue_ahead.min(level_size(book, side, price));
+            }
+        }
+
+        self.orders.retain(|order| !order.update.status.is_closed());
+    }
+
+    /// Moves resting orders of the market up their queues as the orders
+    /// ahead of them leave its L3 book
+    fn match_queues(&mut self, market: &str) {
+        let book = match self.l3_books.get(market) {
+            Some(book) => book,
+            None => return,
+        };
+
+        for order in self
+            .orders
+            .iter_mut()
+            .filter(|order| &*order.update.market == market)
+        {
+            if let (Some(seq), Some(price)) = (order.queue_seq, order.update.price) {
+                let ahead = book.queue_ahead(book_side(order.update.side), price, seq);
+                order.queue_ahead = order.queue_ahead.min(ahead);
+            }
+        }
+    }
+
+    /// Fills resting orders of the market the trade reached
+    fn match_trade(&mut self, market: &str, trade: &Trade) {
+        let time = now_millis();
+        let mut available = trade.size;
+
+        for order in self
+            .orders
+            .iter_mut()
+            .filter(|order| &*order.update.market == market)
+        {
+            let (side, price) = match (order.update.side, order.update.price) {
+                (side, Some(price)) => (side, price),
+                (_, None) => continue,
+            };
+            let through = match side {
+                Side::Buy => trade.price < price,
+                Side::Sell => trade.price > price,
+            };
+
+            // The whole queue at the price was taken by trades through it
+            let size = if through {
+                order.update.remaining_size()
+            } else if trade.price == price {
+                let ahead = order.queue_ahead.min(available);
+                order.queue_ahead -= ahead;
+                available -= ahead;
+                let size = order.update.remaining_size().min(available);
+                available -= size;
+                size
+            } else {
+                continue;
+            };
+
+            if size > 0.0 {
+                self.events
+                    .push(fill(&mut order.update, price, size, 0.0, time));
+                self.events.push(ExchangeEvent::Order(order.update.clone()));
+            }
+        }
+
+        self.orders.retain(|order| !order.update.status.is_closed());
+    }
+}
+
+#[async_trait(?Send)]
+impl ExecutionAdapter for PaperTradingAdapter {
+    fn name(&self) -> &'static str {
+        "paper-trading-adapter"
+    }
+
+    /// Matches the order against the book right away, fills are returned
+    /// by the next poll
+    async fn place_order(&mut self, order: &OrderRequest) -> Result<OrderUpdate, ExchangeError> {
+        if self
+            .orders
+            .iter()
+            .any(|resting| resting.update.client_id == order.client_id)
+        {
+            return Err(ExchangeError::new(format!(
+                "duplicate order {}",
+                order.client_id
+            )));
+        }
+        let book = match (self.books.get_mut(&order.market), order.price) {
+            (Some(book), _) => Some(book),
+            (None, Some(_)) => None,
+            (None, None) => {
+                return Err(ExchangeError::new(format!(
+                    "no orderbook of {} to fill market order against",
+                    order.market
+                )))
+            }
+        };
+        if order.post_only && matches!(&book, Some(book) if crosses(book, order.side, order.price))
+        {
+            return Err(ExchangeError::new("post only order would take liquidity"));
+        }
+        self.next_id += 1;
+
+        let mut update = OrderUpdate {
+            client_id: order.client_id.clone(),
+            exchange_id: Some(format!("paper-trading-{}", self.next_id).into_boxed_str()),
+            market: order.market.clone(),
+            side: order.side,
+            price: order.price,
+            size: order.size,
+            filled_size: 0.0,
+            status: OrderStatus::Open,
+            reason: None,
+        };
+        let mut queue_ahead = 0.0;
+        let queue_seq = self.l3_books.get(&order.market).map(L3Orderbook::next_seq);
+        if let Some(book) = book {
+            let time = now_millis();
+            for (price, size) in take(book, order.side, order.price, order.size) {
+                self.events.push(fill(&mut update, price, size, 0.0, time));
+            }
+            if let Some(price) = order.price {
+                queue_ahead = level_size(book, order.side, price);
+            }
+        }
+
+        if order.price.is_none() && update.status != OrderStatus::Filled {
+            update.status = OrderStatus::Cancelled;
+            update.reason = Some("market order not fully filled".to_string());
+        }
+        if !update.status.is_closed() {
+            self.orders.push(RestingOrder {
+                update: update.clone(),
+                queue_ahead,
+                queue_seq,
+            });
+        }
+
+        Ok(update)
+    }
+
+    async fn cancel_order(&mut self, cancel: &CancelRequest) -> Result<OrderUpdate, ExchangeError> {
+        let idx = self
+            .orders
+            .iter()
+            .position(|order| order.update.client_id == cancel.client_id)
+            .ok_or_else(|| ExchangeError::new(format!("unknown order {}", cancel.client_id)))?;
+        let mut order = self.orders.remove(idx).update;
+        order.status = OrderStatus::Cancelled;
+
+        Ok(order)
+    }
+
+    /// Changing the price or increasing the size sends the order to the back
+    /// of the queue
+    async fn amend_order(&mut self, amend: &AmendRequest) -> Result<OrderUpdate, ExchangeError> {
+        let order = self
+            .orders
+            .iter_mut()
+            .find(|order| order.update.client_id == amend.client_id)
+            .ok_or_else(|| ExchangeError::new(format!("unknown order {}", amend.client_id)))?;
+        let mut requeue = false;
+        if let Some(price) = amend.price {
+            requeue |= order.update.price != Some(price);
+            order.update.price = Some(price);
+        }
+        if let Some(size) = amend.size {
+            requeue |= size > order.update.size;
+            order.update.size = size.max(order.update.filled_size);
+        }
+        if order.update.remaining_size() == 0.0 {
+            order.update.status = OrderStatus::Filled;
+        }
+        if requeue {
+            order.queue_ahead = match (self.books.get(&order.update.market), order.update.price) {
+                (Some(book), Some(price)) => level_size(book, order.update.side, price),
+                _ => 0.0,
+            };
+            order.queue_seq = self
+                .l3_books
+                .get(&order.update.market)
+                .map(L3Orderbook::next_seq);
+        }
+        let update = order.update.clone();
+
+        self.orders.retain(|order| !order.update.status.is_closed());
+        // Order moved to a price the book crosses is filled by the next poll
+        self.match_book(&update.market);
+
+        Ok(update)
+    }
+
+    async fn cancel_all(&mut self, market: Option<&str>) -> Result<(), ExchangeError> {
+        let (cancelled, orders) = std::mem::take(&mut self.orders)
+            .into_iter()
+            .partition(|order| market.map_or(true, |market| &*order.update.market == market));
+        self.orders = orders;
+
+        for RestingOrder { mut update, .. } in cancelled {
+            update.status = OrderStatus::Cancelled;
+            self.events.push(ExchangeEvent::Order(update));
+        }
+
+        Ok(())
+    }
+
+    async fn poll_events(&mut self) -> Result<Vec<ExchangeEvent>, ExchangeError> {
+        Ok(std::mem::take(&mut self.events))
+    }
+
+    fn on_market_event(&mut self, event: &MarketEvent) {
+        match &event.r#type {
+            MarketEventType::OrderbookUpdate(market, orderbook) => {
+                self.books.insert(market.clone(), (**orderbook).clone());
+                self.match_book(market);
+            }
+            MarketEventType::OrderbookDelta(market, delta) => {
+                if let Some(book) = self.books.get_mut(market) {
+                    book.apply_delta(delta);
+                    self.match_book(market);
+                }
+            }
+            MarketEventType::BboUpdate(market, bbo) => {
+                let mut book = PlainOrderbook::new();
+                book.bids = PriceLevelsVec::from_tuples_vec(&[(bbo.bid, bbo.bid_size)]);
+                book.asks = PriceLevelsVec::from_tuples_vec(&[(bbo.ask, bbo.ask_size)]);
+                self.books.insert(market.clone(), book);
+                self.match_book(market);
+            }
+            MarketEventType::Trades(market, trades) => {
+                for trade in trades.iter() {
+                    self.match_trade(market, trade);
+                }
+            }
+            MarketEventType::L3Snapshot(market, orders) => {
+                let book = L3Orderbook::from_orders(orders.iter());
+                // Positions in the previous book are lost, resting orders
+                // queue behind the whole level again
+                for order in self
+                    .orders
+                    .iter_mut()
+                    .filter(|order| &order.update.market == market)
+                {
+                    order.queue_seq = Some(book.next_seq());
+                }
+                self.l3_books.insert(market.clone(), book);
+                self.match_queues(market);
+            }
+            MarketEventType::L3Update(market, updates) => {
+                if let Some(book) = self.l3_books.get_mut(market) {
+                    for update in updates.iter() {
+                        book.apply(update);
+                    }
+                    self.match_queues(market);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn book_side(side: Side) -> BookSide {
+    match side {
+        Side::Buy => BookSide::Bid,
+        Side::Sell => BookSide::Ask,
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn adapter() -> PaperTradingAdapter {
+        let mut book = PlainOrderbook::new();
+        book.bids = PriceLevelsVec::from_tuples_vec(&[(98.0, 1.0), (99.0, 2.0)]);
+        book.asks = PriceLevelsVec::from_tuples_vec(&[(101.0, 1.0), (102.0, 2.0)]);
+
+        let mut adapter = PaperTradingAdapter::default();
+        adapter.on_market_event(&MarketEvent::orderbook_update(
+            Box::from("BTC-PERP"),
+            Box::new(book),
+        ));
+        adapter
+    }
+
+    fn trades(trades: &[(f64, f64)]) -> MarketEvent {
+        let trades = trades
+            .iter()
+            .map(|(price, size)| Trade::new(*price, *size, Utc::now()))
+            .collect();
+
+        MarketEvent::trades(Box::from("BTC-PERP"), trades)
+    }
+
+    fn fills(events: &[ExchangeEvent]) -> Vec<(f64, f64)> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                ExchangeEvent::Fill(fill) => Some((fill.price, fill.size)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_market_order_crosses_spread() {
+        let mut adapter = adapter();
+
+        crate::rt::block_on(async {
+            let order = OrderRequest::market("1", "BTC-PERP", Side::Buy, 1.5);
+            let update = adapter.place_order(&order).await.unwrap();
+            assert_eq!(OrderStatus::Filled, update.status);
+            assert_eq!(
+                vec![(101.0, 1.0), (102.0, 0.5)],
+                fills(&adapter.poll_events().await.unwrap())
+            );
+
+            let order = OrderRequest::market("2", "ETH-PERP", Side::Buy, 1.0);
+            assert!(adapter.place_order(&order).await.is_err());
+        });
+    }
+
+    #[test]
+    fn test_limit_order_queue_position() {
+        let mut adapter = adapter();
+
+        crate::rt::block_on(async {
+            let order = OrderRequest::limit("1", "BTC-PERP", Side::Buy, 99.0, 1.0);
+            let update = adapter.place_order(&order).await.unwrap();
+            assert_eq!(OrderStatus::Open, update.status);
+
+            // Trades fill the 2.0 ahead in the queue first
+            adapter.on_market_event(&trades(&[(99.0, 1.5)]));
+            assert!(adapter.poll_events().await.unwrap().is_empty());
+
+            // Cancellations ahead move the order up
+            let mut book = PlainOrderbook::new();
+            book.bids = PriceLevelsVec::from_tuples_vec(&[(98.0, 1.0), (99.0, 0.2)]);
+            book.asks = PriceLevelsVec::from_tuples_vec(&[(101.0, 1.0)]);
+            adapter.on_market_event(&MarketEvent::orderbook_update(
+                Box::from("BTC-PERP"),
+                Box::new(book),
+            ));
+            adapter.on_market_event(&trades(&[(99.0, 1.0)]));
+            assert_eq!(
+                vec![(99.0, 0.8)],
+                fills(&adapter.poll_events().await.unwrap())
+            );
+
+            // Trade through the price fills the rest
+            adapter.on_market_event(&trades(&[(98.5, 0.01)]));
+            match &adapter.poll_events().await.unwrap()[..] {
+                [ExchangeEvent::Fill(fill), ExchangeEvent::Order(order)] => {
+                    assert!((fill.size - 0.2).abs() < 1e-9);
+                    assert_eq!(OrderStatus::Filled, order.status);
+                }
+                other => panic!("unexpected events {other:?}"),
+            }
+            assert!(adapter.orders.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_l3_queue_position() {
+        use botvana::market::orderbook::l3::{L3Order, L3Update};
+
+        let mut adapter = adapter();
+        let bid = |id: &str, size: f64| L3Order {
+            id: Box::from(id),
+            side: BookSide::Bid,
+            price: 99.0,
+            size,
+        };
+        let l3_update = |updates: Vec<L3Update>| {
+            MarketEvent::l3_update(Box::from("BTC-PERP"), updates.into_boxed_slice())
+        };
+        adapter.on_market_event(&MarketEvent::l3_snapshot(
+            Box::from("BTC-PERP"),
+            Box::new([bid("a", 1.5), bid("b", 0.5)]),
+        ));
+
+        crate::rt::block_on(async {
+            let order = OrderRequest::limit("1", "BTC-PERP", Side::Buy, 99.0, 1.0);
+            adapter.place_order(&order).await.unwrap();
+            assert_eq!(2.0, adapter.orders[0].queue_ahead);
+
+            // Order behind leaving doesn't move the order up
+            adapter.on_market_event(&l3_update(vec![
+                L3Update::Open(bid("c", 3.0)),
+                L3Update::Done { id: Box::from("c") },
+            ]));
+            assert_eq!(2.0, adapter.orders[0].queue_ahead);
+
+            adapter.on_market_event(&l3_update(vec![L3Update::Done { id: Box::from("a") }]));
+            assert_eq!(0.5, adapter.orders[0].queue_ahead);
+
+            // Trade fills the 0.5 of b first
+            adapter.on_market_event(&trades(&[(99.0, 1.0)]));
+            assert_eq!(
+                vec![(99.0, 0.5)],
+                fills(&adapter.poll_events().await.unwrap())
+            );
+        });
+    }
+
+    #[test]
+    fn test_post_only_and_cancel() {
+        let mut adapter = adapter();
+
+        crate::rt::block_on(async {
+            let crossing = OrderRequest::limit("1", "BTC-PERP", Side::Sell, 99.0, 1.0).post_only();
+            assert!(adapter.place_order(&crossing).await.is_err());
+
+            let order = OrderRequest::limit("2", "BTC-PERP", Side::Sell, 105.0, 1.0).post_only();
+            adapter.place_order(&order).await.unwrap();
+            let cancel = CancelRequest {
+                client_id: Box::from("2"),
+                market: Box::from("BTC-PERP"),
+            };
+            let update = adapter.cancel_order(&cancel).await.unwrap();
+            assert_eq!(OrderStatus::Cancelled, update.status);
+            assert!(adapter.cancel_order(&cancel).await.is_err());
+        });
+    }
+}
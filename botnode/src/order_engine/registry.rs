@@ -0,0 +1,140 @@
+//! Execution adapter registry
+//!
+//! Maps execution adapter names as used in the configuration to factories
+//! spawning the order engine with the matching adapter.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    audit::AuditProducer,
+    bus::Subscriber,
+    control::ControlCommand,
+    order_engine::{engine::OrderEngine, ExchangeEvent, ExchangeRequest, ExecutionAdapter},
+    prelude::*,
+    risk_engine::RiskConfig,
+    rt::EngineExecutor,
+    secrets::SecretsConfig,
+};
+
+/// Everything needed to spawn an order engine
+pub(crate) struct ExecutionSpawn {
+    /// Core and settings of the engine's executor
+    pub executor: EngineExecutor,
+    pub config_rx: spsc_queue::Consumer<BotConfiguration>,
+    pub request_rx: spsc_queue::Consumer<ExchangeRequest>,
+    /// Commands of botvana-server
+    pub commands: Subscriber<ControlCommand>,
+    pub audit_tx: AuditProducer,
+    /// Where the adapter loads its API credentials from
+    pub secrets: SecretsConfig,
+    pub risk: RiskConfig,
+    /// Exchange the orders are executed on and its market data, for
+    /// adapters simulating execution
+    pub market_data: Option<(ExchangeId, ConsumersMap<Box<str>, MarketEvent>)>,
+    /// Number of engines consuming the order events
+    pub consumers: usize,
+    pub shutdown: Shutdown,
+}
+
+/// Spawns order engine, returning it with a receiver of its events per
+/// consumer
+pub(crate) type ExecutionFactory =
+    fn(
+        ExecutionSpawn,
+    ) -> Result<(SpawnedEngine, Vec<spsc_queue::Consumer<ExchangeEvent>>), StartEngineError>;
+
+/// Order engine factories by execution adapter name
+#[derive(Clone)]
+pub(crate) struct ExecutionRegistry {
+    factories: BTreeMap<String, ExecutionFactory>,
+}
+
+impl ExecutionRegistry {
+    /// Registers default-constructed adapter under given name
+    pub fn register_adapter<A>(&mut self, name: &str)
+    where
+        A: ExecutionAdapter + Default + Send + 'static,
+    {
+        self.factories.insert(name.to_string(), spawn_adapter::<A>);
+    }
+
+    /// Returns whether there is an adapter with given name
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(name)
+    }
+
+    /// Spawns order engine with the named adapter
+    pub fn spawn(
+        &self,
+        name: &str,
+        spawn: ExecutionSpawn,
+    ) -> Result<(SpawnedEngine, Vec<spsc_queue::Consumer<ExchangeEvent>>), StartEngineError> {
+        match self.factories.get(name) {
+            Some(factory) => factory(spawn),
+            None => Err(StartEngineError {
+                source: format!("Unknown execution adapter {name}").into(),
+            }),
+        }
+    }
+}
+
+impl Default for ExecutionRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            factories: BTreeMap::new(),
+        };
+        registry.register_adapter::<crate::order_engine::null_adapter::NullAdapter>("null");
+        registry
+            .register_adapter::<crate::order_engine::paper_trading_adapter::PaperTradingAdapter>(
+                "paper-trading",
+            );
+        #[cfg(feature = "ftx")]
+        registry.register_adapter::<crate::order_engine::ftx::Ftx>("ftx");
+        registry
+    }
+}
+
+fn spawn_adapter<A>(
+    spawn: ExecutionSpawn,
+) -> Result<(SpawnedEngine, Vec<spsc_queue::Consumer<ExchangeEvent>>), StartEngineError>
+where
+    A: ExecutionAdapter + Default + Send + 'static,
+{
+    let mut engine = OrderEngine::new(
+        spawn.config_rx,
+        A::default(),
+        spawn.request_rx,
+        spawn.audit_tx,
+        spawn.secrets,
+        spawn.risk,
+    )
+    .with_commands(spawn.commands);
+    if let Some((exchange, market_data_rxs)) = spawn.market_data {
+        engine = engine.with_market_data(exchange, market_data_rxs);
+    }
+    let status_rx = engine.status_rx();
+    let events_rxs = (0..spawn.consumers).map(|_| engine.data_rx()).collect();
+
+    let spawned = SpawnedEngine {
+        r#type: EngineType::OrderEngine,
+        status_rx,
+        handle: spawn_engine(spawn.executor, engine, spawn.shutdown)?,
+    };
+
+    Ok((spawned, events_rxs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_default_adapters() {
+        let registry = ExecutionRegistry::default();
+
+        assert!(registry.contains("null"));
+        assert!(registry.contains("paper-trading"));
+        assert_eq!(cfg!(feature = "ftx"), registry.contains("ftx"));
+        assert!(!registry.contains("unknown"));
+    }
+}
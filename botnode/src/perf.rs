@@ -0,0 +1,182 @@
+//! Hardware performance counters
+//!
+//! With the `perf` feature enabled on Linux, [`PerfSection`] samples CPU
+//! cycles, instructions and cache misses around hot path sections. Without it
+//! the section only runs the measured closure.
+
+/// Measure every n-th call, reading the counters costs a few syscalls
+pub const DEFAULT_SAMPLE_EVERY: u32 = 64;
+
+#[cfg(all(feature = "perf", target_os = "linux"))]
+pub use linux::PerfSection;
+
+#[cfg(not(all(feature = "perf", target_os = "linux")))]
+pub use noop::PerfSection;
+
+#[cfg(all(feature = "perf", target_os = "linux"))]
+mod linux {
+    use perf_event::{events::Hardware, Builder, Counter, Group};
+    use tracing::warn;
+
+    struct Counters {
+        group: Group,
+        cycles: Counter,
+        instructions: Counter,
+        cache_misses: Counter,
+    }
+
+    impl Counters {
+        fn new() -> std::io::Result<Self> {
+            let mut group = Group::new()?;
+            let cycles = Builder::new()
+                .group(&mut group)
+                .kind(Hardware::CPU_CYCLES)
+                .build()?;
+            let instructions = Builder::new()
+                .group(&mut group)
+                .kind(Hardware::INSTRUCTIONS)
+                .build()?;
+            let cache_misses = Builder::new()
+                .group(&mut group)
+                .kind(Hardware::CACHE_MISSES)
+                .build()?;
+
+            Ok(Self {
+                group,
+                cycles,
+                instructions,
+                cache_misses,
+            })
+        }
+    }
+
+    /// Hot path section measured with hardware counters
+    ///
+    /// Counters are bound to the calling thread, so the section has to be
+    /// created on the thread it measures.
+    pub struct PerfSection {
+        name: &'static str,
+        counters: Option<Counters>,
+        sample_every: u32,
+        calls: u32,
+        samples: u64,
+        cycles: u64,
+        instructions: u64,
+        cache_misses: u64,
+    }
+
+    impl PerfSection {
+        pub fn new(name: &'static str, sample_every: u32) -> Self {
+            let counters = match Counters::new() {
+                Ok(counters) => Some(counters),
+                Err(e) => {
+                    warn!("Failed to open perf counters for {name}: {e}");
+                    None
+                }
+            };
+
+            Self {
+                name,
+                counters,
+                sample_every: sample_every.max(1),
+                calls: 0,
+                samples: 0,
+                cycles: 0,
+                instructions: 0,
+                cache_misses: 0,
+            }
+        }
+
+        /// Runs given closure, measuring it when the call is sampled
+        #[inline]
+        pub fn measure<R>(&mut self, f: impl FnOnce() -> R) -> R {
+            self.calls = self.calls.wrapping_add(1);
+
+            let counters = match &mut self.counters {
+                Some(counters) if self.calls % self.sample_every == 0 => counters,
+                _ => return f(),
+            };
+
+            if counters.group.reset().and(counters.group.enable()).is_err() {
+                return f();
+            }
+            let res = f();
+            let _ = counters.group.disable();
+
+            if let Ok(counts) = counters.group.read() {
+                self.samples += 1;
+                self.cycles += counts[&counters.cycles];
+                self.instructions += counts[&counters.instructions];
+                self.cache_misses += counts[&counters.cache_misses];
+            }
+
+            res
+        }
+
+        /// Publishes per-call averages into the metrics registry and resets them
+        pub fn publish(&mut self) {
+            if self.samples == 0 {
+                return;
+            }
+
+            let registry = crate::metrics::registry();
+            let labels = crate::metrics::MetricLabels::new().with("section", self.name);
+            let samples = self.samples as f64;
+
+            registry.set_gauge(
+                "perf_cycles_per_call",
+                &labels,
+                self.cycles as f64 / samples,
+            );
+            registry.set_gauge(
+                "perf_instructions_per_call",
+                &labels,
+                self.instructions as f64 / samples,
+            );
+            registry.set_gauge(
+                "perf_cache_misses_per_call",
+                &labels,
+                self.cache_misses as f64 / samples,
+            );
+
+            self.samples = 0;
+            self.cycles = 0;
+            self.instructions = 0;
+            self.cache_misses = 0;
+        }
+    }
+}
+
+#[cfg(not(all(feature = "perf", target_os = "linux")))]
+mod noop {
+    /// Hot path section, hardware counters are disabled in this build
+    pub struct PerfSection;
+
+    impl PerfSection {
+        pub fn new(_name: &'static str, _sample_every: u32) -> Self {
+            Self
+        }
+
+        #[inline(always)]
+        pub fn measure<R>(&mut self, f: impl FnOnce() -> R) -> R {
+            f()
+        }
+
+        pub fn publish(&mut self) {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_perf_section_returns_result() {
+        let mut section = PerfSection::new("test", 1);
+
+        for i in 0..4 {
+            assert_eq!(i * 2, section.measure(|| i * 2));
+        }
+        section.publish();
+    }
+}
@@ -0,0 +1,266 @@
+//! Placement of the engines on the NUMA node of the NIC
+//!
+//! Memory and interrupts of a NIC belong to one NUMA node, engines reading
+//! its traffic from cores of another node pay for every cache miss crossing
+//! the interconnect. With `cpu.nic` set, the engines without a core from the
+//! configuration get the free cores of the NIC's node, read from
+//! `/sys/devices/system/node`. Market data and order engines pinned to cores
+//! of another node, cores the machine doesn't have and nodes with too few
+//! free cores fail the validation.
+//!
+//! Machines without the sysfs topology are treated as a single node with all
+//! the available cores.
+
+use std::{fs, path::Path};
+
+use crate::config::{CpuAssignment, CpuConfig};
+
+const NODES_PATH: &str = "/sys/devices/system/node";
+
+/// NUMA node with its cores
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NumaNode {
+    pub id: usize,
+    /// Cores of the node in ascending order
+    pub cpus: Vec<usize>,
+}
+
+/// NUMA nodes of the machine
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Topology {
+    nodes: Vec<NumaNode>,
+}
+
+impl Topology {
+    pub fn new(nodes: Vec<NumaNode>) -> Self {
+        Self { nodes }
+    }
+
+    /// Reads the topology of this machine
+    pub fn read() -> Self {
+        match read_nodes(Path::new(NODES_PATH)) {
+            Some(nodes) if !nodes.is_empty() => Self::new(nodes),
+            _ => {
+                let n_cpus = std::thread::available_parallelism().map_or(1, |n| n.get());
+                Self::new(vec![NumaNode {
+                    id: 0,
+                    cpus: (0..n_cpus).collect(),
+                }])
+            }
+        }
+    }
+
+    /// Returns the nodes ordered by id
+    pub fn nodes(&self) -> &[NumaNode] {
+        &self.nodes
+    }
+
+    /// Returns the node with given id
+    pub fn node(&self, id: usize) -> Option<&NumaNode> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    /// Returns id of the node the core belongs to
+    pub fn node_of(&self, cpu: usize) -> Option<usize> {
+        self.nodes
+            .iter()
+            .find(|node| node.cpus.contains(&cpu))
+            .map(|node| node.id)
+    }
+}
+
+/// Returns the nodes listed in the sysfs directory
+fn read_nodes(path: &Path) -> Option<Vec<NumaNode>> {
+    let mut nodes: Vec<_> = fs::read_dir(path)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name();
+            let id = name.to_str()?.strip_prefix("node")?.parse().ok()?;
+            let cpus = fs::read_to_string(entry.path().join("cpulist")).ok()?;
+
+            Some(NumaNode {
+                id,
+                cpus: parse_cpu_list(&cpus)?,
+            })
+        })
+        .collect();
+    nodes.sort_by_key(|node| node.id);
+
+    Some(nodes)
+}
+
+/// Parses sysfs CPU list like `0-3,8-11`
+pub fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let mut cpus = Vec::new();
+
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((first, last)) => {
+                let (first, last): (usize, usize) = (first.parse().ok()?, last.parse().ok()?);
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(range.parse().ok()?),
+        }
+    }
+    cpus.sort_unstable();
+
+    Some(cpus)
+}
+
+/// Returns NUMA node of the network interface
+///
+/// Interfaces of single-node machines and virtual interfaces belong to no
+/// node, `None` is returned for them.
+pub fn nic_node(nic: &str) -> Result<Option<usize>, String> {
+    let path = Path::new("/sys/class/net").join(nic);
+    if !path.exists() {
+        return Err(format!("network interface {nic} not found"));
+    }
+
+    Ok(fs::read_to_string(path.join("device/numa_node"))
+        .ok()
+        .and_then(|node| node.trim().parse().ok()))
+}
+
+/// Assigns the engines without a configured core to free cores of the node
+///
+/// Cores are assigned in the order of the [`CpuConfig`] fields.
+pub fn place(
+    config: &CpuConfig,
+    n_exchanges: usize,
+    topology: &Topology,
+    node: usize,
+) -> Result<CpuAssignment, String> {
+    let node_cpus = &topology
+        .node(node)
+        .ok_or_else(|| format!("NUMA node {node} not found"))?
+        .cpus;
+
+    let mut configured = vec![
+        ("control", config.control),
+        ("indicator", config.indicator),
+        ("trading", config.trading),
+        ("exchange", config.exchange),
+        ("audit", config.audit),
+        ("alert", config.alert),
+        ("position", config.position),
+        ("recorder", config.recorder),
+    ];
+    if let Some(cores) = &config.market_data {
+        configured.extend(cores.iter().map(|core| ("market_data", Some(*core))));
+    }
+    for (engine, core) in &configured {
+        let core = match core {
+            Some(core) => *core,
+            None => continue,
+        };
+        let core_node = topology
+            .node_of(core)
+            .ok_or_else(|| format!("core {core} of {engine} engine doesn't exist"))?;
+        // Engines reading the exchange traffic
+        let exchange_io = matches!(*engine, "market_data" | "exchange");
+        if exchange_io && core_node != node {
+            return Err(format!(
+                "core {core} of {engine} engine is on NUMA node {core_node}, \
+                 the NIC is on node {node}"
+            ));
+        }
+    }
+
+    let mut free = node_cpus
+        .iter()
+        .copied()
+        .filter(|cpu| !configured.iter().any(|(_, core)| *core == Some(*cpu)));
+    let n_unset = configured.iter().filter(|(_, core)| core.is_none()).count()
+        + config.market_data.as_ref().map_or(n_exchanges, |_| 0);
+    let mut next = |core: Option<usize>| {
+        core.or_else(|| free.next())
+            .ok_or_else(|| format!("NUMA node {node} has too few free cores for {n_unset} engines"))
+    };
+
+    Ok(CpuAssignment {
+        control: next(config.control)?,
+        market_data: match &config.market_data {
+            Some(cores) => cores.clone(),
+            None => (0..n_exchanges)
+                .map(|_| next(None))
+                .collect::<Result<_, _>>()?,
+        },
+        indicator: next(config.indicator)?,
+        trading: next(config.trading)?,
+        exchange: next(config.exchange)?,
+        audit: next(config.audit)?,
+        alert: next(config.alert)?,
+        position: next(config.position)?,
+        recorder: next(config.recorder)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topology() -> Topology {
+        Topology::new(vec![
+            NumaNode {
+                id: 0,
+                cpus: (0..8).collect(),
+            },
+            NumaNode {
+                id: 1,
+                cpus: (8..20).collect(),
+            },
+        ])
+    }
+
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(
+            Some(vec![0, 1, 2, 3, 8, 10, 11]),
+            parse_cpu_list("0-3,8,10-11\n")
+        );
+        assert_eq!(Some(vec![]), parse_cpu_list("\n"));
+        assert_eq!(None, parse_cpu_list("0-x"));
+    }
+
+    #[test]
+    fn test_place_on_nic_node() {
+        let config = CpuConfig {
+            trading: Some(9),
+            ..CpuConfig::default()
+        };
+        let assignment = place(&config, 2, &topology(), 1).unwrap();
+
+        assert_eq!(8, assignment.control);
+        assert_eq!(&[10, 11][..], &*assignment.market_data);
+        assert_eq!(12, assignment.indicator);
+        assert_eq!(9, assignment.trading);
+        assert_eq!(13, assignment.exchange);
+        assert_eq!(17, assignment.recorder);
+    }
+
+    #[test]
+    fn test_place_conflicts() {
+        let config = CpuConfig {
+            market_data: Some(Box::new([2])),
+            ..CpuConfig::default()
+        };
+        assert!(place(&config, 1, &topology(), 1)
+            .unwrap_err()
+            .contains("NUMA node 0"));
+
+        let config = CpuConfig {
+            audit: Some(42),
+            ..CpuConfig::default()
+        };
+        assert!(place(&config, 1, &topology(), 1)
+            .unwrap_err()
+            .contains("doesn't exist"));
+
+        // 8 cores for 10 engines
+        assert!(place(&CpuConfig::default(), 2, &topology(), 0)
+            .unwrap_err()
+            .contains("too few free cores"));
+    }
+}
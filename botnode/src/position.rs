@@ -0,0 +1,125 @@
+//! Position and P&L tracking
+//!
+//! The position engine applies fills from the order engine to per-market
+//! positions and marks them to the last traded price from market data.
+//! Every change of a position is pushed over its ring channels, so the
+//! trading engine can pass it to strategies, and recorded by the audit
+//! engine.
+
+pub(crate) mod engine;
+
+use botvana::exchange::ExchangeId;
+
+use crate::trading::strategy::Side;
+
+/// Position in a single market
+#[derive(Clone, Debug, PartialEq)]
+pub struct Position {
+    pub exchange: ExchangeId,
+    pub market: Box<str>,
+    /// Signed position size, negative when short
+    pub size: f64,
+    /// Average price the open position was entered at, zero when flat
+    pub avg_entry_price: f64,
+    /// P&L of the closed part of the position, fees included
+    pub realized_pnl: f64,
+    /// Last traded price on the market
+    pub mark_price: Option<f64>,
+}
+
+impl Position {
+    pub fn new(exchange: ExchangeId, market: &str) -> Self {
+        Self {
+            exchange,
+            market: Box::from(market),
+            size: 0.0,
+            avg_entry_price: 0.0,
+            realized_pnl: 0.0,
+            mark_price: None,
+        }
+    }
+
+    /// Applies the fill, updating entry price and realized P&L
+    ///
+    /// Fills on the side of the position increase it at the size-weighted
+    /// average price, fills on the other side realize P&L of the closed
+    /// size. A fill larger than the position flips it, the rest is entered
+    /// at the fill price.
+    pub fn apply_fill(&mut self, side: Side, price: f64, size: f64, fee: f64) {
+        let fill = side.signed(size);
+
+        if self.size == 0.0 || self.size.signum() == fill.signum() {
+            let total = self.size.abs() + size;
+            self.avg_entry_price = (self.avg_entry_price * self.size.abs() + price * size) / total;
+        } else {
+            let closed = size.min(self.size.abs());
+            self.realized_pnl += closed * (price - self.avg_entry_price) * self.size.signum();
+
+            if size > closed {
+                self.avg_entry_price = price;
+            }
+        }
+
+        self.size += fill;
+        if self.size.abs() < f64::EPSILON {
+            self.size = 0.0;
+            self.avg_entry_price = 0.0;
+        }
+        self.realized_pnl -= fee;
+    }
+
+    /// Returns P&L of the open position at the mark price
+    pub fn unrealized_pnl(&self) -> f64 {
+        match self.mark_price {
+            Some(mark) if self.size != 0.0 => (mark - self.avg_entry_price) * self.size,
+            _ => 0.0,
+        }
+    }
+
+    /// Returns the position as shown in the status snapshot
+    pub fn summary(&self) -> crate::status::PositionSummary {
+        crate::status::PositionSummary {
+            exchange: self.exchange,
+            market: self.market.clone(),
+            size: self.size,
+            avg_entry_price: self.avg_entry_price,
+            realized_pnl: self.realized_pnl,
+            unrealized_pnl: self.unrealized_pnl(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_apply_fills() {
+        let mut position = Position::new(ExchangeId::Ftx, "BTC-PERP");
+
+        position.apply_fill(Side::Buy, 100.0, 1.0, 0.0);
+        position.apply_fill(Side::Buy, 110.0, 1.0, 0.0);
+        assert_eq!(2.0, position.size);
+        assert_eq!(105.0, position.avg_entry_price);
+
+        position.mark_price = Some(120.0);
+        assert_eq!(30.0, position.unrealized_pnl());
+
+        position.apply_fill(Side::Sell, 120.0, 1.0, 0.5);
+        assert_eq!(1.0, position.size);
+        assert_eq!(105.0, position.avg_entry_price);
+        assert_eq!(14.5, position.realized_pnl);
+
+        // Flips short
+        position.apply_fill(Side::Sell, 100.0, 3.0, 0.0);
+        assert_eq!(-2.0, position.size);
+        assert_eq!(100.0, position.avg_entry_price);
+        assert_eq!(9.5, position.realized_pnl);
+
+        position.apply_fill(Side::Buy, 90.0, 2.0, 0.0);
+        assert_eq!(0.0, position.size);
+        assert_eq!(0.0, position.avg_entry_price);
+        assert_eq!(29.5, position.realized_pnl);
+        assert_eq!(0.0, position.unrealized_pnl());
+    }
+}
@@ -0,0 +1,248 @@
+use super::Position;
+use crate::audit::{AuditEvent, AuditProducer, Category, Severity};
+use crate::metrics::MetricLabels;
+use crate::order_engine::{ExchangeEvent, Fill};
+use crate::prelude::*;
+
+const CONSUMER_LIMIT: usize = 16;
+const QUEUE_LEN: usize = 1024;
+
+/// How often positions marked to new prices are published
+const MARK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Position tracking engine
+pub struct PositionEngine {
+    /// Exchange the orders are executed on, positions are marked with its
+    /// trades
+    exchange: ExchangeId,
+    exchange_rx: spsc_queue::Consumer<ExchangeEvent>,
+    market_data_rxs: ConsumersMap<Box<str>, MarketEvent>,
+    audit_tx: AuditProducer,
+    positions: HashMap<Box<str>, Position>,
+    data_txs: ArrayVec<spsc_queue::Producer<Position>, CONSUMER_LIMIT>,
+    status_tx: spsc_queue::Producer<EngineStatus>,
+    status_rx: spsc_queue::Consumer<EngineStatus>,
+}
+
+impl PositionEngine {
+    pub fn new(
+        exchange: ExchangeId,
+        exchange_rx: spsc_queue::Consumer<ExchangeEvent>,
+        market_data_rxs: ConsumersMap<Box<str>, MarketEvent>,
+        audit_tx: AuditProducer,
+    ) -> Self {
+        let (status_tx, status_rx) = spsc_queue::make(1);
+        Self {
+            exchange,
+            exchange_rx,
+            market_data_rxs,
+            audit_tx,
+            positions: HashMap::new(),
+            data_txs: ArrayVec::new(),
+            status_tx,
+            status_rx,
+        }
+    }
+
+    /// Applies the fill to the position of its market and publishes it
+    fn process_fill(&mut self, fill: &Fill) {
+        let exchange = self.exchange;
+        let position = self
+            .positions
+            .entry(fill.market.clone())
+            .or_insert_with(|| Position::new(exchange, &fill.market));
+        position.apply_fill(fill.side, fill.price, fill.size, fill.fee);
+        let position = position.clone();
+
+        crate::audit::record(
+            &self.audit_tx,
+            AuditEvent::new(
+                Severity::Info,
+                Category::Position,
+                "position-engine",
+                format!(
+                    "position {} {} @ {}, realized P&L {}",
+                    position.market, position.size, position.avg_entry_price, position.realized_pnl
+                ),
+            ),
+        );
+        crate::status::update(|s| {
+            s.record_fill(crate::status::Fill {
+                seq: 0,
+                order_id: fill
+                    .client_id
+                    .as_deref()
+                    .unwrap_or(&*fill.exchange_id)
+                    .to_string(),
+                exchange,
+                market: fill.market.clone(),
+                side: fill.side,
+                price: fill.price,
+                size: fill.size,
+                time: fill.time,
+            })
+        });
+
+        self.publish(position);
+    }
+
+    /// Marks the position of the market to the last traded price, returns
+    /// whether there is such position
+    fn process_trades(&mut self, market: &str, trades: &[botvana::market::trade::Trade]) -> bool {
+        match (self.positions.get_mut(market), trades.last()) {
+            (Some(position), Some(trade)) => {
+                position.mark_price = Some(trade.price);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Pushes the position to all consumers and updates the status snapshot
+    fn publish(&self, position: Position) {
+        {
+            let labels = MetricLabels::new()
+                .exchange(position.exchange.as_str())
+                .symbol(&position.market);
+            let registry = crate::metrics::registry();
+            registry.set_gauge("position_size", &labels, position.size);
+            registry.set_gauge("position_realized_pnl", &labels, position.realized_pnl);
+            registry.set_gauge(
+                "position_unrealized_pnl",
+                &labels,
+                position.unrealized_pnl(),
+            );
+        }
+
+        crate::status::update(|s| {
+            s.positions.retain(|p| p.market != position.market);
+            s.positions.push(position.summary());
+        });
+
+        self.push_value(position);
+    }
+
+    /// Runs the position event loop
+    fn run_event_loop(mut self, shutdown: Shutdown) -> Result<(), EngineError> {
+        let mut marked = Vec::<Box<str>>::new();
+        let mut last_mark = std::time::Instant::now();
+
+        self.status_tx.try_push(EngineStatus::Running);
+
+        loop {
+            if shutdown.shutdown_started() {
+                break Ok(());
+            }
+
+            if let Some(ExchangeEvent::Fill(fill)) = self.exchange_rx.try_pop() {
+                self.process_fill(&fill);
+            }
+
+            // Markets of other exchanges are drained too so their queues
+            // don't fill up
+            let mut trades = Vec::new();
+            for (exchange, market_data_rx) in self.market_data_rxs.iter() {
+                if let Some(MarketEvent {
+                    r#type: MarketEventType::Trades(market, market_trades),
+                    ..
+                }) = market_data_rx.try_pop()
+                {
+                    if &**exchange == self.exchange.as_str() {
+                        trades.push((market, market_trades));
+                    }
+                }
+            }
+            for (market, market_trades) in trades {
+                if self.process_trades(&market, &market_trades) && !marked.contains(&market) {
+                    marked.push(market);
+                }
+            }
+
+            if last_mark.elapsed() >= MARK_INTERVAL {
+                last_mark = std::time::Instant::now();
+                for market in marked.drain(..) {
+                    if let Some(position) = self.positions.get(&market) {
+                        self.publish(position.clone());
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Engine for PositionEngine {
+    fn name(&self) -> String {
+        "position-engine".to_string()
+    }
+
+    fn status_rx(&self) -> spsc_queue::Consumer<EngineStatus> {
+        self.status_rx.clone()
+    }
+
+    async fn start(self, shutdown: Shutdown) -> Result<(), EngineError> {
+        info!("Starting position engine for {}", self.exchange.as_str());
+
+        self.status_tx.try_push(EngineStatus::Booting);
+
+        self.run_event_loop(shutdown)
+    }
+}
+
+#[async_trait(?Send)]
+impl EngineData for PositionEngine {
+    type Data = Position;
+
+    fn data_txs(&self) -> &[spsc_queue::Producer<Self::Data>] {
+        &self.data_txs
+    }
+
+    fn data_rx(&mut self) -> spsc_queue::Consumer<Self::Data> {
+        let (data_tx, data_rx) = spsc_queue::make(QUEUE_LEN);
+        self.data_txs.push(data_tx);
+        data_rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trading::strategy::Side;
+
+    #[test]
+    fn test_process_fill_publishes_position() {
+        let (_exchange_tx, exchange_rx) = spsc_queue::make(1);
+        let (audit_tx, audit_rx) = spsc_queue::make(1);
+        let mut engine = PositionEngine::new(
+            ExchangeId::Ftx,
+            exchange_rx,
+            ConsumersMap::default(),
+            audit_tx,
+        );
+        let position_rx = engine.data_rx();
+
+        engine.process_fill(&Fill {
+            client_id: Some(Box::from("bot-1")),
+            exchange_id: Box::from("1"),
+            market: Box::from("ETH-PERP"),
+            side: Side::Sell,
+            price: 2000.0,
+            size: 0.5,
+            fee: 0.1,
+            time: 0,
+        });
+        assert!(engine.process_trades(
+            "ETH-PERP",
+            &[botvana::market::trade::Trade::new(1900.0, 1.0, Utc::now())]
+        ));
+
+        let position = position_rx.try_pop().unwrap();
+        assert_eq!(-0.5, position.size);
+        assert_eq!(-0.1, position.realized_pnl);
+        assert_eq!(
+            Some(1900.0),
+            engine.positions.get("ETH-PERP").unwrap().mark_price
+        );
+        assert!(audit_rx.try_pop().is_some());
+    }
+}
@@ -0,0 +1,367 @@
+//! Rate limits of the exchange REST APIs
+//!
+//! Every REST call of the market data and execution adapters first takes a
+//! token from the bucket of its exchange and endpoint category. Buckets are
+//! shared by all engines of the process, so the quota of an exchange holds
+//! however many engines call it. Calls over the limit wait until the bucket
+//! refills, they are counted in `rest_throttled_total` and the time they
+//! waited in `rest_throttled_wait_ms_total`.
+//!
+//! The default limits follow the documented quotas of the exchanges, for
+//! reference see [`default_limit`]. `[rate_limits]` overrides them by
+//! exchange and endpoint:
+//!
+//! ```toml
+//! [rate_limits.binance]
+//! orderbook = { requests = 100, interval_ms = 60000 }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+
+use crate::{metrics::MetricLabels, prelude::*};
+
+static LIMITER: OnceCell<RateLimiter> = OnceCell::new();
+
+/// Category of the REST endpoints sharing a rate limit
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Endpoint {
+    /// Listed markets
+    Markets,
+    /// Orderbook snapshots
+    Orderbook,
+    /// Funding rates and open interest
+    Funding,
+    /// Placing, amending and cancelling orders and polling fills
+    Orders,
+    /// Server time
+    Time,
+}
+
+impl Endpoint {
+    /// Returns the name used in the configuration and metric labels
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Endpoint::Markets => "markets",
+            Endpoint::Orderbook => "orderbook",
+            Endpoint::Funding => "funding",
+            Endpoint::Orders => "orders",
+            Endpoint::Time => "time",
+        }
+    }
+}
+
+/// Requests allowed per interval, all of them can be sent at once
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub requests: u32,
+    pub interval_ms: u64,
+}
+
+impl RateLimit {
+    pub const fn new(requests: u32, interval_ms: u64) -> Self {
+        Self {
+            requests,
+            interval_ms,
+        }
+    }
+
+    /// Returns description of the first invalid value
+    pub fn validate(&self) -> Result<(), String> {
+        if self.requests == 0 {
+            return Err("requests must be positive".to_string());
+        }
+        if self.interval_ms == 0 {
+            return Err("interval_ms must be positive".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Limits of the endpoints of one exchange, the defaults apply to the ones
+/// not set
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EndpointLimits {
+    pub markets: Option<RateLimit>,
+    pub orderbook: Option<RateLimit>,
+    pub funding: Option<RateLimit>,
+    pub orders: Option<RateLimit>,
+    pub time: Option<RateLimit>,
+}
+
+impl EndpointLimits {
+    pub fn get(&self, endpoint: Endpoint) -> Option<RateLimit> {
+        match endpoint {
+            Endpoint::Markets => self.markets,
+            Endpoint::Orderbook => self.orderbook,
+            Endpoint::Funding => self.funding,
+            Endpoint::Orders => self.orders,
+            Endpoint::Time => self.time,
+        }
+    }
+}
+
+/// Limits overriding the defaults, by exchange
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct RateLimitsConfig {
+    pub exchanges: BTreeMap<String, EndpointLimits>,
+}
+
+impl RateLimitsConfig {
+    /// Returns description of the first invalid value
+    pub fn validate(&self) -> Result<(), String> {
+        for (exchange, limits) in &self.exchanges {
+            exchange.parse::<ExchangeId>()?;
+
+            for endpoint in [
+                Endpoint::Markets,
+                Endpoint::Orderbook,
+                Endpoint::Funding,
+                Endpoint::Orders,
+                Endpoint::Time,
+            ] {
+                if let Some(limit) = limits.get(endpoint) {
+                    limit
+                        .validate()
+                        .map_err(|e| format!("{exchange}.{}: {e}", endpoint.as_str()))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the configured limit of the endpoint or the default one
+    pub fn limit(&self, exchange: ExchangeId, endpoint: Endpoint) -> RateLimit {
+        self.exchanges
+            .iter()
+            .filter(|(name, _)| name.parse::<ExchangeId>() == Ok(exchange))
+            .find_map(|(_, limits)| limits.get(endpoint))
+            .unwrap_or_else(|| default_limit(exchange, endpoint))
+    }
+}
+
+/// Returns the documented quota of the exchange endpoints
+///
+/// Quotas in request weight are converted to requests of the weight the
+/// adapters send, e.g. Binance allows 1200 weight per minute, the limits of
+/// the snapshots and exchange info together stay below it.
+pub const fn default_limit(exchange: ExchangeId, endpoint: Endpoint) -> RateLimit {
+    match (exchange, endpoint) {
+        // 30 requests per second
+        (ExchangeId::Ftx, _) => RateLimit::new(30, 1000),
+        // 1200 weight per minute, 10 orders per second
+        (ExchangeId::BinanceSpot, Endpoint::Markets) => RateLimit::new(10, 60_000),
+        (ExchangeId::BinanceSpot, Endpoint::Orderbook) => RateLimit::new(200, 60_000),
+        (ExchangeId::BinanceSpot, _) => RateLimit::new(10, 1000),
+        // serum-vial doesn't document any
+        (ExchangeId::Serum, _) => RateLimit::new(10, 1000),
+        // 10 public and 15 private requests per second
+        (ExchangeId::Coinbase, Endpoint::Orders) => RateLimit::new(15, 1000),
+        (ExchangeId::Coinbase, _) => RateLimit::new(10, 1000),
+        // 1 public request per second, private counter of 15 decaying by
+        // 0.33 per second
+        (ExchangeId::Kraken, Endpoint::Orders) => RateLimit::new(15, 45_000),
+        (ExchangeId::Kraken, _) => RateLimit::new(1, 1000),
+        // 600 requests per 5 seconds, 10 orders per second
+        (ExchangeId::Bybit, Endpoint::Orders) => RateLimit::new(10, 1000),
+        (ExchangeId::Bybit, _) => RateLimit::new(600, 5000),
+        // Per endpoint quotas per 2 seconds
+        (ExchangeId::Okx, Endpoint::Orderbook) => RateLimit::new(40, 2000),
+        (ExchangeId::Okx, Endpoint::Orders) => RateLimit::new(60, 2000),
+        (ExchangeId::Okx, _) => RateLimit::new(20, 2000),
+        // 20 non-matching and 5 matching engine requests per second
+        (ExchangeId::Deribit, Endpoint::Orders) => RateLimit::new(5, 1000),
+        (ExchangeId::Deribit, _) => RateLimit::new(20, 1000),
+        // 400 requests per second
+        (ExchangeId::Bitstamp, _) => RateLimit::new(400, 1000),
+        // 120 public and 600 private requests per minute
+        (ExchangeId::Gemini, Endpoint::Orders) => RateLimit::new(600, 60_000),
+        (ExchangeId::Gemini, _) => RateLimit::new(120, 60_000),
+    }
+}
+
+/// Token bucket refilling continuously up to the limit
+///
+/// Tokens are reserved ahead, the balance goes negative by the calls
+/// waiting for the refill so that they are let through one by one.
+#[derive(Debug)]
+struct Bucket {
+    limit: RateLimit,
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            limit,
+            tokens: limit.requests as f64,
+            last: now,
+        }
+    }
+
+    /// Takes a token, returns how long the call has to wait for it
+    fn reserve(&mut self, now: Instant) -> Duration {
+        let requests = self.limit.requests as f64;
+        let interval_ms = self.limit.interval_ms as f64;
+        let elapsed_ms = now.saturating_duration_since(self.last).as_secs_f64() * 1000.0;
+        self.last = self.last.max(now);
+        self.tokens = (self.tokens + elapsed_ms * requests / interval_ms).min(requests) - 1.0;
+
+        match self.tokens < 0.0 {
+            true => Duration::from_secs_f64(-self.tokens * interval_ms / requests / 1000.0),
+            false => Duration::ZERO,
+        }
+    }
+}
+
+/// Rate limits of all exchanges and endpoints
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitsConfig,
+    buckets: Mutex<HashMap<(ExchangeId, Endpoint), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitsConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Takes a token of the endpoint, returns how long the call has to wait
+    /// before it's sent
+    pub fn reserve(&self, exchange: ExchangeId, endpoint: Endpoint, now: Instant) -> Duration {
+        let mut buckets = self.buckets.lock().unwrap();
+
+        buckets
+            .entry((exchange, endpoint))
+            .or_insert_with(|| Bucket::new(self.config.limit(exchange, endpoint), now))
+            .reserve(now)
+    }
+
+    /// Waits until the REST call to the endpoint is within its limit
+    pub async fn acquire(&self, exchange: ExchangeId, endpoint: Endpoint) {
+        let wait = self.reserve(exchange, endpoint, Instant::now());
+        if wait.is_zero() {
+            return;
+        }
+
+        debug!(
+            "Throttling {} {} request for {wait:?}",
+            exchange,
+            endpoint.as_str()
+        );
+        let labels = MetricLabels::new()
+            .exchange(exchange.as_str())
+            .with("endpoint", endpoint.as_str());
+        let registry = crate::metrics::registry();
+        registry.incr_counter("rest_throttled_total", &labels, 1);
+        registry.incr_counter(
+            "rest_throttled_wait_ms_total",
+            &labels,
+            wait.as_millis() as u64,
+        );
+
+        crate::rt::timer::sleep(wait).await;
+    }
+}
+
+/// Sets the limits of the process, the defaults apply when never called
+pub fn configure(config: &RateLimitsConfig) {
+    if LIMITER.set(RateLimiter::new(config.clone())).is_err() {
+        warn!("REST rate limits are already in use, keeping the previous ones");
+    }
+}
+
+/// Returns rate limiter shared by all engines
+pub fn limiter() -> &'static RateLimiter {
+    LIMITER.get_or_init(|| RateLimiter::new(RateLimitsConfig::default()))
+}
+
+/// Waits until the REST call to the endpoint is within its limit
+pub async fn acquire(exchange: ExchangeId, endpoint: Endpoint) {
+    limiter().acquire(exchange, endpoint).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_tokens() {
+        let now = Instant::now();
+        let mut config = RateLimitsConfig::default();
+        config.exchanges.insert(
+            "binance".to_string(),
+            EndpointLimits {
+                orders: Some(RateLimit::new(2, 1000)),
+                ..EndpointLimits::default()
+            },
+        );
+        let limiter = RateLimiter::new(config);
+
+        let orders = |elapsed_ms| {
+            limiter.reserve(
+                ExchangeId::BinanceSpot,
+                Endpoint::Orders,
+                now + Duration::from_millis(elapsed_ms),
+            )
+        };
+        assert_eq!(Duration::ZERO, orders(0));
+        assert_eq!(Duration::ZERO, orders(0));
+        assert_eq!(Duration::from_millis(500), orders(0));
+        assert_eq!(Duration::from_millis(1000), orders(0));
+        // Two tokens refilled, both already reserved by the waiting calls
+        assert_eq!(Duration::from_millis(500), orders(1000));
+        assert_eq!(Duration::ZERO, orders(3000));
+
+        // Other endpoints keep their own default limits
+        assert_eq!(
+            Duration::ZERO,
+            limiter.reserve(ExchangeId::BinanceSpot, Endpoint::Orderbook, now)
+        );
+    }
+
+    #[test]
+    fn test_config() {
+        let config: RateLimitsConfig = toml::from_str(
+            r#"
+            [ftx]
+            orders = { requests = 5, interval_ms = 200 }
+            "#,
+        )
+        .unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(
+            RateLimit::new(5, 200),
+            config.limit(ExchangeId::Ftx, Endpoint::Orders)
+        );
+        assert_eq!(
+            default_limit(ExchangeId::Ftx, Endpoint::Markets),
+            config.limit(ExchangeId::Ftx, Endpoint::Markets)
+        );
+
+        let config: RateLimitsConfig = toml::from_str(
+            r#"
+            [nasdaq]
+            orders = { requests = 5, interval_ms = 200 }
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+    }
+}
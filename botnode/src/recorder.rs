@@ -0,0 +1,162 @@
+//! Market data recorder
+//!
+//! The recorder engine consumes the market data channel of every exchange
+//! and appends every [`MarketEvent`] into a rotating newline-delimited JSON
+//! log, so that sessions can be replayed and debugged later. Each line is a
+//! [`RecordedEvent`], recordings are replayed by the
+//! [`ReplayAdapter`](crate::market_data::replay::ReplayAdapter).
+
+pub mod engine;
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    prelude::*,
+    util::rotating_log::{self, RotatingLog},
+};
+
+/// Name the recording files start with
+pub const LOG_NAME: &str = "market-data";
+
+/// Recorder configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RecorderConfig {
+    /// Whether the recorder engine is spawned
+    pub enabled: bool,
+    /// Directory the recordings are written to
+    pub dir: PathBuf,
+    /// Size after which the recording is rotated
+    pub max_bytes: u64,
+    /// Seconds after which the recording is rotated
+    pub max_age_secs: u64,
+}
+
+impl Default for RecorderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::from("recordings"),
+            max_bytes: 256 * 1024 * 1024,
+            max_age_secs: 60 * 60,
+        }
+    }
+}
+
+impl RecorderConfig {
+    /// Returns age after which the recording is rotated
+    pub fn max_age(&self) -> Duration {
+        Duration::from_secs(self.max_age_secs)
+    }
+
+    /// Opens the recording, appending to the current file if it exists
+    pub fn open(&self) -> io::Result<RotatingLog> {
+        RotatingLog::open(&self.dir, LOG_NAME, self.max_bytes, self.max_age())
+    }
+}
+
+/// Single line of a recording
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RecordedEvent {
+    /// Exchange the event came from
+    pub exchange: Box<str>,
+    pub event: MarketEvent,
+}
+
+/// Returns recording files in the directory in the order they were written,
+/// rotated files by their timestamps followed by the current file
+pub fn recording_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    rotating_log::log_files(dir, LOG_NAME)
+}
+
+/// Reads events of all recording files in the directory in the order they
+/// were recorded
+///
+/// Files that can't be opened are logged and skipped, lines that can't be
+/// read or parsed are returned as errors.
+pub fn read_recording(dir: &Path) -> io::Result<impl Iterator<Item = io::Result<RecordedEvent>>> {
+    rotating_log::read_log(dir, LOG_NAME)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use botvana::market::trade::Trade;
+
+    #[test]
+    fn test_recorded_event_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("botnode-recorder-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = RecorderConfig {
+            enabled: true,
+            dir: dir.clone(),
+            ..RecorderConfig::default()
+        };
+        let mut log = config.open().unwrap();
+
+        log.write(&RecordedEvent {
+            exchange: Box::from("ftx"),
+            event: MarketEvent::trades(
+                Box::from("BTC/USD"),
+                Box::new([Trade::new(100.0, 1.5, Utc::now())]),
+            ),
+        })
+        .unwrap();
+        log.flush().unwrap();
+
+        let contents = fs::read_to_string(dir.join("market-data.log")).unwrap();
+        let recorded = serde_json::from_str::<RecordedEvent>(contents.trim_end()).unwrap();
+        assert_eq!("ftx", &*recorded.exchange);
+        match recorded.event.r#type {
+            MarketEventType::Trades(market, trades) => {
+                assert_eq!("BTC/USD", &*market);
+                assert_eq!(1.5, trades[0].size);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_recording_files_order() {
+        let dir =
+            std::env::temp_dir().join(format!("botnode-recorder-files-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "market-data.log",
+            "market-data-200.log",
+            "market-data-100-1.log",
+            "market-data-100.log",
+            "audit.log",
+        ] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let names: Vec<_> = recording_files(&dir)
+            .unwrap()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            vec![
+                "market-data-100.log",
+                "market-data-100-1.log",
+                "market-data-200.log",
+                "market-data.log"
+            ],
+            names
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
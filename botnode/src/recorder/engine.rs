@@ -0,0 +1,134 @@
+use super::{RecordedEvent, RecorderConfig};
+use crate::{prelude::*, util::rotating_log::RotatingLog};
+
+/// Maximum number of market events written per loop iteration
+const MAX_EVENTS_PER_ITERATION: usize = 256;
+
+/// Interval the recording is flushed to disk in
+const FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Market data recording engine
+///
+/// Consumes market events of all exchanges and appends them to the rotating
+/// recording log.
+#[derive(Debug)]
+pub struct RecorderEngine {
+    market_data_rxs: ConsumersMap<Box<str>, MarketEvent>,
+    config: RecorderConfig,
+    status_tx: spsc_queue::Producer<EngineStatus>,
+    status_rx: spsc_queue::Consumer<EngineStatus>,
+}
+
+impl RecorderEngine {
+    pub fn new(
+        market_data_rxs: ConsumersMap<Box<str>, MarketEvent>,
+        config: RecorderConfig,
+    ) -> Self {
+        let (status_tx, status_rx) = spsc_queue::make(1);
+
+        Self {
+            market_data_rxs,
+            config,
+            status_tx,
+            status_rx,
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Engine for RecorderEngine {
+    fn name(&self) -> String {
+        "recorder-engine".to_string()
+    }
+
+    fn status_rx(&self) -> spsc_queue::Consumer<EngineStatus> {
+        self.status_rx.clone()
+    }
+
+    async fn start(self, shutdown: Shutdown) -> Result<(), EngineError> {
+        info!(
+            "Starting recorder engine, recording to {:?}",
+            self.config.dir
+        );
+
+        self.status_tx.try_push(EngineStatus::Booting);
+
+        let log = self.config.open().map_err(|e| {
+            self.status_tx.try_push(EngineStatus::Error);
+            EngineError::config(e)
+        })?;
+
+        run_recorder_loop(self.status_tx, self.market_data_rxs, log, shutdown).await
+    }
+}
+
+/// Recorder engine loop
+pub async fn run_recorder_loop(
+    status_tx: spsc_queue::Producer<EngineStatus>,
+    market_data_rxs: ConsumersMap<Box<str>, MarketEvent>,
+    mut log: RotatingLog,
+    shutdown: Shutdown,
+) -> Result<(), EngineError> {
+    status_tx.try_push(EngineStatus::Running);
+
+    let mut last_flush = std::time::Instant::now();
+    let mut recorded = 0u64;
+
+    loop {
+        for _ in 0..MAX_EVENTS_PER_ITERATION {
+            match market_data_rxs.poll_values() {
+                Some((exchange, event)) => {
+                    write_event(&mut log, exchange, event);
+                    recorded += 1;
+                }
+                None => break,
+            }
+        }
+
+        if last_flush.elapsed() >= FLUSH_INTERVAL {
+            if let Err(e) = log.flush() {
+                error!("Failed to flush recording: {e}");
+            }
+
+            if shutdown.shutdown_started() {
+                info!("shutting down recorder engine");
+
+                // Record whatever the market data engines managed to send
+                while let Some((exchange, event)) = market_data_rxs.poll_values() {
+                    write_event(&mut log, exchange, event);
+                    recorded += 1;
+                }
+                if let Err(e) = log.flush() {
+                    error!("Failed to flush recording: {e}");
+                }
+                info!("recorded {recorded} market events");
+
+                status_tx.try_push(EngineStatus::ShuttingDown);
+
+                return Ok(());
+            }
+
+            last_flush = std::time::Instant::now();
+            crate::metrics::registry().set_gauge(
+                "recorded_market_events_total",
+                &crate::metrics::MetricLabels::new(),
+                recorded as f64,
+            );
+        }
+    }
+}
+
+fn write_event(log: &mut RotatingLog, exchange: &str, event: MarketEvent) {
+    // Requested by the server, replaying it would answer a request again
+    if let MarketEventType::OrderbookSnapshot(..) = event.r#type {
+        return;
+    }
+    let recorded = RecordedEvent {
+        exchange: Box::from(exchange),
+        event,
+    };
+
+    if let Err(e) = log.write(&recorded) {
+        error!("Failed to record market event: {e}");
+    }
+}
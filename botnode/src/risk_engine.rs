@@ -0,0 +1,478 @@
+//! Pre-trade risk checks
+//!
+//! The risk engine gates the order flow between strategies and the
+//! execution adapter: the order engine checks every order with
+//! [`RiskEngine::check_order`] before it is sent and rejects the ones over
+//! the configured limits, the rejection reason is audited with the order.
+//!
+//! The kill switch is global to the bot. It is tripped with [`trip`], e.g.
+//! when botvana-server sends `KillSwitch` over the control channel, and
+//! while it is tripped every new order is rejected.
+//!
+//! Trading is paused with [`pause`] when botvana-server sends
+//! `PauseTrading`. Unlike the kill switch, open orders are left alone and
+//! orders reducing positions are still accepted, so positions can be
+//! flattened while paused.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use botvana::cfg::RiskLimits;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+
+use crate::{
+    metrics::MetricLabels,
+    notify::{self, Notification},
+    order_engine::{Fill, OrderRequest, OrderUpdate},
+    prelude::*,
+    trading::strategy::Side,
+};
+
+/// Window of the notional limit
+const NOTIONAL_WINDOW: Duration = Duration::from_secs(60);
+
+/// Reason the kill switch was tripped with, `None` while trading is allowed
+static KILL_SWITCH: Lazy<Mutex<Option<String>>> = Lazy::new(|| Mutex::new(None));
+
+/// Trips the kill switch, stopping all new orders
+pub fn trip<T: ToString>(reason: T) {
+    let reason = reason.to_string();
+    warn!("Kill switch tripped: {reason}");
+
+    let mut kill_switch = KILL_SWITCH.lock().unwrap_or_else(|e| e.into_inner());
+    if kill_switch.is_none() {
+        notify::notify(Notification::kill_switch(&reason));
+    }
+    *kill_switch = Some(reason);
+    crate::metrics::registry().set_gauge("risk_kill_switch_tripped", &MetricLabels::new(), 1.0);
+}
+
+/// Resets the kill switch, allowing new orders again
+pub fn reset() {
+    info!("Kill switch reset");
+
+    *KILL_SWITCH.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    crate::metrics::registry().set_gauge("risk_kill_switch_tripped", &MetricLabels::new(), 0.0);
+}
+
+/// Returns the reason the kill switch was tripped with
+pub fn kill_switch() -> Option<String> {
+    KILL_SWITCH
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// Whether trading is paused
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Pauses trading, only orders reducing positions are accepted
+pub fn pause() {
+    warn!("Trading paused");
+
+    PAUSED.store(true, Ordering::Relaxed);
+    crate::metrics::registry().set_gauge("risk_trading_paused", &MetricLabels::new(), 1.0);
+}
+
+/// Resumes paused trading
+pub fn resume() {
+    info!("Trading resumed");
+
+    PAUSED.store(false, Ordering::Relaxed);
+    crate::metrics::registry().set_gauge("risk_trading_paused", &MetricLabels::new(), 0.0);
+}
+
+/// Returns whether trading is paused
+pub fn paused() -> bool {
+    PAUSED.load(Ordering::Relaxed)
+}
+
+/// Risk limits, every limit is disabled when not set
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RiskConfig {
+    /// Largest size of a single order
+    pub max_order_size: Option<f64>,
+    /// Largest absolute position per market, counting open orders on the
+    /// same side
+    pub max_position: Option<f64>,
+    /// Largest notional value of orders placed within a minute, across all
+    /// markets
+    pub max_notional_per_minute: Option<f64>,
+}
+
+impl From<RiskLimits> for RiskConfig {
+    fn from(limits: RiskLimits) -> Self {
+        Self {
+            max_order_size: limits.max_order_size,
+            max_position: limits.max_position,
+            max_notional_per_minute: limits.max_notional_per_minute,
+        }
+    }
+}
+
+/// Reason an order was rejected by the risk engine
+#[derive(Clone, Debug, PartialEq, thiserror::Error)]
+pub enum RiskRejection {
+    #[error("kill switch tripped: {0}")]
+    KillSwitch(String),
+    #[error("trading paused")]
+    Paused,
+    #[error("order size {size} over limit {limit}")]
+    OrderSize { size: f64, limit: f64 },
+    #[error("position {position} on {market} would exceed limit {limit}")]
+    Position {
+        market: Box<str>,
+        position: f64,
+        limit: f64,
+    },
+    #[error("notional {notional} placed within a minute would exceed limit {limit}")]
+    Notional { notional: f64, limit: f64 },
+    #[error("no reference price to value market order on {0}")]
+    NoPrice(Box<str>),
+}
+
+impl RiskRejection {
+    /// Returns short name of the rejection used in metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RiskRejection::KillSwitch(_) => "kill_switch",
+            RiskRejection::Paused => "paused",
+            RiskRejection::OrderSize { .. } => "order_size",
+            RiskRejection::Position { .. } => "position",
+            RiskRejection::Notional { .. } => "notional",
+            RiskRejection::NoPrice(_) => "no_price",
+        }
+    }
+}
+
+/// Order gate enforcing the risk limits
+#[derive(Debug, Default)]
+pub struct RiskEngine {
+    config: RiskConfig,
+    /// Filled position by market, positive when long
+    positions: HashMap<Box<str>, f64>,
+    /// Open orders by client id
+    open_orders: HashMap<Box<str>, OrderUpdate>,
+    /// Last known price by market, used to value market orders
+    prices: HashMap<Box<str>, f64>,
+    /// Notional of orders placed within the window
+    placed: VecDeque<(Instant, f64)>,
+}
+
+impl RiskEngine {
+    pub fn new(config: RiskConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Replaces the limits, positions and open orders are kept
+    pub fn set_config(&mut self, config: RiskConfig) {
+        self.config = config;
+    }
+
+    /// Checks the order against the limits, an accepted order is counted
+    /// towards the notional limit
+    pub fn check_order(&mut self, order: &OrderRequest, now: Instant) -> Result<(), RiskRejection> {
+        let res = self.check(order, now);
+
+        if let Err(rejection) = &res {
+            crate::metrics::registry().incr_counter(
+                "risk_rejections_total",
+                &MetricLabels::new().with("reason", rejection.kind()),
+                1,
+            );
+        }
+
+        res
+    }
+
+    fn check(&mut self, order: &OrderRequest, now: Instant) -> Result<(), RiskRejection> {
+        if let Some(reason) = kill_switch() {
+            return Err(RiskRejection::KillSwitch(reason));
+        }
+
+        if paused() && !self.reduces_position(order) {
+            return Err(RiskRejection::Paused);
+        }
+
+        if let Some(limit) = self.config.max_order_size {
+            if order.size > limit {
+                return Err(RiskRejection::OrderSize {
+                    size: order.size,
+                    limit,
+                });
+            }
+        }
+
+        if let Some(limit) = self.config.max_position {
+            let position = self.exposure(&order.market, order.side) + order.side.signed(order.size);
+
+            if position.abs() > limit {
+                return Err(RiskRejection::Position {
+                    market: order.market.clone(),
+                    position,
+                    limit,
+                });
+            }
+        }
+
+        let price = order
+            .price
+            .or_else(|| self.prices.get(&order.market).copied());
+        if let Some(limit) = self.config.max_notional_per_minute {
+            let price = price.ok_or_else(|| RiskRejection::NoPrice(order.market.clone()))?;
+
+            while let Some((time, _)) = self.placed.front() {
+                if now.duration_since(*time) < NOTIONAL_WINDOW {
+                    break;
+                }
+                self.placed.pop_front();
+            }
+
+            let notional = self
+                .placed
+                .iter()
+                .map(|(_, notional)| notional)
+                .sum::<f64>()
+                + order.size * price;
+            if notional > limit {
+                return Err(RiskRejection::Notional { notional, limit });
+            }
+        }
+
+        if let Some(price) = price {
+            self.prices.insert(order.market.clone(), price);
+            self.placed.push_back((now, order.size * price));
+        }
+
+        Ok(())
+    }
+
+    /// Tracks the state of an order
+    pub fn on_order_update(&mut self, update: &OrderUpdate) {
+        if update.status.is_closed() {
+            self.open_orders.remove(&update.client_id);
+        } else {
+            self.open_orders
+                .insert(update.client_id.clone(), update.clone());
+        }
+    }
+
+    /// Updates the position with the fill
+    ///
+    /// Until the order update of the fill arrives the filled size is also
+    /// counted as open, overstating the exposure.
+    pub fn on_fill(&mut self, fill: &Fill) {
+        *self.positions.entry(fill.market.clone()).or_default() += fill.side.signed(fill.size);
+        self.prices.insert(fill.market.clone(), fill.price);
+    }
+
+    /// Returns filled position on the market
+    pub fn position(&self, market: &str) -> f64 {
+        self.positions.get(market).copied().unwrap_or_default()
+    }
+
+    /// Returns market orders closing all filled positions
+    pub fn closing_orders(&self) -> Vec<(Box<str>, Side, f64)> {
+        self.positions
+            .iter()
+            .filter(|(_, position)| position.abs() > f64::EPSILON)
+            .map(|(market, position)| {
+                let side = match *position > 0.0 {
+                    true => Side::Sell,
+                    false => Side::Buy,
+                };
+                (market.clone(), side, position.abs())
+            })
+            .collect()
+    }
+
+    /// Returns whether the order only reduces the filled position
+    fn reduces_position(&self, order: &OrderRequest) -> bool {
+        let position = self.position(&order.market);
+        let after = position + order.side.signed(order.size);
+
+        after.abs() < position.abs() && after * position >= 0.0
+    }
+
+    /// Returns position on the market if all open orders on the given side
+    /// got filled
+    fn exposure(&self, market: &str, side: Side) -> f64 {
+        self.open_orders
+            .values()
+            .filter(|order| &*order.market == market && order.side == side)
+            .fold(self.position(market), |position, order| {
+                position + side.signed(order.remaining_size())
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order_engine::OrderStatus;
+
+    fn fill(side: Side, size: f64) -> Fill {
+        Fill {
+            client_id: None,
+            exchange_id: Box::from("1"),
+            market: Box::from("BTC-PERP"),
+            side,
+            price: 100.0,
+            size,
+            fee: 0.0,
+            time: 0,
+        }
+    }
+
+    #[test]
+    fn test_order_size_limit() {
+        let mut risk = RiskEngine::new(RiskConfig {
+            max_order_size: Some(1.0),
+            ..Default::default()
+        });
+        let now = Instant::now();
+
+        assert!(risk
+            .check_order(
+                &OrderRequest::limit("1", "BTC-PERP", Side::Buy, 100.0, 1.0),
+                now
+            )
+            .is_ok());
+        assert_eq!(
+            Err(RiskRejection::OrderSize {
+                size: 2.0,
+                limit: 1.0
+            }),
+            risk.check_order(
+                &OrderRequest::limit("2", "BTC-PERP", Side::Buy, 100.0, 2.0),
+                now
+            )
+        );
+
+        // Limits pushed by the server replace the configured ones
+        risk.set_config(
+            RiskLimits {
+                max_order_size: Some(2.0),
+                ..Default::default()
+            }
+            .into(),
+        );
+        assert!(risk
+            .check_order(
+                &OrderRequest::limit("3", "BTC-PERP", Side::Buy, 100.0, 2.0),
+                now
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_position_limit() {
+        let mut risk = RiskEngine::new(RiskConfig {
+            max_position: Some(2.0),
+            ..Default::default()
+        });
+        let now = Instant::now();
+        risk.on_fill(&fill(Side::Buy, 1.0));
+        risk.on_order_update(&OrderUpdate {
+            client_id: Box::from("1"),
+            exchange_id: None,
+            market: Box::from("BTC-PERP"),
+            side: Side::Buy,
+            price: Some(100.0),
+            size: 0.5,
+            filled_size: 0.0,
+            status: OrderStatus::Open,
+            reason: None,
+        });
+
+        assert!(matches!(
+            risk.check_order(
+                &OrderRequest::limit("2", "BTC-PERP", Side::Buy, 100.0, 1.0),
+                now
+            ),
+            Err(RiskRejection::Position { .. })
+        ));
+        // Selling flips the position to -2.0, right at the limit
+        assert!(risk
+            .check_order(
+                &OrderRequest::limit("3", "BTC-PERP", Side::Sell, 100.0, 3.0),
+                now
+            )
+            .is_ok());
+        // Flipped past the limit on the short side
+        assert!(matches!(
+            risk.check_order(
+                &OrderRequest::limit("4", "BTC-PERP", Side::Sell, 100.0, 3.5),
+                now
+            ),
+            Err(RiskRejection::Position { .. })
+        ));
+    }
+
+    #[test]
+    fn test_notional_limit() {
+        let mut risk = RiskEngine::new(RiskConfig {
+            max_notional_per_minute: Some(250.0),
+            ..Default::default()
+        });
+        let now = Instant::now();
+
+        assert_eq!(
+            Err(RiskRejection::NoPrice(Box::from("BTC-PERP"))),
+            risk.check_order(&OrderRequest::market("1", "BTC-PERP", Side::Buy, 1.0), now)
+        );
+        for id in ["2", "3"] {
+            assert!(risk
+                .check_order(
+                    &OrderRequest::limit(id, "BTC-PERP", Side::Buy, 100.0, 1.0),
+                    now
+                )
+                .is_ok());
+        }
+        assert!(matches!(
+            risk.check_order(&OrderRequest::market("4", "BTC-PERP", Side::Sell, 1.0), now),
+            Err(RiskRejection::Notional { .. })
+        ));
+        assert!(risk
+            .check_order(
+                &OrderRequest::market("5", "BTC-PERP", Side::Sell, 1.0),
+                now + NOTIONAL_WINDOW
+            )
+            .is_ok());
+    }
+
+    #[test]
+    fn test_closing_orders() {
+        let mut risk = RiskEngine::default();
+        risk.on_fill(&fill(Side::Buy, 2.0));
+
+        assert_eq!(
+            vec![(Box::<str>::from("BTC-PERP"), Side::Sell, 2.0)],
+            risk.closing_orders()
+        );
+        // Orders flipping or growing the position don't reduce it
+        for (side, size, reduces) in [
+            (Side::Sell, 2.0, true),
+            (Side::Sell, 1.0, true),
+            (Side::Sell, 3.0, false),
+            (Side::Buy, 1.0, false),
+        ] {
+            let order = OrderRequest::market("1", "BTC-PERP", side, size);
+            assert_eq!(reduces, risk.reduces_position(&order));
+        }
+
+        risk.on_fill(&fill(Side::Sell, 2.0));
+        assert!(risk.closing_orders().is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+//! Engine runtime
+//!
+//! On Linux every engine runs on a glommio executor pinned to its CPU core.
+//! glommio needs `io_uring`, so other platforms and builds without the
+//! `io-uring` feature get a fallback running each executor on a plain thread
+//! with an `async-executor` and `async-std` networking. CPU pinning is a
+//! no-op there. The fallback makes botnode usable on macOS and Windows for
+//! development and backtesting and it is not meant for latency-sensitive
+//! trading.
+//!
+//! Engines only use the runtime through this module, both backends expose
+//! the same API:
+//!
+//! * [`spsc_queue`] channels between engines
+//! * [`timer::sleep`] and [`timer::timeout`]
+//! * [`net::TcpStream`] and [`net::TcpListener`]
+//! * [`spawn_executor`], [`spawn_local`] and [`block_on`]
+//!
+//! Engine executors are tuned per engine by [`ExecutorConfig`].
+
+#[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+mod fallback;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod linux;
+
+#[cfg(not(all(target_os = "linux", feature = "io-uring")))]
+pub use fallback::*;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub use linux::*;
+
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// Spin duration of busy-polling executors, long enough to never park
+const BUSY_POLL_SPIN: Duration = Duration::from_secs(u32::MAX as u64);
+
+/// Executor settings of an engine
+///
+/// Latency-sensitive engines can spin longer or busy-poll, keeping their core
+/// at 100%, while auxiliary engines park right away. The fallback runtime
+/// ignores the settings.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExecutorConfig {
+    /// How long the idle executor spins before parking its thread
+    pub spin_before_park_us: u64,
+    /// Spins without ever parking, overrides `spin_before_park_us`
+    pub busy_poll: bool,
+    /// Entries of the io_uring submission rings, glommio's default when not
+    /// set
+    pub ring_depth: Option<usize>,
+    /// CPU shares of the task queue the engine runs in
+    pub shares: usize,
+    /// Latency the engine's task queue needs, other queues of the executor
+    /// are preempted to meet it
+    pub latency_ms: Option<u64>,
+}
+
+impl Default for ExecutorConfig {
+    fn default() -> Self {
+        Self {
+            spin_before_park_us: 250,
+            busy_poll: false,
+            ring_depth: None,
+            shares: 1000,
+            latency_ms: None,
+        }
+    }
+}
+
+impl ExecutorConfig {
+    /// Returns how long the idle executor spins before parking
+    pub fn spin_before_park(&self) -> Duration {
+        match self.busy_poll {
+            true => BUSY_POLL_SPIN,
+            false => Duration::from_micros(self.spin_before_park_us),
+        }
+    }
+
+    /// Returns latency requirement of the engine's task queue
+    pub fn latency(&self) -> Option<Duration> {
+        self.latency_ms.map(Duration::from_millis)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        // IORING_MAX_ENTRIES
+        if matches!(self.ring_depth, Some(depth) if depth == 0 || depth > 32768) {
+            return Err("ring_depth must be between 1 and 32768".to_string());
+        }
+        if self.shares == 0 {
+            return Err("shares must be positive".to_string());
+        }
+        if self.latency_ms == Some(0) {
+            return Err("latency_ms must be positive".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Core and settings of the executor an engine is spawned onto
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EngineExecutor {
+    pub cpu: usize,
+    pub config: ExecutorConfig,
+}
+
+impl EngineExecutor {
+    pub fn new(cpu: usize, config: ExecutorConfig) -> Self {
+        Self { cpu, config }
+    }
+}
+
+/// Executor pinned to the core with the default settings
+impl From<usize> for EngineExecutor {
+    fn from(cpu: usize) -> Self {
+        Self::new(cpu, ExecutorConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_executor_config() {
+        let config: ExecutorConfig = toml::from_str("busy_poll = true\nring_depth = 256").unwrap();
+
+        assert_eq!(BUSY_POLL_SPIN, config.spin_before_park());
+        assert_eq!(Some(256), config.ring_depth);
+        assert_eq!(1000, config.shares);
+        assert!(config.validate().is_ok());
+
+        let config = ExecutorConfig::default();
+        assert_eq!(Duration::from_micros(250), config.spin_before_park());
+        assert!(ExecutorConfig {
+            ring_depth: Some(0),
+            ..config
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,298 @@
+//! Thread-based runtime for platforms without glommio
+
+use std::{future::Future, thread};
+
+use async_executor::LocalExecutor;
+
+use super::EngineExecutor;
+use crate::error::StartEngineError;
+
+thread_local! {
+    /// Executor of the current thread, run by [`block_on`]
+    static EXECUTOR: LocalExecutor<'static> = LocalExecutor::new();
+}
+
+/// Handle of a spawned executor
+pub type ExecutorJoinHandle = thread::JoinHandle<()>;
+
+/// Spawns new executor thread running the future produced by `future`
+///
+/// CPU pinning and executor tuning aren't supported, the core and settings
+/// of `executor` are ignored.
+pub fn spawn_executor<G, F>(
+    name: &str,
+    executor: Option<EngineExecutor>,
+    future: G,
+) -> Result<ExecutorJoinHandle, StartEngineError>
+where
+    G: FnOnce() -> F + Send + 'static,
+    F: Future<Output = ()> + 'static,
+{
+    if let Some(executor) = executor {
+        tracing::debug!(
+            "CPU pinning is not supported, {name} ignores CPU {}",
+            executor.cpu
+        );
+    }
+
+    thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || block_on(future()))
+        .map_err(|e| StartEngineError {
+            source: Box::new(e),
+        })
+}
+
+/// Spawns detached task onto the current thread's executor
+pub fn spawn_local<F: Future<Output = ()> + 'static>(future: F) {
+    EXECUTOR.with(|ex| ex.spawn(future).detach());
+}
+
+/// Runs the future to completion on the current thread's executor
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    EXECUTOR.with(|ex| async_io::block_on(ex.run(future)))
+}
+
+/// Bounded single-producer single-consumer queue
+///
+/// Mirrors the API of glommio's `spsc_queue`.
+pub mod spsc_queue {
+    use std::{
+        fmt,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+    };
+
+    use crossbeam_queue::ArrayQueue;
+
+    struct Buffer<T> {
+        queue: ArrayQueue<T>,
+        producer_disconnected: AtomicBool,
+        consumer_disconnected: AtomicBool,
+    }
+
+    /// Sending half of the queue
+    pub struct Producer<T> {
+        buffer: Arc<Buffer<T>>,
+    }
+
+    /// Receiving half of the queue, disconnects when the last clone is
+    /// dropped
+    pub struct Consumer<T> {
+        handle: Arc<ConsumerHandle<T>>,
+    }
+
+    struct ConsumerHandle<T> {
+        buffer: Arc<Buffer<T>>,
+    }
+
+    /// Creates queue holding up to `capacity` values
+    pub fn make<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+        let buffer = Arc::new(Buffer {
+            queue: ArrayQueue::new(capacity.max(1)),
+            producer_disconnected: AtomicBool::new(false),
+            consumer_disconnected: AtomicBool::new(false),
+        });
+
+        (
+            Producer {
+                buffer: buffer.clone(),
+            },
+            Consumer {
+                handle: Arc::new(ConsumerHandle { buffer }),
+            },
+        )
+    }
+
+    impl<T> Producer<T> {
+        /// Pushes the value, returning it back when the queue is full or the
+        /// consumer is gone
+        pub fn try_push(&self, value: T) -> Option<T> {
+            if self.consumer_disconnected() {
+                return Some(value);
+            }
+
+            self.buffer.queue.push(value).err()
+        }
+
+        pub fn consumer_disconnected(&self) -> bool {
+            self.buffer.consumer_disconnected.load(Ordering::Acquire)
+        }
+
+        /// Returns number of values in the queue
+        pub fn size(&self) -> usize {
+            self.buffer.queue.len()
+        }
+
+        /// Returns capacity of the queue
+        pub fn buffer_size(&self) -> usize {
+            self.buffer.queue.capacity()
+        }
+    }
+
+    impl<T> Consumer<T> {
+        /// Pops the oldest value
+        pub fn try_pop(&self) -> Option<T> {
+            self.handle.buffer.queue.pop()
+        }
+
+        /// Disconnects the consumer, pushes fail from then on
+        ///
+        /// Returns whether the consumer was already disconnected
+        pub fn disconnect(&self) -> bool {
+            self.handle
+                .buffer
+                .consumer_disconnected
+                .swap(true, Ordering::Release)
+        }
+
+        pub fn producer_disconnected(&self) -> bool {
+            self.handle
+                .buffer
+                .producer_disconnected
+                .load(Ordering::Acquire)
+        }
+
+        /// Returns number of values in the queue
+        pub fn size(&self) -> usize {
+            self.handle.buffer.queue.len()
+        }
+
+        /// Returns capacity of the queue
+        pub fn buffer_size(&self) -> usize {
+            self.handle.buffer.queue.capacity()
+        }
+    }
+
+    impl<T> Clone for Consumer<T> {
+        fn clone(&self) -> Self {
+            Self {
+                handle: self.handle.clone(),
+            }
+        }
+    }
+
+    impl<T> Drop for Producer<T> {
+        fn drop(&mut self) {
+            self.buffer
+                .producer_disconnected
+                .store(true, Ordering::Release);
+        }
+    }
+
+    impl<T> Drop for ConsumerHandle<T> {
+        fn drop(&mut self) {
+            self.buffer
+                .consumer_disconnected
+                .store(true, Ordering::Release);
+        }
+    }
+
+    impl<T> fmt::Debug for Producer<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("Producer")
+                .field("size", &self.size())
+                .field("buffer_size", &self.buffer_size())
+                .finish()
+        }
+    }
+
+    impl<T> fmt::Debug for Consumer<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("Consumer")
+                .field("size", &self.size())
+                .field("buffer_size", &self.buffer_size())
+                .finish()
+        }
+    }
+}
+
+/// Timers
+pub mod timer {
+    use std::{future::Future, io, time::Duration};
+
+    use futures_lite::FutureExt;
+
+    /// Sleeps for the given duration
+    pub async fn sleep(wait: Duration) {
+        async_io::Timer::after(wait).await;
+    }
+
+    /// Awaits the future for at most `dur`, failing with
+    /// [`io::ErrorKind::TimedOut`] when it doesn't finish in time
+    pub async fn timeout<F, T>(dur: Duration, future: F) -> io::Result<T>
+    where
+        F: Future<Output = io::Result<T>>,
+    {
+        future
+            .or(async {
+                sleep(dur).await;
+                Err(io::ErrorKind::TimedOut.into())
+            })
+            .await
+    }
+}
+
+/// TCP networking
+pub mod net {
+    use std::io;
+
+    pub use async_std::net::TcpStream;
+
+    /// TCP listener bound synchronously like glommio's
+    pub struct TcpListener {
+        listener: async_std::net::TcpListener,
+    }
+
+    impl TcpListener {
+        pub fn bind<A: std::net::ToSocketAddrs>(addr: A) -> io::Result<Self> {
+            let listener = std::net::TcpListener::bind(addr)?;
+
+            Ok(Self {
+                listener: listener.into(),
+            })
+        }
+
+        /// Accepts new incoming connection
+        pub async fn accept(&self) -> io::Result<TcpStream> {
+            self.listener.accept().await.map(|(stream, _addr)| stream)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spsc_queue_disconnect() {
+        let (tx, rx) = spsc_queue::make(1);
+
+        assert_eq!(None, tx.try_push(1));
+        assert_eq!(Some(2), tx.try_push(2));
+        assert_eq!(Some(1), rx.try_pop());
+
+        let rx2 = rx.clone();
+        drop(rx);
+        assert!(!tx.consumer_disconnected());
+        drop(rx2);
+        assert!(tx.consumer_disconnected());
+        assert_eq!(Some(3), tx.try_push(3));
+    }
+
+    #[test]
+    fn test_spawn_executor() {
+        let (tx, rx) = spsc_queue::make(1);
+        spawn_executor("test-executor", Some(0.into()), move || async move {
+            timer::sleep(std::time::Duration::from_millis(1)).await;
+            tx.try_push(1);
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+
+        assert_eq!(Some(1), rx.try_pop());
+    }
+}
@@ -0,0 +1,74 @@
+//! glommio runtime
+
+use std::future::Future;
+
+pub use glommio::{channels::spsc_queue, net, timer};
+use glommio::{Latency, LocalExecutor, LocalExecutorBuilder, Placement, Shares};
+
+use super::EngineExecutor;
+use crate::error::StartEngineError;
+
+/// Handle of a spawned executor
+pub type ExecutorJoinHandle = glommio::ExecutorJoinHandle<()>;
+
+/// Spawns new executor thread running the future produced by `future`
+///
+/// Engine executors are pinned to their core, tuned by their settings and
+/// run the future in a task queue with the configured shares and latency.
+/// Executors of other tasks are unbound and park immediately.
+pub fn spawn_executor<G, F>(
+    name: &str,
+    executor: Option<EngineExecutor>,
+    future: G,
+) -> Result<ExecutorJoinHandle, StartEngineError>
+where
+    G: FnOnce() -> F + Send + 'static,
+    F: Future<Output = ()> + 'static,
+{
+    let executor = match executor {
+        Some(executor) => executor,
+        None => {
+            return LocalExecutorBuilder::new(Placement::Unbound)
+                .name(name)
+                .spawn(future)
+                .map_err(StartEngineError::from)
+        }
+    };
+
+    let config = executor.config;
+    let mut builder = LocalExecutorBuilder::new(Placement::Fixed(executor.cpu))
+        .spin_before_park(config.spin_before_park());
+    if let Some(ring_depth) = config.ring_depth {
+        builder = builder.ring_depth(ring_depth);
+    }
+    let queue = name.to_string();
+
+    builder
+        .name(name)
+        .spawn(move || async move {
+            let latency = match config.latency() {
+                Some(latency) => Latency::Matters(latency),
+                None => Latency::NotImportant,
+            };
+            let task_queue = glommio::executor().create_task_queue(
+                Shares::Static(config.shares),
+                latency,
+                &queue,
+            );
+
+            glommio::spawn_local_into(future(), task_queue)
+                .expect("task queue created on this executor")
+                .await
+        })
+        .map_err(StartEngineError::from)
+}
+
+/// Spawns detached task onto the current executor
+pub fn spawn_local<F: Future<Output = ()> + 'static>(future: F) {
+    glommio::spawn_local(future).detach();
+}
+
+/// Runs the future to completion on a new executor on the current thread
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    LocalExecutor::default().run(future)
+}
@@ -0,0 +1,154 @@
+//! Secrets providers
+//!
+//! API keys and other credentials are looked up by dotted key (e.g.
+//! `ftx.api_secret`) from the provider selected by the `[secrets]`
+//! configuration section:
+//!
+//! * `env` reads `BOTNODE_SECRET_<KEY>` variables, dots become `__`
+//! * `file` decrypts a TOML file encrypted with `botnode encrypt-secrets`
+//! * `vault` reads a HashiCorp Vault KV v2 secret per key prefix
+
+pub mod env;
+pub mod file;
+pub mod vault;
+
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::prelude::*;
+
+/// Secret value that is never printed
+#[derive(Clone, PartialEq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new<T: ToString>(value: T) -> Self {
+        Self(value.to_string())
+    }
+
+    /// Returns the secret value
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+/// Source of secrets
+#[async_trait(?Send)]
+pub trait SecretsProvider {
+    /// Returns the name of the provider
+    fn name(&self) -> &'static str;
+
+    /// Returns the secret stored under the key, `None` if there is none
+    async fn get(&self, key: &str) -> Result<Option<Secret>, SecretsError>;
+
+    /// Returns the secret stored under the key, failing if there is none
+    async fn require(&self, key: &str) -> Result<Secret, SecretsError> {
+        self.get(key)
+            .await?
+            .ok_or_else(|| SecretsError::new(format!("secret {key} not found in {}", self.name())))
+    }
+}
+
+/// Secrets provider configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "provider", rename_all = "lowercase")]
+pub enum SecretsConfig {
+    Env {
+        #[serde(default = "default_env_prefix")]
+        prefix: String,
+    },
+    File {
+        path: PathBuf,
+    },
+    Vault {
+        /// Vault address, e.g. `https://vault.example.com:8200`
+        addr: String,
+        /// Mount point of the KV v2 engine
+        #[serde(default = "default_vault_mount")]
+        mount: String,
+        /// Path under the mount that secrets of this bot are stored in
+        #[serde(default = "default_vault_path")]
+        path: String,
+    },
+}
+
+impl Default for SecretsConfig {
+    fn default() -> Self {
+        SecretsConfig::Env {
+            prefix: default_env_prefix(),
+        }
+    }
+}
+
+fn default_env_prefix() -> String {
+    env::DEFAULT_PREFIX.to_string()
+}
+
+fn default_vault_mount() -> String {
+    "secret".to_string()
+}
+
+fn default_vault_path() -> String {
+    "botnode".to_string()
+}
+
+/// Creates the configured secrets provider
+///
+/// The passphrase of encrypted files is read from `BOTNODE_SECRETS_PASSPHRASE`
+/// and the Vault token from `VAULT_TOKEN`.
+pub fn provider(config: &SecretsConfig) -> Result<Box<dyn SecretsProvider>, SecretsError> {
+    match config {
+        SecretsConfig::Env { prefix } => Ok(Box::new(env::EnvSecrets::new(prefix))),
+        SecretsConfig::File { path } => {
+            let passphrase = std::env::var(file::PASSPHRASE_ENV)
+                .map_err(|_| SecretsError::new(format!("{} is not set", file::PASSPHRASE_ENV)))?;
+            Ok(Box::new(file::FileSecrets::load(path, &passphrase)?))
+        }
+        SecretsConfig::Vault { addr, mount, path } => {
+            let token = std::env::var(vault::TOKEN_ENV)
+                .map_err(|_| SecretsError::new(format!("{} is not set", vault::TOKEN_ENV)))?;
+            Ok(Box::new(vault::VaultSecrets::new(addr, mount, path, token)))
+        }
+    }
+}
+
+/// Error looking up a secret
+#[derive(Debug, thiserror::Error)]
+#[error("Secrets error: {source}")]
+pub struct SecretsError {
+    pub source: Box<dyn std::error::Error>,
+}
+
+impl SecretsError {
+    pub fn new<T: ToString>(reason: T) -> Self {
+        Self {
+            source: reason.to_string().into(),
+        }
+    }
+
+    pub fn with_source(err: impl std::error::Error + 'static) -> Self {
+        Self {
+            source: Box::new(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_debug_redacted() {
+        let secret = Secret::new("hunter2");
+
+        assert_eq!("Secret(***)", format!("{secret:?}"));
+        assert_eq!("hunter2", secret.expose());
+    }
+}
@@ -0,0 +1,58 @@
+//! Secrets from environment variables
+
+use super::{Secret, SecretsError, SecretsProvider};
+use crate::prelude::*;
+
+/// Prefix of the variables holding secrets
+pub const DEFAULT_PREFIX: &str = "BOTNODE_SECRET_";
+
+/// Reads secret `ftx.api_key` from `BOTNODE_SECRET_FTX__API_KEY`
+#[derive(Debug)]
+pub struct EnvSecrets {
+    prefix: String,
+}
+
+impl EnvSecrets {
+    pub fn new<T: ToString>(prefix: T) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+        }
+    }
+
+    /// Returns variable name of the key
+    pub fn var_name(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key.replace('.', "__").to_uppercase())
+    }
+}
+
+#[async_trait(?Send)]
+impl SecretsProvider for EnvSecrets {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Secret>, SecretsError> {
+        match std::env::var(self.var_name(key)) {
+            Ok(value) => Ok(Some(Secret::new(value))),
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(e) => Err(SecretsError::with_source(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_secrets() {
+        let secrets = EnvSecrets::new("BOTNODE_TEST_SECRET_");
+        std::env::set_var("BOTNODE_TEST_SECRET_FTX__API_KEY", "key");
+
+        assert_eq!(
+            Some(Secret::new("key")),
+            smol::block_on(secrets.get("ftx.api_key")).unwrap()
+        );
+        assert_eq!(None, smol::block_on(secrets.get("ftx.missing")).unwrap());
+    }
+}
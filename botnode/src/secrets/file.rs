@@ -0,0 +1,127 @@
+//! Secrets from an encrypted file
+//!
+//! The file holds a flat TOML table of secrets keyed by the dotted secret
+//! key, encrypted with AES-256-GCM under a key derived from a passphrase with
+//! PBKDF2-HMAC-SHA256. Layout: 16 bytes salt, 12 bytes nonce, ciphertext
+//! with the tag appended.
+
+use std::{collections::HashMap, num::NonZeroU32, path::Path};
+
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    pbkdf2,
+    rand::{SecureRandom, SystemRandom},
+};
+
+use super::{Secret, SecretsError, SecretsProvider};
+use crate::prelude::*;
+
+/// Variable holding the passphrase of the secrets file
+pub const PASSPHRASE_ENV: &str = "BOTNODE_SECRETS_PASSPHRASE";
+
+const SALT_LEN: usize = 16;
+const PBKDF2_ITERATIONS: u32 = 100_000;
+
+/// Secrets decrypted from a file at startup
+pub struct FileSecrets {
+    secrets: HashMap<String, String>,
+}
+
+impl FileSecrets {
+    /// Loads and decrypts the secrets file
+    pub fn load<P: AsRef<Path>>(path: P, passphrase: &str) -> Result<Self, SecretsError> {
+        let data = std::fs::read(path).map_err(SecretsError::with_source)?;
+        Self::decrypt(&data, passphrase)
+    }
+
+    /// Decrypts secrets from the file contents
+    pub fn decrypt(data: &[u8], passphrase: &str) -> Result<Self, SecretsError> {
+        if data.len() < SALT_LEN + NONCE_LEN {
+            return Err(SecretsError::new("secrets file is truncated"));
+        }
+        let (salt, rest) = data.split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+        let key = derive_key(passphrase, salt)?;
+        let nonce = Nonce::try_assume_unique_for_key(nonce)
+            .map_err(|_| SecretsError::new("invalid nonce"))?;
+        let mut in_out = ciphertext.to_vec();
+        let plaintext = key
+            .open_in_place(nonce, Aad::empty(), &mut in_out)
+            .map_err(|_| SecretsError::new("wrong passphrase or corrupted secrets file"))?;
+
+        let plaintext = std::str::from_utf8(plaintext).map_err(SecretsError::with_source)?;
+        let secrets = toml::from_str(plaintext).map_err(SecretsError::with_source)?;
+
+        Ok(Self { secrets })
+    }
+}
+
+#[async_trait(?Send)]
+impl SecretsProvider for FileSecrets {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Secret>, SecretsError> {
+        Ok(self.secrets.get(key).map(Secret::new))
+    }
+}
+
+/// Encrypts TOML table of secrets with the passphrase
+pub fn encrypt(plaintext: &str, passphrase: &str) -> Result<Vec<u8>, SecretsError> {
+    toml::from_str::<HashMap<String, String>>(plaintext).map_err(SecretsError::with_source)?;
+
+    let rng = SystemRandom::new();
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill(&mut salt)
+        .and_then(|_| rng.fill(&mut nonce))
+        .map_err(|_| SecretsError::new("failed to generate random bytes"))?;
+
+    let key = derive_key(passphrase, &salt)?;
+    let mut in_out = plaintext.as_bytes().to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::empty(),
+        &mut in_out,
+    )
+    .map_err(|_| SecretsError::new("failed to encrypt secrets"))?;
+
+    let mut data = Vec::with_capacity(SALT_LEN + NONCE_LEN + in_out.len());
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&in_out);
+    Ok(data)
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Result<LessSafeKey, SecretsError> {
+    let mut key = [0u8; 32];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        NonZeroU32::new(PBKDF2_ITERATIONS).unwrap(),
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    let key = UnboundKey::new(&AES_256_GCM, &key).map_err(|_| SecretsError::new("invalid key"))?;
+
+    Ok(LessSafeKey::new(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_decrypt_roundtrip() {
+        let data = encrypt("\"ftx.api_key\" = \"key\"\n", "passphrase").unwrap();
+        let secrets = FileSecrets::decrypt(&data, "passphrase").unwrap();
+
+        assert_eq!(
+            Some(Secret::new("key")),
+            smol::block_on(secrets.get("ftx.api_key")).unwrap()
+        );
+        assert!(FileSecrets::decrypt(&data, "wrong").is_err());
+    }
+}
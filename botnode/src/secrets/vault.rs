@@ -0,0 +1,93 @@
+//! Secrets from HashiCorp Vault
+//!
+//! Secret `ftx.api_key` is the `api_key` field of the KV v2 secret
+//! `<path>/ftx`, read over the Vault HTTP API.
+
+use serde_json::Value;
+
+use super::{Secret, SecretsError, SecretsProvider};
+use crate::prelude::*;
+
+/// Variable holding the Vault token
+pub const TOKEN_ENV: &str = "VAULT_TOKEN";
+
+/// Reads secrets from Vault KV v2 engine
+pub struct VaultSecrets {
+    addr: String,
+    mount: String,
+    path: String,
+    token: String,
+}
+
+impl VaultSecrets {
+    pub fn new<A: ToString, M: ToString, P: ToString>(
+        addr: A,
+        mount: M,
+        path: P,
+        token: String,
+    ) -> Self {
+        Self {
+            addr: addr.to_string().trim_end_matches('/').to_string(),
+            mount: mount.to_string(),
+            path: path.to_string(),
+            token,
+        }
+    }
+
+    /// Returns URL of the secret and its field holding the key
+    pub fn locate<'a>(&self, key: &'a str) -> (String, &'a str) {
+        let (secret, field) = match key.rsplit_once('.') {
+            Some((secret, field)) => (format!("{}/{secret}", self.path), field),
+            None => (self.path.clone(), key),
+        };
+
+        (
+            format!("{}/v1/{}/data/{secret}", self.addr, self.mount),
+            field,
+        )
+    }
+}
+
+#[async_trait(?Send)]
+impl SecretsProvider for VaultSecrets {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Secret>, SecretsError> {
+        let (url, field) = self.locate(key);
+        let mut res = surf::get(&url)
+            .header("X-Vault-Token", self.token.as_str())
+            .await
+            .map_err(SecretsError::new)?;
+
+        match res.status() {
+            surf::StatusCode::NotFound => return Ok(None),
+            status if !status.is_success() => {
+                return Err(SecretsError::new(format!("Vault returned {status}")))
+            }
+            _ => {}
+        }
+
+        let body: Value = res.body_json().await.map_err(SecretsError::new)?;
+        Ok(body["data"]["data"][field].as_str().map(Secret::new))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vault_locate() {
+        let vault = VaultSecrets::new("https://vault:8200/", "secret", "botnode", String::new());
+
+        assert_eq!(
+            (
+                "https://vault:8200/v1/secret/data/botnode/ftx".to_string(),
+                "api_key"
+            ),
+            vault.locate("ftx.api_key")
+        );
+    }
+}
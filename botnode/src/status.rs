@@ -0,0 +1,198 @@
+//! Bot status snapshot
+//!
+//! The control engine keeps a process-wide snapshot of engine states, server
+//! connectivity, latest orderbooks, positions and open orders. Local
+//! inspection servers running outside of the engine cores read it instead of
+//! talking to the engines.
+
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::RwLock,
+    time::SystemTime,
+};
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use botvana::{exchange::ExchangeId, market::orderbook::Orderbook};
+
+use crate::{engine::EngineStatus, trading::strategy::Side};
+
+/// Number of most recent fills kept in the snapshot
+pub const RECENT_FILLS: usize = 256;
+
+static STATUS: Lazy<RwLock<BotStatus>> = Lazy::new(|| RwLock::new(BotStatus::default()));
+
+/// Snapshot of the bot state
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BotStatus {
+    pub bot_id: u16,
+    pub dry_run: bool,
+    /// Bot is connected to botvana-server
+    pub server_connected: bool,
+    /// Reason botvana-server rejected the authentication of the bot
+    pub auth_rejected: Option<String>,
+    /// Engines have been spawned after receiving the configuration
+    pub engines_spawned: bool,
+    /// Latest status of each engine by engine name
+    pub engines: BTreeMap<String, EngineState>,
+    pub positions: Vec<PositionSummary>,
+    pub open_orders: Vec<OpenOrder>,
+    /// Most recent fills, oldest first
+    pub fills: VecDeque<Fill>,
+    /// Sequence number of the last recorded fill
+    pub fill_seq: u64,
+    /// Latest orderbook of each market
+    #[serde(skip)]
+    pub orderbooks: HashMap<(ExchangeId, Box<str>), Orderbook<f64>>,
+}
+
+/// Latest reported status of an engine
+#[derive(Clone, Debug, Serialize)]
+pub struct EngineState {
+    pub status: EngineStatus,
+    /// Time of the report in milliseconds since UNIX epoch
+    pub updated_at: u64,
+}
+
+/// Position in single market
+#[derive(Clone, Debug, Serialize)]
+pub struct PositionSummary {
+    pub exchange: ExchangeId,
+    pub market: Box<str>,
+    /// Signed position size, negative when short
+    pub size: f64,
+    pub avg_entry_price: f64,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+}
+
+/// Order resting on an exchange
+#[derive(Clone, Debug, Serialize)]
+pub struct OpenOrder {
+    pub id: String,
+    pub exchange: ExchangeId,
+    pub market: Box<str>,
+    pub side: Side,
+    pub price: f64,
+    pub size: f64,
+    pub filled_size: f64,
+}
+
+/// Execution of an order
+#[derive(Clone, Debug, Serialize)]
+pub struct Fill {
+    /// Sequence number assigned when the fill is recorded
+    pub seq: u64,
+    pub order_id: String,
+    pub exchange: ExchangeId,
+    pub market: Box<str>,
+    pub side: Side,
+    pub price: f64,
+    pub size: f64,
+    /// Time of the fill in milliseconds since UNIX epoch
+    pub time: u64,
+}
+
+impl BotStatus {
+    /// Returns whether all engines are running
+    pub fn ready(&self) -> bool {
+        self.server_connected
+            && self.engines_spawned
+            && self
+                .engines
+                .values()
+                .all(|engine| matches!(engine.status, EngineStatus::Running))
+    }
+
+    /// Returns realized and unrealized PnL summed over all positions
+    pub fn pnl(&self) -> (f64, f64) {
+        self.positions
+            .iter()
+            .fold((0.0, 0.0), |(realized, unrealized), p| {
+                (realized + p.realized_pnl, unrealized + p.unrealized_pnl)
+            })
+    }
+
+    /// Records the fill, assigning it the next sequence number
+    pub fn record_fill(&mut self, mut fill: Fill) {
+        self.fill_seq += 1;
+        fill.seq = self.fill_seq;
+
+        if self.fills.len() == RECENT_FILLS {
+            self.fills.pop_front();
+        }
+        self.fills.push_back(fill);
+    }
+
+    /// Returns fills recorded after the given sequence number
+    pub fn fills_since(&self, seq: u64) -> impl Iterator<Item = &Fill> {
+        self.fills.iter().filter(move |fill| fill.seq > seq)
+    }
+
+    /// Records engine status reported now
+    pub fn set_engine_status(&mut self, engine: String, status: EngineStatus) {
+        let updated_at = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        self.engines
+            .insert(engine, EngineState { status, updated_at });
+    }
+}
+
+/// Modifies the process-wide status
+pub fn update<R>(f: impl FnOnce(&mut BotStatus) -> R) -> R {
+    let mut status = STATUS.write().unwrap_or_else(|e| e.into_inner());
+    f(&mut status)
+}
+
+/// Reads the process-wide status
+pub fn read<R>(f: impl FnOnce(&BotStatus) -> R) -> R {
+    let status = STATUS.read().unwrap_or_else(|e| e.into_inner());
+    f(&status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bot_status_ready() {
+        let mut status = BotStatus {
+            server_connected: true,
+            engines_spawned: true,
+            ..BotStatus::default()
+        };
+        status.set_engine_status("trading-engine".to_string(), EngineStatus::Booting);
+        assert!(!status.ready());
+
+        status.set_engine_status("trading-engine".to_string(), EngineStatus::Running);
+        assert!(status.ready());
+
+        status.server_connected = false;
+        assert!(!status.ready());
+    }
+
+    #[test]
+    fn test_bot_status_recent_fills() {
+        let mut status = BotStatus::default();
+        for _ in 0..RECENT_FILLS + 2 {
+            status.record_fill(Fill {
+                seq: 0,
+                order_id: "1".to_string(),
+                exchange: ExchangeId::Ftx,
+                market: Box::from("BTC-PERP"),
+                side: Side::Buy,
+                price: 100.0,
+                size: 0.1,
+                time: 0,
+            });
+        }
+
+        assert_eq!(RECENT_FILLS, status.fills.len());
+        assert_eq!(3, status.fills[0].seq);
+        assert_eq!(2, status.fills_since(status.fill_seq - 2).count());
+    }
+}
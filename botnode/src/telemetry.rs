@@ -0,0 +1,304 @@
+//! Tracing and telemetry export setup
+//!
+//! Logs always go to stdout, either as human readable text or as one JSON
+//! object per line. When built with the `otel` feature and
+//! `OTEL_EXPORTER_OTLP_ENDPOINT` is set, spans and published metrics are
+//! additionally exported over OTLP.
+//!
+//! Every `pipeline_sample_every`-th websocket message is traced through the
+//! whole event pipeline: a `tick` span opened when the message is received
+//! carries the `exchange` and `market` and has `parse`, `orderbook_update`,
+//! `strategy` and `order_send` children recorded by the engines the event
+//! passes through, giving a tick-to-trade breakdown per market.
+
+use std::{
+    str::FromStr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing_subscriber::{fmt, prelude::*, EnvFilter};
+
+/// Log output format
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable text
+    Text,
+    /// Newline-delimited JSON including the fields of all entered spans
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" | "Text" => Ok(LogFormat::Text),
+            "json" | "Json" | "JSON" => Ok(LogFormat::Json),
+            _ => Err(format!("Unknown log format: {}", s)),
+        }
+    }
+}
+
+/// Telemetry configuration
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryConfig {
+    pub log_format: LogFormat,
+    /// Traces every n-th websocket message through the pipeline, 0 disables
+    /// pipeline tracing
+    pub pipeline_sample_every: u64,
+}
+
+static PIPELINE_SAMPLE_EVERY: AtomicU64 = AtomicU64::new(0);
+
+/// Sets how often websocket messages are traced through the pipeline
+pub fn set_pipeline_sample_every(every: u64) {
+    PIPELINE_SAMPLE_EVERY.store(every, Ordering::Relaxed);
+}
+
+/// Samples websocket messages traced through the pipeline
+///
+/// Owned by the connection loop of each market data adapter.
+#[derive(Debug)]
+pub struct PipelineSampler {
+    every: u64,
+    seen: u64,
+}
+
+impl Default for PipelineSampler {
+    fn default() -> Self {
+        Self {
+            every: PIPELINE_SAMPLE_EVERY.load(Ordering::Relaxed),
+            seen: 0,
+        }
+    }
+}
+
+impl PipelineSampler {
+    /// Returns the root span of a received message when it is sampled
+    ///
+    /// The market is recorded once the message is parsed.
+    pub fn sample(&mut self, exchange: &str) -> Option<tracing::Span> {
+        if self.every == 0 {
+            return None;
+        }
+
+        self.seen += 1;
+        if self.seen % self.every != 0 {
+            return None;
+        }
+
+        let span = tracing::info_span!(
+            parent: None,
+            "tick",
+            exchange,
+            market = tracing::field::Empty
+        );
+        (!span.is_disabled()).then(|| span)
+    }
+}
+
+/// Keeps telemetry exporters alive, flushes them on shutdown
+#[derive(Default)]
+pub struct TelemetryGuard {
+    #[cfg(feature = "otel")]
+    otlp: Option<otlp::OtlpGuard>,
+}
+
+impl TelemetryGuard {
+    /// Flushes pending telemetry and shuts down exporters
+    pub fn shutdown(self) {
+        #[cfg(feature = "otel")]
+        if let Some(otlp) = self.otlp {
+            otlp.shutdown();
+        }
+    }
+}
+
+/// Error encountered while setting up telemetry
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to initialize telemetry: {source}")]
+pub struct TelemetryError {
+    source: Box<dyn std::error::Error>,
+}
+
+/// Installs the global tracing subscriber and optional exporters
+pub fn init(log_format: LogFormat) -> Result<TelemetryGuard, TelemetryError> {
+    let text_layer = match log_format {
+        LogFormat::Text => Some(fmt::layer().with_thread_names(true)),
+        LogFormat::Json => None,
+    };
+    let json_layer = match log_format {
+        LogFormat::Json => Some(
+            fmt::layer()
+                .json()
+                .with_current_span(true)
+                .with_span_list(true)
+                .with_thread_names(true),
+        ),
+        LogFormat::Text => None,
+    };
+    let registry = tracing_subscriber::registry()
+        .with(EnvFilter::from_default_env())
+        .with(text_layer)
+        .with(json_layer);
+
+    #[cfg(feature = "otel")]
+    if let Ok(endpoint) = std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        let (tracer, guard) = otlp::init(&endpoint)?;
+
+        registry
+            .with(tracing_opentelemetry::layer().with_tracer(tracer))
+            .init();
+
+        return Ok(TelemetryGuard { otlp: Some(guard) });
+    }
+
+    registry.init();
+
+    Ok(TelemetryGuard::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline_sampler() {
+        let mut sampler = PipelineSampler { every: 0, seen: 0 };
+        assert!(sampler.sample("ftx").is_none());
+
+        // Spans are disabled without a subscriber, sampling still counts
+        sampler.every = 2;
+        for _ in 0..4 {
+            assert!(sampler.sample("ftx").is_none());
+        }
+        assert_eq!(4, sampler.seen);
+    }
+
+    #[test]
+    fn test_log_format_from_str() {
+        assert_eq!(LogFormat::Text, "text".parse::<LogFormat>().unwrap());
+        assert_eq!(LogFormat::Json, "json".parse::<LogFormat>().unwrap());
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+}
+
+#[cfg(feature = "otel")]
+mod otlp {
+    use std::{collections::HashMap, time::Duration};
+
+    use opentelemetry::{
+        global,
+        metrics::ValueRecorder,
+        sdk::{metrics::PushController, trace, Resource},
+        KeyValue,
+    };
+    use opentelemetry_otlp::WithExportConfig;
+
+    use super::TelemetryError;
+
+    const SERVICE_NAME: &str = "botnode";
+    const EXPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+    /// Owns the runtime that drives OTLP exporters
+    ///
+    /// Exporters run on their own small Tokio runtime so they never share
+    /// a thread with the pinned engine executors.
+    pub(super) struct OtlpGuard {
+        runtime: tokio::runtime::Runtime,
+        controller: PushController,
+    }
+
+    impl OtlpGuard {
+        pub(super) fn shutdown(self) {
+            let _enter = self.runtime.enter();
+            global::shutdown_tracer_provider();
+            drop(self.controller);
+        }
+    }
+
+    pub(super) fn init(endpoint: &str) -> Result<(trace::Tracer, OtlpGuard), TelemetryError> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("otlp-exporter")
+            .enable_all()
+            .build()
+            .map_err(|e| TelemetryError {
+                source: Box::new(e),
+            })?;
+        let _enter = runtime.enter();
+
+        let resource = Resource::new(vec![KeyValue::new("service.name", SERVICE_NAME)]);
+
+        let tracer = opentelemetry_otlp::new_pipeline()
+            .tracing()
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .with_trace_config(trace::config().with_resource(resource))
+            .install_batch(opentelemetry::runtime::Tokio)
+            .map_err(|e| TelemetryError {
+                source: Box::new(e),
+            })?;
+
+        let controller = opentelemetry_otlp::new_pipeline()
+            .metrics(tokio::spawn, opentelemetry::util::tokio_interval_stream)
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .with_resource(vec![KeyValue::new("service.name", SERVICE_NAME)])
+            .with_period(EXPORT_INTERVAL)
+            .build()
+            .map_err(|e| TelemetryError {
+                source: Box::new(e),
+            })?;
+        global::set_meter_provider(controller.provider());
+
+        runtime.spawn(record_registry_metrics());
+
+        Ok((
+            tracer,
+            OtlpGuard {
+                runtime,
+                controller,
+            },
+        ))
+    }
+
+    /// Periodically copies published registry metrics into OTel instruments
+    async fn record_registry_metrics() {
+        let meter = global::meter(SERVICE_NAME);
+        let mut recorders: HashMap<&'static str, ValueRecorder<f64>> = HashMap::new();
+        let mut interval = tokio::time::interval(EXPORT_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            for (key, value) in crate::metrics::registry().snapshot() {
+                let recorder = recorders
+                    .entry(key.name)
+                    .or_insert_with(|| meter.f64_value_recorder(key.name).init());
+                let labels: Vec<_> = key
+                    .labels
+                    .iter()
+                    .map(|(k, v)| KeyValue::new(*k, v.to_string()))
+                    .collect();
+
+                recorder.record(value.as_f64(), &labels);
+            }
+        }
+    }
+}
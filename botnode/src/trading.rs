@@ -1,4 +1,13 @@
 //! Trading engine
 
+pub(crate) mod books;
 pub(crate) mod engine;
 pub(crate) mod event_loop;
+pub mod indicators;
+pub mod plugin;
+#[cfg(feature = "python")]
+pub mod python;
+pub(crate) mod router;
+pub mod strategy;
+#[cfg(feature = "wasm")]
+pub mod wasm;
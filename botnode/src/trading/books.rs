@@ -0,0 +1,121 @@
+//! Orderbooks maintained by the trading engine
+//!
+//! When market data emits orderbook deltas, the trading engine keeps its own
+//! copy of every book: full updates replace the book and deltas are applied
+//! to it. A gap in the delta sequence drops the book until the next full
+//! update, strategies aren't called with books known to be wrong.
+
+use crate::{market_data::book_events::BookEvents, prelude::*};
+
+/// Book and the sequence of the last delta applied to it
+#[derive(Debug)]
+struct TrackedBook {
+    orderbook: PlainOrderbook<f64>,
+    sequence: u64,
+}
+
+/// Orderbooks by exchange and market
+#[derive(Debug)]
+pub struct Orderbooks {
+    /// Books are only kept when market data emits deltas
+    enabled: bool,
+    books: HashMap<(Box<str>, Box<str>), TrackedBook>,
+}
+
+impl Orderbooks {
+    pub fn new(book_events: BookEvents) -> Self {
+        Self {
+            enabled: book_events == BookEvents::Delta,
+            books: HashMap::new(),
+        }
+    }
+
+    /// Updates the book of the event's market, returns the book when the
+    /// event was a delta applied to it
+    pub fn on_event(
+        &mut self,
+        exchange: &str,
+        event: &MarketEvent,
+    ) -> Option<&PlainOrderbook<f64>> {
+        if !self.enabled {
+            return None;
+        }
+
+        match &event.r#type {
+            MarketEventType::OrderbookUpdate(market, orderbook) => {
+                let book = TrackedBook {
+                    orderbook: (**orderbook).clone(),
+                    sequence: 0,
+                };
+                self.books
+                    .insert((Box::from(exchange), market.clone()), book);
+
+                None
+            }
+            MarketEventType::OrderbookDelta(market, delta) => {
+                let key = (Box::from(exchange), market.clone());
+                let last = self.books.get(&key)?.sequence;
+
+                if delta.sequence != last + 1 {
+                    warn!(
+                        "{exchange} {market} delta {} doesn't follow {last}, dropping the book",
+                        delta.sequence
+                    );
+                    self.books.remove(&key);
+                    return None;
+                }
+
+                let book = self.books.get_mut(&key)?;
+                book.orderbook.apply_delta(delta);
+                book.sequence = delta.sequence;
+
+                Some(&book.orderbook)
+            }
+            _ => None,
+        }
+    }
+
+    /// Drops the books of the markets on all exchanges, once they are
+    /// unsubscribed
+    pub fn remove_markets(&mut self, markets: &[Box<str>]) {
+        self.books
+            .retain(|(_, market), _| !markets.contains(market));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delta(sequence: u64, bid: f64) -> MarketEvent {
+        MarketEvent::orderbook_delta(
+            Box::from("BTC/USD"),
+            Box::new(OrderbookDelta {
+                bids: PriceLevelsVec::from_tuples_vec(&[(bid, 1.0)]),
+                asks: PriceLevelsVec::new(),
+                time: 0.0,
+                sequence,
+            }),
+        )
+    }
+
+    #[test]
+    fn test_orderbooks_delta_gap() {
+        let mut books = Orderbooks::new(BookEvents::Delta);
+        let full = MarketEvent::orderbook_update(Box::from("BTC/USD"), Box::default());
+
+        assert!(books.on_event("ftx", &full).is_none());
+        let book = books.on_event("ftx", &delta(1, 9.0)).unwrap();
+        assert_eq!(vec![9.0], book.bids.price_vec);
+
+        // Delta 2 was missed
+        assert!(books.on_event("ftx", &delta(3, 9.5)).is_none());
+        assert!(books.on_event("ftx", &delta(4, 9.5)).is_none());
+
+        books.on_event("ftx", &full);
+        assert!(books.on_event("ftx", &delta(1, 9.5)).is_some());
+
+        books.remove_markets(&[Box::from("BTC/USD")]);
+        assert!(books.on_event("ftx", &delta(2, 9.5)).is_none());
+    }
+}
@@ -1,16 +1,29 @@
 use crate::{
-    exchange::{ExchangeEvent, ExchangeRequest},
+    audit::AuditProducer,
+    bus::Subscriber,
+    control::ControlCommand,
+    market_data::book_events::BookEvents,
+    order_engine::{ExchangeEvent, ExchangeRequest},
+    position::Position,
     prelude::*,
+    trading::{indicators::IndicatorsConfig, strategy::Strategy},
 };
 
 /// Trading engine
 pub struct TradingEngine {
-    market_data_rxs: ConsumersMap<Box<str>, MarketEvent>,
-    indicator_rx: spsc_queue::Consumer<IndicatorEvent>,
-    exchange_tx: spsc_queue::Producer<ExchangeRequest>,
-    exchange_rx: spsc_queue::Consumer<ExchangeEvent>,
-    status_tx: spsc_queue::Producer<EngineStatus>,
-    status_rx: spsc_queue::Consumer<EngineStatus>,
+    pub(super) market_data_rxs: ConsumersMap<Box<str>, MarketEvent>,
+    pub(super) indicator_rx: spsc_queue::Consumer<IndicatorEvent>,
+    pub(super) exchange_tx: spsc_queue::Producer<ExchangeRequest>,
+    pub(super) exchange_rx: spsc_queue::Consumer<ExchangeEvent>,
+    pub(super) position_rx: spsc_queue::Consumer<Position>,
+    pub(super) commands: Subscriber<ControlCommand>,
+    pub(super) audit_tx: AuditProducer,
+    pub(super) strategies: Vec<Box<dyn Strategy>>,
+    /// Orderbook events emitted by market data
+    pub(super) book_events: BookEvents,
+    pub(super) indicators: IndicatorsConfig,
+    pub(super) status_tx: spsc_queue::Producer<EngineStatus>,
+    pub(super) status_rx: spsc_queue::Consumer<EngineStatus>,
 }
 
 impl TradingEngine {
@@ -19,6 +32,9 @@ impl TradingEngine {
         indicator_rx: spsc_queue::Consumer<IndicatorEvent>,
         exchange_tx: spsc_queue::Producer<ExchangeRequest>,
         exchange_rx: spsc_queue::Consumer<ExchangeEvent>,
+        position_rx: spsc_queue::Consumer<Position>,
+        audit_tx: AuditProducer,
+        strategies: Vec<Box<dyn Strategy>>,
     ) -> Self {
         let (status_tx, status_rx) = spsc_queue::make(1);
         Self {
@@ -26,10 +42,35 @@ impl TradingEngine {
             indicator_rx,
             exchange_tx,
             exchange_rx,
+            position_rx,
+            commands: Subscriber::default(),
+            audit_tx,
+            strategies,
+            book_events: BookEvents::default(),
+            indicators: IndicatorsConfig::default(),
             status_tx,
             status_rx,
         }
     }
+
+    /// Sets subscription to the commands of botvana-server
+    pub fn with_commands(mut self, commands: Subscriber<ControlCommand>) -> Self {
+        self.commands = commands;
+        self
+    }
+
+    /// Sets the orderbook events emitted by market data, books are
+    /// maintained from deltas when they are emitted
+    pub fn with_book_events(mut self, book_events: BookEvents) -> Self {
+        self.book_events = book_events;
+        self
+    }
+
+    /// Sets the indicators calculated for strategies
+    pub fn with_indicators(mut self, indicators: IndicatorsConfig) -> Self {
+        self.indicators = indicators;
+        self
+    }
 }
 
 #[async_trait(?Send)]
@@ -43,18 +84,11 @@ impl Engine for TradingEngine {
     }
 
     /// Starts the trading engine
-    async fn start(mut self, shutdown: Shutdown) -> Result<(), EngineError> {
+    async fn start(self, shutdown: Shutdown) -> Result<(), EngineError> {
         info!("Starting trading engine");
 
         self.status_tx.try_push(EngineStatus::Booting);
 
-        super::event_loop::run_loop(
-            self.market_data_rxs,
-            self.indicator_rx,
-            self.exchange_tx,
-            self.exchange_rx,
-            self.status_tx,
-            shutdown,
-        )
+        super::event_loop::run_loop(self, shutdown)
     }
 }
@@ -1,53 +1,373 @@
-use crate::exchange::{ExchangeEvent, ExchangeRequest};
+use botvana::{
+    cfg::{ConfigUpdate, StrategyParams},
+    net::msg::StrategyReload,
+};
+use figment::value::Dict;
+
+use crate::audit::{AuditEvent, AuditProducer, AuditRecord, Category, Severity};
+use crate::control::ControlCommand;
+use crate::latency::{LatencyHistogram, PipelineLatency, TickStamp};
+use crate::order_engine::ExchangeEvent;
 use crate::prelude::*;
+use crate::trading::{
+    books::Orderbooks,
+    engine::TradingEngine,
+    indicators::{Indicators, MarketIndicators},
+    router::OrderRouter,
+    strategy::{OrderIntent, Strategy},
+};
 
 const STALE_MARKET_EVENT_MS: u64 = 10;
 
+/// How often hot path metrics are published
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often strategies' `on_timer` is called
+const STRATEGY_TIMER_INTERVAL: Duration = Duration::from_secs(1);
+
 /// Runs trading event loop
-pub fn run_loop(
-    market_data_rxs: ConsumersMap<Box<str>, MarketEvent>,
-    indicator_rx: spsc_queue::Consumer<IndicatorEvent>,
-    _exchange_tx: spsc_queue::Producer<ExchangeRequest>,
-    exchange_rx: spsc_queue::Consumer<ExchangeEvent>,
-    status_tx: spsc_queue::Producer<EngineStatus>,
-    shutdown: Shutdown,
-) -> Result<(), EngineError> {
+pub fn run_loop(engine: TradingEngine, shutdown: Shutdown) -> Result<(), EngineError> {
+    let TradingEngine {
+        market_data_rxs,
+        indicator_rx,
+        exchange_tx,
+        exchange_rx,
+        position_rx,
+        commands,
+        audit_tx,
+        mut strategies,
+        book_events,
+        indicators,
+        status_tx,
+        ..
+    } = engine;
     let mut prices = HashMap::new();
+    let mut books = Orderbooks::new(book_events);
+    let mut indicators = Indicators::new(indicators);
+    let mut router = OrderRouter::new(exchange_tx);
+    let mut last_timer = std::time::Instant::now();
+    let mut perf =
+        crate::perf::PerfSection::new("process_market_event", crate::perf::DEFAULT_SAMPLE_EVERY);
+    let mut last_publish = std::time::Instant::now();
+    let mut loop_latency = LatencyHistogram::default();
+    let mut pipeline_latency = PipelineLatency::default();
+    // Strategies are not run while the server paused trading, books and
+    // indicators are kept up to date
+    let mut paused = crate::risk_engine::paused();
+    let mut last_iteration = std::time::Instant::now();
 
     status_tx.try_push(EngineStatus::Running);
+    crate::audit::record(
+        &audit_tx,
+        AuditEvent::new(
+            Severity::Info,
+            Category::Lifecycle,
+            "trading-engine",
+            "trading engine running",
+        ),
+    );
 
     loop {
         if shutdown.shutdown_started() {
+            crate::audit::record(
+                &audit_tx,
+                AuditEvent::new(
+                    Severity::Info,
+                    Category::Lifecycle,
+                    "trading-engine",
+                    "trading engine shutting down",
+                ),
+            );
             return Ok(());
         }
 
+        loop_latency.record(last_iteration.elapsed());
+        last_iteration = std::time::Instant::now();
+        if last_publish.elapsed() >= PUBLISH_INTERVAL {
+            perf.publish();
+            loop_latency.publish(
+                "engine_loop_iteration_microseconds",
+                &crate::metrics::MetricLabels::new().with("engine", "trading-engine"),
+            );
+            pipeline_latency.publish();
+            last_publish = std::time::Instant::now();
+        }
+
         for (exchange, market_data_rx) in market_data_rxs.iter() {
             if let Some(event) = market_data_rx.try_pop() {
-                let elapsed = event.timestamp.elapsed().unwrap();
+                pipeline_latency.record_channel(exchange, &event);
+                let update_span = event.span.as_ref().map_or_else(
+                    tracing::Span::none,
+                    |span| tracing::info_span!(parent: span, "orderbook_update"),
+                );
+                let entered = update_span.enter();
+                // Stale deltas are applied too, later ones build on them
+                let book = books.on_event(exchange, &event);
+                let market_indicators = indicators.on_event(exchange, &event);
+                drop(entered);
+                // Timestamps follow the monotonic clock, the wall clock may
+                // have stepped back behind them
+                let elapsed = event.timestamp.elapsed().unwrap_or_default();
 
                 if elapsed > Duration::from_millis(STALE_MARKET_EVENT_MS) {
                     warn!("Received stale market data: {elapsed:?}");
                     continue;
                 }
 
-                process_market_event(exchange, event, elapsed, &mut prices)?
+                if !paused {
+                    run_strategies(
+                        &mut strategies,
+                        &mut router,
+                        &audit_tx,
+                        &event,
+                        book,
+                        market_indicators,
+                        Some(&mut pipeline_latency),
+                    );
+                }
+                perf.measure(|| process_market_event(exchange, event, book, elapsed, &mut prices))?;
             }
         }
 
+        if !paused && last_timer.elapsed() >= STRATEGY_TIMER_INTERVAL {
+            let now = Utc::now();
+            for (idx, strategy) in strategies.iter_mut().enumerate() {
+                let intents = strategy.on_timer(now);
+                process_intents(&mut router, &audit_tx, idx, strategy.name(), intents, None);
+            }
+            last_timer = std::time::Instant::now();
+        }
+
         if let Some(event) = indicator_rx.try_pop() {
             trace!("indicator = {event:?}");
         }
 
+        if let Some(position) = position_rx.try_pop() {
+            for (idx, strategy) in strategies.iter_mut().enumerate() {
+                let intents = strategy.on_position(&position);
+                process_intents(&mut router, &audit_tx, idx, strategy.name(), intents, None);
+            }
+        }
+
+        match commands.try_recv() {
+            Some(ControlCommand::ConfigUpdate(update)) => {
+                process_config_update(&mut strategies, &mut router, &audit_tx, update);
+            }
+            Some(ControlCommand::PauseTrading) => paused = true,
+            Some(ControlCommand::ResumeTrading) => paused = false,
+            Some(ControlCommand::ReloadStrategy(reload)) => {
+                process_reload(&mut strategies, &audit_tx, reload);
+            }
+            Some(ControlCommand::UnsubscribeMarkets(markets)) => books.remove_markets(&markets),
+            _ => {}
+        }
+
         if let Some(event) = exchange_rx.try_pop() {
             trace!("exchange = {event:?}");
+
+            if let ExchangeEvent::Order(update) = &event {
+                if let Some(idx) = router.owner(update) {
+                    let strategy = &mut strategies[idx];
+                    let intents = strategy.on_order_update(update);
+                    process_intents(&mut router, &audit_tx, idx, strategy.name(), intents, None);
+                }
+            }
+        }
+    }
+}
+
+/// Passes the market event to all strategies
+///
+/// Strategies get orderbook deltas applied to the maintained `book` and
+/// the `indicators` updated with the trades of the event. The time each
+/// strategy takes to decide is recorded into `latency`.
+pub(crate) fn run_strategies(
+    strategies: &mut [Box<dyn Strategy>],
+    router: &mut OrderRouter,
+    audit_tx: &AuditProducer,
+    event: &MarketEvent,
+    book: Option<&PlainOrderbook<f64>>,
+    indicators: Option<&MarketIndicators>,
+    mut latency: Option<&mut PipelineLatency>,
+) {
+    for (idx, strategy) in strategies.iter_mut().enumerate() {
+        let strategy_span = event
+            .span
+            .as_ref()
+            .map(|span| tracing::info_span!(parent: span, "strategy", strategy = strategy.name()));
+        let _entered = strategy_span.as_ref().map(tracing::Span::enter);
+        let started = std::time::Instant::now();
+        let intents = match &event.r#type {
+            MarketEventType::OrderbookUpdate(market, orderbook) => {
+                strategy.on_orderbook(market, orderbook)
+            }
+            MarketEventType::BboUpdate(market, bbo) => strategy.on_bbo(market, bbo),
+            MarketEventType::OrderbookDelta(market, _) => match book {
+                Some(orderbook) => strategy.on_orderbook(market, orderbook),
+                None => continue,
+            },
+            MarketEventType::Trades(market, trades) => {
+                let mut intents: Vec<_> = trades
+                    .iter()
+                    .flat_map(|trade| strategy.on_trade(market, trade))
+                    .collect();
+                if let Some(indicators) = indicators {
+                    intents.extend(strategy.on_indicators(market, indicators));
+                }
+                intents
+            }
+            MarketEventType::FundingRate(market, funding) => {
+                strategy.on_funding_rate(market, funding)
+            }
+            MarketEventType::OpenInterest(market, open_interest) => {
+                strategy.on_open_interest(market, open_interest)
+            }
+            MarketEventType::Liquidation(market, liquidations) => liquidations
+                .iter()
+                .flat_map(|liquidation| strategy.on_liquidation(market, liquidation))
+                .collect(),
+            MarketEventType::Candle(market, candle) => strategy.on_candle(market, candle),
+            _ => continue,
+        };
+        if let Some(latency) = latency.as_deref_mut() {
+            latency.record_decision(strategy.name(), started.elapsed());
+        }
+        if intents.is_empty() {
+            continue;
+        }
+        let mut tick = TickStamp::from_event(strategy.name(), event);
+        // Orders are sent within the span of the strategy that decided them
+        tick.span = strategy_span.clone();
+        process_intents(router, audit_tx, idx, strategy.name(), intents, Some(&tick));
+    }
+}
+
+/// Applies markets and strategy parameters pushed by botvana-server
+fn process_config_update(
+    strategies: &mut [Box<dyn Strategy>],
+    router: &mut OrderRouter,
+    audit_tx: &AuditProducer,
+    update: ConfigUpdate,
+) {
+    let audit = |severity: Severity, msg: String| {
+        crate::audit::record(
+            audit_tx,
+            AuditEvent::new(severity, Category::Config, "trading-engine", msg),
+        );
+    };
+
+    if let Some(markets) = &update.markets {
+        router.set_markets(markets);
+        audit(Severity::Info, format!("trading markets {markets:?}"));
+    }
+
+    for StrategyParams { strategy, params } in update.strategy_params.iter() {
+        let params = match toml::from_str::<Dict>(params) {
+            Ok(params) => params,
+            Err(e) => {
+                audit(
+                    Severity::Warning,
+                    format!("invalid parameters of {strategy}: {e}"),
+                );
+                continue;
+            }
+        };
+
+        match strategies
+            .iter_mut()
+            .find(|s| s.name() == strategy.as_str())
+        {
+            Some(s) => {
+                s.on_params(&params);
+                audit(Severity::Info, format!("updated parameters of {strategy}"));
+            }
+            None => audit(
+                Severity::Warning,
+                format!("parameters of unknown strategy {strategy}"),
+            ),
         }
     }
 }
 
+/// Reloads the strategy requested by the server, strategies that fail to
+/// reload keep running
+fn process_reload(
+    strategies: &mut [Box<dyn Strategy>],
+    audit_tx: &AuditProducer,
+    reload: StrategyReload,
+) {
+    let StrategyReload { strategy, path } = reload;
+    let (severity, msg) = match strategies.iter_mut().find(|s| s.name() == strategy) {
+        Some(s) => match s.reload(path.as_deref()) {
+            Ok(()) => (Severity::Info, format!("reloaded strategy {strategy}")),
+            Err(e) => (
+                Severity::Critical,
+                format!("failed to reload strategy {strategy}: {e}"),
+            ),
+        },
+        None => (
+            Severity::Warning,
+            format!("reload of unknown strategy {strategy}"),
+        ),
+    };
+
+    match severity {
+        Severity::Info => info!("{msg}"),
+        _ => warn!("{msg}"),
+    }
+    crate::audit::record(
+        audit_tx,
+        AuditEvent::new(severity, Category::Config, "trading-engine", msg),
+    );
+}
+
+/// Sends order intents produced by a strategy to the order engine
+pub(crate) fn process_intents(
+    router: &mut OrderRouter,
+    audit_tx: &AuditProducer,
+    strategy_idx: usize,
+    strategy: &str,
+    intents: Vec<OrderIntent>,
+    tick: Option<&TickStamp>,
+) {
+    for intent in &intents {
+        crate::audit::record(
+            audit_tx,
+            AuditEvent::new(
+                Severity::Info,
+                Category::Order,
+                "trading-engine",
+                format!(
+                    "{strategy} decided to {} {} {} @ {:?}",
+                    intent.side.as_str(),
+                    intent.size,
+                    intent.market,
+                    intent.price
+                ),
+            )
+            .with_record(AuditRecord::order_decision(strategy, intent)),
+        );
+    }
+    let dropped = router.route(strategy_idx, strategy, intents, tick);
+
+    if dropped > 0 {
+        crate::audit::record(
+            audit_tx,
+            AuditEvent::new(
+                Severity::Warning,
+                Category::Order,
+                "trading-engine",
+                format!("order queue full, dropped {dropped} intents of {strategy}"),
+            ),
+        );
+    }
+}
+
 #[inline]
 fn process_market_event(
     exchange: &str,
     event: MarketEvent,
+    book: Option<&PlainOrderbook<f64>>,
     elapsed: Duration,
     prices: &mut HashMap<String, (f64, f64)>,
 ) -> Result<(), EngineError> {
@@ -76,6 +396,20 @@ fn process_market_event(
                 }
             }
         }
+        // Only emitted when the best bid or offer changed
+        MarketEventType::BboUpdate(market, bbo) => {
+            trace!(
+                "{exchange} {market}: {}/{} (elapsed={elapsed:?})",
+                bbo.bid,
+                bbo.ask
+            );
+            prices.insert(format!("{exchange}-{market}"), (bbo.bid, bbo.ask));
+        }
+        MarketEventType::OrderbookDelta(market, _) => {
+            if let Some(bbo) = book.and_then(PlainOrderbook::bbo) {
+                prices.insert(format!("{exchange}-{market}"), (bbo.bid, bbo.ask));
+            }
+        }
         _ => {}
     }
 
@@ -0,0 +1,382 @@
+//! Rolling indicators maintained by the trading engine
+//!
+//! Every calculator is updated in constant time and doesn't allocate after
+//! it was created, windows are ring buffers sized once. [`Indicators`] keeps
+//! one set of calculators per exchange and market, updated from the trades
+//! of market events before strategies are called.
+
+use serde::Deserialize;
+
+use crate::prelude::*;
+
+/// Periods of the indicators calculated for every market
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct IndicatorsConfig {
+    /// Whether indicators are calculated at all
+    pub enabled: bool,
+    /// Number of trades the exponential moving average is smoothed over
+    pub ema_period: usize,
+    /// Number of trades averaged by the simple moving average
+    pub sma_period: usize,
+    /// Number of trade-to-trade log returns the volatility is estimated from
+    pub volatility_window: usize,
+    /// Number of trades the VWAP is calculated over
+    pub vwap_window: usize,
+}
+
+impl Default for IndicatorsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ema_period: 20,
+            sma_period: 20,
+            volatility_window: 100,
+            vwap_window: 100,
+        }
+    }
+}
+
+/// Fixed-size window of the latest values
+#[derive(Clone, Debug)]
+pub struct RollingWindow {
+    values: Box<[f64]>,
+    /// Index the next value is written to
+    next: usize,
+    len: usize,
+}
+
+impl RollingWindow {
+    /// Creates window of given capacity, at least one
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            values: vec![0.0; capacity.max(1)].into_boxed_slice(),
+            next: 0,
+            len: 0,
+        }
+    }
+
+    /// Adds the value, returns the value that fell out of the full window
+    pub fn push(&mut self, value: f64) -> Option<f64> {
+        let evicted = match self.is_full() {
+            true => Some(self.values[self.next]),
+            false => None,
+        };
+        self.values[self.next] = value;
+        self.next = (self.next + 1) % self.values.len();
+        self.len = (self.len + 1).min(self.values.len());
+
+        evicted
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == self.values.len()
+    }
+}
+
+/// Exponential moving average
+#[derive(Clone, Debug)]
+pub struct Ema {
+    alpha: f64,
+    value: Option<f64>,
+}
+
+impl Ema {
+    /// Creates EMA with the smoothing `2 / (period + 1)`
+    pub fn new(period: usize) -> Self {
+        Self {
+            alpha: 2.0 / (period.max(1) as f64 + 1.0),
+            value: None,
+        }
+    }
+
+    /// Updates the average, the first value seeds it
+    pub fn update(&mut self, value: f64) -> f64 {
+        let ema = match self.value {
+            Some(ema) => ema + self.alpha * (value - ema),
+            None => value,
+        };
+        self.value = Some(ema);
+
+        ema
+    }
+
+    pub fn value(&self) -> Option<f64> {
+        self.value
+    }
+}
+
+/// Simple moving average
+#[derive(Clone, Debug)]
+pub struct Sma {
+    window: RollingWindow,
+    sum: f64,
+}
+
+impl Sma {
+    pub fn new(period: usize) -> Self {
+        Self {
+            window: RollingWindow::new(period),
+            sum: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, value: f64) -> f64 {
+        self.sum += value - self.window.push(value).unwrap_or(0.0);
+
+        self.sum / self.window.len() as f64
+    }
+
+    /// Returns the average once the window is full
+    pub fn value(&self) -> Option<f64> {
+        match self.window.is_full() {
+            true => Some(self.sum / self.window.len() as f64),
+            false => None,
+        }
+    }
+}
+
+/// Volume-weighted average price over the latest trades
+#[derive(Clone, Debug)]
+pub struct Vwap {
+    notionals: RollingWindow,
+    sizes: RollingWindow,
+    notional: f64,
+    volume: f64,
+}
+
+impl Vwap {
+    pub fn new(window: usize) -> Self {
+        Self {
+            notionals: RollingWindow::new(window),
+            sizes: RollingWindow::new(window),
+            notional: 0.0,
+            volume: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, price: f64, size: f64) {
+        self.notional += price * size - self.notionals.push(price * size).unwrap_or(0.0);
+        self.volume += size - self.sizes.push(size).unwrap_or(0.0);
+    }
+
+    /// Returns the VWAP, `None` before any volume traded
+    pub fn value(&self) -> Option<f64> {
+        match self.volume > 0.0 {
+            true => Some(self.notional / self.volume),
+            false => None,
+        }
+    }
+}
+
+/// Realized volatility estimated as the standard deviation of log returns
+/// between consecutive prices
+#[derive(Clone, Debug)]
+pub struct RealizedVolatility {
+    returns: RollingWindow,
+    sum: f64,
+    sum_sq: f64,
+    last_price: Option<f64>,
+}
+
+impl RealizedVolatility {
+    pub fn new(window: usize) -> Self {
+        Self {
+            returns: RollingWindow::new(window),
+            sum: 0.0,
+            sum_sq: 0.0,
+            last_price: None,
+        }
+    }
+
+    /// Updates the estimate with the next price, non-positive prices are
+    /// ignored
+    pub fn update(&mut self, price: f64) {
+        if price <= 0.0 {
+            return;
+        }
+        if let Some(last_price) = self.last_price.replace(price) {
+            let ret = (price / last_price).ln();
+            let evicted = self.returns.push(ret).unwrap_or(0.0);
+            self.sum += ret - evicted;
+            self.sum_sq += ret * ret - evicted * evicted;
+        }
+    }
+
+    /// Returns the sample standard deviation of the returns in the window,
+    /// not annualized, `None` before two returns
+    pub fn value(&self) -> Option<f64> {
+        let n = self.returns.len() as f64;
+        if self.returns.len() < 2 {
+            return None;
+        }
+        let variance = (self.sum_sq - self.sum * self.sum / n) / (n - 1.0);
+
+        Some(variance.max(0.0).sqrt())
+    }
+}
+
+/// Indicators of a single market
+#[derive(Clone, Debug)]
+pub struct MarketIndicators {
+    pub ema: Ema,
+    pub sma: Sma,
+    pub vwap: Vwap,
+    pub volatility: RealizedVolatility,
+}
+
+impl MarketIndicators {
+    pub fn new(config: &IndicatorsConfig) -> Self {
+        Self {
+            ema: Ema::new(config.ema_period),
+            sma: Sma::new(config.sma_period),
+            vwap: Vwap::new(config.vwap_window),
+            volatility: RealizedVolatility::new(config.volatility_window),
+        }
+    }
+
+    /// Updates all indicators with the trade
+    pub fn on_trade(&mut self, price: f64, size: f64) {
+        self.ema.update(price);
+        self.sma.update(price);
+        self.vwap.update(price, size);
+        self.volatility.update(price);
+    }
+}
+
+/// Indicators by exchange and market
+#[derive(Debug)]
+pub struct Indicators {
+    config: IndicatorsConfig,
+    markets: HashMap<(Box<str>, Box<str>), MarketIndicators>,
+}
+
+impl Indicators {
+    pub fn new(config: IndicatorsConfig) -> Self {
+        Self {
+            config,
+            markets: HashMap::new(),
+        }
+    }
+
+    /// Updates indicators of the event's market with its trades, returns
+    /// the updated indicators
+    pub fn on_event(&mut self, exchange: &str, event: &MarketEvent) -> Option<&MarketIndicators> {
+        if !self.config.enabled {
+            return None;
+        }
+        let (market, trades) = match &event.r#type {
+            MarketEventType::Trades(market, trades) if !trades.is_empty() => (market, trades),
+            _ => return None,
+        };
+
+        let config = &self.config;
+        let indicators = self
+            .markets
+            .entry((Box::from(exchange), market.clone()))
+            .or_insert_with(|| MarketIndicators::new(config));
+        for trade in trades.iter() {
+            indicators.on_trade(trade.price, trade.size);
+        }
+
+        Some(indicators)
+    }
+
+    /// Returns indicators of the market, once any of its trades was seen
+    pub fn get(&self, exchange: &str, market: &str) -> Option<&MarketIndicators> {
+        self.markets.get(&(Box::from(exchange), Box::from(market)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use botvana::market::trade::Trade;
+
+    #[test]
+    fn test_rolling_window() {
+        let mut window = RollingWindow::new(2);
+
+        assert_eq!(None, window.push(1.0));
+        assert_eq!(None, window.push(2.0));
+        assert_eq!(Some(1.0), window.push(3.0));
+        assert_eq!(Some(2.0), window.push(4.0));
+        assert!(window.is_full());
+    }
+
+    #[test]
+    fn test_ema_and_sma() {
+        let mut ema = Ema::new(3);
+        let mut sma = Sma::new(3);
+
+        for price in [1.0, 2.0, 3.0, 4.0] {
+            ema.update(price);
+            sma.update(price);
+        }
+
+        // 1, 1.5, 2.25, 3.125
+        assert_eq!(Some(3.125), ema.value());
+        assert_eq!(Some(3.0), sma.value());
+    }
+
+    #[test]
+    fn test_vwap_window() {
+        let mut vwap = Vwap::new(2);
+        assert_eq!(None, vwap.value());
+
+        vwap.update(100.0, 1.0);
+        vwap.update(110.0, 3.0);
+        assert_eq!(Some(107.5), vwap.value());
+
+        // First trade left the window
+        vwap.update(120.0, 1.0);
+        assert_eq!(Some(112.5), vwap.value());
+    }
+
+    #[test]
+    fn test_realized_volatility() {
+        let mut volatility = RealizedVolatility::new(10);
+        volatility.update(100.0);
+        volatility.update(100.0);
+        assert_eq!(None, volatility.value());
+
+        volatility.update(100.0);
+        assert_eq!(Some(0.0), volatility.value());
+
+        // Alternating returns of +-ln(1.1)
+        let mut volatility = RealizedVolatility::new(2);
+        for price in [100.0, 110.0, 100.0, 110.0] {
+            volatility.update(price);
+        }
+        let expected = (2.0 * 1.1f64.ln().powi(2)).sqrt();
+        assert!((volatility.value().unwrap() - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_indicators_by_market() {
+        let config = IndicatorsConfig {
+            enabled: true,
+            ..IndicatorsConfig::default()
+        };
+        let mut indicators = Indicators::new(config);
+        let trades = MarketEvent::trades(
+            Box::from("BTC/USD"),
+            Box::new([Trade::new(100.0, 1.0, Utc::now())]),
+        );
+
+        assert!(indicators.on_event("ftx", &trades).is_some());
+        assert_eq!(
+            Some(100.0),
+            indicators.get("ftx", "BTC/USD").unwrap().vwap.value()
+        );
+        assert!(indicators.get("binance", "BTC/USD").is_none());
+    }
+}
@@ -0,0 +1,369 @@
+//! C ABI strategy plugins
+//!
+//! Strategies compiled separately from botnode are shared libraries exporting
+//! `botnode_strategy_plugin`, which returns a [`ffi::BotnodeStrategyPlugin`]
+//! table of callbacks. The ABI is described for C in
+//! `botnode/include/botnode_plugin.h` and only changes together with
+//! [`PLUGIN_ABI_VERSION`].
+//!
+//! A strategy section with a `plugin` path loads the library, all other
+//! parameters are passed to `create` as a JSON object.
+
+use std::ffi::{c_void, CString};
+
+use botvana::market::{orderbook::PlainOrderbook, trade::Trade};
+use chrono::{DateTime, Utc};
+use libloading::{Library, Symbol};
+
+use super::strategy::{OrderIntent, Side, Strategy, StrategyError};
+use crate::config::StrategyConfig;
+
+/// Version of the plugin ABI implemented by this botnode
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Symbol every plugin library exports
+pub const PLUGIN_ENTRY_POINT: &[u8] = b"botnode_strategy_plugin\0";
+
+/// Maximum number of intents a single callback can return
+pub const MAX_INTENTS: usize = 16;
+
+/// Types shared with plugins, layout must match `botnode_plugin.h`
+pub mod ffi {
+    use std::{ffi::c_void, os::raw::c_char};
+
+    /// Orderbook levels, both sides sorted by price ascending
+    #[repr(C)]
+    pub struct BotnodeOrderbook {
+        pub bid_prices: *const f64,
+        pub bid_sizes: *const f64,
+        pub bid_len: usize,
+        pub ask_prices: *const f64,
+        pub ask_sizes: *const f64,
+        pub ask_len: usize,
+    }
+
+    #[repr(C)]
+    pub struct BotnodeTrade {
+        pub price: f64,
+        pub size: f64,
+        /// Exchange time in nanoseconds since UNIX epoch
+        pub time_ns: i64,
+    }
+
+    pub const BOTNODE_SIDE_BUY: u8 = 0;
+    pub const BOTNODE_SIDE_SELL: u8 = 1;
+
+    /// Size of the market array of intents, including the terminating NUL
+    pub const BOTNODE_MARKET_LEN: usize = 64;
+
+    /// Order intent written by the plugin
+    ///
+    /// `market` is copied in NUL-terminated, `price` is NaN for market
+    /// orders.
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct BotnodeOrderIntent {
+        pub market: [c_char; BOTNODE_MARKET_LEN],
+        pub side: u8,
+        pub price: f64,
+        pub size: f64,
+    }
+
+    impl BotnodeOrderIntent {
+        pub const EMPTY: Self = Self {
+            market: [0; BOTNODE_MARKET_LEN],
+            side: BOTNODE_SIDE_BUY,
+            price: f64::NAN,
+            size: 0.0,
+        };
+
+        /// Returns the market up to the terminating NUL, `None` when it's
+        /// empty, unterminated or not UTF-8
+        pub fn market(&self) -> Option<&str> {
+            // SAFETY: c_char and u8 have the same size and alignment
+            let bytes: &[u8; BOTNODE_MARKET_LEN] =
+                unsafe { &*(&self.market as *const _ as *const _) };
+            let len = bytes.iter().position(|b| *b == 0)?;
+
+            std::str::from_utf8(&bytes[..len])
+                .ok()
+                .filter(|market| !market.is_empty())
+        }
+    }
+
+    /// Callbacks writing up to `cap` intents into `out` and returning their
+    /// count
+    pub type OnOrderbook = extern "C" fn(
+        state: *mut c_void,
+        market: *const c_char,
+        orderbook: *const BotnodeOrderbook,
+        out: *mut BotnodeOrderIntent,
+        cap: usize,
+    ) -> usize;
+    pub type OnTrade = extern "C" fn(
+        state: *mut c_void,
+        market: *const c_char,
+        trade: *const BotnodeTrade,
+        out: *mut BotnodeOrderIntent,
+        cap: usize,
+    ) -> usize;
+    pub type OnTimer = extern "C" fn(
+        state: *mut c_void,
+        now_ns: i64,
+        out: *mut BotnodeOrderIntent,
+        cap: usize,
+    ) -> usize;
+
+    /// Table returned by `botnode_strategy_plugin`
+    #[repr(C)]
+    pub struct BotnodeStrategyPlugin {
+        pub abi_version: u32,
+        /// Creates strategy state from JSON parameters, null on failure
+        pub create: extern "C" fn(params_json: *const c_char) -> *mut c_void,
+        pub destroy: extern "C" fn(state: *mut c_void),
+        pub on_orderbook: Option<OnOrderbook>,
+        pub on_trade: Option<OnTrade>,
+        pub on_timer: Option<OnTimer>,
+    }
+}
+
+use ffi::*;
+
+/// Strategy implemented by a plugin library
+pub struct PluginStrategy {
+    name: String,
+    plugin: *const BotnodeStrategyPlugin,
+    state: *mut c_void,
+    // Dropped last, the plugin table and state live in the library
+    _library: Library,
+}
+
+// SAFETY: the state is only ever used by the thread owning the strategy,
+// plugins must not keep thread-local references to it
+unsafe impl Send for PluginStrategy {}
+
+impl PluginStrategy {
+    /// Loads the plugin library and creates the strategy state with the
+    /// configured parameters
+    pub fn load(name: &str, config: &StrategyConfig) -> Result<Self, StrategyError> {
+        let mut params = config.params.clone();
+        let path = params
+            .remove("plugin")
+            .and_then(|path| path.into_string())
+            .ok_or_else(|| StrategyError::new("`plugin` must be a path to the plugin library"))?;
+        let params = serde_json::to_string(&params).map_err(StrategyError::with_source)?;
+        let params = CString::new(params).map_err(StrategyError::with_source)?;
+
+        // SAFETY: loading runs the library initializers, plugins are trusted
+        // code configured by the operator
+        let library = unsafe { Library::new(&path) }.map_err(StrategyError::with_source)?;
+        let plugin = unsafe {
+            let entry: Symbol<extern "C" fn() -> *const BotnodeStrategyPlugin> = library
+                .get(PLUGIN_ENTRY_POINT)
+                .map_err(StrategyError::with_source)?;
+            entry()
+        };
+
+        // SAFETY: the table is static data of the library, which is kept
+        // loaded for the lifetime of the strategy
+        let table = unsafe { plugin.as_ref() }
+            .ok_or_else(|| StrategyError::new(format!("{path} returned no plugin table")))?;
+        if table.abi_version != PLUGIN_ABI_VERSION {
+            return Err(StrategyError::new(format!(
+                "{path} implements plugin ABI {}, botnode supports {PLUGIN_ABI_VERSION}",
+                table.abi_version
+            )));
+        }
+
+        let state = (table.create)(params.as_ptr());
+        if state.is_null() {
+            return Err(StrategyError::new(format!(
+                "{path} failed to create strategy"
+            )));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            plugin,
+            state,
+            _library: library,
+        })
+    }
+
+    fn table(&self) -> &BotnodeStrategyPlugin {
+        // SAFETY: checked non-null in `load`, valid while the library is loaded
+        unsafe { &*self.plugin }
+    }
+
+    /// Runs the callback with a fresh intents buffer and converts the
+    /// intents written into it
+    fn collect(
+        &self,
+        callback: impl FnOnce(*mut BotnodeOrderIntent, usize) -> usize,
+    ) -> Vec<OrderIntent> {
+        let mut out = [BotnodeOrderIntent::EMPTY; MAX_INTENTS];
+        let n = callback(out.as_mut_ptr(), MAX_INTENTS);
+        if n > MAX_INTENTS {
+            tracing::warn!(
+                "Strategy {} returned {n} intents, only {MAX_INTENTS} are used",
+                self.name
+            );
+        }
+
+        convert_intents(&out[..n.min(MAX_INTENTS)])
+    }
+}
+
+impl Drop for PluginStrategy {
+    fn drop(&mut self) {
+        (self.table().destroy)(self.state);
+    }
+}
+
+impl Strategy for PluginStrategy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn on_orderbook(&mut self, market: &str, orderbook: &PlainOrderbook<f64>) -> Vec<OrderIntent> {
+        let callback = match self.table().on_orderbook {
+            Some(callback) => callback,
+            None => return Vec::new(),
+        };
+        let market = match CString::new(market) {
+            Ok(market) => market,
+            Err(_) => return Vec::new(),
+        };
+        let orderbook = BotnodeOrderbook {
+            bid_prices: orderbook.bids.price_vec.as_ptr(),
+            bid_sizes: orderbook.bids.size_vec.as_ptr(),
+            bid_len: orderbook.bids.len(),
+            ask_prices: orderbook.asks.price_vec.as_ptr(),
+            ask_sizes: orderbook.asks.size_vec.as_ptr(),
+            ask_len: orderbook.asks.len(),
+        };
+
+        self.collect(|out, cap| callback(self.state, market.as_ptr(), &orderbook, out, cap))
+    }
+
+    fn on_trade(&mut self, market: &str, trade: &Trade) -> Vec<OrderIntent> {
+        let callback = match self.table().on_trade {
+            Some(callback) => callback,
+            None => return Vec::new(),
+        };
+        let market = match CString::new(market) {
+            Ok(market) => market,
+            Err(_) => return Vec::new(),
+        };
+        let trade = BotnodeTrade {
+            price: trade.price,
+            size: trade.size,
+            time_ns: trade.time.timestamp_nanos(),
+        };
+
+        self.collect(|out, cap| callback(self.state, market.as_ptr(), &trade, out, cap))
+    }
+
+    fn on_timer(&mut self, now: DateTime<Utc>) -> Vec<OrderIntent> {
+        let callback = match self.table().on_timer {
+            Some(callback) => callback,
+            None => return Vec::new(),
+        };
+
+        self.collect(|out, cap| callback(self.state, now.timestamp_nanos(), out, cap))
+    }
+}
+
+/// Converts intents written by a plugin, skipping malformed ones
+fn convert_intents(intents: &[BotnodeOrderIntent]) -> Vec<OrderIntent> {
+    intents
+        .iter()
+        .filter_map(|intent| {
+            if !intent.size.is_finite() || intent.size <= 0.0 {
+                return None;
+            }
+            let side = match intent.side {
+                BOTNODE_SIDE_BUY => Side::Buy,
+                BOTNODE_SIDE_SELL => Side::Sell,
+                _ => return None,
+            };
+            let market = intent.market()?;
+
+            Some(OrderIntent {
+                market: Box::from(market),
+                side,
+                price: Some(intent.price).filter(|price| !price.is_nan()),
+                size: intent.size,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn market(name: &str) -> [std::os::raw::c_char; BOTNODE_MARKET_LEN] {
+        let mut market = [0; BOTNODE_MARKET_LEN];
+        for (c, b) in market.iter_mut().zip(name.bytes()) {
+            *c = b as _;
+        }
+        market
+    }
+
+    #[test]
+    fn test_convert_intents() {
+        let intents = [
+            BotnodeOrderIntent {
+                market: market("BTC/USD"),
+                side: BOTNODE_SIDE_SELL,
+                price: 100.5,
+                size: 1.0,
+            },
+            BotnodeOrderIntent {
+                market: market("BTC/USD"),
+                side: BOTNODE_SIDE_BUY,
+                price: f64::NAN,
+                size: 2.0,
+            },
+            // Malformed intents are skipped
+            BotnodeOrderIntent::EMPTY,
+            BotnodeOrderIntent {
+                market: market("BTC/USD"),
+                side: 7,
+                price: 1.0,
+                size: 1.0,
+            },
+            BotnodeOrderIntent {
+                market: market(&"X".repeat(BOTNODE_MARKET_LEN)),
+                side: BOTNODE_SIDE_BUY,
+                price: 1.0,
+                size: 1.0,
+            },
+        ];
+
+        assert_eq!(
+            vec![
+                OrderIntent::limit("BTC/USD", Side::Sell, 100.5, 1.0),
+                OrderIntent::market("BTC/USD", Side::Buy, 2.0),
+            ],
+            convert_intents(&intents)
+        );
+    }
+
+    #[test]
+    fn test_load_missing_plugin() {
+        let config = crate::config::BotnodeConfig::from_toml(
+            r#"
+            bot_id = 1
+            server_addr = "127.0.0.1:7978"
+
+            [strategy.missing]
+            plugin = "/nonexistent/libmissing.so"
+            "#,
+        )
+        .unwrap();
+
+        assert!(PluginStrategy::load("missing", &config.strategy["missing"]).is_err());
+    }
+}
@@ -0,0 +1,342 @@
+//! Python strategies
+//!
+//! A Python strategy is a class loaded from the file given by the `python`
+//! parameter of its configuration, `class` names the class (`Strategy` by
+//! default) and all other parameters are passed to its constructor as keyword
+//! arguments. The class implements any of `on_orderbook(market, orderbook)`,
+//! `on_trade(market, trade)` and `on_timer(now)`, each returning a list of
+//! `botnode.OrderIntent` or `None`:
+//!
+//! ```python
+//! import botnode
+//!
+//! class Strategy:
+//!     def __init__(self, size=1.0):
+//!         self.size = size
+//!
+//!     def on_orderbook(self, market, orderbook):
+//!         if orderbook.spread() > 10:
+//!             return [botnode.OrderIntent(market, "buy", self.size, orderbook.best_bid())]
+//! ```
+//!
+//! Callbacks run on the trading engine core while holding the GIL, every call
+//! copies the orderbook into Python and costs microseconds rather than the
+//! nanoseconds of a Rust strategy.
+
+use std::path::Path;
+
+use pyo3::{exceptions::PyValueError, prelude::*, types::PyDict};
+
+use botvana::market::{orderbook::PlainOrderbook, trade::Trade};
+use chrono::{DateTime, Utc};
+
+use super::strategy::{OrderIntent, Side, Strategy, StrategyError};
+use crate::config::StrategyConfig;
+
+/// Strategy implemented by a Python object
+pub struct PyStrategy {
+    name: String,
+    object: PyObject,
+}
+
+impl PyStrategy {
+    /// Loads the strategy class and instantiates it with the configured
+    /// parameters
+    pub fn load(name: &str, config: &StrategyConfig) -> Result<Self, StrategyError> {
+        let mut params = config.params.clone();
+        let path = params
+            .remove("python")
+            .and_then(|path| path.into_string())
+            .ok_or_else(|| StrategyError::new("`python` must be a path to the strategy file"))?;
+        let class = match params.remove("class") {
+            Some(class) => class
+                .into_string()
+                .ok_or_else(|| StrategyError::new("`class` must be a string"))?,
+            None => "Strategy".to_string(),
+        };
+        let code = std::fs::read_to_string(&path).map_err(StrategyError::with_source)?;
+        let kwargs = serde_json::to_string(&params).map_err(StrategyError::with_source)?;
+
+        Python::with_gil(|py| {
+            let object = instantiate(py, &code, Path::new(&path), name, &class, &kwargs)
+                .map_err(|e| StrategyError::new(format_err(py, e)))?;
+
+            Ok(Self {
+                name: name.to_string(),
+                object,
+            })
+        })
+    }
+
+    /// Calls the callback if the object implements it and converts returned
+    /// intents
+    fn call(&self, method: &str, args: impl IntoPy<Py<pyo3::types::PyTuple>>) -> Vec<OrderIntent> {
+        Python::with_gil(|py| {
+            let object = self.object.as_ref(py);
+            match object.hasattr(method) {
+                Ok(true) => {}
+                _ => return Vec::new(),
+            }
+
+            let result =
+                object
+                    .call_method1(method, args)
+                    .and_then(|result| match result.is_none() {
+                        true => Ok(Vec::new()),
+                        false => result.extract::<Vec<PyOrderIntent>>(),
+                    });
+
+            match result {
+                Ok(intents) => intents.into_iter().map(OrderIntent::from).collect(),
+                Err(e) => {
+                    tracing::error!(
+                        "Strategy {} {method} failed: {}",
+                        self.name,
+                        format_err(py, e)
+                    );
+                    Vec::new()
+                }
+            }
+        })
+    }
+}
+
+impl Strategy for PyStrategy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn on_orderbook(&mut self, market: &str, orderbook: &PlainOrderbook<f64>) -> Vec<OrderIntent> {
+        let orderbook = PyOrderbook {
+            orderbook: orderbook.clone(),
+        };
+        self.call("on_orderbook", (market, orderbook))
+    }
+
+    fn on_trade(&mut self, market: &str, trade: &Trade) -> Vec<OrderIntent> {
+        let trade = PyTrade {
+            price: trade.price,
+            size: trade.size,
+            time: unix_seconds(trade.time),
+        };
+        self.call("on_trade", (market, trade))
+    }
+
+    fn on_timer(&mut self, now: DateTime<Utc>) -> Vec<OrderIntent> {
+        self.call("on_timer", (unix_seconds(now),))
+    }
+}
+
+/// Registers the `botnode` module and instantiates the strategy class
+fn instantiate(
+    py: Python,
+    code: &str,
+    path: &Path,
+    name: &str,
+    class: &str,
+    kwargs: &str,
+) -> PyResult<PyObject> {
+    let sys_modules = py.import("sys")?.getattr("modules")?;
+    if !sys_modules.contains("botnode")? {
+        sys_modules.set_item("botnode", botnode_module(py)?)?;
+    }
+
+    let file_name = path.to_string_lossy();
+    let module = PyModule::from_code(py, code, &file_name, &format!("strategy_{name}"))?;
+    let kwargs: &PyDict = py
+        .import("json")?
+        .call_method1("loads", (kwargs,))?
+        .downcast()?;
+    let object = module.getattr(class)?.call((), Some(kwargs))?;
+
+    Ok(object.into())
+}
+
+/// Returns the `botnode` module importable by strategies
+fn botnode_module(py: Python) -> PyResult<&PyModule> {
+    let module = PyModule::new(py, "botnode")?;
+    module.add_class::<PyOrderIntent>()?;
+    module.add_class::<PyOrderbook>()?;
+    module.add_class::<PyTrade>()?;
+    Ok(module)
+}
+
+/// Formats Python exception including the traceback
+fn format_err(py: Python, err: PyErr) -> String {
+    match err.traceback(py).map(|tb| tb.format()) {
+        Some(Ok(traceback)) => format!("{err}\n{traceback}"),
+        _ => err.to_string(),
+    }
+}
+
+fn unix_seconds(time: DateTime<Utc>) -> f64 {
+    time.timestamp_nanos() as f64 / 1e9
+}
+
+/// `botnode.OrderIntent(market, side, size, price=None)`
+#[pyclass(name = "OrderIntent")]
+#[derive(Clone, Debug)]
+struct PyOrderIntent {
+    #[pyo3(get)]
+    market: String,
+    side: Side,
+    #[pyo3(get)]
+    size: f64,
+    #[pyo3(get)]
+    price: Option<f64>,
+}
+
+#[pymethods]
+impl PyOrderIntent {
+    #[new]
+    #[args(price = "None")]
+    fn new(market: String, side: String, size: f64, price: Option<f64>) -> PyResult<Self> {
+        let side = match side.as_str() {
+            "buy" => Side::Buy,
+            "sell" => Side::Sell,
+            _ => return Err(PyValueError::new_err("side must be \"buy\" or \"sell\"")),
+        };
+
+        Ok(Self {
+            market,
+            side,
+            size,
+            price,
+        })
+    }
+
+    #[getter]
+    fn side(&self) -> &'static str {
+        self.side.as_str()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "OrderIntent({:?}, {:?}, {}, {:?})",
+            self.market,
+            self.side(),
+            self.size,
+            self.price
+        )
+    }
+}
+
+impl From<PyOrderIntent> for OrderIntent {
+    fn from(intent: PyOrderIntent) -> Self {
+        Self {
+            market: intent.market.into_boxed_str(),
+            side: intent.side,
+            price: intent.price,
+            size: intent.size,
+        }
+    }
+}
+
+/// Read-only orderbook snapshot, bids and asks are sorted by price ascending
+#[pyclass(name = "Orderbook")]
+struct PyOrderbook {
+    orderbook: PlainOrderbook<f64>,
+}
+
+#[pymethods]
+impl PyOrderbook {
+    fn best_bid(&self) -> Option<f64> {
+        self.orderbook.bids.price_vec.last().copied()
+    }
+
+    fn best_ask(&self) -> Option<f64> {
+        self.orderbook.asks.price_vec.first().copied()
+    }
+
+    fn mid(&self) -> Option<f64> {
+        Some((self.best_bid()? + self.best_ask()?) / 2.0)
+    }
+
+    fn spread(&self) -> Option<f64> {
+        Some(self.best_ask()? - self.best_bid()?)
+    }
+
+    /// Returns up to `depth` best bids as `(price, size)`, best first
+    fn bids(&self, depth: usize) -> Vec<(f64, f64)> {
+        let bids = &self.orderbook.bids;
+        bids.price_vec
+            .iter()
+            .zip(bids.size_vec.iter())
+            .rev()
+            .take(depth)
+            .map(|(price, size)| (*price, *size))
+            .collect()
+    }
+
+    /// Returns up to `depth` best asks as `(price, size)`, best first
+    fn asks(&self, depth: usize) -> Vec<(f64, f64)> {
+        let asks = &self.orderbook.asks;
+        asks.price_vec
+            .iter()
+            .zip(asks.size_vec.iter())
+            .take(depth)
+            .map(|(price, size)| (*price, *size))
+            .collect()
+    }
+}
+
+/// Trade with exchange time in seconds since UNIX epoch
+#[pyclass(name = "Trade")]
+struct PyTrade {
+    #[pyo3(get)]
+    price: f64,
+    #[pyo3(get)]
+    size: f64,
+    #[pyo3(get)]
+    time: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_py_strategy_callbacks() {
+        let path = std::env::temp_dir().join(format!("botnode-strategy-{}.py", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+import botnode
+
+class Strategy:
+    def __init__(self, size):
+        self.size = size
+
+    def on_orderbook(self, market, orderbook):
+        return [botnode.OrderIntent(market, "buy", self.size, orderbook.best_bid())]
+"#,
+        )
+        .unwrap();
+
+        let config = crate::config::BotnodeConfig::from_toml(&format!(
+            r#"
+            bot_id = 1
+            server_addr = "127.0.0.1:7978"
+
+            [strategy.test]
+            python = {:?}
+            size = 0.5
+            "#,
+            path.to_string_lossy()
+        ))
+        .unwrap();
+        let mut strategy = PyStrategy::load("test", &config.strategy["test"]).unwrap();
+
+        let mut orderbook = PlainOrderbook::new();
+        orderbook.bids.price_vec.push(99.0);
+        orderbook.bids.size_vec.push(1.0);
+
+        assert_eq!(
+            vec![OrderIntent::limit("BTC/USD", Side::Buy, 99.0, 0.5)],
+            strategy.on_orderbook("BTC/USD", &orderbook)
+        );
+        assert!(strategy.on_timer(Utc::now()).is_empty());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
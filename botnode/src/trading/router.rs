@@ -0,0 +1,171 @@
+//! Routing of order intents to the order engine
+
+use std::collections::HashSet;
+
+use crate::{
+    latency::TickStamp,
+    order_engine::{ExchangeRequest, OrderRequest, OrderUpdate},
+    prelude::*,
+    trading::strategy::OrderIntent,
+};
+
+/// Turns order intents of strategies into order requests
+///
+/// Every order gets a client id unique across restarts of the bot, the
+/// router remembers which strategy placed it so that order updates are
+/// passed back to that strategy only.
+pub(crate) struct OrderRouter {
+    exchange_tx: spsc_queue::Producer<ExchangeRequest>,
+    /// Prefix of client ids, makes ids unique across restarts
+    session: i64,
+    next_id: u64,
+    /// Index of the strategy owning each open order
+    owners: HashMap<Box<str>, usize>,
+    /// Markets orders are placed on, all when not set
+    markets: Option<HashSet<Box<str>>>,
+}
+
+impl OrderRouter {
+    pub fn new(exchange_tx: spsc_queue::Producer<ExchangeRequest>) -> Self {
+        Self {
+            exchange_tx,
+            session: Utc::now().timestamp_millis(),
+            next_id: 0,
+            owners: HashMap::new(),
+            markets: None,
+        }
+    }
+
+    /// Restricts orders to given markets, intents on other markets are
+    /// dropped
+    pub fn set_markets(&mut self, markets: &[Box<str>]) {
+        self.markets = Some(markets.iter().cloned().collect());
+    }
+
+    /// Sends order requests for the intents of the strategy at given index,
+    /// returns the number of intents dropped because the order queue is full
+    pub fn route(
+        &mut self,
+        strategy_idx: usize,
+        strategy: &str,
+        intents: Vec<OrderIntent>,
+        tick: Option<&TickStamp>,
+    ) -> usize {
+        let mut dropped = 0;
+
+        for intent in intents {
+            debug!("Strategy {strategy} intent: {intent:?}");
+            if matches!(&self.markets, Some(markets) if !markets.contains(&intent.market)) {
+                warn!(
+                    "Dropping intent of {strategy} on untraded market {}",
+                    intent.market
+                );
+                continue;
+            }
+            self.next_id += 1;
+            let client_id = format!("{strategy}-{}-{}", self.session, self.next_id);
+
+            let mut order = OrderRequest {
+                price: intent.price,
+                ..OrderRequest::market(&client_id, &intent.market, intent.side, intent.size)
+            };
+            if let Some(tick) = tick {
+                order = order.with_tick(tick.clone());
+            }
+
+            match self
+                .exchange_tx
+                .try_push(ExchangeRequest::PlaceOrder(order))
+            {
+                None => {
+                    self.owners.insert(client_id.into_boxed_str(), strategy_idx);
+                }
+                Some(_) => dropped += 1,
+            }
+        }
+
+        dropped
+    }
+
+    /// Returns index of the strategy that placed the order, forgetting the
+    /// order once it is closed
+    pub fn owner(&mut self, update: &OrderUpdate) -> Option<usize> {
+        if update.status.is_closed() {
+            self.owners.remove(&update.client_id)
+        } else {
+            self.owners.get(&update.client_id).copied()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{order_engine::OrderStatus, trading::strategy::Side};
+
+    #[test]
+    fn test_route_intents() {
+        let (exchange_tx, exchange_rx) = spsc_queue::make(1);
+        let mut router = OrderRouter::new(exchange_tx);
+
+        let dropped = router.route(
+            3,
+            "maker",
+            vec![
+                OrderIntent::limit("BTC-PERP", Side::Buy, 100.0, 1.0),
+                OrderIntent::market("BTC-PERP", Side::Sell, 1.0),
+            ],
+            None,
+        );
+        assert_eq!(1, dropped);
+
+        let mut update = match exchange_rx.try_pop() {
+            Some(ExchangeRequest::PlaceOrder(order)) => {
+                assert!(order.client_id.starts_with("maker-"));
+                assert_eq!(Some(100.0), order.price);
+
+                OrderUpdate {
+                    client_id: order.client_id,
+                    exchange_id: None,
+                    market: order.market,
+                    side: order.side,
+                    price: order.price,
+                    size: order.size,
+                    filled_size: 0.0,
+                    status: OrderStatus::Open,
+                    reason: None,
+                }
+            }
+            other => panic!("unexpected request {other:?}"),
+        };
+
+        assert_eq!(Some(3), router.owner(&update));
+        update.status = OrderStatus::Filled;
+        assert_eq!(Some(3), router.owner(&update));
+        assert_eq!(None, router.owner(&update));
+    }
+
+    #[test]
+    fn test_route_untraded_market() {
+        let (exchange_tx, exchange_rx) = spsc_queue::make(2);
+        let mut router = OrderRouter::new(exchange_tx);
+        router.set_markets(&[Box::from("ETH-PERP")]);
+
+        let dropped = router.route(
+            0,
+            "maker",
+            vec![
+                OrderIntent::market("BTC-PERP", Side::Buy, 1.0),
+                OrderIntent::market("ETH-PERP", Side::Buy, 1.0),
+            ],
+            None,
+        );
+
+        assert_eq!(0, dropped);
+        match exchange_rx.try_pop() {
+            Some(ExchangeRequest::PlaceOrder(order)) => assert_eq!("ETH-PERP", &*order.market),
+            other => panic!("unexpected request {other:?}"),
+        }
+        assert!(exchange_rx.try_pop().is_none());
+    }
+}
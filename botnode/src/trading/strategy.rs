@@ -0,0 +1,299 @@
+//! Trading strategies
+//!
+//! Strategies are driven by the trading engine, which calls them on every
+//! orderbook or best bid and offer update, trade, candle, liquidation,
+//! funding rate and open interest update and timer tick. They answer with order intents
+//! that the trading engine turns into orders, state changes of those orders
+//! are passed back to the strategy that placed them.
+
+use botvana::market::{
+    candle::Candle,
+    derivatives::{FundingRate, Liquidation, OpenInterest},
+    orderbook::{Bbo, PlainOrderbook},
+    trade::Trade,
+};
+use chrono::{DateTime, Utc};
+use figment::value::Dict;
+
+use crate::{
+    config::StrategyConfig, order_engine::OrderUpdate, position::Position,
+    trading::indicators::MarketIndicators,
+};
+
+/// Side of an order
+#[derive(Clone, Copy, Debug, Eq, PartialEq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Buy,
+    Sell,
+}
+
+impl Side {
+    /// Returns lower-case name of the side
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Side::Buy => "buy",
+            Side::Sell => "sell",
+        }
+    }
+
+    /// Returns the size signed by the side, negative when selling
+    pub fn signed(&self, size: f64) -> f64 {
+        match self {
+            Side::Buy => size,
+            Side::Sell => -size,
+        }
+    }
+}
+
+/// Order a strategy wants to have placed
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderIntent {
+    pub market: Box<str>,
+    pub side: Side,
+    /// Limit price, `None` for a market order
+    pub price: Option<f64>,
+    pub size: f64,
+}
+
+impl OrderIntent {
+    /// Creates limit order intent
+    pub fn limit(market: &str, side: Side, price: f64, size: f64) -> Self {
+        Self {
+            market: Box::from(market),
+            side,
+            price: Some(price),
+            size,
+        }
+    }
+
+    /// Creates market order intent
+    pub fn market(market: &str, side: Side, size: f64) -> Self {
+        Self {
+            market: Box::from(market),
+            side,
+            price: None,
+            size,
+        }
+    }
+}
+
+/// User-defined trading logic
+///
+/// All callbacks default to producing no intents so strategies only
+/// implement the ones they need.
+pub trait Strategy: Send {
+    /// Returns name of the strategy as used in the configuration
+    fn name(&self) -> &str;
+
+    /// Called when orderbook of the market is updated
+    fn on_orderbook(
+        &mut self,
+        _market: &str,
+        _orderbook: &PlainOrderbook<f64>,
+    ) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    /// Called when best bid or offer of the market changes, instead of
+    /// [`Strategy::on_orderbook`] when market data emits BBO events only
+    fn on_bbo(&mut self, _market: &str, _bbo: &Bbo<f64>) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    /// Called for every trade on the market
+    fn on_trade(&mut self, _market: &str, _trade: &Trade) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    /// Called when a new funding rate of the perpetual futures market is
+    /// received
+    fn on_funding_rate(&mut self, _market: &str, _funding: &FundingRate) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    /// Called when a new open interest of the futures market is received
+    fn on_open_interest(
+        &mut self,
+        _market: &str,
+        _open_interest: &OpenInterest,
+    ) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    /// Called after indicators of the market were updated with its trades,
+    /// when `indicators` are enabled
+    fn on_indicators(&mut self, _market: &str, _indicators: &MarketIndicators) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    /// Called when a candle of the market closes, candles are built when
+    /// `market_data.candle_intervals_secs` are configured
+    fn on_candle(&mut self, _market: &str, _candle: &Candle) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    /// Called for every position liquidated on the market
+    fn on_liquidation(&mut self, _market: &str, _liquidation: &Liquidation) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    /// Called periodically by the trading engine
+    fn on_timer(&mut self, _now: DateTime<Utc>) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    /// Called when order placed by the strategy changes its state, e.g. it
+    /// is acknowledged, filled or rejected
+    fn on_order_update(&mut self, _update: &OrderUpdate) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    /// Called when a position of the bot changes, after fills and as it is
+    /// marked to new prices
+    fn on_position(&mut self, _position: &Position) -> Vec<OrderIntent> {
+        Vec::new()
+    }
+
+    /// Called when botvana-server pushes new parameters of the strategy
+    fn on_params(&mut self, _params: &Dict) {}
+
+    /// Replaces the implementation of the strategy while botnode runs,
+    /// from `path` or from where it was loaded. The strategy keeps running
+    /// the old implementation when it fails.
+    fn reload(&mut self, _path: Option<&str>) -> Result<(), StrategyError> {
+        Err(StrategyError::new(format!(
+            "strategy {} can't be reloaded",
+            self.name()
+        )))
+    }
+}
+
+/// Creates strategy added programmatically, called every time the trading
+/// engine is spawned
+pub type StrategyFactory = std::sync::Arc<dyn Fn() -> Box<dyn Strategy> + Send + Sync>;
+
+/// Builds the enabled strategies from the configuration
+///
+/// Strategies with a `plugin` parameter are loaded from a plugin library,
+/// with `python` from a Python file and with `wasm` from a WebAssembly
+/// module. Strategies that fail to load are logged
+/// and skipped.
+pub fn load_strategies<'a>(
+    strategies: impl IntoIterator<Item = (&'a String, &'a StrategyConfig)>,
+) -> Vec<Box<dyn Strategy>> {
+    strategies
+        .into_iter()
+        .filter(|(_, config)| config.enabled)
+        .filter_map(|(name, config)| match load_strategy(name, config) {
+            Ok(strategy) => Some(strategy),
+            Err(e) => {
+                tracing::error!("Failed to load strategy {name}: {e}");
+                None
+            }
+        })
+        .collect()
+}
+
+fn load_strategy(name: &str, config: &StrategyConfig) -> Result<Box<dyn Strategy>, StrategyError> {
+    if config.params.contains_key("plugin") {
+        return Ok(Box::new(super::plugin::PluginStrategy::load(name, config)?));
+    }
+
+    if config.params.contains_key("python") {
+        #[cfg(feature = "python")]
+        return Ok(Box::new(super::python::PyStrategy::load(name, config)?));
+
+        #[cfg(not(feature = "python"))]
+        return Err(StrategyError::new(
+            "python strategies require botnode built with the `python` feature",
+        ));
+    }
+
+    if config.params.contains_key("wasm") {
+        #[cfg(feature = "wasm")]
+        return Ok(Box::new(super::wasm::WasmStrategy::load(name, config)?));
+
+        #[cfg(not(feature = "wasm"))]
+        return Err(StrategyError::new(
+            "wasm strategies require botnode built with the `wasm` feature",
+        ));
+    }
+
+    Err(StrategyError::new(format!("unknown strategy {name}")))
+}
+
+/// Error loading a strategy
+#[derive(Debug, thiserror::Error)]
+#[error("Strategy error: {source}")]
+pub struct StrategyError {
+    pub source: Box<dyn std::error::Error>,
+}
+
+impl StrategyError {
+    pub fn new<T: ToString>(reason: T) -> Self {
+        Self {
+            source: reason.to_string().into(),
+        }
+    }
+
+    pub fn with_source(err: impl std::error::Error + 'static) -> Self {
+        Self {
+            source: Box::new(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Crossing;
+
+    impl Strategy for Crossing {
+        fn name(&self) -> &str {
+            "crossing"
+        }
+
+        fn on_orderbook(
+            &mut self,
+            market: &str,
+            orderbook: &PlainOrderbook<f64>,
+        ) -> Vec<OrderIntent> {
+            match orderbook.asks.price_vec.first() {
+                Some(ask) => vec![OrderIntent::limit(market, Side::Buy, *ask, 1.0)],
+                None => Vec::new(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_strategy_default_callbacks() {
+        let mut strategy = Crossing;
+        let mut orderbook = PlainOrderbook::new();
+        orderbook.asks.price_vec.push(101.0);
+        orderbook.asks.size_vec.push(2.0);
+
+        assert_eq!(
+            vec![OrderIntent::limit("BTC/USD", Side::Buy, 101.0, 1.0)],
+            strategy.on_orderbook("BTC/USD", &orderbook)
+        );
+        assert!(strategy.on_timer(Utc::now()).is_empty());
+    }
+
+    #[test]
+    fn test_load_strategies_skips_unknown() {
+        let config = crate::config::BotnodeConfig::from_toml(
+            r#"
+            bot_id = 1
+            server_addr = "127.0.0.1:7978"
+
+            [strategy.unknown]
+            spread_bps = 5
+            "#,
+        )
+        .unwrap();
+
+        assert!(load_strategies(&config.strategy).is_empty());
+    }
+}
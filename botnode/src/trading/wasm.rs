@@ -0,0 +1,441 @@
+//! WebAssembly strategies
+//!
+//! A strategy section with a `wasm` path loads a module compiled to
+//! WebAssembly (or written in the text format) and runs it with wasmtime,
+//! all other parameters are passed to `botnode_init` as a JSON object. The
+//! module can be swapped for a new build while botnode runs with the
+//! `ReloadStrategy` command from botvana-server, the state of the old
+//! instance is dropped.
+//!
+//! Modules implement version [`WASM_ABI_VERSION`] of the host API and
+//! export:
+//!
+//! * `memory`
+//! * `botnode_abi_version() -> i32`
+//! * `botnode_alloc(len: i32) -> i32` returning an 8-byte aligned buffer
+//!   of `len` bytes, called once per callback for all its inputs, which
+//!   are only read until the callback returns
+//! * `botnode_init(params: i32, params_len: i32) -> i32` returning 0 when
+//!   the strategy is ready
+//! * optionally `botnode_on_orderbook(market: i32, market_len: i32,
+//!   levels: i32, bids: i32, asks: i32)`, `levels` holds `bids` bid prices,
+//!   `bids` bid sizes, `asks` ask prices and `asks` ask sizes as `f64`,
+//!   prices ascending
+//! * optionally `botnode_on_trade(market: i32, market_len: i32, price: f64,
+//!   size: f64, time_ns: i64)`
+//! * optionally `botnode_on_timer(now_ns: i64)`
+//! * optionally `botnode_on_params(params: i32, params_len: i32)` called
+//!   with JSON of the parameters pushed by botvana-server
+//!
+//! Strings are UTF-8. Callbacks place orders by calling the imported
+//! `botnode.place_order(market: i32, market_len: i32, side: i32, price: f64,
+//! size: f64)`, side 0 buys and 1 sells, `price` is NaN for market orders.
+//! `botnode.log(msg: i32, msg_len: i32)` logs a message.
+
+use std::path::Path;
+
+use botvana::market::{orderbook::PlainOrderbook, trade::Trade};
+use chrono::{DateTime, Utc};
+use figment::value::Dict;
+use wasmtime::{
+    Caller, Engine, Instance, Linker, Memory, Module, Store, TypedFunc, WasmParams, WasmResults,
+};
+
+use super::strategy::{OrderIntent, Side, Strategy, StrategyError};
+use crate::config::StrategyConfig;
+
+/// Version of the host API implemented by this botnode
+pub const WASM_ABI_VERSION: i32 = 1;
+
+/// Strategy implemented by a WebAssembly module
+pub struct WasmStrategy {
+    name: String,
+    path: String,
+    /// JSON of the parameters the instances are initialized with
+    params: String,
+    engine: Engine,
+    instance: WasmInstance,
+}
+
+impl WasmStrategy {
+    /// Loads the module and initializes it with the configured parameters
+    pub fn load(name: &str, config: &StrategyConfig) -> Result<Self, StrategyError> {
+        let mut params = config.params.clone();
+        let path = params
+            .remove("wasm")
+            .and_then(|path| path.into_string())
+            .ok_or_else(|| StrategyError::new("`wasm` must be a path to the strategy module"))?;
+        let params = serde_json::to_string(&params).map_err(StrategyError::with_source)?;
+        let engine = Engine::default();
+        let instance = WasmInstance::new(&engine, name, Path::new(&path), &params)?;
+
+        Ok(Self {
+            name: name.to_string(),
+            path,
+            params,
+            engine,
+            instance,
+        })
+    }
+}
+
+impl Strategy for WasmStrategy {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn on_orderbook(&mut self, market: &str, orderbook: &PlainOrderbook<f64>) -> Vec<OrderIntent> {
+        let callback = match self.instance.on_orderbook {
+            Some(callback) => callback,
+            None => return Vec::new(),
+        };
+        let levels: Vec<u8> = [
+            &orderbook.bids.price_vec,
+            &orderbook.bids.size_vec,
+            &orderbook.asks.price_vec,
+            &orderbook.asks.size_vec,
+        ]
+        .iter()
+        .flat_map(|values| values.iter())
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+        let (bids, asks) = (orderbook.bids.len() as i32, orderbook.asks.len() as i32);
+
+        self.instance.call(&self.name, "on_orderbook", |instance| {
+            let (market, levels) = instance.write_inputs(market.as_bytes(), &levels)?;
+            callback.call(
+                &mut instance.store,
+                (market.0, market.1, levels.0, bids, asks),
+            )?;
+            Ok(())
+        })
+    }
+
+    fn on_trade(&mut self, market: &str, trade: &Trade) -> Vec<OrderIntent> {
+        let callback = match self.instance.on_trade {
+            Some(callback) => callback,
+            None => return Vec::new(),
+        };
+        let args = (trade.price, trade.size, trade.time.timestamp_nanos());
+
+        self.instance.call(&self.name, "on_trade", |instance| {
+            let (market, _) = instance.write_inputs(market.as_bytes(), &[])?;
+            callback.call(
+                &mut instance.store,
+                (market.0, market.1, args.0, args.1, args.2),
+            )?;
+            Ok(())
+        })
+    }
+
+    fn on_timer(&mut self, now: DateTime<Utc>) -> Vec<OrderIntent> {
+        let callback = match self.instance.on_timer {
+            Some(callback) => callback,
+            None => return Vec::new(),
+        };
+
+        self.instance.call(&self.name, "on_timer", |instance| {
+            callback.call(&mut instance.store, now.timestamp_nanos())?;
+            Ok(())
+        })
+    }
+
+    fn on_params(&mut self, params: &Dict) {
+        let callback = match self.instance.on_params {
+            Some(callback) => callback,
+            None => return,
+        };
+        let params = match serde_json::to_string(params) {
+            Ok(params) => params,
+            Err(e) => {
+                tracing::error!("Strategy {} parameters can't be serialized: {e}", self.name);
+                return;
+            }
+        };
+
+        let intents = self.instance.call(&self.name, "on_params", |instance| {
+            let (params, _) = instance.write_inputs(params.as_bytes(), &[])?;
+            callback.call(&mut instance.store, params)?;
+            Ok(())
+        });
+        if !intents.is_empty() {
+            tracing::warn!(
+                "Strategy {} placed orders from on_params, they are ignored",
+                self.name
+            );
+        }
+    }
+
+    fn reload(&mut self, path: Option<&str>) -> Result<(), StrategyError> {
+        let path = path.unwrap_or(&self.path).to_string();
+        self.instance =
+            WasmInstance::new(&self.engine, &self.name, Path::new(&path), &self.params)?;
+        self.path = path;
+
+        Ok(())
+    }
+}
+
+/// Data of the store the host functions have access to
+#[derive(Default)]
+struct HostState {
+    /// Intents placed during the current callback
+    intents: Vec<OrderIntent>,
+}
+
+/// Instantiated module with its exports
+struct WasmInstance {
+    store: Store<HostState>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    on_orderbook: Option<TypedFunc<(i32, i32, i32, i32, i32), ()>>,
+    on_trade: Option<TypedFunc<(i32, i32, f64, f64, i64), ()>>,
+    on_timer: Option<TypedFunc<i64, ()>>,
+    on_params: Option<TypedFunc<(i32, i32), ()>>,
+}
+
+impl WasmInstance {
+    /// Compiles and instantiates the module, checks its ABI version and
+    /// initializes it with the parameters
+    fn new(engine: &Engine, name: &str, path: &Path, params: &str) -> Result<Self, StrategyError> {
+        let module = Module::from_file(engine, path).map_err(StrategyError::new)?;
+        let mut store = Store::new(engine, HostState::default());
+        let instance = linker(engine)?
+            .instantiate(&mut store, &module)
+            .map_err(StrategyError::new)?;
+
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| StrategyError::new("module doesn't export `memory`"))?;
+        let abi_version = required::<(), i32>(&instance, &mut store, "botnode_abi_version")?
+            .call(&mut store, ())
+            .map_err(StrategyError::new)?;
+        if abi_version != WASM_ABI_VERSION {
+            return Err(StrategyError::new(format!(
+                "{} implements host API {abi_version}, botnode supports {WASM_ABI_VERSION}",
+                path.display()
+            )));
+        }
+        let init = required::<(i32, i32), i32>(&instance, &mut store, "botnode_init")?;
+
+        let mut wasm = Self {
+            alloc: required(&instance, &mut store, "botnode_alloc")?,
+            on_orderbook: optional(&instance, &mut store, "botnode_on_orderbook")?,
+            on_trade: optional(&instance, &mut store, "botnode_on_trade")?,
+            on_timer: optional(&instance, &mut store, "botnode_on_timer")?,
+            on_params: optional(&instance, &mut store, "botnode_on_params")?,
+            store,
+            memory,
+        };
+
+        let (params, _) = wasm
+            .write_inputs(params.as_bytes(), &[])
+            .map_err(StrategyError::new)?;
+        match init
+            .call(&mut wasm.store, params)
+            .map_err(StrategyError::new)?
+        {
+            0 => Ok(wasm),
+            code => Err(StrategyError::new(format!(
+                "{} failed to initialize strategy {name} with code {code}",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Writes the inputs of a callback into a buffer allocated by the
+    /// module, returns pointers and lengths of both, `second` follows
+    /// `first` 8-byte aligned
+    fn write_inputs(
+        &mut self,
+        first: &[u8],
+        second: &[u8],
+    ) -> anyhow::Result<((i32, i32), (i32, i32))> {
+        let offset = (first.len() + 7) & !7;
+        let len = i32::try_from(offset + second.len())?;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        let start = usize::try_from(ptr)?;
+
+        self.memory.write(&mut self.store, start, first)?;
+        self.memory.write(&mut self.store, start + offset, second)?;
+
+        Ok((
+            (ptr, first.len() as i32),
+            (ptr + offset as i32, second.len() as i32),
+        ))
+    }
+
+    /// Runs the callback and returns the intents it placed, errors and
+    /// traps are logged and drop the intents
+    fn call(
+        &mut self,
+        strategy: &str,
+        callback: &str,
+        f: impl FnOnce(&mut Self) -> anyhow::Result<()>,
+    ) -> Vec<OrderIntent> {
+        self.store.data_mut().intents.clear();
+
+        match f(self) {
+            Ok(()) => std::mem::take(&mut self.store.data_mut().intents),
+            Err(e) => {
+                tracing::error!("Strategy {strategy} {callback} failed: {e}");
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Returns linker providing the `botnode` host functions
+fn linker(engine: &Engine) -> Result<Linker<HostState>, StrategyError> {
+    let mut linker = Linker::new(engine);
+
+    linker
+        .func_wrap(
+            "botnode",
+            "place_order",
+            |mut caller: Caller<'_, HostState>,
+             market: i32,
+             market_len: i32,
+             side: i32,
+             price: f64,
+             size: f64| {
+                let market = match read_str(&mut caller, market, market_len) {
+                    Some(market) => market,
+                    None => return,
+                };
+                if let Some(intent) = convert_intent(&market, side, price, size) {
+                    caller.data_mut().intents.push(intent);
+                }
+            },
+        )
+        .map_err(StrategyError::new)?;
+    linker
+        .func_wrap(
+            "botnode",
+            "log",
+            |mut caller: Caller<'_, HostState>, msg: i32, msg_len: i32| {
+                if let Some(msg) = read_str(&mut caller, msg, msg_len) {
+                    tracing::info!("wasm strategy: {msg}");
+                }
+            },
+        )
+        .map_err(StrategyError::new)?;
+
+    Ok(linker)
+}
+
+/// Reads string from the memory of the calling module
+fn read_str(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> Option<String> {
+    let memory = caller.get_export("memory")?.into_memory()?;
+    let start = usize::try_from(ptr).ok()?;
+    let end = start.checked_add(usize::try_from(len).ok()?)?;
+    let bytes = memory.data(&*caller).get(start..end)?;
+
+    std::str::from_utf8(bytes).ok().map(str::to_string)
+}
+
+/// Converts order placed by the module, malformed orders are skipped
+fn convert_intent(market: &str, side: i32, price: f64, size: f64) -> Option<OrderIntent> {
+    if !size.is_finite() || size <= 0.0 {
+        return None;
+    }
+    let side = match side {
+        0 => Side::Buy,
+        1 => Side::Sell,
+        _ => return None,
+    };
+
+    Some(OrderIntent {
+        market: Box::from(market),
+        side,
+        price: Some(price).filter(|price| !price.is_nan()),
+        size,
+    })
+}
+
+fn required<P: WasmParams, R: WasmResults>(
+    instance: &Instance,
+    store: &mut Store<HostState>,
+    name: &str,
+) -> Result<TypedFunc<P, R>, StrategyError> {
+    optional(instance, store, name)?
+        .ok_or_else(|| StrategyError::new(format!("module doesn't export `{name}`")))
+}
+
+fn optional<P: WasmParams, R: WasmResults>(
+    instance: &Instance,
+    store: &mut Store<HostState>,
+    name: &str,
+) -> Result<Option<TypedFunc<P, R>>, StrategyError> {
+    match instance.get_func(&mut *store, name) {
+        Some(func) => func
+            .typed::<P, R, _>(&*store)
+            .map(Some)
+            .map_err(|e| StrategyError::new(format!("`{name}` has wrong signature: {e}"))),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(path: &str) -> StrategyConfig {
+        let config = crate::config::BotnodeConfig::from_toml(&format!(
+            r#"
+            bot_id = 1
+            server_addr = "127.0.0.1:7978"
+
+            [strategy.example]
+            wasm = "{path}"
+            size = 0.01
+            "#
+        ))
+        .unwrap();
+
+        config.strategy["example"].clone()
+    }
+
+    fn example_path() -> String {
+        format!("{}/../strategies/example.wat", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    #[test]
+    fn test_example_strategy() {
+        let mut strategy = WasmStrategy::load("example", &config(&example_path())).unwrap();
+        let mut orderbook = PlainOrderbook::new();
+        orderbook.bids.price_vec.extend([99.0, 100.0]);
+        orderbook.bids.size_vec.extend([1.0, 1.0]);
+        orderbook.asks.price_vec.extend([104.0, 105.0]);
+        orderbook.asks.size_vec.extend([1.0, 1.0]);
+
+        assert_eq!(
+            vec![
+                OrderIntent::limit("BTC/USD", Side::Buy, 101.0, 0.01),
+                OrderIntent::limit("BTC/USD", Side::Sell, 103.0, 0.01),
+            ],
+            strategy.on_orderbook("BTC/USD", &orderbook)
+        );
+        assert!(strategy.on_timer(Utc::now()).is_empty());
+
+        strategy.reload(None).unwrap();
+        assert_eq!(2, strategy.on_orderbook("BTC/USD", &orderbook).len());
+        assert!(strategy.reload(Some("/nonexistent/strategy.wasm")).is_err());
+        assert_eq!(2, strategy.on_orderbook("BTC/USD", &orderbook).len());
+    }
+
+    #[test]
+    fn test_convert_intent() {
+        assert_eq!(
+            Some(OrderIntent::market("BTC/USD", Side::Sell, 2.0)),
+            convert_intent("BTC/USD", 1, f64::NAN, 2.0)
+        );
+        assert_eq!(None, convert_intent("BTC/USD", 2, 1.0, 1.0));
+        assert_eq!(None, convert_intent("BTC/USD", 0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn test_load_missing_module() {
+        assert!(WasmStrategy::load("example", &config("/nonexistent/strategy.wasm")).is_err());
+    }
+}
@@ -1 +1,4 @@
+//! Utilities shared by the engines
 
+pub mod backoff;
+pub mod rotating_log;
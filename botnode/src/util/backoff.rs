@@ -0,0 +1,177 @@
+//! Exponential backoff with jitter
+//!
+//! Used between reconnect attempts to botvana-server and to the exchanges.
+//! Every attempt waits `multiplier` times longer than the previous one, up
+//! to `max_delay_ms`, and a random part of the delay up to `jitter` is taken
+//! off so that bots disconnected at the same time don't reconnect at once.
+
+use std::time::{Duration, SystemTime};
+
+use serde::Deserialize;
+
+/// Backoff policy
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackoffConfig {
+    /// Milliseconds to wait before the first retry
+    pub initial_delay_ms: u64,
+    /// Factor the delay grows by with every retry
+    pub multiplier: f64,
+    /// Milliseconds the delay never exceeds
+    pub max_delay_ms: u64,
+    /// Largest fraction of the delay randomly taken off, between 0 and 1
+    pub jitter: f64,
+    /// Retries after which to give up, retries forever when not set
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self {
+            initial_delay_ms: 1000,
+            multiplier: 2.0,
+            max_delay_ms: 60_000,
+            jitter: 0.2,
+            max_attempts: None,
+        }
+    }
+}
+
+impl BackoffConfig {
+    /// Returns description of the first invalid value
+    pub fn validate(&self) -> Result<(), String> {
+        if self.initial_delay_ms == 0 {
+            return Err("initial_delay_ms must be positive".to_string());
+        }
+        if self.max_delay_ms < self.initial_delay_ms {
+            return Err("max_delay_ms must not be less than initial_delay_ms".to_string());
+        }
+        if self.multiplier.is_nan() || self.multiplier < 1.0 {
+            return Err("multiplier must be at least 1".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err("jitter must be between 0 and 1".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// State of the backoff between consecutive failed attempts
+#[derive(Debug)]
+pub struct Backoff {
+    config: BackoffConfig,
+    attempts: u32,
+    delay: Duration,
+    rng: u64,
+}
+
+impl Backoff {
+    pub fn new(config: BackoffConfig) -> Self {
+        let seed = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64;
+
+        Self {
+            delay: Duration::from_millis(config.initial_delay_ms),
+            config,
+            attempts: 0,
+            // xorshift state must not be zero
+            rng: seed | 1,
+        }
+    }
+
+    /// Returns the delay before the next attempt, `None` when the attempts
+    /// are exhausted
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if matches!(self.config.max_attempts, Some(max) if self.attempts >= max) {
+            return None;
+        }
+        self.attempts += 1;
+
+        let delay = self.delay;
+        let max_delay = Duration::from_millis(self.config.max_delay_ms);
+        self.delay = delay.mul_f64(self.config.multiplier).min(max_delay);
+
+        Some(delay.mul_f64(1.0 - self.config.jitter * self.random()))
+    }
+
+    /// Returns the number of attempts since the last reset
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Starts over from the initial delay, called after a successful attempt
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+        self.delay = Duration::from_millis(self.config.initial_delay_ms);
+    }
+
+    /// Returns pseudo-random number in `[0, 1)`
+    fn random(&mut self) -> f64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+
+        (self.rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_delays() {
+        let mut backoff = Backoff::new(BackoffConfig {
+            initial_delay_ms: 100,
+            multiplier: 2.0,
+            max_delay_ms: 500,
+            jitter: 0.0,
+            max_attempts: Some(5),
+        });
+
+        let delays: Vec<_> = std::iter::from_fn(|| backoff.next_delay())
+            .map(|delay| delay.as_millis())
+            .collect();
+        assert_eq!(vec![100, 200, 400, 500, 500], delays);
+        assert_eq!(None, backoff.next_delay());
+
+        backoff.reset();
+        assert_eq!(Some(Duration::from_millis(100)), backoff.next_delay());
+    }
+
+    #[test]
+    fn test_backoff_jitter() {
+        let mut backoff = Backoff::new(BackoffConfig {
+            initial_delay_ms: 1000,
+            multiplier: 1.0,
+            max_delay_ms: 1000,
+            jitter: 0.5,
+            max_attempts: None,
+        });
+
+        for _ in 0..100 {
+            let delay = backoff.next_delay().unwrap();
+            assert!(delay > Duration::from_millis(500) && delay <= Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn test_backoff_config_validate() {
+        assert!(BackoffConfig::default().validate().is_ok());
+        assert!(BackoffConfig {
+            jitter: 1.5,
+            ..BackoffConfig::default()
+        }
+        .validate()
+        .is_err());
+        assert!(BackoffConfig {
+            multiplier: 0.5,
+            ..BackoffConfig::default()
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -0,0 +1,218 @@
+//! Size and time rotated NDJSON log
+//!
+//! Records are written as newline-delimited JSON into `<name>.log` in the
+//! log directory. Once the file grows over the size limit or gets older than
+//! the age limit it is renamed to `<name>-<timestamp>.log` and a new file is
+//! started.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Rotating log writer
+#[derive(Debug)]
+pub struct RotatingLog {
+    dir: PathBuf,
+    name: &'static str,
+    max_bytes: u64,
+    max_age: Duration,
+    writer: BufWriter<File>,
+    written: u64,
+    opened_at: Instant,
+}
+
+impl RotatingLog {
+    /// Opens the log, appending to the current file if it exists
+    pub fn open(
+        dir: &Path,
+        name: &'static str,
+        max_bytes: u64,
+        max_age: Duration,
+    ) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(current_path(dir, name))?;
+        let written = file.metadata()?.len();
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            name,
+            max_bytes,
+            max_age,
+            writer: BufWriter::new(file),
+            written,
+            opened_at: Instant::now(),
+        })
+    }
+
+    /// Returns path of the file currently written to
+    pub fn current_path(&self) -> PathBuf {
+        current_path(&self.dir, self.name)
+    }
+
+    /// Writes single record, rotating the log first when needed
+    pub fn write<T: Serialize>(&mut self, record: &T) -> io::Result<()> {
+        if self.written >= self.max_bytes || self.opened_at.elapsed() >= self.max_age {
+            self.rotate()?;
+        }
+
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.writer.write_all(&line)?;
+        self.written += line.len() as u64;
+
+        Ok(())
+    }
+
+    /// Flushes buffered records to the file
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    /// Renames the current file and starts a new one
+    pub fn rotate(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or_default();
+        let current = self.current_path();
+        let mut rotated = self.dir.join(format!("{}-{timestamp}.log", self.name));
+        // Rotations within the same millisecond get a counter suffix
+        let mut n = 1;
+        while rotated.exists() {
+            rotated = self.dir.join(format!("{}-{timestamp}-{n}.log", self.name));
+            n += 1;
+        }
+        fs::rename(&current, rotated)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&current)?;
+        self.writer = BufWriter::new(file);
+        self.written = 0;
+        self.opened_at = Instant::now();
+
+        Ok(())
+    }
+}
+
+fn current_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{name}.log"))
+}
+
+/// Returns files of the log in the directory in the order they were written,
+/// rotated files by their timestamps followed by the current file
+pub fn log_files(dir: &Path, name: &str) -> io::Result<Vec<PathBuf>> {
+    let current = format!("{name}.log");
+    let mut rotated = Vec::new();
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let file_name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(file_name) => file_name,
+            None => continue,
+        };
+        if file_name == current {
+            files.push(path);
+            continue;
+        }
+        // `<name>-<timestamp>.log` or `<name>-<timestamp>-<n>.log`
+        let order = file_name
+            .strip_prefix(name)
+            .and_then(|file_name| file_name.strip_prefix('-'))
+            .and_then(|file_name| file_name.strip_suffix(".log"))
+            .and_then(|suffix| {
+                let mut parts = suffix.splitn(2, '-').map(str::parse::<u128>);
+                match (parts.next(), parts.next()) {
+                    (Some(Ok(timestamp)), None) => Some((timestamp, 0)),
+                    (Some(Ok(timestamp)), Some(Ok(n))) => Some((timestamp, n)),
+                    _ => None,
+                }
+            });
+        if let Some(order) = order {
+            rotated.push((order, path));
+        }
+    }
+    rotated.sort();
+
+    Ok(rotated
+        .into_iter()
+        .map(|(_, path)| path)
+        .chain(files)
+        .collect())
+}
+
+/// Reads records of all files of the log in the directory in the order they
+/// were written
+///
+/// Files that can't be opened are logged and skipped, lines that can't be
+/// read or parsed are returned as errors. Fails when the directory holds no
+/// file of the log.
+pub fn read_log<T: DeserializeOwned>(
+    dir: &Path,
+    name: &str,
+) -> io::Result<impl Iterator<Item = io::Result<T>>> {
+    let files = log_files(dir, name)?;
+    if files.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no {name} logs in {}", dir.display()),
+        ));
+    }
+
+    let lines = files.into_iter().flat_map(|path| {
+        let lines = match File::open(&path) {
+            Ok(file) => Some(BufReader::new(file).lines()),
+            Err(e) => {
+                tracing::error!("Failed to open {}: {e}", path.display());
+                None
+            }
+        };
+
+        lines.into_iter().flatten()
+    });
+
+    Ok(lines.map(|line| Ok(serde_json::from_str::<T>(&line?)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "botnode-rotating-log-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_rotating_log_rotates_on_size() {
+        let dir = test_dir("size");
+        let mut log = RotatingLog::open(&dir, "test", 1, Duration::from_secs(60)).unwrap();
+
+        for i in 0..3 {
+            log.write(&i).unwrap();
+        }
+        log.flush().unwrap();
+
+        assert_eq!(3, fs::read_dir(&dir).unwrap().count());
+        assert_eq!("2\n", fs::read_to_string(log.current_path()).unwrap());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,503 @@
+//! HTTP API for managing the bots
+//!
+//! * `GET /dashboard` serves a page showing the connected bots, their feed
+//!   message rates, positions and P&L, refreshed every second
+//! * `GET /bots` lists the connected bots with their latest status report,
+//!   positions and P&L
+//! * `GET /bots/:id` returns status report, positions and metrics of a
+//!   connected bot
+//! * `GET /bots/:id/markets` returns the markets the bot is subscribed to,
+//!   by exchange
+//! * `GET /bots/:id/events` and `GET /events` return the recent events of
+//!   the bot or of all bots, at most `?limit=` of them (100 by default)
+//! * `GET /markets` returns the markets known to the server
+//! * `GET /registry` returns every bot the server has seen with its last
+//!   configuration and last seen time, connected or not
+//! * `GET /bots/:id/commands` returns history of the commands of the bot,
+//!   at most `?limit=` of them
+//! * `POST /bots/:id/commands` queues a command for the bot, e.g.
+//!   `{"command": "pause"}` or
+//!   `{"command": "kill_switch", "tripped": true, "reason": "..."}` or
+//!   `{"command": "reload_strategy", "strategy": "...", "path": "..."}` or
+//!   `{"command": "subscribe", "markets": ["..."]}` (`unsubscribe` alike) or
+//!   `{"command": "orderbook_snapshot", "market": "..."}`
+//! * `GET /bots/:id/orderbooks` returns the latest orderbook snapshots the
+//!   bot sent in response to `orderbook_snapshot`, of `?market=` when set
+//!
+//! Commands are answered with 202 once queued, they are sent to the bot
+//! over its connection shortly after.
+
+use serde::Deserialize;
+use serde_json::json;
+use tide::{Request, Response, StatusCode};
+use tracing::{error, info};
+
+use crate::{config::ApiServerConfig, store::Store};
+use botvana::{
+    net::msg::{BotId, Message, StrategyReload},
+    state::{BotEventKind, GlobalState},
+};
+
+/// Page of the dashboard, it polls `/bots`
+const DASHBOARD: &str = include_str!("../static/dashboard.html");
+
+/// Number of records returned when the request doesn't set `limit`
+const DEFAULT_LIMIT: usize = 100;
+
+/// Command sent to a bot through the API
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum BotCommand {
+    /// Strategies stop trading until resumed
+    Pause,
+    /// Strategies trade again
+    Resume,
+    /// Open orders are cancelled and positions closed
+    Flatten,
+    /// The bot shuts down
+    Shutdown,
+    /// Trips or resets the kill switch
+    KillSwitch { tripped: bool, reason: String },
+    /// Reloads the strategy, from `path` when set
+    ReloadStrategy {
+        strategy: String,
+        path: Option<String>,
+    },
+    /// Subscribes market data of the markets
+    Subscribe { markets: Box<[Box<str>]> },
+    /// Stops market data of the markets
+    Unsubscribe { markets: Box<[Box<str>]> },
+    /// Requests the orderbook of the market held by the bot
+    OrderbookSnapshot { market: Box<str> },
+}
+
+impl BotCommand {
+    /// Returns the message carrying the command to the bot
+    pub fn into_message(self) -> Message {
+        match self {
+            BotCommand::Pause => Message::PauseTrading,
+            BotCommand::Resume => Message::ResumeTrading,
+            BotCommand::Flatten => Message::FlattenPositions,
+            BotCommand::Shutdown => Message::Shutdown,
+            BotCommand::KillSwitch { tripped, reason } => Message::kill_switch(tripped, reason),
+            BotCommand::ReloadStrategy { strategy, path } => {
+                Message::ReloadStrategy(StrategyReload { strategy, path })
+            }
+            BotCommand::Subscribe { markets } => Message::SubscribeMarkets(markets),
+            BotCommand::Unsubscribe { markets } => Message::UnsubscribeMarkets(markets),
+            BotCommand::OrderbookSnapshot { market } => Message::GetOrderbookSnapshot(market),
+        }
+    }
+}
+
+/// State shared by the API handlers
+#[derive(Clone, Debug)]
+pub struct ApiState {
+    pub global: GlobalState,
+    pub store: Store,
+}
+
+#[derive(Deserialize)]
+struct LimitQuery {
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct MarketQuery {
+    market: Option<Box<str>>,
+}
+
+/// Builds the tide application serving the API
+pub fn app(state: ApiState) -> tide::Server<ApiState> {
+    let mut app = tide::with_state(state);
+
+    app.at("/").get(|req: Request<ApiState>| async move {
+        let connected_bots: Vec<_> = req
+            .state()
+            .global
+            .connected_bots()
+            .iter()
+            .map(|bot_id| bot_id.0)
+            .collect();
+
+        Ok(json!({
+            "connected_bots": connected_bots,
+        }))
+    });
+
+    app.at("/dashboard")
+        .get(|_req: Request<ApiState>| async move {
+            Ok(Response::builder(StatusCode::Ok)
+                .content_type(tide::http::mime::HTML)
+                .body(DASHBOARD)
+                .build())
+        });
+
+    app.at("/bots").get(|req: Request<ApiState>| async move {
+        let state = &req.state().global;
+        let bots: Vec<_> = state
+            .connected_bots()
+            .into_iter()
+            .map(|bot_id| {
+                let status = state.bot_status(&bot_id);
+                let positions = state.bot_positions(&bot_id);
+                let (realized, unrealized) = positions.iter().fold((0.0, 0.0), |pnl, p| {
+                    (pnl.0 + p.realized_pnl, pnl.1 + p.unrealized_pnl)
+                });
+                json!({
+                    "bot_id": bot_id,
+                    "healthy": status.as_ref().map(|status| status.healthy()),
+                    "status": status,
+                    "positions": positions,
+                    "pnl": {
+                        "realized": realized,
+                        "unrealized": unrealized,
+                    },
+                })
+            })
+            .collect();
+
+        Ok(json!(bots))
+    });
+
+    app.at("/bots/:id")
+        .get(|req: Request<ApiState>| async move {
+            let bot_id = connected_bot(&req)?;
+            let state = &req.state().global;
+
+            Ok(json!({
+                "bot_id": bot_id,
+                "status": state.bot_status(&bot_id),
+                "positions": state.bot_positions(&bot_id),
+                "metrics": state.bot_metrics(&bot_id),
+            }))
+        });
+
+    app.at("/bots/:id/markets")
+        .get(|req: Request<ApiState>| async move {
+            let bot_id = connected_bot(&req)?;
+            let markets: Vec<_> = req
+                .state()
+                .global
+                .bot_status(&bot_id)
+                .map(|status| status.feeds.into_vec())
+                .unwrap_or_default()
+                .into_iter()
+                .map(|feed| {
+                    json!({
+                        "exchange": feed.exchange,
+                        "markets": feed.markets,
+                    })
+                })
+                .collect();
+
+            Ok(json!(markets))
+        });
+
+    app.at("/bots/:id/orderbooks")
+        .get(|req: Request<ApiState>| async move {
+            let bot_id = connected_bot(&req)?;
+            let query: MarketQuery = req.query()?;
+
+            Ok(json!(req
+                .state()
+                .global
+                .bot_orderbook_snapshots(&bot_id, query.market.as_deref())))
+        });
+
+    app.at("/bots/:id/events")
+        .get(|req: Request<ApiState>| async move {
+            let bot_id = bot_id(&req)?;
+            let limit = limit(&req)?;
+
+            Ok(json!(req
+                .state()
+                .global
+                .recent_events(Some(&bot_id), limit)))
+        });
+
+    app.at("/bots/:id/commands")
+        .get(|req: Request<ApiState>| async move {
+            let bot_id = bot_id(&req)?;
+            let limit = limit(&req)?;
+
+            Ok(json!(req.state().store.commands(&bot_id, limit)?))
+        })
+        .post(|mut req: Request<ApiState>| async move {
+            let bot_id = bot_id(&req)?;
+            let command: BotCommand = req.body_json().await.map_err(|mut e| {
+                e.set_status(StatusCode::BadRequest);
+                e
+            })?;
+            let msg = command.into_message();
+            let name = msg.name();
+
+            if !req.state().global.send_to_bot(&bot_id, msg) {
+                return Err(not_connected(&bot_id));
+            }
+            info!("Queued {name} for bot {bot_id:?}");
+            if let Err(e) = req.state().store.record_command(&bot_id, name) {
+                error!("Failed to store command of bot {bot_id:?}: {e}");
+            }
+            req.state().global.record_event(
+                bot_id,
+                BotEventKind::Command {
+                    command: Box::from(name),
+                },
+            );
+
+            Ok(Response::builder(StatusCode::Accepted)
+                .body(json!({ "queued": name }))
+                .build())
+        });
+
+    app.at("/events").get(|req: Request<ApiState>| async move {
+        let limit = limit(&req)?;
+
+        Ok(json!(req.state().global.recent_events(None, limit)))
+    });
+
+    app.at("/registry")
+        .get(|req: Request<ApiState>| async move {
+            let connected_bots = req.state().global.connected_bots();
+            let bots: Vec<_> = req
+                .state()
+                .store
+                .bots()?
+                .into_iter()
+                .map(|record| {
+                    json!({
+                        "connected": connected_bots.contains(&record.bot_id),
+                        "bot": record,
+                    })
+                })
+                .collect();
+
+            Ok(json!(bots))
+        });
+
+    app.at("/markets")
+        .get(|req: Request<ApiState>| async move { Ok(json!(req.state().global.markets())) });
+
+    app
+}
+
+/// Runs the API server until it fails
+pub async fn run_listener(config: ApiServerConfig, state: ApiState) {
+    info!("Serving HTTP API on {}", config.listen_address);
+
+    if let Err(e) = app(state).listen(config.listen_address).await {
+        error!("HTTP API server failed: {e}");
+    }
+}
+
+/// Returns id of the bot in the path
+fn bot_id(req: &Request<ApiState>) -> tide::Result<BotId> {
+    req.param("id")?
+        .parse()
+        .map(BotId)
+        .map_err(|e| tide::Error::new(StatusCode::BadRequest, e))
+}
+
+/// Returns id of the bot in the path, 404 when it's not connected
+fn connected_bot(req: &Request<ApiState>) -> tide::Result<BotId> {
+    let bot_id = bot_id(req)?;
+
+    match req.state().global.connected_bots().contains(&bot_id) {
+        true => Ok(bot_id),
+        false => Err(not_connected(&bot_id)),
+    }
+}
+
+/// Returns number of records requested with `?limit=`
+fn limit(req: &Request<ApiState>) -> tide::Result<usize> {
+    let query: LimitQuery = req.query()?;
+
+    Ok(query.limit.unwrap_or(DEFAULT_LIMIT))
+}
+
+fn not_connected(bot_id: &BotId) -> tide::Error {
+    tide::Error::from_str(
+        StatusCode::NotFound,
+        format!("bot {} is not connected", bot_id.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use botvana::{
+        exchange::ExchangeId,
+        market::orderbook::{Orderbook, PriceLevelsVec},
+    };
+    use tide::http::{Method, Url};
+
+    fn state() -> ApiState {
+        ApiState {
+            global: GlobalState::new(),
+            store: Store::temporary().unwrap(),
+        }
+    }
+
+    fn request(state: &ApiState, method: Method, path: &str, body: &str) -> (StatusCode, String) {
+        let mut req = tide::http::Request::new(
+            method,
+            Url::parse(&format!("http://localhost{path}")).unwrap(),
+        );
+        req.set_body(body);
+        let mut res: tide::http::Response =
+            async_std::task::block_on(app(state.clone()).respond(req)).unwrap();
+        let body = async_std::task::block_on(res.body_string()).unwrap();
+
+        (res.status(), body)
+    }
+
+    #[test]
+    fn test_bots() {
+        let state = state();
+        state.global.add_bot(BotId(3));
+
+        let (status, body) = request(&state, Method::Get, "/bots", "");
+        assert_eq!(StatusCode::Ok, status);
+        let bots: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(3, bots[0]["bot_id"]);
+        assert_eq!(0.0, bots[0]["pnl"]["realized"]);
+
+        let (status, body) = request(&state, Method::Get, "/dashboard", "");
+        assert_eq!(StatusCode::Ok, status);
+        assert!(body.contains("/bots"));
+
+        assert_eq!(
+            StatusCode::Ok,
+            request(&state, Method::Get, "/bots/3", "").0
+        );
+        assert_eq!(
+            StatusCode::NotFound,
+            request(&state, Method::Get, "/bots/4", "").0
+        );
+        assert_eq!(
+            StatusCode::BadRequest,
+            request(&state, Method::Get, "/bots/x", "").0
+        );
+    }
+
+    #[test]
+    fn test_orderbook_snapshots() {
+        let state = state();
+        state.global.add_bot(BotId(1));
+        state.global.update_orderbook_snapshot(
+            BotId(1),
+            Orderbook {
+                exchange: ExchangeId::Ftx,
+                market: Box::from("BTC-PERP"),
+                bids: PriceLevelsVec::from_tuples_vec(&[(100.0, 1.0)]),
+                asks: PriceLevelsVec::from_tuples_vec(&[(101.0, 2.0)]),
+                time: 1.0,
+            },
+        );
+
+        let (status, body) = request(&state, Method::Get, "/bots/1/orderbooks", "");
+        assert_eq!(StatusCode::Ok, status);
+        let snapshots: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!("BTC-PERP", snapshots[0]["market"]);
+
+        let (_, body) = request(
+            &state,
+            Method::Get,
+            "/bots/1/orderbooks?market=ETH-PERP",
+            "",
+        );
+        assert_eq!("[]", body);
+        assert_eq!(
+            StatusCode::NotFound,
+            request(&state, Method::Get, "/bots/2/orderbooks", "").0
+        );
+    }
+
+    #[test]
+    fn test_commands() {
+        let state = state();
+        state.global.add_bot(BotId(1));
+
+        let (status, _) = request(
+            &state,
+            Method::Post,
+            "/bots/1/commands",
+            r#"{"command": "pause"}"#,
+        );
+        assert_eq!(StatusCode::Accepted, status);
+        let (status, _) = request(
+            &state,
+            Method::Post,
+            "/bots/1/commands",
+            r#"{"command": "kill_switch", "tripped": true, "reason": "test"}"#,
+        );
+        assert_eq!(StatusCode::Accepted, status);
+
+        let msgs = state.global.take_bot_messages(&BotId(1));
+        assert!(matches!(msgs[0], Message::PauseTrading));
+        assert!(matches!(&msgs[1], Message::KillSwitch(k) if k.tripped && k.reason == "test"));
+
+        let (status, body) = request(&state, Method::Get, "/bots/1/events?limit=1", "");
+        assert_eq!(StatusCode::Ok, status);
+        let events: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(1, events.as_array().unwrap().len());
+        assert_eq!("KillSwitch", events[0]["kind"]["command"]);
+
+        let (status, body) = request(&state, Method::Get, "/bots/1/commands", "");
+        assert_eq!(StatusCode::Ok, status);
+        let commands: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!("PauseTrading", commands[0]["command"]);
+        assert_eq!("KillSwitch", commands[1]["command"]);
+
+        let (status, _) = request(
+            &state,
+            Method::Post,
+            "/bots/1/commands",
+            r#"{"command": "reload_strategy", "strategy": "maker"}"#,
+        );
+        assert_eq!(StatusCode::Accepted, status);
+        assert!(matches!(
+            &state.global.take_bot_messages(&BotId(1))[..],
+            [Message::ReloadStrategy(StrategyReload { strategy, path: None })] if strategy == "maker"
+        ));
+        let (status, _) = request(
+            &state,
+            Method::Post,
+            "/bots/1/commands",
+            r#"{"command": "unsubscribe", "markets": ["ETH-PERP"]}"#,
+        );
+        assert_eq!(StatusCode::Accepted, status);
+        assert!(matches!(
+            &state.global.take_bot_messages(&BotId(1))[..],
+            [Message::UnsubscribeMarkets(markets)] if &*markets[0] == "ETH-PERP"
+        ));
+
+        let (status, _) = request(
+            &state,
+            Method::Post,
+            "/bots/1/commands",
+            r#"{"command": "orderbook_snapshot", "market": "BTC-PERP"}"#,
+        );
+        assert_eq!(StatusCode::Accepted, status);
+        assert!(matches!(
+            &state.global.take_bot_messages(&BotId(1))[..],
+            [Message::GetOrderbookSnapshot(market)] if &**market == "BTC-PERP"
+        ));
+
+        let (status, _) = request(
+            &state,
+            Method::Post,
+            "/bots/2/commands",
+            r#"{"command": "pause"}"#,
+        );
+        assert_eq!(StatusCode::NotFound, status);
+        let (status, _) = request(
+            &state,
+            Method::Post,
+            "/bots/1/commands",
+            r#"{"command": "explode"}"#,
+        );
+        assert_eq!(StatusCode::BadRequest, status);
+    }
+}
@@ -1,21 +1,30 @@
-use std::{net::ToSocketAddrs, rc::Rc, time::Duration};
+use std::{
+    net::ToSocketAddrs,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use futures::{prelude::*, stream::StreamExt};
 use glommio::{enclose, net::TcpListener, net::TcpStream, sync::Semaphore, timer::sleep, Task};
 use tracing::{debug, error, info, warn};
 
-use crate::config::BotnodeConfig;
+use crate::{config::BotnodeConfig, store::Store};
 use botvana::{
     cfg::{BotConfiguration, PeerBot},
     net::{
-        codec,
-        msg::{BotId, Message},
+        auth, codec,
+        compression::{Compression, CompressionConfig},
+        format::WireFormat,
+        msg::{AuthResult, BotId, BotMetadata, Message},
     },
     state,
 };
 
 const ACTIVITY_TIMEOUT_SECS: u64 = 15;
 
+/// How often messages queued for the bot are sent
+const OUTBOX_INTERVAL: Duration = Duration::from_millis(100);
+
 #[derive(thiserror::Error, Debug)]
 pub enum BotServerError {
     #[error("error while reading socket")]
@@ -28,18 +37,37 @@ pub enum BotServerError {
     DuplicateHello,
     #[error("unknown bot id supplied")]
     UnknownBotID,
+    #[error("bot is not authenticated")]
+    Unauthenticated,
+    #[error("unsupported protocol version {0}")]
+    UnsupportedProtocol(u8),
 }
 
 /// Bot server loop
 pub async fn serve<A>(
     addr: A,
+    wire_format: WireFormat,
+    compression: CompressionConfig,
     max_connections: usize,
     global_state: state::GlobalState,
+    store: Store,
     botnode_configs: Box<[BotnodeConfig]>,
 ) -> std::io::Result<()>
 where
     A: ToSocketAddrs + std::net::ToSocketAddrs,
 {
+    if wire_format.serializer().is_none() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("botvana is built without the {wire_format:?} wire format"),
+        ));
+    }
+    if let Some(algorithm) = compression.algorithm.filter(|a| !a.is_supported()) {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("botvana is built without {algorithm:?} compression"),
+        ));
+    }
     let listener = TcpListener::bind(addr)?;
     let conn_control = Rc::new(Semaphore::new(max_connections as _));
 
@@ -53,6 +81,8 @@ where
             }
         };
         let global_state = global_state.clone();
+        let store = store.clone();
+        let compression = compression.clone();
 
         {
             let botnode_configs = botnode_configs.clone();
@@ -61,9 +91,12 @@ where
                     .acquire_permit(1)
                     .await
                     .expect("failed to acquire permit");
-                let mut stream = codec::Framed::new(stream, codec::BotvanaCodec);
+                let serializer = wire_format.serializer().expect("wire format is checked on start");
+                let codec = codec::BotvanaCodec::new(serializer).with_compression(compression);
+                let protocol = codec.protocol();
+                let mut stream = codec::Framed::new(stream, codec);
 
-                if let Err(e) = handle_connection(&mut stream, global_state, botnode_configs).await {
+                if let Err(e) = handle_connection(&mut stream, protocol, global_state, store, botnode_configs).await {
                     error!("Error while handling the connection: {}", e);
                 }
             }})
@@ -72,52 +105,87 @@ where
     }
 }
 
+/// State of a bot connection
+#[derive(Debug, Default)]
+pub struct Connection {
+    /// Id the bot sent in `Hello`
+    pub bot_id: Option<BotId>,
+    /// Metadata the bot sent in `Hello`, stored once it's authenticated
+    metadata: Option<BotMetadata>,
+    /// Nonce of the challenge the bot has to answer
+    challenge: Option<Vec<u8>>,
+    /// Whether the bot is authenticated and received its configuration
+    pub authenticated: bool,
+    /// Protocol versions of the connection, negotiated on `Hello`
+    pub protocol: codec::Protocol,
+}
+
 /// Handle an incoming connection from the bot
 pub async fn handle_connection(
     stream: &mut codec::Framed<TcpStream, codec::BotvanaCodec>,
+    protocol: codec::Protocol,
     global_state: state::GlobalState,
+    store: Store,
     botnode_configs: Box<[BotnodeConfig]>,
 ) -> Result<(), BotServerError> {
-    let mut conn_bot_id = None;
+    let mut conn = Connection {
+        protocol,
+        ..Default::default()
+    };
+    let mut last_activity = Instant::now();
 
-    loop {
+    let res = loop {
         futures::select! {
             frame = stream.next().fuse() => {
                 let frame = match frame {
                     Some(Ok(frame)) => frame,
-                    _ => {
-                        if let Some(conn_bot_id) = conn_bot_id {
-                            global_state.remove_bot(conn_bot_id);
-                        }
-
-                        if frame.is_none() {
-                            return Ok(())
-                        } else {
-                            return Err(BotServerError::ReadError)
-                        }
-                    }
+                    None => break Ok(()),
+                    Some(Err(_)) => break Err(BotServerError::ReadError),
                 };
+                last_activity = Instant::now();
 
-                debug!("received frame={:?} botid={:?}", frame, conn_bot_id);
+                debug!("received frame={:?} botid={:?}", frame, conn.bot_id);
 
-                process_bot_message(stream, &mut conn_bot_id, global_state.clone(), &botnode_configs, frame)
-                    .await
-                    .expect("Failed to process");
+                if let Err(e) = process_bot_message(stream, &mut conn, global_state.clone(), &store, &botnode_configs, frame).await {
+                    break Err(e);
+                }
             }
-            _ = sleep(Duration::from_secs(ACTIVITY_TIMEOUT_SECS)).fuse() => {
-                warn!("Timeout while waiting for activity");
+            _ = sleep(OUTBOX_INTERVAL).fuse() => {
+                if last_activity.elapsed() >= Duration::from_secs(ACTIVITY_TIMEOUT_SECS) {
+                    warn!("Timeout while waiting for activity");
+
+                    break Err(BotServerError::Timeout)
+                }
 
-                break Err(BotServerError::Timeout)
+                if let (true, Some(bot_id)) = (conn.authenticated, &conn.bot_id) {
+                    if let Err(e) = send_queued(stream, &conn.protocol, &global_state, bot_id).await {
+                        break Err(e);
+                    }
+                }
             }
         }
+    };
+
+    if let (true, Some(bot_id)) = (conn.authenticated, conn.bot_id) {
+        global_state.record_event(bot_id.clone(), state::BotEventKind::Disconnected);
+        if let Err(e) = store.bot_seen(&bot_id) {
+            error!("Failed to store disconnection of bot {:?}: {}", bot_id, e);
+        }
+        global_state.remove_bot(bot_id);
     }
+
+    res
 }
 
 /// Process one message coming from the bot over the network
+///
+/// Until the bot is authenticated only the handshake messages and pings are
+/// accepted, anything else is an error that closes the connection.
 pub async fn process_bot_message(
     stream: &mut codec::Framed<TcpStream, codec::BotvanaCodec>,
-    conn_bot_id: &mut Option<BotId>,
+    conn: &mut Connection,
     global_state: state::GlobalState,
+    store: &Store,
     botnode_configs: &[BotnodeConfig],
     msg: Message,
 ) -> Result<(), BotServerError> {
@@ -125,53 +193,69 @@ pub async fn process_bot_message(
         Message::Hello(bot_id, bot_metadata) => {
             // If the bot has sent Hello message before, we don't accept it and
             // break the current connection
-            if let Some(conn_bot_id) = conn_bot_id {
+            if let Some(conn_bot_id) = &conn.bot_id {
                 warn!("Bot {:?} sending duplicate Hello message", conn_bot_id);
-                global_state.remove_bot(conn_bot_id.clone());
                 return Err(BotServerError::DuplicateHello);
             }
+            negotiate_protocol(stream, conn).await?;
 
-            if bot_id.0 >= botnode_configs.len() as u16 {
-                warn!("Invalid bot id supplied: {:?}", bot_id);
-                return Err(BotServerError::UnknownBotID);
+            let config = match botnode_configs.get(bot_id.0 as usize) {
+                Some(config) => config,
+                None => {
+                    warn!("Invalid bot id supplied: {:?}", bot_id);
+                    reject(stream, "unknown bot id").await?;
+                    return Err(BotServerError::UnknownBotID);
+                }
+            };
+
+            info!(
+                "Hello from bot id = {:?}, metadata = {:?}",
+                bot_id, bot_metadata
+            );
+            if bot_metadata.dry_run {
+                warn!("Bot {:?} is running in dry-run mode", bot_id);
             }
 
             // Save the bot_id in local variable that's scoped for this
             // connection only
-            *conn_bot_id = Some(bot_id.clone());
-
-            let bots = global_state.connected_bots();
+            conn.bot_id = Some(bot_id);
+            conn.metadata = Some(bot_metadata);
 
-            global_state.add_bot(bot_id.clone());
-
-            let peer_bots = bots
-                .iter()
-                .map(|id| PeerBot { bot_id: id.clone() })
-                .collect();
-
-            info!(
-                "Hello from bot id = {:?}, metadata = {:?}; total = {}",
-                bot_id,
-                bot_metadata,
-                bots.len()
-            );
+            if config.auth.is_required() {
+                let nonce = auth::nonce();
+                conn.challenge = Some(nonce.clone());
+                stream
+                    .send(Message::AuthChallenge(nonce))
+                    .await
+                    .map_err(|_| BotServerError::WriteError)?;
+            } else {
+                accept_bot(stream, conn, &global_state, store, config).await?;
+            }
+        }
+        Message::AuthResponse(proof) => {
+            let (bot_id, nonce) = match (&conn.bot_id, conn.challenge.take()) {
+                (Some(bot_id), Some(nonce)) => (bot_id.clone(), nonce),
+                _ => {
+                    warn!(
+                        "Unexpected authentication response from bot {:?}",
+                        conn.bot_id
+                    );
+                    return Err(BotServerError::Unauthenticated);
+                }
+            };
 
-            let config = botnode_configs.get(bot_id.0 as usize).unwrap();
-            let exchanges = config.exchanges.clone();
-            let markets = config.markets.clone();
-            let out_msg = Message::BotConfiguration(BotConfiguration {
-                bot_id: bot_id.clone(),
-                peer_bots,
-                exchanges,
-                markets,
-                indicators: Box::new([]),
-            });
-            info!("Sending bot configuration {:?}", out_msg);
+            let config = &botnode_configs[bot_id.0 as usize];
+            if !config.auth.verify(&bot_id, &nonce, &proof) {
+                warn!("Bot {:?} failed to authenticate", bot_id);
+                reject(stream, "invalid credentials").await?;
+                return Err(BotServerError::Unauthenticated);
+            }
 
-            stream
-                .send(out_msg)
-                .await
-                .map_err(|_| BotServerError::WriteError)?;
+            accept_bot(stream, conn, &global_state, store, config).await?;
+        }
+        Message::Compression(algorithms) => {
+            debug!("Bot {:?} decompresses {:?}", conn.bot_id, algorithms);
+            conn.protocol.set_peer_compression(&algorithms);
         }
         Message::Ping(timestamp) => {
             debug!("received ping {}", timestamp);
@@ -181,9 +265,42 @@ pub async fn process_bot_message(
                 .await
                 .map_err(|_| BotServerError::WriteError)?;
         }
+        msg if !conn.authenticated => {
+            warn!(
+                "Message {:?} from unauthenticated bot {:?}",
+                msg, conn.bot_id
+            );
+            return Err(BotServerError::Unauthenticated);
+        }
         Message::MarketList(markets_vec) => {
             global_state.update_markets(markets_vec);
         }
+        Message::Alert(alert) => {
+            warn!("Alert from bot {:?}: {:?}", conn.bot_id, alert);
+            if let Some(bot_id) = &conn.bot_id {
+                global_state.record_event(bot_id.clone(), state::BotEventKind::Alert(alert));
+            }
+        }
+        Message::Metrics(report) => match &conn.bot_id {
+            Some(bot_id) => global_state.update_metrics(bot_id.clone(), report),
+            None => warn!("Metrics received before Hello"),
+        },
+        Message::StatusReport(report) => match &conn.bot_id {
+            Some(bot_id) => {
+                if !report.healthy() {
+                    warn!("Bot {:?} is unhealthy: {:?}", bot_id, report);
+                }
+                if let Err(e) = store.bot_seen(bot_id) {
+                    error!("Failed to store last seen time of bot {:?}: {}", bot_id, e);
+                }
+                global_state.update_status(bot_id.clone(), report);
+            }
+            None => warn!("Status report received before Hello"),
+        },
+        Message::Positions(positions) => match &conn.bot_id {
+            Some(bot_id) => global_state.update_positions(bot_id.clone(), positions),
+            None => warn!("Positions received before Hello"),
+        },
         Message::Orderbook(orderbook) => {
             global_state.update_orderbook(
                 orderbook.exchange,
@@ -191,10 +308,169 @@ pub async fn process_bot_message(
                 orderbook.into(),
             );
         }
+        Message::OrderbookSnapshot(orderbook) => match &conn.bot_id {
+            Some(bot_id) => {
+                info!(
+                    "Orderbook snapshot of {} {} from bot {bot_id:?}",
+                    orderbook.exchange, orderbook.market
+                );
+                global_state.update_orderbook_snapshot(bot_id.clone(), orderbook);
+            }
+            None => warn!("Orderbook snapshot received before Hello"),
+        },
         msg => {
-            warn!("Unhandled message = {:?} from bot {:?}", msg, conn_bot_id);
+            warn!("Unhandled message = {:?} from bot {:?}", msg, conn.bot_id);
         }
     }
 
     Ok(())
 }
+
+/// Picks the protocol version spoken with the bot that sent `Hello`
+///
+/// Bots speaking version 1 don't know the `ProtocolVersion` message, they
+/// are just answered with version 1 frames.
+async fn negotiate_protocol(
+    stream: &mut codec::Framed<TcpStream, codec::BotvanaCodec>,
+    conn: &mut Connection,
+) -> Result<(), BotServerError> {
+    let peer_version = conn.protocol.peer_version();
+    let version = match codec::negotiate(peer_version) {
+        Some(version) => version,
+        None => {
+            warn!("Bot speaks unsupported protocol version {}", peer_version);
+            conn.protocol.set_version(codec::MIN_PROTOCOL_VERSION);
+            reject(stream, "unsupported protocol version").await?;
+            return Err(BotServerError::UnsupportedProtocol(peer_version));
+        }
+    };
+    debug!("Negotiated protocol version {}", version);
+    conn.protocol.set_version(version);
+
+    if version >= 2 {
+        stream
+            .send(Message::ProtocolVersion(version))
+            .await
+            .map_err(|_| BotServerError::WriteError)?;
+    }
+    Ok(())
+}
+
+/// Sends messages queued for the bot, e.g. operator commands
+///
+/// Messages newer than the negotiated protocol version are dropped, the bot
+/// couldn't decode them.
+async fn send_queued(
+    stream: &mut codec::Framed<TcpStream, codec::BotvanaCodec>,
+    protocol: &codec::Protocol,
+    global_state: &state::GlobalState,
+    bot_id: &BotId,
+) -> Result<(), BotServerError> {
+    for msg in global_state.take_bot_messages(bot_id) {
+        if msg.since_version() > protocol.version() {
+            warn!(
+                "Dropping {} for bot {:?}, it speaks protocol version {}",
+                msg.name(),
+                bot_id,
+                protocol.version()
+            );
+            continue;
+        }
+        info!("Sending {:?} to bot {:?}", msg, bot_id);
+
+        stream
+            .send(msg)
+            .await
+            .map_err(|_| BotServerError::WriteError)?;
+    }
+
+    Ok(())
+}
+
+/// Accepts the authenticated bot and sends it the configuration
+async fn accept_bot(
+    stream: &mut codec::Framed<TcpStream, codec::BotvanaCodec>,
+    conn: &mut Connection,
+    global_state: &state::GlobalState,
+    store: &Store,
+    config: &BotnodeConfig,
+) -> Result<(), BotServerError> {
+    let bot_id = conn.bot_id.clone().ok_or(BotServerError::Unauthenticated)?;
+    // Bots failing authentication can't touch their registry entry
+    if let Some(metadata) = conn.metadata.take() {
+        if let Err(e) = store.bot_hello(&bot_id, &metadata) {
+            error!("Failed to store bot {:?}: {}", bot_id, e);
+        }
+    }
+
+    let bots = global_state.connected_bots();
+    global_state.add_bot(bot_id.clone());
+    global_state.record_event(bot_id.clone(), state::BotEventKind::Connected);
+    conn.authenticated = true;
+    info!("Accepted bot id = {:?}; total = {}", bot_id, bots.len() + 1);
+
+    let peer_bots = bots
+        .iter()
+        .map(|id| PeerBot { bot_id: id.clone() })
+        .collect();
+
+    // Compressed frames are only accepted from authenticated bots, the
+    // announcement precedes the result the bot waits for
+    if conn.protocol.version() >= 3 {
+        stream
+            .send(Message::Compression(Compression::supported()))
+            .await
+            .map_err(|_| BotServerError::WriteError)?;
+    }
+
+    stream
+        .send(Message::AuthResult(AuthResult::Accepted))
+        .await
+        .map_err(|_| BotServerError::WriteError)?;
+
+    let configuration = BotConfiguration {
+        bot_id,
+        peer_bots,
+        exchanges: config.exchanges.clone(),
+        markets: config.markets.clone(),
+        indicators: Box::new([]),
+    };
+    if let Err(e) = store.bot_configured(&configuration.bot_id, &configuration) {
+        error!(
+            "Failed to store configuration of bot {:?}: {}",
+            configuration.bot_id, e
+        );
+    }
+
+    let out_msg = Message::BotConfiguration(configuration);
+    info!("Sending bot configuration {:?}", out_msg);
+
+    stream
+        .send(out_msg)
+        .await
+        .map_err(|_| BotServerError::WriteError)?;
+
+    if let Some(update) = config.config_update() {
+        info!("Pushing configuration update {:?}", update);
+
+        stream
+            .send(Message::ConfigUpdate(update))
+            .await
+            .map_err(|_| BotServerError::WriteError)?;
+    }
+
+    Ok(())
+}
+
+/// Tells the bot it was rejected before the connection is closed
+async fn reject(
+    stream: &mut codec::Framed<TcpStream, codec::BotvanaCodec>,
+    reason: &str,
+) -> Result<(), BotServerError> {
+    stream
+        .send(Message::AuthResult(AuthResult::Rejected(
+            reason.to_string(),
+        )))
+        .await
+        .map_err(|_| BotServerError::WriteError)
+}
@@ -1,9 +1,21 @@
 use serde::Deserialize;
 
+use crate::store::StoreConfig;
+use botvana::{
+    cfg::{ConfigUpdate, RiskLimits, StrategyParams},
+    net::{auth::BotCredentials, compression::CompressionConfig, format::WireFormat},
+};
+
 /// Configuration for the bot server
 #[derive(Deserialize)]
 pub struct BotServerConfig {
     pub listen_address: String,
+    /// Serialization of the messages, every bot has to use the same
+    #[serde(default)]
+    pub wire_format: WireFormat,
+    /// Compression of the messages sent to bots
+    #[serde(default)]
+    pub compression: CompressionConfig,
 }
 
 /// Configuration for Websocket gateway
@@ -12,11 +24,52 @@ pub struct WebsocketServerConfig {
     pub listen_address: String,
 }
 
+/// Configuration for the HTTP API
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ApiServerConfig {
+    pub listen_address: String,
+}
+
+impl Default for ApiServerConfig {
+    fn default() -> Self {
+        Self {
+            listen_address: "127.0.0.1:8080".to_string(),
+        }
+    }
+}
+
 /// Generic configuration for botnodes
 #[derive(Clone, Deserialize)]
 pub struct BotnodeConfig {
     pub markets: Box<[Box<str>]>,
     pub exchanges: Box<[Box<str>]>,
+    /// Credentials the bot authenticates with, any bot is accepted when
+    /// none are set
+    #[serde(default)]
+    pub auth: BotCredentials,
+    /// Risk limits pushed to the bot after its configuration
+    #[serde(default)]
+    pub risk: Option<RiskLimits>,
+    /// Strategy parameters pushed to the bot after its configuration
+    #[serde(default)]
+    pub strategy_params: Box<[StrategyParams]>,
+}
+
+impl BotnodeConfig {
+    /// Returns the update pushed to the bot once it's accepted, if any
+    /// settings go beyond the bot configuration
+    pub fn config_update(&self) -> Option<ConfigUpdate> {
+        if self.risk.is_none() && self.strategy_params.is_empty() {
+            return None;
+        }
+
+        Some(ConfigUpdate {
+            markets: None,
+            risk: self.risk.clone(),
+            strategy_params: self.strategy_params.clone(),
+        })
+    }
 }
 
 /// botvana-server configuration
@@ -24,5 +77,10 @@ pub struct BotnodeConfig {
 pub struct ServerConfig {
     pub bot_server: BotServerConfig,
     pub ws_server: WebsocketServerConfig,
+    #[serde(default)]
+    pub api_server: ApiServerConfig,
+    /// Database of the known bots
+    #[serde(default)]
+    pub store: StoreConfig,
     pub botnode: Box<[BotnodeConfig]>,
 }
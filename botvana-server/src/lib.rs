@@ -1,3 +1,5 @@
+pub mod api;
 pub mod bot_server;
 pub mod config;
+pub mod store;
 pub mod ws;
@@ -7,7 +7,6 @@ use futures::prelude::*;
 use glommio::{prelude::*, CpuSet};
 use signal_hook::consts::signal::*;
 use signal_hook_async_std::Signals;
-use tide::{prelude::*, Request};
 use tracing::{debug, error, info};
 use tracing_subscriber::EnvFilter;
 
@@ -30,32 +29,27 @@ fn main() {
 
     let state = state::GlobalState::new();
     let state_ref = state.clone();
+    let store = store::Store::open(&config.store).expect("Failed to open the store");
+    match store.bots() {
+        Ok(bots) => info!(
+            "{} bots known from {}",
+            bots.len(),
+            config.store.path.display()
+        ),
+        Err(e) => error!("Failed to read known bots: {}", e),
+    }
+    let store_ref = store.clone();
 
     LocalExecutorBuilder::new()
         .spawn(|| async move {
             {
-                let state_ref = state_ref.clone();
+                let api_state = api::ApiState {
+                    global: state_ref.clone(),
+                    store: store_ref.clone(),
+                };
                 glommio::Task::local(async move {
                     tide::log::start();
-                    let mut app = tide::with_state(state_ref);
-
-                    app.at("/")
-                        .get(|req: Request<state::GlobalState>| async move {
-                            let state = req.state();
-                            let connected_bots: Vec<_> = state
-                                .connected_bots()
-                                .iter()
-                                .map(|bot_id| bot_id.0)
-                                .collect();
-
-                            Ok(json!({
-                                "connected_bots": connected_bots,
-                            }))
-                        });
-
-                    app.listen("127.0.0.1:8080")
-                        .await
-                        .expect("Tide listener failed");
+                    api::run_listener(config.api_server, api_state).await;
                 })
                 .detach();
             }
@@ -67,13 +61,16 @@ fn main() {
 
     LocalExecutorPoolBuilder::new(2)
         .placement(Placement::MaxSpread(CpuSet::online().ok()))
-        .on_all_shards(|| async move {
+        .on_all_shards(move || async move {
             debug!("Starting executor");
 
             if let Err(e) = bot_server::serve::<_>(
                 config.bot_server.listen_address,
+                config.bot_server.wire_format,
+                config.bot_server.compression.clone(),
                 4096,
                 state,
+                store.clone(),
                 config.botnode,
             )
             .await
@@ -0,0 +1,293 @@
+//! Persistent registry of the bots
+//!
+//! Bots the server has seen, the configuration they were last sent, when
+//! they were last seen and the commands queued for them are kept in a sled
+//! database, so they survive restarts of the server. Records are stored as
+//! JSON keyed by big-endian bot id, commands additionally by a sequence
+//! number so they iterate in the order they were issued. Sled writes the
+//! changes to disk in the background every half a second.
+
+use std::{path::PathBuf, time::SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use botvana::{
+    cfg::BotConfiguration,
+    net::msg::{BotId, BotMetadata},
+};
+
+/// Store configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StoreConfig {
+    /// Directory of the database
+    pub path: PathBuf,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("data/botvana-server"),
+        }
+    }
+}
+
+/// Error reading or writing the store
+#[derive(Debug, thiserror::Error)]
+#[error("store error: {source}")]
+pub struct StoreError {
+    source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl StoreError {
+    fn with_source<T: std::error::Error + Send + Sync + 'static>(source: T) -> Self {
+        Self {
+            source: Box::new(source),
+        }
+    }
+}
+
+/// Bot known to the server
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BotRecord {
+    pub bot_id: BotId,
+    /// Version the bot sent in its last `Hello`
+    pub bot_version: u32,
+    pub dry_run: bool,
+    /// Configuration the bot was last sent
+    pub configuration: Option<BotConfiguration>,
+    /// Time the bot first connected in milliseconds since UNIX epoch
+    pub first_seen: u64,
+    /// Time of the latest message from the bot in milliseconds since UNIX
+    /// epoch
+    pub last_seen: u64,
+}
+
+/// Command queued for a bot
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct CommandRecord {
+    /// Time the command was queued in milliseconds since UNIX epoch
+    pub timestamp: u64,
+    pub bot_id: BotId,
+    /// Name of the message carrying the command
+    pub command: Box<str>,
+}
+
+/// Persistent store of botvana-server
+#[derive(Clone, Debug)]
+pub struct Store {
+    db: sled::Db,
+    bots: sled::Tree,
+    commands: sled::Tree,
+}
+
+impl Store {
+    /// Opens the store at the configured path, creating it when missing
+    pub fn open(config: &StoreConfig) -> Result<Self, StoreError> {
+        Self::from_db(sled::open(&config.path).map_err(StoreError::with_source)?)
+    }
+
+    /// Opens a store removed once dropped
+    pub fn temporary() -> Result<Self, StoreError> {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .map_err(StoreError::with_source)?;
+
+        Self::from_db(db)
+    }
+
+    fn from_db(db: sled::Db) -> Result<Self, StoreError> {
+        Ok(Self {
+            bots: db.open_tree("bots").map_err(StoreError::with_source)?,
+            commands: db.open_tree("commands").map_err(StoreError::with_source)?,
+            db,
+        })
+    }
+
+    /// Returns the record of given bot
+    pub fn bot(&self, bot_id: &BotId) -> Result<Option<BotRecord>, StoreError> {
+        self.bots
+            .get(bot_id.0.to_be_bytes())
+            .map_err(StoreError::with_source)?
+            .map(|value| decode(&value))
+            .transpose()
+    }
+
+    /// Returns records of all known bots ordered by bot id
+    pub fn bots(&self) -> Result<Vec<BotRecord>, StoreError> {
+        self.bots
+            .iter()
+            .values()
+            .map(|value| decode(&value.map_err(StoreError::with_source)?))
+            .collect()
+    }
+
+    /// Records `Hello` from the bot
+    pub fn bot_hello(&self, bot_id: &BotId, metadata: &BotMetadata) -> Result<(), StoreError> {
+        self.update_bot(bot_id, |record| {
+            record.bot_version = metadata.bot_version;
+            record.dry_run = metadata.dry_run;
+        })
+    }
+
+    /// Records configuration sent to the bot
+    pub fn bot_configured(
+        &self,
+        bot_id: &BotId,
+        configuration: &BotConfiguration,
+    ) -> Result<(), StoreError> {
+        self.update_bot(bot_id, |record| {
+            record.configuration = Some(configuration.clone())
+        })
+    }
+
+    /// Records activity of the bot
+    pub fn bot_seen(&self, bot_id: &BotId) -> Result<(), StoreError> {
+        self.update_bot(bot_id, |_| {})
+    }
+
+    /// Updates record of the bot and its last seen time, the record is
+    /// created when the bot is new
+    fn update_bot<F: FnOnce(&mut BotRecord)>(
+        &self,
+        bot_id: &BotId,
+        update: F,
+    ) -> Result<(), StoreError> {
+        let now = now();
+        let mut record = self.bot(bot_id)?.unwrap_or(BotRecord {
+            bot_id: bot_id.clone(),
+            bot_version: 0,
+            dry_run: false,
+            configuration: None,
+            first_seen: now,
+            last_seen: now,
+        });
+        update(&mut record);
+        record.last_seen = now;
+
+        self.bots
+            .insert(&bot_id.0.to_be_bytes()[..], encode(&record)?)
+            .map_err(StoreError::with_source)?;
+
+        Ok(())
+    }
+
+    /// Records command queued for the bot
+    pub fn record_command(&self, bot_id: &BotId, command: &str) -> Result<(), StoreError> {
+        let seq = self.db.generate_id().map_err(StoreError::with_source)?;
+        let record = CommandRecord {
+            timestamp: now(),
+            bot_id: bot_id.clone(),
+            command: Box::from(command),
+        };
+
+        self.commands
+            .insert(&command_key(bot_id, seq)[..], encode(&record)?)
+            .map_err(StoreError::with_source)?;
+
+        Ok(())
+    }
+
+    /// Returns up to `limit` most recent commands of the bot, oldest first
+    pub fn commands(&self, bot_id: &BotId, limit: usize) -> Result<Vec<CommandRecord>, StoreError> {
+        let mut commands = self
+            .commands
+            .scan_prefix(bot_id.0.to_be_bytes())
+            .values()
+            .rev()
+            .take(limit)
+            .map(|value| decode(&value.map_err(StoreError::with_source)?))
+            .collect::<Result<Vec<_>, _>>()?;
+        commands.reverse();
+
+        Ok(commands)
+    }
+}
+
+fn command_key(bot_id: &BotId, seq: u64) -> [u8; 10] {
+    let mut key = [0; 10];
+    key[..2].copy_from_slice(&bot_id.0.to_be_bytes());
+    key[2..].copy_from_slice(&seq.to_be_bytes());
+    key
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<Vec<u8>, StoreError> {
+    serde_json::to_vec(value).map_err(StoreError::with_source)
+}
+
+fn decode<T: for<'de> Deserialize<'de>>(value: &[u8]) -> Result<T, StoreError> {
+    serde_json::from_slice(value).map_err(StoreError::with_source)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bot_records() {
+        let store = Store::temporary().unwrap();
+
+        store
+            .bot_hello(&BotId(2), &BotMetadata::new(7).dry_run(true))
+            .unwrap();
+        store.bot_seen(&BotId(1)).unwrap();
+        let record = store.bot(&BotId(2)).unwrap().unwrap();
+        assert_eq!(7, record.bot_version);
+        assert!(record.dry_run);
+        assert!(record.configuration.is_none());
+
+        store
+            .bot_configured(
+                &BotId(2),
+                &BotConfiguration {
+                    bot_id: BotId(2),
+                    peer_bots: Box::new([]),
+                    exchanges: Box::new([Box::from("ftx")]),
+                    markets: Box::new([Box::from("BTC-PERP")]),
+                    indicators: Box::new([]),
+                },
+            )
+            .unwrap();
+        let updated = store.bot(&BotId(2)).unwrap().unwrap();
+        assert_eq!(record.first_seen, updated.first_seen);
+        assert!(updated.last_seen >= record.last_seen);
+        assert_eq!(7, updated.bot_version);
+        assert_eq!("BTC-PERP", &*updated.configuration.unwrap().markets[0]);
+
+        let bots: Vec<_> = store
+            .bots()
+            .unwrap()
+            .into_iter()
+            .map(|r| r.bot_id)
+            .collect();
+        assert_eq!(vec![BotId(1), BotId(2)], bots);
+        assert!(store.bot(&BotId(3)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_command_history() {
+        let store = Store::temporary().unwrap();
+
+        store.record_command(&BotId(1), "PauseTrading").unwrap();
+        store.record_command(&BotId(2), "Shutdown").unwrap();
+        store.record_command(&BotId(1), "ResumeTrading").unwrap();
+        store.record_command(&BotId(1), "FlattenPositions").unwrap();
+
+        let commands: Vec<_> = store
+            .commands(&BotId(1), 2)
+            .unwrap()
+            .into_iter()
+            .map(|c| c.command.to_string())
+            .collect();
+        assert_eq!(vec!["ResumeTrading", "FlattenPositions"], commands);
+        assert_eq!(1, store.commands(&BotId(2), 10).unwrap().len());
+    }
+}
@@ -92,6 +92,8 @@ async fn send_state(
     let connected_bots = state.connected_bots();
     let markets = state.markets();
     let orderbooks = state.orderbooks();
+    let metrics = state.metrics();
+    let statuses = state.statuses();
 
     Ok(ws_stream
         .send(Message::Text(
@@ -99,6 +101,8 @@ async fn send_state(
                 "connected_bots": connected_bots,
                 "markets": markets,
                 "orderbooks": orderbooks,
+                "metrics": metrics,
+                "statuses": statuses,
             })
             .to_string(),
         ))
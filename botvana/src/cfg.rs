@@ -16,6 +16,34 @@ pub struct BotConfiguration {
     pub indicators: Box<[IndicatorConfig]>,
 }
 
+/// Configuration pushed by the server to a running bot
+///
+/// Values that are not set are kept as they are.
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct ConfigUpdate {
+    /// Markets the bot trades
+    pub markets: Option<Box<[Box<str>]>>,
+    pub risk: Option<RiskLimits>,
+    /// New parameters of strategies
+    pub strategy_params: Box<[StrategyParams]>,
+}
+
+/// Risk limits, every limit is disabled when not set
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct RiskLimits {
+    pub max_order_size: Option<f64>,
+    pub max_position: Option<f64>,
+    pub max_notional_per_minute: Option<f64>,
+}
+
+/// Parameters of a strategy
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct StrategyParams {
+    pub strategy: String,
+    /// Parameters encoded as a TOML table
+    pub params: String,
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct PeerBot {
     pub bot_id: BotId,
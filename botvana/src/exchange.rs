@@ -8,6 +8,31 @@ pub enum ExchangeId {
     Ftx,
     BinanceSpot,
     Serum,
+    Coinbase,
+    Kraken,
+    Bybit,
+    Okx,
+    Deribit,
+    Bitstamp,
+    Gemini,
+}
+
+impl ExchangeId {
+    /// Returns stable lower-case identifier of the exchange
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExchangeId::Ftx => "ftx",
+            ExchangeId::BinanceSpot => "binance_spot",
+            ExchangeId::Serum => "serum",
+            ExchangeId::Coinbase => "coinbase",
+            ExchangeId::Kraken => "kraken",
+            ExchangeId::Bybit => "bybit",
+            ExchangeId::Okx => "okx",
+            ExchangeId::Deribit => "deribit",
+            ExchangeId::Bitstamp => "bitstamp",
+            ExchangeId::Gemini => "gemini",
+        }
+    }
 }
 
 impl std::fmt::Display for ExchangeId {
@@ -24,6 +49,13 @@ impl FromStr for ExchangeId {
             "ftx" | "Ftx" | "FTX" => Ok(ExchangeId::Ftx),
             "binance" | "Binance" | "binance_spot" | "BinanceSpot" => Ok(ExchangeId::BinanceSpot),
             "serum" | "Serum" | "serum_dex" => Ok(ExchangeId::Serum),
+            "coinbase" | "Coinbase" | "gdax" => Ok(ExchangeId::Coinbase),
+            "kraken" | "Kraken" => Ok(ExchangeId::Kraken),
+            "bybit" | "Bybit" => Ok(ExchangeId::Bybit),
+            "okx" | "Okx" | "OKX" => Ok(ExchangeId::Okx),
+            "deribit" | "Deribit" => Ok(ExchangeId::Deribit),
+            "bitstamp" | "Bitstamp" => Ok(ExchangeId::Bitstamp),
+            "gemini" | "Gemini" => Ok(ExchangeId::Gemini),
             _ => Err(format!("Unknown exchange: {}", s)),
         }
     }
@@ -45,6 +77,25 @@ mod tests {
             ExchangeId::BinanceSpot,
             "binance_spot".parse::<ExchangeId>().unwrap()
         );
+        assert_eq!(ExchangeId::Coinbase, "gdax".parse::<ExchangeId>().unwrap());
+    }
+
+    #[test]
+    fn exchange_id_as_str() {
+        for id in [
+            ExchangeId::Ftx,
+            ExchangeId::BinanceSpot,
+            ExchangeId::Serum,
+            ExchangeId::Coinbase,
+            ExchangeId::Kraken,
+            ExchangeId::Bybit,
+            ExchangeId::Okx,
+            ExchangeId::Deribit,
+            ExchangeId::Bitstamp,
+            ExchangeId::Gemini,
+        ] {
+            assert_eq!(id, id.as_str().parse::<ExchangeId>().unwrap());
+        }
     }
 
     #[test]
@@ -5,5 +5,6 @@
 pub mod cfg;
 pub mod exchange;
 pub mod market;
+pub mod metrics;
 pub mod net;
 pub mod state;
@@ -1,6 +1,9 @@
 //! Market module
 
+pub mod candle;
+pub mod derivatives;
 pub mod event;
+pub mod fixed;
 pub mod orderbook;
 pub mod trade;
 
@@ -22,6 +25,17 @@ pub struct Market {
     pub r#type: MarketType,
 }
 
+impl Market {
+    /// Returns the scales converting prices and sizes of the market to
+    /// fixed-point, `None` when an increment isn't positive
+    pub fn scales(&self) -> Option<fixed::MarketScales> {
+        Some(fixed::MarketScales {
+            price: fixed::Scale::new(self.price_increment)?,
+            size: fixed::Scale::new(self.size_increment)?,
+        })
+    }
+}
+
 impl From<MarketRef<'_>> for Market {
     fn from(market_ref: MarketRef) -> Self {
         Self {
@@ -39,7 +53,8 @@ impl From<MarketRef<'_>> for Market {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum MarketType {
     Spot(SpotMarket),
-    Futures,
+    Futures(FuturesMarket),
+    Options(OptionMarket),
 }
 
 /// Spot market information
@@ -52,9 +67,46 @@ pub struct SpotMarket {
 /// Futures market
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct FuturesMarket {
+    /// `None` for perpetuals and when the exchange doesn't list the expiry
     pub expires_at: Option<DateTime<Utc>>,
 }
 
+impl FuturesMarket {
+    /// Returns perpetual futures market
+    pub fn perpetual() -> Self {
+        Self { expires_at: None }
+    }
+
+    /// Returns futures market expiring at given time
+    pub fn expiring(expires_at: DateTime<Utc>) -> Self {
+        Self {
+            expires_at: Some(expires_at),
+        }
+    }
+
+    /// Returns whether the market never expires
+    pub fn is_perpetual(&self) -> bool {
+        self.expires_at.is_none()
+    }
+}
+
+/// Option market
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OptionMarket {
+    /// Underlying currency, e.g. `BTC`
+    pub underlying: String,
+    pub kind: OptionKind,
+    pub strike: f64,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Option kind enum
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum OptionKind {
+    Call,
+    Put,
+}
+
 impl From<Box<[Market]>> for MarketVec {
     fn from(markets: Box<[Market]>) -> Self {
         let mut vec = Self::with_capacity(markets.len());
@@ -0,0 +1,96 @@
+//! OHLCV candles
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::trade::Trade;
+
+/// Open, high, low and close prices and traded volume over an interval
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Candle {
+    /// Start of the interval covered by the candle
+    pub open_time: DateTime<Utc>,
+    /// Length of the interval, serialized in milliseconds
+    #[serde(rename = "interval_ms", with = "interval_ms")]
+    pub interval: Duration,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// Traded size
+    pub volume: f64,
+    /// Number of trades
+    pub trades: u64,
+}
+
+impl Candle {
+    /// Creates candle of the interval with its first trade
+    pub fn new(open_time: DateTime<Utc>, interval: Duration, trade: &Trade) -> Self {
+        Self {
+            open_time,
+            interval,
+            open: trade.price,
+            high: trade.price,
+            low: trade.price,
+            close: trade.price,
+            volume: trade.size,
+            trades: 1,
+        }
+    }
+
+    /// Returns the end of the interval, exclusive
+    pub fn close_time(&self) -> DateTime<Utc> {
+        self.open_time + self.interval
+    }
+
+    /// Updates the candle with the next trade
+    pub fn update(&mut self, trade: &Trade) {
+        self.high = self.high.max(trade.price);
+        self.low = self.low.min(trade.price);
+        self.close = trade.price;
+        self.volume += trade.size;
+        self.trades += 1;
+    }
+}
+
+mod interval_ms {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(interval: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(interval.num_milliseconds())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        i64::deserialize(deserializer).map(Duration::milliseconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_candle_update() {
+        let time = Utc.timestamp(1_600_000_000, 0);
+        let mut candle = Candle::new(time, Duration::minutes(1), &Trade::new(10.0, 1.0, time));
+        candle.update(&Trade::new(12.0, 0.5, time));
+        candle.update(&Trade::new(9.0, 2.0, time));
+
+        assert_eq!(
+            (10.0, 12.0, 9.0, 9.0),
+            (candle.open, candle.high, candle.low, candle.close)
+        );
+        assert_eq!(3.5, candle.volume);
+        assert_eq!(3, candle.trades);
+        assert_eq!(Utc.timestamp(1_600_000_060, 0), candle.close_time());
+    }
+
+    #[test]
+    fn test_candle_serde() {
+        let time = Utc.timestamp(1_600_000_000, 0);
+        let candle = Candle::new(time, Duration::minutes(1), &Trade::new(10.0, 1.0, time));
+
+        let bytes = bincode::serialize(&candle).unwrap();
+        assert_eq!(candle, bincode::deserialize::<Candle>(&bytes).unwrap());
+    }
+}
@@ -0,0 +1,42 @@
+//! Funding, open interest and liquidations of futures
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Funding rate of a perpetual futures market
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct FundingRate {
+    /// Rate paid by longs to shorts, negative when shorts pay longs
+    pub rate: f64,
+    /// Time the rate is paid at, when the exchange lists it
+    pub next_funding_time: Option<DateTime<Utc>>,
+    /// Time the rate was reported by the exchange
+    pub time: DateTime<Utc>,
+}
+
+/// Open interest of a futures market
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct OpenInterest {
+    /// Open contracts in the size unit of the market
+    pub open_interest: f64,
+    /// Time the open interest was reported by the exchange
+    pub time: DateTime<Utc>,
+}
+
+/// Side of a liquidated position
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum PositionSide {
+    /// Long position closed by a sell order
+    Long,
+    /// Short position closed by a buy order
+    Short,
+}
+
+/// Forced closing of a position
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Liquidation {
+    pub position: PositionSide,
+    pub price: f64,
+    pub size: f64,
+    /// Time of the liquidation specified by the exchange
+    pub time: DateTime<Utc>,
+}
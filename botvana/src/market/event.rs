@@ -1,13 +1,41 @@
-use super::{orderbook::*, trade::*, MarketVec};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    candle::Candle,
+    derivatives::*,
+    orderbook::{
+        l3::{L3Order, L3Update},
+        *,
+    },
+    trade::*,
+    MarketVec,
+};
 
 /// Market event enum produced by market data engine
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct MarketEvent {
     pub r#type: MarketEventType,
     pub timestamp: std::time::SystemTime,
+    /// Span following the event through the pipeline, set on events sampled
+    /// for tracing only
+    #[serde(skip)]
+    pub span: Option<tracing::Span>,
+    /// Times the event passed the stages of the pipeline, set on events
+    /// received from a live feed
+    #[serde(skip)]
+    pub times: Option<PipelineTimes>,
+}
+
+/// Monotonic times a market event passed the stages of the pipeline
+#[derive(Clone, Copy, Debug)]
+pub struct PipelineTimes {
+    /// Time the message of the event was read from the socket
+    pub received: std::time::Instant,
+    /// Time the event was pushed to the consumers of the market data engine
+    pub sent: std::time::Instant,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub enum MarketEventType {
     /// Markets update
     Markets(Box<MarketVec>),
@@ -17,6 +45,29 @@ pub enum MarketEventType {
     OrderbookUpdate(Box<str>, Box<PlainOrderbook<f64>>),
     /// Mid-price changed
     MidPriceChange(Box<str>, f64, f64),
+    /// Best bid or offer changed, emitted instead of `OrderbookUpdate` by
+    /// adapters configured for top of book only
+    BboUpdate(Box<str>, Bbo<f64>),
+    /// Levels changed since the previous `OrderbookUpdate` or delta, emitted
+    /// by adapters configured for deltas
+    OrderbookDelta(Box<str>, Box<OrderbookDelta<f64>>),
+    /// Funding rate of perpetual futures polled or streamed by the adapter
+    FundingRate(Box<str>, FundingRate),
+    /// Open interest of futures polled or streamed by the adapter
+    OpenInterest(Box<str>, OpenInterest),
+    /// Positions liquidated by the exchange
+    Liquidation(Box<str>, Box<[Liquidation]>),
+    /// Candle closed by the candle aggregation of the market data engine
+    Candle(Box<str>, Candle),
+    /// Orders of the order-by-order book, emitted by adapters configured
+    /// for L3 books after every snapshot
+    L3Snapshot(Box<str>, Box<[L3Order]>),
+    /// Changes of the order-by-order book since the previous `L3Snapshot`
+    /// or update
+    L3Update(Box<str>, Box<[L3Update]>),
+    /// Orderbook held by the market data engine, emitted when botvana-server
+    /// requests it and not a change of the book
+    OrderbookSnapshot(Box<str>, Box<PlainOrderbook<f64>>),
 }
 
 impl MarketEvent {
@@ -25,6 +76,8 @@ impl MarketEvent {
         Self {
             r#type,
             timestamp: std::time::SystemTime::now(),
+            span: None,
+            times: None,
         }
     }
 
@@ -38,13 +91,97 @@ impl MarketEvent {
         Self::new(MarketEventType::MidPriceChange(market, bid, ask))
     }
 
+    /// Creates new `MarketEvent::BboUpdate` variant
+    pub fn bbo_update(market: Box<str>, bbo: Bbo<f64>) -> Self {
+        Self::new(MarketEventType::BboUpdate(market, bbo))
+    }
+
+    /// Creates new `MarketEvent::OrderbookDelta` variant
+    pub fn orderbook_delta(market: Box<str>, delta: Box<OrderbookDelta<f64>>) -> Self {
+        Self::new(MarketEventType::OrderbookDelta(market, delta))
+    }
+
     /// Creates new `MarketEvent::OrderbookUpdate` variant
     pub fn orderbook_update(market: Box<str>, orderbook: Box<PlainOrderbook<f64>>) -> Self {
         Self::new(MarketEventType::OrderbookUpdate(market, orderbook))
     }
 
+    /// Creates new `MarketEvent::FundingRate` variant
+    pub fn funding_rate(market: Box<str>, funding: FundingRate) -> Self {
+        Self::new(MarketEventType::FundingRate(market, funding))
+    }
+
+    /// Creates new `MarketEvent::OpenInterest` variant
+    pub fn open_interest(market: Box<str>, open_interest: OpenInterest) -> Self {
+        Self::new(MarketEventType::OpenInterest(market, open_interest))
+    }
+
+    /// Creates new `MarketEvent::Liquidation` variant
+    pub fn liquidation(market: Box<str>, liquidations: Box<[Liquidation]>) -> Self {
+        Self::new(MarketEventType::Liquidation(market, liquidations))
+    }
+
+    /// Creates new `MarketEvent::Candle` variant
+    pub fn candle(market: Box<str>, candle: Candle) -> Self {
+        Self::new(MarketEventType::Candle(market, candle))
+    }
+
+    /// Creates new `MarketEvent::L3Snapshot` variant
+    pub fn l3_snapshot(market: Box<str>, orders: Box<[L3Order]>) -> Self {
+        Self::new(MarketEventType::L3Snapshot(market, orders))
+    }
+
+    /// Creates new `MarketEvent::L3Update` variant
+    pub fn l3_update(market: Box<str>, updates: Box<[L3Update]>) -> Self {
+        Self::new(MarketEventType::L3Update(market, updates))
+    }
+
+    /// Creates new `MarketEvent::OrderbookSnapshot` variant
+    pub fn orderbook_snapshot(market: Box<str>, orderbook: Box<PlainOrderbook<f64>>) -> Self {
+        Self::new(MarketEventType::OrderbookSnapshot(market, orderbook))
+    }
+
     /// Creates new `MarketEvent::Markets` variant
     pub fn markets(market_vec: Box<MarketVec>) -> Self {
         Self::new(MarketEventType::Markets(market_vec))
     }
+
+    /// Returns name of the market the event is about, `None` for `Markets`
+    pub fn market(&self) -> Option<&str> {
+        match &self.r#type {
+            MarketEventType::Markets(_) => None,
+            MarketEventType::Trades(market, _)
+            | MarketEventType::OrderbookUpdate(market, _)
+            | MarketEventType::MidPriceChange(market, _, _)
+            | MarketEventType::BboUpdate(market, _)
+            | MarketEventType::OrderbookDelta(market, _)
+            | MarketEventType::FundingRate(market, _)
+            | MarketEventType::OpenInterest(market, _)
+            | MarketEventType::Liquidation(market, _)
+            | MarketEventType::Candle(market, _)
+            | MarketEventType::L3Snapshot(market, _)
+            | MarketEventType::L3Update(market, _)
+            | MarketEventType::OrderbookSnapshot(market, _) => Some(market),
+        }
+    }
+
+    /// Returns mutable name of the market the event is about, `None` for
+    /// `Markets`
+    pub fn market_mut(&mut self) -> Option<&mut Box<str>> {
+        match &mut self.r#type {
+            MarketEventType::Markets(_) => None,
+            MarketEventType::Trades(market, _)
+            | MarketEventType::OrderbookUpdate(market, _)
+            | MarketEventType::MidPriceChange(market, _, _)
+            | MarketEventType::BboUpdate(market, _)
+            | MarketEventType::OrderbookDelta(market, _)
+            | MarketEventType::FundingRate(market, _)
+            | MarketEventType::OpenInterest(market, _)
+            | MarketEventType::Liquidation(market, _)
+            | MarketEventType::Candle(market, _)
+            | MarketEventType::L3Snapshot(market, _)
+            | MarketEventType::L3Update(market, _)
+            | MarketEventType::OrderbookSnapshot(market, _) => Some(market),
+        }
+    }
 }
@@ -0,0 +1,135 @@
+//! Fixed-point prices and sizes
+//!
+//! A [`Fixed`] is the number of increments of its market, so levels compare
+//! exactly and sum without rounding errors. [`Scale`] converts between
+//! `f64` and `Fixed` using the `price_increment` or `size_increment` of the
+//! market.
+
+use serde::{Deserialize, Serialize};
+
+/// Fixed-point number, the count of increments of its scale
+#[derive(
+    Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd, Deserialize, Serialize,
+)]
+pub struct Fixed(pub i64);
+
+impl Fixed {
+    pub const ZERO: Fixed = Fixed(0);
+
+    /// Returns the number of increments
+    pub fn mantissa(&self) -> i64 {
+        self.0
+    }
+}
+
+impl std::ops::Add for Fixed {
+    type Output = Fixed;
+
+    fn add(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0 + rhs.0)
+    }
+}
+
+impl std::ops::Sub for Fixed {
+    type Output = Fixed;
+
+    fn sub(self, rhs: Fixed) -> Fixed {
+        Fixed(self.0 - rhs.0)
+    }
+}
+
+/// Conversion between `f64` and [`Fixed`] in steps of an increment
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Scale {
+    increment: f64,
+    /// Number of increments in one, set for increments dividing one so
+    /// converting back is a single exact division
+    per_unit: Option<f64>,
+}
+
+impl Scale {
+    /// Creates scale of given increment, `None` unless it is positive
+    pub fn new(increment: f64) -> Option<Self> {
+        if !(increment > 0.0 && increment.is_finite()) {
+            return None;
+        }
+
+        let per_unit = (1.0 / increment).round();
+        let per_unit = match increment < 1.0 && (per_unit * increment - 1.0).abs() < 1e-9 {
+            true => Some(per_unit),
+            false => None,
+        };
+
+        Some(Self {
+            increment,
+            per_unit,
+        })
+    }
+
+    /// Returns the increment
+    pub fn increment(&self) -> f64 {
+        self.increment
+    }
+
+    /// Converts the value to the nearest number of increments
+    pub fn to_fixed(&self, value: f64) -> Fixed {
+        let increments = match self.per_unit {
+            Some(per_unit) => value * per_unit,
+            None => value / self.increment,
+        };
+
+        Fixed(increments.round() as i64)
+    }
+
+    /// Converts the number of increments back to `f64`
+    pub fn to_f64(&self, value: Fixed) -> f64 {
+        match self.per_unit {
+            Some(per_unit) => value.0 as f64 / per_unit,
+            None => value.0 as f64 * self.increment,
+        }
+    }
+
+    /// Rounds the value to the nearest increment
+    ///
+    /// Values rounded by the same scale are equal whenever their fixed
+    /// representations are.
+    pub fn round(&self, value: f64) -> f64 {
+        self.to_f64(self.to_fixed(value))
+    }
+}
+
+/// Price and size scales of a market
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MarketScales {
+    pub price: Scale,
+    pub size: Scale,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scale_roundtrip() {
+        let scale = Scale::new(0.1).unwrap();
+
+        assert_eq!(Fixed(3), scale.to_fixed(0.1 + 0.2));
+        assert_eq!(0.3, scale.to_f64(Fixed(3)));
+        assert_eq!(0.3, scale.round(0.1 + 0.2));
+
+        let scale = Scale::new(5.0).unwrap();
+        assert_eq!(Fixed(21), scale.to_fixed(104.0));
+        assert_eq!(105.0, scale.to_f64(Fixed(21)));
+
+        assert!(Scale::new(0.0).is_none());
+        assert!(Scale::new(f64::NAN).is_none());
+    }
+
+    #[test]
+    fn test_scale_small_increment() {
+        let scale = Scale::new(0.00000001).unwrap();
+
+        assert_eq!(Fixed(123_456_789), scale.to_fixed(1.23456789));
+        assert_eq!(1.23456789, scale.to_f64(Fixed(123_456_789)));
+    }
+}
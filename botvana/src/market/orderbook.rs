@@ -1,10 +1,15 @@
 //! Orderbook
 
+use super::fixed::{Fixed, MarketScales};
 use crate::exchange::ExchangeId;
 
 use rust_decimal::prelude::*;
 use serde::{Deserialize, Serialize};
 
+pub mod analytics;
+pub mod l3;
+pub mod snapshot;
+
 /// Trait representing an orderbook API
 pub trait UpdateOrderbook<T> {
     fn update(&mut self, bids: &PriceLevelsVec<T>, asks: &PriceLevelsVec<T>);
@@ -22,6 +27,9 @@ pub struct PlainOrderbook<T> {
     pub bids: PriceLevelsVec<T>,
     pub asks: PriceLevelsVec<T>,
     pub time: f64,
+    /// Number of best levels kept on each side, all when not set
+    #[serde(skip)]
+    pub max_depth: Option<usize>,
 }
 
 impl<T> PlainOrderbook<T> {
@@ -30,6 +38,7 @@ impl<T> PlainOrderbook<T> {
             bids: PriceLevelsVec::new(),
             asks: PriceLevelsVec::new(),
             time: 0.0,
+            max_depth: None,
         }
     }
 
@@ -38,6 +47,47 @@ impl<T> PlainOrderbook<T> {
             bids: PriceLevelsVec::with_capacity(cap),
             asks: PriceLevelsVec::with_capacity(cap),
             time: 0.0,
+            max_depth: None,
+        }
+    }
+
+    /// Returns the orderbook keeping only given number of best levels
+    pub fn with_max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.set_max_depth(max_depth);
+        self
+    }
+
+    /// Sets the number of best levels kept on each side and drops the
+    /// levels beyond it
+    pub fn set_max_depth(&mut self, max_depth: Option<usize>) {
+        self.max_depth = max_depth;
+        self.truncate();
+    }
+
+    /// Returns the best bid and offer, `None` while either side is empty
+    pub fn bbo(&self) -> Option<Bbo<T>>
+    where
+        T: Copy,
+    {
+        let bid = self.bids.price_vec.len().checked_sub(1)?;
+        if self.asks.price_vec.is_empty() {
+            return None;
+        }
+
+        Some(Bbo {
+            bid: self.bids.price_vec[bid],
+            bid_size: self.bids.size_vec[bid],
+            ask: self.asks.price_vec[0],
+            ask_size: self.asks.size_vec[0],
+        })
+    }
+
+    /// Drops the levels beyond `max_depth`, bids and asks are both sorted
+    /// by ascending price so the best bids are at the end
+    fn truncate(&mut self) {
+        if let Some(depth) = self.max_depth {
+            self.bids.retain_highest(depth);
+            self.asks.retain_lowest(depth);
         }
     }
 }
@@ -46,6 +96,7 @@ impl UpdateOrderbook<f64> for PlainOrderbook<f64> {
     fn update(&mut self, bids: &PriceLevelsVec<f64>, asks: &PriceLevelsVec<f64>) {
         self.bids.update(bids);
         self.asks.update(asks);
+        self.truncate();
     }
 
     fn update_with_timestamp(
@@ -54,8 +105,7 @@ impl UpdateOrderbook<f64> for PlainOrderbook<f64> {
         asks: &PriceLevelsVec<f64>,
         time: f64,
     ) {
-        self.bids.update(bids);
-        self.asks.update(asks);
+        self.update(bids, asks);
         self.time = time;
     }
 }
@@ -64,6 +114,7 @@ impl UpdateOrderbook<Decimal> for PlainOrderbook<Decimal> {
     fn update(&mut self, bids: &PriceLevelsVec<Decimal>, asks: &PriceLevelsVec<Decimal>) {
         self.bids.update(bids);
         self.asks.update(asks);
+        self.truncate();
     }
 
     fn update_with_timestamp(
@@ -72,12 +123,86 @@ impl UpdateOrderbook<Decimal> for PlainOrderbook<Decimal> {
         asks: &PriceLevelsVec<Decimal>,
         time: f64,
     ) {
+        self.update(bids, asks);
+        self.time = time;
+    }
+}
+
+impl UpdateOrderbook<Fixed> for PlainOrderbook<Fixed> {
+    fn update(&mut self, bids: &PriceLevelsVec<Fixed>, asks: &PriceLevelsVec<Fixed>) {
         self.bids.update(bids);
         self.asks.update(asks);
+        self.truncate();
+    }
+
+    fn update_with_timestamp(
+        &mut self,
+        bids: &PriceLevelsVec<Fixed>,
+        asks: &PriceLevelsVec<Fixed>,
+        time: f64,
+    ) {
+        self.update(bids, asks);
         self.time = time;
     }
 }
 
+impl PlainOrderbook<f64> {
+    /// Returns the orderbook converted to fixed-point with the scales of its
+    /// market
+    pub fn to_fixed(&self, scales: &MarketScales) -> PlainOrderbook<Fixed> {
+        PlainOrderbook {
+            bids: self.bids.to_fixed(scales),
+            asks: self.asks.to_fixed(scales),
+            time: self.time,
+            max_depth: self.max_depth,
+        }
+    }
+}
+
+impl PlainOrderbook<Fixed> {
+    /// Returns the fixed-point orderbook converted back to `f64`
+    pub fn to_f64(&self, scales: &MarketScales) -> PlainOrderbook<f64> {
+        PlainOrderbook {
+            bids: self.bids.to_f64(scales),
+            asks: self.asks.to_f64(scales),
+            time: self.time,
+            max_depth: self.max_depth,
+        }
+    }
+}
+
+/// Price levels changed by an orderbook update
+///
+/// Levels with zero size were removed. Deltas of a market are numbered from 1
+/// after every full orderbook, a gap in the sequence means deltas were missed
+/// and the book has to be replaced by the next full one.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct OrderbookDelta<T> {
+    pub bids: PriceLevelsVec<T>,
+    pub asks: PriceLevelsVec<T>,
+    pub time: f64,
+    pub sequence: u64,
+}
+
+impl<T> PlainOrderbook<T>
+where
+    Self: UpdateOrderbook<T>,
+{
+    /// Applies the changed levels of the delta
+    pub fn apply_delta(&mut self, delta: &OrderbookDelta<T>) {
+        self.update_with_timestamp(&delta.bids, &delta.asks, delta.time);
+    }
+}
+
+/// Best bid and offer of an orderbook
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Bbo<T> {
+    pub bid: T,
+    pub bid_size: T,
+    pub ask: T,
+    pub ask_size: T,
+}
+
 /// Orderbook with market name and exchange with bids and asks
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Orderbook<T> {
@@ -107,6 +232,7 @@ impl<T> From<Orderbook<T>> for PlainOrderbook<T> {
             bids: orderbook.bids,
             asks: orderbook.asks,
             time: orderbook.time,
+            max_depth: None,
         }
     }
 }
@@ -148,23 +274,59 @@ impl<T> PriceLevelsVec<T> {
     where
         T: PartialOrd + Clone + Copy,
     {
-        let mut price_vec = Vec::with_capacity(data.len());
-        let mut size_vec = Vec::with_capacity(data.len());
+        let mut levels = Self::with_capacity(data.len());
+        levels.extend_from_tuples(data);
+
+        levels
+    }
+
+    /// Appends given price and size tuples, sorted like the existing levels
+    pub fn extend_from_tuples(&mut self, data: &[(T, T)])
+    where
+        T: Copy,
+    {
+        self.price_vec.reserve(data.len());
+        self.size_vec.reserve(data.len());
 
         data.iter().for_each(|(price, size)| {
-            price_vec.push(*price);
-            size_vec.push(*size);
+            self.price_vec.push(*price);
+            self.size_vec.push(*size);
         });
+    }
 
-        PriceLevelsVec {
-            price_vec,
-            size_vec,
-        }
+    /// Replaces the levels with copies of given ones, reusing the allocated
+    /// buffers
+    pub fn copy_from(&mut self, other: &Self)
+    where
+        T: Copy,
+    {
+        self.price_vec.clone_from(&other.price_vec);
+        self.size_vec.clone_from(&other.size_vec);
+    }
+
+    /// Removes all levels, keeping the allocated buffers
+    pub fn clear(&mut self) {
+        self.price_vec.clear();
+        self.size_vec.clear();
     }
 
     pub fn len(&self) -> usize {
         self.price_vec.len()
     }
+
+    /// Keeps only given number of levels with the highest prices
+    pub fn retain_highest(&mut self, depth: usize) {
+        if let Some(excess) = self.len().checked_sub(depth) {
+            self.price_vec.drain(..excess);
+            self.size_vec.drain(..excess);
+        }
+    }
+
+    /// Keeps only given number of levels with the lowest prices
+    pub fn retain_lowest(&mut self, depth: usize) {
+        self.price_vec.truncate(depth);
+        self.size_vec.truncate(depth);
+    }
 }
 
 impl PriceLevelsVec<Decimal> {
@@ -187,6 +349,8 @@ impl PriceLevelsVec<Decimal> {
                             *old_size = *new_size;
                         }
                     }
+                    // Removal of a level that isn't in the book
+                    Err(_) if *new_size == Decimal::ZERO => {}
                     Err(pos) => {
                         self.price_vec.insert(pos, *price);
                         self.size_vec.insert(pos, *new_size);
@@ -196,7 +360,77 @@ impl PriceLevelsVec<Decimal> {
     }
 }
 
+impl PriceLevelsVec<Fixed> {
+    pub fn update(&mut self, update: &PriceLevelsVec<Fixed>) {
+        update
+            .price_vec
+            .iter()
+            .zip(update.size_vec.iter())
+            .for_each(
+                |(price, new_size)| match self.price_vec.binary_search(price) {
+                    Ok(pos) => {
+                        if *new_size == Fixed::ZERO {
+                            self.price_vec.remove(pos);
+                            self.size_vec.remove(pos);
+                        } else {
+                            self.size_vec[pos] = *new_size;
+                        }
+                    }
+                    // Removal of a level that isn't in the book
+                    Err(_) if *new_size == Fixed::ZERO => {}
+                    Err(pos) => {
+                        self.price_vec.insert(pos, *price);
+                        self.size_vec.insert(pos, *new_size);
+                    }
+                },
+            );
+    }
+
+    /// Returns the levels converted back to `f64`
+    pub fn to_f64(&self, scales: &MarketScales) -> PriceLevelsVec<f64> {
+        PriceLevelsVec {
+            price_vec: self
+                .price_vec
+                .iter()
+                .map(|p| scales.price.to_f64(*p))
+                .collect(),
+            size_vec: self
+                .size_vec
+                .iter()
+                .map(|s| scales.size.to_f64(*s))
+                .collect(),
+        }
+    }
+}
+
 impl PriceLevelsVec<f64> {
+    /// Returns the levels converted to fixed-point
+    pub fn to_fixed(&self, scales: &MarketScales) -> PriceLevelsVec<Fixed> {
+        PriceLevelsVec {
+            price_vec: self
+                .price_vec
+                .iter()
+                .map(|p| scales.price.to_fixed(*p))
+                .collect(),
+            size_vec: self
+                .size_vec
+                .iter()
+                .map(|s| scales.size.to_fixed(*s))
+                .collect(),
+        }
+    }
+
+    /// Rounds prices and sizes to the increments of the market, so equal
+    /// levels compare equal however the exchange formatted them
+    pub fn round_to(&mut self, scales: &MarketScales) {
+        self.price_vec
+            .iter_mut()
+            .for_each(|price| *price = scales.price.round(*price));
+        self.size_vec
+            .iter_mut()
+            .for_each(|size| *size = scales.size.round(*size));
+    }
+
     pub fn update(&mut self, update: &PriceLevelsVec<f64>) {
         update
             .price_vec
@@ -216,6 +450,8 @@ impl PriceLevelsVec<f64> {
                             *old_size = *new_size;
                         }
                     }
+                    // Removal of a level that isn't in the book
+                    Err(_) if *new_size == 0.0 => {}
                     Err(pos) => {
                         self.price_vec.insert(pos, *price);
                         self.size_vec.insert(pos, *new_size);
@@ -319,4 +555,92 @@ mod tests {
         assert_eq!(price_levels.price_vec[3], 13.1);
         assert_eq!(price_levels.size_vec[3], 20.0);
     }
+
+    #[test]
+    fn test_orderbook_max_depth() {
+        let levels = |prices: &[f64]| {
+            PriceLevelsVec::from_tuples_vec(
+                &prices.iter().map(|price| (*price, 1.0)).collect::<Vec<_>>(),
+            )
+        };
+        let mut orderbook = PlainOrderbook::<f64>::new().with_max_depth(Some(2));
+
+        orderbook.update(&levels(&[9.0, 9.5, 9.8]), &levels(&[10.0, 10.2, 10.5]));
+
+        assert_eq!(vec![9.5, 9.8], orderbook.bids.price_vec);
+        assert_eq!(vec![10.0, 10.2], orderbook.asks.price_vec);
+        assert_eq!(2, orderbook.bids.size_vec.len());
+        assert_eq!(2, orderbook.asks.size_vec.len());
+
+        orderbook.set_max_depth(Some(1));
+        assert_eq!(vec![9.8], orderbook.bids.price_vec);
+        assert_eq!(vec![10.0], orderbook.asks.price_vec);
+    }
+
+    #[test]
+    fn test_orderbook_bbo() {
+        let mut orderbook = PlainOrderbook::<f64>::new();
+        orderbook.update(
+            &PriceLevelsVec::from_tuples_vec(&[(9.5, 2.0), (9.8, 1.0)]),
+            &PriceLevelsVec::new(),
+        );
+        assert_eq!(None, orderbook.bbo());
+
+        orderbook.update(
+            &PriceLevelsVec::new(),
+            &PriceLevelsVec::from_tuples_vec(&[(10.0, 3.0), (10.2, 4.0)]),
+        );
+        let bbo = Bbo {
+            bid: 9.8,
+            bid_size: 1.0,
+            ask: 10.0,
+            ask_size: 3.0,
+        };
+        assert_eq!(Some(bbo), orderbook.bbo());
+    }
+
+    #[test]
+    fn test_orderbook_fixed() {
+        let scales = MarketScales {
+            price: crate::market::fixed::Scale::new(0.01).unwrap(),
+            size: crate::market::fixed::Scale::new(0.001).unwrap(),
+        };
+        let mut orderbook = PlainOrderbook::<f64>::new();
+        orderbook.update(
+            &PriceLevelsVec::from_tuples_vec(&[(0.1 + 0.2, 1.5)]),
+            &PriceLevelsVec::from_tuples_vec(&[(0.31, 2.0)]),
+        );
+
+        let mut fixed = orderbook.to_fixed(&scales);
+        assert_eq!(vec![Fixed(30)], fixed.bids.price_vec);
+
+        // Level formatted differently by the exchange is the same level
+        fixed.update(
+            &PriceLevelsVec::from_tuples_vec(&[(scales.price.to_fixed(0.3), Fixed::ZERO)]),
+            &PriceLevelsVec::new(),
+        );
+        assert_eq!(0, fixed.bids.len());
+        assert_eq!(vec![0.31], fixed.to_f64(&scales).asks.price_vec);
+    }
+
+    #[test]
+    fn test_update_skips_removal_of_unknown_level() {
+        let mut fixed = PriceLevelsVec::from_tuples_vec(&[(Fixed(100), Fixed(5))]);
+        fixed.update(&PriceLevelsVec::from_tuples_vec(&[
+            (Fixed(99), Fixed::ZERO),
+            (Fixed(101), Fixed::ZERO),
+        ]));
+        assert_eq!(vec![Fixed(100)], fixed.price_vec);
+        assert_eq!(vec![Fixed(5)], fixed.size_vec);
+
+        let mut orderbook = PlainOrderbook::<f64>::new();
+        orderbook.update(
+            &PriceLevelsVec::from_tuples_vec(&[(9.0, 1.0), (9.5, 0.0)]),
+            &PriceLevelsVec::from_tuples_vec(&[(10.0, 0.0), (10.5, 2.0)]),
+        );
+        assert_eq!(vec![9.0], orderbook.bids.price_vec);
+        assert_eq!(vec![10.5], orderbook.asks.price_vec);
+        assert_eq!(9.0, orderbook.bbo().unwrap().bid);
+        assert_eq!(10.5, orderbook.bbo().unwrap().ask);
+    }
 }
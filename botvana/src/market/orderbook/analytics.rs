@@ -0,0 +1,287 @@
+//! Orderbook analytics
+//!
+//! Measures strategies commonly derive from the top of the book, computed
+//! over the best `depth` levels of each side without copying them.
+
+use super::{PlainOrderbook, PriceLevelsVec};
+
+impl PriceLevelsVec<f64> {
+    /// Returns the highest price levels, best first
+    pub fn best_bids(&self, depth: usize) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.price_vec
+            .iter()
+            .copied()
+            .zip(self.size_vec.iter().copied())
+            .rev()
+            .take(depth)
+    }
+
+    /// Returns the lowest price levels, best first
+    pub fn best_asks(&self, depth: usize) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.price_vec
+            .iter()
+            .copied()
+            .zip(self.size_vec.iter().copied())
+            .take(depth)
+    }
+}
+
+/// Returns summed size and size-weighted average price of the levels
+fn weighted(levels: impl Iterator<Item = (f64, f64)>) -> Option<(f64, f64)> {
+    let (size, notional) = levels.fold((0.0, 0.0), |(size, notional), (p, s)| {
+        (size + s, notional + p * s)
+    });
+
+    (size > 0.0).then(|| (size, notional / size))
+}
+
+impl PlainOrderbook<f64> {
+    /// Returns size of the best `depth` bid levels
+    pub fn bid_depth(&self, depth: usize) -> f64 {
+        self.bids.best_bids(depth).map(|(_, size)| size).sum()
+    }
+
+    /// Returns size of the best `depth` ask levels
+    pub fn ask_depth(&self, depth: usize) -> f64 {
+        self.asks.best_asks(depth).map(|(_, size)| size).sum()
+    }
+
+    /// Returns volume imbalance of the best `depth` levels of each side
+    ///
+    /// `(bid size - ask size) / (bid size + ask size)` in `-1.0..=1.0`,
+    /// positive when the bids outweigh the asks. `None` for an empty book.
+    pub fn imbalance(&self, depth: usize) -> Option<f64> {
+        let (bids, asks) = (self.bid_depth(depth), self.ask_depth(depth));
+        let total = bids + asks;
+
+        (total > 0.0).then(|| (bids - asks) / total)
+    }
+
+    /// Returns the mid price
+    pub fn mid_price(&self) -> Option<f64> {
+        let bbo = self.bbo()?;
+
+        Some((bbo.bid + bbo.ask) / 2.0)
+    }
+
+    /// Returns the microprice, the mid price weighted by the size on the
+    /// opposite side of the best level, leaning towards the side that is
+    /// more likely to be taken out next
+    pub fn microprice(&self) -> Option<f64> {
+        let bbo = self.bbo()?;
+        let size = bbo.bid_size + bbo.ask_size;
+        if size <= 0.0 {
+            return None;
+        }
+
+        Some((bbo.bid * bbo.ask_size + bbo.ask * bbo.bid_size) / size)
+    }
+
+    /// Returns the spread in ticks of given size, negative for a crossed
+    /// book
+    pub fn spread_ticks(&self, tick: f64) -> Option<i64> {
+        if tick <= 0.0 {
+            return None;
+        }
+        let bbo = self.bbo()?;
+
+        Some(((bbo.ask - bbo.bid) / tick).round() as i64)
+    }
+
+    /// Returns size-weighted average price of the best `depth` bid levels
+    pub fn weighted_bid(&self, depth: usize) -> Option<f64> {
+        weighted(self.bids.best_bids(depth)).map(|(_, price)| price)
+    }
+
+    /// Returns size-weighted average price of the best `depth` ask levels
+    pub fn weighted_ask(&self, depth: usize) -> Option<f64> {
+        weighted(self.asks.best_asks(depth)).map(|(_, price)| price)
+    }
+
+    /// Returns average price of selling `size` into the bids, `None` when
+    /// the book doesn't have the size
+    pub fn sell_price(&self, size: f64) -> Option<f64> {
+        fill_price(self.bids.best_bids(usize::MAX), size)
+    }
+
+    /// Returns average price of buying `size` from the asks, `None` when
+    /// the book doesn't have the size
+    pub fn buy_price(&self, size: f64) -> Option<f64> {
+        fill_price(self.asks.best_asks(usize::MAX), size)
+    }
+}
+
+/// Returns average price of filling `size` from the levels in order
+fn fill_price(levels: impl Iterator<Item = (f64, f64)>, size: f64) -> Option<f64> {
+    if size <= 0.0 {
+        return None;
+    }
+    let (mut remaining, mut notional) = (size, 0.0);
+
+    for (price, level_size) in levels {
+        let filled = level_size.min(remaining);
+        notional += price * filled;
+        remaining -= filled;
+        if remaining <= 0.0 {
+            return Some(notional / size);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    const TICK: f64 = 0.5;
+
+    /// Book with bids below and asks above tick 5000
+    fn book(bids: &BTreeMap<u32, f64>, asks: &BTreeMap<u32, f64>) -> PlainOrderbook<f64> {
+        let levels = |levels: &BTreeMap<u32, f64>, offset: u32| {
+            let levels: Vec<_> = levels
+                .iter()
+                .map(|(tick, size)| ((tick + offset) as f64 * TICK, *size))
+                .collect();
+            PriceLevelsVec::from_tuples_vec(&levels)
+        };
+
+        PlainOrderbook {
+            bids: levels(bids, 0),
+            asks: levels(asks, 5000),
+            time: 0.0,
+            max_depth: None,
+        }
+    }
+
+    /// Levels best first, found by sorting all of them
+    fn sorted(levels: &PriceLevelsVec<f64>, bids: bool) -> Vec<(f64, f64)> {
+        let mut levels: Vec<_> = levels
+            .price_vec
+            .iter()
+            .copied()
+            .zip(levels.size_vec.iter().copied())
+            .collect();
+        levels.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        if bids {
+            levels.reverse();
+        }
+        levels
+    }
+
+    fn approx_eq(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-9 * a.abs().max(b.abs()).max(1.0)
+    }
+
+    fn levels() -> impl Strategy<Value = BTreeMap<u32, f64>> {
+        prop::collection::btree_map(1u32..5000, 0.001f64..100.0, 0..30)
+    }
+
+    #[test]
+    fn test_analytics() {
+        let orderbook = book(
+            &BTreeMap::from([(4998, 3.0), (4999, 1.0)]),
+            &BTreeMap::from([(1, 3.0), (2, 1.0)]),
+        );
+
+        assert_eq!(Some(2), orderbook.spread_ticks(TICK));
+        assert_eq!(Some(-0.5), orderbook.imbalance(1));
+        assert_eq!(Some(0.0), orderbook.imbalance(2));
+        // 2499.5 bid, 2500.5 ask, 1 bid and 3 ask
+        assert_eq!(Some(2499.75), orderbook.microprice());
+        assert_eq!(Some(2499.125), orderbook.weighted_bid(2));
+        assert_eq!(Some(2500.5), orderbook.buy_price(2.0));
+        assert_eq!(Some(2500.625), orderbook.buy_price(4.0));
+        assert_eq!(None, orderbook.buy_price(5.0));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_imbalance(bids in levels(), asks in levels(), depth in 0usize..40) {
+            let orderbook = book(&bids, &asks);
+            let size = |levels: Vec<(f64, f64)>| -> f64 {
+                levels.iter().take(depth).map(|l| l.1).sum()
+            };
+            let bid_size = size(sorted(&orderbook.bids, true));
+            let ask_size = size(sorted(&orderbook.asks, false));
+
+            match orderbook.imbalance(depth) {
+                Some(imbalance) => {
+                    let expected = (bid_size - ask_size) / (bid_size + ask_size);
+                    prop_assert!(approx_eq(expected, imbalance));
+                    prop_assert!((-1.0..=1.0).contains(&imbalance));
+                }
+                None => prop_assert_eq!(0.0, bid_size + ask_size),
+            }
+        }
+
+        #[test]
+        fn prop_microprice_and_spread(bids in levels(), asks in levels()) {
+            let orderbook = book(&bids, &asks);
+            let best = bids.iter().next_back().zip(asks.iter().next());
+
+            match best {
+                Some(((bid_tick, bid_size), (ask_tick, ask_size))) => {
+                    let (bid, ask) = (*bid_tick as f64 * TICK, (ask_tick + 5000) as f64 * TICK);
+                    let microprice = (bid * ask_size + ask * bid_size) / (bid_size + ask_size);
+
+                    prop_assert!(approx_eq(microprice, orderbook.microprice().unwrap()));
+                    prop_assert_eq!(
+                        Some((ask_tick + 5000 - bid_tick) as i64),
+                        orderbook.spread_ticks(TICK)
+                    );
+                }
+                None => {
+                    prop_assert_eq!(None, orderbook.microprice());
+                    prop_assert_eq!(None, orderbook.spread_ticks(TICK));
+                }
+            }
+        }
+
+        #[test]
+        fn prop_weighted_prices(bids in levels(), asks in levels(), depth in 1usize..40) {
+            let orderbook = book(&bids, &asks);
+
+            for (levels, weighted) in [
+                (sorted(&orderbook.bids, true), orderbook.weighted_bid(depth)),
+                (sorted(&orderbook.asks, false), orderbook.weighted_ask(depth)),
+            ] {
+                let top = &levels[..depth.min(levels.len())];
+                let size: f64 = top.iter().map(|l| l.1).sum();
+                let notional: f64 = top.iter().map(|l| l.0 * l.1).sum();
+
+                match weighted {
+                    Some(price) => prop_assert!(approx_eq(notional / size, price)),
+                    None => prop_assert!(top.is_empty()),
+                }
+            }
+        }
+
+        #[test]
+        fn prop_fill_price(asks in levels(), size in 0.001f64..500.0) {
+            let orderbook = book(&BTreeMap::new(), &asks);
+            let levels = sorted(&orderbook.asks, false);
+
+            // Fills whole levels until the last partially filled one
+            let mut remaining = size;
+            let mut notional = 0.0;
+            for (price, level_size) in &levels {
+                if remaining <= 0.0 {
+                    break;
+                }
+                let filled = if *level_size < remaining { *level_size } else { remaining };
+                notional += price * filled;
+                remaining -= filled;
+            }
+
+            match orderbook.buy_price(size) {
+                Some(price) => prop_assert!(approx_eq(notional / size, price)),
+                None => prop_assert!(remaining > 0.0),
+            }
+        }
+    }
+}
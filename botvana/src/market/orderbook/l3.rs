@@ -0,0 +1,342 @@
+//! Order-by-order (L3) orderbook
+//!
+//! Exchanges publishing every order of their books, like the Coinbase `full`
+//! channel, allow following the queue of each price level. [`L3Orderbook`]
+//! keeps the orders by id with the queue of every level in arrival order,
+//! adapters emit the [`L3Update`]s they apply to it so consumers can keep a
+//! copy of the book.
+//!
+//! Queue positions are expressed with the arrival numbers of the book: an
+//! order joining a level now queues behind every order arrived before
+//! [`L3Orderbook::next_seq`], and only those orders leaving the book move
+//! it up.
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{PlainOrderbook, PriceLevelsVec};
+
+/// Side of the book an order rests on
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BookSide {
+    Bid,
+    Ask,
+}
+
+/// Order resting in the book
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct L3Order {
+    pub id: Box<str>,
+    pub side: BookSide,
+    pub price: f64,
+    pub size: f64,
+}
+
+/// Change of an order-by-order book
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum L3Update {
+    /// Order joined the back of the queue at its price
+    Open(L3Order),
+    /// Size of the order changed, the order keeps its position
+    Change { id: Box<str>, size: f64 },
+    /// Size of the order was taken by a trade
+    Fill { id: Box<str>, size: f64 },
+    /// Order was cancelled or filled and left the book
+    Done { id: Box<str> },
+}
+
+/// Price ordered by [`f64::total_cmp`]
+#[derive(Clone, Copy, Debug)]
+struct Price(f64);
+
+impl PartialEq for Price {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// Order of the book with its arrival number
+#[derive(Clone, Debug)]
+struct RestingOrder {
+    side: BookSide,
+    price: f64,
+    seq: u64,
+}
+
+/// Sizes of the orders of a level by arrival number
+type Queue = BTreeMap<u64, f64>;
+
+/// Orderbook of individual orders
+#[derive(Clone, Debug, Default)]
+pub struct L3Orderbook {
+    orders: HashMap<Box<str>, RestingOrder>,
+    bids: BTreeMap<Price, Queue>,
+    asks: BTreeMap<Price, Queue>,
+    next_seq: u64,
+}
+
+impl L3Orderbook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates book of the orders, queued in the given order
+    pub fn from_orders<'a>(orders: impl IntoIterator<Item = &'a L3Order>) -> Self {
+        let mut book = Self::new();
+        for order in orders {
+            book.open(order);
+        }
+        book
+    }
+
+    /// Returns the orders, bids before asks, levels from the lowest price
+    /// and each level in queue order
+    pub fn orders(&self) -> Vec<L3Order> {
+        let mut orders: Vec<_> = self.orders.iter().collect();
+        orders.sort_by(|(_, a), (_, b)| {
+            (a.side as u8, Price(a.price), a.seq).cmp(&(b.side as u8, Price(b.price), b.seq))
+        });
+
+        orders
+            .into_iter()
+            .map(|(id, order)| L3Order {
+                id: id.clone(),
+                side: order.side,
+                price: order.price,
+                size: self
+                    .queue(order.side, order.price)
+                    .map_or(0.0, |q| q[&order.seq]),
+            })
+            .collect()
+    }
+
+    /// Returns number of orders in the book
+    pub fn len(&self) -> usize {
+        self.orders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orders.is_empty()
+    }
+
+    /// Returns side, price and size of the order
+    pub fn order(&self, id: &str) -> Option<(BookSide, f64, f64)> {
+        let order = self.orders.get(id)?;
+        let size = self.queue(order.side, order.price)?.get(&order.seq)?;
+
+        Some((order.side, order.price, *size))
+    }
+
+    /// Applies the update, returns side and price of the changed level
+    ///
+    /// Updates of orders not in the book are ignored.
+    pub fn apply(&mut self, update: &L3Update) -> Option<(BookSide, f64)> {
+        match update {
+            L3Update::Open(order) => {
+                self.open(order);
+                Some((order.side, order.price))
+            }
+            L3Update::Change { id, size } => self.set_size(id, |_| *size),
+            L3Update::Fill { id, size } => self.set_size(id, |remaining| remaining - size),
+            L3Update::Done { id } => self.remove(id),
+        }
+    }
+
+    fn open(&mut self, order: &L3Order) {
+        // Reopened order goes to the back of the queue
+        self.remove(&order.id);
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        self.orders.insert(
+            order.id.clone(),
+            RestingOrder {
+                side: order.side,
+                price: order.price,
+                seq,
+            },
+        );
+        self.levels_mut(order.side)
+            .entry(Price(order.price))
+            .or_default()
+            .insert(seq, order.size);
+    }
+
+    /// Sets size of the order, removing it once nothing is left
+    fn set_size(&mut self, id: &str, size: impl FnOnce(f64) -> f64) -> Option<(BookSide, f64)> {
+        let order = self.orders.get(id)?.clone();
+        let queue = self.levels_mut(order.side).get_mut(&Price(order.price))?;
+        let remaining = queue.get_mut(&order.seq)?;
+        *remaining = size(*remaining);
+
+        if *remaining <= 0.0 {
+            return self.remove(id);
+        }
+
+        Some((order.side, order.price))
+    }
+
+    fn remove(&mut self, id: &str) -> Option<(BookSide, f64)> {
+        let order = self.orders.remove(id)?;
+        let levels = self.levels_mut(order.side);
+        if let Some(queue) = levels.get_mut(&Price(order.price)) {
+            queue.remove(&order.seq);
+            if queue.is_empty() {
+                levels.remove(&Price(order.price));
+            }
+        }
+
+        Some((order.side, order.price))
+    }
+
+    fn levels(&self, side: BookSide) -> &BTreeMap<Price, Queue> {
+        match side {
+            BookSide::Bid => &self.bids,
+            BookSide::Ask => &self.asks,
+        }
+    }
+
+    fn levels_mut(&mut self, side: BookSide) -> &mut BTreeMap<Price, Queue> {
+        match side {
+            BookSide::Bid => &mut self.bids,
+            BookSide::Ask => &mut self.asks,
+        }
+    }
+
+    fn queue(&self, side: BookSide, price: f64) -> Option<&Queue> {
+        self.levels(side).get(&Price(price))
+    }
+
+    /// Returns size of all orders at the price
+    pub fn level_size(&self, side: BookSide, price: f64) -> f64 {
+        self.queue(side, price)
+            .map_or(0.0, |queue| queue.values().sum())
+    }
+
+    /// Returns arrival number of the next order, orders joining the book
+    /// now queue behind every order arrived before it
+    pub fn next_seq(&self) -> u64 {
+        self.next_seq
+    }
+
+    /// Returns size at the price queued ahead of an order that joined the
+    /// book when [`L3Orderbook::next_seq`] was `seq`
+    pub fn queue_ahead(&self, side: BookSide, price: f64, seq: u64) -> f64 {
+        self.queue(side, price)
+            .map_or(0.0, |queue| queue.range(..seq).map(|(_, size)| size).sum())
+    }
+
+    /// Returns the book aggregated to price levels
+    pub fn to_plain(&self, time: f64) -> PlainOrderbook<f64> {
+        let aggregate = |levels: &BTreeMap<Price, Queue>| {
+            let levels: Vec<_> = levels
+                .iter()
+                .map(|(price, queue)| (price.0, queue.values().sum()))
+                .collect();
+            PriceLevelsVec::from_tuples_vec(&levels)
+        };
+
+        PlainOrderbook {
+            bids: aggregate(&self.bids),
+            asks: aggregate(&self.asks),
+            time,
+            max_depth: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(id: &str, side: BookSide, price: f64, size: f64) -> L3Update {
+        L3Update::Open(L3Order {
+            id: Box::from(id),
+            side,
+            price,
+            size,
+        })
+    }
+
+    #[test]
+    fn test_queue_ahead() {
+        let mut book = L3Orderbook::new();
+        book.apply(&open("a", BookSide::Bid, 100.0, 1.0));
+        book.apply(&open("b", BookSide::Bid, 100.0, 2.0));
+        book.apply(&open("x", BookSide::Ask, 101.0, 5.0));
+
+        // Joins behind a and b
+        let seq = book.next_seq();
+        book.apply(&open("c", BookSide::Bid, 100.0, 4.0));
+        assert_eq!(3.0, book.queue_ahead(BookSide::Bid, 100.0, seq));
+        assert_eq!(7.0, book.level_size(BookSide::Bid, 100.0));
+
+        // Cancellation behind doesn't move the order up
+        book.apply(&L3Update::Done { id: Box::from("c") });
+        assert_eq!(3.0, book.queue_ahead(BookSide::Bid, 100.0, seq));
+
+        book.apply(&L3Update::Fill {
+            id: Box::from("a"),
+            size: 0.5,
+        });
+        assert_eq!(2.5, book.queue_ahead(BookSide::Bid, 100.0, seq));
+        assert_eq!(
+            Some((BookSide::Bid, 100.0)),
+            book.apply(&L3Update::Change {
+                id: Box::from("b"),
+                size: 1.0
+            })
+        );
+        assert_eq!(1.5, book.queue_ahead(BookSide::Bid, 100.0, seq));
+
+        // Filled in full
+        book.apply(&L3Update::Fill {
+            id: Box::from("a"),
+            size: 0.5,
+        });
+        assert_eq!(None, book.order("a"));
+        assert_eq!(None, book.apply(&L3Update::Done { id: Box::from("a") }));
+        assert_eq!(2, book.len());
+    }
+
+    #[test]
+    fn test_aggregate_and_orders() {
+        let mut book = L3Orderbook::new();
+        book.apply(&open("a", BookSide::Bid, 99.0, 1.0));
+        book.apply(&open("b", BookSide::Bid, 100.0, 2.0));
+        book.apply(&open("c", BookSide::Bid, 99.0, 0.5));
+        book.apply(&open("d", BookSide::Ask, 101.0, 3.0));
+
+        let plain = book.to_plain(1.0);
+        assert_eq!(vec![99.0, 100.0], plain.bids.price_vec);
+        assert_eq!(vec![1.5, 2.0], plain.bids.size_vec);
+        assert_eq!(vec![101.0], plain.asks.price_vec);
+
+        let orders = book.orders();
+        let ids: Vec<_> = orders.iter().map(|order| &*order.id).collect();
+        assert_eq!(vec!["a", "c", "b", "d"], ids);
+
+        let copy = L3Orderbook::from_orders(&orders);
+        assert_eq!(orders, copy.orders());
+        assert_eq!(1.5, copy.queue_ahead(BookSide::Bid, 99.0, copy.next_seq()));
+    }
+}
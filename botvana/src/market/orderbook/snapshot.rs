@@ -0,0 +1,117 @@
+//! Binary snapshots of orderbooks
+//!
+//! [`BookSnapshots`] holds the books of one exchange as they were at
+//! `saved_at`. It's encoded with bincode behind a header of [`MAGIC`] and
+//! [`VERSION`], so files written by other versions are rejected instead of
+//! being misread.
+
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use super::PlainOrderbook;
+
+/// Bytes every encoded snapshot starts with
+pub const MAGIC: &[u8; 4] = b"BVOB";
+
+/// Version of the encoding
+pub const VERSION: u16 = 1;
+
+/// Error decoding the snapshot
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    #[error("not an orderbook snapshot")]
+    Magic,
+    #[error("unsupported snapshot version {0}, expected {VERSION}")]
+    Version(u16),
+    #[error("bincode error: {0}")]
+    Bincode(#[from] bincode::Error),
+}
+
+/// Orderbook of a market
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BookSnapshot {
+    pub market: Box<str>,
+    pub orderbook: PlainOrderbook<f64>,
+}
+
+/// Orderbooks saved at the same time
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BookSnapshots {
+    pub saved_at: SystemTime,
+    pub books: Vec<BookSnapshot>,
+}
+
+impl BookSnapshots {
+    pub fn new(books: Vec<BookSnapshot>) -> Self {
+        Self {
+            saved_at: SystemTime::now(),
+            books,
+        }
+    }
+
+    /// Returns time passed since the books were saved
+    pub fn age(&self) -> Duration {
+        SystemTime::now()
+            .duration_since(self.saved_at)
+            .unwrap_or_default()
+    }
+
+    pub fn encode(&self) -> Result<Vec<u8>, SnapshotError> {
+        let mut buf = Vec::with_capacity(4096);
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&VERSION.to_le_bytes());
+        bincode::serialize_into(&mut buf, self)?;
+
+        Ok(buf)
+    }
+
+    pub fn decode(buf: &[u8]) -> Result<Self, SnapshotError> {
+        if buf.len() < MAGIC.len() + 2 || &buf[..MAGIC.len()] != MAGIC {
+            return Err(SnapshotError::Magic);
+        }
+        let version = u16::from_le_bytes([buf[4], buf[5]]);
+        if version != VERSION {
+            return Err(SnapshotError::Version(version));
+        }
+
+        Ok(bincode::deserialize(&buf[6..])?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::market::orderbook::PriceLevelsVec;
+
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let snapshots = BookSnapshots::new(vec![BookSnapshot {
+            market: Box::from("BTC-PERP"),
+            orderbook: PlainOrderbook {
+                bids: PriceLevelsVec::from_tuples_vec(&[(99.0, 1.0), (100.0, 2.0)]),
+                asks: PriceLevelsVec::from_tuples_vec(&[(101.0, 0.5)]),
+                time: 1.5,
+                max_depth: None,
+            },
+        }]);
+
+        let encoded = snapshots.encode().unwrap();
+        let decoded = BookSnapshots::decode(&encoded).unwrap();
+        assert_eq!(snapshots.saved_at, decoded.saved_at);
+        assert_eq!("BTC-PERP", &*decoded.books[0].market);
+        assert_eq!(vec![99.0, 100.0], decoded.books[0].orderbook.bids.price_vec);
+        assert_eq!(vec![0.5], decoded.books[0].orderbook.asks.size_vec);
+
+        let mut newer = encoded.clone();
+        newer[4] = 2;
+        assert!(matches!(
+            BookSnapshots::decode(&newer),
+            Err(SnapshotError::Version(2))
+        ));
+        assert!(matches!(
+            BookSnapshots::decode(b"{}"),
+            Err(SnapshotError::Magic)
+        ));
+    }
+}
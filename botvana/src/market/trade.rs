@@ -1,26 +1,95 @@
 //! Trade
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
-pub struct Trade {
-    pub price: f64,
-    pub size: f64,
+use super::fixed::{Fixed, MarketScales};
+
+/// Side of the taker of a trade
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeSide {
+    /// Taker bought from a resting sell order
+    Buy,
+    /// Taker sold into a resting buy order
+    Sell,
+}
+
+impl std::str::FromStr for TradeSide {
+    type Err = String;
+
+    /// Parses lower-case `buy` or `sell`
+    fn from_str(side: &str) -> Result<Self, Self::Err> {
+        match side {
+            "buy" => Ok(TradeSide::Buy),
+            "sell" => Ok(TradeSide::Sell),
+            side => Err(format!("unknown trade side {side}")),
+        }
+    }
+}
+
+impl TradeSide {
+    /// Returns the size signed by the side, negative when the taker sold
+    pub fn signed(&self, size: f64) -> f64 {
+        match self {
+            TradeSide::Buy => size,
+            TradeSide::Sell => -size,
+        }
+    }
+}
+
+/// Trade with `f64` or fixed-point price and size
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Trade<T = f64> {
+    pub price: T,
+    pub size: T,
+    /// Side of the taker, when reported by the exchange
+    #[serde(default)]
+    pub side: Option<TradeSide>,
     /// Time of the trade specified by the exchange
     pub time: DateTime<Utc>,
-    /// Time the trade was received
+    /// Time the trade was received, deserialized trades are received when
+    /// they are read
+    #[serde(skip, default = "std::time::Instant::now")]
     pub received_at: std::time::Instant,
 }
 
-impl Trade {
+impl<T> Trade<T> {
     /// Creates new trade
-    pub fn new(price: f64, size: f64, time: DateTime<Utc>) -> Self {
+    pub fn new(price: T, size: T, time: DateTime<Utc>) -> Self {
         Self {
             price,
             size,
+            side: None,
             time,
             received_at: std::time::Instant::now(),
         }
     }
+
+    /// Returns the trade with the side of its taker
+    pub fn with_side(mut self, side: TradeSide) -> Self {
+        self.side = Some(side);
+        self
+    }
+}
+
+impl Trade<f64> {
+    /// Returns the trade converted to fixed-point with the scales of its
+    /// market
+    pub fn to_fixed(&self, scales: &MarketScales) -> Trade<Fixed> {
+        Trade {
+            price: scales.price.to_fixed(self.price),
+            size: scales.size.to_fixed(self.size),
+            side: self.side,
+            time: self.time,
+            received_at: self.received_at,
+        }
+    }
+
+    /// Rounds price and size to the increments of the market
+    pub fn round_to(&mut self, scales: &MarketScales) {
+        self.price = scales.price.round(self.price);
+        self.size = scales.size.round(self.size);
+    }
 }
 
 /// Columnar struct of trades
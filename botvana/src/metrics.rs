@@ -0,0 +1,70 @@
+//! Metrics shared between botnode and botvana-server
+
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+/// Compact set of metrics reported by a botnode
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct MetricsReport {
+    /// Time the report was taken in milliseconds since UNIX epoch
+    pub timestamp: u64,
+    pub samples: Box<[MetricSample]>,
+}
+
+impl MetricsReport {
+    /// Creates new report timestamped with current time
+    pub fn new(samples: Box<[MetricSample]>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        Self { timestamp, samples }
+    }
+
+    /// Returns sample with given name and labels
+    pub fn get(&self, name: &str, labels: &[(&str, &str)]) -> Option<&MetricSample> {
+        self.samples.iter().find(|sample| {
+            &*sample.name == name
+                && sample.labels.len() == labels.len()
+                && sample
+                    .labels
+                    .iter()
+                    .zip(labels.iter())
+                    .all(|((k1, v1), (k2, v2))| &**k1 == *k2 && &**v1 == *v2)
+        })
+    }
+}
+
+/// Single metric value
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct MetricSample {
+    pub name: Box<str>,
+    pub labels: Box<[(Box<str>, Box<str>)]>,
+    pub value: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_report_get() {
+        let report = MetricsReport::new(Box::new([MetricSample {
+            name: Box::from("throughput"),
+            labels: Box::new([(Box::from("adapter"), Box::from("ftx"))]),
+            value: 1.0,
+        }]));
+
+        assert!(report.timestamp > 0);
+        assert_eq!(
+            Some(1.0),
+            report
+                .get("throughput", &[("adapter", "ftx")])
+                .map(|s| s.value)
+        );
+        assert!(report.get("throughput", &[]).is_none());
+        assert!(report.get("latency", &[("adapter", "ftx")]).is_none());
+    }
+}
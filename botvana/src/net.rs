@@ -1,5 +1,8 @@
 //! Botvana network module
 
+pub mod auth;
 pub mod codec;
+pub mod compression;
+pub mod format;
 pub mod frame;
 pub mod msg;
@@ -0,0 +1,113 @@
+//! Authentication of bots connecting to the server
+//!
+//! After `Hello` the server sends an [`AuthChallenge`](super::msg::Message)
+//! with a random nonce. The bot answers with its API token, or with the
+//! HMAC-SHA256 of the bot id and the nonce keyed by a shared secret so the
+//! secret never goes over the wire. The server replies with the
+//! [`AuthResult`](super::msg::AuthResult) and disconnects rejected bots.
+
+use ring::{
+    constant_time, hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::Deserialize;
+
+use super::msg::{AuthProof, BotId};
+
+/// Length of the challenge nonce in bytes
+pub const NONCE_LEN: usize = 32;
+
+/// Returns new random challenge nonce
+pub fn nonce() -> Vec<u8> {
+    let mut nonce = vec![0; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .expect("failed to generate nonce");
+    nonce
+}
+
+/// Returns HMAC proof of the bot answering the challenge
+pub fn sign(secret: &[u8], bot_id: &BotId, nonce: &[u8]) -> Vec<u8> {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+    hmac::sign(&key, &payload(bot_id, nonce)).as_ref().to_vec()
+}
+
+/// Credentials the server accepts from a bot
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BotCredentials {
+    pub token: Option<String>,
+    pub secret: Option<String>,
+}
+
+impl BotCredentials {
+    /// Returns whether the bot has to authenticate
+    pub fn is_required(&self) -> bool {
+        self.token.is_some() || self.secret.is_some()
+    }
+
+    /// Verifies the proof sent in answer to the challenge
+    pub fn verify(&self, bot_id: &BotId, nonce: &[u8], proof: &AuthProof) -> bool {
+        match (proof, &self.token, &self.secret) {
+            (AuthProof::Token(token), Some(expected), _) => {
+                constant_time::verify_slices_are_equal(token.as_bytes(), expected.as_bytes())
+                    .is_ok()
+            }
+            (AuthProof::Hmac(tag), _, Some(secret)) => {
+                let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+                hmac::verify(&key, &payload(bot_id, nonce), tag).is_ok()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl std::fmt::Debug for BotCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("BotCredentials")
+            .field("token", &self.token.as_ref().map(|_| "***"))
+            .field("secret", &self.secret.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
+/// Signed payload, the nonce prefixed with the bot id
+fn payload(bot_id: &BotId, nonce: &[u8]) -> Vec<u8> {
+    let mut payload = bot_id.0.to_be_bytes().to_vec();
+    payload.extend_from_slice(nonce);
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_hmac() {
+        let credentials = BotCredentials {
+            token: None,
+            secret: Some("secret".to_string()),
+        };
+        let nonce = nonce();
+        let proof = AuthProof::Hmac(sign(b"secret", &BotId(1), &nonce));
+
+        assert!(credentials.verify(&BotId(1), &nonce, &proof));
+        // Proof is bound to the bot id and the nonce
+        assert!(!credentials.verify(&BotId(2), &nonce, &proof));
+        assert!(!credentials.verify(&BotId(1), &[0; NONCE_LEN], &proof));
+        assert!(!credentials.verify(&BotId(1), &nonce, &AuthProof::Token("secret".into())));
+    }
+
+    #[test]
+    fn test_verify_token() {
+        let credentials = BotCredentials {
+            token: Some("token".to_string()),
+            secret: None,
+        };
+
+        assert!(credentials.is_required());
+        assert!(credentials.verify(&BotId(1), &[], &AuthProof::Token("token".into())));
+        assert!(!credentials.verify(&BotId(1), &[], &AuthProof::Token("other".into())));
+        assert!(!BotCredentials::default().is_required());
+    }
+}
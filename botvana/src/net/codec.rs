@@ -1,50 +1,280 @@
+//! Framing of the botvana protocol messages
+//!
+//! Every frame starts with the protocol version and the little-endian size
+//! of the message, serialized with one of the [`format`](super::format)s.
+//! The header is the same in every version, so frames the codec can't
+//! decode are skipped whole instead of being misread.
+//!
+//! Newer protocol versions only add messages and
+//! [`compression`](super::compression) of frames the peer asked for. The
+//! bot sends `Hello` with the newest version it speaks, the server picks the
+//! version both sides speak with [`negotiate`] and tells bots speaking
+//! version 2 or newer with `ProtocolVersion`. Messages the negotiated
+//! version doesn't know aren't encoded.
+//!
+//! Compressed frames are only decoded once this side sent `Compression`
+//! announcing their algorithm, other ones are rejected.
+
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc,
+};
+
 use async_codec::*;
-use tracing::{error, trace};
+use tracing::{error, trace, warn};
 
-use super::msg::*;
+use super::{
+    compression::{Compression, CompressionConfig, COMPRESSED, MAX_DECOMPRESSED_SIZE},
+    format::{Bincode, FormatError, Serializer},
+    msg::*,
+};
 
 pub use async_codec::Framed;
 
+/// Newest protocol version
+pub const PROTOCOL_VERSION: u8 = 7;
+
+/// Oldest protocol version still spoken
+pub const MIN_PROTOCOL_VERSION: u8 = 1;
+
+/// Size of the frame header
+const HEADER_LEN: usize = 5;
+
+/// Returns the version to talk to a peer speaking `peer_version` with, or
+/// `None` when the peer is too old
+pub fn negotiate(peer_version: u8) -> Option<u8> {
+    (peer_version >= MIN_PROTOCOL_VERSION).then(|| peer_version.min(PROTOCOL_VERSION))
+}
+
+/// Protocol versions of a connection, shared by the codec and the
+/// connection negotiating them
+#[derive(Clone, Debug)]
+pub struct Protocol(Arc<ProtocolVersions>);
+
+#[derive(Debug)]
+struct ProtocolVersions {
+    version: AtomicU8,
+    peer_version: AtomicU8,
+    /// Bits of the ids of the algorithms the peer decompresses
+    peer_compression: AtomicU8,
+    /// Bits of the ids of the algorithms announced to the peer
+    compression: AtomicU8,
+}
+
+impl Protocol {
+    /// Returns the version frames are encoded with
+    pub fn version(&self) -> u8 {
+        self.0.version.load(Ordering::Relaxed)
+    }
+
+    /// Sets the version frames are encoded with
+    pub fn set_version(&self, version: u8) {
+        self.0.version.store(version, Ordering::Relaxed);
+    }
+
+    /// Returns the version of the last frame received, 0 before the first
+    pub fn peer_version(&self) -> u8 {
+        self.0.peer_version.load(Ordering::Relaxed)
+    }
+
+    /// Sets the compression algorithms the peer decompresses
+    pub fn set_peer_compression(&self, algorithms: &[Compression]) {
+        self.0
+            .peer_compression
+            .store(compression_bits(algorithms), Ordering::Relaxed);
+    }
+
+    /// Returns whether the peer decompresses frames compressed with given
+    /// algorithm
+    pub fn peer_decompresses(&self, algorithm: Compression) -> bool {
+        self.0.peer_compression.load(Ordering::Relaxed) & (1 << algorithm.id()) != 0
+    }
+
+    /// Returns whether frames compressed with given algorithm were
+    /// announced to the peer
+    pub fn decompresses(&self, algorithm: Compression) -> bool {
+        self.0.compression.load(Ordering::Relaxed) & (1 << algorithm.id()) != 0
+    }
+}
+
+/// Returns bits of the ids of the algorithms
+fn compression_bits(algorithms: &[Compression]) -> u8 {
+    algorithms
+        .iter()
+        .fold(0, |bits, algorithm| bits | (1 << algorithm.id()))
+}
+
+impl Default for Protocol {
+    fn default() -> Self {
+        Self(Arc::new(ProtocolVersions {
+            version: AtomicU8::new(PROTOCOL_VERSION),
+            peer_version: AtomicU8::new(0),
+            peer_compression: AtomicU8::new(0),
+            compression: AtomicU8::new(0),
+        }))
+    }
+}
+
+/// Codec of the botvana protocol, encodes with the newest version until
+/// another one is negotiated
 #[derive(Debug)]
-pub struct BotvanaCodec;
+pub struct BotvanaCodec {
+    protocol: Protocol,
+    serializer: Box<dyn Serializer>,
+    compression: CompressionConfig,
+}
+
+impl BotvanaCodec {
+    /// Returns codec serializing messages with given serializer
+    pub fn new(serializer: Box<dyn Serializer>) -> Self {
+        Self {
+            protocol: Protocol::default(),
+            serializer,
+            compression: CompressionConfig::default(),
+        }
+    }
+
+    /// Sets compression of the frames sent
+    pub fn with_compression(mut self, compression: CompressionConfig) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Returns protocol versions of the connection the codec frames
+    pub fn protocol(&self) -> Protocol {
+        self.protocol.clone()
+    }
+}
+
+impl Default for BotvanaCodec {
+    fn default() -> Self {
+        Self::new(Box::new(Bincode))
+    }
+}
+
+impl BotvanaCodec {
+    /// Returns compressed payload prefixed with the algorithm id, when the
+    /// message should be compressed and it pays off
+    fn compress(&self, item: &Message, msg: &[u8], version: u8) -> Option<Vec<u8>> {
+        let algorithm = self.compression.algorithm?;
+        if version < 3
+            || !self.protocol.peer_decompresses(algorithm)
+            || msg.len() < self.compression.threshold(item.name())
+        {
+            return None;
+        }
+
+        match algorithm.compress(msg) {
+            Ok(compressed) if compressed.len() + 1 < msg.len() => {
+                let mut payload = Vec::with_capacity(compressed.len() + 1);
+                payload.push(algorithm.id());
+                payload.extend_from_slice(&compressed);
+
+                Some(payload)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Failed to compress {}: {e}", item.name());
+
+                None
+            }
+        }
+    }
+}
+
+impl BotvanaCodec {
+    /// Returns payload of compressed frame decompressed
+    fn decompress(&self, payload: &[u8], version: u8) -> Result<Vec<u8>, DecodeError> {
+        let (id, compressed) = payload
+            .split_first()
+            .ok_or(DecodeError::UnsupportedCompression(0))?;
+        let algorithm =
+            Compression::from_id(*id).ok_or(DecodeError::UnsupportedCompression(*id))?;
+        if !self.protocol.decompresses(algorithm) {
+            error!("Frame compressed with {algorithm:?}, which wasn't announced");
+
+            return Err(DecodeError::UnexpectedCompression(*id));
+        }
+
+        algorithm
+            .decompress(compressed, MAX_DECOMPRESSED_SIZE)
+            .map_err(|e| {
+                error!("Failed to decompress frame: {e}");
+                DecodeError::Malformed(version)
+            })
+    }
+}
 
 impl Encode for BotvanaCodec {
     type Item = Message;
-    type Error = ();
+    type Error = EncodeError;
 
-    fn encode(&mut self, item: &Self::Item, buf: &mut [u8]) -> EncodeResult<()> {
+    fn encode(&mut self, item: &Self::Item, buf: &mut [u8]) -> EncodeResult<EncodeError> {
         trace!("serializing {:?}", item);
 
-        let msg = match bincode::serialize(item) {
+        let version = self.protocol.version();
+        if item.since_version() > version {
+            error!("Message {item:?} can't be sent with protocol version {version}");
+
+            return EncodeResult::Err(EncodeError::UnsupportedVersion(item.name(), version));
+        }
+
+        let mut msg = match self.serializer.serialize(item) {
             Ok(msg) => msg,
             Err(e) => {
                 error!("Failed to serialize: {}", e);
 
-                return EncodeResult::Err(());
+                return EncodeResult::Err(EncodeError::Serialize(e));
             }
         };
+        if let Message::Compression(algorithms) = item {
+            // The peer may compress as soon as it reads the announcement
+            self.protocol
+                .0
+                .compression
+                .store(compression_bits(algorithms), Ordering::Relaxed);
+        }
+
+        let mut header = version;
+        if let Some(compressed) = self.compress(item, &msg, version) {
+            msg = compressed;
+            header |= COMPRESSED;
+        }
         let msg_size = msg.len();
 
-        if buf.len() < msg_size + 5 {
-            return EncodeResult::Overflow(msg_size + 5);
+        if buf.len() < msg_size + HEADER_LEN {
+            return EncodeResult::Overflow(msg_size + HEADER_LEN);
         }
 
         // Write frame version
-        buf[0] = 1;
+        buf[0] = header;
 
         // Encode frame size & write to stream
-        let encoded: [u8; 4] = unsafe { std::mem::transmute((msg_size as u32).to_le_bytes()) };
-        buf[1..5].copy_from_slice(&encoded);
-        buf[5..msg_size as usize + 5].copy_from_slice(&msg);
+        buf[1..HEADER_LEN].copy_from_slice(&(msg_size as u32).to_le_bytes());
+        buf[HEADER_LEN..msg_size + HEADER_LEN].copy_from_slice(&msg);
 
-        Ok(msg_size + 5).into()
+        Ok(msg_size + HEADER_LEN).into()
     }
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum EncodeError {
+    #[error("Message {0} can't be sent with protocol version {1}")]
+    UnsupportedVersion(&'static str, u8),
+    #[error("Failed to serialize: {0}")]
+    Serialize(FormatError),
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum DecodeError {
-    #[error("Invalid frame version")]
-    InvalidVersion,
+    #[error("Unsupported frame version {0}")]
+    UnsupportedVersion(u8),
+    #[error("Malformed frame of version {0}")]
+    Malformed(u8),
+    #[error("Frame compressed with unsupported algorithm {0}")]
+    UnsupportedCompression(u8),
+    #[error("Frame compressed with algorithm {0} that wasn't announced")]
+    UnexpectedCompression(u8),
 }
 
 impl Decode for BotvanaCodec {
@@ -52,29 +282,52 @@ impl Decode for BotvanaCodec {
     type Error = DecodeError;
 
     fn decode(&mut self, buf: &mut [u8]) -> (usize, DecodeResult<Self::Item, Self::Error>) {
-        if buf.len() < 6 {
+        if buf.len() < HEADER_LEN + 1 {
             return (0, DecodeResult::UnexpectedEnd);
         }
 
-        // Read the frame version
-        if buf[0] == 1 {
-            let size = unsafe {
-                std::mem::transmute::<[u8; 4], u32>(buf[1..5].try_into().expect("Failed try_into"))
-            };
+        let compressed = buf[0] & COMPRESSED != 0;
+        let version = buf[0] & !COMPRESSED;
+        let size = u32::from_le_bytes(buf[1..HEADER_LEN].try_into().expect("Failed try_into"));
+        let end_pos = size as usize + HEADER_LEN;
+        if buf.len() < end_pos {
+            return (0, DecodeResult::UnexpectedEnd);
+        }
 
-            let end_pos = size as usize + 5;
-            if buf.len() < end_pos {
-                (0, DecodeResult::UnexpectedEnd)
-            } else {
-                let msg_buf = &buf[5..end_pos];
-                let message = bincode::deserialize(msg_buf).expect("Failed to deserialize msg_buf");
+        if version < MIN_PROTOCOL_VERSION {
+            error!("Unsupported frame version = {version}");
 
-                (end_pos, Ok(message).into())
-            }
-        } else {
-            error!("Invalid frame version = {}", buf[0]);
+            return (
+                end_pos,
+                Err(DecodeError::UnsupportedVersion(version)).into(),
+            );
+        }
+        self.protocol
+            .0
+            .peer_version
+            .store(version, Ordering::Relaxed);
+
+        let payload = &buf[HEADER_LEN..end_pos];
+        let decompressed;
+        let payload = match compressed {
+            true => match self.decompress(payload, version) {
+                Ok(payload) => {
+                    decompressed = payload;
+                    &decompressed[..]
+                }
+                Err(e) => return (end_pos, Err(e).into()),
+            },
+            false => payload,
+        };
+
+        // Frames of newer versions decode as long as the message is known
+        match self.serializer.deserialize(payload) {
+            Ok(message) => (end_pos, Ok(message).into()),
+            Err(e) => {
+                error!("Failed to deserialize frame of version {version}: {e}");
 
-            (1, Err(DecodeError::InvalidVersion).into())
+                (end_pos, Err(DecodeError::Malformed(version)).into())
+            }
         }
     }
 }
@@ -86,23 +339,147 @@ mod tests {
     use super::*;
     use futures::{SinkExt, StreamExt};
 
+    const HELLO_V1: [u8; 16] = [1, 11, 0, 0, 0, 0, 0, 0, 0, 77, 1, 1, 0, 0, 0, 0];
+
     #[async_std::test]
     async fn framed_writer() {
         let mut bytes = Vec::with_capacity(1024);
         let writer = Cursor::new(&mut bytes);
 
-        let mut framed = Framed::new(writer, BotvanaCodec);
+        let mut framed = Framed::new(writer, BotvanaCodec::default());
         let hello = Message::Hello(BotId(333), BotMetadata::new(1));
         framed.send(hello).await.unwrap();
 
-        assert_eq!(bytes, &[1, 10, 0, 0, 0, 0, 0, 0, 0, 77, 1, 1, 0, 0, 0]);
+        let mut expected = HELLO_V1;
+        expected[0] = PROTOCOL_VERSION;
+        assert_eq!(bytes, &expected);
     }
 
     #[async_std::test]
     async fn framed_reader() {
-        let bytes: Vec<_> = [1, 10, 0, 0, 0, 0, 0, 0, 0, 77, 1, 1, 0, 0, 0].into();
-        let reader = Cursor::new(&bytes);
-        let mut framed = Framed::new(reader, BotvanaCodec);
+        let codec = BotvanaCodec::default();
+        let protocol = codec.protocol();
+        let reader = Cursor::new(&HELLO_V1);
+        let mut framed = Framed::new(reader, codec);
         while let Some(_frame) = framed.next().await.transpose().expect("Failed to read") {}
+
+        assert_eq!(1, protocol.peer_version());
+    }
+
+    #[async_std::test]
+    async fn framed_reader_skips_unsupported_frames() {
+        let mut bytes = vec![0, 3, 0, 0, 0, 1, 2, 3];
+        bytes.extend_from_slice(&HELLO_V1);
+        let reader = Cursor::new(&bytes);
+        let mut framed = Framed::new(reader, BotvanaCodec::default());
+
+        assert!(framed.next().await.unwrap().is_err());
+        assert!(matches!(
+            framed.next().await.unwrap(),
+            Ok(Message::Hello(BotId(333), _))
+        ));
+    }
+
+    #[async_std::test]
+    async fn framed_writer_downgrades() {
+        let mut bytes = Vec::with_capacity(1024);
+        let writer = Cursor::new(&mut bytes);
+        let codec = BotvanaCodec::default();
+        codec.protocol().set_version(1);
+        let mut framed = Framed::new(writer, codec);
+
+        assert!(framed.send(Message::ProtocolVersion(2)).await.is_err());
+        framed.send(Message::hello(BotId(333))).await.unwrap();
+
+        assert_eq!(bytes, &HELLO_V1);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[async_std::test]
+    async fn framed_compression() {
+        let mut bytes = Vec::with_capacity(1024);
+        let codec = BotvanaCodec::default().with_compression(CompressionConfig {
+            algorithm: Some(Compression::Lz4),
+            ..Default::default()
+        });
+        let protocol = codec.protocol();
+        let mut framed = Framed::new(Cursor::new(&mut bytes), codec);
+
+        // Only compressed once the peer tells it decompresses
+        framed
+            .send(Message::AuthChallenge(vec![7; 4096]))
+            .await
+            .unwrap();
+        protocol.set_peer_compression(&[Compression::Lz4]);
+        framed
+            .send(Message::AuthChallenge(vec![7; 4096]))
+            .await
+            .unwrap();
+        drop(framed);
+
+        assert_eq!(PROTOCOL_VERSION, bytes[0]);
+        assert!(bytes.len() < 4096 + 200);
+
+        // Compressed frames are rejected until the algorithm is announced
+        let mut framed = Framed::new(Cursor::new(&bytes), BotvanaCodec::default());
+        assert!(framed.next().await.unwrap().is_ok());
+        assert!(matches!(
+            framed.next().await.unwrap(),
+            Err(ReadFrameError::Decode(DecodeError::UnexpectedCompression(
+                _
+            )))
+        ));
+
+        let mut codec = BotvanaCodec::default();
+        let mut buf = [0; 64];
+        let announcement = Message::Compression(Box::new([Compression::Lz4]));
+        assert!(matches!(
+            codec.encode(&announcement, &mut buf),
+            EncodeResult::Ok(_)
+        ));
+        let mut framed = Framed::new(Cursor::new(&bytes), codec);
+        for _ in 0..2 {
+            match framed.next().await.unwrap() {
+                Ok(Message::AuthChallenge(nonce)) => assert_eq!(vec![7; 4096], nonce),
+                msg => panic!("unexpected message {msg:?}"),
+            }
+        }
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn decode_rejects_oversized_frames() {
+        let mut codec = BotvanaCodec::default();
+        codec
+            .protocol()
+            .0
+            .compression
+            .store(compression_bits(&[Compression::Lz4]), Ordering::Relaxed);
+
+        // Claims to decompress to 4 GiB
+        let mut frame = vec![
+            PROTOCOL_VERSION | COMPRESSED,
+            6,
+            0,
+            0,
+            0,
+            Compression::Lz4.id(),
+        ];
+        frame.extend_from_slice(&u32::MAX.to_le_bytes());
+        frame.push(0);
+
+        let (consumed, result) = codec.decode(&mut frame);
+        assert_eq!(frame.len(), consumed);
+        assert!(matches!(
+            result,
+            DecodeResult::Err(DecodeError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(None, negotiate(0));
+        assert_eq!(Some(1), negotiate(1));
+        assert_eq!(Some(PROTOCOL_VERSION), negotiate(PROTOCOL_VERSION + 1));
     }
 }
@@ -0,0 +1,221 @@
+//! Compression of the frame payload
+//!
+//! Peers speaking protocol version 3 tell each other the algorithms they
+//! decompress with `Compression`, bots after the version is negotiated and
+//! the server once it accepted the bot. From then on frames are compressed
+//! when the peer supports the configured algorithm and the payload reaches
+//! the threshold of its message type, compressed frames have the
+//! [`COMPRESSED`] bit set in the version byte.
+//!
+//! Peers only accept frames compressed with the algorithms they announced
+//! and decompress at most [`MAX_DECOMPRESSED_SIZE`] bytes per frame, so a
+//! small frame can't make them allocate arbitrary amounts of memory.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// Bit of the frame version byte set for compressed frames
+pub const COMPRESSED: u8 = 0x80;
+
+/// Bytes a compressed frame decompresses to at most
+pub const MAX_DECOMPRESSED_SIZE: usize = 16 << 20;
+
+/// Compression algorithm
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Compression {
+    /// LZ4, `lz4` feature
+    Lz4,
+    /// Zstandard, `zstd` feature
+    Zstd,
+}
+
+impl Compression {
+    const ALL: [Compression; 2] = [Compression::Lz4, Compression::Zstd];
+
+    /// Returns the algorithms botvana is built with
+    pub fn supported() -> Box<[Compression]> {
+        Self::ALL
+            .into_iter()
+            .filter(|compression| compression.is_supported())
+            .collect()
+    }
+
+    /// Returns whether botvana is built with the algorithm
+    pub fn is_supported(self) -> bool {
+        match self {
+            Compression::Lz4 => cfg!(feature = "lz4"),
+            Compression::Zstd => cfg!(feature = "zstd"),
+        }
+    }
+
+    /// Returns id of the algorithm written before the compressed payload
+    pub fn id(self) -> u8 {
+        match self {
+            Compression::Lz4 => 1,
+            Compression::Zstd => 2,
+        }
+    }
+
+    /// Returns the algorithm with given id
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|compression| compression.id() == id)
+    }
+
+    /// Compresses the payload
+    pub fn compress(self, buf: &[u8]) -> Result<Vec<u8>, CompressionError> {
+        match self {
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Ok(lz4_flex::compress_prepend_size(buf)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::encode_all(buf, 0).map_err(CompressionError::with_source),
+            #[allow(unreachable_patterns)]
+            _ => {
+                let _ = buf;
+
+                Err(CompressionError::Unsupported(self))
+            }
+        }
+    }
+
+    /// Decompresses the payload, failing when it decompresses to more than
+    /// `max_size` bytes
+    pub fn decompress(self, buf: &[u8], max_size: usize) -> Result<Vec<u8>, CompressionError> {
+        match self {
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => {
+                let (size, compressed) = lz4_flex::block::uncompressed_size(buf)
+                    .map_err(CompressionError::with_source)?;
+                if size > max_size {
+                    return Err(CompressionError::TooLarge(max_size));
+                }
+
+                lz4_flex::decompress(compressed, size).map_err(CompressionError::with_source)
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                use std::io::Read;
+
+                let mut decoder =
+                    zstd::stream::read::Decoder::new(buf).map_err(CompressionError::with_source)?;
+                // The window is allocated before any output is produced
+                let window_log = max_size.max(1 << 10).next_power_of_two().trailing_zeros();
+                decoder
+                    .window_log_max(window_log)
+                    .map_err(CompressionError::with_source)?;
+
+                let mut decompressed = Vec::new();
+                decoder
+                    .take(max_size as u64 + 1)
+                    .read_to_end(&mut decompressed)
+                    .map_err(CompressionError::with_source)?;
+                if decompressed.len() > max_size {
+                    return Err(CompressionError::TooLarge(max_size));
+                }
+
+                Ok(decompressed)
+            }
+            #[allow(unreachable_patterns)]
+            _ => {
+                let _ = (buf, max_size);
+
+                Err(CompressionError::Unsupported(self))
+            }
+        }
+    }
+}
+
+/// Error compressing or decompressing the payload
+#[derive(Debug, thiserror::Error)]
+pub enum CompressionError {
+    #[error("botvana is built without {0:?} compression")]
+    Unsupported(Compression),
+    #[error("payload decompresses to more than {0} bytes")]
+    TooLarge(usize),
+    #[error("compression error: {0}")]
+    Failed(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl CompressionError {
+    #[allow(dead_code)]
+    fn with_source<T: std::error::Error + Send + Sync + 'static>(source: T) -> Self {
+        Self::Failed(Box::new(source))
+    }
+}
+
+/// Compression configuration
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompressionConfig {
+    /// Algorithm frames are compressed with, frames are sent uncompressed
+    /// when not set or the peer doesn't support it
+    pub algorithm: Option<Compression>,
+    /// Bytes from which payloads are compressed
+    pub min_size: usize,
+    /// Bytes from which payloads are compressed by message type, e.g.
+    /// `Orderbook = 256`, overriding `min_size`
+    pub thresholds: BTreeMap<String, usize>,
+}
+
+impl CompressionConfig {
+    /// Returns bytes from which payloads of given message type are
+    /// compressed
+    pub fn threshold(&self, message: &str) -> usize {
+        self.thresholds
+            .get(message)
+            .copied()
+            .unwrap_or(self.min_size)
+    }
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: None,
+            min_size: 1024,
+            thresholds: BTreeMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compression_ids() {
+        for compression in Compression::ALL {
+            assert_eq!(Some(compression), Compression::from_id(compression.id()));
+        }
+        assert_eq!(None, Compression::from_id(0));
+    }
+
+    #[test]
+    fn test_compression_roundtrip() {
+        let buf = [7; 4096];
+
+        for compression in Compression::supported().iter() {
+            let compressed = compression.compress(&buf).unwrap();
+            assert!(compressed.len() < buf.len());
+            assert_eq!(
+                &buf[..],
+                &compression
+                    .decompress(&compressed, MAX_DECOMPRESSED_SIZE)
+                    .unwrap()
+            );
+            assert!(compression.decompress(&compressed, 1024).is_err());
+        }
+    }
+
+    #[test]
+    fn test_compression_thresholds() {
+        let mut config = CompressionConfig::default();
+        config.thresholds.insert("Orderbook".to_string(), 256);
+
+        assert_eq!(256, config.threshold("Orderbook"));
+        assert_eq!(1024, config.threshold("StatusReport"));
+    }
+}
@@ -0,0 +1,238 @@
+//! Serialization formats of the frame payload
+//!
+//! Bincode is the default. MessagePack (`msgpack` feature) and protobuf
+//! (`protobuf` feature) can be picked for tooling written in other
+//! languages, both sides of the connection have to use the same format.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::msg::Message;
+
+/// Serializer of the messages carried in frames
+pub trait Serializer: fmt::Debug + Send {
+    /// Returns name of the format
+    fn name(&self) -> &'static str;
+
+    /// Serializes the message into the frame payload
+    fn serialize(&self, msg: &Message) -> Result<Vec<u8>, FormatError>;
+
+    /// Deserializes the message from the frame payload
+    fn deserialize(&self, buf: &[u8]) -> Result<Message, FormatError>;
+}
+
+/// Error serializing or deserializing the message
+#[derive(Debug, thiserror::Error)]
+#[error("{format} error: {source}")]
+pub struct FormatError {
+    format: &'static str,
+    source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl FormatError {
+    pub fn with_source<T: std::error::Error + Send + Sync + 'static>(
+        format: &'static str,
+        source: T,
+    ) -> Self {
+        Self {
+            format,
+            source: Box::new(source),
+        }
+    }
+}
+
+/// Wire format selectable in configuration
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WireFormat {
+    Bincode,
+    #[serde(rename = "msgpack")]
+    MessagePack,
+    Protobuf,
+}
+
+impl WireFormat {
+    /// Returns serializer of the format, `None` when botvana is built
+    /// without the feature of the format
+    pub fn serializer(self) -> Option<Box<dyn Serializer>> {
+        match self {
+            WireFormat::Bincode => Some(Box::new(Bincode)),
+            #[cfg(feature = "msgpack")]
+            WireFormat::MessagePack => Some(Box::new(MessagePack)),
+            #[cfg(feature = "protobuf")]
+            WireFormat::Protobuf => Some(Box::new(Protobuf)),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+}
+
+impl Default for WireFormat {
+    fn default() -> Self {
+        Self::Bincode
+    }
+}
+
+/// Bincode, the compact format both botnode and botvana-server default to
+#[derive(Debug)]
+pub struct Bincode;
+
+impl Serializer for Bincode {
+    fn name(&self) -> &'static str {
+        "bincode"
+    }
+
+    fn serialize(&self, msg: &Message) -> Result<Vec<u8>, FormatError> {
+        bincode::serialize(msg).map_err(|e| FormatError::with_source(self.name(), e))
+    }
+
+    fn deserialize(&self, buf: &[u8]) -> Result<Message, FormatError> {
+        bincode::deserialize(buf).map_err(|e| FormatError::with_source(self.name(), e))
+    }
+}
+
+/// MessagePack with named struct fields
+#[cfg(feature = "msgpack")]
+#[derive(Debug)]
+pub struct MessagePack;
+
+#[cfg(feature = "msgpack")]
+impl Serializer for MessagePack {
+    fn name(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn serialize(&self, msg: &Message) -> Result<Vec<u8>, FormatError> {
+        rmp_serde::to_vec_named(msg).map_err(|e| FormatError::with_source(self.name(), e))
+    }
+
+    fn deserialize(&self, buf: &[u8]) -> Result<Message, FormatError> {
+        rmp_serde::from_slice(buf).map_err(|e| FormatError::with_source(self.name(), e))
+    }
+}
+
+/// Protobuf `google.protobuf.Value` holding the message as it would be
+/// serialized to JSON
+///
+/// Messages decode with the well-known types of any protobuf library.
+/// Numbers are doubles, integers above 2^53 lose precision.
+#[cfg(feature = "protobuf")]
+#[derive(Debug)]
+pub struct Protobuf;
+
+#[cfg(feature = "protobuf")]
+impl Serializer for Protobuf {
+    fn name(&self) -> &'static str {
+        "protobuf"
+    }
+
+    fn serialize(&self, msg: &Message) -> Result<Vec<u8>, FormatError> {
+        use prost::Message as _;
+
+        let value =
+            serde_json::to_value(msg).map_err(|e| FormatError::with_source(self.name(), e))?;
+
+        Ok(protobuf::to_proto(value).encode_to_vec())
+    }
+
+    fn deserialize(&self, buf: &[u8]) -> Result<Message, FormatError> {
+        use prost::Message as _;
+
+        let value = prost_types::Value::decode(buf)
+            .map_err(|e| FormatError::with_source(self.name(), e))?;
+
+        serde_json::from_value(protobuf::from_proto(value))
+            .map_err(|e| FormatError::with_source(self.name(), e))
+    }
+}
+
+#[cfg(feature = "protobuf")]
+mod protobuf {
+    use prost_types::{value::Kind, ListValue, Struct, Value};
+
+    /// Converts JSON value to the protobuf one
+    pub fn to_proto(value: serde_json::Value) -> Value {
+        let kind = match value {
+            serde_json::Value::Null => Kind::NullValue(0),
+            serde_json::Value::Bool(b) => Kind::BoolValue(b),
+            serde_json::Value::Number(n) => Kind::NumberValue(n.as_f64().unwrap_or_default()),
+            serde_json::Value::String(s) => Kind::StringValue(s),
+            serde_json::Value::Array(values) => Kind::ListValue(ListValue {
+                values: values.into_iter().map(to_proto).collect(),
+            }),
+            serde_json::Value::Object(fields) => Kind::StructValue(Struct {
+                fields: fields.into_iter().map(|(k, v)| (k, to_proto(v))).collect(),
+            }),
+        };
+
+        Value { kind: Some(kind) }
+    }
+
+    /// Converts protobuf value to the JSON one, integral numbers become
+    /// integers so they deserialize into integer fields
+    pub fn from_proto(value: Value) -> serde_json::Value {
+        match value.kind {
+            None | Some(Kind::NullValue(_)) => serde_json::Value::Null,
+            Some(Kind::BoolValue(b)) => serde_json::Value::Bool(b),
+            Some(Kind::NumberValue(n)) if n.fract() == 0.0 && n >= 0.0 && n < u64::MAX as f64 => {
+                serde_json::Value::from(n as u64)
+            }
+            Some(Kind::NumberValue(n)) if n.fract() == 0.0 && n >= i64::MIN as f64 && n < 0.0 => {
+                serde_json::Value::from(n as i64)
+            }
+            Some(Kind::NumberValue(n)) => serde_json::Number::from_f64(n)
+                .map_or(serde_json::Value::Null, serde_json::Value::Number),
+            Some(Kind::StringValue(s)) => serde_json::Value::String(s),
+            Some(Kind::ListValue(list)) => {
+                serde_json::Value::Array(list.values.into_iter().map(from_proto).collect())
+            }
+            Some(Kind::StructValue(st)) => serde_json::Value::Object(
+                st.fields
+                    .into_iter()
+                    .map(|(k, v)| (k, from_proto(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::msg::{BotId, BotMetadata};
+
+    fn assert_roundtrip(serializer: &dyn Serializer) {
+        let hello = Message::Hello(BotId(333), BotMetadata::new(7).dry_run(true));
+        let buf = serializer.serialize(&hello).unwrap();
+
+        match serializer.deserialize(&buf).unwrap() {
+            Message::Hello(BotId(333), metadata) => {
+                assert_eq!(7, metadata.bot_version);
+                assert!(metadata.dry_run);
+            }
+            msg => panic!("unexpected message {msg:?}"),
+        }
+        assert!(matches!(
+            serializer.deserialize(&serializer.serialize(&Message::PauseTrading).unwrap()),
+            Ok(Message::PauseTrading)
+        ));
+    }
+
+    #[test]
+    fn test_bincode_roundtrip() {
+        assert_roundtrip(&*WireFormat::Bincode.serializer().unwrap());
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn test_msgpack_roundtrip() {
+        assert_roundtrip(&*WireFormat::MessagePack.serializer().unwrap());
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[test]
+    fn test_protobuf_roundtrip() {
+        assert_roundtrip(&*WireFormat::Protobuf.serializer().unwrap());
+    }
+}
@@ -2,9 +2,11 @@ use std::{num::ParseIntError, str::FromStr, time::SystemTime};
 
 use serde::{Deserialize, Serialize};
 
+use super::compression::Compression;
 use crate::{
-    cfg::BotConfiguration,
+    cfg::{BotConfiguration, ConfigUpdate},
     market::{orderbook::*, MarketVec},
+    metrics::MetricsReport,
 };
 
 /// Botvana protocol message
@@ -14,9 +16,21 @@ pub enum Message {
     ///
     /// Bot sends this message when it connects to the server
     Hello(BotId, BotMetadata),
+    /// Authentication challenge
+    ///
+    /// Sent by server in response to `Hello` when the bot has to
+    /// authenticate, inner value is the nonce to sign.
+    AuthChallenge(Vec<u8>),
+    /// Answer to the authentication challenge
+    AuthResponse(AuthProof),
+    /// Result of the authentication
+    ///
+    /// Sent by server before the bot configuration, rejected bots are
+    /// disconnected.
+    AuthResult(AuthResult),
     /// Bot configuration
     ///
-    /// This is first send by the server upon receiving `Hello`
+    /// This is first send by the server once the bot is authenticated
     BotConfiguration(BotConfiguration),
     /// Bot error
     ///
@@ -36,12 +50,140 @@ pub enum Message {
     /// List of markets that the bot has access to
     Orderbook(Orderbook<f64>),
     /// A set of metrics
-    Metrics,
+    ///
+    /// Sent periodically by bot so metrics are available centrally even
+    /// when the bot doesn't expose any metrics endpoint.
+    Metrics(MetricsReport),
     /// Status report
-    StatusReport,
+    ///
+    /// Sent periodically by bot in place of a ping so the server knows the
+    /// health of the bot and not only that it's alive.
+    StatusReport(StatusReport),
+    /// Alert raised by the bot
+    ///
+    /// Sent by bot when it detects condition that needs operator attention.
+    Alert(Alert),
+    /// Kill switch
+    ///
+    /// Sent by server to stop the bot from placing orders, or to allow it
+    /// again.
+    KillSwitch(KillSwitch),
+    /// Configuration update
+    ///
+    /// Sent by server any time after the bot configuration to change the
+    /// configuration of the running bot.
+    ConfigUpdate(ConfigUpdate),
+    /// Pause trading
+    ///
+    /// Sent by server to stop strategies from trading, only orders reducing
+    /// positions are placed until trading is resumed.
+    PauseTrading,
+    /// Resume trading
+    ///
+    /// Sent by server to resume trading paused by `PauseTrading`.
+    ResumeTrading,
+    /// Flatten positions
+    ///
+    /// Sent by server to cancel all open orders and close all positions with
+    /// market orders. Trading is not paused, strategies can open new
+    /// positions unless `PauseTrading` is sent first.
+    FlattenPositions,
+    /// Shutdown
+    ///
+    /// Sent by server to shut the bot down.
+    Shutdown,
+    /// Negotiated protocol version
+    ///
+    /// Sent by server in response to `Hello` of bots speaking protocol
+    /// version 2 or newer, both sides encode frames with this version from
+    /// then on.
+    ProtocolVersion(u8),
+    /// Compression algorithms the sender decompresses
+    ///
+    /// Sent by bots once protocol version 3 or newer is negotiated and by
+    /// the server once it accepted the bot, frames compressed with any of
+    /// them can be sent to the sender from then on.
+    Compression(Box<[Compression]>),
+    /// Positions of the bot
+    ///
+    /// Sent by bot with every status report once protocol version 4 or
+    /// newer is negotiated.
+    Positions(Box<[PositionReport]>),
+    /// Reload strategy
+    ///
+    /// Sent by server to swap the implementation of a running strategy,
+    /// e.g. a new build of a WebAssembly strategy, without restarting the
+    /// bot.
+    ReloadStrategy(StrategyReload),
+    /// Subscribe markets
+    ///
+    /// Sent by server to add markets to the running bot, the market data
+    /// engines of all exchanges listing them subscribe without restarting.
+    SubscribeMarkets(Box<[Box<str>]>),
+    /// Unsubscribe markets
+    ///
+    /// Sent by server to stop receiving market data of the markets.
+    UnsubscribeMarkets(Box<[Box<str>]>),
+    /// Get orderbook snapshot
+    ///
+    /// Sent by server to request the orderbook of the market the market data
+    /// engines currently hold.
+    GetOrderbookSnapshot(Box<str>),
+    /// Orderbook snapshot
+    ///
+    /// Sent by bot in response to `GetOrderbookSnapshot`, once for every
+    /// exchange subscribing the market.
+    OrderbookSnapshot(Orderbook<f64>),
 }
 
 impl Message {
+    /// Returns the protocol version the message was introduced in, peers
+    /// speaking older versions can't decode it
+    pub fn since_version(&self) -> u8 {
+        match self {
+            Message::ProtocolVersion(_) => 2,
+            Message::Compression(_) => 3,
+            Message::Positions(_) => 4,
+            Message::ReloadStrategy(_) => 5,
+            Message::SubscribeMarkets(_) | Message::UnsubscribeMarkets(_) => 6,
+            Message::GetOrderbookSnapshot(_) | Message::OrderbookSnapshot(_) => 7,
+            _ => 1,
+        }
+    }
+
+    /// Returns name of the message type
+    pub fn name(&self) -> &'static str {
+        match self {
+            Message::Hello(..) => "Hello",
+            Message::AuthChallenge(_) => "AuthChallenge",
+            Message::AuthResponse(_) => "AuthResponse",
+            Message::AuthResult(_) => "AuthResult",
+            Message::BotConfiguration(_) => "BotConfiguration",
+            Message::BotError(_) => "BotError",
+            Message::Ping(_) => "Ping",
+            Message::Pong(_) => "Pong",
+            Message::MarketList(_) => "MarketList",
+            Message::Orderbook(_) => "Orderbook",
+            Message::Metrics(_) => "Metrics",
+            Message::StatusReport(_) => "StatusReport",
+            Message::Alert(_) => "Alert",
+            Message::KillSwitch(_) => "KillSwitch",
+            Message::ConfigUpdate(_) => "ConfigUpdate",
+            Message::PauseTrading => "PauseTrading",
+            Message::ResumeTrading => "ResumeTrading",
+            Message::FlattenPositions => "FlattenPositions",
+            Message::Shutdown => "Shutdown",
+            Message::ProtocolVersion(_) => "ProtocolVersion",
+            Message::Compression(_) => "Compression",
+            Message::Positions(_) => "Positions",
+            Message::ReloadStrategy(_) => "ReloadStrategy",
+            Message::SubscribeMarkets(_) => "SubscribeMarkets",
+            Message::UnsubscribeMarkets(_) => "UnsubscribeMarkets",
+            Message::GetOrderbookSnapshot(_) => "GetOrderbookSnapshot",
+            Message::OrderbookSnapshot(_) => "OrderbookSnapshot",
+        }
+    }
+
     /// Creates new Hello message
     pub fn hello(bot_id: BotId) -> Self {
         Message::Hello(bot_id, BotMetadata::new(1))
@@ -72,10 +214,24 @@ impl Message {
     pub fn orderbook(orderbook: Orderbook<f64>) -> Self {
         Self::Orderbook(orderbook)
     }
+
+    /// Returns new metrics message
+    pub fn metrics(report: MetricsReport) -> Self {
+        Self::Metrics(report)
+    }
+
+    /// Returns new kill switch message
+    pub fn kill_switch<T: ToString>(tripped: bool, reason: T) -> Self {
+        Self::KillSwitch(KillSwitch {
+            tripped,
+            reason: reason.to_string(),
+        })
+    }
 }
 
 /// Unique ID representing bot
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone)]
+#[serde(transparent)]
 pub struct BotId(pub u16);
 
 impl FromStr for BotId {
@@ -90,14 +246,182 @@ impl FromStr for BotId {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct BotMetadata {
     pub bot_version: u32,
+    /// Bot routes all orders to a paper adapter
+    pub dry_run: bool,
 }
 
 impl BotMetadata {
     pub fn new(bot_version: u32) -> Self {
-        Self { bot_version }
+        Self {
+            bot_version,
+            dry_run: false,
+        }
+    }
+
+    /// Marks the bot as running in dry-run mode
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
     }
 }
 
+/// Proof of identity sent by the bot
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum AuthProof {
+    /// API token
+    Token(String),
+    /// HMAC-SHA256 of the bot id and the challenge nonce
+    Hmac(Vec<u8>),
+}
+
+/// Authentication result sent by the server
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum AuthResult {
+    Accepted,
+    Rejected(String),
+}
+
+/// Kill switch state requested by the server
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct KillSwitch {
+    /// Whether trading is stopped
+    pub tripped: bool,
+    pub reason: String,
+}
+
+/// Strategy reload requested by the server
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct StrategyReload {
+    /// Name of the strategy as configured on the bot
+    pub strategy: String,
+    /// Module to load instead of the one the strategy was loaded from
+    pub path: Option<String>,
+}
+
+/// Health of a bot reported to the server
+#[derive(Clone, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct StatusReport {
+    /// Time the report was taken in milliseconds since UNIX epoch
+    pub timestamp: u64,
+    pub engines: Box<[EngineHealth]>,
+    pub feeds: Box<[FeedHealth]>,
+    /// Bytes of physical memory held by the allocator, when it reports it
+    pub memory_bytes: Option<u64>,
+}
+
+impl StatusReport {
+    /// Creates new report timestamped with current time
+    pub fn new(
+        engines: Box<[EngineHealth]>,
+        feeds: Box<[FeedHealth]>,
+        memory_bytes: Option<u64>,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        Self {
+            timestamp,
+            engines,
+            feeds,
+            memory_bytes,
+        }
+    }
+
+    /// Returns whether all engines are running and all feeds connected
+    pub fn healthy(&self) -> bool {
+        self.engines
+            .iter()
+            .all(|engine| &*engine.status == "running")
+            && self.feeds.iter().all(|feed| feed.connected)
+    }
+}
+
+/// Latest status of an engine
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct EngineHealth {
+    pub engine: Box<str>,
+    /// Lower-case name of the status, `running` when operating as expected
+    pub status: Box<str>,
+    /// Time of the status change in milliseconds since UNIX epoch
+    pub updated_at: u64,
+}
+
+/// Health of the market data feed of an exchange
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct FeedHealth {
+    pub exchange: Box<str>,
+    /// Websocket connection is established and subscribed
+    pub connected: bool,
+    /// Subscribed markets
+    pub markets: Box<[Box<str>]>,
+    /// Websocket messages received per second since the previous report
+    pub messages_per_second: f64,
+}
+
+/// Position of the bot in a market
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct PositionReport {
+    pub exchange: Box<str>,
+    pub market: Box<str>,
+    /// Signed position size, negative when short
+    pub size: f64,
+    pub avg_entry_price: f64,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+}
+
+/// Alert raised by botnode
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub message: String,
+    /// Time the alert was raised in milliseconds since UNIX epoch
+    pub timestamp: u64,
+}
+
+impl Alert {
+    /// Creates new alert timestamped with current time
+    pub fn new<T: ToString>(kind: AlertKind, message: T) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        Self {
+            kind,
+            message: message.to_string(),
+            timestamp,
+        }
+    }
+}
+
+/// Kinds of alerts raised by botnode
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub enum AlertKind {
+    /// Consumer of an inter-engine channel is not keeping up
+    SlowConsumer {
+        channel: String,
+        consumer: usize,
+        occupancy: f64,
+    },
+    /// Exchange clock differs from local clock
+    ClockSkew { exchange: String, skew_ms: i64 },
+    /// Alert rule threshold was crossed
+    Threshold {
+        rule: String,
+        metric: String,
+        value: f64,
+    },
+    /// Values were dropped for a lagging consumer of an inter-engine channel
+    DroppedEvents {
+        channel: String,
+        consumer: usize,
+        dropped: u64,
+    },
+}
+
 /// Enum of possible errors reported by botnode
 #[derive(Serialize, Deserialize, Debug)]
 pub enum BotError {
@@ -110,14 +434,21 @@ mod tests {
 
     #[test]
     fn ser_deser_hello() {
-        let hello = Message::Hello(BotId(0), BotMetadata::new(1));
+        let hello = Message::Hello(BotId(0), BotMetadata::new(1).dry_run(true));
         let encoded = bincode::serialize(&hello).unwrap();
         let decoded: Message = bincode::deserialize(&encoded).unwrap();
 
         match decoded {
-            Message::Hello(BotId(bot_id), BotMetadata { bot_version }) => {
+            Message::Hello(
+                BotId(bot_id),
+                BotMetadata {
+                    bot_version,
+                    dry_run,
+                },
+            ) => {
                 assert_eq!(bot_id, 0);
                 assert_eq!(bot_version, 1);
+                assert!(dry_run);
             }
             _ => {
                 panic!("unexpected message deserialized");
@@ -125,6 +456,134 @@ mod tests {
         }
     }
 
+    #[test]
+    fn ser_deser_auth() {
+        let msg = Message::AuthResponse(AuthProof::Hmac(vec![1, 2, 3]));
+        let encoded = bincode::serialize(&msg).unwrap();
+        let decoded: Message = bincode::deserialize(&encoded).unwrap();
+
+        match decoded {
+            Message::AuthResponse(proof) => assert_eq!(AuthProof::Hmac(vec![1, 2, 3]), proof),
+            _ => {
+                panic!("unexpected message deserialized");
+            }
+        }
+    }
+
+    #[test]
+    fn ser_deser_config_update() {
+        let update = ConfigUpdate {
+            markets: Some(Box::from([Box::from("BTC-PERP")])),
+            risk: Some(crate::cfg::RiskLimits {
+                max_order_size: Some(1.0),
+                ..Default::default()
+            }),
+            strategy_params: Box::new([crate::cfg::StrategyParams {
+                strategy: "maker".to_string(),
+                params: "spread_bps = 5".to_string(),
+            }]),
+        };
+        let encoded = bincode::serialize(&Message::ConfigUpdate(update.clone())).unwrap();
+        let decoded: Message = bincode::deserialize(&encoded).unwrap();
+
+        match decoded {
+            Message::ConfigUpdate(decoded) => assert_eq!(update, decoded),
+            _ => {
+                panic!("unexpected message deserialized");
+            }
+        }
+    }
+
+    #[test]
+    fn ser_deser_kill_switch() {
+        let msg = Message::kill_switch(true, "manual");
+        let encoded = bincode::serialize(&msg).unwrap();
+        let decoded: Message = bincode::deserialize(&encoded).unwrap();
+
+        match decoded {
+            Message::KillSwitch(KillSwitch { tripped, reason }) => {
+                assert!(tripped);
+                assert_eq!(reason, "manual");
+            }
+            _ => {
+                panic!("unexpected message deserialized");
+            }
+        }
+    }
+
+    #[test]
+    fn ser_deser_metrics() {
+        let metrics = Message::metrics(MetricsReport::new(Box::new([
+            crate::metrics::MetricSample {
+                name: Box::from("throughput"),
+                labels: Box::new([]),
+                value: 2.0,
+            },
+        ])));
+        let encoded = bincode::serialize(&metrics).unwrap();
+        let decoded: Message = bincode::deserialize(&encoded).unwrap();
+
+        match decoded {
+            Message::Metrics(report) => {
+                assert_eq!(report.samples.len(), 1);
+                assert_eq!(report.samples[0].value, 2.0);
+            }
+            _ => {
+                panic!("unexpected message deserialized");
+            }
+        }
+    }
+
+    #[test]
+    fn ser_deser_status_report() {
+        let report = StatusReport::new(
+            Box::new([EngineHealth {
+                engine: Box::from("trading-engine"),
+                status: Box::from("running"),
+                updated_at: 1,
+            }]),
+            Box::new([FeedHealth {
+                exchange: Box::from("ftx"),
+                connected: false,
+                markets: Box::new([Box::from("BTC-PERP")]),
+                messages_per_second: 10.0,
+            }]),
+            Some(1024),
+        );
+        assert!(!report.healthy());
+
+        let encoded = bincode::serialize(&Message::StatusReport(report.clone())).unwrap();
+        let decoded: Message = bincode::deserialize(&encoded).unwrap();
+
+        match decoded {
+            Message::StatusReport(decoded) => assert_eq!(report, decoded),
+            _ => {
+                panic!("unexpected message deserialized");
+            }
+        }
+    }
+
+    #[test]
+    fn ser_deser_alert() {
+        let alert = Alert::new(
+            AlertKind::SlowConsumer {
+                channel: "market-data".to_string(),
+                consumer: 1,
+                occupancy: 0.9,
+            },
+            "slow",
+        );
+        let encoded = bincode::serialize(&Message::Alert(alert.clone())).unwrap();
+        let decoded: Message = bincode::deserialize(&encoded).unwrap();
+
+        match decoded {
+            Message::Alert(decoded) => assert_eq!(alert, decoded),
+            _ => {
+                panic!("unexpected message deserialized");
+            }
+        }
+    }
+
     #[test]
     fn ser_deser_configuration() {
         let hello = Message::BotConfiguration(BotConfiguration {
@@ -1,15 +1,24 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+    time::SystemTime,
+};
 
 use parking_lot::RwLock;
+use serde::Serialize;
 
 use crate::{
     exchange::*,
     market::{orderbook::*, MarketVec},
-    net::msg::BotId,
+    metrics::MetricsReport,
+    net::msg::{Alert, BotId, Message, PositionReport, StatusReport},
 };
 
 const SYMBOL_TABLE_CAP: u32 = 1024;
 
+/// Number of the most recent bot events kept
+const EVENTS_CAP: usize = 1024;
+
 /// Global state held by botvana-server
 #[derive(Clone, Debug)]
 pub struct GlobalState {
@@ -17,6 +26,15 @@ pub struct GlobalState {
     markets: Arc<RwLock<MarketVec>>,
     symbol_table: Arc<RwLock<MarketSymbolTable>>,
     orderbooks: Arc<RwLock<HashMap<(ExchangeId, u32), PlainOrderbook<f64>>>>,
+    metrics: Arc<RwLock<HashMap<BotId, MetricsReport>>>,
+    statuses: Arc<RwLock<HashMap<BotId, StatusReport>>>,
+    positions: Arc<RwLock<HashMap<BotId, Box<[PositionReport]>>>>,
+    /// Latest orderbook snapshots sent by the bots on request
+    orderbook_snapshots: Arc<RwLock<HashMap<BotId, Vec<Orderbook<f64>>>>>,
+    /// Messages waiting to be sent to connected bots
+    outbox: Arc<RwLock<HashMap<BotId, Vec<Message>>>>,
+    /// Recent events of all bots, oldest first
+    events: Arc<RwLock<VecDeque<BotEvent>>>,
 }
 
 impl GlobalState {
@@ -29,6 +47,12 @@ impl GlobalState {
                 SYMBOL_TABLE_CAP,
             ))),
             orderbooks: Arc::new(RwLock::new(HashMap::new())),
+            metrics: Arc::new(RwLock::new(HashMap::new())),
+            statuses: Arc::new(RwLock::new(HashMap::new())),
+            positions: Arc::new(RwLock::new(HashMap::new())),
+            orderbook_snapshots: Arc::new(RwLock::new(HashMap::new())),
+            outbox: Arc::new(RwLock::new(HashMap::new())),
+            events: Arc::new(RwLock::new(VecDeque::with_capacity(EVENTS_CAP))),
         }
     }
 
@@ -43,6 +67,10 @@ impl GlobalState {
     pub fn remove_bot(&self, bot_id: BotId) {
         let mut bots = self.connected_bots.write();
 
+        self.statuses.write().remove(&bot_id);
+        self.positions.write().remove(&bot_id);
+        self.orderbook_snapshots.write().remove(&bot_id);
+        self.outbox.write().remove(&bot_id);
         bots.retain(|id| *id != bot_id);
     }
 
@@ -51,6 +79,138 @@ impl GlobalState {
         self.connected_bots.read().to_vec()
     }
 
+    /// Stores the latest metrics reported by a bot
+    pub fn update_metrics(&self, bot_id: BotId, report: MetricsReport) {
+        let mut metrics = self.metrics.write();
+
+        metrics.insert(bot_id, report);
+    }
+
+    /// Returns the latest metrics reported by given bot
+    pub fn bot_metrics(&self, bot_id: &BotId) -> Option<MetricsReport> {
+        self.metrics.read().get(bot_id).cloned()
+    }
+
+    /// Returns the latest metrics of all bots
+    pub fn metrics(&self) -> Vec<(BotId, MetricsReport)> {
+        self.metrics
+            .read()
+            .iter()
+            .map(|(bot_id, report)| (bot_id.clone(), report.clone()))
+            .collect()
+    }
+
+    /// Stores the latest status reported by a bot
+    pub fn update_status(&self, bot_id: BotId, report: StatusReport) {
+        let mut statuses = self.statuses.write();
+
+        statuses.insert(bot_id, report);
+    }
+
+    /// Returns the latest status reported by given bot
+    pub fn bot_status(&self, bot_id: &BotId) -> Option<StatusReport> {
+        self.statuses.read().get(bot_id).cloned()
+    }
+
+    /// Returns the latest status of all connected bots
+    pub fn statuses(&self) -> Vec<(BotId, StatusReport)> {
+        self.statuses
+            .read()
+            .iter()
+            .map(|(bot_id, report)| (bot_id.clone(), report.clone()))
+            .collect()
+    }
+
+    /// Stores the latest positions reported by a bot
+    pub fn update_positions(&self, bot_id: BotId, positions: Box<[PositionReport]>) {
+        let mut all_positions = self.positions.write();
+
+        all_positions.insert(bot_id, positions);
+    }
+
+    /// Returns the latest positions reported by given bot
+    pub fn bot_positions(&self, bot_id: &BotId) -> Box<[PositionReport]> {
+        self.positions
+            .read()
+            .get(bot_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Stores orderbook snapshot sent by a bot, replacing the previous
+    /// snapshot of the same exchange market
+    pub fn update_orderbook_snapshot(&self, bot_id: BotId, orderbook: Orderbook<f64>) {
+        let mut snapshots = self.orderbook_snapshots.write();
+        let snapshots = snapshots.entry(bot_id).or_default();
+
+        snapshots.retain(|snapshot| {
+            snapshot.exchange != orderbook.exchange || snapshot.market != orderbook.market
+        });
+        snapshots.push(orderbook);
+    }
+
+    /// Returns the latest orderbook snapshots sent by given bot, of given
+    /// market when set, oldest first
+    pub fn bot_orderbook_snapshots(
+        &self,
+        bot_id: &BotId,
+        market: Option<&str>,
+    ) -> Vec<Orderbook<f64>> {
+        self.orderbook_snapshots
+            .read()
+            .get(bot_id)
+            .map(|snapshots| {
+                snapshots
+                    .iter()
+                    .filter(|snapshot| market.map_or(true, |market| &*snapshot.market == market))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Queues message to be sent to a connected bot, returns false when the
+    /// bot is not connected
+    pub fn send_to_bot(&self, bot_id: &BotId, msg: Message) -> bool {
+        if !self.connected_bots.read().contains(bot_id) {
+            return false;
+        }
+
+        let mut outbox = self.outbox.write();
+        outbox.entry(bot_id.clone()).or_default().push(msg);
+        true
+    }
+
+    /// Takes messages queued for given bot, oldest first
+    pub fn take_bot_messages(&self, bot_id: &BotId) -> Vec<Message> {
+        self.outbox.write().remove(bot_id).unwrap_or_default()
+    }
+
+    /// Records event of a bot, dropping the oldest one once the log is full
+    pub fn record_event(&self, bot_id: BotId, kind: BotEventKind) {
+        let mut events = self.events.write();
+
+        if events.len() == EVENTS_CAP {
+            events.pop_front();
+        }
+        events.push_back(BotEvent::new(bot_id, kind));
+    }
+
+    /// Returns up to `limit` most recent events, of given bot when set,
+    /// oldest first
+    pub fn recent_events(&self, bot_id: Option<&BotId>, limit: usize) -> Vec<BotEvent> {
+        let events = self.events.read();
+        let mut recent: Vec<_> = events
+            .iter()
+            .rev()
+            .filter(|event| bot_id.map_or(true, |bot_id| event.bot_id == *bot_id))
+            .take(limit)
+            .cloned()
+            .collect();
+        recent.reverse();
+        recent
+    }
+
     /// Returns current known markets
     pub fn markets(&self) -> MarketVec {
         self.markets.read().clone()
@@ -130,6 +290,45 @@ impl Default for GlobalState {
     }
 }
 
+/// Event of a bot recorded by the server
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BotEvent {
+    /// Time the event was recorded in milliseconds since UNIX epoch
+    pub timestamp: u64,
+    pub bot_id: BotId,
+    pub kind: BotEventKind,
+}
+
+impl BotEvent {
+    /// Creates new event timestamped with current time
+    pub fn new(bot_id: BotId, kind: BotEventKind) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+
+        Self {
+            timestamp,
+            bot_id,
+            kind,
+        }
+    }
+}
+
+/// Kind of the bot event
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum BotEventKind {
+    /// Bot was accepted by the server
+    Connected,
+    /// Connection to the bot was closed
+    Disconnected,
+    /// Alert raised by the bot
+    Alert(Alert),
+    /// Command queued for the bot, the name of its message
+    Command { command: Box<str> },
+}
+
 /// Interning table for market symbols
 #[derive(Debug, Default)]
 pub struct MarketSymbolTable {
@@ -249,6 +448,76 @@ mod tests {
         assert_eq!(state.connected_bots().len(), 3);
     }
 
+    #[test]
+    fn test_update_positions() {
+        let state = GlobalState::new();
+        state.add_bot(BotId(0));
+
+        state.update_positions(
+            BotId(0),
+            Box::new([PositionReport {
+                exchange: Box::from("ftx"),
+                market: Box::from("BTC-PERP"),
+                size: 0.5,
+                avg_entry_price: 20000.0,
+                realized_pnl: 10.0,
+                unrealized_pnl: -2.0,
+            }]),
+        );
+        assert_eq!(1, state.bot_positions(&BotId(0)).len());
+
+        state.remove_bot(BotId(0));
+        assert!(state.bot_positions(&BotId(0)).is_empty());
+    }
+
+    #[test]
+    fn test_orderbook_snapshots() {
+        let state = GlobalState::new();
+        let snapshot = |exchange, market: &str, time| Orderbook {
+            exchange,
+            market: Box::from(market),
+            bids: PriceLevelsVec::from_tuples_vec(&[(100.0, 1.0)]),
+            asks: PriceLevelsVec::from_tuples_vec(&[(101.0, 2.0)]),
+            time,
+        };
+
+        state.update_orderbook_snapshot(BotId(0), snapshot(ExchangeId::Ftx, "BTC-PERP", 1.0));
+        state.update_orderbook_snapshot(BotId(0), snapshot(ExchangeId::Ftx, "ETH-PERP", 1.0));
+        state.update_orderbook_snapshot(BotId(0), snapshot(ExchangeId::Ftx, "BTC-PERP", 2.0));
+
+        let snapshots = state.bot_orderbook_snapshots(&BotId(0), Some("BTC-PERP"));
+        assert_eq!(1, snapshots.len());
+        assert_eq!(2.0, snapshots[0].time);
+        assert_eq!(2, state.bot_orderbook_snapshots(&BotId(0), None).len());
+
+        state.remove_bot(BotId(0));
+        assert!(state.bot_orderbook_snapshots(&BotId(0), None).is_empty());
+    }
+
+    #[test]
+    fn test_recent_events() {
+        let state = GlobalState::new();
+
+        state.record_event(BotId(0), BotEventKind::Connected);
+        state.record_event(BotId(1), BotEventKind::Connected);
+        state.record_event(BotId(0), BotEventKind::Disconnected);
+
+        let events = state.recent_events(None, 2);
+        assert_eq!(2, events.len());
+        assert_eq!(BotId(1), events[0].bot_id);
+        assert_eq!(BotEventKind::Disconnected, events[1].kind);
+
+        let events = state.recent_events(Some(&BotId(0)), 10);
+        assert_eq!(2, events.len());
+        assert_eq!(BotEventKind::Connected, events[0].kind);
+
+        for _ in 0..EVENTS_CAP {
+            state.record_event(BotId(2), BotEventKind::Connected);
+        }
+        assert_eq!(EVENTS_CAP, state.events.read().len());
+        assert!(state.recent_events(Some(&BotId(0)), 10).is_empty());
+    }
+
     #[test]
     fn test_markets() {
         let state = GlobalState::new();
@@ -339,6 +608,48 @@ mod tests {
         assert!(state.get_orderbook(ExchangeId::Ftx, "eth/eur").is_none());
     }
 
+    #[test]
+    fn test_update_metrics() {
+        let state = GlobalState::new();
+        assert!(state.bot_metrics(&BotId(0)).is_none());
+
+        state.update_metrics(BotId(0), MetricsReport::new(Box::new([])));
+        state.update_metrics(BotId(1), MetricsReport::new(Box::new([])));
+
+        assert!(state.bot_metrics(&BotId(0)).is_some());
+        assert_eq!(2, state.metrics().len());
+    }
+
+    #[test]
+    fn test_update_status() {
+        let state = GlobalState::new();
+        state.add_bot(BotId(0));
+        state.update_status(BotId(0), StatusReport::default());
+        assert!(state.bot_status(&BotId(0)).is_some());
+
+        // Statuses of disconnected bots are stale
+        state.remove_bot(BotId(0));
+        assert!(state.bot_status(&BotId(0)).is_none());
+        assert!(state.statuses().is_empty());
+    }
+
+    #[test]
+    fn test_send_to_bot() {
+        let state = GlobalState::new();
+        assert!(!state.send_to_bot(&BotId(0), Message::PauseTrading));
+
+        state.add_bot(BotId(0));
+        assert!(state.send_to_bot(&BotId(0), Message::PauseTrading));
+        assert!(state.send_to_bot(&BotId(0), Message::FlattenPositions));
+
+        let messages = state.take_bot_messages(&BotId(0));
+        assert!(matches!(
+            &messages[..],
+            [Message::PauseTrading, Message::FlattenPositions]
+        ));
+        assert!(state.take_bot_messages(&BotId(0)).is_empty());
+    }
+
     #[test]
     fn test_get_orderbook_unknown() {
         let market = "BTC/USD";
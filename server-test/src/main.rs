@@ -13,7 +13,7 @@ async fn test_client<A: ToSocketAddrs>(
     bot_id: u16,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let stream = TcpStream::connect(addr).await.expect("Failed to connect");
-    let mut framed = Framed::new(stream, BotvanaCodec);
+    let mut framed = Framed::new(stream, BotvanaCodec::default());
 
     let msg = Message::hello(BotId(bot_id));
     if let Err(e) = framed.send(msg).await {
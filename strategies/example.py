@@ -0,0 +1,28 @@
+"""Example botnode strategy
+
+Quotes one tick inside the spread whenever the spread is wider than
+`min_spread`. Enable it in cfg/botnode.toml:
+
+    [strategy.example]
+    python = "strategies/example.py"
+    size = 0.01
+    min_spread = 1.0
+"""
+
+import botnode
+
+
+class Strategy:
+    def __init__(self, size=0.01, min_spread=1.0):
+        self.size = size
+        self.min_spread = min_spread
+
+    def on_orderbook(self, market, orderbook):
+        spread = orderbook.spread()
+        if spread is None or spread < self.min_spread:
+            return None
+
+        return [
+            botnode.OrderIntent(market, "buy", self.size, orderbook.best_bid() + spread / 4),
+            botnode.OrderIntent(market, "sell", self.size, orderbook.best_ask() - spread / 4),
+        ]
@@ -0,0 +1,53 @@
+;; Example botnode WebAssembly strategy
+;;
+;; Quotes a quarter of the spread inside it whenever the spread is wider
+;; than 1.0, like `strategies/example.py`. Enable it in cfg/botnode.toml of
+;; a botnode built with `--features wasm`:
+;;
+;;     [strategy.example-wasm]
+;;     wasm = "strategies/example.wat"
+;;
+;; Strategies are usually compiled to `.wasm` from Rust, C or any other
+;; language targeting wasm32, the text format is loaded just the same.
+;; The host API is described in `botnode/src/trading/wasm.rs`.
+(module
+  (import "botnode" "place_order" (func $place_order (param i32 i32 i32 f64 f64)))
+  (memory (export "memory") 1)
+
+  (func (export "botnode_abi_version") (result i32)
+    i32.const 1)
+
+  ;; Inputs of every callback are written to the same scratch buffer
+  (func (export "botnode_alloc") (param $len i32) (result i32)
+    i32.const 1024)
+
+  (func (export "botnode_init") (param $params i32) (param $len i32) (result i32)
+    i32.const 0)
+
+  (func (export "botnode_on_orderbook")
+    (param $market i32) (param $market_len i32)
+    (param $levels i32) (param $bids i32) (param $asks i32)
+    (local $bid f64) (local $ask f64) (local $quarter f64)
+
+    (if (i32.or (i32.eqz (local.get $bids)) (i32.eqz (local.get $asks)))
+      (then (return)))
+
+    ;; Best bid is the last of the ascending bid prices, best ask the first
+    ;; ask price following the bid prices and sizes
+    (local.set $bid
+      (f64.load (i32.add (local.get $levels)
+        (i32.mul (i32.sub (local.get $bids) (i32.const 1)) (i32.const 8)))))
+    (local.set $ask
+      (f64.load (i32.add (local.get $levels)
+        (i32.mul (local.get $bids) (i32.const 16)))))
+
+    (if (f64.lt (f64.sub (local.get $ask) (local.get $bid)) (f64.const 1.0))
+      (then (return)))
+    (local.set $quarter
+      (f64.div (f64.sub (local.get $ask) (local.get $bid)) (f64.const 4)))
+
+    (call $place_order (local.get $market) (local.get $market_len) (i32.const 0)
+      (f64.add (local.get $bid) (local.get $quarter)) (f64.const 0.01))
+    (call $place_order (local.get $market) (local.get $market_len) (i32.const 1)
+      (f64.sub (local.get $ask) (local.get $quarter)) (f64.const 0.01)))
+)